@@ -0,0 +1,404 @@
+//! Post-organization analysis of family folders
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use crate::models::Config;
+use crate::font::cache::cached_font_metadata;
+use crate::font::weight::weight_name;
+use crate::utils::{directory_size, format_size};
+
+/// Weights every family is expected to have
+const COMMON_WEIGHTS: [u16; 2] = [400, 700];
+
+/// Which weights and styles a family folder contains and which are missing
+#[derive(Debug, Clone)]
+pub struct FamilyCompletenessReport {
+    /// Family folder name
+    pub family_name: String,
+    /// Path of the family folder
+    pub family_dir: PathBuf,
+    /// Weights present in any style, ascending
+    pub present_weights: Vec<u16>,
+    /// Weights that have an upright face but no italic counterpart
+    pub missing_italic_weights: Vec<u16>,
+    /// Common weights (400/700) absent from the family
+    pub missing_common_weights: Vec<u16>,
+    /// Share of expected weight/style combinations present, 0-100
+    pub completeness: f32,
+}
+
+impl FamilyCompletenessReport {
+    /// Whether nothing is missing
+    pub fn is_complete(&self) -> bool {
+        self.missing_italic_weights.is_empty() && self.missing_common_weights.is_empty()
+    }
+}
+
+/// Analyze which weights and italic counterparts a family folder is missing.
+///
+/// A family is expected to contain an upright and an italic face for every
+/// weight it has, plus the common Regular (400) and Bold (700) weights.
+/// Families without any upright face (italic-only scripts) are only expected
+/// to have italics, their missing romans will never exist.
+pub fn analyze_family_completeness(family_dir: &Path, config: &Config) -> FamilyCompletenessReport {
+    let mut upright: BTreeSet<u16> = BTreeSet::new();
+    let mut italic: BTreeSet<u16> = BTreeSet::new();
+
+    for path in family_font_files(family_dir) {
+        if let Ok(Some(metadata)) = cached_font_metadata(&path, config) {
+            if metadata.is_italic {
+                italic.insert(metadata.weight);
+            } else {
+                upright.insert(metadata.weight);
+            }
+        }
+    }
+
+    let present: BTreeSet<u16> = upright.union(&italic).copied().collect();
+    let missing_italic_weights: Vec<u16> = upright.difference(&italic).copied().collect();
+    let missing_common_weights: Vec<u16> = COMMON_WEIGHTS.iter()
+        .copied()
+        .filter(|weight| !present.contains(weight))
+        .collect();
+
+    // Every present or common weight should exist both upright and italic,
+    // or only italic in an italic-only family
+    let styles = if upright.is_empty() && !italic.is_empty() { 1 } else { 2 };
+    let expected_weights: BTreeSet<u16> = present.iter().copied().chain(COMMON_WEIGHTS).collect();
+    let expected_faces = expected_weights.len() * styles;
+    let present_faces = upright.len() + italic.len();
+    let completeness = present_faces as f32 * 100.0 / expected_faces as f32;
+
+    FamilyCompletenessReport {
+        family_name: folder_name(family_dir),
+        family_dir: family_dir.to_path_buf(),
+        present_weights: present.into_iter().collect(),
+        missing_italic_weights,
+        missing_common_weights,
+        completeness,
+    }
+}
+
+/// Print completeness reports for incomplete families, optionally only those
+/// below `min_completeness` percent
+pub fn print_completeness_reports(reports: &[FamilyCompletenessReport], min_completeness: Option<f32>) {
+    let incomplete: Vec<&FamilyCompletenessReport> = reports.iter()
+        .filter(|report| !report.is_complete())
+        .collect();
+    let shown: Vec<&FamilyCompletenessReport> = incomplete.iter()
+        .copied()
+        .filter(|report| min_completeness.is_none_or(|min| report.completeness < min))
+        .collect();
+
+    println!("Family completeness:");
+    println!("  - {} families checked, {} incomplete", reports.len(), incomplete.len());
+    if let Some(min) = min_completeness {
+        println!("  - {} below {:.0}% complete", shown.len(), min);
+    }
+
+    for report in shown {
+        println!(
+            "  {} ({:.0}% complete) in {}",
+            report.family_name, report.completeness, report.family_dir.display()
+        );
+        println!("      present weights: {}", join_weights(&report.present_weights));
+        if !report.missing_common_weights.is_empty() {
+            println!("      missing weights: {}", join_weights(&report.missing_common_weights));
+        }
+        if !report.missing_italic_weights.is_empty() {
+            println!("      missing italics: {}", join_weights(&report.missing_italic_weights));
+        }
+    }
+}
+
+/// Files in a family folder, including those nested in weight folders
+pub(crate) fn family_font_files(family_dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(family_dir) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                if let Ok(nested) = fs::read_dir(&path) {
+                    files.extend(nested.flatten().map(|entry| entry.path()).filter(|p| p.is_file()));
+                }
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+fn join_weights(weights: &[u16]) -> String {
+    weights.iter().map(|weight| weight.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Disk usage of one family folder, counting every file in it including sidecars
+#[derive(Debug, Clone)]
+pub struct FamilySize {
+    /// Family folder name
+    pub family_name: String,
+    /// Folder the family is nested in below the scanned root (foundry or style class)
+    pub group: Option<String>,
+    /// Path of the family folder
+    pub family_dir: PathBuf,
+    /// Total size of all files in the folder
+    pub bytes: u64,
+    /// Number of files in the folder
+    pub files: usize,
+}
+
+impl FamilySize {
+    /// Measure a family folder
+    pub fn measure(family_dir: &Path, group: Option<String>) -> Self {
+        let (bytes, files) = directory_size(family_dir);
+        Self {
+            family_name: folder_name(family_dir),
+            group,
+            family_dir: family_dir.to_path_buf(),
+            bytes,
+            files,
+        }
+    }
+}
+
+/// Disk usage of a foundry or style class folder, summed over its families
+#[derive(Debug, Clone)]
+pub struct GroupSize {
+    /// Group folder name, or "(top level)" for families directly below the root
+    pub name: String,
+    /// Total size of its families
+    pub bytes: u64,
+    /// Number of files in its families
+    pub files: usize,
+    /// Number of families
+    pub families: usize,
+}
+
+/// Measure a set of family folders, e.g. those registered during a run, largest first
+pub fn measure_family_folders(family_dirs: impl IntoIterator<Item = PathBuf>) -> Vec<FamilySize> {
+    let mut dirs: Vec<PathBuf> = family_dirs.into_iter().filter(|dir| dir.is_dir()).collect();
+    dirs.sort();
+    dirs.dedup();
+
+    let mut sizes: Vec<FamilySize> = dirs.iter().map(|dir| FamilySize::measure(dir, None)).collect();
+    sort_by_size(&mut sizes);
+    sizes
+}
+
+/// Find every family folder below an organized root and measure it, largest first.
+///
+/// A family folder is one that holds files directly, or only weight folders
+/// (`700/`, `Bold/`) as created by `--group-by-weight`.
+pub fn scan_family_sizes(root: &Path) -> Vec<FamilySize> {
+    let mut sizes = Vec::new();
+    collect_family_sizes(root, root, &mut sizes);
+    sort_by_size(&mut sizes);
+    sizes
+}
+
+fn collect_family_sizes(dir: &Path, root: &Path, sizes: &mut Vec<FamilySize>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+
+    for path in entries.flatten().map(|entry| entry.path()).filter(|path| path.is_dir()) {
+        if is_family_folder(&path) {
+            let group = (dir != root).then(|| folder_name(dir));
+            sizes.push(FamilySize::measure(&path, group));
+        } else {
+            collect_family_sizes(&path, root, sizes);
+        }
+    }
+}
+
+fn is_family_folder(dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else { return false };
+    let paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+
+    if paths.iter().any(|path| path.is_file()) {
+        return true;
+    }
+
+    !paths.is_empty() && paths.iter().all(|path| {
+        let name = folder_name(path);
+        name.parse::<u16>().is_ok() || (100..=950).step_by(50).any(|weight| weight_name(weight) == name)
+    })
+}
+
+/// Sort family sizes largest first, then by name
+pub fn sort_by_size(sizes: &mut [FamilySize]) {
+    sizes.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.family_name.cmp(&b.family_name)));
+}
+
+/// Sum family sizes per group folder, largest first
+pub fn aggregate_group_sizes(sizes: &[FamilySize]) -> Vec<GroupSize> {
+    let mut groups: HashMap<String, GroupSize> = HashMap::new();
+
+    for size in sizes {
+        let name = size.group.clone().unwrap_or_else(|| "(top level)".to_string());
+        let group = groups.entry(name.clone()).or_insert(GroupSize { name, bytes: 0, files: 0, families: 0 });
+        group.bytes += size.bytes;
+        group.files += size.files;
+        group.families += 1;
+    }
+
+    let mut groups: Vec<GroupSize> = groups.into_values().collect();
+    groups.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    groups
+}
+
+/// Print the `top` largest families of a run
+pub fn print_largest_families(sizes: &[FamilySize], top: usize) {
+    if top == 0 || sizes.is_empty() {
+        return;
+    }
+
+    println!("Largest families by size:");
+    for size in sizes.iter().take(top) {
+        println!("  {:>10}  {} ({} files)", format_size(size.bytes), size.family_name, size.files);
+    }
+}
+
+/// Print family and group size breakdowns, hiding entries below `threshold` bytes
+pub fn print_size_report(sizes: &[FamilySize], threshold: u64) {
+    let groups = aggregate_group_sizes(sizes);
+    let total: u64 = sizes.iter().map(|size| size.bytes).sum();
+
+    println!("Family sizes:");
+    for size in sizes.iter().filter(|size| size.bytes >= threshold) {
+        let label = match &size.group {
+            Some(group) => format!("{}/{}", group, size.family_name),
+            None => size.family_name.clone(),
+        };
+        println!("  {:>10}  {} ({} files)", format_size(size.bytes), label, size.files);
+    }
+
+    if groups.iter().any(|group| group.name != "(top level)") {
+        println!("Foundry / group sizes:");
+        for group in groups.iter().filter(|group| group.bytes >= threshold) {
+            println!(
+                "  {:>10}  {} ({} families, {} files)",
+                format_size(group.bytes), group.name, group.families, group.files
+            );
+        }
+    }
+
+    println!("Total: {} in {} families", format_size(total), sizes.len());
+}
+
+/// Family and group size breakdowns as JSON, hiding entries below `threshold` bytes
+pub fn size_report_json(sizes: &[FamilySize], threshold: u64) -> Value {
+    let families: Vec<Value> = sizes.iter()
+        .filter(|size| size.bytes >= threshold)
+        .map(|size| json!({
+            "family": size.family_name,
+            "group": size.group,
+            "path": size.family_dir.display().to_string(),
+            "bytes": size.bytes,
+            "files": size.files,
+        }))
+        .collect();
+    let groups: Vec<Value> = aggregate_group_sizes(sizes).iter()
+        .filter(|group| group.bytes >= threshold)
+        .map(|group| json!({
+            "group": group.name,
+            "bytes": group.bytes,
+            "files": group.files,
+            "families": group.families,
+        }))
+        .collect();
+
+    json!({
+        "total_bytes": sizes.iter().map(|size| size.bytes).sum::<u64>(),
+        "families": families,
+        "groups": groups,
+    })
+}
+
+fn folder_name(dir: &Path) -> String {
+    dir.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TestFont};
+
+    fn write_file(path: &Path, bytes: usize) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; bytes]).unwrap();
+    }
+
+    /// Adobe/Minion (two fonts), Adobe/Myriad in weight folders and a
+    /// top-level Roboto with a metrics sidecar
+    fn library() -> TempDir {
+        let tmp = TempDir::new("sizes");
+        write_file(&tmp.join("Adobe/Minion/Minion Regular.otf"), 300);
+        write_file(&tmp.join("Adobe/Minion/Minion Bold.otf"), 200);
+        write_file(&tmp.join("Adobe/Myriad/400/Myriad Regular.otf"), 150);
+        write_file(&tmp.join("Adobe/Myriad/Bold/Myriad Bold.otf"), 100);
+        write_file(&tmp.join("Roboto/Roboto Regular.pfb"), 40);
+        write_file(&tmp.join("Roboto/Roboto Regular.afm"), 10);
+        tmp
+    }
+
+    #[test]
+    fn scan_measures_family_folders_largest_first() {
+        let tmp = library();
+        let sizes = scan_family_sizes(tmp.path());
+        let summary: Vec<(&str, Option<&str>, u64, usize)> = sizes.iter()
+            .map(|size| (size.family_name.as_str(), size.group.as_deref(), size.bytes, size.files))
+            .collect();
+        assert_eq!(summary, [
+            ("Minion", Some("Adobe"), 500, 2),
+            ("Myriad", Some("Adobe"), 250, 2),
+            ("Roboto", None, 50, 2),
+        ]);
+    }
+
+    #[test]
+    fn groups_sum_their_families() {
+        let tmp = library();
+        let groups = aggregate_group_sizes(&scan_family_sizes(tmp.path()));
+        let summary: Vec<(&str, u64, usize, usize)> = groups.iter()
+            .map(|group| (group.name.as_str(), group.bytes, group.files, group.families))
+            .collect();
+        assert_eq!(summary, [("Adobe", 750, 4, 2), ("(top level)", 50, 2, 1)]);
+    }
+
+    #[test]
+    fn json_report_hides_entries_below_the_threshold() {
+        let tmp = library();
+        let report = size_report_json(&scan_family_sizes(tmp.path()), 100);
+        assert_eq!(report["total_bytes"], 800);
+        let families: Vec<&str> = report["families"].as_array().unwrap().iter()
+            .map(|family| family["family"].as_str().unwrap())
+            .collect();
+        assert_eq!(families, ["Minion", "Myriad"]);
+        assert_eq!(report["groups"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn italic_only_family_misses_no_romans() {
+        let tmp = TempDir::new("italic-only");
+        TestFont::new("Script Test", "Regular").fs_selection(1).write(&tmp.join("Script Test/Script Test (Italic).ttf"));
+        TestFont::new("Script Test", "Bold").weight(700).fs_selection(1).write(&tmp.join("Script Test/Script Test (Bold).ttf"));
+
+        let report = analyze_family_completeness(&tmp.join("Script Test"), &Config::default());
+        assert_eq!(report.present_weights, [400, 700]);
+        assert!(report.missing_italic_weights.is_empty());
+        assert!(report.is_complete());
+        assert_eq!(report.completeness, 100.0);
+    }
+
+    #[test]
+    fn roman_family_misses_its_italics() {
+        let tmp = TempDir::new("completeness");
+        TestFont::new("Roboto", "Regular").write(&tmp.join("Roboto/Roboto.ttf"));
+        TestFont::new("Roboto", "Bold").weight(700).write(&tmp.join("Roboto/Roboto (Bold).ttf"));
+
+        let report = analyze_family_completeness(&tmp.join("Roboto"), &Config::default());
+        assert_eq!(report.missing_italic_weights, [400, 700]);
+        assert_eq!(report.completeness, 50.0);
+    }
+}
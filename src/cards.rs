@@ -0,0 +1,251 @@
+//! Compact family cards of a library for sharing (`list <DIR>`)
+//!
+//! One line per family under its foundry, e.g.
+//! `  Inter — Regular 400, Medium 500, Bold 700 (+italics) [ttf, v3.019]`,
+//! with italic faces collapsed onto their roman counterparts.
+
+use std::collections::{BTreeMap, BTreeSet};
+use glob::{MatchOptions, Pattern};
+use crate::font::metadata::split_face_index;
+use crate::font::weight::{weight_name, width_class_name};
+use crate::inventory::InventoryEntry;
+
+/// A face of a family card: a weight and width, or a variable font, with
+/// whether it exists upright, italic or both
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardFace {
+    /// "Bold 700", "Condensed Regular 400" or "Variable [wght 100-900]"
+    pub name: String,
+    pub roman: bool,
+    pub italic: bool,
+}
+
+/// One family of a foundry
+#[derive(Debug, Clone, PartialEq)]
+pub struct FamilyCard {
+    pub family: String,
+    /// Faces ordered by width, then weight, variable fonts last
+    pub faces: Vec<CardFace>,
+    /// File extensions of the family's fonts, lowercase and sorted
+    pub formats: Vec<String>,
+    /// Versions of the family's fonts, sorted
+    pub versions: Vec<String>,
+}
+
+impl FamilyCard {
+    /// Faces and italics, e.g. "Regular 400, Bold 700 (+italics)". When every
+    /// face has an italic it is noted once; otherwise each face says whether
+    /// it also has one (`+italic`) or only exists as one (`italic`).
+    pub fn faces_label(&self) -> String {
+        let all_paired = self.faces.iter().all(|face| face.roman && face.italic);
+        let faces: Vec<String> = self.faces.iter()
+            .map(|face| match (face.roman, face.italic) {
+                (true, true) if !all_paired => format!("{} +italic", face.name),
+                (false, true) => format!("{} italic", face.name),
+                _ => face.name.clone(),
+            })
+            .collect();
+        let faces = faces.join(", ");
+        if all_paired { format!("{} (+italics)", faces) } else { faces }
+    }
+
+    /// Formats and versions, e.g. "ttf, v3.019" or "otf/ttf, v1.0/v2.1"
+    pub fn details_label(&self) -> String {
+        let mut details = vec![self.formats.join("/")];
+        if !self.versions.is_empty() {
+            details.push(self.versions.iter().map(|version| format!("v{}", version)).collect::<Vec<_>>().join("/"));
+        }
+        details.retain(|detail| !detail.is_empty());
+        details.join(", ")
+    }
+}
+
+/// Families of one foundry, sorted by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundryCards {
+    pub foundry: String,
+    pub families: Vec<FamilyCard>,
+}
+
+/// Build the family cards of a library's fonts, foundries and families sorted
+/// by name. With `family_glob` only families whose name matches it (ignoring
+/// case) are kept.
+pub fn family_cards(entries: &[InventoryEntry], family_glob: Option<&Pattern>) -> Vec<FoundryCards> {
+    let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+
+    // Faces keyed by width, weight and name, so they sort as a type specimen
+    // does, with the family's formats and versions
+    type Family = (BTreeMap<(u16, u16, String), (bool, bool)>, BTreeSet<String>, BTreeSet<String>);
+    let mut foundries: BTreeMap<String, BTreeMap<String, Family>> = BTreeMap::new();
+
+    for entry in entries {
+        if family_glob.is_some_and(|glob| !glob.matches_with(&entry.family, options)) {
+            continue;
+        }
+
+        let (faces, formats, versions) = foundries.entry(entry.foundry.clone()).or_default()
+            .entry(entry.family.clone()).or_default();
+
+        let key = match &entry.variable {
+            // The slant of a variable font is shown as its italic flag
+            Some(label) => (u16::MAX, u16::MAX, label.replacen(" Italic", "", 1)),
+            None => {
+                let width = width_class_name(entry.width);
+                let weight = weight_name(entry.weight);
+                let name = if width.is_empty() {
+                    format!("{} {}", weight, entry.weight)
+                } else {
+                    format!("{} {} {}", width, weight, entry.weight)
+                };
+                (entry.width, entry.weight, name)
+            }
+        };
+        let (roman, italic) = faces.entry(key).or_default();
+        if entry.italic { *italic = true } else { *roman = true }
+
+        if let Some(extension) = split_face_index(&entry.path).0.extension() {
+            formats.insert(extension.to_string_lossy().to_lowercase());
+        }
+        if let Some(version) = &entry.version {
+            versions.insert(version.clone());
+        }
+    }
+
+    foundries.into_iter()
+        .map(|(foundry, families)| FoundryCards {
+            foundry,
+            families: families.into_iter()
+                .map(|(family, (faces, formats, versions))| FamilyCard {
+                    family,
+                    faces: faces.into_iter()
+                        .map(|((_, _, name), (roman, italic))| CardFace { name, roman, italic })
+                        .collect(),
+                    formats: formats.into_iter().collect(),
+                    versions: versions.into_iter().collect(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Render family cards as an aligned text tree, or as Markdown with a
+/// heading per foundry and a bullet per family
+pub fn format_cards(cards: &[FoundryCards], markdown: bool) -> String {
+    let mut out = String::new();
+
+    for (index, foundry) in cards.iter().enumerate() {
+        if markdown {
+            if index > 0 {
+                out.push('\n');
+            }
+            out.push_str(&format!("## {}\n\n", foundry.foundry));
+            for card in &foundry.families {
+                out.push_str(&format!("- **{}** — {} `[{}]`\n", card.family, card.faces_label(), card.details_label()));
+            }
+        } else {
+            let families = foundry.families.len();
+            out.push_str(&format!("{} ({} {})\n", foundry.foundry, families, if families == 1 { "family" } else { "families" }));
+            let width = foundry.families.iter().map(|card| card.family.chars().count()).max().unwrap_or(0);
+            for card in &foundry.families {
+                let padding = " ".repeat(width - card.family.chars().count());
+                out.push_str(&format!("  {}{} — {} [{}]\n", card.family, padding, card.faces_label(), card.details_label()));
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::list_fonts;
+    use crate::models::{Config, FontStyleClass};
+    use crate::testing::{TempDir, TestFont};
+
+    /// A small library: a family with every face paired with an italic, one
+    /// with italics for some faces in two formats, and a condensed width
+    fn fixture_library(tmp: &TempDir) -> Vec<InventoryEntry> {
+        let face = |family: &str, subfamily: &str, vendor: &[u8; 4], version: &str| {
+            TestFont::new(family, subfamily).vendor(vendor).name(5, &format!("Version {}", version))
+        };
+        for (subfamily, weight) in [("Regular", 400), ("Medium", 500), ("Bold", 700)] {
+            face("Inter", subfamily, b"GOOG", "3.019").weight(weight).write(&tmp.join(format!("Inter/{}.ttf", subfamily)));
+            face("Inter", &format!("{} Italic", subfamily), b"GOOG", "3.019").weight(weight).fs_selection(1)
+                .write(&tmp.join(format!("Inter/{}Italic.ttf", subfamily)));
+        }
+        face("Roboto", "Regular", b"GOOG", "2.137").write(&tmp.join("Roboto/Roboto-Regular.ttf"));
+        face("Roboto", "Bold", b"GOOG", "3.000").weight(700).write(&tmp.join("Roboto/Roboto-Bold.otf"));
+        face("Roboto", "Bold Italic", b"GOOG", "3.000").weight(700).fs_selection(1).write(&tmp.join("Roboto/Roboto-BoldItalic.otf"));
+        face("Roboto", "Light Italic", b"GOOG", "2.137").weight(300).fs_selection(1).write(&tmp.join("Roboto/Roboto-LightItalic.ttf"));
+        face("Source Serif", "Regular", b"ADBE", "4.005").write(&tmp.join("Source Serif/Regular.otf"));
+        face("Source Serif", "Condensed Bold", b"ADBE", "4.005").weight(700).width(3).write(&tmp.join("Source Serif/CondBold.otf"));
+
+        list_fonts(tmp.path(), &Config { recursive: true, ..Config::default() }).unwrap()
+    }
+
+    #[test]
+    fn golden_text_cards() {
+        let tmp = TempDir::new("cards");
+        let cards = family_cards(&fixture_library(&tmp), None);
+        assert_eq!(format_cards(&cards, false), "\
+Adobe (1 family)
+  Source Serif — Condensed Bold 700, Regular 400 [otf, v4.005]
+Google (2 families)
+  Inter  — Regular 400, Medium 500, Bold 700 (+italics) [ttf, v3.019]
+  Roboto — Light 300 italic, Regular 400, Bold 700 +italic [otf/ttf, v2.137/v3.000]
+");
+    }
+
+    #[test]
+    fn golden_markdown_cards() {
+        let tmp = TempDir::new("cards");
+        let cards = family_cards(&fixture_library(&tmp), None);
+        assert_eq!(format_cards(&cards, true), "\
+## Adobe
+
+- **Source Serif** — Condensed Bold 700, Regular 400 `[otf, v4.005]`
+
+## Google
+
+- **Inter** — Regular 400, Medium 500, Bold 700 (+italics) `[ttf, v3.019]`
+- **Roboto** — Light 300 italic, Regular 400, Bold 700 +italic `[otf/ttf, v2.137/v3.000]`
+");
+    }
+
+    #[test]
+    fn family_glob_ignores_case() {
+        let tmp = TempDir::new("cards");
+        let glob = Pattern::new("ro*").unwrap();
+        let cards = family_cards(&fixture_library(&tmp), Some(&glob));
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].foundry, "Google");
+        let families: Vec<&str> = cards[0].families.iter().map(|card| card.family.as_str()).collect();
+        assert_eq!(families, ["Roboto"]);
+    }
+
+    #[test]
+    fn variable_fonts_are_listed_by_their_axes_last() {
+        let entry = |path: &str, italic: bool, variable: Option<&str>| InventoryEntry {
+            path: path.into(),
+            family: "Inter".to_string(),
+            subfamily: String::new(),
+            foundry: "Rasmus Andersson".to_string(),
+            weight: 400,
+            width: 5,
+            italic,
+            style_class: FontStyleClass::SansSerif,
+            version: None,
+            size: 0,
+            variable: variable.map(str::to_string),
+        };
+        let entries = [
+            entry("Inter[wght].ttf", false, Some("Variable [wght 100-900]")),
+            entry("Inter-Italic[wght].ttf", true, Some("Variable [wght 100-900] Italic")),
+            entry("Inter-Regular.woff2", false, None),
+        ];
+        let card = &family_cards(&entries, None)[0].families[0];
+        assert_eq!(card.faces_label(), "Regular 400, Variable [wght 100-900] +italic");
+        assert_eq!(card.details_label(), "ttf/woff2");
+    }
+}
@@ -0,0 +1,303 @@
+//! Consistency check of an organized library (`--check`, `--check --fix`)
+//!
+//! Every font is compared with the folder it is in and the name the naming
+//! pattern gives it. Families kept together by a merge are not reported: a
+//! font belongs in a folder named after a similar family, under the merge
+//! settings of the run, or in the target folder of a force-merge rule.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::error::Result;
+use crate::font::cache::cached_font_metadata;
+use crate::font::metadata::diagnose_font_file;
+use crate::models::{Config, FontMetadata, MoveAction, PlannedMove};
+use crate::organizer::{is_set_aside_folder, is_weight_folder};
+use crate::organizer::migrate::stem_matches;
+use crate::organizer::processor::{
+    are_family_names_similar,
+    claim_target_path,
+    execute_planned_move,
+    font_target_dir,
+    grouping_folder_name,
+    is_font_extension,
+    VARIABLE_FOLDER,
+};
+use crate::utils::{format_font_name, log};
+
+/// A problem found in an organized library
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrganizationIssue {
+    /// A font in the folder of another family, or loose in the library root
+    FileInWrongFamily {
+        path: PathBuf,
+        /// Folder name the font's family is grouped under
+        actual_family: String,
+        /// Name of the folder it is in
+        folder_name: String,
+    },
+    /// Two fonts with the same file name, ignoring case
+    DuplicateFilename { path1: PathBuf, path2: PathBuf },
+    /// A font whose file name does not follow the naming pattern
+    MalformedFilename {
+        path: PathBuf,
+        /// File name the naming pattern gives it
+        expected: String,
+    },
+    /// A file with a font extension that cannot be parsed
+    CorruptFont(PathBuf),
+}
+
+/// Headings issues are listed under, in the order they are printed
+const ISSUE_KINDS: [&str; 4] = [
+    "Fonts in the wrong family folder",
+    "Duplicate file names",
+    "File names not following the naming pattern",
+    "Fonts that cannot be parsed",
+];
+
+impl OrganizationIssue {
+    /// Heading the issue is listed under
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OrganizationIssue::FileInWrongFamily { .. } => ISSUE_KINDS[0],
+            OrganizationIssue::DuplicateFilename { .. } => ISSUE_KINDS[1],
+            OrganizationIssue::MalformedFilename { .. } => ISSUE_KINDS[2],
+            OrganizationIssue::CorruptFont(_) => ISSUE_KINDS[3],
+        }
+    }
+}
+
+impl fmt::Display for OrganizationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrganizationIssue::FileInWrongFamily { path, actual_family, folder_name } => write!(
+                f, "{}: family '{}' is in folder '{}'", path.display(), actual_family, folder_name
+            ),
+            OrganizationIssue::DuplicateFilename { path1, path2 } => write!(
+                f, "{}: same file name as {}", path2.display(), path1.display()
+            ),
+            OrganizationIssue::MalformedFilename { path, expected } => write!(
+                f, "{}: expected the name '{}'", path.display(), expected
+            ),
+            OrganizationIssue::CorruptFont(path) => write!(f, "{}: cannot be parsed", path.display()),
+        }
+    }
+}
+
+/// A font of the library with the family folder it is in
+struct CheckedFont {
+    path: PathBuf,
+    family_dir: PathBuf,
+    metadata: FontMetadata,
+}
+
+/// Check the library at `dir` for fonts in the wrong family folder, file
+/// names used twice, names not following `config.naming_pattern` and fonts
+/// that cannot be parsed. Set-aside and hidden folders are not checked.
+pub fn check_organization(dir: &Path, config: &Config) -> Result<Vec<OrganizationIssue>> {
+    let mut fonts = Vec::new();
+    let mut issues = Vec::new();
+    collect_fonts(dir, config, &mut fonts, &mut issues);
+
+    let mut file_names: HashMap<String, &Path> = HashMap::new();
+    for font in &fonts {
+        if let Some(actual_family) = wrong_family(font, dir, config) {
+            issues.push(OrganizationIssue::FileInWrongFamily {
+                path: font.path.clone(),
+                actual_family,
+                folder_name: folder_name(&font.family_dir),
+            });
+        }
+
+        let file_name = font.path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+        match file_names.get(&file_name) {
+            Some(first) => issues.push(OrganizationIssue::DuplicateFilename {
+                path1: first.to_path_buf(),
+                path2: font.path.clone(),
+            }),
+            None => {
+                file_names.insert(file_name, &font.path);
+            }
+        }
+
+        let (stem, extension) = stem_and_extension(&font.path);
+        let expected_stem = expected_stem(&font.metadata, &extension, config);
+        if !stem_matches(&stem, &expected_stem) {
+            issues.push(OrganizationIssue::MalformedFilename {
+                path: font.path.clone(),
+                expected: format!("{}.{}", expected_stem, extension),
+            });
+        }
+    }
+    Ok(issues)
+}
+
+/// Resolve the issues `--fix` can: fonts in the wrong family folder move to
+/// their family's folder next to it, and malformed names are renamed to the
+/// naming pattern. Duplicate names and corrupt fonts are left for the user.
+/// Folders left empty by the moves are removed.
+///
+/// Returns the number of fonts moved or renamed.
+pub fn fix_organization(dir: &Path, issues: &[OrganizationIssue], config: &Config) -> Result<usize> {
+    let misplaced: HashMap<&Path, &str> = issues.iter()
+        .filter_map(|issue| match issue {
+            OrganizationIssue::FileInWrongFamily { path, actual_family, .. } => Some((path.as_path(), actual_family.as_str())),
+            _ => None,
+        })
+        .collect();
+    let mut paths: Vec<&Path> = issues.iter()
+        .filter_map(|issue| match issue {
+            OrganizationIssue::FileInWrongFamily { path, .. } | OrganizationIssue::MalformedFilename { path, .. } => Some(path.as_path()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    paths.sort();
+
+    let mut claimed = HashSet::new();
+    let mut emptied: HashSet<PathBuf> = HashSet::new();
+    let mut fixed = 0;
+    for path in paths {
+        let Some(metadata) = cached_font_metadata(path, config).ok().flatten() else { continue };
+        let Some(current_dir) = path.parent() else { continue };
+        let (stem, extension) = stem_and_extension(path);
+        let expected_stem = expected_stem(&metadata, &extension, config);
+        let base_name = if stem_matches(&stem, &expected_stem) { stem } else { expected_stem };
+
+        let target_dir = match misplaced.get(path) {
+            Some(family) => {
+                let family_dir = family_dir_of(path, &metadata);
+                let library_dir = if family_dir == dir { dir } else { family_dir.parent().unwrap_or(dir) };
+                font_target_dir(&library_dir.join(config.folder_name(family)), &metadata, config)
+            }
+            None => current_dir.to_path_buf(),
+        };
+        let destination = claim_target_path(&target_dir, &base_name, &extension, &mut claimed);
+        log(config, format!("Fixing {} -> {}", path.display(), destination.display()));
+        execute_planned_move(&PlannedMove::new(path.to_path_buf(), destination, MoveAction::Move), config)?;
+        emptied.insert(current_dir.to_path_buf());
+        fixed += 1;
+    }
+
+    // Weight and family folders left empty are removed, deepest first
+    let mut emptied: Vec<PathBuf> = emptied.into_iter().collect();
+    emptied.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    for empty_dir in emptied.iter().filter(|_| !config.dry_run) {
+        let mut empty_dir = empty_dir.as_path();
+        while empty_dir != dir && fs::remove_dir(empty_dir).is_ok() {
+            log(config, format!("Removed empty folder {}", empty_dir.display()));
+            let Some(parent) = empty_dir.parent() else { break };
+            empty_dir = parent;
+        }
+    }
+    Ok(fixed)
+}
+
+/// Print the issues grouped by kind, with a count per kind
+pub fn print_issues(issues: &[OrganizationIssue]) {
+    if issues.is_empty() {
+        println!("No issues found.");
+        return;
+    }
+
+    println!("{} issues found:", issues.len());
+    for kind in ISSUE_KINDS {
+        let matching: Vec<&OrganizationIssue> = issues.iter().filter(|issue| issue.kind() == kind).collect();
+        if matching.is_empty() {
+            continue;
+        }
+        println!("  {} ({}):", kind, matching.len());
+        for issue in matching {
+            println!("    - {}", issue);
+        }
+    }
+}
+
+/// Fonts below `dir` in path order, skipping set-aside and hidden folders;
+/// fonts that cannot be parsed are reported as corrupt
+fn collect_fonts(dir: &Path, config: &Config, fonts: &mut Vec<CheckedFont>, issues: &mut Vec<OrganizationIssue>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            let name = folder_name(&path);
+            if !name.starts_with('.') && !is_set_aside_folder(&name, config) {
+                collect_fonts(&path, config, fonts, issues);
+            }
+            continue;
+        }
+        if !is_font_extension(&path) {
+            continue;
+        }
+
+        match cached_font_metadata(&path, config) {
+            Ok(Some(metadata)) => {
+                let family_dir = family_dir_of(&path, &metadata);
+                fonts.push(CheckedFont { path, family_dir, metadata });
+            }
+            failed => {
+                if let Some(reason) = diagnose_font_file(&path).or_else(|| failed.err().map(|e| e.to_string())) {
+                    log(config, format!("Cannot parse {}: {}", path.display(), reason));
+                    issues.push(OrganizationIssue::CorruptFont(path));
+                }
+            }
+        }
+    }
+}
+
+/// Family folder of a font: its parent, or its grandparent when the parent
+/// is its weight folder or the `Variable` folder
+fn family_dir_of(path: &Path, metadata: &FontMetadata) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    let in_weight_folder = parent.file_name().is_some_and(|folder| {
+        let folder = folder.to_string_lossy();
+        is_weight_folder(&folder, metadata.weight, metadata.width_class()) || (metadata.is_variable && folder == VARIABLE_FOLDER)
+    });
+    match parent.parent().filter(|_| in_weight_folder) {
+        Some(family_dir) => family_dir.to_path_buf(),
+        None => parent.to_path_buf(),
+    }
+}
+
+/// The folder name a font's family is grouped under, when the folder it is
+/// in belongs to another family or it is loose in the library root `dir`
+fn wrong_family(font: &CheckedFont, dir: &Path, config: &Config) -> Option<String> {
+    let family = grouping_folder_name(&font.metadata.family_name);
+    if family.is_empty() {
+        return None;
+    }
+    if font.family_dir == dir {
+        return Some(family);
+    }
+
+    let folder = folder_name(&font.family_dir);
+    let forced = config.merge_rules.as_ref()
+        .and_then(|rules| rules.force_target(&font.metadata.family_name, &family))
+        .is_some_and(|target| config.folder_name(target).eq_ignore_ascii_case(&folder));
+    let same = config.folder_name(&family).eq_ignore_ascii_case(&folder)
+        || are_family_names_similar(&family, &folder, config);
+    (!forced && !same).then_some(family)
+}
+
+/// File name the naming pattern gives a font, without the extension
+fn expected_stem(metadata: &FontMetadata, extension: &str, config: &Config) -> String {
+    config.file_stem(&format_font_name(metadata, &config.naming_pattern), extension)
+}
+
+/// File stem and lowercased extension of a path
+fn stem_and_extension(path: &Path) -> (String, String) {
+    (
+        path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        path.extension().unwrap_or_default().to_string_lossy().to_lowercase(),
+    )
+}
+
+/// Last component of a path
+fn folder_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
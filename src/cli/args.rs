@@ -1,44 +1,823 @@
+//! Command-line options: the definition of every flag and subcommand, the
+//! parser that checks the arguments against them, and the help and shell
+//! completion scripts generated from the same definitions
+
+use std::collections::HashMap;
 use std::env;
-use crate::models::NamingPattern;
-
-/// Parse command line arguments into naming pattern
-pub fn parse_args() -> NamingPattern {
-    let args: Vec<String> = env::args().collect();
-    
-    if args.contains(&"--foundry-family-subfamily".to_string()) {
-        NamingPattern::FoundryFamilySubfamily
-    } else if args.contains(&"--family-weight".to_string()) {
-        NamingPattern::FamilyWeight
-    } else if args.contains(&"--foundry-family".to_string()) {
-        NamingPattern::FoundryFamily
-    } else {
-        NamingPattern::FamilySubfamily
+use std::fs;
+use std::path::{Path, PathBuf};
+use glob::Pattern;
+use crate::error::{Error, Result};
+use crate::models::{FontFilter, NamingPattern};
+use crate::utils::LogLevel;
+
+/// What follows a flag on the command line, and what it completes to
+#[derive(Clone, Copy)]
+enum FlagValue {
+    /// The flag takes no value
+    Switch,
+    File,
+    Directory,
+    /// Two directories, e.g. `--diff <BEFORE> <AFTER>`
+    DirectoryPair,
+    /// Free text such as a number, a size or a glob
+    Text,
+    /// One of a fixed set of names, offered by shell completion
+    Choices(&'static [&'static str]),
+}
+
+impl FlagValue {
+    /// Number of arguments the flag consumes
+    fn arity(self) -> usize {
+        match self {
+            FlagValue::Switch => 0,
+            FlagValue::DirectoryPair => 2,
+            _ => 1,
+        }
     }
 }
 
-/// Get the help message for command-line usage
-pub fn get_help_message() -> String {
-    r#"Font Organizer - A tool for organizing font collections
+/// A command-line flag
+struct FlagSpec {
+    name: &'static str,
+    /// Single-dash alias, e.g. `-h` for `--help`
+    short: Option<&'static str>,
+    /// Placeholder of the value in the help, e.g. `<FILE>`
+    value_name: &'static str,
+    value: FlagValue,
+    help: &'static str,
+    /// Accepted but left out of the help and completion scripts
+    hidden: bool,
+}
+
+impl FlagSpec {
+    const fn short(self, short: &'static str) -> Self {
+        FlagSpec { short: Some(short), ..self }
+    }
+
+    const fn hidden(self) -> Self {
+        FlagSpec { hidden: true, ..self }
+    }
+
+    fn matches(&self, arg: &str) -> bool {
+        self.name == arg || self.short == Some(arg)
+    }
+}
+
+const fn switch(name: &'static str, help: &'static str) -> FlagSpec {
+    FlagSpec { name, short: None, value_name: "", value: FlagValue::Switch, help, hidden: false }
+}
+
+const fn option(name: &'static str, value_name: &'static str, value: FlagValue, help: &'static str) -> FlagSpec {
+    FlagSpec { name, short: None, value_name, value, help, hidden: false }
+}
+
+/// Flags of an organize run, also accepted by every subcommand
+const FLAGS: &[FlagSpec] = &[
+    switch("--help", "Show this help message").short("-h"),
+    switch("--version", "Print the version and exit"),
+    option("--generate-completion", "<SHELL>", FlagValue::Choices(&Shell::NAMES),
+        "Print a completion script for bash, zsh, fish or powershell"),
+    option("--completions", "<SHELL>", FlagValue::Choices(&Shell::NAMES), "Same as --generate-completion").hidden(),
+    switch("--quiet", "Only print errors").short("-q"),
+    switch("-v", "Print debug messages; warnings and progress are printed by default"),
+    switch("-vv", "Print debug and per-file trace messages"),
+    switch("--debug", "Same as -v (deprecated)"),
+    switch("--dry-run", "Report what would be moved without moving"),
+    switch("--recursive", "Also organize fonts in subdirectories"),
+    switch("--no-default-ignores", "Also scan .DS_Store, ._* AppleDouble, __MACOSX, Thumbs.db and desktop.ini files"),
+    switch("--extract-zips", "Extract fonts (also from zips inside zips) of .zip archives before organizing"),
+    switch("--delete-zips", "Delete archives whose fonts were extracted (implies --extract-zips)"),
+    switch("--rename-only", "Rename fonts to the naming pattern inside the folder they are in, without family folders; cannot be combined with --batch-output"),
+    switch("--watch", "Keep running and organize fonts as they are added to the directory, until Ctrl-C"),
+    switch("--list", "Write a CSV inventory of the fonts (path, family, subfamily, foundry, weight, italic, version, size) without moving anything; choice 3 of the menu does the same"),
+    switch("--check", "Check an organized directory for fonts in the wrong family folder, duplicate file names, names not following the naming pattern and corrupt fonts, without moving anything; exits with status 4 when issues are found"),
+    switch("--fix", "With --check, move fonts into their family folders and rename them to the naming pattern"),
+    option("--list-output", "<FILE>", FlagValue::File, "Write the inventory to FILE instead of stdout"),
+    option("--batch", "<FILE>", FlagValue::File, "Process multiple directories listed in a file, one per line, each optionally followed by a preset (@archive, @quick) and flags"),
+    option("--batch-output", "<DIR>", FlagValue::Directory, "Organize batch sources into this root instead of in place"),
+    option("--batch-output-layout", "<LAYOUT>", FlagValue::Choices(&["merged", "per-source"]),
+        "merged (default): one library, families unified across sources; per-source: <DIR>/<source>/"),
+    option("--batch-parallelism", "<N>", FlagValue::Text, "Batch sources organized at the same time (default 4, merged batches one at a time)"),
+    switch("--foundry-family-subfamily", "Use \"Foundry Family (Subfamily)\" naming pattern"),
+    switch("--family-weight", "Use \"Family Weight\" naming pattern"),
+    switch("--foundry-family", "Use \"Foundry/Family\" directory structure"),
+    option("--pattern", "<NAME|TEMPLATE>", FlagValue::Choices(&NamingPattern::BUILTIN_NAMES),
+        "Use a built-in pattern by name (family-subfamily, foundry-family-subfamily, family-weight, foundry-family) or a custom template such as \"%Family% %Width% %Weight% %Style%\""),
+    option("--max-name-length", "<BYTES>", FlagValue::Text, "Cut longer folder and file names to this many bytes, keeping the extension (default 255, at least 32)"),
+    switch("--ignore-system-fonts", "Leave fonts of families installed on the system (e.g. copies of Arial or Helvetica) in place"),
+    switch("--no-filename-fallback", "Skip fonts without a usable name table instead of guessing their family and style from the file name"),
+    switch("--ascii-names", "Like --transliterate, but keep CJK names and prefix them with the family of the PostScript name"),
+    switch("--transliterate", "Replace non-ASCII characters of family, style and foundry names with an ASCII approximation for ASCII-only paths (\"Нота\" -> \"Nota\")"),
+    option("--merge-families", "<MODE>", FlagValue::Choices(&["aggressive", "conservative", "off"]),
+        "aggressive (default) also merges families with a 70% common prefix or a 20% edit distance, conservative only within a 10% edit distance, off groups strictly by normalized family name, e.g. keeps DIN and DIN Pro apart"),
+    option("--similarity", "<FLOAT>", FlagValue::Text, "Edit distance allowed between family names that are merged, as a fraction of the shorter name (0.0-1.0, overrides --merge-families)"),
+    switch("--no-similarity-merge", "Same as --merge-families off"),
+    option("--merge-rules", "<FILE>", FlagValue::File, "never_merge pairs and force_merge targets that override the similarity merge (default ./merges.toml when present)"),
+    switch("--review-merges", "Ask y/n/a(ll)/q at the terminal before each family merge; a accepts the remaining merges, q stops before any file is moved (needs a terminal, not with --batch or --watch)"),
+    switch("--interactive-merge", "Ask y(es)/n(o)/a(lways)/ne(v)er/m(erge all)/s(kip all)/q(uit) before each family merge and remember always and never answers in ~/.config/fontsrt/merge_rules.json; q stops before any file is moved, and without a terminal every merge is declined (not with --batch or --watch)"),
+    switch("--yes", "Run without asking about family merges and decline every one, keeping similar families apart"),
+    option("--merge", "<MODE>", FlagValue::Choices(&["always", "never", "auto"]),
+        "always (default) merges similar families, never skips the merge phase, auto skips it when few sampled family names have a similar name"),
+    option("--merge-sample", "<N>", FlagValue::Text, "Family names sampled by --merge auto (default 500)"),
+    option("--merge-threshold", "<PERCENT>", FlagValue::Text, "Share of sampled names with a similar name below which --merge auto skips merging (default 5)"),
+    switch("--group-by-weight", "Nest fonts in weight folders inside each family folder, e.g. Roboto/700/"),
+    option("--weight-folders", "<STYLE>", FlagValue::Choices(&["numeric", "textual"]), "Name weight folders numeric (400, default) or textual (Regular, Bold)"),
+    switch("--separate-variable", "Put variable fonts in a Variable folder inside each family folder, apart from static fonts"),
+    switch("--group-by-style", "After organizing, nest family folders in style class folders (Serif, Sans Serif, Script, Decorative, Symbol, Monospace, Unknown)"),
+    option("--group-by", "<LAYOUT>", FlagValue::Choices(&["style", "designer", "technology", "script", "license", "first-letter"]),
+        "After organizing, nest family folders by style, designer, technology (Type 1, Legacy TrueType, OpenType, OpenType CFF, Variable, Color), script, license or first-letter"),
+    switch("--group-by-script", "After organizing, nest family folders in folders of their dominant script (Latin, Greek, Cyrillic, Hebrew, Arabic, Devanagari, Thai, CJK)"),
+    switch("--group-by-license", "After organizing, nest family folders in Libre (OFL, Apache, GPL with font exception), Commercial and Unknown License folders"),
+    switch("--group-by-first-letter", "After organizing and any other grouping, put the top-level folders in A-Z buckets by first letter (# for other characters), e.g. R/Roboto/ or A/Adobe/Acumin/"),
+    switch("--classify", "After organizing, nest family folders in classification folders (Serif, Sans, Slab, Mono, Script, Display, Unclassified) by majority vote of their fonts; foundry grouping then nests foundry folders inside them"),
+    option("--dup-policy", "<POLICY>", FlagValue::Choices(&["keep-all", "keep-best", "keep-first"]),
+        "Different files of the same face (family, weight, width, italic): keep-all (default) suffixes them _1, _2; keep-best keeps the highest version, then CFF over TrueType, then most glyphs; keep-first keeps the first by path. Others go to duplicates"),
+    option("--large-threshold", "<SIZE>", FlagValue::Text, "Fonts above SIZE (default 20MB, e.g. CJK fonts) are pre-screened for copies with a sampled hash and only set aside as confirmed copies"),
+    switch("--no-verify", "Skip the checksum comparing a font copied to another drive with its original before the original is deleted"),
+    option("--duplicates-dir", "<DIR>", FlagValue::Directory, "Duplicates folder name inside the library, or an absolute vault collecting set-aside files of every library in <DIR>/<library-name>/"),
+    switch("--detect-synthetic", "Flag faces that look like mechanically sheared obliques or faux weights of another face"),
+    switch("--quarantine-synthetic", "Move flagged synthetic faces into a synthetic folder (implies --detect-synthetic)"),
+    switch("--quarantine", "Move files with a font extension that fail to parse into a corrupt folder, with the reason for each in corrupt/report.txt"),
+    switch("--group-by-designer", "After organizing, nest family folders in designer folders (name ID 9, falling back to the foundry)"),
+    switch("--export-designers", "Print a \"designer -> [families]\" mapping"),
+    switch("--fingerprint", "Report fonts with matching outlines under different family names, never merging them (requires the `fingerprint` feature)"),
+    switch("--check-completeness", "Report families missing common weights (400/700) or italic counterparts after organizing"),
+    option("--min-completeness", "<PERCENT>", FlagValue::Text, "Only report families below this completeness"),
+    switch("--error-report", "Write files that could not be organized to fontsrt-errors.json (path, stage, error)"),
+    switch("--no-fail-on-error", "Exit successfully even when some files could not be organized"),
+    option("--log-file", "<PATH>", FlagValue::File, "Append the log to a file as JSON lines with an ISO 8601 timestamp and level, whatever the verbosity; fails at startup if not writable"),
+    option("--log-level", "<LEVEL>", FlagValue::Choices(&LogLevel::NAMES), "Least severe level written to the --log-file (default info)"),
+    option("--json-events", "<FILE>", FlagValue::File, "Write a newline-delimited JSON event stream (\"-\" for stderr); every event has a run_id and gap-free seq, the last one is \"end\""),
+    option("--report", "<FILE>", FlagValue::File, "Write a JSON report of every font moved, renamed, set aside, skipped or failed, with totals and timing"),
+    option("--generate-preview", "<FILE>", FlagValue::File, "Write a self-contained HTML index of the organized families (the planned ones in a dry run) with a search box"),
+    option("--parse-css", "<FILE>", FlagValue::File, "Also organize the local and data: URI fonts of a stylesheet's @font-face rules, named by the rule when they lack a name table"),
+    option("--html-gallery", "<FILE>", FlagValue::File, "Write a static HTML page with a card and live sample of every family of the library, loading the fonts by relative paths"),
+    option("--top-families", "<N>", FlagValue::Text, "Number of largest families listed after a run (default 5, 0 to hide)"),
+    switch("--stats", "Print moved and skipped files, folder counts, size saved by deduplication, the weight distribution, the 10 largest families and single-font families after organizing"),
+    option("--foundry-aliases", "<FILE>", FlagValue::File, "Map raw foundry names to canonical ones (defaults to ./foundries.toml when present)"),
+    option("--foundry-map", "<FILE>", FlagValue::File, "Assign foundries to family name patterns, before any detection (defaults to ~/.config/fontsrt/foundry_overrides.json)"),
+    switch("--validate-foundry-map", "Check the foundry map for invalid entries and exit without processing fonts"),
+    option("--lockfile", "<FILE>", FlagValue::File, "Only organize families approved in this TOML file, optionally limited to some weights and styles; report approved families missing"),
+    option("--lockfile-mode", "<MODE>", FlagValue::Choices(&["review", "skip"]), "review (default) moves unapproved fonts to _Unapproved, skip leaves them in place"),
+    option("--only-foundry", "<NAME>", FlagValue::Text, "Only organize fonts of this foundry"),
+    option("--only-weight-range", "<MIN-MAX>", FlagValue::Text, "Only organize fonts of these weights (600-900)"),
+    switch("--only-italic", "Only organize italic and oblique fonts"),
+    option("--exclude", "<GLOB>", FlagValue::Text, "Leave fonts of families matching GLOB in place, ignoring case; repeatable, e.g. \"Arial*\""),
+    option("--exclude-file", "<PATH>", FlagValue::File, "Exclude patterns, one glob per line"),
+    option("--filter-action", "<ACTION>", FlagValue::Choices(&["skip", "quarantine", "ignore"]),
+        "What happens to fonts the --only-* flags reject: skip (default) leaves and lists them, quarantine moves them to _skipped, ignore leaves them unlisted"),
+    option("--reproduce", "<FILE>", FlagValue::File, "Repeat a run with the settings, foundry and merge files and lockfile recorded in its fontsrt-snapshot.json"),
+    option("--diff", "<BEFORE> <AFTER>", FlagValue::DirectoryPair, "List fonts added, removed, moved to another folder or renamed between two versions of a directory, matching moved fonts by content"),
+    switch("--diff-json", "Print the --diff result as JSON"),
+    switch("--no-cache", "Parse every font instead of reusing metadata cached in ~/.cache/fontsrt/metadata.json"),
+    switch("--rebuild-cache", "Parse every font and replace its cache entry"),
+    switch("--rebuild-db", "Parse every font again into the library's font database (.fontsrt.db)"),
+];
+
+/// A subcommand, given as the first argument
+struct SubcommandSpec {
+    name: &'static str,
+    /// Heading of its help section
+    title: &'static str,
+    /// Arguments after the name in the usage line
+    usage: &'static str,
+    help: &'static str,
+    /// One line for shell completion
+    summary: &'static str,
+    /// Flags only this subcommand accepts, or described differently here
+    flags: &'static [FlagSpec],
+}
+
+const SUBCOMMANDS: &[SubcommandSpec] = &[
+    SubcommandSpec {
+        name: "du",
+        title: "DISK USAGE",
+        usage: "<DIRECTORY> [--json] [--threshold <SIZE>]",
+        help: "Print family and foundry size breakdowns of an organized directory, counting every file",
+        summary: "Print family and foundry sizes of a library",
+        flags: &[
+            switch("--json", "Print the breakdown as JSON"),
+            option("--threshold", "<SIZE>", FlagValue::Text, "Hide entries smaller than SIZE (e.g. 500K, 10MB)"),
+        ],
+    },
+    SubcommandSpec {
+        name: "doctor",
+        title: "DOCTOR",
+        usage: "<DIRECTORY> [--json]",
+        help: "Check permissions, file system limits, free space, leftovers of earlier runs and a sample of fonts, one pass/warn/fail line each; exits non-zero on failures",
+        summary: "Check a directory before organizing it",
+        flags: &[switch("--json", "Print the checks as JSON for bug reports")],
+    },
+    SubcommandSpec {
+        name: "compare-installed",
+        title: "COMPARE INSTALLED",
+        usage: "<LIBRARY> [--json]",
+        help: "List installed fonts missing from an organized library and library fonts newer than the installed copy; reads the Windows registry and user fonts folder, or the system and user font folders elsewhere. Read-only",
+        summary: "Compare installed fonts with a library",
+        flags: &[switch("--json", "Print the comparison as JSON")],
+    },
+    SubcommandSpec {
+        name: "migrate",
+        title: "MIGRATE",
+        usage: "<DIRECTORY> [--skip <CATEGORIES>] [--yes] [--dry-run]",
+        help: "Move a library organized by an older FontSrt version or other settings to the current folder names, weight folders and naming pattern, journaled in fontsrt-migration.jsonl",
+        summary: "Move a library to the current layout",
+        flags: &[
+            option("--skip", "<CATEGORIES>", FlagValue::Choices(&["folders", "regroup", "renames"]), "Leave out folders, regroup and/or renames (comma separated)"),
+            switch("--yes", "Apply without asking after the preview"),
+            switch("--dry-run", "Print the preview only"),
+        ],
+    },
+    SubcommandSpec {
+        name: "list",
+        title: "LIST",
+        usage: "<DIRECTORY> [--family <GLOB>] [--markdown]",
+        help: "Print one line per family under its foundry, with its weights (italics collapsed onto the upright line), formats and versions",
+        summary: "Print one line per family under its foundry",
+        flags: &[
+            option("--family", "<GLOB>", FlagValue::Text, "Only families matching GLOB, ignoring case"),
+            switch("--markdown", "Print Markdown for wikis instead of text"),
+        ],
+    },
+    SubcommandSpec {
+        name: "ungroup",
+        title: "UNGROUP",
+        usage: "<DIRECTORY> [--dry-run]",
+        help: "Move family folders out of their foundry folders, undoing foundry grouping",
+        summary: "Move family folders out of foundry folders",
+        flags: &[],
+    },
+    SubcommandSpec {
+        name: "serve",
+        title: "SERVER (requires the `serve` feature)",
+        usage: "--socket <PATH|PORT>",
+        help: "Accept newline-delimited JSON-RPC requests (info, organize, group) on a Unix socket or on a localhost TCP port",
+        summary: "Accept JSON-RPC requests on a socket",
+        flags: &[option("--socket", "<PATH|PORT>", FlagValue::Text, "Unix socket path, or localhost TCP port")],
+    },
+];
+
+/// Flag definition of `arg` in a run of `command`: its own flags first, then
+/// the flags of every run
+fn find_flag(command: Option<&SubcommandSpec>, arg: &str) -> Option<&'static FlagSpec> {
+    command.into_iter()
+        .flat_map(|command| command.flags.iter())
+        .chain(FLAGS.iter())
+        .find(|flag| flag.matches(arg))
+}
+
+/// Subcommands whose own flags include `arg`
+fn subcommands_accepting(arg: &str) -> Vec<&'static str> {
+    SUBCOMMANDS.iter()
+        .filter(|command| command.flags.iter().any(|flag| flag.matches(arg)))
+        .map(|command| command.name)
+        .collect()
+}
+
+/// Command-line options of a run, checked against the flag definitions:
+/// unknown flags, missing values and flags of other subcommands are errors
+#[derive(Debug, Clone, Default)]
+pub struct CliOptions {
+    /// Subcommand given as the first argument, e.g. `du`
+    pub command: Option<&'static str>,
+    /// Arguments that are neither flags nor flag values
+    pub paths: Vec<PathBuf>,
+    /// Values of each flag given, by the flag's long name, in order; empty
+    /// for switches
+    flags: HashMap<&'static str, Vec<String>>,
+}
+
+impl CliOptions {
+    /// Parse the arguments of this process
+    pub fn from_env() -> Result<Self> {
+        Self::parse(env::args().skip(1))
+    }
+
+    /// Parse command-line arguments, without the program name
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut args = args.into_iter().peekable();
+        let mut options = CliOptions::default();
+
+        let command = args.peek().and_then(|first| SUBCOMMANDS.iter().find(|command| command.name == first));
+        if let Some(command) = command {
+            options.command = Some(command.name);
+            args.next();
+        }
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') || arg == "-" {
+                options.paths.push(PathBuf::from(arg));
+                continue;
+            }
+
+            let Some(flag) = find_flag(command, &arg) else {
+                let owners = subcommands_accepting(&arg);
+                return Err(Error::Config(if owners.is_empty() {
+                    format!("unknown flag '{}', see --help", arg)
+                } else {
+                    format!("{} is only accepted by {}", arg, owners.join(", "))
+                }));
+            };
+
+            let values = options.flags.entry(flag.name).or_default();
+            for _ in 0..flag.value.arity() {
+                let value = args.next()
+                    .filter(|value| find_flag(command, value).is_none())
+                    .ok_or_else(|| Error::Config(format!("{} requires a value {}", flag.name, flag.value_name)))?;
+                if matches!(flag.value, FlagValue::File) && Path::new(&value).is_dir() {
+                    return Err(Error::Config(format!("{} expects a file, but '{}' is a directory", flag.name, value)));
+                }
+                values.push(value);
+            }
+        }
+
+        if options.paths.len() > 1 {
+            return Err(Error::Config(format!(
+                "unexpected argument '{}', only one directory can be given",
+                options.paths[1].display()
+            )));
+        }
+        Ok(options)
+    }
+
+    /// Whether a flag was given, by its long name
+    pub fn has(&self, flag: &str) -> bool {
+        debug_assert!(is_defined(flag), "undefined flag {}", flag);
+        self.flags.contains_key(flag)
+    }
+
+    /// Value of a flag, the first one when it was given several times
+    pub fn value(&self, flag: &str) -> Option<String> {
+        self.values(flag).first().cloned()
+    }
+
+    /// Values of every occurrence of a repeatable flag, or of a flag taking
+    /// several values
+    pub fn values(&self, flag: &str) -> &[String] {
+        debug_assert!(is_defined(flag), "undefined flag {}", flag);
+        self.flags.get(flag).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Directory given on the command line
+    pub fn directory(&self) -> Option<&Path> {
+        self.paths.first().map(PathBuf::as_path)
+    }
+
+    /// Verbosity of the run from `-q`/`--quiet`, `-v`, `-vv` and the older
+    /// `--debug` (same as `-v`), `Info` when none is given
+    pub fn verbosity(&self) -> Result<LogLevel> {
+        let verbose = if self.has("-vv") {
+            Some(LogLevel::Trace)
+        } else if self.has("-v") || self.has("--debug") {
+            Some(LogLevel::Debug)
+        } else {
+            None
+        };
+        match (self.has("--quiet"), verbose) {
+            (true, Some(_)) => Err(Error::Config("--quiet cannot be combined with -v, -vv or --debug".to_string())),
+            (true, None) => Ok(LogLevel::Error),
+            (false, verbose) => Ok(verbose.unwrap_or(LogLevel::Info)),
+        }
+    }
+
+    /// Naming pattern from `--pattern` or the older pattern switches
+    pub fn naming_pattern(&self) -> Result<NamingPattern> {
+        if let Some(pattern) = self.value("--pattern") {
+            return NamingPattern::from_name(&pattern).ok_or_else(|| Error::Config(format!(
+                "--pattern expects one of {} or a %Token% template, got '{}'",
+                NamingPattern::BUILTIN_NAMES.join(", "), pattern
+            )));
+        }
+
+        Ok(if self.has("--foundry-family-subfamily") {
+            NamingPattern::FoundryFamilySubfamily
+        } else if self.has("--family-weight") {
+            NamingPattern::FamilyWeight
+        } else if self.has("--foundry-family") {
+            NamingPattern::FoundryFamily
+        } else {
+            NamingPattern::FamilySubfamily
+        })
+    }
+
+    /// Font filter of the `--only-*` flags, empty when none is given
+    pub fn font_filter(&self) -> Result<FontFilter> {
+        let mut filter = FontFilter::default();
+
+        if let Some(foundry) = self.value("--only-foundry") {
+            filter = filter.by_foundry(&foundry);
+        }
+        if let Some(range) = self.value("--only-weight-range") {
+            let (min, max) = range.split_once('-')
+                .and_then(|(min, max)| Some((min.trim().parse::<u16>().ok()?, max.trim().parse::<u16>().ok()?)))
+                .ok_or_else(|| Error::Config(format!("--only-weight-range expects MIN-MAX such as 600-900, got '{}'", range)))?;
+            filter = filter.by_weight_range(min, max);
+        }
+        if self.has("--only-italic") {
+            filter = filter.by_italic(true);
+        }
+        Ok(filter)
+    }
+
+    /// Family name patterns of the repeatable `--exclude <GLOB>` flag and the
+    /// lines of `--exclude-file <PATH>`. Blank lines and lines starting with
+    /// `#` in the file are skipped.
+    pub fn exclude_patterns(&self) -> Result<Vec<Pattern>> {
+        let mut globs = self.values("--exclude").to_vec();
+        if let Some(path) = self.value("--exclude-file") {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| Error::Config(format!("Cannot read exclude file {}: {}", path, e)))?;
+            globs.extend(content.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string));
+        }
+
+        globs.iter()
+            .map(|glob| Pattern::new(glob).map_err(|e| Error::Config(format!("Invalid exclude pattern '{}': {}", glob, e))))
+            .collect()
+    }
+}
+
+/// Whether a long flag name is defined, for the checks of `CliOptions`
+fn is_defined(flag: &str) -> bool {
+    FLAGS.iter().chain(SUBCOMMANDS.iter().flat_map(|command| command.flags.iter())).any(|spec| spec.name == flag)
+}
+
+/// Text after the flag column of the help
+const HELP_INDENT: usize = 36;
+const HELP_WIDTH: usize = 44;
+
+/// Prose sections of the help, after the options and subcommands
+const HELP_NOTES: &str = r#"NAMING TOKENS:
+    %Family%      Family name             %Subfamily%   Subfamily (style) name
+    %Foundry%     Foundry name            %Weight%      Numeric weight (400, 700)
+    %Width%       Width name, empty for normal width ("Condensed", "Expanded")
+    %Style%       "Italic" or "Oblique", empty for upright faces
+    %Version%     Font version ("3.000"), empty when missing or malformed
+    %Tech%        Technology: Type 1, Legacy TrueType, OpenType, OpenType
+                  CFF, Variable or Color
+    %License%     License: OFL, Apache, GPL+FE, Proprietary or Unknown
+
+FOUNDRY ALIASES:
+    The alias file is TOML with one "raw name" = "Canonical" pair per line.
+    Keys match raw foundry strings or OS/2 vendor IDs case-insensitively
+    and may use glob wildcards:
+
+        "Adobe Systems" = "Adobe"
+        "Adobe*" = "Adobe"
+        "TT" = "TypeType"
 
-USAGE:
-    FontSrt [OPTIONS] [DIRECTORY]
+FOUNDRY MAP:
+    The foundry map is a JSON array of family name patterns and foundries.
+    The first matching entry wins over vendor IDs, names and aliases; plain
+    names match exactly, ignoring case:
 
-ARGS:
-    <DIRECTORY>    Path to the directory containing font files (optional)
+        [{"pattern": "Hybrea*", "foundry": "Hypertypes"},
+         {"pattern": "Festivo", "foundry": "Ahmet Altun"}]
 
-OPTIONS:
-    -h, --help                      Show this help message
-    --debug                         Enable debug output
-    --batch <FILE>                  Process multiple directories listed in a file
-    --foundry-family-subfamily      Use "Foundry Family (Subfamily)" naming pattern
-    --family-weight                 Use "Family Weight" naming pattern
-    --foundry-family                Use "Foundry/Family" directory structure
+LOCKFILE:
+    The lockfile lists approved families in a [families] table. Names match
+    ignoring case, spaces and punctuation; weights and styles are optional:
+
+        [families]
+        "Helvetica Neue" = { weights = [400, 700], styles = ["upright"] }
+        "Inter" = {}
+
+EXIT CODES:
+    0    Success
+    1    An IO or other error stopped the run, or a doctor check failed
+    2    Invalid arguments, configuration file or directory
+    3    No fonts found in the directory
+    4    Some files could not be organized (see --no-fail-on-error)
+    5    Not enough disk space
 
 By default, fonts are organized using the "Family (Subfamily)" naming pattern.
 
 After organizing fonts, the program will ask if you want to group them by foundry.
 This will create a structure where fonts are organized into foundry folders first,
 then by family within each foundry folder.
-"#.to_string()
+"#;
+
+/// The help message, generated from the flag and subcommand definitions
+pub fn get_help_message() -> String {
+    let mut help = String::from("Font Organizer - A tool for organizing font collections\n\nUSAGE:\n");
+    help.push_str(&format!("    {} [OPTIONS] [DIRECTORY]\n", COMMAND));
+    for command in SUBCOMMANDS {
+        help.push_str(&format!("    {} {} {}\n", COMMAND, command.name, command.usage));
+    }
+    help.push_str("\nARGS:\n    <DIRECTORY>    Path to the directory containing font files (optional)\n\nOPTIONS:\n");
+    for flag in FLAGS.iter().filter(|flag| !flag.hidden) {
+        push_help_entry(&mut help, &flag_label(flag), flag.help);
+    }
+
+    for command in SUBCOMMANDS {
+        help.push_str(&format!("\n{}:\n", command.title));
+        let argument = command.usage.split(" [").next().unwrap_or_default();
+        push_help_entry(&mut help, &format!("{} {}", command.name, argument), command.help);
+        for flag in command.flags {
+            push_help_entry(&mut help, &flag_label(flag), flag.help);
+        }
+    }
+
+    help.push('\n');
+    help.push_str(HELP_NOTES);
+    help
+}
+
+/// "-h, --help" or "--batch <FILE>"
+fn flag_label(flag: &FlagSpec) -> String {
+    let mut label = match flag.short {
+        Some(short) => format!("{}, {}", short, flag.name),
+        None => flag.name.to_string(),
+    };
+    if !flag.value_name.is_empty() {
+        label.push(' ');
+        label.push_str(flag.value_name);
+    }
+    label
+}
+
+/// An indented help line, the text wrapped in a column after the label
+fn push_help_entry(help: &mut String, label: &str, text: &str) {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= HELP_WIDTH => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+
+    let label = format!("    {}", label);
+    let mut lines = lines.into_iter();
+    let first = lines.next().unwrap_or_default();
+    if label.chars().count() < HELP_INDENT {
+        help.push_str(&format!("{:width$}{}\n", label, first, width = HELP_INDENT));
+    } else {
+        help.push_str(&format!("{}\n{:width$}{}\n", label, "", first, width = HELP_INDENT));
+    }
+    for line in lines {
+        help.push_str(&format!("{:width$}{}\n", "", line, width = HELP_INDENT));
+    }
+}
+
+/// Shells `--generate-completion` writes a completion script for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+}
+
+impl Shell {
+    /// Names accepted by `--generate-completion`
+    pub const NAMES: [&'static str; 4] = ["bash", "zsh", "fish", "powershell"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "powershell" | "pwsh" => Some(Shell::PowerShell),
+            _ => None,
+        }
+    }
+}
+
+
+/// A flag as offered by shell completion, short aliases as flags of their own
+struct CompletionFlag {
+    name: &'static str,
+    value: FlagValue,
+    description: &'static str,
+}
+
+/// Every flag of a run or subcommand, each name once
+fn completion_flags() -> Vec<CompletionFlag> {
+    let mut flags: Vec<CompletionFlag> = Vec::new();
+    let specs = FLAGS.iter().chain(SUBCOMMANDS.iter().flat_map(|command| command.flags.iter()));
+    for spec in specs.filter(|spec| !spec.hidden) {
+        for name in std::iter::once(spec.name).chain(spec.short) {
+            if flags.iter().all(|flag| flag.name != name) {
+                flags.push(CompletionFlag { name, value: spec.value, description: spec.help });
+            }
+        }
+    }
+    flags
 }
 
+/// Name of the executable the completion scripts are registered for
+const COMMAND: &str = "FontSrt";
+
+/// A completion script for `shell` covering every flag, the values of flags
+/// with a fixed set of them (such as the built-in `--pattern` names) and the
+/// subcommands
+pub fn generate_completion(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash_completion(),
+        Shell::Zsh => zsh_completion(),
+        Shell::Fish => fish_completion(),
+        Shell::PowerShell => powershell_completion(),
+    }
+}
+
+fn bash_completion() -> String {
+    let flags_of = |wanted: fn(FlagValue) -> bool| -> String {
+        completion_flags().iter().filter(|flag| wanted(flag.value)).map(|flag| flag.name).collect::<Vec<_>>().join("|")
+    };
+
+    let mut cases = String::new();
+    for flag in &completion_flags() {
+        if let FlagValue::Choices(choices) = flag.value {
+            cases.push_str(&format!(
+                "        {})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            return ;;\n",
+                flag.name, choices.join(" ")
+            ));
+        }
+    }
+    cases.push_str(&format!(
+        "        {})\n            COMPREPLY=($(compgen -f -- \"$cur\"))\n            return ;;\n",
+        flags_of(|value| matches!(value, FlagValue::File))
+    ));
+    cases.push_str(&format!(
+        "        {})\n            COMPREPLY=($(compgen -d -- \"$cur\"))\n            return ;;\n",
+        flags_of(|value| matches!(value, FlagValue::Directory | FlagValue::DirectoryPair))
+    ));
+    cases.push_str(&format!("        {})\n            return ;;\n", flags_of(|value| matches!(value, FlagValue::Text))));
+
+    let flags: Vec<&str> = completion_flags().iter().map(|flag| flag.name).collect();
+    let subcommands: Vec<&str> = SUBCOMMANDS.iter().map(|command| command.name).collect();
+
+    format!(
+        r#"# bash completion for {command}
+_fontsrt() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+{cases}    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{flags}" -- "$cur"))
+    elif [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W "{subcommands}" -- "$cur") $(compgen -d -- "$cur"))
+    else
+        COMPREPLY=($(compgen -d -- "$cur"))
+    fi
+}}
+complete -o filenames -F _fontsrt {command}
+"#,
+        command = COMMAND,
+        cases = cases,
+        flags = flags.join(" "),
+        subcommands = subcommands.join(" "),
+    )
+}
+
+fn zsh_completion() -> String {
+    // Brackets and colons end a description in an _arguments spec
+    let describe = |text: &str| text.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:");
+
+    let mut specs = String::new();
+    for flag in &completion_flags() {
+        // --exclude may be given several times
+        let repeat = if flag.name == "--exclude" { "*" } else { "" };
+        let value = match flag.value {
+            FlagValue::Switch => String::new(),
+            FlagValue::File => ":file:_files".to_string(),
+            FlagValue::Directory => ":directory:_files -/".to_string(),
+            FlagValue::DirectoryPair => ":before:_files -/:after:_files -/".to_string(),
+            FlagValue::Text => ":value: ".to_string(),
+            FlagValue::Choices(choices) => format!(":value:({})", choices.join(" ")),
+        };
+        specs.push_str(&format!("    '{}{}[{}]{}' \\\n", repeat, flag.name, describe(flag.description), value));
+    }
+
+    let subcommands: String = SUBCOMMANDS.iter()
+        .map(|command| format!("    '{}:{}'\n", command.name, describe(command.summary)))
+        .collect();
+
+    format!(
+        r#"#compdef {command}
+
+_fontsrt_first() {{
+  local -a subcommands
+  subcommands=(
+{subcommands}  )
+  _describe -t subcommands subcommand subcommands
+  _files -/
+}}
+
+_fontsrt() {{
+  _arguments -s \
+{specs}    '1: :_fontsrt_first' \
+    '*:directory:_files -/'
+}}
+
+_fontsrt "$@"
+"#,
+        command = COMMAND,
+        subcommands = subcommands,
+        specs = specs,
+    )
+}
+
+fn fish_completion() -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"));
+
+    let mut script = format!("# fish completion for {}\ncomplete -c {} -f\n", COMMAND, COMMAND);
+    for command in SUBCOMMANDS {
+        script.push_str(&format!(
+            "complete -c {} -n __fish_use_subcommand -a {} -d {}\n",
+            COMMAND, command.name, quote(command.summary)
+        ));
+    }
+    script.push_str(&format!("complete -c {} -a '(__fish_complete_directories)'\n", COMMAND));
+
+    for flag in &completion_flags() {
+        // -vv is an old-style option to fish, as it is not one letter
+        let option = match flag.name.strip_prefix("--") {
+            Some(long) => format!("-l {}", long),
+            None if flag.name.len() == 2 => format!("-s {}", &flag.name[1..]),
+            None => format!("-o {}", &flag.name[1..]),
+        };
+        let value = match flag.value {
+            FlagValue::Switch => String::new(),
+            FlagValue::File => " -r -F".to_string(),
+            FlagValue::Directory | FlagValue::DirectoryPair => " -x -a '(__fish_complete_directories)'".to_string(),
+            FlagValue::Text => " -x".to_string(),
+            FlagValue::Choices(choices) => format!(" -x -a {}", quote(&choices.join(" "))),
+        };
+        script.push_str(&format!("complete -c {} {}{} -d {}\n", COMMAND, option, value, quote(flag.description)));
+    }
+    script
+}
+
+fn powershell_completion() -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+
+    // Commas keep the pairs from being flattened into one list
+    let pairs = |pairs: Vec<(&str, &str)>| -> String {
+        pairs.iter()
+            .map(|(name, description)| format!("        @({}, {})", quote(name), quote(description)))
+            .collect::<Vec<_>>()
+            .join(",\n")
+    };
+    let flags = pairs(completion_flags().iter().map(|flag| (flag.name, flag.description)).collect());
+    let values: String = completion_flags().iter()
+        .filter_map(|flag| match flag.value {
+            FlagValue::Choices(choices) => Some(format!(
+                "        {} = @({})\n",
+                quote(flag.name),
+                choices.iter().map(|choice| quote(choice)).collect::<Vec<_>>().join(", ")
+            )),
+            _ => None,
+        })
+        .collect();
+    let subcommands = pairs(SUBCOMMANDS.iter().map(|command| (command.name, command.summary)).collect());
+
+    format!(
+        r#"# PowerShell completion for {command}
+Register-ArgumentCompleter -Native -CommandName {command} -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+
+    $flags = @(
+{flags}
+    )
+    $values = @{{
+{values}    }}
+    $subcommands = @(
+{subcommands}
+    )
+
+    $words = @($commandAst.CommandElements | ForEach-Object {{ $_.ToString() }})
+    $previous = if ($wordToComplete) {{ $words[-2] }} else {{ $words[-1] }}
+
+    if ($values.ContainsKey($previous)) {{
+        $values[$previous] | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+            [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+        }}
+        return
+    }}
+    if ($wordToComplete -like '-*') {{
+        $flags | Where-Object {{ $_[0] -like "$wordToComplete*" }} | ForEach-Object {{
+            [System.Management.Automation.CompletionResult]::new($_[0], $_[0], 'ParameterName', $_[1])
+        }}
+        return
+    }}
+    if ($words.Count -le 2) {{
+        $subcommands | Where-Object {{ $_[0] -like "$wordToComplete*" }} | ForEach-Object {{
+            [System.Management.Automation.CompletionResult]::new($_[0], $_[0], 'Command', $_[1])
+        }}
+    }}
+}}
+"#,
+        command = COMMAND,
+        flags = flags,
+        values = values,
+        subcommands = subcommands,
+    )
+}
@@ -1,22 +1,14 @@
 use std::path::{Path, PathBuf};
 use std::io::{self, Write};
 use crate::error::{Result, Error};
-use crate::models::Config;
+use crate::models::{Config, MergeDecision, ProposedMerge};
 use crate::utils::log;
 
-/// Get the input directory from command line args or user input
-pub fn get_user_input(config: &Config) -> Result<PathBuf> {
-    let args: Vec<String> = std::env::args().collect();
-
-    // Check if path is provided as command-line argument
-    for i in 1..args.len() {
-        if !args[i].starts_with("--") {
-            let path = Path::new(&args[i]).to_path_buf();
-            if path.is_dir() {
-                log(config, format!("Using directory from command line: {}", path.display()));
-                return Ok(path);
-            }
-        }
+/// Get the input directory from the command line, or ask for it
+pub fn get_user_input(config: &Config, directory: Option<&Path>) -> Result<PathBuf> {
+    if let Some(path) = directory.filter(|path| path.is_dir()) {
+        log(config, format!("Using directory from command line: {}", path.display()));
+        return Ok(path.to_path_buf());
     }
 
     // Otherwise ask for input
@@ -40,7 +32,8 @@ pub fn get_user_choice() -> Result<String> {
     println!("What would you like to do?");
     println!("1. Sort fonts (organize by family)");
     println!("2. Group font folders by foundry");
-    print!("Enter your choice (1 or 2): ");
+    println!("3. List fonts as CSV without moving anything");
+    print!("Enter your choice (1, 2 or 3): ");
     io::stdout().flush()?;
 
     let mut choice = String::new();
@@ -58,3 +51,63 @@ pub fn ask_group_by_foundry() -> Result<bool> {
     Ok(input.trim().to_lowercase() == "y")
 }
 
+
+/// Ask whether to apply a proposed family merge (`--review-merges`), until
+/// the answer is one of y, n, a(ll) or q. "a" accepts every remaining merge.
+/// End of input counts as q.
+pub fn ask_merge_review(proposal: &ProposedMerge) -> Result<MergeDecision> {
+    loop {
+        print!(
+            "Merge \"{}\" ({} fonts) into \"{}\" ({} fonts)? [y/n/a(ll)/q]: ",
+            proposal.family, proposal.fonts, proposal.merged_into, proposal.merged_into_fonts
+        );
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(MergeDecision::Quit);
+        }
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(MergeDecision::Accept),
+            "n" | "no" => return Ok(MergeDecision::Reject),
+            "a" | "all" => return Ok(MergeDecision::AcceptAll),
+            "q" | "quit" => return Ok(MergeDecision::Quit),
+            _ => println!("Please answer y, n, a or q."),
+        }
+    }
+}
+
+/// Ask how to resolve merging `family1` into `family2` (`--interactive-merge`),
+/// until the answer is one of y(es), n(o), a(lways), ne(v)er, m(erge all),
+/// s(kip all) or q(uit). End of input, or a terminal that cannot be read,
+/// counts as skip all, merging nothing more.
+pub fn ask_merge_decision(family1: &str, family2: &str) -> MergeDecision {
+    loop {
+        print!("Merge \"{}\" into \"{}\"? [y]es/[n]o/[a]lways/ne[v]er/[m]erge all/[s]kip all/[q]uit: ", family1, family2);
+        let mut input = String::new();
+        match io::stdout().flush().and_then(|_| io::stdin().read_line(&mut input)) {
+            Ok(0) | Err(_) => return MergeDecision::RejectAll,
+            Ok(_) => {}
+        }
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => return MergeDecision::Accept,
+            "n" | "no" => return MergeDecision::Reject,
+            "a" | "always" => return MergeDecision::Always,
+            "v" | "never" => return MergeDecision::Never,
+            "m" | "merge all" => return MergeDecision::AcceptAll,
+            "s" | "skip" | "skip all" => return MergeDecision::RejectAll,
+            "q" | "quit" => return MergeDecision::Quit,
+            _ => println!("Please answer y, n, a, v, m, s or q."),
+        }
+    }
+}
+
+/// Ask whether to apply a previewed migration. End of input counts as no.
+pub fn ask_apply_migration(moves: usize) -> Result<bool> {
+    print!("Apply the migration ({} moves)? (y/n): ", moves);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -2,7 +2,9 @@
 
 mod args;
 mod interaction;
+mod progress;
 
-pub use args::{parse_args, get_help_message};
-pub use interaction::{get_user_input, get_user_choice, ask_group_by_foundry};
+pub use args::{CliOptions, get_help_message, generate_completion, Shell};
+pub use progress::ProgressDisplay;
+pub use interaction::{get_user_input, get_user_choice, ask_group_by_foundry, ask_merge_decision, ask_merge_review, ask_apply_migration};
 
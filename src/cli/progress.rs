@@ -0,0 +1,232 @@
+//! Progress bar with rate and ETA for long organize runs
+//!
+//! Driven by `ProgressEvent`s: a bar on stderr when it is a terminal, or
+//! `[PROGRESS]` lines at every tenth of a phase with `-v`, so the progress
+//! never overwrites debug output. `--quiet` shows neither.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::models::ProgressEvent;
+use crate::utils::LogLevel;
+
+const BAR_WIDTH: usize = 24;
+
+/// Bars are redrawn at most this often
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    Scanning,
+    Moving,
+}
+
+impl Phase {
+    fn label(self) -> &'static str {
+        match self {
+            Phase::Scanning => "Scanning metadata",
+            Phase::Moving => "Moving files",
+        }
+    }
+}
+
+/// Phase of one directory being organized
+struct DirectoryProgress {
+    dir: PathBuf,
+    phase: Phase,
+    done: usize,
+    total: usize,
+    started: Instant,
+    /// Last tenth printed in line mode
+    last_tenth: usize,
+}
+
+#[derive(Default)]
+struct State {
+    /// Directories finished and in total, in batch mode
+    batch: Option<(usize, usize)>,
+    /// Directories being organized; several at once in a parallel batch
+    running: Vec<DirectoryProgress>,
+    /// Index in `running` of the directory shown on the bar
+    shown: usize,
+    last_draw: Option<Instant>,
+    /// Whether the bar is on screen and must be cleared before other output
+    drawn: bool,
+}
+
+/// Shows the scanning and moving phases of organize runs, with the
+/// directories of a batch run as an outer count
+pub struct ProgressDisplay {
+    state: Mutex<State>,
+    /// Print `[PROGRESS]` lines instead of redrawing a bar
+    lines: bool,
+    enabled: bool,
+}
+
+impl ProgressDisplay {
+    /// A bar on stderr when it is a terminal, progress lines from the
+    /// `Debug` verbosity up and nothing below `Info`
+    pub fn new(verbosity: LogLevel) -> Self {
+        let lines = verbosity >= LogLevel::Debug;
+        ProgressDisplay {
+            state: Mutex::new(State::default()),
+            lines,
+            enabled: verbosity >= LogLevel::Info && (lines || io::stderr().is_terminal()),
+        }
+    }
+
+    /// Update the display with an event
+    pub fn handle(&self, event: &ProgressEvent) {
+        if !self.enabled {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+
+        match event {
+            ProgressEvent::BatchStarted { directories } => state.batch = Some((0, *directories)),
+            ProgressEvent::ScanStarted { dir, total } => self.start(&mut state, dir, Phase::Scanning, *total),
+            ProgressEvent::MoveStarted { dir, total } => self.start(&mut state, dir, Phase::Moving, *total),
+            ProgressEvent::ScanningFile(path) => self.advance(&mut state, path, Phase::Scanning),
+            ProgressEvent::MovingFile { from, .. } => self.advance(&mut state, from, Phase::Moving),
+            ProgressEvent::DirectoryFinished(dir) => {
+                state.running.retain(|progress| progress.dir != *dir);
+                state.shown = 0;
+                if let Some((finished, _)) = &mut state.batch {
+                    *finished += 1;
+                }
+                self.draw(&mut state, true);
+            }
+            ProgressEvent::Complete(_) => {
+                state.running.clear();
+                self.clear(&mut state);
+            }
+            _ => {}
+        }
+    }
+
+    fn clear(&self, state: &mut State) {
+        if state.drawn {
+            eprint!("\r\x1b[2K");
+            let _ = io::stderr().flush();
+            state.drawn = false;
+        }
+    }
+
+    fn start(&self, state: &mut State, dir: &Path, phase: Phase, total: usize) {
+        let progress = DirectoryProgress {
+            dir: dir.to_path_buf(),
+            phase,
+            done: 0,
+            total,
+            started: Instant::now(),
+            last_tenth: 0,
+        };
+        match state.running.iter().position(|running| running.dir == dir) {
+            Some(index) => {
+                state.running[index] = progress;
+                state.shown = index;
+            }
+            None => {
+                state.running.push(progress);
+                state.shown = state.running.len() - 1;
+            }
+        }
+        self.draw(state, true);
+    }
+
+    /// Count a file of the directory it belongs to, the deepest one when
+    /// batch directories are nested
+    fn advance(&self, state: &mut State, path: &Path, phase: Phase) {
+        let Some(index) = state.running.iter()
+            .enumerate()
+            .filter(|(_, running)| running.phase == phase && path.starts_with(&running.dir))
+            .max_by_key(|(_, running)| running.dir.components().count())
+            .map(|(index, _)| index)
+        else {
+            return;
+        };
+
+        let progress = &mut state.running[index];
+        progress.done = (progress.done + 1).min(progress.total);
+        let complete = progress.done == progress.total;
+        state.shown = index;
+
+        // A finished phase leaves the screen to prompts between phases, such
+        // as merge reviews
+        if complete && !self.lines && state.running.len() == 1 {
+            self.clear(state);
+        } else {
+            self.draw(state, complete);
+        }
+    }
+
+    fn draw(&self, state: &mut State, force: bool) {
+        let Some(progress) = state.running.get(state.shown) else {
+            if !self.lines {
+                self.clear(state);
+            }
+            return;
+        };
+
+        if self.lines {
+            let tenth = (progress.done * 10).checked_div(progress.total).unwrap_or(10);
+            if progress.done > 0 && tenth == progress.last_tenth {
+                return;
+            }
+            let line = format!("[PROGRESS] {}", status_line(state.batch, progress, false));
+            state.running[state.shown].last_tenth = tenth;
+            println!("{}", line);
+            return;
+        }
+
+        let now = Instant::now();
+        if !force && state.last_draw.is_some_and(|last| now - last < REDRAW_INTERVAL) {
+            return;
+        }
+        eprint!("\r\x1b[2K{}", status_line(state.batch, progress, true));
+        let _ = io::stderr().flush();
+        state.last_draw = Some(now);
+        state.drawn = true;
+    }
+}
+
+/// "[2/5 dirs] Moving files [#####-----] 120/400 35/s ETA 0:08 (fonts/inbox)"
+fn status_line(batch: Option<(usize, usize)>, progress: &DirectoryProgress, bar: bool) -> String {
+    let mut line = String::new();
+    if let Some((finished, total)) = batch {
+        line.push_str(&format!("[{}/{} dirs] ", finished, total));
+    }
+    line.push_str(progress.phase.label());
+
+    if bar {
+        let filled = (progress.done * BAR_WIDTH).checked_div(progress.total).unwrap_or(BAR_WIDTH);
+        line.push_str(&format!(" [{}{}]", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled)));
+    } else {
+        let percent = (progress.done * 100).checked_div(progress.total).unwrap_or(100);
+        line.push_str(&format!(" {}%", percent));
+    }
+    line.push_str(&format!(" {}/{}", progress.done, progress.total));
+
+    let elapsed = progress.started.elapsed().as_secs_f64();
+    if progress.done > 0 && elapsed > 0.0 {
+        let rate = progress.done as f64 / elapsed;
+        let remaining = (progress.total - progress.done) as f64 / rate;
+        line.push_str(&format!(" {:.0}/s ETA {}", rate, format_eta(remaining as u64)));
+    }
+
+    if batch.is_some() {
+        line.push_str(&format!(" ({})", progress.dir.display()));
+    }
+    line
+}
+
+/// "0:42", "12:05" or "1:02:03"
+fn format_eta(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
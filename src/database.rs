@@ -0,0 +1,331 @@
+//! Per-library font index in a SQLite file (`<output_dir>/.fontsrt.db`).
+//!
+//! Each row holds the metadata of one font with its content hash, the
+//! modification time it was parsed at and where the organizer put it. Fonts
+//! whose modification time is unchanged are not parsed again; the index is a
+//! cache and `--rebuild-db` parses everything afresh.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use rayon::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::types::Value as SqlValue;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use crate::error::{Error, Result};
+use crate::font::cache::{cache_settings, CACHE_FORMAT};
+use crate::font::metadata::{extract_font_metadata, FONT_EXTENSIONS};
+use crate::models::{Config, FontMetadata};
+use crate::utils::{content_hash, log};
+
+/// File name of the index in the output directory. SQLite keeps its journal
+/// next to it under the same name with a suffix.
+pub const DATABASE_FILE: &str = ".fontsrt.db";
+
+/// Version of the table layout, bumped when columns change
+const SCHEMA_VERSION: u64 = 2;
+
+/// How a `FontMetadata` field is stored
+#[derive(Clone, Copy)]
+enum Column {
+    Text,
+    Integer,
+    Bool,
+    /// Lists and structures, as JSON text
+    Json,
+}
+
+/// Metadata fields with a column each, in table order. `original_path` is the
+/// `path` key of the row.
+const METADATA_COLUMNS: [(&str, Column); 21] = [
+    ("family_name", Column::Text),
+    ("legacy_family_name", Column::Text),
+    ("subfamily", Column::Text),
+    ("full_name", Column::Text),
+    ("foundry", Column::Text),
+    ("designer", Column::Text),
+    ("weight", Column::Integer),
+    ("width", Column::Integer),
+    ("version", Column::Text),
+    ("is_italic", Column::Bool),
+    ("slant", Column::Text),
+    ("style_class", Column::Text),
+    ("classification", Column::Text),
+    ("scripts", Column::Json),
+    ("format", Column::Text),
+    ("technology", Column::Text),
+    ("license", Column::Text),
+    ("is_variable", Column::Bool),
+    ("axes", Column::Json),
+    ("named_instances", Column::Json),
+    ("source", Column::Text),
+];
+
+/// Whether a file is the index or one of its SQLite side files, which the
+/// organizer never treats as fonts
+pub fn is_database_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(DATABASE_FILE))
+}
+
+/// Outcome of `FontDatabase::update_for_directory`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IndexUpdate {
+    /// Fonts in the directory found unchanged in the index
+    pub unchanged: usize,
+    /// Fonts parsed because they were new, changed or rebuilt
+    pub parsed: usize,
+    /// Rows dropped because their file is gone
+    pub removed: usize,
+}
+
+/// Modification time in nanoseconds and size of a file
+fn file_stamp(path: &Path) -> Option<(i64, i64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((i64::try_from(mtime.as_nanos()).ok()?, i64::try_from(metadata.len()).ok()?))
+}
+
+fn database_error(e: rusqlite::Error) -> Error {
+    Error::Database(e.to_string())
+}
+
+/// SQLite-backed index of the fonts of one library
+pub struct FontDatabase {
+    path: PathBuf,
+    connection: Mutex<Connection>,
+    /// Parse every font again, replacing its row (`--rebuild-db`)
+    rebuild: bool,
+}
+
+impl FontDatabase {
+    /// Location of the index of the library at `output_root`
+    pub fn default_path(output_root: &Path) -> PathBuf {
+        output_root.join(DATABASE_FILE)
+    }
+
+    /// Open or create the index at `path`. An index written by another schema,
+    /// metadata format or under other settings (see `cache_settings`) is
+    /// emptied, since its metadata may no longer be what parsing gives.
+    pub fn open(path: &Path, config: &Config) -> Result<Self> {
+        let connection = Connection::open(path).map_err(database_error)?;
+        // The index is rebuilt from the fonts when lost, so durability is traded for speed
+        connection.execute_batch("PRAGMA synchronous = OFF;").map_err(database_error)?;
+
+        let stamp = format!("{}:{}:{}", SCHEMA_VERSION, CACHE_FORMAT, cache_settings(config));
+        connection.execute("CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)", [])
+            .map_err(database_error)?;
+        let recorded: Option<String> = connection
+            .query_row("SELECT value FROM settings WHERE key = 'stamp'", [], |row| row.get(0))
+            .optional()
+            .map_err(database_error)?;
+        if recorded.as_deref() != Some(stamp.as_str()) {
+            connection.execute("DROP TABLE IF EXISTS fonts", []).map_err(database_error)?;
+        }
+
+        let columns = METADATA_COLUMNS.iter()
+            .map(|(name, column)| format!("{} {}", name, match column {
+                Column::Integer | Column::Bool => "INTEGER",
+                Column::Text | Column::Json => "TEXT",
+            }))
+            .collect::<Vec<_>>()
+            .join(", ");
+        connection.execute(&format!(
+            "CREATE TABLE IF NOT EXISTS fonts (path TEXT PRIMARY KEY, {}, file_hash TEXT NOT NULL, \
+             last_modified INTEGER NOT NULL, size INTEGER NOT NULL, organized_path TEXT)",
+            columns
+        ), []).map_err(database_error)?;
+        connection.execute("INSERT OR REPLACE INTO settings (key, value) VALUES ('stamp', ?1)", [&stamp])
+            .map_err(database_error)?;
+
+        Ok(Self { path: path.to_path_buf(), connection: Mutex::new(connection), rebuild: false })
+    }
+
+    /// Parse every font again on the next `update_for_directory`, replacing its row
+    pub fn rebuilding(mut self) -> Self {
+        self.rebuild = true;
+        self
+    }
+
+    /// Path of the index file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of fonts in the index
+    pub fn len(&self) -> usize {
+        self.connection.lock().unwrap()
+            .query_row("SELECT COUNT(*) FROM fonts", [], |row| row.get::<_, i64>(0))
+            .map(|count| count as usize)
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Indexed metadata of a font, if its modification time and size are
+    /// unchanged since it was parsed
+    pub fn get(&self, path: &Path) -> Option<FontMetadata> {
+        let key = std::path::absolute(path).ok()?;
+        let (last_modified, size) = file_stamp(path)?;
+
+        let names = METADATA_COLUMNS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+        let connection = self.connection.lock().unwrap();
+        let values: Vec<SqlValue> = connection.query_row(
+            &format!("SELECT {} FROM fonts WHERE path = ?1 AND last_modified = ?2 AND size = ?3", names),
+            params![key.to_string_lossy(), last_modified, size],
+            |row| (0..METADATA_COLUMNS.len()).map(|index| row.get(index)).collect(),
+        ).optional().ok()??;
+
+        let mut fields = Map::new();
+        for ((name, column), value) in METADATA_COLUMNS.iter().zip(values) {
+            let field = match (column, value) {
+                (_, SqlValue::Null) => Value::Null,
+                (Column::Bool, SqlValue::Integer(flag)) => Value::Bool(flag != 0),
+                (Column::Integer, SqlValue::Integer(number)) => Value::from(number),
+                (Column::Json, SqlValue::Text(json)) => serde_json::from_str(&json).ok()?,
+                (Column::Text, SqlValue::Text(text)) => Value::String(text),
+                _ => return None,
+            };
+            fields.insert(name.to_string(), field);
+        }
+        fields.insert("original_path".to_string(), Value::String(path.to_string_lossy().into_owned()));
+        FontMetadata::deserialize(Value::Object(fields)).ok()
+    }
+
+    /// Record freshly parsed metadata of a font, replacing its row
+    pub fn insert(&self, path: &Path, metadata: &FontMetadata) -> Result<()> {
+        let connection = self.connection.lock().unwrap();
+        insert_row(&connection, path, metadata)
+    }
+
+    /// Follow a font the organizer moved; the row remembers the new path as
+    /// where the font was organized to. Moves keep size and modification
+    /// time, so the row stays valid.
+    pub fn relocate(&self, from: &Path, to: &Path) {
+        let (Ok(from), Ok(to)) = (std::path::absolute(from), std::path::absolute(to)) else {
+            return;
+        };
+        let to = to.to_string_lossy();
+        let _ = self.connection.lock().unwrap().execute(
+            "UPDATE OR REPLACE fonts SET path = ?2, organized_path = ?2 WHERE path = ?1",
+            params![from.to_string_lossy(), to],
+        );
+    }
+
+    /// Follow a folder that was moved, updating the rows of every font below it
+    pub fn relocate_tree(&self, from: &Path, to: &Path) {
+        let (Ok(from), Ok(to)) = (std::path::absolute(from), std::path::absolute(to)) else {
+            return;
+        };
+        let connection = self.connection.lock().unwrap();
+        let Ok(mut statement) = connection.prepare("SELECT path FROM fonts") else { return };
+        let Ok(rows) = statement.query_map([], |row| row.get::<_, String>(0)) else { return };
+        let moved: Vec<PathBuf> = rows.flatten().map(PathBuf::from).filter(|path| path.starts_with(&from)).collect();
+        for path in moved {
+            if let Ok(relative) = path.strip_prefix(&from) {
+                let _ = connection.execute(
+                    "UPDATE OR REPLACE fonts SET path = ?2, organized_path = ?2 WHERE path = ?1",
+                    params![path.to_string_lossy(), to.join(relative).to_string_lossy()],
+                );
+            }
+        }
+    }
+
+    /// Bring the rows of the fonts below `dir` up to date: fonts whose
+    /// modification time or size changed, and new fonts, are parsed again (all
+    /// of them when rebuilding), and rows of files that are gone are dropped.
+    /// Unchanged fonts are not read at all.
+    pub fn update_for_directory(&self, dir: &Path, config: &Config) -> Result<IndexUpdate> {
+        let mut fonts = Vec::new();
+        collect_font_files(dir, &mut fonts);
+
+        let stale: Vec<PathBuf> = fonts.iter().filter(|path| self.rebuild || self.get(path).is_none()).cloned().collect();
+        let parsed: Vec<(PathBuf, FontMetadata)> = stale.par_iter()
+            .filter_map(|path| match extract_font_metadata(path, config) {
+                Ok(Some(metadata)) => Some((path.clone(), metadata)),
+                _ => None,
+            })
+            .collect();
+
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(database_error)?;
+        for (path, metadata) in &parsed {
+            insert_row(&transaction, path, metadata)?;
+        }
+
+        let root = std::path::absolute(dir)?;
+        let indexed: Vec<String> = {
+            let mut statement = transaction.prepare("SELECT path FROM fonts").map_err(database_error)?;
+            let rows = statement.query_map([], |row| row.get::<_, String>(0)).map_err(database_error)?;
+            rows.flatten().collect()
+        };
+        let mut removed = 0;
+        for path in indexed.iter().filter(|path| Path::new(path).starts_with(&root) && !Path::new(path).is_file()) {
+            removed += transaction.execute("DELETE FROM fonts WHERE path = ?1", [path]).map_err(database_error)?;
+        }
+        transaction.commit().map_err(database_error)?;
+
+        let update = IndexUpdate { unchanged: fonts.len() - stale.len(), parsed: parsed.len(), removed };
+        log(config, format!(
+            "Font database {}: {} fonts unchanged, {} parsed, {} removed",
+            self.path.display(), update.unchanged, update.parsed, update.removed
+        ));
+        Ok(update)
+    }
+}
+
+/// Write the row of a font
+fn insert_row(connection: &Connection, path: &Path, metadata: &FontMetadata) -> Result<()> {
+    let key = std::path::absolute(path)?;
+    let (last_modified, size) = file_stamp(path).ok_or_else(|| Error::InvalidPath(path.to_path_buf()))?;
+    let file_hash = content_hash(path)?.to_hex().to_string();
+
+    let fields = serde_json::to_value(metadata).map_err(|e| Error::Metadata(e.to_string()))?;
+    let mut values: Vec<SqlValue> = vec![SqlValue::Text(key.to_string_lossy().into_owned())];
+    for (name, column) in METADATA_COLUMNS {
+        let field = fields.get(name).cloned().unwrap_or(Value::Null);
+        values.push(match (column, field) {
+            (_, Value::Null) => SqlValue::Null,
+            (Column::Bool, Value::Bool(flag)) => SqlValue::Integer(flag as i64),
+            (Column::Integer, Value::Number(number)) => number.as_i64().map(SqlValue::Integer).unwrap_or(SqlValue::Null),
+            (Column::Text, Value::String(text)) => SqlValue::Text(text),
+            (_, field) => SqlValue::Text(field.to_string()),
+        });
+    }
+    values.extend([SqlValue::Text(file_hash), SqlValue::Integer(last_modified), SqlValue::Integer(size)]);
+
+    let names = METADATA_COLUMNS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+    let placeholders = (1..=values.len()).map(|index| format!("?{}", index)).collect::<Vec<_>>().join(", ");
+    // A re-parsed font keeps the organized path of its row
+    connection.execute(
+        &format!(
+            "INSERT INTO fonts (path, {}, file_hash, last_modified, size) VALUES ({}) \
+             ON CONFLICT(path) DO UPDATE SET ({}, file_hash, last_modified, size) = ({})",
+            names,
+            placeholders,
+            names,
+            (2..=values.len()).map(|index| format!("?{}", index)).collect::<Vec<_>>().join(", ")
+        ),
+        rusqlite::params_from_iter(values),
+    ).map_err(database_error)?;
+    Ok(())
+}
+
+/// Font files below `dir`, skipping hidden folders
+fn collect_font_files(dir: &Path, fonts: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            if !path.file_name().unwrap_or_default().to_string_lossy().starts_with('.') {
+                collect_font_files(&path, fonts);
+            }
+        } else if !is_database_file(&path)
+            && path.extension().is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        {
+            fonts.push(path);
+        }
+    }
+}
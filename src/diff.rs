@@ -0,0 +1,182 @@
+//! `FontSrt --diff <BEFORE> <AFTER>`: what changed between two versions of a
+//! font directory, e.g. a copy taken before a run and the organized result
+//!
+//! Fonts are matched by content hash, so a font that changed folder or name is
+//! reported as moved or renamed instead of as removed and added.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use crate::error::{Error, Result};
+use crate::font::metadata::FONT_EXTENSIONS;
+use crate::models::Config;
+use crate::utils::{content_hash, log_warn};
+
+/// Changes between two versions of a font directory. Paths are relative to
+/// the directory they are in, and each list is sorted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FontDiff {
+    /// Fonts only in the after directory
+    pub added: Vec<PathBuf>,
+    /// Fonts only in the before directory
+    pub removed: Vec<PathBuf>,
+    /// Fonts now in another folder, as (before, after)
+    pub moved: Vec<(PathBuf, PathBuf)>,
+    /// Fonts given another file name in the same folder, as (before, after)
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+    /// Fonts at the same path with the same content
+    pub unchanged: usize,
+}
+
+impl FontDiff {
+    /// Whether no font of the before directory went missing, so the change
+    /// only added, moved or renamed fonts
+    pub fn is_additive(&self) -> bool {
+        self.removed.is_empty()
+    }
+
+    /// Whether both directories hold the same fonts at the same paths
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty() && self.renamed.is_empty()
+    }
+}
+
+/// Compare the fonts of `before` with those of `after`, both searched
+/// recursively. A font whose content is at a new path counts as moved, or as
+/// renamed when it stayed in the same folder; a font at the same path with
+/// other content counts as removed and added.
+pub fn diff_directories(before: &Path, after: &Path, config: &Config) -> Result<FontDiff> {
+    for dir in [before, after] {
+        if !dir.is_dir() {
+            return Err(Error::InvalidPath(dir.to_path_buf()));
+        }
+    }
+    let before_hashes = hash_fonts(before, config);
+    let mut after_hashes = hash_fonts(after, config);
+
+    let mut diff = FontDiff::default();
+    let mut gone: Vec<(PathBuf, blake3::Hash)> = Vec::new();
+    for (path, hash) in before_hashes {
+        if after_hashes.get(&path) == Some(&hash) {
+            after_hashes.remove(&path);
+            diff.unchanged += 1;
+        } else {
+            gone.push((path, hash));
+        }
+    }
+
+    // Fonts that appeared, by content, in path order
+    let mut appeared: HashMap<blake3::Hash, Vec<PathBuf>> = HashMap::new();
+    for (path, hash) in after_hashes {
+        appeared.entry(hash).or_default().push(path);
+    }
+
+    for (path, hash) in gone {
+        let Some(candidates) = appeared.get_mut(&hash).filter(|candidates| !candidates.is_empty()) else {
+            diff.removed.push(path);
+            continue;
+        };
+        // Copies of one font are paired by file name where possible
+        let index = candidates.iter().position(|candidate| candidate.file_name() == path.file_name()).unwrap_or(0);
+        let target = candidates.remove(index);
+        if target.parent() == path.parent() {
+            diff.renamed.push((path, target));
+        } else {
+            diff.moved.push((path, target));
+        }
+    }
+
+    diff.added = appeared.into_values().flatten().collect();
+    diff.added.sort();
+    Ok(diff)
+}
+
+/// Content hash of every font under `dir`, keyed by its path relative to `dir`
+fn hash_fonts(dir: &Path, config: &Config) -> BTreeMap<PathBuf, blake3::Hash> {
+    let mut files = Vec::new();
+    collect_font_files(dir, &mut files);
+
+    files.par_iter()
+        .filter_map(|path| match content_hash(path) {
+            Ok(hash) => Some((path.strip_prefix(dir).unwrap_or(path).to_path_buf(), hash)),
+            Err(e) => {
+                log_warn(config, format!("Could not hash {}, leaving it out of the diff: {}", path.display(), e));
+                None
+            }
+        })
+        .collect()
+}
+
+/// Font files in `dir` and its subfolders
+fn collect_font_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_font_files(&path, files);
+        } else if path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Print the diff with a section per kind of change
+pub fn print_diff(diff: &FontDiff, before: &Path, after: &Path) {
+    println!("Fonts of {} compared with {}", before.display(), after.display());
+    if diff.is_empty() {
+        println!("No changes: {} fonts at the same paths", diff.unchanged);
+        return;
+    }
+
+    let section = |title: &str, count: usize| println!("{} ({}):", title, count);
+    if !diff.added.is_empty() {
+        section("Added", diff.added.len());
+        for path in &diff.added {
+            println!("    + {}", path.display());
+        }
+    }
+    if !diff.removed.is_empty() {
+        section("Removed", diff.removed.len());
+        for path in &diff.removed {
+            println!("    - {}", path.display());
+        }
+    }
+    if !diff.moved.is_empty() {
+        section("Moved", diff.moved.len());
+        for (from, to) in &diff.moved {
+            println!("    {} -> {}", from.display(), to.display());
+        }
+    }
+    if !diff.renamed.is_empty() {
+        section("Renamed", diff.renamed.len());
+        for (from, to) in &diff.renamed {
+            println!("    {} -> {}", from.display(), to.display());
+        }
+    }
+
+    println!(
+        "{} added, {} removed, {} moved, {} renamed, {} unchanged{}",
+        diff.added.len(), diff.removed.len(), diff.moved.len(), diff.renamed.len(), diff.unchanged,
+        if diff.is_additive() { " (no font went missing)" } else { "" }
+    );
+}
+
+/// The diff as JSON
+pub fn diff_json(diff: &FontDiff) -> Value {
+    let paths = |paths: &[PathBuf]| paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>();
+    let pairs = |pairs: &[(PathBuf, PathBuf)]| pairs.iter()
+        .map(|(from, to)| json!({ "from": from.display().to_string(), "to": to.display().to_string() }))
+        .collect::<Vec<_>>();
+    json!({
+        "added": paths(&diff.added),
+        "removed": paths(&diff.removed),
+        "moved": pairs(&diff.moved),
+        "renamed": pairs(&diff.renamed),
+        "unchanged": diff.unchanged,
+        "additive": diff.is_additive(),
+    })
+}
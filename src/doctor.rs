@@ -0,0 +1,420 @@
+//! `FontSrt doctor <DIR>`: environment and library checks for support requests
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use serde_json::{json, Value};
+use crate::font::metadata::extract_font_metadata;
+use crate::models::Config;
+use crate::organizer::collect_font_candidates;
+use crate::utils::{format_size, free_space};
+
+/// Free space below which the free space check warns
+const LOW_SPACE_BYTES: u64 = 500 << 20;
+
+/// Number of fonts parsed by the sample parse check
+const SAMPLE_FONTS: usize = 5;
+
+/// Folders that hold installed system or user fonts
+const SYSTEM_FONT_DIRS: [&str; 6] = [
+    "/usr/share/fonts",
+    "/usr/local/share/fonts",
+    "/System/Library/Fonts",
+    "/Library/Fonts",
+    "C:\\Windows\\Fonts",
+    ".local/share/fonts",
+];
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "pass"),
+            CheckStatus::Warn => write!(f, "warn"),
+            CheckStatus::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+/// One line of the doctor report
+#[derive(Debug, Clone)]
+pub struct Check {
+    /// Short name of what was checked
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// What was found
+    pub message: String,
+    /// What to do about a warning or failure
+    pub suggestion: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Pass, message: message.into(), suggestion: None }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, suggestion: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Warn, message: message.into(), suggestion: Some(suggestion.into()) }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, suggestion: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Fail, message: message.into(), suggestion: Some(suggestion.into()) }
+    }
+}
+
+/// Run every check against `dir`
+pub fn run_checks(dir: &Path, config: &Config) -> Vec<Check> {
+    vec![
+        check_binary(),
+        check_permissions(dir),
+        check_case_sensitivity(dir),
+        check_symlinks(dir),
+        check_name_length(dir),
+        check_free_space(dir),
+        check_system_folder(dir),
+        check_artifacts(dir, config),
+        check_sample_parse(dir, config),
+    ]
+}
+
+/// Whether any check failed
+pub fn has_failures(checks: &[Check]) -> bool {
+    checks.iter().any(|check| check.status == CheckStatus::Fail)
+}
+
+/// Print the checks as one pass/warn/fail line each, with suggestions below
+pub fn print_checks(checks: &[Check]) {
+    for check in checks {
+        println!("[{}] {}: {}", check.status.to_string().to_uppercase(), check.name, check.message);
+        if let Some(suggestion) = &check.suggestion {
+            println!("       -> {}", suggestion);
+        }
+    }
+}
+
+/// The checks as JSON for bug reports
+pub fn checks_json(checks: &[Check]) -> Value {
+    Value::Array(checks.iter()
+        .map(|check| json!({
+            "check": check.name,
+            "status": check.status.to_string(),
+            "message": check.message,
+            "suggestion": check.suggestion,
+        }))
+        .collect())
+}
+
+/// Version of the binary and the optional features compiled in
+fn check_binary() -> Check {
+    let features = if cfg!(feature = "serve") { "serve" } else { "none" };
+    Check::pass("binary", format!(
+        "FontSrt {} on {}, features: {}",
+        env!("CARGO_PKG_VERSION"), std::env::consts::OS, features
+    ))
+}
+
+/// The directory must be readable and writable for fonts to be moved
+fn check_permissions(dir: &Path) -> Check {
+    if let Err(e) = fs::read_dir(dir) {
+        return Check::fail("permissions", format!("cannot read {}: {}", dir.display(), e), "check the path and its permissions");
+    }
+
+    let probe = dir.join(".fontsrt-doctor-probe");
+    match fs::write(&probe, b"probe") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Check::pass("permissions", "directory is readable and writable")
+        }
+        Err(e) => Check::fail(
+            "permissions",
+            format!("cannot write to {}: {}", dir.display(), e),
+            "run as a user that owns the directory, or use --dry-run to preview",
+        ),
+    }
+}
+
+/// Case-insensitive file systems make "Roboto" and "ROBOTO" the same folder
+fn check_case_sensitivity(dir: &Path) -> Check {
+    let probe = dir.join(".fontsrt-Case-Probe");
+    if fs::write(&probe, b"probe").is_err() {
+        return Check::warn("case sensitivity", "could not probe, directory is not writable", "fix permissions first");
+    }
+    let insensitive = dir.join(".fontsrt-case-probe").exists();
+    let _ = fs::remove_file(&probe);
+
+    if insensitive {
+        Check::warn(
+            "case sensitivity",
+            "file system is case-insensitive",
+            "families differing only in case share a folder; review merges after organizing",
+        )
+    } else {
+        Check::pass("case sensitivity", "file system is case-sensitive")
+    }
+}
+
+/// Symlinks are followed as regular files when scanning
+fn check_symlinks(dir: &Path) -> Check {
+    let target = dir.join(".fontsrt-link-target");
+    let link = dir.join(".fontsrt-link-probe");
+    if fs::write(&target, b"probe").is_err() {
+        return Check::warn("symlinks", "could not probe, directory is not writable", "fix permissions first");
+    }
+
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(&target, &link);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_file(&target, &link);
+    #[cfg(not(any(unix, windows)))]
+    let linked: std::io::Result<()> = Err(std::io::Error::other("unsupported platform"));
+
+    let _ = fs::remove_file(&link);
+    let _ = fs::remove_file(&target);
+
+    match linked {
+        Ok(()) => Check::pass("symlinks", "symbolic links are supported"),
+        Err(e) => Check::warn(
+            "symlinks",
+            format!("symbolic links are not supported: {}", e),
+            "linked font files are copied rather than moved",
+        ),
+    }
+}
+
+/// Long family and style names need long file names
+fn check_name_length(dir: &Path) -> Check {
+    let probe = dir.join(format!(".fontsrt-{}", "n".repeat(240)));
+    match fs::write(&probe, b"probe") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Check::pass("path length", "file names of 250 characters are allowed")
+        }
+        Err(e) => Check::warn(
+            "path length",
+            format!("250 character file names are rejected: {}", e),
+            "use a shorter naming pattern such as family-weight",
+        ),
+    }
+}
+
+/// Moves across folders on the same drive need little space, but vaults and
+/// copy fallbacks need room for the largest fonts
+fn check_free_space(dir: &Path) -> Check {
+    match free_space(dir) {
+        Some(bytes) if bytes < LOW_SPACE_BYTES => Check::warn(
+            "free space",
+            format!("only {} free", format_size(bytes)),
+            "free up space before organizing into another drive or a vault",
+        ),
+        Some(bytes) => Check::pass("free space", format!("{} free", format_size(bytes))),
+        None => Check::warn("free space", "could not determine free space", "check the drive manually"),
+    }
+}
+
+/// Organizing an installed font folder renames fonts under the OS
+fn check_system_folder(dir: &Path) -> Check {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let text = dir.to_string_lossy().replace('\\', "/").to_lowercase();
+
+    let system = SYSTEM_FONT_DIRS.iter()
+        .map(|system_dir| system_dir.replace('\\', "/").to_lowercase())
+        .any(|system_dir| if system_dir.starts_with('/') || system_dir.contains(':') {
+            text.starts_with(&system_dir)
+        } else {
+            text.contains(&format!("/{}", system_dir))
+        });
+
+    if system {
+        Check::warn(
+            "system folder",
+            format!("{} looks like an installed font folder", dir.display()),
+            "copy the fonts elsewhere and organize the copy",
+        )
+    } else {
+        Check::pass("system folder", "not an installed font folder")
+    }
+}
+
+/// Files left by earlier runs: set-aside folders and the error report
+fn check_artifacts(dir: &Path, config: &Config) -> Check {
+    let error_report = dir.join("fontsrt-errors.json");
+    if error_report.is_file() {
+        let valid = fs::read_to_string(&error_report).ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+            .is_some_and(|report| report.is_array());
+        if !valid {
+            return Check::warn(
+                "artifacts",
+                format!("{} is not a valid error report", error_report.display()),
+                "delete it, the next --error-report run rewrites it",
+            );
+        }
+    }
+
+    let set_aside: Vec<PathBuf> = [
+        config.duplicates_path(dir),
+        config.set_aside_path(dir, "synthetic"),
+        config.set_aside_path(dir, "corrupt"),
+    ]
+    .into_iter()
+    .filter(|folder| folder.is_dir())
+    .collect();
+
+    if set_aside.is_empty() {
+        Check::pass("artifacts", "no files set aside by earlier runs")
+    } else {
+        let names: Vec<String> = set_aside.iter().map(|folder| folder.display().to_string()).collect();
+        Check::pass("artifacts", format!("set-aside folders from earlier runs: {}", names.join(", ")))
+    }
+}
+
+/// Parse a few fonts and time it, to catch unreadable collections early
+fn check_sample_parse(dir: &Path, config: &Config) -> Check {
+    let candidates = match collect_font_candidates(dir, config) {
+        Ok(candidates) => candidates,
+        Err(e) => return Check::fail("sample parse", format!("cannot list fonts: {}", e), "check the directory"),
+    };
+    let sample: Vec<&PathBuf> = candidates.iter()
+        .filter(|path| path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "ttf" | "otf")))
+        .take(SAMPLE_FONTS)
+        .collect();
+
+    if sample.is_empty() {
+        return Check::warn("sample parse", "no .ttf or .otf files at the top level", "point FontSrt at the folder holding the fonts, or add --recursive");
+    }
+
+    let start = Instant::now();
+    let parsed = sample.iter()
+        .filter(|path| matches!(extract_font_metadata(path, config), Ok(Some(_))))
+        .count();
+    let elapsed = start.elapsed();
+
+    let message = format!("parsed {} of {} sample fonts in {} ms", parsed, sample.len(), elapsed.as_millis());
+    if parsed == sample.len() {
+        Check::pass("sample parse", message)
+    } else {
+        Check::warn("sample parse", message, "run with --quarantine to set unreadable fonts aside")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TestFont};
+
+    /// Names of the files left in `dir`
+    fn leftovers(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn binary_reports_the_version() {
+        let check = check_binary();
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(check.message.contains(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn permissions() {
+        let dir = TempDir::new("doctor-permissions");
+        assert_eq!(check_permissions(dir.path()).status, CheckStatus::Pass);
+        assert_eq!(check_permissions(&dir.join("missing")).status, CheckStatus::Fail);
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn file_system_probes_clean_up() {
+        let dir = TempDir::new("doctor-probes");
+        for check in [check_case_sensitivity(dir.path()), check_symlinks(dir.path()), check_name_length(dir.path())] {
+            assert_ne!(check.status, CheckStatus::Fail, "{}", check.message);
+        }
+        #[cfg(unix)]
+        assert_eq!(check_symlinks(dir.path()).status, CheckStatus::Pass);
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn probes_warn_on_a_missing_directory() {
+        let dir = TempDir::new("doctor-missing");
+        let missing = dir.join("missing");
+        assert_eq!(check_case_sensitivity(&missing).status, CheckStatus::Warn);
+        assert_eq!(check_symlinks(&missing).status, CheckStatus::Warn);
+        assert_eq!(check_name_length(&missing).status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn free_space_is_known() {
+        let dir = TempDir::new("doctor-space");
+        let check = check_free_space(dir.path());
+        assert_ne!(check.status, CheckStatus::Fail);
+        assert!(check.message.contains("free"));
+    }
+
+    #[test]
+    fn system_folders() {
+        assert_eq!(check_system_folder(Path::new("/usr/share/fonts/truetype")).status, CheckStatus::Warn);
+        assert_eq!(check_system_folder(Path::new("/home/someone/.local/share/fonts")).status, CheckStatus::Warn);
+        assert_eq!(check_system_folder(Path::new("C:\\Windows\\Fonts")).status, CheckStatus::Warn);
+        let dir = TempDir::new("doctor-system");
+        assert_eq!(check_system_folder(dir.path()).status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn artifacts() {
+        let dir = TempDir::new("doctor-artifacts");
+        let config = Config::default();
+        let check = check_artifacts(dir.path(), &config);
+        assert_eq!((check.status, check.message.as_str()), (CheckStatus::Pass, "no files set aside by earlier runs"));
+
+        fs::create_dir(dir.join("duplicates")).unwrap();
+        assert!(check_artifacts(dir.path(), &config).message.contains("duplicates"));
+
+        fs::write(dir.join("fontsrt-errors.json"), "{ not json").unwrap();
+        assert_eq!(check_artifacts(dir.path(), &config).status, CheckStatus::Warn);
+        fs::write(dir.join("fontsrt-errors.json"), "[]").unwrap();
+        assert_eq!(check_artifacts(dir.path(), &config).status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn sample_parse() {
+        let dir = TempDir::new("doctor-sample");
+        let config = Config::default();
+        assert_eq!(check_sample_parse(dir.path(), &config).status, CheckStatus::Warn);
+
+        TestFont::new("Roboto", "Regular").write(&dir.join("Roboto-Regular.ttf"));
+        let check = check_sample_parse(dir.path(), &config);
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.message);
+        assert!(check.message.starts_with("parsed 1 of 1"));
+
+        fs::write(dir.join("Broken.otf"), b"not a font").unwrap();
+        assert_eq!(check_sample_parse(dir.path(), &config).status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn failures_and_json() {
+        let checks = vec![
+            Check::pass("binary", "ok"),
+            Check::warn("symlinks", "not supported", "copy instead"),
+        ];
+        assert!(!has_failures(&checks));
+        let json = checks_json(&checks);
+        assert_eq!(json[1]["status"], "warn");
+        assert_eq!(json[1]["suggestion"], "copy instead");
+        assert_eq!(json[0]["suggestion"], Value::Null);
+
+        let checks = vec![Check::fail("permissions", "cannot write", "fix permissions")];
+        assert!(has_failures(&checks));
+    }
+}
@@ -13,12 +13,19 @@ pub enum Error {
     InvalidPath(PathBuf),
     /// Configuration errors
     Config(String),
+    /// Move plan that breaks an invariant of the move phase
+    Plan(String),
+    /// Run stopped on the user's request
+    Cancelled(String),
+    /// Library locked by another run (`.fontsrt.lock`)
+    Locked(PathBuf),
     /// Batch processing errors
-    #[allow(dead_code)]
     Batch(String),
     /// Font metadata extraction errors
     #[allow(dead_code)]
     Metadata(String),
+    /// Font database (`.fontsrt.db`) errors
+    Database(String),
 }
 
 impl std::error::Error for Error {}
@@ -30,12 +37,51 @@ impl fmt::Display for Error {
             Error::Font(msg) => write!(f, "Font error: {}", msg),
             Error::InvalidPath(path) => write!(f, "Invalid path: {}", path.display()),
             Error::Config(msg) => write!(f, "Configuration error: {}", msg),
+            Error::Plan(msg) => write!(f, "Invalid organization plan: {}", msg),
+            Error::Cancelled(msg) => write!(f, "Cancelled: {}", msg),
+            Error::Locked(dir) => write!(f, "Another run is already changing {}", dir.display()),
             Error::Batch(msg) => write!(f, "Batch processing error: {}", msg),
             Error::Metadata(msg) => write!(f, "Metadata extraction error: {}", msg),
+            Error::Database(msg) => write!(f, "Font database error: {}", msg),
         }
     }
 }
 
+impl Error {
+    /// Exit status of the command-line tool failing with this error
+    pub fn exit_code(&self) -> i32 {
+        let code = match self {
+            Error::Io(err) if err.kind() == io::ErrorKind::StorageFull => ExitCode::DiskFull,
+            Error::Config(_) | Error::InvalidPath(_) => ExitCode::InvalidArguments,
+            _ => ExitCode::Failure,
+        };
+        code as i32
+    }
+}
+
+/// Exit status of the command-line tool, so scripts can tell failures apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    /// An IO or other error stopped the run
+    Failure = 1,
+    /// A flag, its value, a configuration file or a path is invalid
+    InvalidArguments = 2,
+    /// The directory holds no readable font
+    NoFonts = 3,
+    /// The run finished, but some files could not be organized
+    PartialFailure = 4,
+    /// The drive ran out of space, or would have (see `check_available_space`)
+    DiskFull = 5,
+}
+
+impl ExitCode {
+    /// Exit the process with this status
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
         Error::Io(err)
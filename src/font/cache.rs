@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::error::{Error, Result};
+use crate::models::{Config, FontMetadata};
+use crate::utils::log;
+use super::metadata::extract_font_metadata;
+
+/// Version of the cache file layout, bumped when entries change shape
+pub(crate) const CACHE_FORMAT: u64 = 9;
+
+/// Size and modification time a cache entry was recorded with. A file replaced
+/// in place almost always changes one of them, which invalidates the entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileStamp {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+        })
+    }
+}
+
+/// Font metadata of earlier runs, keyed by absolute path and checked against
+/// each file's size and modification time before it is used.
+///
+/// The cache is one JSON file shared by every library. It also records a
+/// fingerprint of the settings that shape metadata (foundry aliases and
+/// overrides); a cache written under other settings starts out empty.
+pub struct MetadataCache {
+    path: PathBuf,
+    settings: String,
+    entries: Mutex<HashMap<PathBuf, (FileStamp, FontMetadata)>>,
+    /// Parse every font again, replacing the entries it finds (`--rebuild-cache`)
+    refresh: bool,
+    changed: AtomicBool,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl MetadataCache {
+    /// Default location, `~/.cache/fontsrt/metadata.json`, honouring `XDG_CACHE_HOME`
+    pub fn default_path() -> Option<PathBuf> {
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(cache_home.join("fontsrt").join("metadata.json"))
+    }
+
+    /// An empty cache that is written to `path` on save
+    pub fn empty(path: &Path, settings: &str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            settings: settings.to_string(),
+            entries: Mutex::new(HashMap::new()),
+            refresh: false,
+            changed: AtomicBool::new(false),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Load the cache at `path`. A missing file, or one written under other
+    /// settings, gives an empty cache. An unreadable or corrupt file also gives
+    /// an empty cache, along with a warning for the user.
+    pub fn load(path: &Path, settings: &str) -> (Self, Option<String>) {
+        let cache = Self::empty(path, settings);
+
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (cache, None),
+            Err(e) => return (cache, Some(format!("Ignoring unreadable metadata cache {}: {}", path.display(), e))),
+        };
+
+        match parse_cache(&content, settings) {
+            Ok(Some(entries)) => {
+                *cache.entries.lock().unwrap() = entries;
+                (cache, None)
+            }
+            Ok(None) => (cache, None),
+            Err(e) => (cache, Some(format!("Discarding corrupt metadata cache {}: {}", path.display(), e))),
+        }
+    }
+
+    /// Ignore existing entries and parse every font again. Entries of fonts
+    /// outside the run are kept.
+    pub fn refreshing(mut self) -> Self {
+        self.refresh = true;
+        self
+    }
+
+    /// Cached metadata of a font, if the file is unchanged since it was recorded
+    pub fn get(&self, path: &Path) -> Option<FontMetadata> {
+        if self.refresh {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        let key = std::path::absolute(path).ok()?;
+        let stamp = FileStamp::of(path)?;
+
+        let found = self.entries.lock().unwrap().get(&key)
+            .filter(|(recorded, _)| *recorded == stamp)
+            .map(|(_, metadata)| FontMetadata { original_path: path.to_path_buf(), ..metadata.clone() });
+
+        let counter = if found.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    /// Record freshly extracted metadata of a font
+    pub fn insert(&self, path: &Path, metadata: &FontMetadata) {
+        let (Ok(key), Some(stamp)) = (std::path::absolute(path), FileStamp::of(path)) else {
+            return;
+        };
+        self.entries.lock().unwrap().insert(key, (stamp, metadata.clone()));
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Follow a font that was moved, so the next run finds it at its new path.
+    /// Moves keep size and modification time, so the entry stays valid.
+    pub fn relocate(&self, from: &Path, to: &Path) {
+        let (Ok(from), Ok(to)) = (std::path::absolute(from), std::path::absolute(to)) else {
+            return;
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.remove(&from) {
+            entries.insert(to, entry);
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Follow a folder that was moved, re-keying the entries of every font below it
+    pub fn relocate_tree(&self, from: &Path, to: &Path) {
+        let (Ok(from), Ok(to)) = (std::path::absolute(from), std::path::absolute(to)) else {
+            return;
+        };
+        let mut entries = self.entries.lock().unwrap();
+        let moved: Vec<PathBuf> = entries.keys().filter(|path| path.starts_with(&from)).cloned().collect();
+        for path in moved {
+            if let (Some(entry), Ok(relative)) = (entries.remove(&path), path.strip_prefix(&from)) {
+                entries.insert(to.join(relative), entry);
+                self.changed.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Number of fonts served from the cache and parsed afresh so far
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+
+    /// Write the cache back if anything changed, dropping entries of files that
+    /// no longer exist. The file is replaced atomically.
+    pub fn save(&self) -> Result<()> {
+        if !self.changed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let mut entries: Vec<Value> = self.entries.lock().unwrap().iter()
+            .filter(|(path, _)| path.is_file())
+            .map(|(path, (stamp, metadata))| json!({
+                "path": path.to_string_lossy(),
+                "size": stamp.size,
+                "mtime_secs": stamp.mtime_secs,
+                "mtime_nanos": stamp.mtime_nanos,
+                "metadata": metadata,
+            }))
+            .collect();
+        entries.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+
+        let content = json!({
+            "format": CACHE_FORMAT,
+            "settings": self.settings,
+            "entries": entries,
+        });
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp = self.path.with_extension(format!("tmp-{}", std::process::id()));
+        fs::write(&temp, content.to_string())?;
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Fingerprint of the settings that change extracted metadata: the crate
+/// version, `--transliterate`, `--ascii-names`, `--no-filename-fallback`, the
+/// `--parse-css` rules and the contents of the foundry alias and override files
+pub fn cache_settings(config: &Config) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update(&[config.transliterate as u8, config.ascii_names as u8, config.filename_fallback as u8]);
+    for face in config.css_faces.iter().flat_map(|faces| faces.iter()) {
+        hasher.update(format!("{:?}:{:?}:{}\0", face.family, face.weight, face.italic).as_bytes());
+    }
+    for file in [&config.foundry_alias_file, &config.foundry_override_file].into_iter().flatten() {
+        hasher.update(&fs::read(file).unwrap_or_default());
+        hasher.update(b"\0");
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Extract font metadata through the library's font database and the run's
+/// metadata cache, when there are. Only fonts that parsed are recorded;
+/// everything else is read again next time.
+pub fn cached_font_metadata(path: &Path, config: &Config) -> Result<Option<FontMetadata>> {
+    let database = config.font_database.as_ref().filter(|_| !config.dry_run);
+    if let Some(metadata) = database.and_then(|database| database.get(path)) {
+        return Ok(Some(metadata));
+    }
+    if let Some(metadata) = config.metadata_cache.as_ref().and_then(|cache| cache.get(path)) {
+        return Ok(Some(metadata));
+    }
+
+    let metadata = extract_font_metadata(path, config)?;
+    if let Some(metadata) = &metadata {
+        if let Some(cache) = &config.metadata_cache {
+            cache.insert(path, metadata);
+        }
+        if let Some(database) = database {
+            if let Err(e) = database.insert(path, metadata) {
+                log(config, format!("Could not index {}: {}", path.display(), e));
+            }
+        }
+    }
+    Ok(metadata)
+}
+
+/// Entries of a cache file, or `None` when it was written under other settings
+fn parse_cache(content: &str, settings: &str) -> Result<Option<HashMap<PathBuf, (FileStamp, FontMetadata)>>> {
+    let corrupt = |what: &str| Error::Config(format!("invalid {}", what));
+
+    let root: Value = serde_json::from_str(content).map_err(|e| Error::Config(e.to_string()))?;
+    if root.get("format").and_then(Value::as_u64) != Some(CACHE_FORMAT)
+        || root.get("settings").and_then(Value::as_str) != Some(settings)
+    {
+        return Ok(None);
+    }
+
+    let mut entries = HashMap::new();
+    for entry in root.get("entries").and_then(Value::as_array).ok_or_else(|| corrupt("entry list"))? {
+        let path = entry.get("path").and_then(Value::as_str).ok_or_else(|| corrupt("path"))?;
+        let stamp = FileStamp {
+            size: entry.get("size").and_then(Value::as_u64).ok_or_else(|| corrupt("size"))?,
+            mtime_secs: entry.get("mtime_secs").and_then(Value::as_u64).ok_or_else(|| corrupt("mtime"))?,
+            mtime_nanos: entry.get("mtime_nanos").and_then(Value::as_u64)
+                .and_then(|nanos| u32::try_from(nanos).ok())
+                .ok_or_else(|| corrupt("mtime"))?,
+        };
+        let metadata = entry.get("metadata")
+            .and_then(|metadata| FontMetadata::deserialize(metadata).ok())
+            .ok_or_else(|| corrupt(&format!("metadata for {}", path)))?;
+        entries.insert(PathBuf::from(path), (stamp, metadata));
+    }
+
+    Ok(Some(entries))
+}
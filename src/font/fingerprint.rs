@@ -0,0 +1,298 @@
+//! Outline fingerprints for spotting one design sold or re-released under
+//! different family names ("Helmet" for Helvetica). Report only: fonts with
+//! matching fingerprints are never merged.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use ttf_parser::{Face, OutlineBuilder};
+use crate::font::cache::cached_font_metadata;
+use crate::models::Config;
+use crate::organizer::lockfile::family_match_key;
+
+/// Glyphs sketched for a fingerprint, chosen for their distinctive shapes
+pub const FINGERPRINT_CHARS: &str = "agRe";
+
+/// Bounding boxes are rounded to this many units per em, so re-exports with
+/// another em size or slightly different rounding still match
+const BBOX_GRID: f32 = 100.0;
+
+/// Point count, normalized bounding box and contour directions of one glyph
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GlyphSketch {
+    pub points: usize,
+    /// x min, y min, x max, y max in hundredths of an em
+    pub bbox: [i32; 4],
+    /// Per contour, whether it runs clockwise
+    pub clockwise: Vec<bool>,
+}
+
+/// Sketches of the `FINGERPRINT_CHARS` glyphs of a face
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OutlineFingerprint {
+    pub glyphs: Vec<GlyphSketch>,
+}
+
+impl OutlineFingerprint {
+    /// Short hex digest of the sketches, for reports
+    pub fn digest(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for glyph in &self.glyphs {
+            hasher.update(format!("{}:{:?}:{:?};", glyph.points, glyph.bbox, glyph.clockwise).as_bytes());
+        }
+        hasher.finalize().to_hex()[..16].to_string()
+    }
+}
+
+/// Collects point counts and contour directions of a glyph outline
+#[derive(Default)]
+struct ContourSketcher {
+    points: usize,
+    /// Twice the signed area of the contour being drawn, from its on-curve points
+    area: f32,
+    start: (f32, f32),
+    last: (f32, f32),
+    clockwise: Vec<bool>,
+}
+
+impl ContourSketcher {
+    fn edge_to(&mut self, x: f32, y: f32) {
+        self.area += self.last.0 * y - x * self.last.1;
+        self.last = (x, y);
+    }
+}
+
+impl OutlineBuilder for ContourSketcher {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.points += 1;
+        self.area = 0.0;
+        self.start = (x, y);
+        self.last = (x, y);
+    }
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.points += 1;
+        self.edge_to(x, y);
+    }
+    fn quad_to(&mut self, _x1: f32, _y1: f32, x: f32, y: f32) {
+        self.points += 2;
+        self.edge_to(x, y);
+    }
+    fn curve_to(&mut self, _x1: f32, _y1: f32, _x2: f32, _y2: f32, x: f32, y: f32) {
+        self.points += 3;
+        self.edge_to(x, y);
+    }
+    fn close(&mut self) {
+        let (x, y) = self.start;
+        self.edge_to(x, y);
+        self.clockwise.push(self.area < 0.0);
+    }
+}
+
+/// Fingerprint a face, `None` when it lacks any of the `FINGERPRINT_CHARS`
+pub fn fingerprint_face(face: &Face) -> Option<OutlineFingerprint> {
+    let units_per_em = face.units_per_em() as f32;
+    let scale = |value: i16| (value as f32 * BBOX_GRID / units_per_em).round() as i32;
+
+    let glyphs = FINGERPRINT_CHARS.chars()
+        .map(|c| {
+            let glyph = face.glyph_index(c)?;
+            let mut sketcher = ContourSketcher::default();
+            let bbox = face.outline_glyph(glyph, &mut sketcher)?;
+            Some(GlyphSketch {
+                points: sketcher.points,
+                bbox: [scale(bbox.x_min), scale(bbox.y_min), scale(bbox.x_max), scale(bbox.y_max)],
+                clockwise: sketcher.clockwise,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(OutlineFingerprint { glyphs })
+}
+
+/// Fingerprint the first face of a font file
+pub fn fingerprint_file(path: &Path) -> Option<OutlineFingerprint> {
+    let data = fs::read(path).ok()?;
+    let face = Face::parse(&data, 0).ok()?;
+    fingerprint_face(&face)
+}
+
+/// A font file with its family name and fingerprint
+#[derive(Debug, Clone)]
+pub struct FingerprintedFont {
+    pub path: PathBuf,
+    pub family: String,
+    pub fingerprint: OutlineFingerprint,
+}
+
+/// Fonts sharing one fingerprint under more than one family name
+#[derive(Debug, Clone)]
+pub struct RenamedCluster {
+    /// Digest of the shared fingerprint
+    pub digest: String,
+    /// Family names the design appears under, sorted
+    pub families: Vec<String>,
+    /// The matching fonts, sorted by path
+    pub fonts: Vec<FingerprintedFont>,
+}
+
+/// Cluster fonts by fingerprint, keeping the clusters whose fonts carry
+/// different family names (ignoring case, spaces and punctuation).
+/// Clusters are sorted by their first family name.
+pub fn find_renamed_clusters(fonts: Vec<FingerprintedFont>) -> Vec<RenamedCluster> {
+    let mut by_fingerprint: BTreeMap<String, Vec<FingerprintedFont>> = BTreeMap::new();
+    for font in fonts {
+        by_fingerprint.entry(font.fingerprint.digest()).or_default().push(font);
+    }
+
+    let mut clusters: Vec<RenamedCluster> = by_fingerprint.into_iter()
+        .filter_map(|(digest, mut fonts)| {
+            let keys: BTreeSet<String> = fonts.iter().map(|font| family_match_key(&font.family)).collect();
+            if keys.len() < 2 {
+                return None;
+            }
+            fonts.sort_by(|a, b| a.path.cmp(&b.path));
+            let families: BTreeSet<String> = fonts.iter().map(|font| font.family.clone()).collect();
+            Some(RenamedCluster { digest, families: families.into_iter().collect(), fonts })
+        })
+        .collect();
+    clusters.sort_by(|a, b| a.families.cmp(&b.families));
+    clusters
+}
+
+/// Font files in a folder and its subfolders
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Fingerprint every font in the family folders and cluster the matches
+/// found under different family names
+pub fn renamed_clusters_in(family_dirs: &[PathBuf], config: &Config) -> Vec<RenamedCluster> {
+    let mut files = Vec::new();
+    for dir in family_dirs {
+        collect_files(dir, &mut files);
+    }
+    files.sort();
+    files.dedup();
+
+    let fonts: Vec<FingerprintedFont> = files.par_iter()
+        .filter_map(|path| {
+            let metadata = cached_font_metadata(path, config).ok().flatten()?;
+            Some(FingerprintedFont {
+                path: path.clone(),
+                family: metadata.family_name,
+                fingerprint: fingerprint_file(path)?,
+            })
+        })
+        .collect();
+
+    find_renamed_clusters(fonts)
+}
+
+/// Print the clusters of one design found under several family names
+pub fn print_renamed_clusters(clusters: &[RenamedCluster]) {
+    if clusters.is_empty() {
+        println!("Outline fingerprints: no design found under more than one family name");
+        return;
+    }
+
+    println!("Outline fingerprints: {} designs found under more than one family name (not merged):", clusters.len());
+    for cluster in clusters {
+        println!("  {} [{}]", cluster.families.join(" = "), cluster.digest);
+        for font in &cluster.fonts {
+            println!("      {}  {}", font.family, font.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TestFont};
+
+    /// Outer contours run clockwise, counters counter-clockwise
+    const SQUARE: &[(i16, i16)] = &[(0, 0), (0, 500), (400, 500), (400, 0)];
+    const COUNTER: &[(i16, i16)] = &[(100, 100), (300, 100), (300, 400), (100, 400)];
+    const TRIANGLE: &[(i16, i16)] = &[(0, -200), (200, 500), (400, -200)];
+    const STEM: &[(i16, i16)] = &[(0, 0), (0, 700), (100, 700), (100, 0)];
+    const WIDE: &[(i16, i16)] = &[(0, 0), (0, 450), (600, 450), (600, 0)];
+
+    /// One design, under any family name
+    fn design(family: &str, subfamily: &str) -> TestFont {
+        TestFont::new(family, subfamily)
+            .glyph('a', &[SQUARE, COUNTER])
+            .glyph('g', &[TRIANGLE])
+            .glyph('R', &[STEM, COUNTER])
+            .glyph('e', &[SQUARE])
+    }
+
+    /// A genuinely different design
+    fn other_design(family: &str) -> TestFont {
+        TestFont::new(family, "Regular")
+            .glyph('a', &[WIDE])
+            .glyph('g', &[WIDE, COUNTER])
+            .glyph('R', &[STEM])
+            .glyph('e', &[TRIANGLE])
+    }
+
+    fn fingerprint(font: TestFont) -> Option<OutlineFingerprint> {
+        let data = font.build();
+        fingerprint_face(&Face::parse(&data, 0).unwrap())
+    }
+
+    #[test]
+    fn sketches_points_boxes_and_directions() {
+        let fingerprint = fingerprint(design("Helvetica", "Regular")).unwrap();
+        assert_eq!(fingerprint.glyphs.len(), 4);
+        let a = &fingerprint.glyphs[0];
+        assert_eq!(a.bbox, [0, 0, 40, 50]);
+        assert_eq!(a.clockwise, [true, false]);
+        assert_eq!(fingerprint.glyphs[1].bbox, [0, -20, 40, 50]);
+        assert!(fingerprint.glyphs[0].points > fingerprint.glyphs[3].points);
+    }
+
+    #[test]
+    fn names_do_not_change_the_fingerprint() {
+        let original = fingerprint(design("Helvetica", "Regular")).unwrap();
+        let renamed = fingerprint(design("Helmet", "Regular")).unwrap();
+        assert_eq!(original, renamed);
+        assert_eq!(original.digest(), renamed.digest());
+        assert_ne!(original.digest(), fingerprint(other_design("Roboto")).unwrap().digest());
+    }
+
+    #[test]
+    fn missing_glyphs_give_no_fingerprint() {
+        let font = TestFont::new("Helvetica", "Regular").glyph('a', &[SQUARE]).glyph('g', &[TRIANGLE]);
+        assert_eq!(fingerprint(font), None);
+        assert_eq!(fingerprint(TestFont::new("Helvetica", "Regular")), None);
+    }
+
+    #[test]
+    fn clusters_one_design_under_two_names() {
+        let tmp = TempDir::new("fingerprint");
+        design("Helvetica", "Regular").write(&tmp.join("Helvetica/Helvetica.ttf"));
+        design("Helmet", "Regular").write(&tmp.join("Helmet/Helmet.ttf"));
+        other_design("Roboto").write(&tmp.join("Roboto/Roboto.ttf"));
+        // Copies under one family, in any spelling, are not a rename
+        other_design("Open Sans").write(&tmp.join("Open Sans/Open Sans.ttf"));
+        other_design("OpenSans").write(&tmp.join("Open Sans/OpenSans.ttf"));
+
+        let dirs: Vec<PathBuf> = ["Helvetica", "Helmet", "Open Sans"].iter().map(|dir| tmp.join(dir)).collect();
+        let clusters = renamed_clusters_in(&dirs, &Config::default());
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].families, ["Helmet", "Helvetica"]);
+        let paths: Vec<PathBuf> = clusters[0].fonts.iter().map(|font| font.path.clone()).collect();
+        assert_eq!(paths, [tmp.join("Helmet/Helmet.ttf"), tmp.join("Helvetica/Helvetica.ttf")]);
+
+        // The negative control joins no cluster, even with Roboto scanned too
+        let dirs: Vec<PathBuf> = ["Helvetica", "Roboto"].iter().map(|dir| tmp.join(dir)).collect();
+        assert!(renamed_clusters_in(&dirs, &Config::default()).is_empty());
+    }
+}
@@ -1,17 +1,154 @@
-use font_kit::font::Font;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use glob::{MatchOptions, Pattern};
 use regex::Regex;
 use lazy_static::lazy_static;
+use ttf_parser::{Face, Tag};
+use crate::error::{Error, Result};
+use crate::models::Config;
 
 lazy_static! {
     static ref FOUNDRY_PATTERNS: [Regex; 2] = [
         Regex::new(r"^(Adobe|Monotype|Linotype|ITC|URW|Bitstream|Google|Microsoft|Apple|IBM|Hoefler|Typekit|FontFont|Emigre|Dalton Maag|Font Bureau|House Industries|P22|Typotheque|Underware|Fontfabric|Fontsmith|Klim|Process|Commercial|Grilli|Production|Sudtipos|Typofonderie|Canada|Rosetta|Darden|Positype|Typonine|Latinotype|Typejockeys|Suitcase|Elsner\+Flake|Scangraphic|Berthold|Letraset|Agfa|Paratype|Fontshop|Letterhead|Neufville)\s+.*").unwrap(),
         Regex::new(r"^.*(LT|MT|ITC|URW|BT|MS|GD|FF|DF|DM|FB|HI|P22|TT|UW|FS|KT|PT|CT|GT|ST|TF|CD|RT|DD|TN|TJ|SC|EF|SG|LS|AG|LH|NV)$").unwrap(),
     ];
+
+    /// Registered OS/2 achVendID codes of well-known foundries. Placeholder IDs
+    /// written by font editors (e.g. "PfEd", "PYRS", "UKWN") are left out on
+    /// purpose, they say nothing about who made the font.
+    static ref VENDOR_IDS: HashMap<[u8; 4], &'static str> = HashMap::from([
+        (*b"1ASC", "Ascender"),
+        (*b"ADBE", "Adobe"),
+        (*b"AGFA", "Agfa"),
+        (*b"ALTS", "Altsys"),
+        (*b"AMT ", "Agfa Monotype"),
+        (*b"APOS", "Apostrophic Labs"),
+        (*b"APPL", "Apple"),
+        (*b"ARPH", "Arphic"),
+        (*b"ASC ", "Ascender"),
+        (*b"AZLS", "Azalea Software"),
+        (*b"B&H ", "Bigelow & Holmes"),
+        (*b"BERT", "Berthold"),
+        (*b"BITS", "Bitstream"),
+        (*b"CANO", "Canon"),
+        (*b"CASL", "H.W. Caslon"),
+        (*b"COOL", "Cool Fonts"),
+        (*b"DAMA", "Dalton Maag"),
+        (*b"DELV", "Delve Fonts"),
+        (*b"DS  ", "Dainippon Screen"),
+        (*b"DSCI", "Design Science"),
+        (*b"DTC ", "Digital Typeface Corp"),
+        (*b"DYNA", "DynaLab"),
+        (*b"EF  ", "Elsner+Flake"),
+        (*b"ELSE", "Elseware"),
+        (*b"EMGR", "Emigre"),
+        (*b"EPSN", "Epson"),
+        (*b"EVER", "Evertype"),
+        (*b"FBI ", "Font Bureau"),
+        (*b"FCAB", "The Font Cabinet"),
+        (*b"FJ  ", "Fujitsu"),
+        (*b"FSI ", "FontShop"),
+        (*b"FTGD", "Font Garden"),
+        (*b"FWKS", "Fontworks"),
+        (*b"GALA", "Galapagos Design Group"),
+        (*b"GLYF", "Glyph Systems"),
+        (*b"GOOG", "Google"),
+        (*b"GRIL", "Grilli Type"),
+        (*b"HL  ", "High-Logic"),
+        (*b"HOUS", "House Industries"),
+        (*b"HP  ", "Hewlett-Packard"),
+        (*b"HY  ", "HanYang"),
+        (*b"IBM ", "IBM"),
+        (*b"IMPR", "Impress"),
+        (*b"ITC ", "ITC"),
+        (*b"ITFO", "Indian Type Foundry"),
+        (*b"KATF", "Kingsley/ATF"),
+        (*b"KLIM", "Klim"),
+        (*b"LANS", "Lanston"),
+        (*b"LARA", "Larabie"),
+        (*b"LEAF", "Interleaf"),
+        (*b"LETR", "Letraset"),
+        (*b"LHF ", "Letterhead Fonts"),
+        (*b"LING", "Linguist's Software"),
+        (*b"LINO", "Linotype"),
+        (*b"LP  ", "LetterPerfect"),
+        (*b"MACR", "Macromedia"),
+        (*b"MJ  ", "Majus"),
+        (*b"MLGC", "Micrologic"),
+        (*b"MONO", "Monotype"),
+        (*b"MRSW", "Morisawa"),
+        (*b"MS  ", "Microsoft"),
+        (*b"MSFT", "Microsoft"),
+        (*b"MT  ", "Monotype"),
+        (*b"MTY ", "Motoya"),
+        (*b"NB  ", "No Bodoni"),
+        (*b"NDTC", "Neufville"),
+        (*b"NEC ", "NEC"),
+        (*b"NIS ", "NIS"),
+        (*b"OPTI", "Castcraft"),
+        (*b"P22 ", "P22"),
+        (*b"PARA", "ParaType"),
+        (*b"PRFS", "Production First Software"),
+        (*b"PRGR", "Paragraph"),
+        (*b"PSY ", "PSY/OPS"),
+        (*b"QMSI", "QMS"),
+        (*b"RICO", "Ricoh"),
+        (*b"RKFN", "R K Fonts"),
+        (*b"RUDY", "RudynFluffy"),
+        (*b"SAJA", "Sajatypeworks"),
+        (*b"SAND", "Sandoll"),
+        (*b"SHAR", "Sharp"),
+        (*b"SIL ", "SIL International"),
+        (*b"STIX", "STIX"),
+        (*b"SUN ", "Sun Microsystems"),
+        (*b"SWFT", "Swfte"),
+        (*b"TIRO", "Tiro Typeworks"),
+        (*b"TPTC", "Test Pilot Collective"),
+        (*b"TPTQ", "Typotheque"),
+        (*b"TS  ", "TamilSoft"),
+        (*b"UNDR", "Underware"),
+        (*b"URW ", "URW"),
+        (*b"UT  ", "Unitype"),
+        (*b"VLKF", "Visualogik"),
+        (*b"VOG ", "Martin Vogel"),
+        (*b"WASP", "Wasp"),
+        (*b"XRX ", "Xerox"),
+        (*b"Y&Y ", "Y&Y"),
+        (*b"ZSFT", "ZSoft"),
+        (*b"FNDR", "The Font Foundry"),
+        (*b"TYPR", "Type Revivals"),
+        (*b"CTDL", "China Type Designs"),
+        (*b"ECF ", "Emerald City Fontwerks"),
+    ]);
 }
 
 /// Extract foundry information from font metadata and name
-pub fn extract_foundry(font: &Font, family_name: &str) -> String {
-    if let Some(foundry) = extract_foundry_from_metadata(font) {
+pub fn extract_foundry(face: Option<&Face>, family_name: &str, postscript_name: Option<&str>, config: &Config) -> String {
+    // User overrides beat everything we can detect, aliases included
+    if let Some(overrides) = &config.foundry_overrides {
+        if let Some(foundry) = find_foundry_override(overrides, family_name) {
+            return foundry.to_string();
+        }
+    }
+
+    let foundry = face.and_then(extract_foundry_from_vendor_id)
+        .unwrap_or_else(|| detect_foundry(postscript_name, family_name));
+
+    match &config.foundry_aliases {
+        Some(aliases) => {
+            let vendor_id = face.and_then(read_vendor_id);
+            let font_id = postscript_name.unwrap_or(family_name);
+            aliases.resolve(&foundry, vendor_id.as_deref(), font_id)
+        }
+        None => foundry,
+    }
+}
+
+/// Detect the raw foundry name from the font's names when the vendor ID is unknown
+fn detect_foundry(postscript_name: Option<&str>, family_name: &str) -> String {
+    if let Some(foundry) = extract_foundry_from_metadata(postscript_name) {
         return foundry;
     }
 
@@ -27,9 +164,37 @@ pub fn extract_foundry(font: &Font, family_name: &str) -> String {
         .unwrap_or_else(|| "Unknown".to_string())
 }
 
-/// Extract foundry information from font metadata
-pub fn extract_foundry_from_metadata(font: &Font) -> Option<String> {
-    if let Some(postscript_name) = font.postscript_name() {
+/// The raw 4-byte OS/2 achVendID, if the font has an OS/2 table
+fn raw_vendor_id(face: &Face) -> Option<[u8; 4]> {
+    let os2 = face.raw_face().table(Tag::from_bytes(b"OS/2"))?;
+    os2.get(58..62)?.try_into().ok()
+}
+
+/// Read the 4-character OS/2 achVendID, if the font has one
+fn read_vendor_id(face: &Face) -> Option<String> {
+    let vendor_id = raw_vendor_id(face)?;
+    let vendor_id = String::from_utf8_lossy(&vendor_id).trim_end_matches(['\0', ' ']).to_string();
+
+    if vendor_id.is_empty() {
+        None
+    } else {
+        Some(vendor_id)
+    }
+}
+
+/// Look up the foundry registered for the font's OS/2 achVendID, e.g. "ADBE" -> "Adobe"
+pub fn extract_foundry_from_vendor_id(face: &Face) -> Option<String> {
+    let mut vendor_id = raw_vendor_id(face)?;
+    // Short IDs are meant to be space padded, but some fonts pad with NULs
+    for byte in vendor_id.iter_mut().filter(|byte| **byte == 0) {
+        *byte = b' ';
+    }
+    VENDOR_IDS.get(&vendor_id).map(|foundry| foundry.to_string())
+}
+
+/// Extract foundry information from a PostScript name prefix such as "ADBE-"
+pub fn extract_foundry_from_metadata(postscript_name: Option<&str>) -> Option<String> {
+    if let Some(postscript_name) = postscript_name {
         let parts: Vec<&str> = postscript_name.split('-').collect();
         if parts.len() > 1 {
             match parts[0] {
@@ -69,3 +234,253 @@ fn extract_foundry_from_abbreviation(family_name: &str) -> Option<String> {
     }.map(String::from)
 }
 
+
+/// User-editable mapping from raw foundry strings and vendor IDs to canonical names.
+///
+/// Loaded from a TOML file of `"raw" = "Canonical"` pairs. Keys are matched
+/// case-insensitively and may use glob wildcards (`"Adobe*" = "Adobe"`); exact
+/// keys always win over patterns, and longer patterns are tried first.
+#[derive(Debug, Default)]
+pub struct FoundryAliases {
+    /// Exact keys, lowercased
+    exact: HashMap<String, String>,
+    /// Glob keys, most specific first
+    patterns: Vec<(Pattern, String)>,
+    /// Fonts (by PostScript name) whose foundry was remapped
+    remapped: Mutex<HashSet<String>>,
+    /// Raw foundry strings that no alias matched
+    unmapped: Mutex<BTreeSet<String>>,
+}
+
+impl FoundryAliases {
+    /// Load an alias file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let table: toml::Table = content.parse()
+            .map_err(|e| Error::Config(format!("Invalid foundry alias file {}: {}", path.display(), e)))?;
+
+        let mut aliases = FoundryAliases::default();
+
+        for (key, value) in table {
+            let canonical = value.as_str()
+                .ok_or_else(|| Error::Config(format!(
+                    "Foundry alias '{}' in {} must map to a string", key, path.display()
+                )))?
+                .to_string();
+
+            if key.contains(['*', '?', '[']) {
+                let pattern = Pattern::new(&key)
+                    .map_err(|e| Error::Config(format!("Invalid foundry alias pattern '{}': {}", key, e)))?;
+                aliases.patterns.push((pattern, canonical));
+            } else {
+                aliases.exact.insert(key.to_lowercase(), canonical);
+            }
+        }
+
+        aliases.patterns.sort_by(|a, b| {
+            b.0.as_str().len().cmp(&a.0.as_str().len()).then_with(|| a.0.as_str().cmp(b.0.as_str()))
+        });
+
+        Ok(aliases)
+    }
+
+    /// Look up the canonical name for a raw foundry string or vendor ID
+    pub fn lookup(&self, raw: &str) -> Option<&str> {
+        if let Some(canonical) = self.exact.get(&raw.to_lowercase()) {
+            return Some(canonical);
+        }
+
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+
+        self.patterns.iter()
+            .find(|(pattern, _)| pattern.matches_with(raw, options))
+            .map(|(_, canonical)| canonical.as_str())
+    }
+
+    /// Map a detected foundry to its canonical name, recording the outcome for the report
+    pub fn resolve(&self, foundry: &str, vendor_id: Option<&str>, font_id: &str) -> String {
+        let canonical = self.lookup(foundry)
+            .or_else(|| vendor_id.and_then(|id| self.lookup(id)));
+
+        match canonical {
+            Some(canonical) => {
+                if canonical != foundry {
+                    self.remapped.lock().unwrap().insert(font_id.to_string());
+                }
+                canonical.to_string()
+            }
+            None => {
+                if foundry != "Unknown" {
+                    self.unmapped.lock().unwrap().insert(foundry.to_string());
+                }
+                foundry.to_string()
+            }
+        }
+    }
+
+    /// Number of distinct fonts whose foundry was remapped
+    pub fn remapped_count(&self) -> usize {
+        self.remapped.lock().unwrap().len()
+    }
+
+    /// Raw foundry strings seen during the run without a matching alias
+    pub fn unmapped(&self) -> Vec<String> {
+        self.unmapped.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Print the end-of-run alias summary
+    pub fn print_report(&self) {
+        println!("Foundry aliases: {} fonts remapped", self.remapped_count());
+
+        let unmapped = self.unmapped();
+        if !unmapped.is_empty() {
+            println!("  Foundry names without an alias:");
+            for name in unmapped {
+                println!("    - {}", name);
+            }
+        }
+    }
+}
+
+/// A user-supplied foundry for families matching a pattern, from the foundry
+/// override file (`--foundry-map`)
+#[derive(Debug, Clone)]
+pub struct FoundryOverride {
+    /// Family name pattern; a plain name matches exactly, ignoring case
+    pub pattern: Pattern,
+    /// Foundry assigned to matching families
+    pub foundry: String,
+}
+
+impl FoundryOverride {
+    /// Whether the override applies to a family name
+    pub fn matches(&self, family_name: &str) -> bool {
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+        self.pattern.matches_with(family_name, options)
+    }
+}
+
+/// Foundry of the first override matching the family name, in file order
+pub fn find_foundry_override<'a>(overrides: &'a [FoundryOverride], family_name: &str) -> Option<&'a str> {
+    overrides.iter()
+        .find(|entry| entry.matches(family_name))
+        .map(|entry| entry.foundry.as_str())
+}
+
+/// Load a foundry override file: a JSON array of
+/// `{"pattern": "Hybrea*", "foundry": "Hypertypes"}` objects.
+/// Any invalid entry fails the whole file.
+pub fn load_foundry_overrides(path: &Path) -> Result<Vec<FoundryOverride>> {
+    let (overrides, problems) = parse_foundry_overrides(path)?;
+    match problems.first() {
+        Some(problem) => Err(Error::Config(format!("Invalid foundry override file {}: {}", path.display(), problem))),
+        None => Ok(overrides),
+    }
+}
+
+/// Problems with each invalid entry of a foundry override file, empty when the
+/// file is valid. Fails only when the file cannot be read or is not a JSON array.
+pub fn validate_foundry_overrides(path: &Path) -> Result<Vec<String>> {
+    parse_foundry_overrides(path).map(|(_, problems)| problems)
+}
+
+/// The valid entries of an override file, and a message per invalid one
+fn parse_foundry_overrides(path: &Path) -> Result<(Vec<FoundryOverride>, Vec<String>)> {
+    let content = fs::read_to_string(path)?;
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&content)
+        .map_err(|e| Error::Config(format!(
+            "Foundry override file {} must be a JSON array: {}", path.display(), e
+        )))?;
+
+    let mut overrides = Vec::new();
+    let mut problems = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let pattern = entry.get("pattern").and_then(|value| value.as_str());
+        let foundry = entry.get("foundry").and_then(|value| value.as_str());
+
+        match (pattern, foundry) {
+            (Some(pattern), Some(foundry)) if !foundry.trim().is_empty() => match Pattern::new(pattern) {
+                Ok(compiled) => overrides.push(FoundryOverride {
+                    pattern: compiled,
+                    foundry: foundry.trim().to_string(),
+                }),
+                Err(e) => problems.push(format!("entry {}: invalid pattern '{}': {}", index + 1, pattern, e)),
+            },
+            (Some(pattern), Some(_)) => problems.push(format!("entry {}: empty foundry for pattern '{}'", index + 1, pattern)),
+            _ => problems.push(format!("entry {}: expected string \"pattern\" and \"foundry\" fields", index + 1)),
+        }
+    }
+
+    Ok((overrides, problems))
+}
+
+/// FontSrt's folder of user settings, `~/.config/fontsrt`, honouring `XDG_CONFIG_HOME`
+pub(crate) fn user_config_dir() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("fontsrt"))
+}
+
+/// Default location of the foundry override file, `~/.config/fontsrt/foundry_overrides.json`
+pub fn default_foundry_override_file() -> Option<PathBuf> {
+    Some(user_config_dir()?.join("foundry_overrides.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::testing::TestFont;
+
+    fn foundry_of(font: TestFont, config: &Config) -> String {
+        let data = font.build();
+        let face = Face::parse(&data, 0).unwrap();
+        extract_foundry(Some(&face), "Roboto", Some("Roboto-Regular"), config)
+    }
+
+    #[test]
+    fn registered_vendor_ids() {
+        let config = Config::default();
+        assert_eq!(foundry_of(TestFont::new("Roboto", "Regular").vendor(b"ADBE"), &config), "Adobe");
+        assert_eq!(foundry_of(TestFont::new("Roboto", "Regular").vendor(b"GOOG"), &config), "Google");
+        assert!(VENDOR_IDS.len() >= 100);
+    }
+
+    #[test]
+    fn nul_padded_vendor_id() {
+        let data = TestFont::new("Roboto", "Regular").vendor(b"B&H\0").build();
+        let face = Face::parse(&data, 0).unwrap();
+        assert_eq!(extract_foundry_from_vendor_id(&face).as_deref(), Some("Bigelow & Holmes"));
+    }
+
+    #[test]
+    fn unknown_vendor_falls_back_to_names() {
+        let data = TestFont::new("Adobe Garamond", "Regular").vendor(b"PfEd").build();
+        let face = Face::parse(&data, 0).unwrap();
+        assert_eq!(extract_foundry_from_vendor_id(&face), None);
+        assert_eq!(extract_foundry(Some(&face), "Adobe Garamond", Some("AdobeGaramond-Regular"), &Config::default()), "Adobe");
+        assert_eq!(extract_foundry(None, "Roboto", Some("MONO-Roboto"), &Config::default()), "Monotype");
+        assert_eq!(extract_foundry(None, "Roboto", Some("Roboto-Regular"), &Config::default()), "Unknown");
+    }
+
+    #[test]
+    fn override_beats_vendor_id() {
+        let config = Config {
+            foundry_overrides: Some(Arc::new(vec![FoundryOverride {
+                pattern: Pattern::new("Robo*").unwrap(),
+                foundry: "Christian Robertson".to_string(),
+            }])),
+            ..Config::default()
+        };
+        assert_eq!(foundry_of(TestFont::new("Roboto", "Regular").vendor(b"GOOG"), &config), "Christian Robertson");
+    }
+}
@@ -0,0 +1,373 @@
+use ttf_parser::{Face, Tag};
+use crate::models::{NamedInstance, VariationAxis};
+use super::metadata::read_name;
+
+/// fvar axis flag: the axis is not meant to be shown to users
+const HIDDEN_AXIS: u16 = 0x0001;
+
+/// STAT axis value flag: the name is left out of composed instance names ("Regular")
+const ELIDABLE_AXIS_VALUE_NAME: u16 = 0x0002;
+
+/// Name IDs 2 and 17 mean "use the font's own subfamily" in fvar instance records
+const NAME_ID_SUBFAMILY: u16 = 2;
+const NAME_ID_TYPOGRAPHIC_SUBFAMILY: u16 = 17;
+
+/// Read a big-endian u16 at `offset`
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+/// Read a big-endian u32 at `offset`
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Read a 16.16 fixed-point number at `offset`
+fn fixed_at(data: &[u8], offset: usize) -> Option<f32> {
+    Some(u32_at(data, offset)? as i32 as f32 / 65536.0)
+}
+
+/// Read an axis tag at `offset`, with trailing padding spaces removed
+fn tag_at(data: &[u8], offset: usize) -> Option<String> {
+    let tag = data.get(offset..offset + 4)?;
+    Some(String::from_utf8_lossy(tag).trim_end().to_string())
+}
+
+/// Design axes of a variable font in fvar order, leaving out hidden axes.
+/// Empty for static fonts.
+pub fn read_variation_axes(face: &Face) -> Vec<VariationAxis> {
+    let Some(data) = face.raw_face().table(Tag::from_bytes(b"fvar")) else {
+        return Vec::new();
+    };
+    let (Some(axes_offset), Some(axis_count), Some(axis_size)) = (u16_at(data, 4), u16_at(data, 8), u16_at(data, 10)) else {
+        return Vec::new();
+    };
+
+    (0..axis_count as usize)
+        .filter_map(|axis| {
+            let record = axes_offset as usize + axis * axis_size as usize;
+            if u16_at(data, record + 16)? & HIDDEN_AXIS != 0 {
+                return None;
+            }
+            Some(VariationAxis {
+                tag: tag_at(data, record)?,
+                min: fixed_at(data, record + 4)?,
+                default: fixed_at(data, record + 8)?,
+                max: fixed_at(data, record + 12)?,
+            })
+        })
+        .collect()
+}
+
+/// One instance record of the fvar table, before names are resolved
+struct InstanceRecord {
+    subfamily_name_id: u16,
+    postscript_name_id: Option<u16>,
+    coordinates: Vec<f32>,
+}
+
+/// Axis tags and instance records of the fvar table
+fn parse_fvar(data: &[u8]) -> Option<(Vec<String>, Vec<InstanceRecord>)> {
+    let axes_offset = u16_at(data, 4)? as usize;
+    let axis_count = u16_at(data, 8)? as usize;
+    let axis_size = u16_at(data, 10)? as usize;
+    let instance_count = u16_at(data, 12)? as usize;
+    let instance_size = u16_at(data, 14)? as usize;
+
+    let tags = (0..axis_count)
+        .map(|axis| tag_at(data, axes_offset + axis * axis_size))
+        .collect::<Option<Vec<_>>>()?;
+
+    // Instance records follow the axis records; the PostScript name ID is
+    // only present when the record has room for it
+    let instances_offset = axes_offset + axis_count * axis_size;
+    let has_postscript_name = instance_size >= axis_count * 4 + 6;
+    let instances = (0..instance_count)
+        .map(|index| {
+            let record = instances_offset + index * instance_size;
+            let coordinates = (0..axis_count)
+                .map(|axis| fixed_at(data, record + 4 + axis * 4))
+                .collect::<Option<Vec<_>>>()?;
+            let postscript_name_id = if has_postscript_name {
+                u16_at(data, record + 4 + axis_count * 4).filter(|&id| id != 0xFFFF)
+            } else {
+                None
+            };
+            Some(InstanceRecord {
+                subfamily_name_id: u16_at(data, record)?,
+                postscript_name_id,
+                coordinates,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((tags, instances))
+}
+
+/// A STAT axis value: the name of a position (or range) on one or more axes
+struct AxisValue {
+    /// Design axis index and value pairs that must all match
+    conditions: Vec<(usize, AxisMatch)>,
+    flags: u16,
+    name_id: u16,
+}
+
+/// How an axis value matches a coordinate
+enum AxisMatch {
+    Exact(f32),
+    Range(f32, f32),
+}
+
+impl AxisMatch {
+    fn contains(&self, value: f32) -> bool {
+        match *self {
+            AxisMatch::Exact(exact) => (value - exact).abs() < 0.001,
+            AxisMatch::Range(min, max) => value >= min && value <= max,
+        }
+    }
+}
+
+/// Names of positions on the design axes, from the STAT table
+struct StatTable {
+    /// Design axis tags and their ordering in composed names
+    axes: Vec<(String, u16)>,
+    values: Vec<AxisValue>,
+    elided_fallback_name_id: Option<u16>,
+}
+
+fn parse_stat(data: &[u8]) -> Option<StatTable> {
+    let minor_version = u16_at(data, 2)?;
+    let axis_size = u16_at(data, 4)? as usize;
+    let axis_count = u16_at(data, 6)? as usize;
+    let axes_offset = u32_at(data, 8)? as usize;
+    let value_count = u16_at(data, 12)? as usize;
+    let value_offsets = u32_at(data, 14)? as usize;
+    let elided_fallback_name_id = if minor_version >= 1 { u16_at(data, 18) } else { None };
+
+    let axes = (0..axis_count)
+        .map(|axis| {
+            let record = axes_offset + axis * axis_size;
+            Some((tag_at(data, record)?, u16_at(data, record + 6)?))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    // Axis value tables are addressed relative to the offset array itself;
+    // formats we do not know are skipped rather than failing the table
+    let values = (0..value_count)
+        .filter_map(|index| {
+            let table = value_offsets + u16_at(data, value_offsets + index * 2)? as usize;
+            let format = u16_at(data, table)?;
+            let (conditions, flags, name_id) = match format {
+                1 | 3 => (
+                    vec![(u16_at(data, table + 2)? as usize, AxisMatch::Exact(fixed_at(data, table + 8)?))],
+                    u16_at(data, table + 4)?,
+                    u16_at(data, table + 6)?,
+                ),
+                2 => (
+                    vec![(
+                        u16_at(data, table + 2)? as usize,
+                        AxisMatch::Range(fixed_at(data, table + 12)?, fixed_at(data, table + 16)?),
+                    )],
+                    u16_at(data, table + 4)?,
+                    u16_at(data, table + 6)?,
+                ),
+                4 => {
+                    let count = u16_at(data, table + 2)? as usize;
+                    let conditions = (0..count)
+                        .map(|i| {
+                            let record = table + 8 + i * 6;
+                            Some((u16_at(data, record)? as usize, AxisMatch::Exact(fixed_at(data, record + 2)?)))
+                        })
+                        .collect::<Option<Vec<_>>>()?;
+                    (conditions, u16_at(data, table + 4)?, u16_at(data, table + 6)?)
+                }
+                _ => return None,
+            };
+            Some(AxisValue { conditions, flags, name_id })
+        })
+        .collect();
+
+    Some(StatTable { axes, values, elided_fallback_name_id })
+}
+
+impl StatTable {
+    /// Compose an instance name from the axis value names matching its
+    /// coordinates, in the STAT axis ordering, e.g. "Display Black Italic".
+    /// Elidable names ("Regular") are left out; an instance whose names are
+    /// all elided takes the elided fallback name.
+    fn compose_name(&self, face: &Face, fvar_tags: &[String], coordinates: &[f32]) -> Option<String> {
+        // Coordinate of each STAT design axis, looked up by tag
+        let coordinate_of = |axis: usize| -> Option<f32> {
+            let tag = &self.axes.get(axis)?.0;
+            let position = fvar_tags.iter().position(|fvar_tag| fvar_tag == tag)?;
+            coordinates.get(position).copied()
+        };
+
+        let mut parts: Vec<(u16, String)> = Vec::new();
+        let mut matched_any = false;
+        for value in &self.values {
+            let matches = value.conditions.iter()
+                .all(|(axis, axis_match)| coordinate_of(*axis).is_some_and(|coordinate| axis_match.contains(coordinate)));
+            if !matches {
+                continue;
+            }
+            matched_any = true;
+            if value.flags & ELIDABLE_AXIS_VALUE_NAME != 0 {
+                continue;
+            }
+            let Some(name) = read_name(face, value.name_id) else { continue };
+            let ordering = value.conditions.iter()
+                .filter_map(|(axis, _)| self.axes.get(*axis).map(|(_, ordering)| *ordering))
+                .min()
+                .unwrap_or(u16::MAX);
+            if !parts.iter().any(|(_, part)| *part == name) {
+                parts.push((ordering, name));
+            }
+        }
+
+        if !matched_any {
+            return None;
+        }
+        if parts.is_empty() {
+            return self.elided_fallback_name_id
+                .and_then(|name_id| read_name(face, name_id))
+                .or_else(|| Some("Regular".to_string()));
+        }
+
+        parts.sort_by_key(|(ordering, _)| *ordering);
+        Some(parts.into_iter().map(|(_, name)| name).collect::<Vec<_>>().join(" "))
+    }
+}
+
+/// Named instances of a variable font, empty for static fonts.
+///
+/// Names come from each fvar instance record's subfamily name ID. When that
+/// name is missing, or is the font's own subfamily (IDs 2 and 17), the name is
+/// composed from the STAT axis value names matching the instance coordinates.
+pub fn read_named_instances(face: &Face) -> Vec<NamedInstance> {
+    let raw = face.raw_face();
+    let Some((tags, records)) = raw.table(Tag::from_bytes(b"fvar")).and_then(parse_fvar) else {
+        return Vec::new();
+    };
+    let stat = raw.table(Tag::from_bytes(b"STAT")).and_then(parse_stat);
+
+    records.into_iter()
+        .filter_map(|record| {
+            let own_name = !matches!(record.subfamily_name_id, NAME_ID_SUBFAMILY | NAME_ID_TYPOGRAPHIC_SUBFAMILY);
+            let name = own_name.then(|| read_name(face, record.subfamily_name_id)).flatten()
+                .or_else(|| stat.as_ref()?.compose_name(face, &tags, &record.coordinates))
+                .or_else(|| read_name(face, record.subfamily_name_id))?;
+
+            Some(NamedInstance {
+                name,
+                postscript_name: record.postscript_name_id.and_then(|name_id| read_name(face, name_id)),
+                coordinates: tags.iter().cloned().zip(record.coordinates).collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use crate::font::metadata::extract_font_metadata_from_bytes;
+    use crate::models::Config;
+    use crate::testing::{fvar_table, stat_table, TestFont};
+
+    const ELIDABLE: u16 = ELIDABLE_AXIS_VALUE_NAME;
+
+    /// A variable "Inter" with weight and italic axes. Two instances name
+    /// themselves; the others point at the font's subfamily and are named
+    /// from STAT.
+    fn inter() -> Vec<u8> {
+        TestFont::new("Inter", "Regular")
+            .name(256, "Thin")
+            .name(257, "Thin Italic")
+            .name(260, "Black")
+            .name(261, "Italic")
+            .name(262, "Regular")
+            .name(263, "Roman")
+            .name(264, "Upright")
+            .table(b"fvar", fvar_table(
+                &[(b"wght", 100.0, 400.0, 900.0), (b"ital", 0.0, 0.0, 1.0)],
+                &[
+                    (256, vec![100.0, 0.0]),
+                    (257, vec![100.0, 1.0]),
+                    (NAME_ID_SUBFAMILY, vec![900.0, 1.0]),
+                    (NAME_ID_TYPOGRAPHIC_SUBFAMILY, vec![400.0, 0.0]),
+                ],
+            ))
+            .table(b"STAT", stat_table(
+                &[(b"wght", 0), (b"ital", 1)],
+                &[
+                    (1, 0, 261, 1.0),
+                    (0, 0, 260, 900.0),
+                    (0, ELIDABLE, 262, 400.0),
+                    (1, ELIDABLE, 263, 0.0),
+                ],
+                264,
+            ))
+            .build()
+    }
+
+    #[test]
+    fn axes() {
+        let data = inter();
+        let face = Face::parse(&data, 0).unwrap();
+        let axes: Vec<(String, f32, f32, f32)> = read_variation_axes(&face).into_iter()
+            .map(|axis| (axis.tag, axis.min, axis.default, axis.max))
+            .collect();
+        assert_eq!(axes, [("wght".to_string(), 100.0, 400.0, 900.0), ("ital".to_string(), 0.0, 0.0, 1.0)]);
+    }
+
+    #[test]
+    fn instance_names_and_coordinates() {
+        let data = inter();
+        let face = Face::parse(&data, 0).unwrap();
+        let instances: Vec<(String, String)> = read_named_instances(&face).into_iter()
+            .map(|instance| (instance.name.clone(), instance.coordinates_label()))
+            .collect();
+        let expected = [
+            ("Thin", "wght=100 ital=0"),
+            ("Thin Italic", "wght=100 ital=1"),
+            // Composed in STAT axis order, not in axis value order
+            ("Black Italic", "wght=900 ital=1"),
+            // Every name elided, so the elided fallback
+            ("Upright", "wght=400 ital=0"),
+        ];
+        let expected: Vec<(String, String)> = expected.iter()
+            .map(|(name, coordinates)| (name.to_string(), coordinates.to_string()))
+            .collect();
+        assert_eq!(instances, expected);
+    }
+
+    #[test]
+    fn without_stat_the_subfamily_is_used() {
+        let data = TestFont::new("Inter", "Regular")
+            .table(b"fvar", fvar_table(&[(b"wght", 100.0, 400.0, 900.0)], &[(NAME_ID_SUBFAMILY, vec![400.0])]))
+            .build();
+        let face = Face::parse(&data, 0).unwrap();
+        let instances = read_named_instances(&face);
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].name, "Regular");
+    }
+
+    #[test]
+    fn static_fonts_have_no_instances() {
+        let data = TestFont::new("Inter", "Regular").build();
+        let face = Face::parse(&data, 0).unwrap();
+        assert!(read_variation_axes(&face).is_empty());
+        assert!(read_named_instances(&face).is_empty());
+    }
+
+    #[test]
+    fn instances_reach_the_metadata() {
+        let metadata = extract_font_metadata_from_bytes(Path::new("Inter.ttf"), &inter(), &Config::default())
+            .unwrap()
+            .unwrap();
+        assert!(metadata.is_variable);
+        let names: Vec<&str> = metadata.named_instances.iter().map(|instance| instance.name.as_str()).collect();
+        assert_eq!(names, ["Thin", "Thin Italic", "Black Italic", "Upright"]);
+    }
+}
@@ -0,0 +1,52 @@
+use ttf_parser::Face;
+use crate::models::FontLicense;
+use super::metadata::read_name;
+
+/// Name ID of the license description
+const NAME_ID_LICENSE: u16 = 13;
+/// Name ID of the license URL
+const NAME_ID_LICENSE_URL: u16 = 14;
+
+/// Phrases and URL fragments identifying each license, matched against the
+/// lowercased license description and URL
+const LICENSE_MARKERS: [(FontLicense, &[&str]); 3] = [
+    (FontLicense::Ofl, &["open font license", "scripts.sil.org/ofl", "openfontlicense.org"]),
+    (FontLicense::Apache, &["apache license", "apache.org/licenses"]),
+    (FontLicense::Proprietary, &["end user license", "end-user license", "eula", "commercial license", "license agreement", "proprietary"]),
+];
+
+/// Markers of the GPL, which counts as libre only with the font exception
+const GPL_MARKERS: [&str; 3] = ["gnu general public license", "gnu.org/licenses/gpl", "gpl"];
+const FONT_EXCEPTION_MARKERS: [&str; 2] = ["font exception", "special exception"];
+
+/// License of a face from name IDs 13 and 14
+pub fn font_license(face: &Face) -> FontLicense {
+    classify_license(
+        read_name(face, NAME_ID_LICENSE).as_deref(),
+        read_name(face, NAME_ID_LICENSE_URL).as_deref(),
+    )
+}
+
+/// Classify a license description and URL.
+///
+/// The classification is conservative: the text has to match the markers of
+/// exactly one license, so texts matching none, or several (an OFL font with a
+/// commercial EULA link), are `Unknown`. The GPL without the font exception is
+/// `Unknown` too, since documents embedding such fonts may fall under the GPL.
+pub fn classify_license(description: Option<&str>, url: Option<&str>) -> FontLicense {
+    let text = [description, url].into_iter().flatten().collect::<Vec<_>>().join(" ").to_lowercase();
+    let mentions = |markers: &[&str]| markers.iter().any(|marker| text.contains(marker));
+
+    let mut matches: Vec<FontLicense> = LICENSE_MARKERS.iter()
+        .filter(|(_, markers)| mentions(markers))
+        .map(|(license, _)| *license)
+        .collect();
+    if mentions(&GPL_MARKERS) {
+        matches.push(if mentions(&FONT_EXCEPTION_MARKERS) { FontLicense::GplFontException } else { FontLicense::Unknown });
+    }
+
+    match matches.as_slice() {
+        [license] => *license,
+        _ => FontLicense::Unknown,
+    }
+}
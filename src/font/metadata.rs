@@ -1,95 +1,737 @@
 use std::fs;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use font_kit::font::Font;
-use ttf_parser::Face;
-use crate::models::{Config, FontMetadata, NamingPattern};
+use lazy_static::lazy_static;
+use regex::Regex;
+use ttf_parser::{Face, OutlineBuilder, PlatformId, Tag};
+use crate::models::{Config, FontClassification, FontFormat, FontLicense, FontMetadata, FontStyleClass, FontTechnology, MetadataSource, NamingPattern, Slant, SyntheticKind};
 use crate::error::{Result, Error};
-use crate::utils::{log, clean_name, generate_font_filename};
-use super::{foundry::extract_foundry, weight::{determine_weight, is_italic_font}};
+use crate::utils::{log, log_info, log_trace, ascii_name, format_font_name, names_from_filename, tag_cjk_name, transliterate_name};
+use super::{
+    foundry::extract_foundry,
+    instances::{read_named_instances, read_variation_axes},
+    license::font_license,
+    scripts::font_scripts,
+    style::{determine_classification, determine_style_class},
+    weight::{determine_weight, determine_slant, determine_width, is_italic_font, weight_name, WIDTH_NORMAL},
+};
 
 /// Check if a file is a valid font file
 pub fn is_valid_font_file(path: &Path, config: &Config) -> bool {
-    if let Some(ext) = path.extension() {
-        let ext = ext.to_str().unwrap_or("").to_lowercase();
-        if ext == "ttf" || ext == "otf" {
-            if let Ok(mut file) = fs::File::open(path) {
-                let mut header = [0u8; 4];
-                if file.read_exact(&mut header).is_ok() {
-                    let is_valid_magic =
-                        header == [0x00, 0x01, 0x00, 0x00] || // TTF
-                        header == [0x4F, 0x54, 0x54, 0x4F];   // OTF
-
-                    if is_valid_magic {
-                        if let Ok(_face) = Face::parse(&fs::read(path).unwrap_or_default(), 0) {
-                            log(config, format!("Valid font file: {}", path.display()));
-                            return true;
-                        }
-                    }
-                }
-            }
+    if !has_organizable_extension(path) && !is_extensionless_sfnt(path) {
+        log(config, format!("Invalid font file: {}", path.display()));
+        return false;
+    }
+    match fs::read(path) {
+        Ok(data) => is_valid_font_data(path, &data, config),
+        Err(_) => {
+            log(config, format!("Invalid font file: {}", path.display()));
+            false
         }
     }
+}
+
+/// Check if the bytes of the file at `path` are a valid font, without
+/// touching the disk. The extension, the magic and a full parse must all
+/// pass; a file without an extension passes as a single TrueType or
+/// OpenType font. Type 1 fonts are parsed by font-kit, everything else by
+/// ttf-parser.
+pub fn is_valid_font_data(path: &Path, data: &[u8], config: &Config) -> bool {
+    let parses = match data.get(..4) {
+        Some([0x00, 0x01, 0x00, 0x00]) | Some(b"true") | Some(b"OTTO") => Face::parse(data, 0).is_ok(),
+        Some(b"ttcf") if is_collection_font(path) => Face::parse(data, 0).is_ok(),
+        _ if is_type1(path, data) => Font::from_bytes(Arc::new(data.to_vec()), 0).is_ok(),
+        _ => false,
+    };
+
+    if (has_organizable_extension(path) || path.extension().is_none()) && parses {
+        log_trace(config, format!("Valid font file: {}", path.display()));
+        return true;
+    }
     log(config, format!("Invalid font file: {}", path.display()));
     false
 }
 
-/// Extract metadata from a font file
+/// Whether a path has one of the extensions whose fonts are organized
+fn has_organizable_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "ttf" | "otf" | "ttc" | "otc" | "pfb" | "pfa"))
+}
+
+/// Whether a file without an extension starts with the signature of a single
+/// TrueType or OpenType font. Only the signature is read.
+fn is_extensionless_sfnt(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    path.extension().is_none()
+        && fs::File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok()
+        && matches!(&magic, [0x00, 0x01, 0x00, 0x00] | b"true" | b"OTTO")
+}
+
+/// Canonical lowercase extension of a font: `otf` for CFF outlines and `ttf`
+/// for TrueType ones, whatever the file is called. Collections and Type 1
+/// fonts keep their own extension, lowercased.
+pub fn font_extension(metadata: &FontMetadata) -> String {
+    let current = metadata.file_path()
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match metadata.format {
+        _ if COLLECTION_EXTENSIONS.contains(&current.as_str()) => current,
+        FontFormat::TrueType => "ttf".to_string(),
+        FontFormat::OpenType => "otf".to_string(),
+        _ if !current.is_empty() => current,
+        _ => "ttf".to_string(),
+    }
+}
+
+/// Extensions of TrueType and OpenType collections, several faces in one file
+/// sharing the `ttcf` header
+pub const COLLECTION_EXTENSIONS: [&str; 2] = ["ttc", "otc"];
+
+/// Whether a path names a TrueType (`.ttc`) or OpenType (`.otc`) collection
+pub fn is_collection_font(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| COLLECTION_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Path naming face `index` of a collection, `Fonts.otc#2`. This is a
+/// convention of `FontMetadata::original_path`, not a path on disk.
+pub fn face_path(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!("#{}", index));
+    PathBuf::from(name)
+}
+
+/// Split the `#N` face index off a path made by `face_path`. Other paths,
+/// including file names that merely contain `#`, come back whole.
+pub fn split_face_index(path: &Path) -> (PathBuf, Option<u32>) {
+    let text = path.to_string_lossy();
+    if let Some((file, index)) = text.rsplit_once('#') {
+        if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) && is_collection_font(Path::new(file)) {
+            if let Ok(index) = index.parse() {
+                return (PathBuf::from(file), Some(index));
+            }
+        }
+    }
+    (path.to_path_buf(), None)
+}
+
+/// Whether `data` is a Type 1 font with a Type 1 extension
+fn is_type1(path: &Path, data: &[u8]) -> bool {
+    let extension = path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    matches!(extension.as_str(), "pfb" | "pfa") && FontFormat::detect(data) == Some(FontFormat::Type1)
+}
+
+/// Extensions of font files, including formats that are not organized yet
+pub const FONT_EXTENSIONS: [&str; 8] = ["ttf", "otf", "ttc", "otc", "woff", "woff2", "pfb", "pfa"];
+
+/// Extensions of metric files that travel with a Type 1 font of the same
+/// name: Adobe font metrics and Windows printer font metrics
+pub const METRIC_SIDECAR_EXTENSIONS: [&str; 2] = ["afm", "pfm"];
+
+/// Why a file with a font extension cannot be read as a font, e.g.
+/// "bad magic 0x12345678", or `None` when it looks intact. Files without a
+/// font extension are never diagnosed.
+pub fn diagnose_font_file(path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if !FONT_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) => return Some(format!("read error: {}", e)),
+    };
+    if data.len() < 4 {
+        return Some(format!("truncated file ({} bytes)", data.len()));
+    }
+
+    let magic = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let magic_ok = match ext.as_str() {
+        "woff" => &data[..4] == b"wOFF",
+        "woff2" => &data[..4] == b"wOF2",
+        "pfb" | "pfa" => FontFormat::detect(&data) == Some(FontFormat::Type1),
+        _ => magic == 0x0001_0000 || matches!(&data[..4], b"OTTO" | b"true" | b"ttcf"),
+    };
+    if !magic_ok {
+        return Some(format!("bad magic 0x{:08X}", magic));
+    }
+
+    if matches!(ext.as_str(), "pfb" | "pfa") {
+        return Font::from_bytes(Arc::new(data), 0).err().map(|e| format!("Type 1 parse error: {:?}", e));
+    }
+
+    // Compressed web fonts are not parsed yet, a matching signature is all we check
+    if ext.starts_with("woff") {
+        return None;
+    }
+    Face::parse(&data, 0).err().map(|e| format!("Face::parse error: {}", e))
+}
+
+/// Extract metadata from a font file. The file is read once and the same
+/// bytes are validated and parsed.
 pub fn extract_font_metadata(path: &Path, config: &Config) -> Result<Option<FontMetadata>> {
-    log(config, format!("Extracting metadata from: {}", path.display()));
+    log_trace(config, format!("Extracting metadata from: {}", path.display()));
 
-    if !is_valid_font_file(path, config) {
+    // Files that are skipped anyway are not read at all
+    if !has_organizable_extension(path) && !is_extensionless_sfnt(path) {
+        log(config, format!("Invalid font file: {}", path.display()));
         return Ok(None);
     }
+    match fs::read(path) {
+        Ok(data) => extract_metadata_from_data(path, Arc::new(data), config),
+        Err(_) => {
+            log(config, format!("Invalid font file: {}", path.display()));
+            Ok(None)
+        }
+    }
+}
 
-    match Font::from_path(path, 0) {
-        Ok(font) => {
-            let family_name = font.family_name();
-            if family_name.is_empty() {
-                log(config, format!("Empty family name: {}", path.display()));
-                return Ok(None);
-            }
+/// Extract the metadata of every face of a collection, in face order, each
+/// with its `face_path` as the original path. Faces are parsed with
+/// increasing indices until one fails. Other fonts give their one metadata.
+pub fn extract_faces_metadata(path: &Path, config: &Config) -> Result<Vec<FontMetadata>> {
+    if !is_collection_font(path) {
+        return Ok(extract_font_metadata(path, config)?.into_iter().collect());
+    }
+
+    log(config, format!("Extracting metadata from the faces of: {}", path.display()));
+    let data = Arc::new(fs::read(path)?);
+    if !is_valid_font_data(path, &data, config) {
+        return Ok(Vec::new());
+    }
+
+    let mut faces = Vec::new();
+    for index in 0.. {
+        if Face::parse(&data, index).is_err() {
+            break;
+        }
+        if let Some(metadata) = extract_face_metadata(path, data.clone(), index, config)? {
+            faces.push(FontMetadata { original_path: face_path(path, index), ..metadata });
+        }
+    }
+    Ok(faces)
+}
+
+/// Extract metadata from the bytes of the font file at `path`, for callers
+/// that already hold them. `path` decides the extension check and is
+/// recorded as the original path; the file itself is not read.
+pub fn extract_font_metadata_from_bytes(path: &Path, data: &[u8], config: &Config) -> Result<Option<FontMetadata>> {
+    log_trace(config, format!("Extracting metadata from: {}", path.display()));
+    extract_metadata_from_data(path, Arc::new(data.to_vec()), config)
+}
 
-            let subfamily = font.postscript_name()
-                .unwrap_or_else(|| "Regular".to_string())
-                .split('-')
-                .nth(1)
-                .unwrap_or("Regular")
-                .to_string();
-
-            let full_name = font.postscript_name().unwrap_or_else(|| family_name.clone());
-            let foundry = extract_foundry(&font, &family_name);
-            let weight = determine_weight(&subfamily);
-            let is_italic = is_italic_font(&subfamily);
-
-            log(config, format!(
-                "Metadata extracted - Family: {}, Subfamily: {}, Foundry: {}, Weight: {}, Italic: {}",
-                family_name, subfamily, foundry, weight, is_italic
-            ));
-
-            Ok(Some(FontMetadata {
-                family_name,
-                subfamily,
-                full_name,
-                foundry,
-                weight,
-                is_italic,
-                original_path: path.to_path_buf(),
-            }))
+fn extract_metadata_from_data(path: &Path, data: Arc<Vec<u8>>, config: &Config) -> Result<Option<FontMetadata>> {
+    if !is_valid_font_data(path, &data, config) {
+        return Ok(None);
+    }
+
+    // A collection is organized as one file, under its first face
+    extract_face_metadata(path, data, 0, config)
+}
+
+/// Metadata of face `index` of the font at `path`; 0 for fonts that are not collections
+fn extract_face_metadata(path: &Path, data: Arc<Vec<u8>>, index: u32, config: &Config) -> Result<Option<FontMetadata>> {
+    // font-kit shares the buffer rather than opening the file again. Fonts it
+    // rejects are still read when ttf-parser parses them, which tolerates
+    // more out-of-spec tables.
+    let face = Face::parse(&data, index).ok();
+    let font = match Font::from_bytes(data.clone(), index) {
+        Ok(font) => Some(font),
+        Err(e) if face.is_some() => {
+            log(config, format!("font-kit could not load {} ({}), metadata read with ttf-parser", path.display(), e));
+            None
         }
         Err(e) => {
             log(config, format!("Failed to load font: {}", e));
-            Err(Error::Font(format!("Failed to load font: {}", e)))
+            return Err(Error::Font(format!("Failed to load font: {}", e)));
+        }
+    };
+
+    // font-kit's names differ by backend (it may substitute ID 16, or pick a
+    // Mac record on macOS), so names are read from our own name-table reader
+    // and font-kit is only a fallback for fonts without a usable name table
+    let legacy_family = face.as_ref()
+        .and_then(|face| read_name(face, NAME_ID_FAMILY))
+        .or_else(|| font.as_ref().map(Font::family_name))
+        .unwrap_or_default();
+
+    // Precedence contract: the typographic family (name ID 16) always wins
+    // over the legacy family (ID 1) when present, and the typographic
+    // subfamily (ID 17) is used alongside it so both come from one source.
+    let typographic_family = face.as_ref().and_then(|face| read_name(face, NAME_ID_TYPOGRAPHIC_FAMILY));
+    let typographic_subfamily = face.as_ref().and_then(|face| read_name(face, NAME_ID_TYPOGRAPHIC_SUBFAMILY));
+
+    let mut family_name = typographic_family.clone().unwrap_or_else(|| legacy_family.clone());
+    let mut source = MetadataSource::NameTable;
+    // Web fonts stripped of their name table are named by their @font-face rule
+    let css_face = family_name.is_empty().then(|| config.css_face(path)).flatten();
+    if let Some(css_family) = css_face.and_then(|face| face.family.clone()) {
+        log_info(config, format!("Family '{}' taken from the stylesheet: {}", css_family, path.display()));
+        family_name = css_family;
+        source = MetadataSource::Stylesheet;
+    }
+    // Failing that, the file name is a guess, flagged as such in reports
+    let guessed = (family_name.is_empty() && config.filename_fallback)
+        .then(|| names_from_filename(path))
+        .flatten();
+    let filename_subfamily = guessed.map(|(family, subfamily)| {
+        log_info(config, format!("No family name, '{}' ({}) guessed from the file name: {}", family, subfamily, path.display()));
+        family_name = family;
+        source = MetadataSource::Filename;
+        subfamily
+    });
+    if family_name.is_empty() {
+        log(config, format!("Empty family name: {}", path.display()));
+        return Ok(None);
+    }
+
+    let postscript_name = face.as_ref()
+        .and_then(|face| read_name(face, NAME_ID_POSTSCRIPT))
+        .or_else(|| font.as_ref().and_then(Font::postscript_name));
+
+    let subfamily = match (&typographic_family, typographic_subfamily) {
+        (Some(_), Some(subfamily)) => subfamily,
+        _ => postscript_name.as_deref()
+            .unwrap_or("Regular")
+            .split('-')
+            .nth(1)
+            .unwrap_or("Regular")
+            .to_string(),
+    };
+    // A stylesheet's rule names the style a nameless font lacks
+    let subfamily = match css_face {
+        Some(css) if css.weight.is_some() || css.italic => {
+            match (css.weight.map_or("Regular", weight_name), css.italic) {
+                ("Regular", true) => "Italic".to_string(),
+                (weight, true) => format!("{} Italic", weight),
+                (weight, false) => weight.to_string(),
+            }
         }
+        _ => subfamily,
+    };
+    let subfamily = filename_subfamily.unwrap_or(subfamily);
+
+    let full_name = postscript_name.clone().unwrap_or_else(|| family_name.clone());
+    let foundry = extract_foundry(face.as_ref(), &family_name, postscript_name.as_deref(), config);
+    // Fonts font-kit rejects are read from their tables alone, weight included
+    let os2_weight = face.as_ref()
+        .filter(|face| font.is_none() && face.tables().os2.is_some())
+        .map(|face| face.weight().to_number());
+    let weight = css_face.and_then(|face| face.weight)
+        .or(os2_weight)
+        .unwrap_or_else(|| determine_weight(&subfamily));
+    let width = face.as_ref().map(determine_width).unwrap_or(WIDTH_NORMAL);
+    let version = face.as_ref().and_then(read_version);
+    let designer = face.as_ref().and_then(|face| read_name(face, NAME_ID_DESIGNER));
+    let slant = match &face {
+        _ if css_face.is_some_and(|face| face.italic) => Some(Slant::Italic),
+        Some(face) => determine_slant(face, &subfamily),
+        None => is_italic_font(&subfamily).then_some(Slant::Italic),
+    };
+    let is_italic = slant.is_some();
+    let style_class = face.as_ref()
+        .map(determine_style_class)
+        .unwrap_or(FontStyleClass::Unknown);
+    let classification = face.as_ref()
+        .map(determine_classification)
+        .unwrap_or(FontClassification::Unclassified);
+    let scripts = face.as_ref().map(font_scripts).unwrap_or_default();
+    // Collections have their own header, the outlines tell TrueType from OpenType
+    let format = FontFormat::detect(&data)
+        .or_else(|| face.as_ref()
+            .filter(|face| face.raw_face().table(Tag::from_bytes(b"CFF ")).is_some())
+            .map(|_| FontFormat::OpenType))
+        .unwrap_or(FontFormat::TrueType);
+    let technology = match (&face, format) {
+        (_, FontFormat::Type1) => FontTechnology::Type1,
+        (Some(face), _) => font_technology(face),
+        (None, _) => FontTechnology::LegacyTrueType,
+    };
+    let license = face.as_ref().map(font_license).unwrap_or(FontLicense::Unknown);
+    let is_variable = face.as_ref().is_some_and(|face| face.raw_face().table(Tag::from_bytes(b"fvar")).is_some());
+    let axes = face.as_ref().map(read_variation_axes).unwrap_or_default();
+    let named_instances = face.as_ref().map(read_named_instances).unwrap_or_default();
+    let legacy_family_name = (legacy_family != family_name && !legacy_family.is_empty())
+        .then_some(legacy_family);
+    if let Some(legacy) = &legacy_family_name {
+        log_trace(config, format!(
+            "Typographic family '{}' used over legacy family '{}': {}",
+            family_name, legacy, path.display()
+        ));
     }
+
+    // ASCII-only workflows get transliterated names, and so ASCII paths
+    let (family_name, legacy_family_name, subfamily, full_name, foundry, designer) = if config.transliterate {
+        (
+            transliterate_name(&family_name),
+            legacy_family_name.map(|name| transliterate_name(&name)),
+            transliterate_name(&subfamily),
+            transliterate_name(&full_name),
+            transliterate_name(&foundry),
+            designer.map(|name| transliterate_name(&name)),
+        )
+    } else if config.ascii_names {
+        let ascii_family = |name: &str| tag_cjk_name(&ascii_name(name), postscript_name.as_deref());
+        (
+            ascii_family(&family_name),
+            legacy_family_name.map(|name| ascii_family(&name)),
+            ascii_name(&subfamily),
+            ascii_name(&full_name),
+            ascii_name(&foundry),
+            designer.map(|name| ascii_name(&name)),
+        )
+    } else {
+        (family_name, legacy_family_name, subfamily, full_name, foundry, designer)
+    };
+
+    log(config, format!(
+        "Metadata extracted - Family: {}, Subfamily: {}, Foundry: {}, Designer: {}, Weight: {}, Width: {}, Version: {}, Slant: {}, Style: {}, Classification: {}, Scripts: {}, Technology: {}, License: {}",
+        family_name, subfamily, foundry, designer.as_deref().unwrap_or("unknown"),
+        weight, width, version.as_deref().unwrap_or("unknown"),
+        slant.map(|s| s.to_string()).unwrap_or_else(|| "Upright".to_string()),
+        style_class, classification,
+        if scripts.is_empty() { "none".to_string() } else { scripts.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", ") },
+        technology, license
+    ));
+    if is_variable {
+        log(config, format!("Variable font, axes: {}", axes.iter().map(|axis| axis.range_label()).collect::<Vec<_>>().join(", ")));
+    }
+    for instance in &named_instances {
+        log(config, format!("Named instance: {} ({})", instance.name, instance.coordinates_label()));
+    }
+
+    Ok(Some(FontMetadata {
+        family_name,
+        legacy_family_name,
+        subfamily,
+        full_name,
+        foundry,
+        designer,
+        weight,
+        width,
+        version,
+        is_italic,
+        slant,
+        style_class,
+        classification,
+        scripts,
+        format,
+        technology,
+        license,
+        is_variable,
+        axes,
+        named_instances,
+        source,
+        original_path: path.to_path_buf(),
+    }))
 }
 
+/// Ordered decision list of `font_technology`. The first rule with any of its
+/// tables present decides:
+///
+/// 1. Color: COLR, CBDT, sbix or SVG, whatever the outlines
+/// 2. Variable: fvar
+/// 3. OpenType CFF: CFF or CFF2 outlines
+/// 4. OpenType: GSUB or GPOS layout tables
+///
+/// Fonts matching no rule are OpenType when their OS/2 table is version 4 or
+/// later, and Legacy TrueType otherwise. Reports and retention policies rely
+/// on this order, so new rules are appended rather than inserted.
+pub const TECHNOLOGY_RULES: [(FontTechnology, &[&[u8; 4]]); 4] = [
+    (FontTechnology::Color, &[b"COLR", b"CBDT", b"sbix", b"SVG "]),
+    (FontTechnology::Variable, &[b"fvar"]),
+    (FontTechnology::OpenTypeCff, &[b"CFF ", b"CFF2"]),
+    (FontTechnology::OpenType, &[b"GSUB", b"GPOS"]),
+];
+
+/// First OS/2 table version of the OpenType 1.5 era
+const OS2_OPENTYPE_VERSION: u8 = 4;
+
+/// Technology generation of a face, following `TECHNOLOGY_RULES`
+pub fn font_technology(face: &Face) -> FontTechnology {
+    let has_table = |tag: &[u8; 4]| face.raw_face().table(Tag::from_bytes(tag)).is_some();
+    TECHNOLOGY_RULES.iter()
+        .find(|(_, tables)| tables.iter().any(|tag| has_table(tag)))
+        .map(|(technology, _)| *technology)
+        .unwrap_or_else(|| match face.tables().os2 {
+            Some(os2) if os2.version >= OS2_OPENTYPE_VERSION => FontTechnology::OpenType,
+            _ => FontTechnology::LegacyTrueType,
+        })
+}
+
+/// Name ID of the legacy family name
+const NAME_ID_FAMILY: u16 = 1;
+/// Name ID of the typographic family name
+const NAME_ID_TYPOGRAPHIC_FAMILY: u16 = 16;
+/// Name ID of the typographic subfamily name
+const NAME_ID_TYPOGRAPHIC_SUBFAMILY: u16 = 17;
+/// Name ID of the version string ("Version 3.000; ...")
+const NAME_ID_VERSION: u16 = 5;
+/// Name ID of the PostScript name
+const NAME_ID_POSTSCRIPT: u16 = 6;
+/// Name ID of the designer's name
+const NAME_ID_DESIGNER: u16 = 9;
+/// Windows language ID for English (United States)
+const LANGUAGE_ENGLISH_US: u16 = 0x0409;
+
+/// Read a name record in the same way on every operating system.
+///
+/// Records with the requested ID are ranked, and the best decodable,
+/// non-empty one wins (ties go to the record that comes first in the table):
+///
+/// 1. Windows platform, English (United States)
+/// 2. Windows platform, any other English
+/// 3. Unicode platform
+/// 4. Windows platform, any other language
+/// 5. Macintosh platform, English
+/// 6. anything else
+///
+/// font-kit picks different records depending on its backend, so it must not
+/// be used for names that decide where a font is organized.
+pub fn read_name(face: &Face, name_id: u16) -> Option<String> {
+    let mut best: Option<(u8, String)> = None;
+
+    for name in face.names() {
+        if name.name_id != name_id {
+            continue;
+        }
+
+        let decoded = match name.platform_id {
+            PlatformId::Macintosh if name.encoding_id == MAC_ENCODING_ROMAN => Some(decode_mac_roman(name.name)),
+            _ => name.to_string(),
+        };
+        let value = match decoded {
+            Some(value) if !value.trim().is_empty() => value.trim().to_string(),
+            _ => continue,
+        };
+
+        let rank = name_record_rank(name.platform_id, name.language_id);
+        if best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank) {
+            best = Some((rank, value));
+        }
+    }
+
+    best.map(|(_, value)| value)
+}
+
+/// Macintosh encoding ID of Mac Roman, the only Mac encoding names are read in
+const MAC_ENCODING_ROMAN: u16 = 0;
+
+/// Upper half of the Mac Roman character set, bytes 0x80 to 0xFF
+const MAC_ROMAN_HIGH: &str = concat!(
+    "ÄÅÇÉÑÖÜáàâäãåçéèêëíìîïñóòôöõúùûü",
+    "†°¢£§•¶ß®©™´¨≠ÆØ∞±≤≥¥µ∂∑∏π∫ªºΩæø",
+    "¿¡¬√ƒ≈∆«»…\u{A0}ÀÃÕŒœ–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ",
+    "‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ\u{F8FF}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ",
+);
+
+/// Decode a Mac Roman name record; ttf-parser only decodes UTF-16 records
+fn decode_mac_roman(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|&byte| match byte {
+            0x00..=0x7F => char::from(byte),
+            _ => MAC_ROMAN_HIGH.chars().nth(usize::from(byte - 0x80)).unwrap_or('\u{FFFD}'),
+        })
+        .collect()
+}
+
+/// Preference of a name record's platform and language, lower is better
+fn name_record_rank(platform_id: PlatformId, language_id: u16) -> u8 {
+    // Windows language IDs keep the primary language in the low 10 bits
+    const PRIMARY_LANGUAGE_ENGLISH: u16 = 0x09;
+    const MAC_LANGUAGE_ENGLISH: u16 = 0;
+
+    match platform_id {
+        PlatformId::Windows if language_id == LANGUAGE_ENGLISH_US => 0,
+        PlatformId::Windows if language_id & 0x3FF == PRIMARY_LANGUAGE_ENGLISH => 1,
+        PlatformId::Unicode => 2,
+        PlatformId::Windows => 3,
+        PlatformId::Macintosh if language_id == MAC_LANGUAGE_ENGLISH => 4,
+        _ => 5,
+    }
+}
+
+lazy_static! {
+    static ref VERSION_NUMBER: Regex = Regex::new(r"\d+(?:\.\d+)?").unwrap();
+}
+
+/// Properties that decide which of two copies of the same face is better
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontQuality {
+    /// Font version normalized to three decimals, e.g. "3.000"
+    pub version: Option<String>,
+    /// Whether the outlines are CFF (PostScript flavoured OpenType)
+    pub is_cff: bool,
+    /// Number of glyphs from the maxp table
+    pub glyph_count: u16,
+}
+
+impl FontQuality {
+    /// Numeric version for comparisons, missing versions rank lowest
+    pub fn version_number(&self) -> f64 {
+        self.version.as_deref().and_then(|version| version.parse().ok()).unwrap_or(-1.0)
+    }
+}
+
+/// Read the version, outline format and glyph count of a font file
+pub fn read_font_quality(path: &Path) -> Option<FontQuality> {
+    let data = fs::read(path).ok()?;
+    let face = Face::parse(&data, 0).ok()?;
+
+    Some(FontQuality {
+        version: read_version(&face),
+        is_cff: face.tables().cff.is_some() || face.tables().cff2.is_some(),
+        glyph_count: face.number_of_glyphs(),
+    })
+}
+
+/// Font version from name ID 5, falling back to the head table's fontRevision
+pub fn read_version(face: &Face) -> Option<String> {
+    read_name(face, NAME_ID_VERSION)
+        .and_then(|text| normalize_version(&text))
+        .or_else(|| {
+            let head = face.raw_face().table(Tag::from_bytes(b"head"))?;
+            let revision = i32::from_be_bytes(head.get(4..8)?.try_into().ok()?);
+            normalize_version(&format!("{}", revision as f64 / 65536.0))
+        })
+}
+
+/// Normalize a version string such as "Version 2.1; build 33" to "2.100".
+/// Missing or zero versions yield `None`.
+pub fn normalize_version(text: &str) -> Option<String> {
+    let version: f64 = VERSION_NUMBER.find(text)?.as_str().parse().ok()?;
+    (version > 0.0).then(|| format!("{:.3}", version))
+}
+
+/// Characters whose outlines are sampled when looking for synthetic faces
+const SYNTHETIC_SAMPLE_CHARS: &str = "aegnosHORb";
+/// Minimum number of glyphs both faces must have for a comparison
+const SYNTHETIC_MIN_GLYPHS: usize = 4;
+/// Share of compared glyphs that must match for a face to count as synthetic
+const SYNTHETIC_MATCH_RATIO: f32 = 0.9;
+/// Allowed relative difference in glyph height between a face and its sheared copy
+const SYNTHETIC_HEIGHT_TOLERANCE: f32 = 0.02;
+/// Allowed relative difference in outline point count between a face and its sheared copy
+const SYNTHETIC_POINT_TOLERANCE: f32 = 0.5;
+
+/// Outline metrics of one sampled glyph
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GlyphSample {
+    points: usize,
+    height: i16,
+    advance: u16,
+}
+
+/// Outline metrics of a face's sample glyphs, in `SYNTHETIC_SAMPLE_CHARS` order
+#[derive(Debug, Clone)]
+pub struct OutlineSample {
+    glyphs: Vec<Option<GlyphSample>>,
+}
+
+/// Counts the points of a glyph outline
+struct PointCounter(usize);
+
+impl OutlineBuilder for PointCounter {
+    fn move_to(&mut self, _x: f32, _y: f32) { self.0 += 1; }
+    fn line_to(&mut self, _x: f32, _y: f32) { self.0 += 1; }
+    fn quad_to(&mut self, _x1: f32, _y1: f32, _x: f32, _y: f32) { self.0 += 2; }
+    fn curve_to(&mut self, _x1: f32, _y1: f32, _x2: f32, _y2: f32, _x: f32, _y: f32) { self.0 += 3; }
+    fn close(&mut self) {}
+}
+
+/// Sample outline point counts and glyph metrics for synthetic face detection
+pub fn sample_outlines(path: &Path) -> Option<OutlineSample> {
+    let data = fs::read(path).ok()?;
+    let face = Face::parse(&data, 0).ok()?;
+
+    let glyphs = SYNTHETIC_SAMPLE_CHARS.chars()
+        .map(|c| {
+            let glyph = face.glyph_index(c)?;
+            let mut counter = PointCounter(0);
+            let bbox = face.outline_glyph(glyph, &mut counter)?;
+            Some(GlyphSample {
+                points: counter.0,
+                height: bbox.height(),
+                advance: face.glyph_hor_advance(glyph)?,
+            })
+        })
+        .collect();
+
+    Some(OutlineSample { glyphs })
+}
+
+/// Decide whether `candidate` is a mechanically transformed copy of `reference`.
+///
+/// A sheared copy keeps every glyph's height and advance width, and its point
+/// counts stay close (re-adding curve extrema after the shear adds a few). An
+/// emboldened copy keeps point counts exactly along with the advance widths.
+/// Genuine italics and weights are redrawn and almost always change some of them.
+pub fn detect_synthetic(
+    reference: &OutlineSample,
+    reference_metadata: &FontMetadata,
+    candidate: &OutlineSample,
+    candidate_metadata: &FontMetadata,
+) -> Option<SyntheticKind> {
+    let pairs: Vec<(&GlyphSample, &GlyphSample)> = reference.glyphs.iter()
+        .zip(&candidate.glyphs)
+        .filter_map(|(a, b)| Some((a.as_ref()?, b.as_ref()?)))
+        .collect();
+
+    if pairs.len() < SYNTHETIC_MIN_GLYPHS {
+        return None;
+    }
+
+    let all_match = |matches: &dyn Fn(&GlyphSample, &GlyphSample) -> bool| {
+        let count = pairs.iter().filter(|(a, b)| matches(a, b)).count();
+        count as f32 / pairs.len() as f32 >= SYNTHETIC_MATCH_RATIO
+    };
+
+    if !all_match(&|a, b| a.advance == b.advance) {
+        return None;
+    }
+
+    if candidate_metadata.is_italic && !reference_metadata.is_italic {
+        let same_height = all_match(&|a, b| {
+            let tolerance = (a.height.unsigned_abs() as f32 * SYNTHETIC_HEIGHT_TOLERANCE).max(1.0);
+            (a.height as f32 - b.height as f32).abs() <= tolerance
+        });
+        let similar_points = all_match(&|a, b| {
+            a.points.abs_diff(b.points) as f32 <= a.points as f32 * SYNTHETIC_POINT_TOLERANCE
+        });
+        return (same_height && similar_points).then_some(SyntheticKind::Oblique);
+    }
+
+    if candidate_metadata.is_italic == reference_metadata.is_italic
+        && candidate_metadata.weight != reference_metadata.weight
+    {
+        return all_match(&|a, b| a.points == b.points).then_some(SyntheticKind::Weight);
+    }
+
+    None
+}
+
+/// Optical size and ornamental variant suffixes stripped from family names, so
+/// "Garamond Premier Pro Caption" and "Garamond Premier Pro Display" share a folder
+pub const OPTICAL_SIZE_TOKENS: &[&str] = &[
+    "Caption", "Text", "Display", "Subhead", "Micro", "Deck", "Poster", "Banner", "Fine", "Regular", "Book",
+];
+
+/// Shortest name left after stripping `OPTICAL_SIZE_TOKENS`
+const MIN_STRIPPED_FAMILY_LEN: usize = 4;
 
 /// Extract the root family name (the true shared "family" for grouping).
 /// This handles cases where multiple subfamily/variant folders (e.g. "Festivo Basic", "Festivo Sketch1", "Festivo Sketch2") should be grouped under a common root ("Festivo").
 ///
 /// This function now uses a more conservative approach to prevent unrelated fonts from being grouped together.
 /// It preserves the full family name in most cases, only grouping fonts that are clearly part of the same family.
+/// Optical size suffixes from `OPTICAL_SIZE_TOKENS` are stripped first.
 ///
 /// # Arguments
 /// * `family_name` - Raw family name extracted from font metadata
@@ -98,7 +740,17 @@ pub fn extract_font_metadata(path: &Path, config: &Config) -> Result<Option<Font
 /// * `String` - The root family name to use for grouping
 pub fn extract_root_family(family_name: &str) -> String {
     // Split the family name into tokens
-    let tokens: Vec<&str> = family_name.split_whitespace().collect();
+    let mut tokens: Vec<&str> = family_name.split_whitespace().collect();
+
+    // Strip optical size suffixes ("... Caption", "... Display Regular") as long
+    // as a substantial name remains, so a lone "Text" is left as it is
+    while let Some((last, rest)) = tokens.split_last() {
+        if !OPTICAL_SIZE_TOKENS.contains(last) || rest.join(" ").chars().count() < MIN_STRIPPED_FAMILY_LEN {
+            break;
+        }
+        tokens.pop();
+    }
+    let family_name = tokens.join(" ");
     let len = tokens.len();
 
     // For single-word names, return as is
@@ -135,11 +787,22 @@ pub fn extract_root_family(family_name: &str) -> String {
 #[allow(dead_code)]
 pub fn is_already_organized(path: &Path, metadata: &FontMetadata, config: &Config) -> bool {
     // Get parent directories
-    let parent = match path.parent() {
+    let mut parent = match path.parent() {
         Some(p) => p,
         None => return false,
     };
 
+    // When grouping by weight the font sits in a weight folder below its family folder
+    if let Some(weight_folder) = config.weight_folder(metadata.weight, metadata.width_class()) {
+        if parent.file_name().and_then(|n| n.to_str()) != Some(weight_folder.as_str()) {
+            return false;
+        }
+        parent = match parent.parent() {
+            Some(p) => p,
+            None => return false,
+        };
+    }
+
     // Get grandparent for hierarchy check
     let grandparent = parent.parent();
 
@@ -161,8 +824,8 @@ pub fn is_already_organized(path: &Path, metadata: &FontMetadata, config: &Confi
         };
 
         // Check if parent is family name and grandparent is foundry name
-        if clean_name(&metadata.family_name) != parent_name || 
-           clean_name(&metadata.foundry) != grandparent_name {
+        if config.folder_name(&metadata.family_name) != parent_name ||
+           config.folder_name(&metadata.foundry) != grandparent_name {
             return false;
         }
     } else {
@@ -172,13 +835,14 @@ pub fn is_already_organized(path: &Path, metadata: &FontMetadata, config: &Confi
             None => return false,
         };
 
-        if clean_name(&metadata.family_name) != parent_name {
+        if config.folder_name(&metadata.family_name) != parent_name {
             return false;
         }
     }
 
     // Now check filename
-    let expected_filename = generate_font_filename(metadata, &config.naming_pattern);
+    let extension = font_extension(metadata);
+    let expected_filename = format!("{}.{}", config.file_stem(&format_font_name(metadata, &config.naming_pattern), &extension), extension);
     let actual_filename = match path.file_name().and_then(|n| n.to_str()) {
         Some(name) => name,
         None => return false,
@@ -186,3 +850,263 @@ pub fn is_already_organized(path: &Path, metadata: &FontMetadata, config: &Confi
 
     actual_filename == expected_filename
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::NamingPattern;
+    use crate::testing::{collection, italic_outline, sheared_outline, upright_outline, with_sample_glyphs, TempDir, TestFont, PLATFORM_MACINTOSH, PLATFORM_UNICODE, PLATFORM_WINDOWS};
+    use crate::utils::generate_font_filename;
+
+    const LANGUAGE_ENGLISH_UK: u16 = 0x809;
+    const LANGUAGE_FRENCH: u16 = 0x40C;
+
+    fn family_of(font: TestFont) -> Option<String> {
+        let data = font.build();
+        let face = Face::parse(&data, 0).unwrap();
+        read_name(&face, 1)
+    }
+
+    #[test]
+    fn record_ranks() {
+        assert_eq!(name_record_rank(PlatformId::Windows, LANGUAGE_ENGLISH_US), 0);
+        assert_eq!(name_record_rank(PlatformId::Windows, LANGUAGE_ENGLISH_UK), 1);
+        assert_eq!(name_record_rank(PlatformId::Unicode, 0), 2);
+        assert_eq!(name_record_rank(PlatformId::Windows, LANGUAGE_FRENCH), 3);
+        assert_eq!(name_record_rank(PlatformId::Macintosh, 0), 4);
+        assert_eq!(name_record_rank(PlatformId::Macintosh, 2), 5);
+        assert_eq!(name_record_rank(PlatformId::Iso, 0), 5);
+    }
+
+    #[test]
+    fn windows_us_english_wins() {
+        let font = TestFont::new("Roboto", "Regular")
+            .name_record(PLATFORM_MACINTOSH, 0, 1, "Roboto Mac")
+            .name_record(PLATFORM_WINDOWS, LANGUAGE_FRENCH, 1, "Roboto Francais")
+            .name_record(PLATFORM_WINDOWS, LANGUAGE_ENGLISH_UK, 1, "Roboto UK")
+            .name_record(PLATFORM_UNICODE, 0, 1, "Roboto Unicode");
+        assert_eq!(family_of(font).as_deref(), Some("Roboto"));
+    }
+
+    #[test]
+    fn other_english_before_unicode_and_mac() {
+        let font = TestFont::new("Roboto", "Regular")
+            .without_name(1)
+            .name_record(PLATFORM_MACINTOSH, 0, 1, "Roboto Mac")
+            .name_record(PLATFORM_WINDOWS, LANGUAGE_FRENCH, 1, "Roboto Francais")
+            .name_record(PLATFORM_UNICODE, 0, 1, "Roboto Unicode")
+            .name_record(PLATFORM_WINDOWS, LANGUAGE_ENGLISH_UK, 1, "Roboto UK");
+        assert_eq!(family_of(font).as_deref(), Some("Roboto UK"));
+    }
+
+    #[test]
+    fn mac_english_as_last_resort() {
+        let font = TestFont::new("Roboto", "Regular")
+            .without_name(1)
+            .name_record(PLATFORM_MACINTOSH, 2, 1, "Roboto German Mac")
+            .name_record(PLATFORM_MACINTOSH, 0, 1, "Roboto Mac");
+        assert_eq!(family_of(font).as_deref(), Some("Roboto Mac"));
+    }
+
+    #[test]
+    fn mac_roman_upper_half() {
+        assert_eq!(MAC_ROMAN_HIGH.chars().count(), 128);
+        assert_eq!(decode_mac_roman(b"Caf\x8E \xAF\xD5"), "Café Ø’");
+    }
+
+    #[test]
+    fn blank_records_are_skipped() {
+        let font = TestFont::new("Roboto", "Regular")
+            .without_name(1)
+            .name_record(PLATFORM_WINDOWS, LANGUAGE_ENGLISH_US, 1, "  ")
+            .name_record(PLATFORM_WINDOWS, LANGUAGE_FRENCH, 1, " Roboto Francais ");
+        assert_eq!(family_of(font).as_deref(), Some("Roboto Francais"));
+    }
+
+    fn metadata_of(font: TestFont) -> FontMetadata {
+        extract_font_metadata_from_bytes(Path::new("font.ttf"), &font.build(), &Config::default())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn typographic_family_wins_over_a_longer_legacy_family() {
+        let metadata = metadata_of(
+            TestFont::new("Roboto Light", "Regular")
+                .name(NAME_ID_TYPOGRAPHIC_FAMILY, "Roboto")
+                .name(NAME_ID_TYPOGRAPHIC_SUBFAMILY, "Light"),
+        );
+        assert_eq!(metadata.family_name, "Roboto");
+        assert_eq!(metadata.subfamily, "Light");
+        assert_eq!(metadata.legacy_family_name.as_deref(), Some("Roboto Light"));
+    }
+
+    #[test]
+    fn typographic_family_wins_over_a_shorter_legacy_family() {
+        let metadata = metadata_of(
+            TestFont::new("Helvetica", "Bold")
+                .name(NAME_ID_TYPOGRAPHIC_FAMILY, "Helvetica Now Display")
+                .name(NAME_ID_TYPOGRAPHIC_SUBFAMILY, "Bold"),
+        );
+        assert_eq!(metadata.family_name, "Helvetica Now Display");
+        assert_eq!(metadata.subfamily, "Bold");
+        assert_eq!(metadata.legacy_family_name.as_deref(), Some("Helvetica"));
+    }
+
+    #[test]
+    fn legacy_family_is_used_without_a_typographic_one() {
+        let metadata = metadata_of(TestFont::new("Roboto", "Bold"));
+        assert_eq!(metadata.family_name, "Roboto");
+        assert_eq!(metadata.subfamily, "Bold");
+        assert_eq!(metadata.legacy_family_name, None);
+
+        // A typographic family equal to the legacy one is no change either
+        let metadata = metadata_of(TestFont::new("Roboto", "Bold").name(NAME_ID_TYPOGRAPHIC_FAMILY, "Roboto"));
+        assert_eq!(metadata.legacy_family_name, None);
+    }
+
+    #[test]
+    fn sheared_copies_are_synthetic_and_redrawn_italics_are_not() {
+        let tmp = TempDir::new("detect-synthetic");
+        let face = |name: &str, font: TestFont| {
+            let path = font.write(&tmp.join(name));
+            (sample_outlines(&path).unwrap(), extract_font_metadata(&path, &Config::default()).unwrap().unwrap())
+        };
+        let (upright, upright_metadata) = face("upright.ttf", with_sample_glyphs(TestFont::new("Faked", "Regular"), upright_outline));
+        let (sheared, sheared_metadata) = face(
+            "sheared.ttf",
+            with_sample_glyphs(TestFont::new("Faked", "Italic").fs_selection(1), sheared_outline),
+        );
+        let (italic, italic_metadata) = face(
+            "italic.ttf",
+            with_sample_glyphs(TestFont::new("Faked", "Italic").fs_selection(1), italic_outline),
+        );
+
+        assert_eq!(detect_synthetic(&upright, &upright_metadata, &sheared, &sheared_metadata), Some(SyntheticKind::Oblique));
+        assert_eq!(detect_synthetic(&upright, &upright_metadata, &italic, &italic_metadata), None);
+        // An upright face is never an oblique of another
+        assert_eq!(detect_synthetic(&upright, &upright_metadata, &upright, &upright_metadata), None);
+    }
+
+    #[test]
+    fn technology_decision_list() {
+        let cases: [(&str, u16, &[&[u8; 4]], FontTechnology); 9] = [
+            ("OS/2 version 3, no layout", 3, &[], FontTechnology::LegacyTrueType),
+            ("OS/2 version 4, no layout", 4, &[], FontTechnology::OpenType),
+            ("GSUB only", 3, &[b"GSUB"], FontTechnology::OpenType),
+            ("GPOS only", 3, &[b"GPOS"], FontTechnology::OpenType),
+            ("CFF outlines with layout", 3, &[b"CFF ", b"GPOS"], FontTechnology::OpenTypeCff),
+            ("CFF2 outlines", 4, &[b"CFF2"], FontTechnology::OpenTypeCff),
+            ("variable CFF2", 4, &[b"CFF2", b"fvar"], FontTechnology::Variable),
+            ("variable color", 4, &[b"fvar", b"COLR"], FontTechnology::Color),
+            ("bitmap color", 3, &[b"CBDT"], FontTechnology::Color),
+        ];
+        for (description, os2_version, tables, expected) in cases {
+            let font = tables.iter()
+                .fold(TestFont::new("Roboto", "Regular").os2_version(os2_version), |font, tag| font.table(tag, vec![0; 8]));
+            let data = font.build();
+            let face = Face::parse(&data, 0).unwrap();
+            assert_eq!(font_technology(&face), expected, "{}", description);
+        }
+    }
+
+    #[test]
+    fn every_color_table_is_a_rule() {
+        for tag in [b"COLR", b"CBDT", b"sbix", b"SVG "] {
+            let data = TestFont::new("Roboto", "Regular").table(tag, vec![0; 8]).build();
+            let face = Face::parse(&data, 0).unwrap();
+            assert_eq!(font_technology(&face), FontTechnology::Color, "{}", String::from_utf8_lossy(tag));
+        }
+    }
+
+    #[test]
+    fn technology_reaches_metadata_and_names() {
+        let data = TestFont::new("Roboto", "Regular").table(b"GSUB", vec![0; 8]).build();
+        let metadata = extract_font_metadata_from_bytes(Path::new("Roboto.ttf"), &data, &Config::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.technology, FontTechnology::OpenType);
+        let pattern = NamingPattern::Custom("%Family% (%Tech%)".to_string());
+        assert_eq!(format_font_name(&metadata, &pattern), "Roboto (OpenType)");
+    }
+
+    #[test]
+    fn optical_size_suffixes_are_stripped() {
+        let cases = [
+            ("Garamond Premier Pro Caption", "Garamond Premier Pro"),
+            ("Garamond Premier Pro Display", "Garamond Premier Pro"),
+            ("Garamond Premier Pro Text", "Garamond Premier Pro"),
+            ("Garamond Premier Pro Subhead Regular", "Garamond Premier Pro"),
+            ("Garamond Premier Pro", "Garamond Premier Pro"),
+            // Nothing substantial would be left
+            ("Text", "Text"),
+            ("Ivy Display", "Ivy Display"),
+            // Only whole trailing tokens count
+            ("Captionista", "Captionista"),
+            ("Display Serif", "Display Serif"),
+        ];
+        for (family, root) in cases {
+            assert_eq!(extract_root_family(family), root, "{}", family);
+        }
+    }
+
+    #[test]
+    fn short_second_words_join_the_root_family() {
+        assert_eq!(extract_root_family("Breul A"), "Breul");
+        assert_eq!(extract_root_family("Roboto 2"), "Roboto");
+        assert_eq!(extract_root_family("Hygge Sans"), "Hygge Sans");
+    }
+
+    /// A collection of SF Pro's upright, bold and italic faces
+    fn sf_pro_collection(path: &Path) {
+        let faces = [
+            TestFont::new("SF Pro", "Regular").build(),
+            TestFont::new("SF Pro", "Bold").weight(700).build(),
+            TestFont::new("SF Pro", "Italic").fs_selection(1).build(),
+        ];
+        fs::write(path, collection(&faces)).unwrap();
+    }
+
+    #[test]
+    fn collection_extensions() {
+        for (name, expected) in [("SF-Pro.otc", true), ("Fonts.TTC", true), ("SF-Pro.otf", false), ("otc", false)] {
+            assert_eq!(is_collection_font(Path::new(name)), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn face_index_round_trip() {
+        let path = Path::new("/fonts/SF-Pro.otc");
+        assert_eq!(face_path(path, 2), Path::new("/fonts/SF-Pro.otc#2"));
+        assert_eq!(split_face_index(&face_path(path, 2)), (path.to_path_buf(), Some(2)));
+
+        // Only a number after a collection's extension is a face index
+        for whole in ["/fonts/Font #1.ttf", "/fonts/SF-Pro.otf#2", "/fonts/SF-Pro.otc#", "/fonts/SF-Pro.otc#b"] {
+            assert_eq!(split_face_index(Path::new(whole)), (PathBuf::from(whole), None), "{}", whole);
+        }
+    }
+
+    #[test]
+    fn every_face_of_an_otc_is_read() {
+        let tmp = TempDir::new("collections");
+        let path = tmp.join("SF-Pro.otc");
+        sf_pro_collection(&path);
+        let config = Config::default();
+        assert!(is_valid_font_file(&path, &config));
+
+        let faces = extract_faces_metadata(&path, &config).unwrap();
+        let summary: Vec<(&str, u16, bool, Option<u32>)> = faces.iter()
+            .map(|face| (face.subfamily.as_str(), face.weight, face.is_italic, face.face_index()))
+            .collect();
+        assert_eq!(summary, [("Regular", 400, false, Some(0)), ("Bold", 700, false, Some(1)), ("Italic", 400, true, Some(2))]);
+        assert!(faces.iter().all(|face| face.file_path() == path && face.family_name == "SF Pro"));
+        assert_eq!(faces[1].original_path, tmp.join("SF-Pro.otc#1"));
+
+        // The face index stays out of file names
+        assert_eq!(generate_font_filename(&faces[1], &NamingPattern::FamilySubfamily), "SF Pro (Bold).otc");
+
+        // Organized as one file, under its first face
+        let first = extract_font_metadata(&path, &config).unwrap().unwrap();
+        assert_eq!((first.subfamily.as_str(), first.face_index()), ("Regular", None));
+    }
+}
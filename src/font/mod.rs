@@ -3,6 +3,13 @@
 pub mod metadata;
 pub mod foundry;
 pub mod weight;
+pub mod style;
+pub mod instances;
+pub mod scripts;
+pub mod license;
+pub mod cache;
+#[cfg(feature = "fingerprint")]
+pub mod fingerprint;
 
 // Public functions are imported directly in consumer code
 
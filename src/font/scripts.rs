@@ -0,0 +1,85 @@
+use std::collections::{BTreeSet, HashMap};
+use ttf_parser::Face;
+use crate::models::FontScript;
+
+/// Representative letters per script. A font covers a script when it maps at
+/// least `SCRIPT_SAMPLE_RATIO` of one of its samples; CJK has one sample each
+/// for Han, Kana and Hangul, so Japanese-only or Korean-only fonts count too.
+pub const SCRIPT_SAMPLES: [(FontScript, &str); 10] = [
+    (FontScript::Latin, "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz"),
+    (FontScript::Greek, "ΑΒΓΔΕΖΗΘΙΚΛΜΝΞΟΠΡΣΤΥΦΧΨΩαβγδεζηθικλμνξοπρστυφχψω"),
+    (FontScript::Cyrillic, "АБВГДЕЖЗИЙКЛМНОПРСТУФХЦЧШЩЪЫЬЭЮЯабвгдежзийклмнопрстуфхцчшщъыьэюя"),
+    (FontScript::Hebrew, "אבגדהוזחטיכלמנסעפצקרשת"),
+    (FontScript::Arabic, "ابتثجحخدذرزسشصضطظعغفقكلمنهوي"),
+    (FontScript::Devanagari, "अआइईउऊएऐओऔकखगघङचछजझञटठडढणतथदधनपफबभमयरलवशषसह"),
+    (FontScript::Thai, "กขคงจฉชซญดตถทธนบปผพฟภมยรลวศษสหอฮ"),
+    (FontScript::Cjk, "的一是不了人我在有他这中大来上国个到说们为子和你地出道也时年"),
+    (FontScript::Cjk, "あいうえおかきくけこさしすせそアイウエオカキクケコサシスセソ"),
+    (FontScript::Cjk, "가나다라마바사아자차카타파하고노도로모보소오조초코토포호"),
+];
+
+/// Share of a sample a font has to map to cover the script
+const SCRIPT_SAMPLE_RATIO: f32 = 0.8;
+
+/// Unicode blocks counted to find the dominant script of a font
+const SCRIPT_RANGES: [(FontScript, u32, u32); 19] = [
+    (FontScript::Latin, 0x0041, 0x024F),
+    (FontScript::Latin, 0x1E00, 0x1EFF),
+    (FontScript::Greek, 0x0370, 0x03FF),
+    (FontScript::Greek, 0x1F00, 0x1FFF),
+    (FontScript::Cyrillic, 0x0400, 0x052F),
+    (FontScript::Hebrew, 0x0590, 0x05FF),
+    (FontScript::Arabic, 0x0600, 0x06FF),
+    (FontScript::Arabic, 0x0750, 0x077F),
+    (FontScript::Arabic, 0xFB50, 0xFDFF),
+    (FontScript::Arabic, 0xFE70, 0xFEFF),
+    (FontScript::Devanagari, 0x0900, 0x097F),
+    (FontScript::Thai, 0x0E00, 0x0E7F),
+    // Hangul Jamo, Kana, CJK Extension A, CJK Unified Ideographs
+    (FontScript::Cjk, 0x1100, 0x11FF),
+    (FontScript::Cjk, 0x3040, 0x30FF),
+    (FontScript::Cjk, 0x3400, 0x4DBF),
+    (FontScript::Cjk, 0x4E00, 0x9FFF),
+    // Hangul syllables, compatibility ideographs, supplementary ideographs
+    (FontScript::Cjk, 0xAC00, 0xD7AF),
+    (FontScript::Cjk, 0xF900, 0xFAFF),
+    (FontScript::Cjk, 0x20000, 0x2FFFF),
+];
+
+/// Scripts a face covers, most mapped characters first.
+///
+/// Coverage is decided by the `SCRIPT_SAMPLES` letters, and the order by how
+/// many characters of each script's Unicode blocks the cmap maps, so a CJK font
+/// with a basic Latin set lists CJK first.
+pub fn font_scripts(face: &Face) -> Vec<FontScript> {
+    let covered: BTreeSet<FontScript> = SCRIPT_SAMPLES.iter()
+        .filter(|(_, sample)| {
+            let mapped = sample.chars().filter(|c| face.glyph_index(*c).is_some()).count();
+            mapped as f32 >= sample.chars().count() as f32 * SCRIPT_SAMPLE_RATIO
+        })
+        .map(|(script, _)| *script)
+        .collect();
+    if covered.is_empty() {
+        return Vec::new();
+    }
+
+    let mut codepoints: BTreeSet<u32> = BTreeSet::new();
+    if let Some(cmap) = face.tables().cmap {
+        for subtable in cmap.subtables.into_iter().filter(|subtable| subtable.is_unicode()) {
+            subtable.codepoints(|codepoint| {
+                codepoints.insert(codepoint);
+            });
+        }
+    }
+
+    let mut counts: HashMap<FontScript, usize> = HashMap::new();
+    for codepoint in codepoints {
+        if let Some((script, _, _)) = SCRIPT_RANGES.iter().find(|(_, start, end)| (*start..=*end).contains(&codepoint)) {
+            *counts.entry(*script).or_default() += 1;
+        }
+    }
+
+    let mut scripts: Vec<FontScript> = covered.into_iter().collect();
+    scripts.sort_by_key(|script| std::cmp::Reverse(counts.get(script).copied().unwrap_or(0)));
+    scripts
+}
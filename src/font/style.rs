@@ -0,0 +1,172 @@
+use ttf_parser::{Face, Tag};
+use crate::models::{FontClassification, FontStyleClass};
+
+/// Offset of the 10-byte PANOSE classification in the OS/2 table
+const PANOSE_OFFSET: usize = 32;
+
+/// Offset of sFamilyClass in the OS/2 table, class ID in the high byte
+const FAMILY_CLASS_OFFSET: usize = 30;
+
+/// PANOSE family kinds (byte 0)
+const FAMILY_LATIN_TEXT: u8 = 2;
+const FAMILY_LATIN_HAND_WRITTEN: u8 = 3;
+const FAMILY_LATIN_DECORATIVE: u8 = 4;
+const FAMILY_LATIN_SYMBOL: u8 = 5;
+
+/// PANOSE Latin Text proportion value for monospaced designs (byte 3)
+const PROPORTION_MONOSPACED: u8 = 9;
+
+/// Read the raw PANOSE bytes from the OS/2 table
+pub fn read_panose(face: &Face) -> Option<[u8; 10]> {
+    let os2 = face.raw_face().table(Tag::from_bytes(b"OS/2"))?;
+    os2.get(PANOSE_OFFSET..PANOSE_OFFSET + 10)?.try_into().ok()
+}
+
+/// Classify a face from its PANOSE bytes.
+///
+/// Only the Latin Text family kind carries serif and proportion information;
+/// the other kinds map directly to a class. Fonts without usable PANOSE data
+/// are still recognised as monospaced from `post.isFixedPitch`.
+pub fn determine_style_class(face: &Face) -> FontStyleClass {
+    let class = read_panose(face).map(classify_panose).unwrap_or(FontStyleClass::Unknown);
+
+    if class == FontStyleClass::Unknown && face.is_monospaced() {
+        FontStyleClass::Monospace
+    } else {
+        class
+    }
+}
+
+/// Map PANOSE bytes to a style class
+pub fn classify_panose(panose: [u8; 10]) -> FontStyleClass {
+    let family_kind = panose[0];
+    let serif_style = panose[1];
+    let proportion = panose[3];
+
+    match family_kind {
+        FAMILY_LATIN_TEXT if proportion == PROPORTION_MONOSPACED => FontStyleClass::Monospace,
+        FAMILY_LATIN_TEXT => match serif_style {
+            // Cove, obtuse cove, square, thin, bone, exaggerated, triangle, flared
+            2..=10 | 14 => FontStyleClass::Serif,
+            // Normal, obtuse and perpendicular sans, rounded
+            11..=13 | 15 => FontStyleClass::SansSerif,
+            _ => FontStyleClass::Unknown,
+        },
+        FAMILY_LATIN_HAND_WRITTEN => FontStyleClass::Script,
+        FAMILY_LATIN_DECORATIVE => FontStyleClass::Decorative,
+        FAMILY_LATIN_SYMBOL => FontStyleClass::Symbol,
+        _ => FontStyleClass::Unknown,
+    }
+}
+
+/// Read the IBM font class ID (the high byte of OS/2 sFamilyClass)
+pub fn read_family_class(face: &Face) -> Option<u8> {
+    let os2 = face.raw_face().table(Tag::from_bytes(b"OS/2"))?;
+    os2.get(FAMILY_CLASS_OFFSET).copied()
+}
+
+/// Classify a face from its PANOSE bytes, OS/2 family class and fixed pitch flag.
+///
+/// Fixed pitch faces are Mono. Otherwise PANOSE and the family class each
+/// suggest a classification; when both do they must agree (a serif and a slab
+/// reading agree as Slab), and faces with no suggestion or a contradictory one
+/// are Unclassified rather than guessed.
+pub fn determine_classification(face: &Face) -> FontClassification {
+    let panose = read_panose(face);
+    if face.is_monospaced() || panose.is_some_and(|panose| panose[0] == FAMILY_LATIN_TEXT && panose[3] == PROPORTION_MONOSPACED) {
+        return FontClassification::Mono;
+    }
+
+    let from_panose = panose.and_then(classification_from_panose);
+    let from_family_class = read_family_class(face).and_then(classification_from_family_class);
+    match (from_panose, from_family_class) {
+        (Some(a), Some(b)) if a == b => a,
+        (Some(FontClassification::Serif), Some(FontClassification::Slab))
+        | (Some(FontClassification::Slab), Some(FontClassification::Serif)) => FontClassification::Slab,
+        (Some(_), Some(_)) | (None, None) => FontClassification::Unclassified,
+        (Some(class), None) | (None, Some(class)) => class,
+    }
+}
+
+/// Classification suggested by PANOSE bytes, `None` when they are empty
+/// or do not say
+pub fn classification_from_panose(panose: [u8; 10]) -> Option<FontClassification> {
+    match panose[0] {
+        FAMILY_LATIN_TEXT => match panose[1] {
+            // Square cove, obtuse square cove, square
+            4..=6 => Some(FontClassification::Slab),
+            // Cove, obtuse cove, thin, bone, exaggerated, triangle, flared
+            2 | 3 | 7..=10 | 14 => Some(FontClassification::Serif),
+            // Normal, obtuse and perpendicular sans, rounded
+            11..=13 | 15 => Some(FontClassification::Sans),
+            _ => None,
+        },
+        FAMILY_LATIN_HAND_WRITTEN => Some(FontClassification::Script),
+        FAMILY_LATIN_DECORATIVE => Some(FontClassification::Display),
+        _ => None,
+    }
+}
+
+/// Classification suggested by an IBM font class ID, `None` for no
+/// classification, symbolic and reserved classes
+pub fn classification_from_family_class(class_id: u8) -> Option<FontClassification> {
+    match class_id {
+        // Oldstyle, transitional, modern and freeform serifs
+        1..=3 | 7 => Some(FontClassification::Serif),
+        // Clarendon and slab serifs
+        4 | 5 => Some(FontClassification::Slab),
+        8 => Some(FontClassification::Sans),
+        9 => Some(FontClassification::Display),
+        10 => Some(FontClassification::Script),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestFont;
+
+    const COURIER_NEW: [u8; 10] = [2, 7, 3, 9, 2, 2, 5, 2, 4, 4];
+    const ARIAL: [u8; 10] = [2, 11, 6, 4, 2, 2, 2, 2, 2, 4];
+    const TIMES_NEW_ROMAN: [u8; 10] = [2, 2, 6, 3, 5, 4, 5, 2, 3, 4];
+
+    fn style_class(font: TestFont) -> FontStyleClass {
+        let data = font.build();
+        determine_style_class(&Face::parse(&data, 0).unwrap())
+    }
+
+    #[test]
+    fn courier_new_is_monospace() {
+        let font = TestFont::new("Courier New", "Regular").panose(COURIER_NEW);
+        assert_eq!(style_class(font), FontStyleClass::Monospace);
+    }
+
+    #[test]
+    fn fixed_pitch_without_panose_is_monospace() {
+        let font = TestFont::new("Fixedsys", "Regular").fixed_pitch();
+        assert_eq!(style_class(font), FontStyleClass::Monospace);
+    }
+
+    #[test]
+    fn no_panose_and_proportional_is_unknown() {
+        assert_eq!(style_class(TestFont::new("Mystery", "Regular")), FontStyleClass::Unknown);
+    }
+
+    #[test]
+    fn panose_classes() {
+        let cases = [
+            (ARIAL, FontStyleClass::SansSerif),
+            (TIMES_NEW_ROMAN, FontStyleClass::Serif),
+            (COURIER_NEW, FontStyleClass::Monospace),
+            ([3, 1, 1, 1, 1, 1, 1, 1, 1, 1], FontStyleClass::Script),
+            ([4, 1, 1, 1, 1, 1, 1, 1, 1, 1], FontStyleClass::Decorative),
+            ([5, 1, 1, 1, 1, 1, 1, 1, 1, 1], FontStyleClass::Symbol),
+            ([2, 0, 6, 4, 0, 0, 0, 0, 0, 0], FontStyleClass::Unknown),
+            ([0; 10], FontStyleClass::Unknown),
+        ];
+        for (panose, expected) in cases {
+            assert_eq!(classify_panose(panose), expected, "{:?}", panose);
+        }
+    }
+}
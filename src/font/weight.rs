@@ -1,3 +1,6 @@
+use ttf_parser::{Face, Tag};
+use crate::models::{Slant, WidthClass};
+
 /// Determine the weight value from a subfamily name
 pub fn determine_weight(subfamily: &str) -> u16 {
     let subfamily_lower = subfamily.to_lowercase();
@@ -17,9 +20,185 @@ pub fn determine_weight(subfamily: &str) -> u16 {
     }
 }
 
+/// Conventional name of a numeric weight, e.g. "Bold" for 700
+pub fn weight_name(weight: u16) -> &'static str {
+    match weight {
+        0..=149 => "Thin",
+        150..=249 => "ExtraLight",
+        250..=349 => "Light",
+        350..=449 => "Regular",
+        450..=549 => "Medium",
+        550..=649 => "SemiBold",
+        650..=749 => "Bold",
+        750..=849 => "ExtraBold",
+        850..=924 => "Black",
+        _ => "ExtraBlack",
+    }
+}
+
+/// OS/2 usWidthClass of a normal-width face
+pub const WIDTH_NORMAL: u16 = 5;
+
+/// Read the OS/2 usWidthClass (1-9), defaulting to normal width
+pub fn determine_width(face: &Face) -> u16 {
+    face.tables().os2
+        .map(|os2| os2.width().to_number())
+        .unwrap_or(WIDTH_NORMAL)
+}
+
+/// Width class of a face from its OS/2 usWidthClass, normal without an OS/2 table
+pub fn determine_width_class(face: &Face) -> WidthClass {
+    WidthClass::from_number(determine_width(face))
+}
+
+/// Human-readable name of a width class, empty for normal width
+pub fn width_class_name(width: u16) -> &'static str {
+    WidthClass::from_number(width).name()
+}
+
 /// Check if a font is italic based on its subfamily name
 pub fn is_italic_font(subfamily: &str) -> bool {
     let subfamily_lower = subfamily.to_lowercase();
     subfamily_lower.contains("italic") || subfamily_lower.contains("oblique")
 }
 
+
+/// fsSelection bit 0: the face is italic
+const FS_SELECTION_ITALIC: u16 = 1 << 0;
+/// fsSelection bit 9: the face is oblique (OS/2 version 4 and later)
+const FS_SELECTION_OBLIQUE: u16 = 1 << 9;
+/// macStyle bit 1: the face is italic
+const MAC_STYLE_ITALIC: u16 = 1 << 1;
+
+/// Read a big-endian u16 from a table at the given offset
+fn read_u16(table: &[u8], offset: usize) -> Option<u16> {
+    table.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Determine the slant of a face from its OS/2 fsSelection and head macStyle
+/// flags combined with the subfamily name heuristic.
+///
+/// Any italic signal marks the face as slanted; it is reported as oblique when
+/// fsSelection bit 9 is set or the subfamily name says "Oblique", and as italic
+/// otherwise.
+pub fn determine_slant(face: &Face, subfamily: &str) -> Option<Slant> {
+    let raw = face.raw_face();
+
+    let (fs_selection, os2_version) = raw.table(Tag::from_bytes(b"OS/2"))
+        .map(|os2| (read_u16(os2, 62).unwrap_or(0), read_u16(os2, 0).unwrap_or(0)))
+        .unwrap_or((0, 0));
+    let mac_style = raw.table(Tag::from_bytes(b"head"))
+        .and_then(|head| read_u16(head, 44))
+        .unwrap_or(0);
+
+    let flag_italic = fs_selection & FS_SELECTION_ITALIC != 0 || mac_style & MAC_STYLE_ITALIC != 0;
+    let flag_oblique = os2_version >= 4 && fs_selection & FS_SELECTION_OBLIQUE != 0;
+    let name_oblique = subfamily.to_lowercase().contains("oblique");
+
+    if flag_oblique || (name_oblique && (flag_italic || is_italic_font(subfamily))) {
+        Some(Slant::Oblique)
+    } else if flag_italic || is_italic_font(subfamily) {
+        Some(Slant::Italic)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use crate::font::metadata::extract_font_metadata_from_bytes;
+    use crate::models::{Config, NamingPattern};
+    use crate::testing::TestFont;
+    use crate::utils::format_font_name;
+
+    fn slant_of(font: TestFont, subfamily: &str) -> Option<Slant> {
+        let data = font.build();
+        let face = Face::parse(&data, 0).unwrap();
+        determine_slant(&face, subfamily)
+    }
+
+    #[test]
+    fn italic_from_fs_selection() {
+        let font = TestFont::new("Roboto", "Regular").fs_selection(FS_SELECTION_ITALIC);
+        assert_eq!(slant_of(font, "Regular"), Some(Slant::Italic));
+    }
+
+    #[test]
+    fn italic_from_mac_style() {
+        let font = TestFont::new("Roboto", "Regular").mac_style(MAC_STYLE_ITALIC);
+        assert_eq!(slant_of(font, "Regular"), Some(Slant::Italic));
+    }
+
+    #[test]
+    fn oblique_from_fs_selection_in_os2_version_4() {
+        let font = TestFont::new("Roboto", "Regular").fs_selection(FS_SELECTION_OBLIQUE);
+        assert_eq!(slant_of(font, "Regular"), Some(Slant::Oblique));
+    }
+
+    #[test]
+    fn oblique_bit_is_ignored_before_os2_version_4() {
+        let font = TestFont::new("Roboto", "Regular").os2_version(3).fs_selection(FS_SELECTION_OBLIQUE);
+        assert_eq!(slant_of(font, "Regular"), None);
+    }
+
+    #[test]
+    fn oblique_from_name_with_italic_flag() {
+        let font = TestFont::new("Roboto", "Bold Oblique").fs_selection(FS_SELECTION_ITALIC);
+        assert_eq!(slant_of(font, "Bold Oblique"), Some(Slant::Oblique));
+    }
+
+    #[test]
+    fn italic_from_name_without_flags() {
+        assert_eq!(slant_of(TestFont::new("Roboto", "Italic"), "Italic"), Some(Slant::Italic));
+    }
+
+    #[test]
+    fn upright_without_flags_or_name() {
+        assert_eq!(slant_of(TestFont::new("Roboto", "Bold"), "Bold"), None);
+    }
+
+    #[test]
+    fn metadata_of_a_flagged_face_is_italic() {
+        let data = TestFont::new("Roboto", "Regular").fs_selection(FS_SELECTION_ITALIC).build();
+        let metadata = extract_font_metadata_from_bytes(Path::new("Roboto.ttf"), &data, &Config::default())
+            .unwrap()
+            .unwrap();
+        assert!(metadata.is_italic);
+        assert_eq!(metadata.slant, Some(Slant::Italic));
+    }
+
+    fn width_class_of(font: TestFont) -> WidthClass {
+        let data = font.build();
+        determine_width_class(&Face::parse(&data, 0).unwrap())
+    }
+
+    #[test]
+    fn width_class_from_os2() {
+        let cases = [
+            (1, WidthClass::UltraCondensed),
+            (3, WidthClass::Condensed),
+            (4, WidthClass::SemiCondensed),
+            (5, WidthClass::Normal),
+            (7, WidthClass::Expanded),
+            (9, WidthClass::UltraExpanded),
+            // Out of range values are normal
+            (0, WidthClass::Normal),
+            (12, WidthClass::Normal),
+        ];
+        for (width, expected) in cases {
+            assert_eq!(width_class_of(TestFont::new("Roboto", "Regular").width(width)), expected, "{}", width);
+        }
+    }
+
+    #[test]
+    fn condensed_face_is_named_by_width() {
+        let data = TestFont::new("Roboto", "Condensed Bold").weight(700).width(3).build();
+        let metadata = extract_font_metadata_from_bytes(Path::new("Roboto-CondensedBold.ttf"), &data, &Config::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.width_class(), WidthClass::Condensed);
+        assert_eq!(format_font_name(&metadata, &NamingPattern::FamilyWeight), "Roboto Condensed 700");
+    }
+}
@@ -0,0 +1,200 @@
+//! Static HTML gallery of a library with a live sample of every family (`--html-gallery`)
+//!
+//! Unlike the preview, the gallery is built from the fonts on disk and loads
+//! them with `@font-face` rules, by paths relative to the page, so the page
+//! renders every family as long as it stays next to the library.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::error::Result;
+use crate::inventory::InventoryEntry;
+use crate::models::FontStyleClass;
+use crate::utils::logging::civil_date;
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 72rem; padding: 0 1rem; color: #222; }
+h1 { margin-bottom: 0.2rem; }
+.summary { color: #666; margin-top: 0; }
+#search { width: 100%; font-size: 1rem; padding: 0.5rem; margin: 1rem 0; box-sizing: border-box; }
+.cards { display: grid; grid-template-columns: repeat(auto-fill, minmax(20rem, 1fr)); gap: 1rem; }
+.card { border: 1px solid #ddd; border-radius: 6px; padding: 0.8rem 1rem; }
+.card h2 { font-size: 1.1rem; margin: 0 0 0.4rem; }
+.sample { font-size: 1.6rem; margin: 0.4rem 0; overflow-wrap: anywhere; }
+dl { display: grid; grid-template-columns: auto 1fr; gap: 0.1rem 0.8rem; margin: 0.4rem 0; font-size: 0.9em; }
+dt { color: #666; }
+dd { margin: 0; }
+summary { cursor: pointer; color: #666; font-size: 0.9em; }
+pre { font-size: 0.8em; overflow-x: auto; background: #f6f6f6; padding: 0.5rem; }
+";
+
+const SCRIPT: &str = "\
+var search = document.getElementById('search');
+var cards = document.querySelectorAll('.card');
+var shown = document.getElementById('shown');
+search.addEventListener('input', function () {
+  var query = search.value.trim().toLowerCase();
+  var visible = 0;
+  cards.forEach(function (card) {
+    var match = !query || card.dataset.family.indexOf(query) !== -1;
+    card.hidden = !match;
+    if (match) { visible++; }
+  });
+  shown.textContent = query ? visible + ' shown' : '';
+});
+";
+
+/// Text every family is sampled with
+const SAMPLE: &str = "The quick brown fox jumps over the lazy dog";
+
+/// Write the gallery of the fonts of `inventory`, found in `organized_dir`,
+/// to `output`, with one card per family
+pub fn generate_html_gallery(organized_dir: &Path, inventory: &[InventoryEntry], output: &Path) -> Result<()> {
+    let output = std::path::absolute(output)?;
+    let page_dir = output.parent().unwrap_or(Path::new("/"));
+    fs::write(&output, render_gallery(organized_dir, inventory, page_dir))?;
+    Ok(())
+}
+
+/// The gallery page, loading the fonts by paths relative to `page_dir`
+pub fn render_gallery(organized_dir: &Path, inventory: &[InventoryEntry], page_dir: &Path) -> String {
+    let mut families: BTreeMap<&str, Vec<&InventoryEntry>> = BTreeMap::new();
+    for entry in inventory {
+        families.entry(&entry.family).or_default().push(entry);
+    }
+
+    let title = escape(&organized_dir.display().to_string());
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>Font gallery {}</title>\n<style>\n{}", title, STYLE));
+    let rules: Vec<String> = families.values().enumerate()
+        .map(|(index, fonts)| font_face_rules(index, fonts, page_dir))
+        .collect();
+    for rule in &rules {
+        html.push_str(rule);
+    }
+    html.push_str("</style>\n</head>\n<body>\n");
+    html.push_str(&format!("<h1>{}</h1>\n", title));
+    html.push_str(&format!(
+        "<p class=\"summary\">{} fonts in {} families, generated {} <span id=\"shown\"></span></p>\n",
+        inventory.len(),
+        families.len(),
+        today()
+    ));
+    html.push_str("<input id=\"search\" type=\"search\" placeholder=\"Search families\">\n");
+
+    html.push_str("<div class=\"cards\">\n");
+    for ((index, (name, fonts)), rule) in families.iter().enumerate().zip(&rules) {
+        push_card(&mut html, index, name, fonts, rule);
+    }
+    html.push_str("</div>\n");
+
+    html.push_str(&format!("<script>\n{}</script>\n</body>\n</html>\n", SCRIPT));
+    html
+}
+
+/// The `@font-face` rules of a family under its gallery alias, one per font
+/// a browser can load; faces of collections and Type 1 fonts have none
+fn font_face_rules(index: usize, fonts: &[&InventoryEntry], page_dir: &Path) -> String {
+    let mut rules = String::new();
+    for font in fonts {
+        let extension = font.path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        let format = match extension.as_str() {
+            "ttf" => "truetype",
+            "otf" => "opentype",
+            "woff" => "woff",
+            "woff2" => "woff2",
+            _ => continue,
+        };
+        let Ok(path) = std::path::absolute(&font.path) else { continue };
+        rules.push_str(&format!(
+            "@font-face {{ font-family: \"gallery-{}\"; src: url(\"{}\") format(\"{}\"); font-weight: {}; font-style: {}; }}\n",
+            index,
+            relative_url(&path, page_dir),
+            format,
+            font.weight,
+            if font.italic { "italic" } else { "normal" }
+        ));
+    }
+    rules
+}
+
+/// A family card: name, live sample, foundry, style class, weight range and
+/// the family's `@font-face` rules
+fn push_card(html: &mut String, index: usize, name: &str, fonts: &[&InventoryEntry], rules: &str) {
+    let min_weight = fonts.iter().map(|font| font.weight).min().unwrap_or(400);
+    let max_weight = fonts.iter().map(|font| font.weight).max().unwrap_or(400);
+    let weights = if min_weight == max_weight { min_weight.to_string() } else { format!("{}–{}", min_weight, max_weight) };
+
+    html.push_str(&format!("<article class=\"card\" data-family=\"{}\">\n", escape(&name.to_lowercase())));
+    html.push_str(&format!("<h2>{}</h2>\n", escape(name)));
+    html.push_str(&format!("<p class=\"sample\" style=\"font-family: 'gallery-{}', sans-serif\">{}</p>\n", index, SAMPLE));
+    html.push_str("<dl>\n");
+    html.push_str(&format!("<dt>Foundry</dt><dd>{}</dd>\n", escape(&fonts[0].foundry)));
+    html.push_str(&format!("<dt>Style</dt><dd>{}</dd>\n", family_style_class(fonts)));
+    html.push_str(&format!("<dt>Weights</dt><dd>{}</dd>\n", weights));
+    html.push_str(&format!("<dt>Fonts</dt><dd>{}</dd>\n", fonts.len()));
+    html.push_str("</dl>\n");
+    if !rules.is_empty() {
+        html.push_str(&format!("<details><summary>@font-face</summary><pre>{}</pre></details>\n", escape(rules)));
+    }
+    html.push_str("</article>\n");
+}
+
+/// Style class most fonts of a family have, ignoring unclassified ones
+fn family_style_class(fonts: &[&InventoryEntry]) -> FontStyleClass {
+    let mut counts: Vec<(FontStyleClass, usize)> = Vec::new();
+    for font in fonts.iter().filter(|font| font.style_class != FontStyleClass::Unknown) {
+        match counts.iter_mut().find(|(class, _)| *class == font.style_class) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((font.style_class, 1)),
+        }
+    }
+    // The first class seen wins a tie, as fonts are in path order
+    counts.iter().rev().max_by_key(|(_, count)| *count).map_or(FontStyleClass::Unknown, |(class, _)| *class)
+}
+
+/// `path` relative to `base`, both absolute, as a URL path. Paths with no
+/// common root, e.g. on another Windows drive, are given as `file:` URLs.
+fn relative_url(path: &Path, base: &Path) -> String {
+    let path_parts: Vec<Component> = path.components().collect();
+    let base_parts: Vec<Component> = base.components().collect();
+    let common = path_parts.iter().zip(&base_parts).take_while(|(a, b)| a == b).count();
+    let encoded = |parts: &[Component]| parts.iter()
+        .filter(|part| matches!(part, Component::Normal(_)))
+        .map(|part| percent_encode(&part.as_os_str().to_string_lossy()))
+        .collect::<Vec<_>>();
+
+    if common == 0 {
+        return format!("file:///{}", encoded(&path_parts).join("/"));
+    }
+    let mut segments = vec!["..".to_string(); base_parts.len() - common];
+    segments.extend(encoded(&path_parts[common..]));
+    segments.join("/")
+}
+
+/// Percent-encode a path segment, keeping only unreserved characters
+fn percent_encode(segment: &str) -> String {
+    segment.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Today's UTC date, e.g. "2024-05-01"
+fn today() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (year, month, day) = civil_date(seconds / 86_400);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Escape text for HTML content and attribute values
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,449 @@
+//! `FontSrt compare-installed <LIBRARY>`: fonts installed on the system compared
+//! with an organized library
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use crate::error::Result;
+use crate::font::metadata::{extract_font_metadata, FONT_EXTENSIONS};
+use crate::models::Config;
+use crate::organizer::collect_font_candidates;
+use crate::organizer::lockfile::family_match_key;
+
+/// Where the fonts installed on a platform are found
+pub trait InstalledFonts {
+    /// Where the fonts were found, for reports
+    fn source_name(&self) -> String;
+
+    /// Paths of the installed font files
+    fn font_files(&self) -> Vec<PathBuf>;
+}
+
+/// Installed fonts found by scanning font folders
+#[derive(Debug, Clone)]
+pub struct FontFolders {
+    pub dirs: Vec<PathBuf>,
+}
+
+impl FontFolders {
+    /// System and user font folders of macOS
+    pub fn macos() -> Self {
+        let mut dirs = vec![PathBuf::from("/System/Library/Fonts"), PathBuf::from("/Library/Fonts")];
+        if let Some(home) = std::env::var_os("HOME") {
+            dirs.push(Path::new(&home).join("Library/Fonts"));
+        }
+        Self { dirs }
+    }
+
+    /// System and user font folders of Linux and other Unix systems
+    pub fn linux() -> Self {
+        let mut dirs = vec![PathBuf::from("/usr/share/fonts"), PathBuf::from("/usr/local/share/fonts")];
+        if let Some(home) = std::env::var_os("HOME") {
+            dirs.push(Path::new(&home).join(".local/share/fonts"));
+            dirs.push(Path::new(&home).join(".fonts"));
+        }
+        Self { dirs }
+    }
+}
+
+impl InstalledFonts for FontFolders {
+    fn source_name(&self) -> String {
+        let dirs: Vec<String> = self.dirs.iter().map(|dir| dir.display().to_string()).collect();
+        format!("font folders {}", dirs.join(", "))
+    }
+
+    fn font_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for dir in &self.dirs {
+            collect_font_files(dir, &mut files);
+        }
+        files
+    }
+}
+
+/// Font files in `dir` and its subfolders
+fn collect_font_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_font_files(&path, files);
+        } else if has_font_extension(&path) {
+            files.push(path);
+        }
+    }
+}
+
+fn has_font_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Fonts registered under the HKLM and HKCU `Fonts` registry keys, plus the
+/// per-user fonts folder
+#[cfg(windows)]
+#[derive(Debug, Clone, Default)]
+pub struct WindowsInstalledFonts;
+
+#[cfg(windows)]
+impl WindowsInstalledFonts {
+    /// Registry keys fonts are registered under
+    const FONT_KEYS: [&'static str; 2] = [
+        r"HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion\Fonts",
+        r"HKCU\SOFTWARE\Microsoft\Windows NT\CurrentVersion\Fonts",
+    ];
+
+    fn system_fonts_dir() -> PathBuf {
+        let windows = std::env::var_os("WINDIR").unwrap_or_else(|| r"C:\Windows".into());
+        Path::new(&windows).join("Fonts")
+    }
+
+    fn user_fonts_dir() -> Option<PathBuf> {
+        std::env::var_os("LOCALAPPDATA").map(|local| Path::new(&local).join(r"Microsoft\Windows\Fonts"))
+    }
+
+    /// File values of a `Fonts` key, read with `reg query`. System fonts are
+    /// registered by file name, per-user fonts by full path.
+    fn registered_files(key: &str) -> Vec<PathBuf> {
+        let Ok(output) = std::process::Command::new("reg").args(["query", key]).output() else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&output.stdout).lines()
+            .filter_map(|line| {
+                let (_, value) = line.split_once("REG_SZ").or_else(|| line.split_once("REG_EXPAND_SZ"))?;
+                let value = value.trim();
+                (!value.is_empty()).then(|| Self::system_fonts_dir().join(value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(windows)]
+impl InstalledFonts for WindowsInstalledFonts {
+    fn source_name(&self) -> String {
+        "Windows registry and user fonts folder".to_string()
+    }
+
+    fn font_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Self::FONT_KEYS.iter()
+            .flat_map(|key| Self::registered_files(key))
+            .filter(|path| path.is_file())
+            .collect();
+        if let Some(dir) = Self::user_fonts_dir() {
+            collect_font_files(&dir, &mut files);
+        }
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+/// Installed fonts of the platform FontSrt runs on
+#[cfg(windows)]
+pub fn platform_installed_fonts() -> Box<dyn InstalledFonts> {
+    Box::new(WindowsInstalledFonts)
+}
+
+/// Installed fonts of the platform FontSrt runs on
+#[cfg(target_os = "macos")]
+pub fn platform_installed_fonts() -> Box<dyn InstalledFonts> {
+    Box::new(FontFolders::macos())
+}
+
+/// Installed fonts of the platform FontSrt runs on
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn platform_installed_fonts() -> Box<dyn InstalledFonts> {
+    Box::new(FontFolders::linux())
+}
+
+/// A face as compared between the system and the library
+#[derive(Debug, Clone)]
+pub struct Face {
+    pub family: String,
+    pub subfamily: String,
+    /// Font version normalized to three decimals, e.g. "3.000"
+    pub version: Option<String>,
+    pub path: PathBuf,
+}
+
+impl Face {
+    /// Family and style a face is matched by, ignoring case, spaces and punctuation
+    fn key(&self) -> (String, String) {
+        let subfamily = self.subfamily.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect();
+        (family_match_key(&self.family), subfamily)
+    }
+
+    /// Numeric version for comparisons, missing versions rank lowest
+    fn version_number(&self) -> f64 {
+        self.version.as_deref().and_then(|version| version.parse().ok()).unwrap_or(-1.0)
+    }
+}
+
+/// A library face with a higher version than the installed copy
+#[derive(Debug, Clone)]
+pub struct NewerFace {
+    pub library: Face,
+    pub installed: Face,
+}
+
+/// Installed fonts compared with a library
+#[derive(Debug, Clone)]
+pub struct InstalledComparison {
+    /// Where the installed fonts were found
+    pub source: String,
+    pub installed_faces: usize,
+    pub library_faces: usize,
+    /// Installed faces with no face of the same family and style in the
+    /// library, candidates to archive
+    pub missing_from_library: Vec<Face>,
+    /// Library faces newer than the installed face, candidates to update
+    pub newer_in_library: Vec<NewerFace>,
+}
+
+/// Compare installed faces with library faces by family and style.
+///
+/// Returns the installed faces missing from the library and the library faces
+/// with a higher version than the installed one, both sorted by family and style.
+pub fn compare_faces(installed: &[Face], library: &[Face]) -> (Vec<Face>, Vec<NewerFace>) {
+    // The newest library copy of each face is compared
+    let mut newest: HashMap<(String, String), &Face> = HashMap::new();
+    for face in library {
+        let entry = newest.entry(face.key()).or_insert(face);
+        if face.version_number() > entry.version_number() {
+            *entry = face;
+        }
+    }
+
+    let mut missing = Vec::new();
+    let mut newer = Vec::new();
+    for face in installed {
+        match newest.get(&face.key()) {
+            None => missing.push(face.clone()),
+            Some(library_face) if library_face.version_number() > face.version_number() => {
+                newer.push(NewerFace { library: (*library_face).clone(), installed: face.clone() });
+            }
+            Some(_) => {}
+        }
+    }
+
+    missing.sort_by(|a, b| a.key().cmp(&b.key()).then_with(|| a.path.cmp(&b.path)));
+    newer.sort_by(|a, b| a.installed.key().cmp(&b.installed.key()).then_with(|| a.installed.path.cmp(&b.installed.path)));
+    (missing, newer)
+}
+
+/// Read the faces of font files, skipping files that cannot be parsed
+pub fn read_faces(paths: &[PathBuf], config: &Config) -> Vec<Face> {
+    paths.par_iter()
+        .filter_map(|path| {
+            let metadata = extract_font_metadata(path, config).ok().flatten()?;
+            Some(Face {
+                family: metadata.family_name,
+                subfamily: metadata.subfamily,
+                version: metadata.version,
+                path: path.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Lowercased family names of the fonts installed on the platform FontSrt
+/// runs on, read with `config` so they compare with the library's names
+pub fn get_system_font_families(config: &Config) -> HashSet<String> {
+    read_faces(&platform_installed_fonts().font_files(), config)
+        .into_iter()
+        .map(|face| face.family.to_lowercase())
+        .collect()
+}
+
+/// Compare the fonts installed per `installed` with the library at `library`.
+/// Nothing is moved or written.
+pub fn compare_installed(library: &Path, installed: &dyn InstalledFonts, config: &Config) -> Result<InstalledComparison> {
+    let library_config = Config { recursive: true, ..config.clone() };
+    let library_files: Vec<PathBuf> = collect_font_candidates(library, &library_config)?
+        .into_iter()
+        .filter(|path| has_font_extension(path))
+        .collect();
+
+    let installed_faces = read_faces(&installed.font_files(), config);
+    let library_faces = read_faces(&library_files, config);
+    let (missing_from_library, newer_in_library) = compare_faces(&installed_faces, &library_faces);
+
+    Ok(InstalledComparison {
+        source: installed.source_name(),
+        installed_faces: installed_faces.len(),
+        library_faces: library_faces.len(),
+        missing_from_library,
+        newer_in_library,
+    })
+}
+
+/// Print the comparison
+pub fn print_comparison(comparison: &InstalledComparison) {
+    println!(
+        "Compared {} installed faces ({}) with {} library faces",
+        comparison.installed_faces, comparison.source, comparison.library_faces
+    );
+
+    if comparison.missing_from_library.is_empty() {
+        println!("Every installed face is in the library.");
+    } else {
+        println!("Installed but missing from the library ({}), candidates to archive:", comparison.missing_from_library.len());
+        for face in &comparison.missing_from_library {
+            println!("    {} {}  {}", face.family, face.subfamily, face.path.display());
+        }
+    }
+
+    if !comparison.newer_in_library.is_empty() {
+        println!("Newer in the library than installed ({}), candidates to update:", comparison.newer_in_library.len());
+        for newer in &comparison.newer_in_library {
+            println!(
+                "    {} {}  {} > {}  {} (installed: {})",
+                newer.installed.family,
+                newer.installed.subfamily,
+                newer.library.version.as_deref().unwrap_or("unknown"),
+                newer.installed.version.as_deref().unwrap_or("unknown"),
+                newer.library.path.display(),
+                newer.installed.path.display()
+            );
+        }
+    }
+}
+
+fn face_json(face: &Face) -> Value {
+    json!({
+        "family": face.family,
+        "subfamily": face.subfamily,
+        "version": face.version,
+        "path": face.path.display().to_string(),
+    })
+}
+
+/// The comparison as JSON
+pub fn comparison_json(comparison: &InstalledComparison) -> Value {
+    json!({
+        "source": comparison.source,
+        "installed_faces": comparison.installed_faces,
+        "library_faces": comparison.library_faces,
+        "missing_from_library": comparison.missing_from_library.iter().map(face_json).collect::<Vec<_>>(),
+        "newer_in_library": comparison.newer_in_library.iter()
+            .map(|newer| json!({ "library": face_json(&newer.library), "installed": face_json(&newer.installed) }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TestFont};
+
+    fn face(family: &str, subfamily: &str, version: Option<&str>, path: &str) -> Face {
+        Face {
+            family: family.to_string(),
+            subfamily: subfamily.to_string(),
+            version: version.map(str::to_string),
+            path: PathBuf::from(path),
+        }
+    }
+
+    fn summary(faces: &[Face]) -> Vec<String> {
+        faces.iter().map(|face| format!("{} {}", face.family, face.subfamily)).collect()
+    }
+
+    /// Installed fonts mocked as the font files of one folder
+    struct MockInstalled(PathBuf);
+
+    impl InstalledFonts for MockInstalled {
+        fn source_name(&self) -> String {
+            "mock".to_string()
+        }
+
+        fn font_files(&self) -> Vec<PathBuf> {
+            let mut files = Vec::new();
+            collect_font_files(&self.0, &mut files);
+            files
+        }
+    }
+
+    #[test]
+    fn installed_faces_missing_from_the_library() {
+        let installed = [
+            face("Arial", "Regular", Some("2.000"), "/installed/arial.ttf"),
+            face("Arial", "Bold", Some("2.000"), "/installed/arialbd.ttf"),
+            face("Segoe UI", "Regular", None, "/installed/segoeui.ttf"),
+        ];
+        // Matched ignoring case, spaces and punctuation
+        let library = [face("arial", "regular", Some("2.000"), "/library/Arial/Arial.ttf")];
+
+        let (missing, newer) = compare_faces(&installed, &library);
+        assert_eq!(summary(&missing), ["Arial Bold", "Segoe UI Regular"]);
+        assert!(newer.is_empty());
+    }
+
+    #[test]
+    fn newest_library_copy_is_compared() {
+        let installed = [
+            face("Inter", "Regular", Some("3.000"), "/installed/inter.ttf"),
+            face("Lato", "Regular", None, "/installed/lato.ttf"),
+            face("Roboto", "Regular", Some("3.000"), "/installed/roboto.ttf"),
+        ];
+        let library = [
+            face("Inter", "Regular", Some("2.000"), "/library/Inter/Inter (2).ttf"),
+            face("Inter", "Regular", Some("4.000"), "/library/Inter/Inter.ttf"),
+            face("Lato", "Regular", Some("1.000"), "/library/Lato/Lato.ttf"),
+            face("Roboto", "Regular", Some("2.000"), "/library/Roboto/Roboto.ttf"),
+        ];
+
+        let (missing, newer) = compare_faces(&installed, &library);
+        assert!(missing.is_empty());
+        let newer: Vec<(&str, &str)> = newer.iter()
+            .map(|newer| (newer.installed.family.as_str(), newer.library.path.to_str().unwrap()))
+            .collect();
+        // A missing installed version ranks lowest; an older library copy is not newer
+        assert_eq!(newer, [("Inter", "/library/Inter/Inter.ttf"), ("Lato", "/library/Lato/Lato.ttf")]);
+    }
+
+    #[test]
+    fn compares_a_mocked_installed_set_with_a_library() {
+        let installed = TempDir::new("installed");
+        let library = TempDir::new("library");
+        TestFont::new("Inter", "Regular").name(5, "Version 3.000").write(&installed.join("Inter.ttf"));
+        TestFont::new("Lato", "Bold").write(&installed.join("nested/Lato-Bold.ttf"));
+        TestFont::new("Inter", "Regular").name(5, "Version 4.000").write(&library.join("Inter/Inter.ttf"));
+
+        let comparison = compare_installed(library.path(), &MockInstalled(installed.path().to_path_buf()), &Config::default()).unwrap();
+        assert_eq!((comparison.installed_faces, comparison.library_faces), (2, 1));
+        assert_eq!(summary(&comparison.missing_from_library), ["Lato Bold"]);
+        assert_eq!(comparison.newer_in_library.len(), 1);
+
+        let json = comparison_json(&comparison);
+        assert_eq!(json["source"], "mock");
+        assert_eq!(json["newer_in_library"][0]["installed"]["family"], "Inter");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_enumerator_lists_font_files() {
+        let folders = FontFolders::linux();
+        assert!(folders.dirs.contains(&PathBuf::from("/usr/share/fonts")));
+        assert!(folders.font_files().iter().all(|path| has_font_extension(path)));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn macos_enumerator_lists_font_files() {
+        let folders = FontFolders::macos();
+        assert!(folders.dirs.contains(&PathBuf::from("/System/Library/Fonts")));
+        assert!(!folders.font_files().is_empty());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_enumerator_lists_registered_fonts() {
+        assert!(!WindowsInstalledFonts.font_files().is_empty());
+    }
+}
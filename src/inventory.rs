@@ -0,0 +1,115 @@
+//! Catalogue of the fonts in a directory as CSV (`--list`), leaving every file in place
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use rayon::prelude::*;
+use crate::error::Result;
+use crate::font::cache::cached_font_metadata;
+use crate::font::metadata::{extract_faces_metadata, is_collection_font};
+use crate::models::{Config, FontStyleClass};
+use crate::organizer::collect_font_candidates;
+use crate::utils::log;
+
+/// Columns of the inventory CSV, in order
+pub const INVENTORY_COLUMNS: [&str; 8] = ["path", "family", "subfamily", "foundry", "weight", "italic", "version", "size"];
+
+/// One font of the inventory
+#[derive(Debug, Clone)]
+pub struct InventoryEntry {
+    pub path: PathBuf,
+    pub family: String,
+    pub subfamily: String,
+    pub foundry: String,
+    pub weight: u16,
+    /// OS/2 width class, not part of the CSV
+    pub width: u16,
+    pub italic: bool,
+    /// Design classification from PANOSE, not part of the CSV
+    pub style_class: FontStyleClass,
+    pub version: Option<String>,
+    /// File size in bytes
+    pub size: u64,
+    /// `FontMetadata::variable_label` of a variable font, not part of the CSV
+    pub variable: Option<String>,
+}
+
+impl InventoryEntry {
+    /// Values of the entry in `INVENTORY_COLUMNS` order
+    fn fields(&self) -> [String; 8] {
+        [
+            self.path.display().to_string(),
+            self.family.clone(),
+            self.subfamily.clone(),
+            self.foundry.clone(),
+            self.weight.to_string(),
+            self.italic.to_string(),
+            self.version.clone().unwrap_or_default(),
+            self.size.to_string(),
+        ]
+    }
+}
+
+/// Read the metadata of every font in `dir` (and its subdirectories when
+/// `config.recursive`) in parallel, sorted by path. Each face of a collection
+/// is an entry of its own, with a `Fonts.otc#N` path and the size of the
+/// whole file. Files that are not fonts or cannot be read are left out.
+pub fn list_fonts(dir: &Path, config: &Config) -> Result<Vec<InventoryEntry>> {
+    let entries: Mutex<Vec<InventoryEntry>> = Mutex::new(Vec::new());
+
+    collect_font_candidates(dir, config)?
+        .par_iter()
+        .for_each(|path| {
+            let faces = if is_collection_font(path) {
+                extract_faces_metadata(path, config)
+            } else {
+                cached_font_metadata(path, config).map(|metadata| metadata.into_iter().collect())
+            };
+            let faces = match faces {
+                Ok(faces) => faces,
+                Err(e) => {
+                    log(config, format!("Error reading {}: {}", path.display(), e));
+                    return;
+                }
+            };
+
+            let size = fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+            entries.lock().unwrap().extend(faces.into_iter().map(|metadata| InventoryEntry {
+                path: if is_collection_font(path) { metadata.original_path.clone() } else { path.clone() },
+                variable: metadata.variable_label(),
+                family: metadata.family_name,
+                subfamily: metadata.subfamily,
+                foundry: metadata.foundry,
+                weight: metadata.weight,
+                width: metadata.width,
+                italic: metadata.is_italic,
+                style_class: metadata.style_class,
+                version: metadata.version,
+                size,
+            }));
+        });
+
+    let mut entries = entries.into_inner().unwrap();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Write the inventory as CSV with a header line
+pub fn write_inventory_csv(entries: &[InventoryEntry], mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "{}", INVENTORY_COLUMNS.join(","))?;
+    for entry in entries {
+        let fields: Vec<String> = entry.fields().iter().map(|field| csv_field(field)).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    out.flush()
+}
+
+/// Quote a CSV field that holds a comma, quote or line break, doubling its quotes
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
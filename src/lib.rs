@@ -0,0 +1,62 @@
+//! Font organizing library behind the `FontSrt` command-line tool.
+//!
+//! [`FontScanner`] is the entry point: point it at a directory, then
+//! [`scan`](FontScanner::scan) it for a [`FontCollection`] or
+//! [`organize`](FontScanner::organize) it into family folders.
+//!
+//! [`organize_fonts`], [`group_by_foundry`] and [`extract_font_metadata`] are
+//! exported for callers that manage the shared folder maps themselves. Apart
+//! from the steps of a batch run, library calls print only warnings and errors
+//! at the default [`Config::verbosity`] (`LogLevel::Error` silences warnings
+//! too): organizing returns an
+//! [`OrganizeReport`], which the CLI prints with [`print_organize_summary`].
+//! [`plan_organize`] decides where every font goes without touching any file,
+//! returning an [`OrganizePlan`] to show or check before [`execute_plan`]
+//! carries it out; `organize_fonts` does both in turn.
+//! [`FontMetadata::from_path`] and [`FontMetadata::from_bytes`] read the
+//! metadata of one font without setting up a `Config`.
+//! Callers that want live progress, e.g. for a progress bar, set
+//! [`Config::progress_callback`] to receive [`ProgressEvent`]s.
+
+pub mod error;
+pub mod analysis;
+pub mod cards;
+pub mod checker;
+pub mod database;
+pub mod diff;
+pub mod doctor;
+pub mod installed;
+pub mod inventory;
+pub mod stats;
+pub mod preview;
+pub mod gallery;
+pub mod models;
+pub mod utils;
+pub mod font;
+pub mod organizer;
+pub mod cli;
+pub mod scanner;
+pub mod watcher;
+#[cfg(feature = "serve")]
+pub mod server;
+#[cfg(test)]
+mod testing;
+
+pub use error::{Error, Result};
+pub use font::metadata::extract_font_metadata;
+pub use models::{
+    Config,
+    FontCollection,
+    FontMetadata,
+    MergeDecision,
+    MergeReviewer,
+    NamingPattern,
+    OrganizePlan,
+    OrganizeReport,
+    OrganizeResult,
+    ProgressCallback,
+    ProgressEvent,
+    ProposedMerge,
+};
+pub use organizer::{execute_plan, group_by_foundry, organize_fonts, plan_organize, print_organize_summary};
+pub use scanner::FontScanner;
@@ -1,117 +1,1175 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use serde_json::json;
 
-mod error;
-mod models;
-mod utils;
-mod font;
-mod organizer;
-mod cli;
+use fontsrt::error::{ExitCode, Result, Error};
+use fontsrt::models::{
+    BatchOutputLayout,
+    Config,
+    DuplicatePolicy,
+    FileError,
+    FilterAction,
+    MergeMode,
+    MergeDecision,
+    MergeReviewer,
+    MergeStrategy,
+    OrganizationDepth,
+    TopLevelGroup,
+    ProgressCallback,
+    ProgressEvent,
+    ProposedMerge,
+    WeightFolderStyle,
+};
+use fontsrt::utils::{log, parse_size, LogLevel};
+use fontsrt::utils::events::EventStream;
+use fontsrt::utils::lock::LibraryLock;
+use fontsrt::utils::logging::LogFile;
+use fontsrt::utils::report::ReportWriter;
+use fontsrt::utils::css::{parse_css_font_faces, DecodedFontsDir};
+use fontsrt::database::FontDatabase;
+use fontsrt::diff;
+use fontsrt::doctor;
+use fontsrt::installed;
+use fontsrt::inventory::{list_fonts, write_inventory_csv};
+use fontsrt::cards::{family_cards, format_cards};
+use fontsrt::checker::{check_organization, fix_organization, print_issues};
+use fontsrt::preview::write_preview;
+use fontsrt::gallery::generate_html_gallery;
+use fontsrt::stats::{compute_statistics, print_statistics, statistics_json};
+use fontsrt::font::cache::{cache_settings, MetadataCache};
+use fontsrt::font::foundry::{default_foundry_override_file, load_foundry_overrides, validate_foundry_overrides};
+use fontsrt::analysis::{
+    analyze_family_completeness,
+    measure_family_folders,
+    print_completeness_reports,
+    print_largest_families,
+    print_size_report,
+    scan_family_sizes,
+    size_report_json,
+};
+use fontsrt::cli::{
+    CliOptions,
+    get_help_message,
+    generate_completion,
+    Shell,
+    get_user_input,
+    get_user_choice,
+    ask_group_by_foundry,
+    ask_merge_decision,
+    ask_merge_review,
+    ask_apply_migration,
+    ProgressDisplay,
+};
+use fontsrt::organizer::{
+    organize_fonts,
+    print_organize_summary,
+    batch_process,
+    designer_families,
+    group_by_designer,
+    group_by_classification,
+    group_by_foundry,
+    group_by_style,
+    group_by_script,
+    group_by_first_letter,
+    group_by_license,
+    group_by_technology,
+    ungroup_foundries,
+    print_designer_families,
+    print_error_report,
+    write_error_report,
+};
+use fontsrt::organizer::lockfile::LockfileMode;
+use fontsrt::organizer::merge_rules::{default_remembered_merges_file, RememberedMerges};
+use fontsrt::organizer::migrate;
+use fontsrt::organizer::snapshot::{write_snapshot, DecisionSnapshot};
+use fontsrt::watcher::watch;
+#[cfg(feature = "fingerprint")]
+use fontsrt::font::fingerprint::{print_renamed_clusters, renamed_clusters_in};
 
-use error::{Result, Error};
-use models::Config;
-use utils::log;
-use cli::{parse_args, get_help_message, get_user_input, get_user_choice, ask_group_by_foundry};
-use organizer::{organize_fonts, batch_process, group_by_foundry};
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+fn run() -> Result<()> {
+    let options = CliOptions::from_env()?;
 
-    // Check if help is requested
-    if args.contains(&"--help".to_string()) || args.contains(&"-h".to_string()) {
+    // Check if help or the version is requested
+    if options.has("--help") {
         println!("{}", get_help_message());
         return Ok(());
     }
+    if options.has("--version") {
+        println!("FontSrt {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    let completion = ["--generate-completion", "--completions"].into_iter()
+        .find_map(|flag| options.value(flag).map(|shell| (flag, shell)));
+    if let Some((flag, shell)) = completion {
+        let shell = Shell::from_name(&shell).ok_or_else(|| Error::Config(format!(
+            "{} expects one of {}, got '{}'", flag, Shell::NAMES.join(", "), shell
+        )))?;
+        print!("{}", generate_completion(shell));
+        return Ok(());
+    }
 
     // Initialize configuration
-    let config = Config::new(
-        args.contains(&"--debug".to_string()),
-        parse_args(),
-    );
+    let verbosity = options.verbosity()?;
+    let mut config = Config::new(verbosity >= LogLevel::Debug, options.naming_pattern()?);
+    config.verbosity = verbosity;
+    config.dry_run = options.has("--dry-run");
+    config.transliterate = options.has("--transliterate");
+    config.ascii_names = options.has("--ascii-names");
+    config.filename_fallback = !options.has("--no-filename-fallback");
+    config.recursive = options.has("--recursive");
+    config.group_by_style = options.has("--group-by-style");
+    config.group_by_designer = options.has("--group-by-designer");
+    config.group_by_script = options.has("--group-by-script");
+    config.group_by_license = options.has("--group-by-license");
+    if options.has("--group-by-first-letter") {
+        config.top_level_group = TopLevelGroup::FirstLetter;
+    }
+    if let Some(layout) = options.value("--group-by") {
+        match layout.trim().to_lowercase().as_str() {
+            "style" => config.group_by_style = true,
+            "designer" => config.group_by_designer = true,
+            "technology" => config.group_by_technology = true,
+            "script" => config.group_by_script = true,
+            "license" => config.group_by_license = true,
+            "first-letter" => config.top_level_group = TopLevelGroup::FirstLetter,
+            _ => return Err(Error::Config(format!(
+                "--group-by must be 'style', 'designer', 'technology', 'script', 'license' or 'first-letter', got '{}'", layout
+            ))),
+        }
+    }
+    config.classify = options.has("--classify");
+    if config.classify && (config.group_by_style || config.group_by_designer || config.group_by_technology || config.group_by_script || config.group_by_license) {
+        return Err(Error::Config("--classify cannot be combined with --group-by-style, --group-by-designer, --group-by-script, --group-by-license or --group-by".to_string()));
+    }
+    config.export_designers = options.has("--export-designers");
+    config.fingerprint = options.has("--fingerprint");
+    if config.fingerprint && !cfg!(feature = "fingerprint") {
+        println!("Error: this build does not include outline fingerprints, rebuild with --features fingerprint");
+        return Err(Error::Config("fingerprint support not compiled in".to_string()));
+    }
+    if let Some(policy) = options.value("--dup-policy") {
+        config.duplicate_policy = DuplicatePolicy::from_name(&policy).ok_or_else(|| {
+            Error::Config(format!("--dup-policy must be 'keep-best', 'keep-all' or 'keep-first', got '{}'", policy))
+        })?;
+    }
+    config.verify_copy = !options.has("--no-verify");
+    config.default_ignores = !options.has("--no-default-ignores");
+    if let Some(size) = options.value("--large-threshold") {
+        config.large_threshold = parse_size(&size).ok_or_else(|| {
+            Error::Config(format!("--large-threshold expects a size such as 20MB, got '{}'", size))
+        })?;
+    }
+    if options.has("--no-similarity-merge") {
+        config.set_merge_strategy(MergeStrategy::Off);
+    }
+    if let Some(strategy) = options.value("--merge-families") {
+        config.set_merge_strategy(MergeStrategy::from_name(&strategy).ok_or_else(|| {
+            Error::Config(format!("--merge-families must be 'off', 'conservative' or 'aggressive', got '{}'", strategy))
+        })?);
+    }
+    if let Some(threshold) = options.value("--similarity") {
+        config.similarity_threshold = threshold.parse::<f32>().ok()
+            .filter(|threshold| (0.0..=1.0).contains(threshold))
+            .ok_or_else(|| Error::Config(format!("--similarity expects a number from 0.0 to 1.0, got '{}'", threshold)))?;
+    }
+    if let Some(mode) = options.value("--merge") {
+        config.merge_mode = MergeMode::from_name(&mode).ok_or_else(|| {
+            Error::Config(format!("--merge must be 'auto', 'always' or 'never', got '{}'", mode))
+        })?;
+    }
+    // --review-merges asks about each merge of this run only, so there must
+    // be a terminal to answer on; --interactive-merge also remembers answers
+    let review_merges = options.has("--review-merges");
+    let interactive_merge = options.has("--interactive-merge");
+    if review_merges && interactive_merge {
+        return Err(Error::Config("--review-merges and --interactive-merge cannot be used together".to_string()));
+    }
+    for flag in ["--review-merges", "--interactive-merge"].into_iter().filter(|flag| options.has(flag)) {
+        if options.has("--batch") || options.has("--watch") {
+            return Err(Error::Config(format!("{} cannot be used with --batch or --watch", flag)));
+        }
+    }
+    if review_merges && !io::stdin().is_terminal() {
+        return Err(Error::Config("--review-merges needs an interactive terminal".to_string()));
+    }
+    // --yes runs without asking and declines every merge, as nobody approved it
+    let decline_merges = options.has("--yes");
+    if decline_merges && (review_merges || interactive_merge) {
+        return Err(Error::Config("--yes declines every merge, it cannot be used with --review-merges or --interactive-merge".to_string()));
+    }
+    if decline_merges {
+        config.merge_reviewer = Some(Arc::new(|_: &ProposedMerge| MergeDecision::RejectAll));
+    }
+    if let Some(sample) = options.value("--merge-sample") {
+        config.merge_sample_size = sample.parse().ok()
+            .filter(|sample| *sample > 0)
+            .ok_or_else(|| Error::Config(format!("--merge-sample expects a positive number, got '{}'", sample)))?;
+    }
+    if let Some(percent) = options.value("--merge-threshold") {
+        config.merge_neighbor_threshold = percent.trim_end_matches('%').parse::<f32>().ok()
+            .filter(|percent| (0.0..=100.0).contains(percent))
+            .ok_or_else(|| Error::Config(format!("--merge-threshold expects a percentage from 0 to 100, got '{}'", percent)))?;
+    }
+    config.quarantine_synthetic = options.has("--quarantine-synthetic");
+    config.quarantine_corrupt = options.has("--quarantine");
+    config.detect_synthetic = options.has("--detect-synthetic") || config.quarantine_synthetic;
+    config.separate_variable = options.has("--separate-variable");
+    if options.has("--group-by-weight") {
+        config.organization_depth = OrganizationDepth::GroupByWeight;
+    }
+    if let Some(style) = options.value("--weight-folders") {
+        config.weight_folder_style = WeightFolderStyle::from_name(&style).ok_or_else(|| {
+            Error::Config(format!("--weight-folders must be 'numeric' or 'textual', got '{}'", style))
+        })?;
+    }
+
+    // --min-completeness implies --check-completeness
+    if let Some(percent) = options.value("--min-completeness") {
+        let percent: f32 = percent.trim_end_matches('%').parse().map_err(|_| {
+            Error::Config(format!("--min-completeness expects a percentage, got '{}'", percent))
+        })?;
+        config.min_completeness = Some(percent);
+    }
+    config.check_completeness = options.has("--check-completeness")
+        || config.min_completeness.is_some();
+
+    if let Some(css_file) = options.value("--parse-css") {
+        // A dry run writes nothing, so fonts embedded as data: URIs are not decoded
+        let decoded = (!config.dry_run).then(|| Arc::new(DecodedFontsDir::default()));
+        let faces = parse_css_font_faces(Path::new(&css_file), decoded.as_deref())?;
+        log(&config, format!("{} @font-face rules read from {}", faces.len(), css_file));
+        config.css_faces = Some(Arc::new(faces));
+        config.decoded_css_fonts = decoded;
+    }
+
+    if options.has("--ignore-system-fonts") {
+        let families = installed::get_system_font_families(&config);
+        log(&config, format!("{} families installed on the system will be left in place", families.len()));
+        config.system_font_families = Some(Arc::new(families));
+    }
+
+    // Foundry aliases come from --foundry-aliases or ./foundries.toml when present
+    config.foundry_alias_file = options.value("--foundry-aliases")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let default_file = Path::new("foundries.toml");
+            default_file.is_file().then(|| default_file.to_path_buf())
+        });
+    config.load_foundry_aliases()?;
+
+    // Foundry overrides come from --foundry-map or the per-user file when present
+    config.foundry_override_file = options.value("--foundry-map")
+        .map(PathBuf::from)
+        .or_else(|| default_foundry_override_file().filter(|file| file.is_file()));
+    if options.has("--validate-foundry-map") {
+        return validate_foundry_map(&config);
+    }
+    config.load_foundry_override_file()?;
+
+    config.lockfile_file = options.value("--lockfile").map(PathBuf::from);
+    config.load_lockfile()?;
+    // Merge rules come from --merge-rules or ./merges.toml when present
+    config.merge_rules_file = options.value("--merge-rules")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let default_file = Path::new("merges.toml");
+            default_file.is_file().then(|| default_file.to_path_buf())
+        });
+    config.load_merge_rules()?;
+    if let Some(mode) = options.value("--lockfile-mode") {
+        config.lockfile_mode = LockfileMode::from_name(&mode).ok_or_else(|| {
+            Error::Config(format!("--lockfile-mode must be 'review' or 'skip', got '{}'", mode))
+        })?;
+    }
+
+    config.set_font_filter(options.font_filter()?);
+    config.exclude_patterns = options.exclude_patterns()?;
+    if let Some(action) = options.value("--filter-action") {
+        config.filter_action = FilterAction::from_name(&action).ok_or_else(|| {
+            Error::Config(format!("--filter-action must be 'skip', 'quarantine' or 'ignore', got '{}'", action))
+        })?;
+    }
+
+    if let Some(path) = options.value("--log-file") {
+        let level = match options.value("--log-level") {
+            Some(level) => LogLevel::from_name(&level).ok_or_else(|| {
+                Error::Config(format!("--log-level must be one of {}, got '{}'", LogLevel::NAMES.join(", "), level))
+            })?,
+            None => LogLevel::Info,
+        };
+        config.log_file = Some(Arc::new(LogFile::create(Path::new(&path), level)?));
+    }
+    if let Some(path) = options.value("--json-events") {
+        config.events = Some(Arc::new(EventStream::create(Path::new(&path))?));
+    }
+    if let Some(path) = options.value("--report") {
+        config.run_report = Some(Arc::new(ReportWriter::new(Path::new(&path), config.dry_run, config.run_id())));
+    }
+
+    if let Some(length) = options.value("--max-name-length") {
+        config.max_name_length = length.parse().ok().filter(|length| *length >= 32).ok_or_else(|| {
+            Error::Config(format!("--max-name-length expects a number of bytes of at least 32, got '{}'", length))
+        })?;
+    }
+    if let Some(top) = options.value("--top-families") {
+        config.top_families = top.parse().map_err(|_| {
+            Error::Config(format!("--top-families expects a number, got '{}'", top))
+        })?;
+    }
+
+    config.show_stats = options.has("--stats");
+    config.rename_only = options.has("--rename-only");
+    config.delete_zips = options.has("--delete-zips");
+    config.extract_zips = options.has("--extract-zips") || config.delete_zips;
+
+    config.duplicates_dir = options.value("--duplicates-dir").map(PathBuf::from);
+
+    config.fail_on_error = !options.has("--no-fail-on-error");
+    if options.has("--error-report") {
+        config.error_report = Some(PathBuf::from("fontsrt-errors.json"));
+    }
+
+    config.batch_output = options.value("--batch-output").map(PathBuf::from);
+    if let Some(parallelism) = options.value("--batch-parallelism") {
+        config.batch_parallelism = parallelism.parse().ok()
+            .filter(|parallelism| *parallelism > 0)
+            .ok_or_else(|| Error::Config(format!("--batch-parallelism expects a positive number, got '{}'", parallelism)))?;
+    }
+    if let Some(layout) = options.value("--batch-output-layout") {
+        config.batch_output_layout = BatchOutputLayout::from_name(&layout).ok_or_else(|| {
+            Error::Config(format!("--batch-output-layout must be 'merged' or 'per-source', got '{}'", layout))
+        })?;
+    }
+
+    // A decision snapshot replaces the settings above, and answers the foundry
+    // question the way the recorded run did
+    let mut reproduced_foundry = None;
+    if let Some(path) = options.value("--reproduce") {
+        config = reproduce_config(Path::new(&path), &config)?;
+        reproduced_foundry = Some(config.group_by_foundry);
+        config.group_by_foundry = false;
+    }
+    config.validate()?;
 
     if config.debug_mode {
         log(&config, "Debug mode enabled".to_string());
         log(&config, format!("Using naming pattern: {}", config.naming_pattern));
     }
 
+    // Check for server mode
+    if options.command == Some("serve") {
+        return run_server(&config, &options);
+    }
+
+    // Check for disk usage mode
+    if options.command == Some("du") {
+        return run_du(&options);
+    }
+
+    // Check for installed fonts missing from a library
+    if options.command == Some("compare-installed") {
+        return run_compare_installed(&config, &options);
+    }
+
+    // Check for environment diagnostics
+    if options.command == Some("doctor") {
+        return run_doctor(&config, &options);
+    }
+
+    // Check for a comparison of two versions of a directory
+    if options.has("--diff") {
+        return run_diff(&config, &options);
+    }
+
+    // The CLI reports progress by printing the summary of each organize run
+    config.progress_callback = Some(print_progress(&config));
+
+    // Metadata of unchanged fonts is reused from earlier runs unless --no-cache
+    if !options.has("--no-cache") {
+        if let Some(path) = MetadataCache::default_path() {
+            let (cache, warning) = MetadataCache::load(&path, &cache_settings(&config));
+            if let Some(warning) = warning {
+                println!("Warning: {}", warning);
+            }
+            let cache = if options.has("--rebuild-cache") { cache.refreshing() } else { cache };
+            config.metadata_cache = Some(Arc::new(cache));
+        }
+    }
+
+    // Check for migration of a library organized by an older version
+    if options.command == Some("migrate") {
+        return run_migrate(&config, &options);
+    }
+
+    // Check for moving family folders out of their foundry folders
+    if options.command == Some("ungroup") {
+        return run_ungroup(&config, &options);
+    }
+
+    // Check for family cards of a library
+    if options.command == Some("list") {
+        return run_list(&config, &options);
+    }
+
     // Check for batch mode
-    if let Some(batch_file_pos) = args.iter().position(|arg| arg == "--batch") {
-        if batch_file_pos + 1 < args.len() {
-            let batch_file = Path::new(&args[batch_file_pos + 1]).to_path_buf();
-            if batch_file.is_file() {
-                return batch_process(&config, &batch_file);
-            } else {
-                println!("Error: Batch file '{}' not found", batch_file.display());
-                return Err(Error::InvalidPath(batch_file));
+    if let Some(batch_file) = options.value("--batch").map(PathBuf::from) {
+        if batch_file.is_file() {
+            // The foundry question is asked once for every source of the batch
+            let other_grouping = config.group_by_style || config.group_by_designer || config.group_by_technology
+                || config.group_by_script || config.group_by_license;
+            if !config.rename_only && !other_grouping {
+                config.group_by_foundry = reproduced_foundry.map_or_else(ask_group_by_foundry, Ok)?;
+            }
+            let outcomes = batch_process(&config, &batch_file)?;
+            print_foundry_alias_report(&config);
+            print_merge_rules_report(&config);
+            let errors: Vec<FileError> = outcomes.iter()
+                .filter_map(|(_, result)| result.as_ref().ok())
+                .flat_map(|report| report.errors.iter().cloned())
+                .collect();
+            finish_run(&config, &errors)?;
+
+            // Sources that failed as a whole are listed in the batch summary
+            if outcomes.iter().any(|(_, result)| result.is_err()) && config.fail_on_error {
+                ExitCode::PartialFailure.exit();
             }
+            return Ok(());
         } else {
-            println!("Error: --batch option requires a file path");
-            return Err(Error::Config("--batch option requires a file path".to_string()));
+            println!("Error: Batch file '{}' not found", batch_file.display());
+            return Err(Error::InvalidPath(batch_file));
         }
     }
 
     // Process single directory
-    let font_dir = get_user_input(&config)?;
+    let font_dir = get_user_input(&config, options.directory())?;
+    if options.has("--check") {
+        return run_check(&font_dir, &config, options.has("--fix"));
+    }
+    if options.has("--fix") {
+        return Err(Error::Config("--fix can only be used with --check".to_string()));
+    }
+    let watching = options.has("--watch");
+    let choice = if watching {
+        String::new()
+    } else if options.has("--list") {
+        "3".to_string()
+    } else if reproduced_foundry.is_some() {
+        "1".to_string()
+    } else {
+        get_user_choice()?
+    };
+
+    // The library is locked and its font database, which spares re-parsing
+    // unchanged fonts, opened only for a run that organizes or groups, so a
+    // dry run, a listing or an invalid choice leaves no trace
+    let mut _library_lock = None;
+    if !config.dry_run && (watching || matches!(choice.as_str(), "1" | "2")) {
+        let output_root = config.output_dir.clone().unwrap_or_else(|| font_dir.clone());
+        _library_lock = Some(LibraryLock::acquire(&output_root)?);
+        match FontDatabase::open(&FontDatabase::default_path(&output_root), &config) {
+            Ok(database) => {
+                let database = if options.has("--rebuild-db") { database.rebuilding() } else { database };
+                if let Err(e) = database.update_for_directory(&font_dir, &config) {
+                    println!("Warning: could not update the font database: {}", e);
+                }
+                config.font_database = Some(Arc::new(database));
+            }
+            Err(e) => println!("Warning: could not open the font database: {}", e),
+        }
+    }
+
+    // Keep organizing fonts as they are dropped into the directory
+    if watching {
+        watch(&font_dir, &config)?;
+        return finish_run(&config, &[]);
+    }
 
     // Initialize shared data structures
     let processed_files = Arc::new(Mutex::new(HashSet::new()));
     let family_folders = Arc::new(Mutex::new(HashMap::new()));
     let foundry_folders = Arc::new(Mutex::new(HashMap::new()));
 
-    match get_user_choice()?.as_str() {
+    let mut errors = Vec::new();
+    let mut organize_result = None;
+    let mut foundry_grouping = false;
+
+    match choice.as_str() {
         "1" => {
-            organize_fonts(
+            let reviewer = if review_merges {
+                let ask: MergeReviewer = Arc::new(|proposal: &ProposedMerge| ask_merge_review(proposal).unwrap_or(MergeDecision::Quit));
+                Some(review_family_merges(&font_dir, &config, ask))
+            } else if interactive_merge {
+                Some(interactive_merge_reviewer(&font_dir, &config))
+            } else {
+                None
+            };
+            if let Some(reviewer) = reviewer {
+                match reviewer {
+                    Ok(reviewer) => config.merge_reviewer = Some(reviewer),
+                    Err(Error::Cancelled(_)) => {
+                        println!("Merge review stopped, no files were moved.");
+                        return finish_run(&config, &[]);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            // Grouping afterwards reuses the foundries found while organizing
+            config.family_foundries = Some(Arc::default());
+            let result = organize_fonts(
                 &font_dir,
                 &config,
                 processed_files.clone(),
                 family_folders.clone(),
                 foundry_folders.clone()
             )?;
+            errors = result.errors.clone();
+            organize_result = Some(result);
 
             println!("Font organization complete!");
 
-            if ask_group_by_foundry()? {
+            if config.rename_only {
+                // Renamed fonts stay in the folders they were found in
+            } else if config.group_by_style {
+                println!("Grouping fonts by style class...");
+                group_by_style(&font_dir, &config, family_folders.clone())?;
+                println!("Fonts grouped by style class successfully!");
+            } else if config.group_by_designer {
+                println!("Grouping fonts by designer...");
+                group_by_designer(&font_dir, &config, family_folders.clone())?;
+                println!("Fonts grouped by designer successfully!");
+            } else if config.group_by_technology {
+                println!("Grouping fonts by technology...");
+                group_by_technology(&font_dir, &config, family_folders.clone())?;
+                println!("Fonts grouped by technology successfully!");
+            } else if config.group_by_script {
+                println!("Grouping fonts by script...");
+                group_by_script(&font_dir, &config, family_folders.clone())?;
+                println!("Fonts grouped by script successfully!");
+            } else if config.group_by_license {
+                println!("Grouping fonts by license...");
+                group_by_license(&font_dir, &config, family_folders.clone())?;
+                println!("Fonts grouped by license successfully!");
+            } else if config.classify {
+                foundry_grouping = reproduced_foundry.map_or_else(ask_group_by_foundry, Ok)?;
+                let config_with_foundry = Config {
+                    group_by_foundry: foundry_grouping,
+                    ..config.clone()
+                };
+                println!("Grouping fonts by classification...");
+                group_by_classification(
+                    &font_dir,
+                    &config_with_foundry,
+                    processed_files,
+                    family_folders.clone(),
+                    foundry_folders
+                )?;
+                println!("Fonts grouped by classification successfully!");
+            } else if reproduced_foundry.map_or_else(ask_group_by_foundry, Ok)? {
+                foundry_grouping = true;
                 println!("Grouping fonts by foundry...");
                 let config_with_foundry = Config {
                     group_by_foundry: true,
-                    ..config
+                    ..config.clone()
                 };
 
                 group_by_foundry(
                     &font_dir,
                     &config_with_foundry,
                     processed_files,
-                    family_folders,
+                    family_folders.clone(),
                     foundry_folders
                 )?;
 
                 println!("Fonts grouped by foundry successfully!");
             }
         },
+        "2" if config.classify => {
+            println!("Grouping fonts by classification and foundry...");
+            let config_with_foundry = Config {
+                group_by_foundry: true,
+                ..config.clone()
+            };
+
+            group_by_classification(
+                &font_dir,
+                &config_with_foundry,
+                processed_files,
+                family_folders.clone(),
+                foundry_folders
+            )?;
+
+            println!("Fonts grouped by classification and foundry successfully!");
+        },
         "2" => {
             println!("Grouping fonts by foundry...");
             let config_with_foundry = Config {
                 group_by_foundry: true,
-                ..config
+                ..config.clone()
             };
 
             group_by_foundry(
                 &font_dir,
                 &config_with_foundry,
                 processed_files,
-                family_folders,
+                family_folders.clone(),
                 foundry_folders
             )?;
 
             println!("Fonts grouped by foundry successfully!");
         },
+        "3" => {
+            let entries = list_fonts(&font_dir, &config)?;
+            match options.value("--list-output") {
+                Some(file) => {
+                    write_inventory_csv(&entries, io::BufWriter::new(fs::File::create(&file)?))?;
+                    println!("Listed {} fonts in {}", entries.len(), file);
+                }
+                None => write_inventory_csv(&entries, io::stdout().lock())?,
+            }
+        },
         _ => {
             println!("Invalid choice. Exiting.");
         }
     }
 
+    // Letter buckets go above whatever grouping the run did
+    if config.top_level_group == TopLevelGroup::FirstLetter && !config.rename_only && matches!(choice.as_str(), "1" | "2") {
+        println!("Grouping folders by first letter...");
+        let output_root = config.output_dir.clone().unwrap_or_else(|| font_dir.clone());
+        group_by_first_letter(&output_root, &config, family_folders.clone())?;
+        println!("Folders grouped by first letter successfully!");
+    }
+
+    // The preview is built from the run's results, so a dry run previews the plan
+    if let (Some(result), Some(preview)) = (&organize_result, options.value("--generate-preview")) {
+        write_preview(Path::new(&preview), result, &font_dir, foundry_grouping)?;
+        println!("Library preview written to {}", preview);
+    }
+
+    // The gallery samples the fonts where they are once the run is done
+    if let Some(gallery) = options.value("--html-gallery") {
+        let library = config.output_dir.clone().unwrap_or_else(|| font_dir.clone());
+        let inventory = list_fonts(&library, &Config { recursive: true, ..config.clone() })?;
+        generate_html_gallery(&library, &inventory, Path::new(&gallery))?;
+        println!("Font gallery of {} fonts written to {}", inventory.len(), gallery);
+    }
+
+    // Record the decisions of the run next to the library it produced
+    if organize_result.is_some() {
+        let snapshot_config = Config { group_by_foundry: foundry_grouping, ..config.clone() };
+        let output_root = config.output_dir.as_deref().unwrap_or(&font_dir);
+        match write_snapshot(output_root, &font_dir, &snapshot_config, &[]) {
+            Ok(Some(path)) => println!("Decision snapshot written to {}", path.display()),
+            Ok(None) => {}
+            Err(e) => println!("Warning: could not write the decision snapshot: {}", e),
+        }
+    }
+
+    let mut family_dirs: Vec<PathBuf> = family_folders.lock().unwrap().values()
+        .filter(|dir| dir.is_dir())
+        .cloned()
+        .collect();
+    family_dirs.sort();
+    family_dirs.dedup();
+
+    let family_sizes = measure_family_folders(family_dirs.iter().cloned());
+    print_largest_families(&family_sizes, config.top_families);
+
+    if config.export_designers {
+        print_designer_families(&designer_families(&family_dirs, &config));
+    }
+
+    #[cfg(feature = "fingerprint")]
+    if config.fingerprint {
+        print_renamed_clusters(&renamed_clusters_in(&family_dirs, &config));
+    }
+
+    if config.check_completeness {
+        let reports: Vec<_> = family_dirs.iter()
+            .map(|dir| analyze_family_completeness(dir, &config))
+            .collect();
+        print_completeness_reports(&reports, config.min_completeness);
+    }
+
+    let found_no_fonts = organize_result.as_ref().is_some_and(|result| result.fonts_processed == 0);
+
+    // Statistics are computed after any grouping, so nested folders are counted
+    if let Some(result) = organize_result.filter(|_| config.show_stats) {
+        let stats = compute_statistics(&result, &font_dir);
+        print_statistics(&stats);
+        config.emit("statistics", json!({ "statistics": statistics_json(&stats) }));
+    }
+
+    print_foundry_alias_report(&config);
+    print_merge_rules_report(&config);
+
+    finish_run(&config, &errors)?;
+    if found_no_fonts {
+        let hint = if config.recursive { "" } else { " (--recursive also scans subdirectories)" };
+        println!("No fonts found in {}{}", font_dir.display(), hint);
+        ExitCode::NoFonts.exit();
+    }
+    Ok(())
+}
+
+/// Ask `ask` about each proposed family merge during a dry run of `font_dir`,
+/// so stopping the review leaves every file in place, and return a reviewer
+/// that replays the answers for the real run
+fn review_family_merges(font_dir: &Path, config: &Config, ask: MergeReviewer) -> Result<MergeReviewer> {
+    let answers: Arc<Mutex<HashMap<(String, String), MergeDecision>>> = Arc::default();
+    let recorder = answers.clone();
+    let preview_config = Config {
+        dry_run: true,
+        events: None,
+        progress_callback: None,
+        merge_reviewer: Some(Arc::new(move |proposal: &ProposedMerge| {
+            let answer = ask(proposal);
+            recorder.lock().unwrap().insert((proposal.family.clone(), proposal.merged_into.clone()), answer);
+            answer
+        })),
+        ..config.clone()
+    };
+    organize_fonts(font_dir, &preview_config, Arc::default(), Arc::default(), Arc::default())?;
+
+    // The real run proposes the same merges in the same order; anything
+    // unexpected is kept apart
+    let answers = answers.lock().unwrap().clone();
+    Ok(Arc::new(move |proposal: &ProposedMerge| {
+        answers.get(&(proposal.family.clone(), proposal.merged_into.clone()))
+            .copied()
+            .unwrap_or(MergeDecision::Reject)
+    }))
+}
+
+/// Reviewer of `--interactive-merge`: merges answered "always" or "never" on
+/// earlier runs are decided without asking, the others are asked about at the
+/// terminal, and new "always" and "never" answers are remembered. Without a
+/// terminal every merge is declined, remembered ones included, like `--yes`.
+fn interactive_merge_reviewer(font_dir: &Path, config: &Config) -> Result<MergeReviewer> {
+    let memory_file = default_remembered_merges_file();
+    let remembered = match &memory_file {
+        Some(path) => RememberedMerges::load(path)?,
+        None => RememberedMerges::default(),
+    };
+
+    if !io::stdin().is_terminal() {
+        log(config, "No terminal to ask on: declining every merge".to_string());
+        return Ok(Arc::new(|_: &ProposedMerge| MergeDecision::RejectAll));
+    }
+
+    let memory = Arc::new(Mutex::new(remembered.clone()));
+    let recorder = memory.clone();
+    let reviewer = review_family_merges(font_dir, config, Arc::new(move |proposal: &ProposedMerge| {
+        if let Some(answer) = recorder.lock().unwrap().recall(&proposal.family, &proposal.merged_into) {
+            return answer;
+        }
+        let answer = ask_merge_decision(&proposal.family, &proposal.merged_into);
+        recorder.lock().unwrap().remember(&proposal.family, &proposal.merged_into, answer);
+        answer
+    }))?;
+
+    let memory = memory.lock().unwrap();
+    if let Some(path) = memory_file.filter(|_| *memory != remembered) {
+        memory.save(&path)?;
+        println!("Merge decisions remembered in {}", path.display());
+    }
+    Ok(reviewer)
+}
+
+/// Settings of a decision snapshot (`--reproduce <FILE>`), refusing snapshots
+/// written under other heuristics. This run's event stream is kept, and
+/// --dry-run and --debug still apply.
+fn reproduce_config(path: &Path, config: &Config) -> Result<Config> {
+    let snapshot = DecisionSnapshot::load(path)?;
+    if let Err(e) = snapshot.check_compatible() {
+        println!("Error: cannot reproduce {}: {}", path.display(), e);
+        return Err(e);
+    }
+    if snapshot.tool_version != env!("CARGO_PKG_VERSION") {
+        println!(
+            "Warning: {} was written by FontSrt {}, this is FontSrt {}",
+            path.display(), snapshot.tool_version, env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    let inputs_dir = env::temp_dir().join(format!("fontsrt-reproduce-{}", std::process::id()));
+    let restored = snapshot.restore_config(&inputs_dir)?;
+    println!("Reproducing the run over {} recorded in {}", snapshot.directory.display(), path.display());
+    Ok(Config {
+        dry_run: restored.dry_run || config.dry_run,
+        debug_mode: restored.debug_mode || config.debug_mode,
+        verbosity: config.verbosity,
+        events: config.events.clone(),
+        run_report: config.run_report.clone(),
+        ..restored
+    })
+}
+
+/// Report files the run left in place, writing the JSON report when requested,
+/// and exit with an error status if there were any unless --no-fail-on-error
+fn finish_run(config: &Config, errors: &[FileError]) -> Result<()> {
+    print_error_report(errors);
+
+    // Stylesheet fonts that were not organized are not kept
+    if let Some(decoded) = &config.decoded_css_fonts {
+        decoded.remove();
+    }
+
+    // A dry run leaves no trace, the cache included
+    if let Some(cache) = config.metadata_cache.as_ref().filter(|_| !config.dry_run) {
+        let (hits, misses) = cache.stats();
+        log(config, format!("Metadata cache: {} fonts reused, {} parsed", hits, misses));
+        if let Err(e) = cache.save() {
+            println!("Warning: could not write the metadata cache: {}", e);
+        }
+    }
+
+    if let Some(path) = &config.error_report {
+        write_error_report(path, errors, config.run_id())?;
+        println!("Error report written to {}", path.display());
+    }
+
+    if let Some(report) = &config.run_report {
+        report.finish()?;
+        println!("Run report written to {}", report.path().display());
+    }
+
+    if let Some(events) = &config.events {
+        events.finish();
+    }
+    if let Some(log_file) = &config.log_file {
+        log_file.flush();
+    }
+
+    if !errors.is_empty() && config.fail_on_error {
+        ExitCode::PartialFailure.exit();
+    }
+    Ok(())
+}
+
+/// Check the foundry override file and report invalid entries without
+/// processing any fonts (`--validate-foundry-map`)
+fn validate_foundry_map(config: &Config) -> Result<()> {
+    let Some(path) = &config.foundry_override_file else {
+        println!("Error: no foundry override file, pass one with --foundry-map <FILE>");
+        return Err(Error::Config("no foundry override file".to_string()));
+    };
+
+    let problems = validate_foundry_overrides(path)?;
+    if problems.is_empty() {
+        let count = load_foundry_overrides(path)?.len();
+        println!("{}: {} overrides, all valid", path.display(), count);
+        return Ok(());
+    }
+
+    println!("{}: {} invalid entries", path.display(), problems.len());
+    for problem in &problems {
+        println!("  - {}", problem);
+    }
+    Err(Error::Config(format!("{} has invalid entries", path.display())))
+}
+
+/// Default progress callback of the CLI, showing a progress bar (progress
+/// lines with `--debug`) and printing the summary when an organize run completes
+fn print_progress(config: &Config) -> ProgressCallback {
+    // The callback is stored in the config, so its copy must not keep the run
+    // report alive: a report dropped with the config is written as aborted
+    let config = Config { run_report: None, ..config.clone() };
+    let display = ProgressDisplay::new(config.log_level());
+    Arc::new(move |event| {
+        display.handle(&event);
+        if let ProgressEvent::Complete(report) = event {
+            print_organize_summary(&report, &config);
+        }
+    })
+}
+
+/// Print how foundry aliases were applied during the run, if an alias file was used
+fn print_foundry_alias_report(config: &Config) {
+    if let Some(aliases) = &config.foundry_aliases {
+        aliases.print_report();
+    }
+}
+
+/// Print which merge rules fired during the run, if a merge rules file was used
+fn print_merge_rules_report(config: &Config) {
+    if let Some(rules) = &config.merge_rules {
+        rules.print_report();
+    }
+}
+ 
+/// Print family and foundry size breakdowns of an organized directory (`du <DIR>`)
+fn run_du(options: &CliOptions) -> Result<()> {
+    let dir = match options.directory() {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            println!("Error: du requires a directory");
+            return Err(Error::Config("du requires a directory".to_string()));
+        }
+    };
+    if !dir.is_dir() {
+        println!("Error: '{}' is not a directory", dir.display());
+        return Err(Error::InvalidPath(dir));
+    }
+
+    let threshold = match options.value("--threshold") {
+        Some(size) => parse_size(&size).ok_or_else(|| {
+            Error::Config(format!("--threshold expects a size such as 500K or 10MB, got '{}'", size))
+        })?,
+        None => 0,
+    };
+
+    let sizes = scan_family_sizes(&dir);
+    if options.has("--json") {
+        println!("{:#}", size_report_json(&sizes, threshold));
+    } else {
+        print_size_report(&sizes, threshold);
+    }
+    Ok(())
+}
+
+/// Compare two versions of a font directory (`--diff <BEFORE> <AFTER>`)
+fn run_diff(config: &Config, options: &CliOptions) -> Result<()> {
+    let dirs: Vec<PathBuf> = options.values("--diff").iter().map(PathBuf::from).collect();
+    let [before, after] = dirs.as_slice() else {
+        println!("Error: --diff requires a before and an after directory");
+        return Err(Error::Config("--diff requires a before and an after directory".to_string()));
+    };
+    for dir in [before, after] {
+        if !dir.is_dir() {
+            println!("Error: '{}' is not a directory", dir.display());
+            return Err(Error::InvalidPath(dir.clone()));
+        }
+    }
+
+    let font_diff = diff::diff_directories(before, after, config)?;
+    if options.has("--diff-json") {
+        println!("{:#}", diff::diff_json(&font_diff));
+    } else {
+        diff::print_diff(&font_diff, before, after);
+    }
+    Ok(())
+}
+
+/// Compare the fonts installed on the system with a library (`compare-installed <DIR>`)
+fn run_compare_installed(config: &Config, options: &CliOptions) -> Result<()> {
+    let dir = match options.directory() {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            println!("Error: compare-installed requires a library directory");
+            return Err(Error::Config("compare-installed requires a library directory".to_string()));
+        }
+    };
+    if !dir.is_dir() {
+        println!("Error: '{}' is not a directory", dir.display());
+        return Err(Error::InvalidPath(dir));
+    }
+
+    let comparison = installed::compare_installed(&dir, installed::platform_installed_fonts().as_ref(), config)?;
+    if options.has("--json") {
+        println!("{:#}", installed::comparison_json(&comparison));
+    } else {
+        installed::print_comparison(&comparison);
+    }
+    Ok(())
+}
+
+/// Check the environment and a library for common problems (`doctor <DIR>`)
+fn run_doctor(config: &Config, options: &CliOptions) -> Result<()> {
+    let dir = match options.directory() {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            println!("Error: doctor requires a directory");
+            return Err(Error::Config("doctor requires a directory".to_string()));
+        }
+    };
+    if !dir.is_dir() {
+        println!("Error: '{}' is not a directory", dir.display());
+        return Err(Error::InvalidPath(dir));
+    }
+
+    let checks = doctor::run_checks(&dir, config);
+    if options.has("--json") {
+        println!("{:#}", doctor::checks_json(&checks));
+    } else {
+        doctor::print_checks(&checks);
+    }
+
+    if doctor::has_failures(&checks) {
+        ExitCode::Failure.exit();
+    }
+    Ok(())
+}
+
+/// Check an organized library for consistency (`--check`), moving misplaced
+/// and misnamed fonts with `--fix`, and exit with an error status while
+/// issues remain
+fn run_check(dir: &Path, config: &Config, fix: bool) -> Result<()> {
+    println!("Checking {}...", dir.display());
+    let mut issues = check_organization(dir, config)?;
+    print_issues(&issues);
+
+    if fix && !issues.is_empty() {
+        let fixed = fix_organization(dir, &issues, config)?;
+        if config.dry_run {
+            println!("Dry run: {} fonts would be moved or renamed", fixed);
+            return Ok(());
+        }
+        println!("Moved or renamed {} fonts", fixed);
+        issues = check_organization(dir, config)?;
+        if !issues.is_empty() {
+            println!("{} issues remain", issues.len());
+        }
+    }
+
+    if !issues.is_empty() {
+        ExitCode::PartialFailure.exit();
+    }
+    Ok(())
+}
+
+/// Print the family cards of a library (`list <DIR>`)
+fn run_list(config: &Config, options: &CliOptions) -> Result<()> {
+    let dir = match options.directory() {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            println!("Error: list requires a library directory");
+            return Err(Error::Config("list requires a library directory".to_string()));
+        }
+    };
+    if !dir.is_dir() {
+        println!("Error: '{}' is not a directory", dir.display());
+        return Err(Error::InvalidPath(dir));
+    }
+    let family_glob = match options.value("--family") {
+        Some(glob) => Some(glob::Pattern::new(&glob).map_err(|e| {
+            Error::Config(format!("--family expects a glob such as 'Inter*', got '{}': {}", glob, e))
+        })?),
+        None => None,
+    };
+
+    // An organized library keeps its fonts in folders; its database spares
+    // parsing the unchanged ones, and fonts it does not know are scanned
+    let mut config = Config { recursive: true, ..config.clone() };
+    let database_path = FontDatabase::default_path(&dir);
+    if database_path.is_file() {
+        match FontDatabase::open(&database_path, &config) {
+            Ok(database) => config.font_database = Some(Arc::new(database)),
+            Err(e) => println!("Warning: could not open the font database: {}", e),
+        }
+    }
+
+    let entries = list_fonts(&dir, &config)?;
+    let cards = family_cards(&entries, family_glob.as_ref());
+    print!("{}", format_cards(&cards, options.has("--markdown")));
     Ok(())
 }
- 
\ No newline at end of file
+
+/// Move family folders back out of their foundry folders (`ungroup <DIR>`)
+fn run_ungroup(config: &Config, options: &CliOptions) -> Result<()> {
+    let dir = match options.directory() {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            println!("Error: ungroup requires a library directory");
+            return Err(Error::Config("ungroup requires a library directory".to_string()));
+        }
+    };
+    if !dir.is_dir() {
+        println!("Error: '{}' is not a directory", dir.display());
+        return Err(Error::InvalidPath(dir));
+    }
+
+    // The library's database follows the moved folders
+    let mut config = config.clone();
+    let _library_lock = if config.dry_run { None } else { Some(LibraryLock::acquire(&dir)?) };
+    let database_path = FontDatabase::default_path(&dir);
+    if database_path.is_file() && !config.dry_run {
+        match FontDatabase::open(&database_path, &config) {
+            Ok(database) => config.font_database = Some(Arc::new(database)),
+            Err(e) => println!("Warning: could not open the font database: {}", e),
+        }
+    }
+
+    let moved = ungroup_foundries(&dir, &config, Arc::new(Mutex::new(HashMap::new())))?;
+    if config.dry_run {
+        println!("Dry run: would move {} family folders out of their foundry folders", moved);
+    } else {
+        println!("Moved {} family folders out of their foundry folders", moved);
+    }
+    Ok(())
+}
+
+/// Move a library organized by an older version to the current conventions (`migrate <DIR>`)
+fn run_migrate(config: &Config, options: &CliOptions) -> Result<()> {
+    let dir = match options.directory() {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            println!("Error: migrate requires a library directory");
+            return Err(Error::Config("migrate requires a library directory".to_string()));
+        }
+    };
+    if !dir.is_dir() {
+        println!("Error: '{}' is not a directory", dir.display());
+        return Err(Error::InvalidPath(dir));
+    }
+    let skip = match options.value("--skip") {
+        Some(categories) => migrate::parse_skip_categories(&categories)?,
+        None => Vec::new(),
+    };
+
+    if let Some(warning) = migrate::interrupted_migration(&dir) {
+        println!("Warning: {}", warning);
+    }
+    let plan = migrate::plan_migration(&dir, config)?;
+    migrate::print_migration_plan(&plan, &skip);
+
+    let moves = plan.estimated_moves(&skip);
+    if moves == 0 || config.dry_run {
+        return Ok(());
+    }
+    if !options.has("--yes") && !ask_apply_migration(moves)? {
+        println!("Migration cancelled, nothing was moved.");
+        return Ok(());
+    }
+
+    let _library_lock = LibraryLock::acquire(&dir)?;
+    let moved = migrate::apply_migration(&plan, &skip, config)?;
+    println!("Migration complete: {} moves, journaled in {}", moved, dir.join(migrate::MIGRATION_JOURNAL).display());
+    match write_snapshot(&dir, &dir, config, &[]) {
+        Ok(Some(path)) => println!("Decision snapshot written to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => println!("Warning: could not write the decision snapshot: {}", e),
+    }
+    Ok(())
+}
+
+/// Run the local JSON-RPC server (`serve --socket <PATH|PORT>`)
+#[cfg(feature = "serve")]
+fn run_server(config: &Config, options: &CliOptions) -> Result<()> {
+    match options.value("--socket") {
+        Some(endpoint) => fontsrt::server::serve(&endpoint, config),
+        None => {
+            println!("Error: serve requires --socket <PATH|PORT>");
+            Err(Error::Config("serve requires --socket <PATH|PORT>".to_string()))
+        }
+    }
+}
+
+/// Report that the server is unavailable in builds without the `serve` feature
+#[cfg(not(feature = "serve"))]
+fn run_server(_config: &Config, _options: &CliOptions) -> Result<()> {
+    println!("Error: this build does not include the server, rebuild with --features serve");
+    Err(Error::Config("server support not compiled in".to_string()))
+}
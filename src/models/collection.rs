@@ -0,0 +1,64 @@
+use std::collections::{HashMap, HashSet};
+use super::FontMetadata;
+
+/// Fonts discovered by a scan, with their metadata
+#[derive(Debug, Clone, Default)]
+pub struct FontCollection {
+    /// Metadata of every readable font, sorted by path
+    pub fonts: Vec<FontMetadata>,
+}
+
+impl FontCollection {
+    /// Create a collection, sorting the fonts by path
+    pub fn new(mut fonts: Vec<FontMetadata>) -> Self {
+        fonts.sort_by(|a, b| a.original_path.cmp(&b.original_path));
+        Self { fonts }
+    }
+
+    /// Number of fonts in the collection
+    pub fn len(&self) -> usize {
+        self.fonts.len()
+    }
+
+    /// Whether the collection holds no fonts
+    pub fn is_empty(&self) -> bool {
+        self.fonts.is_empty()
+    }
+
+    /// Iterate over the fonts
+    pub fn iter(&self) -> impl Iterator<Item = &FontMetadata> {
+        self.fonts.iter()
+    }
+
+    /// Distinct family names
+    pub fn families(&self) -> HashSet<&str> {
+        self.fonts.iter().map(|font| font.family_name.as_str()).collect()
+    }
+
+    /// Fonts grouped by family name
+    pub fn by_family(&self) -> HashMap<&str, Vec<&FontMetadata>> {
+        let mut families: HashMap<&str, Vec<&FontMetadata>> = HashMap::new();
+        for font in &self.fonts {
+            families.entry(font.family_name.as_str()).or_default().push(font);
+        }
+        families
+    }
+
+    /// Fonts grouped by foundry name
+    pub fn by_foundry(&self) -> HashMap<&str, Vec<&FontMetadata>> {
+        let mut foundries: HashMap<&str, Vec<&FontMetadata>> = HashMap::new();
+        for font in &self.fonts {
+            foundries.entry(font.foundry.as_str()).or_default().push(font);
+        }
+        foundries
+    }
+}
+
+impl IntoIterator for FontCollection {
+    type Item = FontMetadata;
+    type IntoIter = std::vec::IntoIter<FontMetadata>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fonts.into_iter()
+    }
+}
@@ -1,18 +1,446 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::database::FontDatabase;
+use crate::font::cache::MetadataCache;
+use crate::font::foundry::{load_foundry_overrides, FoundryAliases, FoundryOverride};
+use crate::organizer::lockfile::{FontLockfile, LockfileMode};
+use crate::organizer::merge_rules::MergeRules;
+use crate::utils::css::{CssFontFace, DecodedFontsDir};
+use crate::utils::events::EventStream;
+use crate::utils::logging::{LogFile, LogLevel};
+use crate::utils::naming::{clean_name, truncate_name, COLLISION_SUFFIX_ROOM};
+use crate::utils::report::ReportWriter;
+use super::{FilterAction, FontFilter, FontFilterFn, FontMetadata, MergeReviewer, ProgressCallback, ProgressEvent, ReportAction, WidthClass};
 
-/// Configuration for the font organization process
-#[derive(Clone)]
+/// Default `Config::max_name_length`: the longest name ext4 and APFS take in
+/// bytes, and NTFS in UTF-16 units, which never outnumber the bytes
+pub const DEFAULT_MAX_NAME_LENGTH: usize = 255;
+
+/// Configuration for the font organization process. Serializes to the
+/// settings of a run; the loaded files and runtime handles are skipped, and
+/// missing fields take their `Config::default` values.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
-    /// Enable debug output
+    /// Deprecated alias of `verbosity` `Debug` (`--debug`, same as `-v`);
+    /// either one turns on debug output
     pub debug_mode: bool,
+    /// Most detailed level of log messages printed: `Error` with `--quiet`,
+    /// `Info` by default, `Debug` with `-v` and `Trace` with `-vv`
+    pub verbosity: LogLevel,
     /// Pattern to use for naming font files
     pub naming_pattern: NamingPattern,
+    /// Replace non-ASCII characters of names with an ASCII approximation
+    pub transliterate: bool,
+    /// Replace non-ASCII characters of names with an ASCII approximation but
+    /// keep CJK names, tagged with their PostScript family (`--ascii-names`);
+    /// `transliterate` wins when both are set
+    pub ascii_names: bool,
+    /// Guess the family and style of a font without a usable name table from
+    /// its file name, rather than skipping it (`--no-filename-fallback` clears it)
+    pub filename_fallback: bool,
+    /// Report what would be moved without touching the file system
+    pub dry_run: bool,
+    /// Also pick up fonts in subdirectories of the source directory
+    pub recursive: bool,
     /// Whether to group fonts by foundry
     pub group_by_foundry: bool,
+    /// Whether to group family folders into PANOSE style class folders
+    pub group_by_style: bool,
+    /// Whether to group family folders into designer folders
+    pub group_by_designer: bool,
+    /// Whether to group family folders into technology folders (Legacy
+    /// TrueType, OpenType, OpenType CFF, Variable, Color)
+    pub group_by_technology: bool,
+    /// Whether to group family folders into folders of their dominant script
+    /// (Latin, Greek, Cyrillic, Hebrew, Arabic, Devanagari, Thai, CJK)
+    pub group_by_script: bool,
+    /// Whether to group family folders into Libre, Commercial and Unknown
+    /// License folders
+    pub group_by_license: bool,
+    /// Whether to group family folders into classification folders (Serif,
+    /// Sans, Slab, Mono, Script, Display, Unclassified), with foundry folders
+    /// inside them when grouping by foundry too
+    pub classify: bool,
+    /// Report fonts whose outline fingerprints match under different family
+    /// names after organizing (needs the `fingerprint` feature)
+    pub fingerprint: bool,
+    /// Print a designer -> families mapping after organizing
+    pub export_designers: bool,
+    /// How deeply fonts are nested below their family folder
+    pub organization_depth: OrganizationDepth,
+    /// Buckets above the family (or foundry) folders
+    pub top_level_group: TopLevelGroup,
+    /// How weight folders are named when grouping by weight
+    pub weight_folder_style: WeightFolderStyle,
+    /// Place variable fonts in a `Variable` folder inside their family folder
+    pub separate_variable: bool,
+    /// How eagerly families with similar names are merged (`--merge-families`)
+    pub merge_strategy: MergeStrategy,
+    /// Path of the merge rules file, if one is used
+    #[serde(with = "super::path_serde::option")]
+    pub merge_rules_file: Option<PathBuf>,
+    /// Never-merge and force-merge rules loaded from `merge_rules_file`
+    #[serde(skip)]
+    pub merge_rules: Option<Arc<MergeRules>>,
+    /// Asked to accept or reject each proposed merge (`--interactive-merge`),
+    /// merges are applied without asking when unset
+    #[serde(skip)]
+    pub merge_reviewer: Option<MergeReviewer>,
+    /// Edit distance allowed between similar family names, as a fraction (0.0-1.0)
+    /// of the shorter name's length
+    pub similarity_threshold: f32,
+    /// Common prefix that makes family names similar, as a fraction (0.0-1.0)
+    /// of the shorter name's length
+    pub min_prefix_ratio: f32,
+    /// Extract fonts from zip archives in the directory before organizing
+    pub extract_zips: bool,
+    /// Delete zip archives whose fonts were extracted
+    pub delete_zips: bool,
+    /// Whether the similarity merge phase runs, or is decided by sampling
+    pub merge_mode: MergeMode,
+    /// Family names sampled by `MergeMode::Auto`
+    pub merge_sample_size: usize,
+    /// Share of sampled family names with a similar name, in percent, below
+    /// which `MergeMode::Auto` skips merging
+    pub merge_neighbor_threshold: f32,
+    /// What to do with different files that share a family/weight/width/italic signature
+    pub duplicate_policy: DuplicatePolicy,
+    /// Check that a file copied across filesystems (when a rename fails) has
+    /// the same BLAKE3 hash as the original before deleting the original
+    pub verify_copy: bool,
+    /// Skip operating system junk (`.DS_Store`, `._*`, `__MACOSX`, `Thumbs.db`,
+    /// `desktop.ini`) when scanning folders and extracting zips
+    pub default_ignores: bool,
+    /// Longest folder or file name created, in bytes (`--max-name-length`).
+    /// Longer cleaned names are cut, keeping the extension and room for a
+    /// collision suffix.
+    pub max_name_length: usize,
+    /// Size in bytes above which a font is a large (CJK or other mega) font:
+    /// it is pre-screened for copies with a sampled hash and only set aside
+    /// as a confirmed byte-identical copy
+    pub large_threshold: u64,
+    /// Compare outlines within families to flag sheared or faux-weight faces
+    pub detect_synthetic: bool,
+    /// Move faces flagged as synthetic into a `synthetic` folder
+    pub quarantine_synthetic: bool,
+    /// Move files with a font extension that cannot be parsed into a `corrupt` folder
+    pub quarantine_corrupt: bool,
+    /// Report families with missing weights or italics after organizing
+    pub check_completeness: bool,
+    /// Only report families below this completeness percentage
+    pub min_completeness: Option<f32>,
+    /// Number of largest families listed after a run, 0 to hide the list
+    pub top_families: usize,
+    /// Print a statistics report after organizing a directory
+    pub show_stats: bool,
+    /// Duplicates folder name inside the library, or an absolute vault directory
+    /// that collects set-aside files of every library in per-library subfolders
+    #[serde(with = "super::path_serde::option")]
+    pub duplicates_dir: Option<PathBuf>,
+    /// Exit with an error status when any file could not be organized
+    pub fail_on_error: bool,
+    /// Write the files that could not be organized to this JSON file
+    #[serde(with = "super::path_serde::option")]
+    pub error_report: Option<PathBuf>,
+    /// Directory to organize fonts into, defaults to the source directory
+    #[serde(with = "super::path_serde::option")]
+    pub output_dir: Option<PathBuf>,
+    /// Only rename fonts to their pattern name inside the folder they are in,
+    /// without creating family folders
+    pub rename_only: bool,
+    /// Shared output root for batch processing
+    #[serde(with = "super::path_serde::option")]
+    pub batch_output: Option<PathBuf>,
+    /// How batch sources are laid out under `batch_output`
+    pub batch_output_layout: BatchOutputLayout,
+    /// Batch sources organized at the same time; merged batches are always
+    /// organized one source at a time
+    pub batch_parallelism: usize,
+    /// Path of the foundry alias file, if one is used
+    #[serde(with = "super::path_serde::option")]
+    pub foundry_alias_file: Option<PathBuf>,
+    /// Foundry aliases loaded from `foundry_alias_file`
+    #[serde(skip)]
+    pub foundry_aliases: Option<Arc<FoundryAliases>>,
+    /// Path of the foundry override file, if one is used
+    #[serde(with = "super::path_serde::option")]
+    pub foundry_override_file: Option<PathBuf>,
+    /// Family pattern to foundry overrides loaded from `foundry_override_file`
+    #[serde(skip)]
+    pub foundry_overrides: Option<Arc<Vec<FoundryOverride>>>,
+    /// Path of the lockfile, if one is used
+    #[serde(with = "super::path_serde::option")]
+    pub lockfile_file: Option<PathBuf>,
+    /// Approved families loaded from `lockfile_file`; other fonts are set
+    /// aside or skipped
+    #[serde(skip)]
+    pub lockfile: Option<Arc<FontLockfile>>,
+    /// What happens to fonts the lockfile does not approve
+    pub lockfile_mode: LockfileMode,
+    /// Family name patterns of fonts left out of the run (`--exclude`); a
+    /// plain name matches exactly, ignoring case
+    #[serde(with = "super::glob_serde")]
+    pub exclude_patterns: Vec<Pattern>,
+    /// Criteria of `font_filter` when it was built from a `FontFilter`
+    pub filter: Option<FontFilter>,
+    /// Only fonts this predicate accepts are organized
+    #[serde(skip)]
+    pub font_filter: Option<Arc<FontFilterFn>>,
+    /// What happens to fonts `font_filter` rejects
+    pub filter_action: FilterAction,
+    /// JSON event stream of the run, if one was requested
+    #[serde(skip)]
+    pub events: Option<Arc<EventStream>>,
+    /// Metadata of earlier runs, so unchanged fonts are not parsed again
+    #[serde(skip)]
+    pub metadata_cache: Option<Arc<MetadataCache>>,
+    /// `@font-face` rules of a stylesheet (`--parse-css <FILE>`): their fonts
+    /// are organized with the directory's, and name fonts without a name table
+    #[serde(skip)]
+    pub css_faces: Option<Arc<Vec<CssFontFace>>>,
+    /// Where the `data:` fonts of that stylesheet were decoded, removed when
+    /// the run finishes; dry runs decode nothing
+    #[serde(skip)]
+    pub decoded_css_fonts: Option<Arc<DecodedFontsDir>>,
+    /// Lowercased family names of the fonts installed on the system; fonts of
+    /// these families are left in place (`--ignore-system-fonts`)
+    #[serde(skip)]
+    pub system_font_families: Option<Arc<HashSet<String>>>,
+    /// Foundry of each family organized, keyed like the family folder map,
+    /// so grouping by foundry after sorting does not parse the fonts again
+    #[serde(skip)]
+    pub family_foundries: Option<Arc<Mutex<HashMap<String, String>>>>,
+    /// Index of the library being organized, consulted before the metadata cache
+    #[serde(skip)]
+    pub font_database: Option<Arc<FontDatabase>>,
+    /// Log file of the run (`--log-file <PATH>`), written at every verbosity
+    #[serde(skip)]
+    pub log_file: Option<Arc<LogFile>>,
+    /// Prefix of every log file line, the source directory in batch runs
+    #[serde(skip)]
+    pub log_context: Option<String>,
+    /// Report of every action on a font (`--report <FILE>`)
+    #[serde(skip)]
+    pub run_report: Option<Arc<ReportWriter>>,
+    /// Receives progress events of organize and grouping runs
+    #[serde(skip)]
+    pub progress_callback: Option<ProgressCallback>,
+    /// Set to stop an organize run early, e.g. when a server client cancels
+    /// its request. Fonts already moved stay moved.
+    #[serde(skip)]
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+/// How batch sources are laid out under a shared batch output root
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BatchOutputLayout {
+    /// All sources are organized into one library, unifying families across sources
+    Merged,
+    /// Each source gets its own `<root>/<source-name>/` library
+    PerSource,
+}
+
+impl BatchOutputLayout {
+    /// Parse a layout from its command-line name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "merged" => Some(BatchOutputLayout::Merged),
+            "per-source" => Some(BatchOutputLayout::PerSource),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BatchOutputLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchOutputLayout::Merged => write!(f, "merged"),
+            BatchOutputLayout::PerSource => write!(f, "per-source"),
+        }
+    }
+}
+
+/// What to do with different files that share a family/weight/width/italic signature
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicatePolicy {
+    /// Keep every file in the family folder, suffixing clashing names with `_N`
+    KeepAll,
+    /// Keep the highest version, then CFF over TrueType, then the most glyphs
+    KeepBest,
+    /// Keep the first file by path
+    KeepFirst,
+}
+
+impl DuplicatePolicy {
+    /// Parse a policy from its command-line name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "keep-all" => Some(DuplicatePolicy::KeepAll),
+            "keep-best" => Some(DuplicatePolicy::KeepBest),
+            "keep-first" => Some(DuplicatePolicy::KeepFirst),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DuplicatePolicy::KeepAll => write!(f, "keep-all"),
+            DuplicatePolicy::KeepBest => write!(f, "keep-best"),
+            DuplicatePolicy::KeepFirst => write!(f, "keep-first"),
+        }
+    }
+}
+
+/// Whether the similarity merge phase runs (`--merge`)
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeMode {
+    /// Run it when a sample of family names shows enough similar names
+    Auto,
+    /// Always merge similar families
+    #[default]
+    Always,
+    /// Never merge, every normalized family name gets its own folder
+    Never,
+}
+
+impl MergeMode {
+    /// Parse a mode from its command-line name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "auto" => Some(MergeMode::Auto),
+            "always" => Some(MergeMode::Always),
+            "never" => Some(MergeMode::Never),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MergeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeMode::Auto => write!(f, "auto"),
+            MergeMode::Always => write!(f, "always"),
+            MergeMode::Never => write!(f, "never"),
+        }
+    }
+}
+
+/// How eagerly families with similar names are merged (`--merge-families`)
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeStrategy {
+    /// No merging, fonts are grouped strictly by normalized root family
+    Off,
+    /// Merge only names that start with one another or share a first word,
+    /// or are within a 10% edit distance
+    Conservative,
+    /// Merge names with a 70% common prefix or a 20% edit distance
+    #[default]
+    Aggressive,
+}
+
+impl MergeStrategy {
+    /// Parse a strategy from its command-line name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "off" => Some(MergeStrategy::Off),
+            "conservative" => Some(MergeStrategy::Conservative),
+            "aggressive" => Some(MergeStrategy::Aggressive),
+            _ => None,
+        }
+    }
+
+    /// Common prefix ratio and edit distance ratio of the strategy, the
+    /// `min_prefix_ratio` and `similarity_threshold` it sets; `None` when off
+    pub fn thresholds(self) -> Option<(f32, f32)> {
+        match self {
+            MergeStrategy::Off => None,
+            MergeStrategy::Conservative => Some((1.0, 0.10)),
+            MergeStrategy::Aggressive => Some((0.70, 0.20)),
+        }
+    }
+}
+
+impl fmt::Display for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeStrategy::Off => write!(f, "off"),
+            MergeStrategy::Conservative => write!(f, "conservative"),
+            MergeStrategy::Aggressive => write!(f, "aggressive"),
+        }
+    }
+}
+
+/// How deeply fonts are nested below their family folder
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OrganizationDepth {
+    /// All fonts of a family sit directly in `Family/`
+    Family,
+    /// Fonts are nested by weight, e.g. `Family/700/`
+    GroupByWeight,
+}
+
+/// Buckets the top-level folders of a library are placed in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TopLevelGroup {
+    /// Family (or foundry) folders sit at the library root
+    None,
+    /// Folders are bucketed by their first letter, e.g. `R/Roboto/`, with
+    /// names not starting with an ASCII letter in `#/`
+    FirstLetter,
+}
+
+/// How weight folders are named when grouping by weight
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WeightFolderStyle {
+    /// "400", "700"
+    Numeric,
+    /// "Regular", "Bold"
+    Textual,
+}
+
+impl WeightFolderStyle {
+    /// Parse a weight folder style from its command-line name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "numeric" => Some(WeightFolderStyle::Numeric),
+            "textual" => Some(WeightFolderStyle::Textual),
+            _ => None,
+        }
+    }
+
+    /// Folder name for a weight in this style, after the width unless it is
+    /// normal: "700" or "Condensed 700", "Bold" or "Condensed Bold"
+    pub fn folder_name(&self, weight: u16, width: WidthClass) -> String {
+        let weight = match self {
+            WeightFolderStyle::Numeric => weight.to_string(),
+            WeightFolderStyle::Textual => crate::font::weight::weight_name(weight).to_string(),
+        };
+        match width {
+            WidthClass::Normal => weight,
+            _ => format!("{} {}", width.name(), weight),
+        }
+    }
 }
 
 /// Patterns for naming font files
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum NamingPattern {
     /// "Helvetica (Bold)"
     FamilySubfamily,
@@ -22,6 +450,8 @@ pub enum NamingPattern {
     FamilyWeight,
     /// "Adobe/Helvetica"
     FoundryFamily,
+    /// User template built from `%Token%` placeholders, e.g. "%Family% %Width% %Weight%"
+    Custom(String),
 }
 
 impl fmt::Display for NamingPattern {
@@ -31,17 +461,308 @@ impl fmt::Display for NamingPattern {
             NamingPattern::FoundryFamilySubfamily => write!(f, "%Foundry% %Family% (%Subfamily%)"),
             NamingPattern::FamilyWeight => write!(f, "%Family% %Weight%"),
             NamingPattern::FoundryFamily => write!(f, "%Foundry%/%Family%"),
+            NamingPattern::Custom(template) => write!(f, "{}", template),
+        }
+    }
+}
+
+impl NamingPattern {
+    /// Names of the built-in patterns as accepted by `from_name`
+    pub const BUILTIN_NAMES: [&'static str; 4] = [
+        "family-subfamily",
+        "foundry-family-subfamily",
+        "family-weight",
+        "foundry-family",
+    ];
+
+    /// Parse a pattern from its command-line name (e.g. "family-weight"), or
+    /// as a custom template when it contains `%Token%` placeholders
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "family-subfamily" => Some(NamingPattern::FamilySubfamily),
+            "foundry-family-subfamily" => Some(NamingPattern::FoundryFamilySubfamily),
+            "family-weight" => Some(NamingPattern::FamilyWeight),
+            "foundry-family" => Some(NamingPattern::FoundryFamily),
+            _ if name.contains('%') => Some(NamingPattern::Custom(name.trim().to_string())),
+            _ => None,
         }
     }
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self::new(false, NamingPattern::FamilySubfamily)
+    }
+}
+
 impl Config {
     /// Create a new configuration with default settings
     pub fn new(debug_mode: bool, naming_pattern: NamingPattern) -> Self {
         Self {
             debug_mode,
+            verbosity: if debug_mode { LogLevel::Debug } else { LogLevel::Info },
             naming_pattern,
+            transliterate: false,
+            ascii_names: false,
+            filename_fallback: true,
+            dry_run: false,
+            recursive: false,
             group_by_foundry: false,
+            group_by_style: false,
+            group_by_designer: false,
+            group_by_technology: false,
+            group_by_script: false,
+            group_by_license: false,
+            classify: false,
+            fingerprint: false,
+            export_designers: false,
+            organization_depth: OrganizationDepth::Family,
+            top_level_group: TopLevelGroup::None,
+            weight_folder_style: WeightFolderStyle::Numeric,
+            separate_variable: false,
+            merge_strategy: MergeStrategy::Aggressive,
+            merge_rules_file: None,
+            merge_rules: None,
+            merge_reviewer: None,
+            similarity_threshold: 0.20,
+            min_prefix_ratio: 0.70,
+            extract_zips: false,
+            delete_zips: false,
+            merge_mode: MergeMode::Always,
+            merge_sample_size: 500,
+            merge_neighbor_threshold: 5.0,
+            duplicate_policy: DuplicatePolicy::KeepAll,
+            verify_copy: true,
+            default_ignores: true,
+            max_name_length: DEFAULT_MAX_NAME_LENGTH,
+            large_threshold: 20 << 20,
+            detect_synthetic: false,
+            quarantine_synthetic: false,
+            quarantine_corrupt: false,
+            check_completeness: false,
+            min_completeness: None,
+            top_families: 5,
+            show_stats: false,
+            duplicates_dir: None,
+            fail_on_error: true,
+            error_report: None,
+            output_dir: None,
+            rename_only: false,
+            batch_output: None,
+            batch_output_layout: BatchOutputLayout::Merged,
+            batch_parallelism: 4,
+            foundry_alias_file: None,
+            foundry_aliases: None,
+            foundry_override_file: None,
+            foundry_overrides: None,
+            lockfile_file: None,
+            lockfile: None,
+            lockfile_mode: LockfileMode::Review,
+            filter: None,
+            exclude_patterns: Vec::new(),
+            font_filter: None,
+            filter_action: FilterAction::Skip,
+            events: None,
+            metadata_cache: None,
+            css_faces: None,
+            decoded_css_fonts: None,
+            system_font_families: None,
+            family_foundries: None,
+            font_database: None,
+            log_file: None,
+            log_context: None,
+            run_report: None,
+            progress_callback: None,
+            cancel: None,
+        }
+    }
+
+    /// Verbosity in effect, raised to `Debug` by the deprecated `debug_mode`
+    pub fn log_level(&self) -> LogLevel {
+        if self.debug_mode {
+            self.verbosity.max(LogLevel::Debug)
+        } else {
+            self.verbosity
+        }
+    }
+
+    /// Check that the options of the configuration can be combined
+    pub fn validate(&self) -> crate::error::Result<()> {
+        if self.rename_only && (self.output_dir.is_some() || self.batch_output.is_some()) {
+            return Err(crate::error::Error::Config(
+                "--rename-only renames fonts where they are and cannot be combined with an output directory".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Use a merge strategy along with its similarity thresholds
+    pub fn set_merge_strategy(&mut self, strategy: MergeStrategy) {
+        self.merge_strategy = strategy;
+        if let Some((min_prefix_ratio, similarity_threshold)) = strategy.thresholds() {
+            self.min_prefix_ratio = min_prefix_ratio;
+            self.similarity_threshold = similarity_threshold;
+        }
+    }
+
+    /// Folder name of a family or foundry: the cleaned name, cut to `max_name_length`
+    pub fn folder_name(&self, name: &str) -> String {
+        truncate_name(&clean_name(name), self.max_name_length)
+    }
+
+    /// Stem of a font file name with `extension`, cut so the whole name with
+    /// a collision suffix fits in `max_name_length`
+    pub fn file_stem(&self, name: &str, extension: &str) -> String {
+        let room = self.max_name_length.saturating_sub(extension.len() + 1 + COLLISION_SUFFIX_ROOM);
+        truncate_name(&clean_name(name), room.max(1))
+    }
+
+    /// The `@font-face` rule of `--parse-css` pointing to `path`, if any
+    pub fn css_face(&self, path: &Path) -> Option<&CssFontFace> {
+        self.css_faces.as_ref()?.iter().find(|face| face.has_source(path))
+    }
+
+    /// Name of the weight folder a font of `weight` and `width` is nested in,
+    /// if grouping by weight
+    pub fn weight_folder(&self, weight: u16, width: WidthClass) -> Option<String> {
+        match self.organization_depth {
+            OrganizationDepth::Family => None,
+            OrganizationDepth::GroupByWeight => Some(self.weight_folder_style.folder_name(weight, width)),
+        }
+    }
+
+    /// Where duplicates of the library at `output_root` are moved: its
+    /// `duplicates` folder by default, a custom folder name inside it, or
+    /// `<vault>/<library-name>/duplicates` for an absolute vault
+    pub fn duplicates_path(&self, output_root: &Path) -> PathBuf {
+        match &self.duplicates_dir {
+            Some(dir) if dir.is_absolute() => self.set_aside_path(output_root, "duplicates"),
+            Some(name) => output_root.join(name),
+            None => output_root.join("duplicates"),
+        }
+    }
+
+    /// Where files of another set-aside `kind` (e.g. "synthetic", "corrupt") of the library
+    /// at `output_root` are moved, following the vault when one is configured
+    pub fn set_aside_path(&self, output_root: &Path, kind: &str) -> PathBuf {
+        match &self.duplicates_dir {
+            Some(vault) if vault.is_absolute() => {
+                let library_name = output_root.canonicalize()
+                    .unwrap_or_else(|_| output_root.to_path_buf())
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "library".to_string());
+                vault.join(library_name).join(kind)
+            }
+            _ => output_root.join(kind),
+        }
+    }
+
+    /// Emit an event on the run's JSON event stream, if there is one
+    pub fn emit(&self, event: &str, data: Value) {
+        if let Some(events) = &self.events {
+            events.emit(event, data);
+        }
+    }
+
+    /// Remember a font's metadata for the run report, if one is written
+    pub fn record_font(&self, path: &Path, metadata: &FontMetadata) {
+        if let Some(report) = &self.run_report {
+            report.record_font(path, metadata);
+        }
+    }
+
+    /// Add an action on a font to the run report, if one is written. Moves,
+    /// renames and duplicates of a dry run are recorded as planned.
+    pub fn record_action(&self, source: &Path, destination: Option<&Path>, action: ReportAction, reason: Option<String>) {
+        let Some(report) = &self.run_report else { return };
+        let action = match action {
+            ReportAction::Moved | ReportAction::Renamed | ReportAction::Duplicate if self.dry_run => ReportAction::Planned,
+            action => action,
+        };
+        report.record(source, destination, action, reason);
+    }
+
+    /// Write the run report so far, at the end of one step of the run
+    pub fn report_checkpoint(&self) {
+        if let Some(report) = &self.run_report {
+            if let Err(e) = report.checkpoint() {
+                crate::utils::log(self, format!("Could not write the report {}: {}", report.path().display(), e));
+            }
+        }
+    }
+
+    /// Pass a progress event to the progress callback, if there is one
+    pub fn report_progress(&self, event: ProgressEvent) {
+        if let Some(callback) = &self.progress_callback {
+            callback(event);
+        }
+    }
+
+    /// Whether the run was asked to stop through `cancel`
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::SeqCst))
+    }
+
+    /// Id of the run when an event stream is written, for tagging other artifacts
+    pub fn run_id(&self) -> Option<&str> {
+        self.events.as_deref().map(EventStream::run_id)
+    }
+
+    /// Load the foundry alias file named by `foundry_alias_file`
+    pub fn load_foundry_aliases(&mut self) -> crate::error::Result<()> {
+        if let Some(path) = &self.foundry_alias_file {
+            self.foundry_aliases = Some(Arc::new(FoundryAliases::load(path)?));
+        }
+        Ok(())
+    }
+
+    /// Load the foundry override file named by `foundry_override_file`
+    pub fn load_foundry_override_file(&mut self) -> crate::error::Result<()> {
+        if let Some(path) = &self.foundry_override_file {
+            self.foundry_overrides = Some(Arc::new(load_foundry_overrides(path)?));
+        }
+        Ok(())
+    }
+
+    /// Load the merge rules file named by `merge_rules_file`
+    pub fn load_merge_rules(&mut self) -> crate::error::Result<()> {
+        if let Some(path) = &self.merge_rules_file {
+            self.merge_rules = Some(Arc::new(MergeRules::load(path)?));
+        }
+        Ok(())
+    }
+
+    /// Load the lockfile named by `lockfile_file`
+    pub fn load_lockfile(&mut self) -> crate::error::Result<()> {
+        if let Some(path) = &self.lockfile_file {
+            self.lockfile = Some(Arc::new(FontLockfile::load(path)?));
+        }
+        Ok(())
+    }
+
+    /// Whether a family name matches one of `exclude_patterns`
+    pub fn is_excluded(&self, family_name: &str) -> bool {
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+        self.exclude_patterns.iter().any(|pattern| pattern.matches_with(family_name, options))
+    }
+
+    /// Whether a family is installed on the system (`--ignore-system-fonts`)
+    pub fn is_system_font(&self, family_name: &str) -> bool {
+        self.system_font_families.as_ref().is_some_and(|families| families.contains(&family_name.to_lowercase()))
+    }
+
+    /// Organize only the fonts `filter` matches. An empty filter clears it.
+    pub fn set_font_filter(&mut self, filter: FontFilter) {
+        if filter.is_empty() {
+            self.filter = None;
+            self.font_filter = None;
+        } else {
+            self.font_filter = Some(Arc::new(filter.clone().build()));
+            self.filter = Some(filter);
         }
     }
 
@@ -68,11 +789,109 @@ impl Config {
             NamingPattern::FamilySubfamily
         };
 
-        Ok(Config {
-            debug_mode: args.contains(&"--debug".to_string()),
-            naming_pattern,
-            group_by_foundry: false,
-        })
+        Ok(Config::new(args.contains(&"--debug".to_string()), naming_pattern))
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::organizer::is_set_aside_folder;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn duplicates_in_the_library_by_default() {
+        let config = Config::default();
+        let library = Path::new("/fonts/Library");
+        assert_eq!(config.duplicates_path(library), library.join("duplicates"));
+        assert_eq!(config.set_aside_path(library, "corrupt"), library.join("corrupt"));
+        assert!(is_set_aside_folder("duplicates", &config));
+    }
+
+    #[test]
+    fn custom_duplicates_folder_name() {
+        let config = Config { duplicates_dir: Some(PathBuf::from("_dupes")), ..Config::default() };
+        let library = Path::new("/fonts/Library");
+        assert_eq!(config.duplicates_path(library), library.join("_dupes"));
+        assert_eq!(config.set_aside_path(library, "corrupt"), library.join("corrupt"));
+        assert!(is_set_aside_folder("_dupes", &config));
+    }
+
+    #[test]
+    fn absolute_vault_gets_a_folder_per_library() {
+        let vault = TempDir::new("vault");
+        let libraries = TempDir::new("libraries");
+        let library = libraries.join("Library");
+        fs::create_dir_all(&library).unwrap();
+        let config = Config { duplicates_dir: Some(vault.path().to_path_buf()), ..Config::default() };
+
+        assert_eq!(config.duplicates_path(&library), vault.join("Library/duplicates"));
+        assert_eq!(config.set_aside_path(&library, "corrupt"), vault.join("Library/corrupt"));
+        assert_eq!(config.duplicates_path(&library.join(".")), vault.join("Library/duplicates"));
+        assert!(!is_set_aside_folder(&vault.path().to_string_lossy(), &config));
+    }
+
+    #[test]
+    fn naming_pattern_json_round_trip() {
+        let patterns = [
+            NamingPattern::FamilySubfamily,
+            NamingPattern::FoundryFamilySubfamily,
+            NamingPattern::FamilyWeight,
+            NamingPattern::FoundryFamily,
+            NamingPattern::Custom("%Family% %Width% %Weight%".to_string()),
+            NamingPattern::Custom(String::new()),
+        ];
+        for pattern in patterns {
+            let json = serde_json::to_string(&pattern).unwrap();
+            assert_eq!(serde_json::from_str::<NamingPattern>(&json).unwrap(), pattern, "{}", json);
+        }
+        assert_eq!(serde_json::to_string(&NamingPattern::FamilyWeight).unwrap(), "\"family-weight\"");
+    }
+
+    #[test]
+    fn config_json_round_trip() {
+        let config = Config {
+            naming_pattern: NamingPattern::Custom("%Foundry%/%Family%".to_string()),
+            duplicates_dir: Some(PathBuf::from("/fonts/_quarantine")),
+            exclude_patterns: vec![Pattern::new("Arial*").unwrap()],
+            ..Config::default()
+        };
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["duplicates_dir"], "/fonts/_quarantine");
+        assert_eq!(json["exclude_patterns"], serde_json::json!(["Arial*"]));
+
+        let restored: Config = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(restored.naming_pattern, config.naming_pattern);
+        assert_eq!(serde_json::to_value(&restored).unwrap(), json);
+    }
+
+    #[test]
+    fn exclude_patterns_match_family_names() {
+        let config = Config {
+            exclude_patterns: ["Arial*", "Times New Roman", "Courier?New"].iter().map(|glob| Pattern::new(glob).unwrap()).collect(),
+            ..Config::default()
+        };
+        let cases = [
+            ("Arial", true),
+            ("Arial Regular", true),
+            ("arial narrow", true),
+            ("Arialita", true),
+            ("Times New Roman", true),
+            ("TIMES NEW ROMAN", true),
+            ("Times New Roman PS", false),
+            ("Courier New", true),
+            ("Roboto", false),
+        ];
+        for (family, excluded) in cases {
+            assert_eq!(config.is_excluded(family), excluded, "{}", family);
+        }
+
+        // Without a wildcard the name must match exactly
+        let exact = Config { exclude_patterns: vec![Pattern::new("Arial").unwrap()], ..Config::default() };
+        assert!(exact.is_excluded("Arial"));
+        assert!(!exact.is_excluded("Arialita"));
+        assert!(!exact.is_excluded("Arial Regular"));
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use super::FontMetadata;
+
+/// Predicate deciding whether a font takes part in an organize run
+pub type FontFilterFn = Box<dyn Fn(&FontMetadata) -> bool + Send + Sync>;
+
+/// Criteria a font must all meet to be organized, built up one at a time:
+///
+/// ```no_run
+/// use fontsrt::models::FontFilter;
+///
+/// let heavy_italics = FontFilter::default()
+///     .by_weight_range(600, 900)
+///     .by_italic(true)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontFilter {
+    /// Foundry name, compared ignoring case
+    pub foundry: Option<String>,
+    /// Inclusive weight range
+    pub weight_range: Option<(u16, u16)>,
+    /// Italic (or oblique) faces only, or upright faces only
+    pub italic: Option<bool>,
+    /// File extension without the dot, compared ignoring case
+    pub extension: Option<String>,
+    /// Start of the family name, compared ignoring case
+    pub family_prefix: Option<String>,
+}
+
+impl FontFilter {
+    /// Only fonts of this foundry
+    pub fn by_foundry(mut self, name: &str) -> Self {
+        self.foundry = Some(name.trim().to_string());
+        self
+    }
+
+    /// Only fonts with a weight from `min` to `max`, inclusive
+    pub fn by_weight_range(mut self, min: u16, max: u16) -> Self {
+        self.weight_range = Some((min.min(max), min.max(max)));
+        self
+    }
+
+    /// Only italic faces, or only upright faces
+    pub fn by_italic(mut self, is_italic: bool) -> Self {
+        self.italic = Some(is_italic);
+        self
+    }
+
+    /// Only font files with this extension, with or without the leading dot
+    pub fn by_extension(mut self, ext: &str) -> Self {
+        self.extension = Some(ext.trim().trim_start_matches('.').to_lowercase());
+        self
+    }
+
+    /// Only families whose name starts with `prefix`
+    pub fn by_family_prefix(mut self, prefix: &str) -> Self {
+        self.family_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Whether no criterion is set, so every font matches
+    pub fn is_empty(&self) -> bool {
+        *self == FontFilter::default()
+    }
+
+    /// Whether a font meets every criterion
+    pub fn matches(&self, metadata: &FontMetadata) -> bool {
+        self.foundry.as_ref().is_none_or(|foundry| metadata.foundry.eq_ignore_ascii_case(foundry))
+            && self.weight_range.is_none_or(|(min, max)| (min..=max).contains(&metadata.weight))
+            && self.italic.is_none_or(|italic| metadata.is_italic == italic)
+            && self.extension.as_ref().is_none_or(|ext| {
+                metadata.file_path().extension()
+                    .is_some_and(|actual| actual.to_string_lossy().to_lowercase() == *ext)
+            })
+            && self.family_prefix.as_ref().is_none_or(|prefix| {
+                metadata.family_name.to_lowercase().starts_with(&prefix.to_lowercase())
+            })
+    }
+
+    /// The predicate for `Config::font_filter`
+    pub fn build(self) -> FontFilterFn {
+        Box::new(move |metadata| self.matches(metadata))
+    }
+}
+
+impl fmt::Display for FontFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut criteria = Vec::new();
+        if let Some(foundry) = &self.foundry {
+            criteria.push(format!("foundry {}", foundry));
+        }
+        if let Some((min, max)) = self.weight_range {
+            criteria.push(format!("weight {}-{}", min, max));
+        }
+        if let Some(italic) = self.italic {
+            criteria.push(if italic { "italic".to_string() } else { "upright".to_string() });
+        }
+        if let Some(ext) = &self.extension {
+            criteria.push(format!(".{} files", ext));
+        }
+        if let Some(prefix) = &self.family_prefix {
+            criteria.push(format!("families starting with \"{}\"", prefix));
+        }
+        if criteria.is_empty() {
+            write!(f, "all fonts")
+        } else {
+            write!(f, "{}", criteria.join(", "))
+        }
+    }
+}
+
+/// What happens to fonts the filter does not match
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterAction {
+    /// Leave them where they are and list them in the summary
+    #[default]
+    Skip,
+    /// Move them into the `_skipped` folder
+    Quarantine,
+    /// Leave them where they are without listing them
+    Ignore,
+}
+
+impl FilterAction {
+    /// Parse an action from its command-line name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "skip" => Some(FilterAction::Skip),
+            "quarantine" => Some(FilterAction::Quarantine),
+            "ignore" => Some(FilterAction::Ignore),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FilterAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterAction::Skip => write!(f, "skip"),
+            FilterAction::Quarantine => write!(f, "quarantine"),
+            FilterAction::Ignore => write!(f, "ignore"),
+        }
+    }
+}
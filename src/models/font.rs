@@ -1,10 +1,453 @@
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use super::Config;
+
+/// Kind of slant a face has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Slant {
+    /// A true italic design
+    Italic,
+    /// A slanted roman
+    Oblique,
+}
+
+impl fmt::Display for Slant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Slant::Italic => write!(f, "Italic"),
+            Slant::Oblique => write!(f, "Oblique"),
+        }
+    }
+}
+
+/// Width of a face, the OS/2 usWidthClass from 1 to 9
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum WidthClass {
+    UltraCondensed = 1,
+    ExtraCondensed = 2,
+    Condensed = 3,
+    SemiCondensed = 4,
+    #[default]
+    Normal = 5,
+    SemiExpanded = 6,
+    Expanded = 7,
+    ExtraExpanded = 8,
+    UltraExpanded = 9,
+}
+
+impl WidthClass {
+    /// The class of a usWidthClass value; values outside 1-9 are normal
+    pub fn from_number(width: u16) -> Self {
+        match width {
+            1 => WidthClass::UltraCondensed,
+            2 => WidthClass::ExtraCondensed,
+            3 => WidthClass::Condensed,
+            4 => WidthClass::SemiCondensed,
+            6 => WidthClass::SemiExpanded,
+            7 => WidthClass::Expanded,
+            8 => WidthClass::ExtraExpanded,
+            9 => WidthClass::UltraExpanded,
+            _ => WidthClass::Normal,
+        }
+    }
+
+    /// The usWidthClass value
+    pub fn number(self) -> u16 {
+        self as u16
+    }
+
+    /// Name in folder and file names ("Semi Condensed"), empty for normal width
+    pub fn name(self) -> &'static str {
+        match self {
+            WidthClass::UltraCondensed => "Ultra Condensed",
+            WidthClass::ExtraCondensed => "Extra Condensed",
+            WidthClass::Condensed => "Condensed",
+            WidthClass::SemiCondensed => "Semi Condensed",
+            WidthClass::Normal => "",
+            WidthClass::SemiExpanded => "Semi Expanded",
+            WidthClass::Expanded => "Expanded",
+            WidthClass::ExtraExpanded => "Extra Expanded",
+            WidthClass::UltraExpanded => "Ultra Expanded",
+        }
+    }
+
+    /// The width a family name spells out, e.g. condensed for "Roboto
+    /// Condensed" or "Univers Cond"; normal when it names none
+    pub fn from_family_name(name: &str) -> Self {
+        let words: Vec<String> = name.split([' ', '-', '_']).map(str::to_lowercase).collect();
+        for (index, word) in words.iter().enumerate() {
+            // "Semi Condensed" is spelled as one word or two
+            let compound = match words.get(index + 1) {
+                Some(next) if matches!(word.as_str(), "ultra" | "extra" | "semi") => format!("{}{}", word, next),
+                _ => word.clone(),
+            };
+            let class = match compound.as_str() {
+                "ultracondensed" => WidthClass::UltraCondensed,
+                "extracondensed" | "compressed" => WidthClass::ExtraCondensed,
+                "condensed" | "cond" | "narrow" => WidthClass::Condensed,
+                "semicondensed" => WidthClass::SemiCondensed,
+                "semiexpanded" => WidthClass::SemiExpanded,
+                "expanded" | "extended" | "wide" => WidthClass::Expanded,
+                "extraexpanded" => WidthClass::ExtraExpanded,
+                "ultraexpanded" => WidthClass::UltraExpanded,
+                _ => continue,
+            };
+            return class;
+        }
+        WidthClass::Normal
+    }
+}
+
+impl fmt::Display for WidthClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WidthClass::Normal => write!(f, "Normal"),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+/// Kind of mechanically generated face detected by outline comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SyntheticKind {
+    /// An upright face sheared into a fake italic
+    Oblique,
+    /// An upright face emboldened or lightened into a fake weight
+    Weight,
+}
+
+impl fmt::Display for SyntheticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyntheticKind::Oblique => write!(f, "synthetic oblique"),
+            SyntheticKind::Weight => write!(f, "faux weight"),
+        }
+    }
+}
+
+/// Broad design classification derived from the PANOSE bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FontStyleClass {
+    Serif,
+    SansSerif,
+    Script,
+    Decorative,
+    Symbol,
+    Monospace,
+    Unknown,
+}
+
+impl FontStyleClass {
+    /// All classes, in the order their folders are listed
+    pub const ALL: [FontStyleClass; 7] = [
+        FontStyleClass::Serif,
+        FontStyleClass::SansSerif,
+        FontStyleClass::Script,
+        FontStyleClass::Decorative,
+        FontStyleClass::Symbol,
+        FontStyleClass::Monospace,
+        FontStyleClass::Unknown,
+    ];
+}
+
+impl fmt::Display for FontStyleClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontStyleClass::Serif => write!(f, "Serif"),
+            FontStyleClass::SansSerif => write!(f, "Sans Serif"),
+            FontStyleClass::Script => write!(f, "Script"),
+            FontStyleClass::Decorative => write!(f, "Decorative"),
+            FontStyleClass::Symbol => write!(f, "Symbol"),
+            FontStyleClass::Monospace => write!(f, "Monospace"),
+            FontStyleClass::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Type classification of a face, decided by `determine_classification` from
+/// the PANOSE bytes, the OS/2 family class and `post.isFixedPitch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FontClassification {
+    Serif,
+    Sans,
+    /// Square serifs: slab and Clarendon designs
+    Slab,
+    Mono,
+    Script,
+    Display,
+    /// Missing or contradictory classification data
+    Unclassified,
+}
+
+impl FontClassification {
+    /// All classifications, in the order their folders are listed
+    pub const ALL: [FontClassification; 7] = [
+        FontClassification::Serif,
+        FontClassification::Sans,
+        FontClassification::Slab,
+        FontClassification::Mono,
+        FontClassification::Script,
+        FontClassification::Display,
+        FontClassification::Unclassified,
+    ];
+}
+
+impl fmt::Display for FontClassification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontClassification::Serif => write!(f, "Serif"),
+            FontClassification::Sans => write!(f, "Sans"),
+            FontClassification::Slab => write!(f, "Slab"),
+            FontClassification::Mono => write!(f, "Mono"),
+            FontClassification::Script => write!(f, "Script"),
+            FontClassification::Display => write!(f, "Display"),
+            FontClassification::Unclassified => write!(f, "Unclassified"),
+        }
+    }
+}
+
+/// File format of a font, from its signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FontFormat {
+    /// sfnt with TrueType outlines
+    TrueType,
+    /// sfnt with CFF outlines (`OTTO`)
+    OpenType,
+    /// PostScript Type 1, binary (.pfb) or ASCII (.pfa)
+    Type1,
+    Woff,
+    Woff2,
+}
+
+impl FontFormat {
+    /// Format of the font in `data`, `None` when no known signature matches
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data.get(..4)? {
+            [0x00, 0x01, 0x00, 0x00] | b"true" => Some(FontFormat::TrueType),
+            b"OTTO" => Some(FontFormat::OpenType),
+            b"wOFF" => Some(FontFormat::Woff),
+            b"wOF2" => Some(FontFormat::Woff2),
+            [0x80, 0x01, ..] | [b'%', b'!', ..] => Some(FontFormat::Type1),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FontFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontFormat::TrueType => write!(f, "TrueType"),
+            FontFormat::OpenType => write!(f, "OpenType"),
+            FontFormat::Type1 => write!(f, "Type 1"),
+            FontFormat::Woff => write!(f, "WOFF"),
+            FontFormat::Woff2 => write!(f, "WOFF2"),
+        }
+    }
+}
+
+/// Writing system a font covers, detected by `font_scripts` from the cmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum FontScript {
+    Latin,
+    Greek,
+    Cyrillic,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Thai,
+    /// Han, Kana or Hangul
+    Cjk,
+}
+
+impl FontScript {
+    /// All scripts, in the order they are listed in reports
+    pub const ALL: [FontScript; 8] = [
+        FontScript::Latin,
+        FontScript::Greek,
+        FontScript::Cyrillic,
+        FontScript::Hebrew,
+        FontScript::Arabic,
+        FontScript::Devanagari,
+        FontScript::Thai,
+        FontScript::Cjk,
+    ];
+}
+
+impl fmt::Display for FontScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontScript::Latin => write!(f, "Latin"),
+            FontScript::Greek => write!(f, "Greek"),
+            FontScript::Cyrillic => write!(f, "Cyrillic"),
+            FontScript::Hebrew => write!(f, "Hebrew"),
+            FontScript::Arabic => write!(f, "Arabic"),
+            FontScript::Devanagari => write!(f, "Devanagari"),
+            FontScript::Thai => write!(f, "Thai"),
+            FontScript::Cjk => write!(f, "CJK"),
+        }
+    }
+}
+
+/// License of a font, decided by `font_license` from name IDs 13 and 14
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FontLicense {
+    /// SIL Open Font License
+    Ofl,
+    /// Apache License
+    Apache,
+    /// GNU GPL with the font exception
+    GplFontException,
+    /// A commercial end user license
+    Proprietary,
+    /// No license text, or one that matches none or several of the above
+    Unknown,
+}
+
+impl FontLicense {
+    /// All licenses, in the order they are listed in reports
+    pub const ALL: [FontLicense; 5] = [
+        FontLicense::Ofl,
+        FontLicense::Apache,
+        FontLicense::GplFontException,
+        FontLicense::Proprietary,
+        FontLicense::Unknown,
+    ];
+
+    /// Whether the license allows free use, modification and redistribution
+    pub fn is_libre(&self) -> bool {
+        matches!(self, FontLicense::Ofl | FontLicense::Apache | FontLicense::GplFontException)
+    }
+}
+
+impl fmt::Display for FontLicense {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontLicense::Ofl => write!(f, "OFL"),
+            FontLicense::Apache => write!(f, "Apache"),
+            FontLicense::GplFontException => write!(f, "GPL+FE"),
+            FontLicense::Proprietary => write!(f, "Proprietary"),
+            FontLicense::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Coarse technology generation of a font, decided by `font_technology` from
+/// the tables it carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FontTechnology {
+    /// PostScript Type 1, which carries no sfnt tables
+    Type1,
+    /// TrueType outlines without OpenType layout tables or an OS/2 version 4+ table
+    LegacyTrueType,
+    /// TrueType outlines with OpenType layout tables (GSUB/GPOS) or an OS/2 version 4+ table
+    OpenType,
+    /// CFF or CFF2 (PostScript flavoured) OpenType
+    OpenTypeCff,
+    /// A variable font (fvar)
+    Variable,
+    /// A color font (COLR, CBDT, sbix or SVG)
+    Color,
+}
+
+impl FontTechnology {
+    /// All technologies, in the order they are listed in reports
+    pub const ALL: [FontTechnology; 6] = [
+        FontTechnology::Type1,
+        FontTechnology::LegacyTrueType,
+        FontTechnology::OpenType,
+        FontTechnology::OpenTypeCff,
+        FontTechnology::Variable,
+        FontTechnology::Color,
+    ];
+}
+
+impl fmt::Display for FontTechnology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FontTechnology::Type1 => write!(f, "Type 1"),
+            FontTechnology::LegacyTrueType => write!(f, "Legacy TrueType"),
+            FontTechnology::OpenType => write!(f, "OpenType"),
+            FontTechnology::OpenTypeCff => write!(f, "OpenType CFF"),
+            FontTechnology::Variable => write!(f, "Variable"),
+            FontTechnology::Color => write!(f, "Color"),
+        }
+    }
+}
+
+/// Where the family and style of a font were read from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataSource {
+    /// The font's name table
+    #[default]
+    NameTable,
+    /// The `@font-face` rule of the stylesheet given to `--parse-css`
+    Stylesheet,
+    /// The file name, for a font without a usable name table; a guess
+    Filename,
+}
+
+impl fmt::Display for MetadataSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataSource::NameTable => write!(f, "name table"),
+            MetadataSource::Stylesheet => write!(f, "stylesheet"),
+            MetadataSource::Filename => write!(f, "file name"),
+        }
+    }
+}
+
+/// A named instance of a variable font, e.g. "Display Black" at wght=900, opsz=32
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedInstance {
+    /// Subfamily name of the instance, from fvar or composed from STAT axis value names
+    pub name: String,
+    /// PostScript name of the instance when the font defines one
+    pub postscript_name: Option<String>,
+    /// Axis tag and coordinate pairs, in fvar axis order
+    pub coordinates: Vec<(String, f32)>,
+}
+
+impl NamedInstance {
+    /// Coordinates as "wght=900 opsz=32"
+    pub fn coordinates_label(&self) -> String {
+        self.coordinates.iter()
+            .map(|(tag, value)| format!("{}={}", tag, value))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// A design axis of a variable font, e.g. wght from 100 to 900
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariationAxis {
+    /// Axis tag ("wght", "wdth", "ital", "opsz")
+    pub tag: String,
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+}
+
+impl VariationAxis {
+    /// Range as "wght 100-900", or "ital 1" for an axis with a single value
+    pub fn range_label(&self) -> String {
+        if self.min == self.max {
+            format!("{} {}", self.tag, self.min)
+        } else {
+            format!("{} {}-{}", self.tag, self.min, self.max)
+        }
+    }
+}
 
 /// Metadata extracted from a font file
-#[derive(Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FontMetadata {
-    /// Font family name
+    /// Font family name, the typographic family (name ID 16) when present
     pub family_name: String,
+    /// Legacy family name (name ID 1) when it differs from `family_name`
+    pub legacy_family_name: Option<String>,
     /// Font subfamily (style variant)
     pub subfamily: String,
     /// Full font name
@@ -12,35 +455,225 @@ pub struct FontMetadata {
     pub full_name: String,
     /// Font foundry name
     pub foundry: String,
+    /// Designer's name from name ID 9
+    pub designer: Option<String>,
     /// Font weight value
     pub weight: u16,
-    /// Whether the font is italic
+    /// OS/2 width class, 1 (ultra condensed) to 9 (ultra expanded), 5 is normal
+    pub width: u16,
+    /// Font version normalized to three decimals ("3.000"), from name ID 5 or head.fontRevision
+    pub version: Option<String>,
+    /// Whether the font is italic (or oblique)
     pub is_italic: bool,
-    /// Original path of the font file
-    #[allow(dead_code)]
+    /// Whether the slant is a true italic or an oblique, `None` for upright faces
+    pub slant: Option<Slant>,
+    /// Design classification from the PANOSE bytes
+    pub style_class: FontStyleClass,
+    /// Type classification of this face alone; families are classified by
+    /// majority vote when grouped
+    pub classification: FontClassification,
+    /// Scripts the font covers, the dominant one (most mapped characters) first
+    pub scripts: Vec<FontScript>,
+    /// File format, from the file signature
+    pub format: FontFormat,
+    /// Technology generation, from the tables the font carries
+    pub technology: FontTechnology,
+    /// License, from the license description and URL name records
+    pub license: FontLicense,
+    /// Whether the font is variable (has an fvar table)
+    pub is_variable: bool,
+    /// Design axes of a variable font, empty for static fonts
+    pub axes: Vec<VariationAxis>,
+    /// Named instances of a variable font, empty for static fonts
+    pub named_instances: Vec<NamedInstance>,
+    /// Where the family and style were read from
+    #[serde(default)]
+    pub source: MetadataSource,
+    /// Original path of the font file; a face of a collection has its index
+    /// appended as `Fonts.otc#2` (see `file_path`)
+    #[serde(with = "super::path_serde")]
     pub original_path: PathBuf,
 }
 
 /// Unique signature for a font variant
-#[derive(Hash, Eq, PartialEq, Debug)]
+#[derive(Hash, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct FontSignature {
     /// Font family name
     pub family_name: String,
     /// Font weight value
     pub weight: u16,
+    /// OS/2 width class
+    pub width: u16,
     /// Whether the font is italic
     pub is_italic: bool,
+    /// Whether the font is variable, so a VF never duplicates its static instances
+    pub is_variable: bool,
 }
 
 impl FontMetadata {
+    /// Read the metadata of a font file with the default `Config`, for
+    /// callers that only want metadata. A collection gives its first face.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        crate::font::metadata::extract_font_metadata(path, &Config::default())?
+            .ok_or_else(|| Error::Metadata(format!("{} is not a valid font", path.display())))
+    }
+
+    /// Read the metadata of a font held in memory, e.g. downloaded or
+    /// memory-mapped. The format is told from the signature; `original_path`
+    /// is left empty.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        // The extension check of a file is satisfied with a name matching the signature
+        let name = match data.get(..4) {
+            Some(b"ttcf") => "font.ttc",
+            Some([0x80, 0x01, ..]) => "font.pfb",
+            Some([b'%', b'!', ..]) => "font.pfa",
+            _ => "font",
+        };
+        let metadata = crate::font::metadata::extract_font_metadata_from_bytes(Path::new(name), data, &Config::default())?
+            .ok_or_else(|| Error::Metadata("the data is not a valid font".to_string()))?;
+        Ok(Self { original_path: PathBuf::new(), ..metadata })
+    }
+
+    /// Script with the most mapped characters among those covered
+    pub fn dominant_script(&self) -> Option<FontScript> {
+        self.scripts.first().copied()
+    }
+
     /// Create a font signature from this metadata
     #[allow(dead_code)]
     pub fn signature(&self) -> FontSignature {
         FontSignature {
             family_name: self.family_name.clone(),
             weight: self.weight,
+            width: self.width,
             is_italic: self.is_italic,
+            is_variable: self.is_variable,
         }
     }
+
+    /// The font file, without the `#N` face index of a collection face
+    pub fn file_path(&self) -> PathBuf {
+        crate::font::metadata::split_face_index(&self.original_path).0
+    }
+
+    /// Index of the face in its collection, `None` unless the metadata was
+    /// read per face of a collection
+    pub fn face_index(&self) -> Option<u32> {
+        crate::font::metadata::split_face_index(&self.original_path).1
+    }
+
+    /// Designer, or the foundry for fonts that don't name one
+    pub fn designer_or_foundry(&self) -> &str {
+        self.designer.as_deref().unwrap_or(&self.foundry)
+    }
+
+    /// Metadata as a JSON object
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("font metadata always serializes")
+    }
+
+    /// Metadata from a JSON object written by `to_json`
+    pub fn from_json(s: &str) -> Result<Self> {
+        serde_json::from_str(s).map_err(|e| Error::Metadata(format!("invalid metadata JSON: {}", e)))
+    }
+
+    /// File name label of a variable font in place of its subfamily, e.g.
+    /// "Variable [wght 100-900]" or "Variable Italic [wght 100-900, opsz 14-32]".
+    /// The slant is named only when no axis varies it. `None` for static fonts.
+    pub fn variable_label(&self) -> Option<String> {
+        if !self.is_variable {
+            return None;
+        }
+        let slant_axis = self.axes.iter().any(|axis| axis.tag == "ital" || axis.tag == "slnt");
+        let slant = self.slant.filter(|_| !slant_axis).map(|slant| format!(" {}", slant)).unwrap_or_default();
+        let ranges = self.axes.iter().map(VariationAxis::range_label).collect::<Vec<_>>().join(", ");
+        Some(if ranges.is_empty() {
+            format!("Variable{}", slant)
+        } else {
+            format!("Variable{} [{}]", slant, ranges)
+        })
+    }
+
+    /// Human-readable width name ("Condensed", "Expanded"), empty for normal width
+    pub fn width_name(&self) -> &'static str {
+        self.width_class().name()
+    }
+
+    /// Width class of the face, from its OS/2 usWidthClass
+    pub fn width_class(&self) -> WidthClass {
+        WidthClass::from_number(self.width)
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use crate::font::metadata::extract_font_metadata_from_bytes;
+    use crate::models::Config;
+    use crate::testing::TestFont;
+
+    /// Metadata of fonts across families, weights, slants and scripts
+    fn samples() -> Vec<FontMetadata> {
+        let mut samples = Vec::new();
+        for family in ["Roboto", "Noto Sans CJK JP", "Żuraw Sans"] {
+            for (subfamily, weight, fs_selection) in [("Regular", 400, 0), ("Bold Italic", 700, 1), ("Thin", 100, 0)] {
+                let font = TestFont::new(family, subfamily).weight(weight).fs_selection(fs_selection);
+                let path = format!("/fonts/{} {}.ttf", family, subfamily);
+                samples.push(extract_font_metadata_from_bytes(Path::new(&path), &font.build(), &Config::default())
+                    .unwrap()
+                    .unwrap());
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn metadata_json_round_trip() {
+        for metadata in samples() {
+            let json = metadata.to_json();
+            let restored = FontMetadata::from_json(&json).unwrap();
+            assert_eq!(restored.to_json(), json);
+
+            // Paths are plain strings
+            let value: Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(value["original_path"], Value::String(metadata.original_path.to_string_lossy().into_owned()));
+        }
+    }
+
+    #[test]
+    fn signature_json_round_trip() {
+        for metadata in samples() {
+            let signature = metadata.signature();
+            let restored: FontSignature = serde_json::from_str(&serde_json::to_string(&signature).unwrap()).unwrap();
+            assert_eq!(restored, signature);
+        }
+    }
+
+    #[test]
+    fn invalid_metadata_json() {
+        assert!(FontMetadata::from_json("{\"family_name\": 3}").is_err());
+    }
+
+    #[test]
+    fn width_class_from_family_name() {
+        let cases = [
+            ("Roboto Condensed", WidthClass::Condensed),
+            ("Univers Cond", WidthClass::Condensed),
+            ("Arial Narrow", WidthClass::Condensed),
+            ("Open Sans Semi Condensed", WidthClass::SemiCondensed),
+            ("Open Sans SemiCondensed", WidthClass::SemiCondensed),
+            ("Helvetica-Compressed", WidthClass::ExtraCondensed),
+            ("Eurostile Extended", WidthClass::Expanded),
+            ("Roboto", WidthClass::Normal),
+            ("Condensa", WidthClass::Normal),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(WidthClass::from_family_name(name), expected, "{}", name);
+        }
+        for number in 1..=9 {
+            assert_eq!(WidthClass::from_number(number).number(), number);
+        }
+    }
+}
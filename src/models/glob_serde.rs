@@ -0,0 +1,17 @@
+//! Serde helpers writing glob patterns as their source strings
+
+use glob::Pattern;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// For `Vec<Pattern>` fields
+pub fn serialize<S: Serializer>(patterns: &[Pattern], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(patterns.iter().map(Pattern::as_str))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Pattern>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| Pattern::new(pattern).map_err(|e| D::Error::custom(format!("invalid pattern '{}': {}", pattern, e))))
+        .collect()
+}
@@ -1,6 +1,35 @@
 pub mod config;
 pub mod font;
+pub mod collection;
+pub mod result;
+pub mod progress;
+pub mod review;
+pub mod filter;
+pub mod report;
+pub mod plan;
+pub(crate) mod path_serde;
+pub(crate) mod glob_serde;
 
-pub use config::{Config, NamingPattern};
-pub use font::FontMetadata;
+pub use config::{BatchOutputLayout, Config, DuplicatePolicy, MergeMode, MergeStrategy, NamingPattern, OrganizationDepth, TopLevelGroup, WeightFolderStyle};
+pub use font::{FontClassification, FontFormat, FontLicense, FontMetadata, FontScript, FontStyleClass, FontTechnology, MetadataSource, NamedInstance, Slant, SyntheticKind, VariationAxis, WidthClass};
 
+pub use collection::FontCollection;
+pub use progress::{ProgressCallback, ProgressEvent};
+pub use review::{MergeDecision, MergeReviewer, ProposedMerge};
+pub use filter::{FilterAction, FontFilter, FontFilterFn};
+pub use report::{Report, ReportAction, ReportEntry, ReportTotals};
+pub use plan::{FamilyPlan, MoveAction, OrganizePlan, PlannedMove, SkipReason};
+pub use result::{
+    CorruptFont,
+    DemotedFont,
+    ErrorStage,
+    FamilyMerge,
+    FileError,
+    MergePhaseDecision,
+    OrganizeReport,
+    OrganizeResult,
+    OrganizedFont,
+    PrecedenceChange,
+    SyntheticFace,
+    UnapprovedFont,
+};
@@ -0,0 +1,44 @@
+//! Serde helpers writing paths as plain JSON strings. Paths that are not valid
+//! Unicode are written lossily.
+
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    String::deserialize(deserializer).map(PathBuf::from)
+}
+
+/// For `Option<PathBuf>` fields, `None` is written as `null`
+pub mod option {
+    use std::path::PathBuf;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+        match path {
+            Some(path) => serializer.serialize_some(&path.to_string_lossy()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+        Option::<String>::deserialize(deserializer).map(|path| path.map(PathBuf::from))
+    }
+}
+
+/// For `Vec<PathBuf>` fields
+pub mod vec {
+    use std::path::PathBuf;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| path.to_string_lossy()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+        Vec::<String>::deserialize(deserializer).map(|paths| paths.into_iter().map(PathBuf::from).collect())
+    }
+}
@@ -0,0 +1,123 @@
+//! A planned organize run: where every font goes, decided before any file is
+//! touched (`plan_organize`) and carried out by `execute_plan`
+
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use super::result::OrganizeReport;
+
+/// Why a font stays where it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The font already has its planned name in its family folder
+    AlreadyOrganized,
+    /// Its family matches an exclude pattern
+    Excluded,
+    /// Its family is installed on the system (`--ignore-system-fonts`)
+    SystemFont,
+    /// The font filter rejects it and the filter action is skip
+    Filtered,
+    /// The lockfile does not approve it and the lockfile mode is skip
+    Unapproved,
+}
+
+/// What happens to a font
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveAction {
+    /// Move the font into its family folder, into the folder it is in under
+    /// its new name (`--rename-only`), or into a set-aside folder
+    Move,
+    /// Move a copy of a font kept elsewhere into the duplicates folder
+    Deduplicate,
+    /// Leave the font where it is
+    Skip(SkipReason),
+}
+
+/// The single destination planned for a font; a skipped font's destination
+/// is its source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedMove {
+    #[serde(with = "super::path_serde")]
+    pub source: PathBuf,
+    #[serde(with = "super::path_serde")]
+    pub destination: PathBuf,
+    pub action: MoveAction,
+    /// Why a font is set aside or skipped, as recorded in the run report
+    pub reason: Option<String>,
+}
+
+impl PlannedMove {
+    pub fn new(source: PathBuf, destination: PathBuf, action: MoveAction) -> Self {
+        Self { source, destination, action, reason: None }
+    }
+
+    /// A font left where it is
+    pub fn skip(source: PathBuf, reason: SkipReason) -> Self {
+        Self::new(source.clone(), source, MoveAction::Skip(reason))
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// Whether the font changes path
+    pub fn moves(&self) -> bool {
+        !matches!(self.action, MoveAction::Skip(_))
+    }
+}
+
+/// Planned moves of one family, executed in order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FamilyPlan {
+    pub family_name: String,
+    /// Folder the family is organized into, `None` when renaming in place
+    #[serde(with = "super::path_serde::option")]
+    pub family_dir: Option<PathBuf>,
+    pub moves: Vec<PlannedMove>,
+}
+
+/// Everything an organize run does, decided before any file is moved:
+/// `execute_plan` moves the set-aside files first, then fills the family
+/// folders in parallel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizePlan {
+    /// Directory the fonts were read from
+    #[serde(with = "super::path_serde")]
+    pub dir: PathBuf,
+    /// Root the families are organized into, `dir` unless `Config::output_dir` is set
+    #[serde(with = "super::path_serde")]
+    pub output_root: PathBuf,
+    /// Corrupt fonts, fonts the filter quarantines, duplicates, synthetic
+    /// faces and unapproved fonts, moved into their folders in that order
+    pub set_aside: Vec<PlannedMove>,
+    /// Family folders and their fonts, sorted by family name
+    pub families: Vec<FamilyPlan>,
+    /// Excluded, filtered and unapproved fonts left in place, sorted by path
+    pub skipped: Vec<PlannedMove>,
+    /// What the planning found, e.g. the duplicates, merges and technologies
+    /// of the run. The counts of moved files and the errors of the moves are
+    /// filled in by `execute_plan`.
+    pub report: OrganizeReport,
+    /// Folders zip archives were extracted into, removed once emptied
+    #[serde(skip)]
+    pub(crate) extraction_dirs: Vec<PathBuf>,
+    /// Archives fonts were extracted from, deleted with `--delete-zips`
+    #[serde(skip)]
+    pub(crate) extracted_archives: Vec<PathBuf>,
+}
+
+impl OrganizePlan {
+    /// Every planned move: set-aside files, family by family, then skipped fonts
+    pub fn moves(&self) -> impl Iterator<Item = &PlannedMove> {
+        self.set_aside.iter()
+            .chain(self.families.iter().flat_map(|family| &family.moves))
+            .chain(&self.skipped)
+    }
+
+    /// Number of files the plan moves
+    pub fn move_count(&self) -> usize {
+        self.moves().filter(|planned| planned.moves()).count()
+    }
+}
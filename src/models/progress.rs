@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use super::OrganizeResult;
+
+/// Progress of an organize or grouping run, passed to `Config::progress_callback`
+#[derive(Debug, Clone)]
+pub enum ProgressEvent {
+    /// A batch run started organizing this many directories
+    BatchStarted { directories: usize },
+    /// The metadata pass over the candidate files of a directory started
+    ScanStarted { dir: PathBuf, total: usize },
+    /// Metadata of a font file is being read
+    ScanningFile(PathBuf),
+    /// The move phase of a directory started, with the number of fonts to
+    /// move or rename
+    MoveStarted { dir: PathBuf, total: usize },
+    /// A font file or family folder was moved (or would be, in a dry run)
+    MovingFile { from: PathBuf, to: PathBuf },
+    /// A family or foundry folder is about to be created
+    CreatingDirectory(PathBuf),
+    /// A file above `Config::large_threshold` is being hashed, with its size
+    /// in bytes. One of these can take longer than a thousand small fonts.
+    HashingLargeFile { path: PathBuf, size: u64 },
+    /// A family was formed, with the number of fonts in it
+    FamilyDetected(String, usize),
+    /// A directory of a batch run was organized; the `Complete` events of
+    /// all directories follow once every directory is done
+    DirectoryFinished(PathBuf),
+    /// An `organize_fonts` run finished
+    Complete(Box<OrganizeResult>),
+}
+
+/// Receives progress events. It is called from worker threads during the
+/// metadata pass, so it must be `Send + Sync`.
+pub type ProgressCallback = Arc<dyn Fn(ProgressEvent) + Send + Sync>;
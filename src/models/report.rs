@@ -0,0 +1,101 @@
+//! Schema of the JSON run report (`--report <FILE>`)
+
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use super::font::MetadataSource;
+
+/// Version of the report schema, bumped when fields change meaning or are
+/// removed. New fields may be added without a bump.
+pub const REPORT_FORMAT: u64 = 1;
+
+/// Every action a run took on a font, with run-level totals and timing.
+///
+/// The report is rewritten as the run goes, so a run that aborts still leaves
+/// the actions taken so far, with `complete` false.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Report {
+    /// `REPORT_FORMAT` of the writer
+    pub format: u64,
+    /// FontSrt version of the run
+    pub tool_version: String,
+    /// Run id shared with the event stream, when one is written
+    pub run_id: Option<String>,
+    /// Whether the run only planned its actions
+    pub dry_run: bool,
+    /// Whether the run finished; false for a report of an aborted run
+    pub complete: bool,
+    /// Start of the run in seconds since the Unix epoch
+    pub started_at: u64,
+    /// Seconds from the start of the run to the last write of the report
+    pub duration_secs: f64,
+    pub totals: ReportTotals,
+    /// One entry per action, in the order they were taken
+    pub fonts: Vec<ReportEntry>,
+}
+
+/// Number of report entries per action
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReportTotals {
+    pub fonts: usize,
+    pub moved: usize,
+    pub renamed: usize,
+    pub duplicate: usize,
+    pub skipped: usize,
+    pub error: usize,
+    pub planned: usize,
+}
+
+impl ReportTotals {
+    /// Count one entry with `action`
+    pub fn count(&mut self, action: ReportAction) {
+        self.fonts += 1;
+        match action {
+            ReportAction::Moved => self.moved += 1,
+            ReportAction::Renamed => self.renamed += 1,
+            ReportAction::Duplicate => self.duplicate += 1,
+            ReportAction::Skipped => self.skipped += 1,
+            ReportAction::Error => self.error += 1,
+            ReportAction::Planned => self.planned += 1,
+        }
+    }
+}
+
+/// What a run did with a font
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportAction {
+    /// Moved into its family folder, a foundry folder or a set-aside folder
+    Moved,
+    /// Renamed inside the folder it is in (`--rename-only`)
+    Renamed,
+    /// Moved to the duplicates folder
+    Duplicate,
+    /// Left in place: rejected by the filter or the lockfile
+    Skipped,
+    /// Could not be read or moved
+    Error,
+    /// A move or rename a dry run would make
+    Planned,
+}
+
+/// One action on one font
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportEntry {
+    /// Where the font was
+    #[serde(with = "super::path_serde")]
+    pub source: PathBuf,
+    /// Where it was moved, `None` when it stayed in place
+    #[serde(with = "super::path_serde::option")]
+    pub destination: Option<PathBuf>,
+    /// Family, foundry, weight and slant, when its metadata could be read
+    pub family: Option<String>,
+    pub foundry: Option<String>,
+    pub weight: Option<u16>,
+    pub italic: Option<bool>,
+    /// Where the family and style were read from; `filename` marks a guess
+    #[serde(default)]
+    pub metadata_source: Option<MetadataSource>,
+    pub action: ReportAction,
+    /// Why a font was set aside, skipped or failed
+    pub reason: Option<String>,
+}
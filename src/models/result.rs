@@ -0,0 +1,275 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use super::config::{MergeMode, MergeStrategy};
+use super::font::{FontLicense, FontScript, FontTechnology, SyntheticKind};
+
+/// Outcome of an `organize_fonts` run. Nothing is printed while organizing,
+/// the CLI prints this report with `print_organize_summary`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrganizeReport {
+    /// Fonts whose metadata could be read
+    pub fonts_processed: usize,
+    /// Fonts moved (or, in a dry run, that would be moved) into family folders
+    pub files_moved: usize,
+    /// Every font of the library the run organized, or would organize in a
+    /// dry run, sorted by destination
+    pub organized: Vec<OrganizedFont>,
+    /// Family folders fonts were organized into, sorted
+    #[serde(with = "super::path_serde::vec")]
+    pub family_dirs: Vec<PathBuf>,
+    /// Byte-identical copies and copies demoted by the duplicate policy,
+    /// moved to the duplicates directory
+    pub duplicates: usize,
+    /// Total size of the byte-identical copies in bytes
+    pub duplicate_bytes: u64,
+    /// Fonts above `Config::large_threshold`, pre-screened with a sampled hash
+    pub large_fonts: usize,
+    /// Total size of the large fonts in bytes
+    pub large_bytes: u64,
+    /// Copies demoted by the duplicate policy, sorted by path
+    pub demoted: Vec<DemotedFont>,
+    /// Where duplicates were moved
+    #[serde(with = "super::path_serde")]
+    pub duplicates_dir: PathBuf,
+    /// Faces flagged as synthetic obliques or faux weights, sorted by path
+    pub synthetic_faces: Vec<SyntheticFace>,
+    /// Processed fonts per technology generation, in `FontTechnology::ALL`
+    /// order, technologies without fonts left out
+    pub technologies: Vec<(FontTechnology, usize)>,
+    /// Processed fonts covering each script, in `FontScript::ALL` order,
+    /// scripts no font covers left out. A font covering several scripts
+    /// counts for each.
+    pub script_coverage: Vec<(FontScript, usize)>,
+    /// Processed fonts per license, in `FontLicense::ALL` order, licenses
+    /// without fonts left out
+    pub licenses: Vec<(FontLicense, usize)>,
+    /// Unreadable files with a font extension moved to the corrupt folder
+    pub corrupt_fonts: Vec<CorruptFont>,
+    /// Where corrupt fonts were moved
+    #[serde(with = "super::path_serde")]
+    pub corrupt_dir: PathBuf,
+    /// Fonts grouped by their typographic family instead of their legacy family
+    pub precedence_changes: Vec<PrecedenceChange>,
+    /// Fonts the lockfile does not approve, sorted by path
+    pub unapproved: Vec<UnapprovedFont>,
+    /// Where unapproved fonts were moved, unless the lockfile mode is skip
+    #[serde(with = "super::path_serde")]
+    pub unapproved_dir: PathBuf,
+    /// Lockfile families no font of the run belongs to
+    pub missing_families: Vec<String>,
+    /// Fonts whose family matches an exclude pattern, left in place, sorted
+    #[serde(with = "super::path_serde::vec")]
+    pub skipped_by_exclusion: Vec<PathBuf>,
+    /// Fonts of families installed on the system, left in place, sorted
+    #[serde(with = "super::path_serde::vec")]
+    pub skipped_system_fonts: Vec<PathBuf>,
+    /// Fonts the filter rejects, sorted; empty when the filter action is ignore
+    #[serde(with = "super::path_serde::vec")]
+    pub filtered: Vec<PathBuf>,
+    /// Where rejected fonts were moved when the filter action is quarantine
+    #[serde(with = "super::path_serde")]
+    pub filtered_dir: PathBuf,
+    /// Fonts without a usable name table, named from their file name, sorted
+    #[serde(with = "super::path_serde::vec")]
+    pub named_from_filename: Vec<PathBuf>,
+    /// Operating system junk files skipped while scanning (`--no-default-ignores` keeps them)
+    pub junk_skipped: usize,
+    /// Zip archives fonts were extracted from (`--extract-zips`)
+    pub zips_extracted: usize,
+    /// Fonts extracted from zip archives and organized with the rest
+    pub fonts_extracted: usize,
+    /// Whether the similarity merge phase ran, and the sample it was decided on
+    pub merge_decision: MergePhaseDecision,
+    /// Families merged into a similar family, by target family
+    pub family_merges: Vec<FamilyMerge>,
+    /// Wall-clock time of the parallel phase creating family folders and moving fonts
+    pub move_phase_time: Duration,
+    /// Time spent on each family during that phase, summed over families
+    pub move_work_time: Duration,
+    /// Files that could not be read, set aside or moved
+    pub errors: Vec<FileError>,
+    /// Whether the run only reported moves without performing them
+    pub dry_run: bool,
+}
+
+/// Former name of [`OrganizeReport`]
+pub type OrganizeResult = OrganizeReport;
+
+impl OrganizeReport {
+    /// Number of family folders fonts were organized into
+    pub fn families_created(&self) -> usize {
+        self.family_dirs.len()
+    }
+
+    /// Byte-identical copies among the duplicates
+    pub fn exact_duplicates(&self) -> usize {
+        self.duplicates - self.demoted.len()
+    }
+}
+
+/// A font of the organized library, with where the run put it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizedFont {
+    /// Where the font was found
+    #[serde(with = "super::path_serde")]
+    pub source: PathBuf,
+    /// Where it was moved or renamed to, or would be in a dry run; the same
+    /// as `source` for fonts already in place
+    #[serde(with = "super::path_serde")]
+    pub destination: PathBuf,
+    /// Family it was organized under, after merging
+    pub family: String,
+    pub subfamily: String,
+    pub foundry: String,
+    pub weight: u16,
+    pub is_italic: bool,
+}
+
+/// A font moved out of the library because a better copy of the same face exists
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DemotedFont {
+    /// Where the font was found
+    #[serde(with = "super::path_serde")]
+    pub path: PathBuf,
+    /// Version of the demoted font
+    pub version: Option<String>,
+    /// The copy that was kept instead
+    #[serde(with = "super::path_serde")]
+    pub kept: PathBuf,
+    /// Version of the kept copy
+    pub kept_version: Option<String>,
+    /// Why the kept copy won, e.g. "older version 2.001 < 3.000"
+    pub reason: String,
+}
+
+/// Whether the similarity merge phase of a run ran, and why
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergePhaseDecision {
+    /// The `--merge` mode the decision was made in
+    pub mode: MergeMode,
+    /// The `--merge-families` strategy of the run
+    pub strategy: MergeStrategy,
+    /// Whether similar families were merged
+    pub merged: bool,
+    /// Family names sampled in auto mode
+    pub sampled: usize,
+    /// Sampled names with at least one similar name among all families
+    pub with_neighbor: usize,
+    /// Share of sampled names with a similar name, in percent, below which
+    /// auto mode skips merging
+    pub threshold: f32,
+    /// A few sampled names and a similar name found for each
+    pub examples: Vec<(String, String)>,
+}
+
+/// A family merged into a family with a similar name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FamilyMerge {
+    /// Normalized name of the merged family
+    pub family: String,
+    /// Family whose folder its fonts joined
+    pub merged_into: String,
+}
+
+impl MergePhaseDecision {
+    /// Share of sampled names with a similar name, in percent
+    pub fn neighbor_rate(&self) -> f32 {
+        if self.sampled == 0 {
+            return 0.0;
+        }
+        self.with_neighbor as f32 * 100.0 / self.sampled as f32
+    }
+}
+
+/// A face that looks like a mechanically derived copy of another face
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyntheticFace {
+    /// Where the face was found
+    #[serde(with = "super::path_serde")]
+    pub path: PathBuf,
+    /// How it was derived
+    pub kind: SyntheticKind,
+    /// The face it appears to be derived from
+    #[serde(with = "super::path_serde")]
+    pub reference: PathBuf,
+}
+
+/// A file with a font extension that could not be parsed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorruptFont {
+    /// Where the file was found
+    #[serde(with = "super::path_serde")]
+    pub path: PathBuf,
+    /// Why it could not be parsed, e.g. "bad magic 0x12345678"
+    pub reason: String,
+}
+
+/// A font set aside or skipped because the lockfile does not approve it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnapprovedFont {
+    /// Where the font was found
+    #[serde(with = "super::path_serde")]
+    pub path: PathBuf,
+    /// Why it is not approved, e.g. "weight 300 is not approved for Inter"
+    pub reason: String,
+}
+
+/// A font grouped by its typographic family (name ID 16) rather than its
+/// legacy family (name ID 1)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrecedenceChange {
+    /// The font
+    #[serde(with = "super::path_serde")]
+    pub path: PathBuf,
+    /// Its legacy family name
+    pub legacy_family: String,
+    /// The typographic family it was grouped by
+    pub family: String,
+}
+
+/// Step of a run at which a file failed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorStage {
+    /// Reading the font's metadata or contents
+    Metadata,
+    /// Creating the folder the font belongs in
+    Directory,
+    /// Moving the font into place or setting it aside
+    Move,
+}
+
+impl fmt::Display for ErrorStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorStage::Metadata => write!(f, "metadata"),
+            ErrorStage::Directory => write!(f, "directory"),
+            ErrorStage::Move => write!(f, "move"),
+        }
+    }
+}
+
+/// A file left in place because a step of the run failed for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileError {
+    /// The file that was left in place
+    #[serde(with = "super::path_serde")]
+    pub path: PathBuf,
+    /// Step at which it failed
+    pub stage: ErrorStage,
+    /// Error text
+    pub error: String,
+}
+
+impl FileError {
+    /// Record a failure of `path` at `stage`
+    pub fn new(path: impl Into<PathBuf>, stage: ErrorStage, error: impl fmt::Display) -> Self {
+        Self {
+            path: path.into(),
+            stage,
+            error: error.to_string(),
+        }
+    }
+}
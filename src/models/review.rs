@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+/// A family merge proposed by the similarity merge phase, passed to
+/// `Config::merge_reviewer` before it is applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProposedMerge {
+    /// Normalized name of the family that would be merged
+    pub family: String,
+    /// Number of fonts in that family
+    pub fonts: usize,
+    /// Family whose folder its fonts would join
+    pub merged_into: String,
+    /// Number of fonts in the family merged into
+    pub merged_into_fonts: usize,
+}
+
+/// Answer to a proposed merge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeDecision {
+    /// Merge the family
+    Accept,
+    /// Keep the family in its own folder, as if the names did not match
+    Reject,
+    /// Merge this family and every remaining proposal without asking
+    AcceptAll,
+    /// Merge the family, and these two families again on later runs
+    Always,
+    /// Keep the family apart, and these two families on later runs
+    Never,
+    /// Keep this family and every remaining proposal apart without asking
+    RejectAll,
+    /// Stop the run before the move phase
+    Quit,
+}
+
+/// Decides each proposed merge. It is called from the thread running
+/// `organize_fonts`, one proposal at a time.
+pub type MergeReviewer = Arc<dyn Fn(&ProposedMerge) -> MergeDecision + Send + Sync>;
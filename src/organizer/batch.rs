@@ -1,70 +1,400 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
-use std::io::{self, Write};
-use crate::error::Result;
-use crate::models::Config;
-use super::{processor::organize_fonts, group::group_by_foundry};
+use crate::analysis::{measure_family_folders, print_largest_families};
+use rayon::prelude::*;
+use crate::error::{Error, Result};
+#[cfg(feature = "fingerprint")]
+use crate::font::fingerprint::{print_renamed_clusters, renamed_clusters_in};
+use crate::models::{BatchOutputLayout, Config, OrganizeResult, ProgressCallback, ProgressEvent, TopLevelGroup};
+use crate::utils::{clean_name, ensure_directory_exists, log_error, log_info, log_warn};
+use serde_json::json;
+use super::{
+    presets::{parse_batch_line, BatchLine},
+    snapshot::write_snapshot,
+    processor::organize_fonts,
+    group::group_by_foundry,
+    style::group_by_style,
+    technology::group_by_technology,
+    script::group_by_script,
+    license::group_by_license,
+    letter::group_by_first_letter,
+    classification::group_by_classification,
+    designer::{designer_families, group_by_designer, print_designer_families},
+};
 
-/// Process multiple directories listed in a batch file
-pub fn batch_process(config: &Config, batch_file: &Path) -> Result<()> {
-    println!("Batch processing enabled. Reading directories from {}", batch_file.display());
+/// Shared tracking maps passed to `organize_fonts` and the grouping steps
+#[derive(Clone)]
+struct SharedMaps {
+    processed_files: Arc<Mutex<HashSet<PathBuf>>>,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+    foundry_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+}
+
+impl SharedMaps {
+    fn new() -> Self {
+        Self {
+            processed_files: Arc::new(Mutex::new(HashSet::new())),
+            family_folders: Arc::new(Mutex::new(HashMap::new())),
+            foundry_folders: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+/// One batch source, organized in the parallel phase
+struct BatchJob {
+    /// 1-based position in the batch file, for progress lines
+    number: usize,
+    dir: PathBuf,
+    config: Config,
+    /// Preset and flags of the batch line, as applied
+    settings: Vec<String>,
+    output_dir: PathBuf,
+    maps: SharedMaps,
+}
+
+/// Process multiple directories listed in a batch file, up to
+/// `config.batch_parallelism` at a time.
+///
+/// Each line names a directory, optionally followed by a `@preset` and flags
+/// (`/fonts/client-a @archive --dry-run`). Global settings are overridden by
+/// the preset, and the preset by the flags of the line.
+///
+/// Sources are organized in parallel; their summaries and the grouping step
+/// follow one source at a time in batch file order. Whether families go into
+/// foundry folders is taken from `config.group_by_foundry`. Returns
+/// the outcome of each listed directory, so one failing source does not stop
+/// the others.
+pub fn batch_process(config: &Config, batch_file: &Path) -> Result<Vec<(PathBuf, Result<OrganizeResult>)>> {
+    log_info(config, format!("Batch processing enabled. Reading directories from {}", batch_file.display()));
 
     let content = fs::read_to_string(batch_file)?;
-    let dirs: Vec<&str> = content.lines()
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty() && !line.starts_with("#"))
+    let dirs: Vec<BatchLine> = content.lines()
+        .enumerate()
+        .filter_map(|(i, line)| parse_batch_line(i + 1, line))
         .collect();
 
-    println!("Found {} directories to process", dirs.len());
+    log_info(config, format!("Found {} directories to process", dirs.len()));
+
+    if let Some(root) = &config.batch_output {
+        ensure_directory_exists(root, config)?;
+        log_info(config, format!("Organizing into {} ({} layout)", root.display(), config.batch_output_layout));
+    }
+
+    // In merged mode every source shares one set of maps so families unify
+    // across sources, and sources are organized one at a time into the library
+    let merged_maps = SharedMaps::new();
+    let merged = config.batch_output.is_some() && config.batch_output_layout == BatchOutputLayout::Merged;
+    let parallelism = if merged { 1 } else { config.batch_parallelism.max(1) };
+    let mut used_source_names: HashSet<String> = HashSet::new();
+    let mut outcomes: Vec<(PathBuf, Result<OrganizeResult>)> = Vec::new();
+    let mut jobs: Vec<BatchJob> = Vec::new();
 
-    for (i, dir_str) in dirs.iter().enumerate() {
-        let dir_path = Path::new(dir_str);
+    for (i, line) in dirs.iter().enumerate() {
+        let dir_path = line.dir.as_path();
         if !dir_path.is_dir() {
-            println!("Warning: '{}' is not a valid directory, skipping", dir_str);
+            log_warn(config, format!("'{}' is not a valid directory, skipping", dir_path.display()));
+            outcomes.push((dir_path.to_path_buf(), Err(Error::InvalidPath(dir_path.to_path_buf()))));
             continue;
         }
 
-        println!("\nProcessing directory {}/{}: {}", i + 1, dirs.len(), dir_str);
+        let (mut dir_config, settings) = match line.resolve(config) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                log_warn(config, format!("{}, skipping {}", e, dir_path.display()));
+                outcomes.push((dir_path.to_path_buf(), Err(e)));
+                continue;
+            }
+        };
+        dir_config.output_dir = match (&config.batch_output, config.batch_output_layout) {
+            (Some(root), BatchOutputLayout::Merged) => Some(root.clone()),
+            (Some(root), BatchOutputLayout::PerSource) => {
+                Some(root.join(unique_source_name(dir_path, &mut used_source_names)))
+            }
+            (None, _) => None,
+        };
+        let output_dir = dir_config.output_dir.clone().unwrap_or_else(|| dir_path.to_path_buf());
 
-        // Use the same shared structures for all directories
-        let processed_files = Arc::new(Mutex::new(HashSet::new()));
-        let family_folders = Arc::new(Mutex::new(HashMap::new()));
-        let foundry_folders = Arc::new(Mutex::new(HashMap::new()));
+        // Summaries are reported after the parallel phase, in batch file order
+        dir_config.log_context = Some(dir_path.display().to_string());
+        let finished_dir = dir_path.to_path_buf();
+        dir_config.progress_callback = config.progress_callback.clone().map(|callback| -> ProgressCallback {
+            Arc::new(move |event| match event {
+                ProgressEvent::Complete(_) => callback(ProgressEvent::DirectoryFinished(finished_dir.clone())),
+                event => callback(event),
+            })
+        });
 
-        organize_fonts(
-            dir_path,
-            config,
-            processed_files.clone(),
-            family_folders.clone(),
-            foundry_folders.clone()
-        )?;
+        let maps = if merged { merged_maps.clone() } else { SharedMaps::new() };
+        jobs.push(BatchJob { number: i + 1, dir: dir_path.to_path_buf(), config: dir_config, settings, output_dir, maps });
+    }
 
-        print!("Would you like to group fonts by foundry for {}? (y/n): ", dir_str);
-        io::stdout().flush()?;
+    log_info(config, format!("Organizing {} directories, {} at a time", jobs.len(), parallelism));
+    config.report_progress(ProgressEvent::BatchStarted { directories: jobs.len() });
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(parallelism)
+        .build()
+        .map_err(|e| Error::Batch(format!("could not start {} batch threads: {}", parallelism, e)))?;
+    let results: Vec<Result<OrganizeResult>> = pool.install(|| {
+        jobs.par_iter()
+            // Families are grouped by foundry after organizing, as in single runs
+            .map(|job| organize_fonts(
+                &job.dir,
+                &Config { group_by_foundry: false, ..job.config.clone() },
+                job.maps.processed_files.clone(),
+                job.maps.family_folders.clone(),
+                job.maps.foundry_folders.clone()
+            ))
+            .collect()
+    });
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+    let mut family_dirs: Vec<PathBuf> = Vec::new();
+    for (job, result) in jobs.into_iter().zip(results) {
+        log_info(config, format!("\nDirectory {}/{}: {}", job.number, dirs.len(), job.dir.display()));
+        if job.config.output_dir.is_some() {
+            log_info(config, format!("Output directory: {}", job.output_dir.display()));
+        }
+        if !job.settings.is_empty() {
+            log_info(config, format!("Settings: {}", job.settings.join(" ")));
+        }
+        config.emit("batch_directory", json!({
+            "dir": job.dir.display().to_string(),
+            "settings": job.settings,
+            "config": job.config,
+            "ok": result.is_ok(),
+        }));
 
-        if input.trim().to_lowercase() == "y" {
-            println!("Grouping fonts by foundry for {}...", dir_str);
-            let mut config_with_foundry = config.clone();
-            config_with_foundry.group_by_foundry = true;
+        match &result {
+            Ok(report) => {
+                config.report_progress(ProgressEvent::Complete(Box::new(report.clone())));
 
-            group_by_foundry(
-                dir_path,
-                &config_with_foundry,
-                processed_files,
-                family_folders,
-                foundry_folders
-            )?;
+                // A merged library is grouped once after all sources have been organized
+                if !merged {
+                    let grouped = group_output(&job.output_dir, &job.config, &job.maps);
+                    family_dirs.extend(job.maps.family_folders.lock().unwrap().values().cloned());
+                    if let Err(e) = grouped {
+                        log_error(config, format!("Could not group {}: {}", job.output_dir.display(), e));
+                        outcomes.push((job.dir, Err(e)));
+                        continue;
+                    }
+                    if let Err(e) = write_snapshot(&job.output_dir, &job.dir, &job.config, &job.settings) {
+                        log_warn(config, format!("Could not write the decision snapshot of {}: {}", job.output_dir.display(), e));
+                    }
+                }
+            }
+            Err(e) => log_error(config, format!("Could not organize {}: {}", job.dir.display(), e)),
+        }
+        outcomes.push((job.dir, result));
+    }
 
-            println!("Fonts grouped by foundry successfully for {}!", dir_str);
+    if merged {
+        if let Some(root) = &config.batch_output {
+            log_info(config, String::new());
+            let mut root_config = config.clone();
+            root_config.output_dir = Some(root.clone());
+            group_output(root, &root_config, &merged_maps)?;
+            // Per-line presets are not recorded for a merged library, only the global settings
+            if let Err(e) = write_snapshot(root, root, &root_config, &[]) {
+                log_warn(config, format!("Could not write the decision snapshot of {}: {}", root.display(), e));
+            }
         }
+        family_dirs.extend(merged_maps.family_folders.lock().unwrap().values().cloned());
+    }
+
+    let processed_count = outcomes.iter().filter(|(_, result)| result.is_ok()).count();
+    log_info(config, "\nBatch processing complete!".to_string());
+    match &config.batch_output {
+        Some(root) => log_info(config, format!(
+            "  - {} of {} directories organized into {} ({} layout)",
+            processed_count, dirs.len(), root.display(), config.batch_output_layout
+        )),
+        None => log_info(config, format!("  - {} of {} directories organized in place", processed_count, dirs.len())),
+    }
+    for (dir, result) in &outcomes {
+        if let Err(e) = result {
+            log_info(config, format!("  - {} failed: {}", dir.display(), e));
+        }
+    }
+    family_dirs.retain(|dir| dir.is_dir());
+    family_dirs.sort();
+    family_dirs.dedup();
+    print_largest_families(&measure_family_folders(family_dirs.iter().cloned()), config.top_families);
+    if config.export_designers {
+        print_designer_families(&designer_families(&family_dirs, config));
+    }
+    #[cfg(feature = "fingerprint")]
+    if config.fingerprint {
+        print_renamed_clusters(&renamed_clusters_in(&family_dirs, config));
+    }
+    Ok(outcomes)
+}
+
+/// Run the optional grouping steps on an organized output directory, then
+/// bucket its top-level folders by first letter if asked
+fn group_output(output_dir: &Path, config: &Config, maps: &SharedMaps) -> Result<()> {
+    // Renamed fonts stay in the folders they were found in
+    if config.rename_only {
+        return Ok(());
+    }
+
+    group_families(output_dir, config, maps)?;
+    if config.top_level_group == TopLevelGroup::FirstLetter {
+        log_info(config, format!("Grouping folders by first letter for {}...", output_dir.display()));
+        group_by_first_letter(output_dir, config, maps.family_folders.clone())?;
+    }
+    Ok(())
+}
+
+/// Run the optional style, designer, technology, script, license, classification or foundry grouping step on an organized output directory.
+/// Foundry folders follow `config.group_by_foundry`, inside the classes when classifying.
+fn group_families(output_dir: &Path, config: &Config, maps: &SharedMaps) -> Result<()> {
+    let label = output_dir.display();
+
+    if config.group_by_style {
+        log_info(config, format!("Grouping fonts by style class for {}...", label));
+        return group_by_style(output_dir, config, maps.family_folders.clone());
+    }
+
+    if config.group_by_designer {
+        log_info(config, format!("Grouping fonts by designer for {}...", label));
+        return group_by_designer(output_dir, config, maps.family_folders.clone());
+    }
+
+    if config.group_by_technology {
+        log_info(config, format!("Grouping fonts by technology for {}...", label));
+        return group_by_technology(output_dir, config, maps.family_folders.clone());
+    }
+
+    if config.group_by_script {
+        log_info(config, format!("Grouping fonts by script for {}...", label));
+        return group_by_script(output_dir, config, maps.family_folders.clone());
+    }
+
+    if config.group_by_license {
+        log_info(config, format!("Grouping fonts by license for {}...", label));
+        return group_by_license(output_dir, config, maps.family_folders.clone());
+    }
+
+    if config.classify {
+        log_info(config, format!("Grouping fonts by classification for {}...", label));
+        return group_by_classification(
+            output_dir,
+            config,
+            maps.processed_files.clone(),
+            maps.family_folders.clone(),
+            maps.foundry_folders.clone()
+        );
+    }
+
+    if config.group_by_foundry {
+        log_info(config, format!("Grouping fonts by foundry for {}...", label));
+        group_by_foundry(
+            output_dir,
+            config,
+            maps.processed_files.clone(),
+            maps.family_folders.clone(),
+            maps.foundry_folders.clone()
+        )?;
+
+        log_info(config, format!("Fonts grouped by foundry successfully for {}!", label));
     }
 
-    println!("\nBatch processing complete!");
     Ok(())
 }
 
+/// Folder name for a source in the per-source layout, disambiguated when two
+/// sources share a directory name (e.g. `/a/fonts` and `/b/fonts`)
+fn unique_source_name(dir: &Path, used: &mut HashSet<String>) -> String {
+    let base = clean_name(&dir.file_name().unwrap_or_default().to_string_lossy());
+    let mut name = base.clone();
+    let mut counter = 2;
+
+    while !used.insert(name.clone()) {
+        name = format!("{}_{}", base, counter);
+        counter += 1;
+    }
+
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TestFont};
+
+    /// Font files below `dir`, relative to it, in path order
+    fn font_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for path in fs::read_dir(&current).unwrap().flatten().map(|entry| entry.path()) {
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "ttf") {
+                    files.push(path.strip_prefix(dir).unwrap().to_path_buf());
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    /// Two sources each holding one face of Roboto, listed in a batch file,
+    /// organized into `<tmp>/library` with the given layout
+    fn organize_two_sources(layout: BatchOutputLayout) -> (TempDir, Vec<PathBuf>) {
+        let tmp = TempDir::new("batch");
+        TestFont::new("Roboto", "Regular").write(&tmp.join("studio/fonts/Roboto-Regular.ttf"));
+        TestFont::new("Roboto", "Bold").weight(700).write(&tmp.join("archive/fonts/Roboto-Bold.ttf"));
+        let batch_file = tmp.join("batch.txt");
+        fs::write(&batch_file, format!(
+            "{}\n{}\n",
+            tmp.join("studio/fonts").display(),
+            tmp.join("archive/fonts").display()
+        )).unwrap();
+
+        let config = Config {
+            batch_output: Some(tmp.join("library")),
+            batch_output_layout: layout,
+            // Style grouping runs without asking about foundries
+            group_by_style: true,
+            ..Config::default()
+        };
+        let outcomes = batch_process(&config, &batch_file).unwrap();
+        assert!(outcomes.iter().all(|(_, result)| result.is_ok()));
+        let files = font_files(&tmp.join("library"));
+        (tmp, files)
+    }
+
+    #[test]
+    fn merged_layout_joins_a_family_split_across_sources() {
+        let (_tmp, files) = organize_two_sources(BatchOutputLayout::Merged);
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|file| file.starts_with("Unknown/Roboto")), "{:?}", files);
+    }
+
+    #[test]
+    fn per_source_layout_keeps_each_source_apart() {
+        let (_tmp, files) = organize_two_sources(BatchOutputLayout::PerSource);
+        assert_eq!(files.len(), 2);
+        assert!(files[0].starts_with("fonts/Unknown/Roboto"), "{:?}", files);
+        assert!(files[1].starts_with("fonts_2/Unknown/Roboto"), "{:?}", files);
+    }
+
+    #[test]
+    fn source_names_sharing_a_directory_name_get_a_suffix() {
+        let mut used = HashSet::new();
+        let names: Vec<String> = ["/a/fonts", "/b/fonts", "/c/Type", "/d/fonts"].iter()
+            .map(|dir| unique_source_name(Path::new(dir), &mut used))
+            .collect();
+        assert_eq!(names, ["fonts", "fonts_2", "Type", "fonts_3"]);
+    }
+
+    #[test]
+    fn layout_names() {
+        assert_eq!(BatchOutputLayout::from_name("merged"), Some(BatchOutputLayout::Merged));
+        assert_eq!(BatchOutputLayout::from_name("per-source"), Some(BatchOutputLayout::PerSource));
+        assert_eq!(BatchOutputLayout::from_name("flat"), None);
+        assert_eq!(BatchOutputLayout::PerSource.to_string(), "per-source");
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::fs;
+use crate::error::Result;
+use crate::models::{Config, FontClassification};
+use crate::utils::{
+    ensure_directory_exists,
+    safe_move_directory,
+    clean_name,
+    log,
+};
+use super::{group::{family_font_metadata, group_by_foundry}, is_set_aside_folder};
+
+/// Classification of a family by majority vote of its classified faces.
+/// A tie, or a family with no classified face, is Unclassified.
+pub fn family_classification(faces: &[FontClassification]) -> FontClassification {
+    let mut votes: HashMap<FontClassification, usize> = HashMap::new();
+    for class in faces.iter().filter(|class| **class != FontClassification::Unclassified) {
+        *votes.entry(*class).or_default() += 1;
+    }
+
+    let most = votes.values().copied().max().unwrap_or(0);
+    let mut leaders = votes.iter().filter(|(_, count)| **count == most);
+    match (leaders.next(), leaders.next()) {
+        (Some((class, _)), None) => *class,
+        _ => FontClassification::Unclassified,
+    }
+}
+
+/// Group font families into top-level folders by their type classification
+/// (Serif, Sans, Slab, Mono, Script, Display, Unclassified).
+///
+/// All fonts of a family stay together under the classification most of its
+/// faces have. With `config.group_by_foundry` set, families are grouped by
+/// foundry inside each classification folder (`Sans/Google/Roboto`).
+pub fn group_by_classification(
+    dir: &Path,
+    config: &Config,
+    processed_files: Arc<Mutex<HashSet<PathBuf>>>,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+    foundry_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<()> {
+    let classification_folder_names: Vec<String> = FontClassification::ALL.iter()
+        .map(|class| clean_name(&class.to_string()))
+        .collect();
+
+    // Classify each family folder by majority vote of its fonts
+    let mut family_to_classification: Vec<(String, FontClassification)> = Vec::new();
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let family_name = path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        // Skip set-aside folders and folders created by a previous classification
+        if is_set_aside_folder(&family_name, config) || classification_folder_names.contains(&family_name) {
+            continue;
+        }
+
+        let faces: Vec<FontClassification> = family_font_metadata(&path, config).iter()
+            .map(|metadata| metadata.classification)
+            .collect();
+        if !faces.is_empty() {
+            family_to_classification.push((family_name, family_classification(&faces)));
+        }
+    }
+
+    // Move each family folder into its classification folder
+    let mut classification_dirs: Vec<PathBuf> = Vec::new();
+    for (family, class) in family_to_classification {
+        let family_dir = dir.join(&family);
+        let class_dir = dir.join(clean_name(&class.to_string()));
+        ensure_directory_exists(&class_dir, config)?;
+
+        let target_dir = class_dir.join(&family);
+        log(
+            config,
+            format!("Moving {} to {} ({})", family_dir.display(), target_dir.display(), class),
+        );
+        safe_move_directory(&family_dir, &target_dir, config)?;
+
+        family_folders.lock().unwrap().insert(family, target_dir);
+        if !classification_dirs.contains(&class_dir) {
+            classification_dirs.push(class_dir);
+        }
+    }
+
+    // Dry runs leave the families where they are, so there is nothing to group further
+    if config.group_by_foundry && !config.dry_run {
+        for class_dir in classification_dirs {
+            group_by_foundry(
+                &class_dir,
+                config,
+                processed_files.clone(),
+                family_folders.clone(),
+                foundry_folders.clone()
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::fs;
+use crate::error::Result;
+use crate::models::Config;
+use crate::utils::{
+    ensure_directory_exists,
+    safe_move_directory,
+    clean_name,
+    log,
+};
+use super::{group::first_font_metadata, is_set_aside_folder};
+
+/// Group font families into top-level folders by their designer (name ID 9),
+/// falling back to the foundry for fonts that don't name a designer
+pub fn group_by_designer(
+    dir: &Path,
+    config: &Config,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<()> {
+    // Determine the designer of each family folder from its first readable font
+    let mut family_to_designer: Vec<(String, String)> = Vec::new();
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let family_name = path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        if is_set_aside_folder(&family_name, config) {
+            continue;
+        }
+
+        if let Some(metadata) = first_font_metadata(&path, config) {
+            let designer = clean_name(metadata.designer_or_foundry());
+
+            // A folder named after its own designer was created by a previous grouping
+            if designer != family_name {
+                family_to_designer.push((family_name, designer));
+            }
+        }
+    }
+
+    // Move each family folder into its designer folder
+    for (family, designer) in family_to_designer {
+        let family_dir = dir.join(&family);
+        let designer_dir = dir.join(&designer);
+        ensure_directory_exists(&designer_dir, config)?;
+
+        let target_dir = designer_dir.join(&family);
+        log(
+            config,
+            format!("Moving {} to {} (designer {})", family_dir.display(), target_dir.display(), designer),
+        );
+        safe_move_directory(&family_dir, &target_dir, config)?;
+
+        family_folders.lock().unwrap().insert(family, target_dir);
+    }
+
+    Ok(())
+}
+
+/// Map each designer (or foundry, when no designer is named) to the families
+/// whose folders are listed
+pub fn designer_families(family_dirs: &[PathBuf], config: &Config) -> BTreeMap<String, BTreeSet<String>> {
+    let mut designers: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    for dir in family_dirs {
+        if let Some(metadata) = first_font_metadata(dir, config) {
+            designers.entry(metadata.designer_or_foundry().to_string())
+                .or_default()
+                .insert(metadata.family_name);
+        }
+    }
+
+    designers
+}
+
+/// Print a `designer -> [families]` mapping
+pub fn print_designer_families(designers: &BTreeMap<String, BTreeSet<String>>) {
+    for (designer, families) in designers {
+        let families: Vec<&str> = families.iter().map(String::as_str).collect();
+        println!("{} -> [{}]", designer, families.join(", "));
+    }
+}
@@ -0,0 +1,389 @@
+//! Setting aside duplicate fonts before they are organized, and planning
+//! where set-aside files go
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use rayon::prelude::*;
+use crate::font::metadata::{read_font_quality, FontQuality};
+use crate::models::{Config, DemotedFont, DuplicatePolicy, ErrorStage, FileError, FontMetadata, MoveAction, PlannedMove, ProgressEvent};
+use crate::utils::{collision_free_path, content_hash, log, log_error, sampled_hash};
+
+/// Size of `path` in bytes if it is a large font, above `Config::large_threshold`
+pub(crate) fn large_font_size(path: &Path, config: &Config) -> Option<u64> {
+    fs::metadata(path).ok()
+        .map(|meta| meta.len())
+        .filter(|size| *size > config.large_threshold)
+}
+
+/// Hash large fonts by sampled hash, then fully hash those whose sampled hash
+/// matches another's, so only candidate copies are read in full.
+///
+/// Returns the confirmed groups by content hash, ready for `set_aside_exact_duplicates`.
+pub(crate) fn hash_large_fonts(
+    large_fonts: &[(PathBuf, u64)],
+    config: &Config,
+    errors: &Mutex<Vec<FileError>>,
+) -> HashMap<blake3::Hash, Vec<PathBuf>> {
+    let hash_all = |fonts: &[&(PathBuf, u64)], hash: fn(&Path) -> crate::error::Result<blake3::Hash>| {
+        let hashes: Mutex<HashMap<blake3::Hash, Vec<PathBuf>>> = Mutex::new(HashMap::new());
+        fonts.par_iter().for_each(|(path, size)| {
+            config.report_progress(ProgressEvent::HashingLargeFile { path: path.clone(), size: *size });
+            match hash(path) {
+                Ok(hash) => hashes.lock().unwrap().entry(hash).or_default().push(path.clone()),
+                Err(e) => {
+                    log_error(config, format!("Could not hash {}: {}", path.display(), e));
+                    errors.lock().unwrap().push(FileError::new(path, ErrorStage::Metadata, format!("could not hash: {}", e)));
+                }
+            }
+        });
+        hashes.into_inner().unwrap()
+    };
+
+    let all: Vec<&(PathBuf, u64)> = large_fonts.iter().collect();
+    let samples = hash_all(&all, sampled_hash);
+    let shared: HashSet<&PathBuf> = samples.values().filter(|paths| paths.len() > 1).flatten().collect();
+    let candidates: Vec<&(PathBuf, u64)> = large_fonts.iter().filter(|(path, _)| shared.contains(path)).collect();
+    if !candidates.is_empty() {
+        log(config, format!("{} large fonts share a sampled hash, confirming with a full hash", candidates.len()));
+    }
+    hash_all(&candidates, content_hash)
+}
+
+/// Files a run sets aside, each planned with a destination no other file of
+/// the run claims
+#[derive(Default)]
+pub(crate) struct SetAsidePlan {
+    pub moves: Vec<PlannedMove>,
+    claimed: HashSet<PathBuf>,
+}
+
+impl SetAsidePlan {
+    /// Plan moving a font into a set-aside folder under its original name and
+    /// drop it from the run, returning where it goes
+    pub fn add(
+        &mut self,
+        path: &Path,
+        dir: &Path,
+        action: MoveAction,
+        reason: String,
+        font_metadata_map: &Mutex<HashMap<PathBuf, FontMetadata>>,
+        processed_files: &Mutex<HashSet<PathBuf>>,
+    ) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path.extension().map(|extension| extension.to_string_lossy());
+        let target = collision_free_path(dir, &stem, extension.as_deref(), &self.claimed);
+        self.claimed.insert(target.clone());
+        self.moves.push(PlannedMove::new(path.to_path_buf(), target.clone(), action).with_reason(reason));
+
+        font_metadata_map.lock().unwrap().remove(path);
+        processed_files.lock().unwrap().insert(path.to_path_buf());
+        target
+    }
+}
+
+/// Folder a duplicate is set aside in: the folder of its family inside
+/// `duplicates_dir`, where it keeps its original file name
+fn family_duplicates_dir(
+    path: &Path,
+    duplicates_dir: &Path,
+    config: &Config,
+    font_metadata_map: &Mutex<HashMap<PathBuf, FontMetadata>>,
+) -> PathBuf {
+    match font_metadata_map.lock().unwrap().get(path) {
+        Some(metadata) => duplicates_dir.join(config.folder_name(&metadata.family_name)),
+        None => duplicates_dir.to_path_buf(),
+    }
+}
+
+/// Set aside every byte-identical copy of a font except the first (by path)
+/// into its family's folder in `duplicates_dir` and drop it from the metadata map.
+///
+/// Returns how many files are set aside and their total size in bytes.
+pub(crate) fn set_aside_exact_duplicates(
+    font_hashes: &HashMap<blake3::Hash, Vec<PathBuf>>,
+    duplicates_dir: &Path,
+    config: &Config,
+    font_metadata_map: &Mutex<HashMap<PathBuf, FontMetadata>>,
+    processed_files: &Mutex<HashSet<PathBuf>>,
+    set_aside: &mut SetAsidePlan,
+) -> (usize, u64) {
+    let mut count = 0;
+    let mut bytes = 0;
+
+    // Groups in path order, so set-aside copies sharing a name get the same
+    // collision suffixes on every run
+    let mut groups: Vec<Vec<PathBuf>> = font_hashes.values()
+        .filter(|paths| paths.len() > 1)
+        .map(|paths| {
+            let mut paths = paths.clone();
+            paths.sort();
+            paths
+        })
+        .collect();
+    groups.sort();
+
+    for paths in &groups {
+        let original = &paths[0];
+
+        for duplicate in &paths[1..] {
+            let size = fs::metadata(duplicate).map(|meta| meta.len()).unwrap_or(0);
+            let dir = family_duplicates_dir(duplicate, duplicates_dir, config, font_metadata_map);
+            log(config, format!(
+                "{} is identical to {}, setting it aside in {}",
+                duplicate.display(), original.display(), dir.display()
+            ));
+
+            let reason = format!("identical to {}", original.display());
+            set_aside.add(duplicate, &dir, MoveAction::Deduplicate, reason, font_metadata_map, processed_files);
+            count += 1;
+            bytes += size;
+        }
+    }
+
+    (count, bytes)
+}
+
+/// Resolve fonts sharing a family/weight/width/italic signature according to
+/// the configured duplicate policy, setting aside all but the kept copy into
+/// its family's folder in `duplicates_dir`. Fonts already set aside are ignored.
+pub(crate) fn resolve_signature_duplicates(
+    font_signatures: &HashMap<String, Vec<PathBuf>>,
+    duplicates_dir: &Path,
+    config: &Config,
+    font_metadata_map: &Mutex<HashMap<PathBuf, FontMetadata>>,
+    processed_files: &Mutex<HashSet<PathBuf>>,
+    set_aside: &mut SetAsidePlan,
+) -> Vec<DemotedFont> {
+    if config.duplicate_policy == DuplicatePolicy::KeepAll {
+        return Vec::new();
+    }
+
+    let groups: Vec<Vec<PathBuf>> = {
+        let metadata_map = font_metadata_map.lock().unwrap();
+        font_signatures.values()
+            .map(|paths| {
+                let mut paths: Vec<PathBuf> = paths.iter()
+                    .filter(|path| metadata_map.contains_key(*path))
+                    .cloned()
+                    .collect();
+                paths.sort();
+                paths
+            })
+            .filter(|paths| paths.len() > 1)
+            .collect()
+    };
+
+    let mut demoted: Vec<DemotedFont> = groups.par_iter()
+        .flat_map_iter(|paths| match config.duplicate_policy {
+            DuplicatePolicy::KeepBest => rank_by_quality(paths),
+            _ => paths[1..].iter()
+                .map(|path| DemotedFont {
+                    path: path.clone(),
+                    version: None,
+                    kept: paths[0].clone(),
+                    kept_version: None,
+                    reason: "keep-first policy, kept copy comes first by path".to_string(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    // A large font is only set aside as a confirmed byte-identical copy of the kept one
+    demoted.retain(|font| {
+        if large_font_size(&font.path, config).is_none() {
+            return true;
+        }
+        let identical = matches!((content_hash(&font.path), content_hash(&font.kept)), (Ok(a), Ok(b)) if a == b);
+        if !identical {
+            log(config, format!(
+                "Keeping large font {}: not byte-identical to {} ({})",
+                font.path.display(), font.kept.display(), font.reason
+            ));
+        }
+        identical
+    });
+
+    // Versions come from the metadata already read for every font
+    {
+        let metadata_map = font_metadata_map.lock().unwrap();
+        let version_of = |path: &PathBuf| metadata_map.get(path).and_then(|metadata| metadata.version.clone());
+        for font in &mut demoted {
+            font.version = version_of(&font.path);
+            font.kept_version = version_of(&font.kept);
+        }
+    }
+    demoted.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for font in &demoted {
+        let dir = family_duplicates_dir(&font.path, duplicates_dir, config, font_metadata_map);
+        log(config, format!(
+            "Demoting {} to {}: {} (kept {})",
+            font.path.display(), dir.display(), font.reason, font.kept.display()
+        ));
+        set_aside.add(&font.path, &dir, MoveAction::Deduplicate, font.reason.clone(), font_metadata_map, processed_files);
+    }
+
+    demoted
+}
+
+/// Keep the best copy of a face and explain why each other copy lost
+fn rank_by_quality(paths: &[PathBuf]) -> Vec<DemotedFont> {
+    let mut ranked: Vec<(&PathBuf, Option<FontQuality>)> = paths.iter()
+        .map(|path| (path, read_font_quality(path)))
+        .collect();
+    ranked.sort_by(|(a_path, a), (b_path, b)| compare_quality(a.as_ref(), b.as_ref()).then_with(|| a_path.cmp(b_path)));
+
+    let (kept_path, kept) = &ranked[0];
+    ranked[1..].iter()
+        .map(|(path, quality)| DemotedFont {
+            path: (*path).clone(),
+            version: None,
+            kept: (*kept_path).clone(),
+            kept_version: None,
+            reason: demotion_reason(kept.as_ref(), quality.as_ref()),
+        })
+        .collect()
+}
+
+/// Order copies best first: higher version, then CFF over TrueType, then more glyphs
+fn compare_quality(a: Option<&FontQuality>, b: Option<&FontQuality>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => b.version_number().total_cmp(&a.version_number())
+            .then_with(|| b.is_cff.cmp(&a.is_cff))
+            .then_with(|| b.glyph_count.cmp(&a.glyph_count)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn demotion_reason(kept: Option<&FontQuality>, demoted: Option<&FontQuality>) -> String {
+    let (Some(kept), Some(demoted)) = (kept, demoted) else {
+        return "unreadable version and glyph data".to_string();
+    };
+
+    if kept.version != demoted.version {
+        format!(
+            "older version {} < {}",
+            demoted.version.as_deref().unwrap_or("(none)"),
+            kept.version.as_deref().unwrap_or("(none)")
+        )
+    } else if kept.is_cff != demoted.is_cff {
+        format!("TrueType outlines, kept CFF copy of version {}", kept.version.as_deref().unwrap_or("(none)"))
+    } else if kept.glyph_count != demoted.glyph_count {
+        format!("fewer glyphs {} < {}", demoted.glyph_count, kept.glyph_count)
+    } else {
+        "same version, format and glyph count, kept copy comes first by path".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom, Write};
+    use std::sync::Arc;
+    use crate::testing::TempDir;
+
+    /// A sparse large file of 4 MB, zero except for each `(offset, bytes)`
+    fn large_file(path: &Path, writes: &[(u64, &[u8])]) -> (PathBuf, u64) {
+        let mut file = fs::File::create(path).unwrap();
+        file.set_len(4 << 20).unwrap();
+        for (offset, bytes) in writes {
+            file.seek(SeekFrom::Start(*offset)).unwrap();
+            file.write_all(bytes).unwrap();
+        }
+        (path.to_path_buf(), 4 << 20)
+    }
+
+    #[test]
+    fn large_threshold_is_exclusive() {
+        let tmp = TempDir::new("large-fonts");
+        let (path, size) = large_file(&tmp.join("NotoSansCJK.otf"), &[(0, b"OTTO")]);
+        let at = Config { large_threshold: size, ..Config::default() };
+        let below = Config { large_threshold: size - 1, ..Config::default() };
+        assert_eq!(large_font_size(&path, &at), None);
+        assert_eq!(large_font_size(&path, &below), Some(size));
+        assert_eq!(large_font_size(&path, &Config::default()), None);
+    }
+
+    #[test]
+    fn sampled_matches_are_confirmed_by_full_hash() {
+        let tmp = TempDir::new("large-fonts");
+        let fonts = [
+            large_file(&tmp.join("a.otf"), &[(0, b"OTTO")]),
+            large_file(&tmp.join("b.otf"), &[(0, b"OTTO")]),
+            // Same ends as the copies, different in the middle
+            large_file(&tmp.join("c.otf"), &[(0, b"OTTO"), (2 << 20, b"CJK")]),
+            // Different first megabyte, never read in full
+            large_file(&tmp.join("d.otf"), &[(0, b"OTTO"), (16, b"CJK")]),
+        ];
+        let hashed: Arc<Mutex<Vec<PathBuf>>> = Arc::default();
+        let events = Arc::clone(&hashed);
+        let config = Config {
+            progress_callback: Some(Arc::new(move |event| {
+                if let ProgressEvent::HashingLargeFile { path, size } = event {
+                    assert_eq!(size, 4 << 20);
+                    events.lock().unwrap().push(path);
+                }
+            })),
+            ..Config::default()
+        };
+
+        let mut groups: Vec<Vec<PathBuf>> = hash_large_fonts(&fonts, &config, &Mutex::default()).into_values()
+            .map(|mut paths| {
+                paths.sort();
+                paths
+            })
+            .collect();
+        groups.sort();
+        assert_eq!(groups, [vec![tmp.join("a.otf"), tmp.join("b.otf")], vec![tmp.join("c.otf")]]);
+
+        // Every font is sampled, and only the three sharing a sample are read in full
+        let hashed = hashed.lock().unwrap();
+        assert_eq!(hashed.len(), 7);
+        assert_eq!(hashed.iter().filter(|path| path.ends_with("d.otf")).count(), 1);
+    }
+
+    #[test]
+    fn exact_duplicates_are_set_aside_in_path_order() {
+        let config = Config::default();
+        let duplicates_dir = Path::new("/library/Duplicates");
+        let groups = [
+            ("z", vec!["/in/z/Font.ttf", "/in/y/Font.ttf"]),
+            ("c", vec!["/in/c/Font.ttf", "/in/b/Font.ttf", "/in/d/Other.ttf"]),
+            ("m", vec!["/in/m/Font.ttf", "/in/n/Font.ttf"]),
+        ];
+
+        let mut targets = None;
+        for _ in 0..10 {
+            // A fresh map each time, so its iteration order varies
+            let font_hashes: HashMap<blake3::Hash, Vec<PathBuf>> = groups.iter()
+                .map(|(seed, paths)| (blake3::hash(seed.as_bytes()), paths.iter().map(PathBuf::from).collect()))
+                .collect();
+            let mut set_aside = SetAsidePlan::default();
+            let (count, _) = set_aside_exact_duplicates(
+                &font_hashes, duplicates_dir, &config, &Mutex::default(), &Mutex::default(), &mut set_aside,
+            );
+            assert_eq!(count, 4);
+
+            let run: Vec<(PathBuf, PathBuf)> = set_aside.moves.iter()
+                .map(|planned| (planned.source.clone(), planned.destination.clone()))
+                .collect();
+            assert_eq!(*targets.get_or_insert_with(|| run.clone()), run);
+        }
+
+        let expected = [
+            ("/in/c/Font.ttf", "/library/Duplicates/Font.ttf"),
+            ("/in/d/Other.ttf", "/library/Duplicates/Other.ttf"),
+            ("/in/n/Font.ttf", "/library/Duplicates/Font_1.ttf"),
+            ("/in/z/Font.ttf", "/library/Duplicates/Font_2.ttf"),
+        ];
+        let expected: Vec<(PathBuf, PathBuf)> = expected.iter()
+            .map(|(source, destination)| (PathBuf::from(source), PathBuf::from(destination)))
+            .collect();
+        assert_eq!(targets.unwrap(), expected);
+    }
+}
@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::Path;
+use serde_json::{json, Value};
+use crate::error::Result;
+use crate::models::FileError;
+
+/// Print the files a run left in place, one row per file with its stage and error
+pub fn print_error_report(errors: &[FileError]) {
+    if errors.is_empty() {
+        return;
+    }
+
+    println!("\n{} files could not be organized:", errors.len());
+    println!("  {:<10} {:<50} ERROR", "STAGE", "FILE");
+    for error in errors {
+        println!("  {:<10} {:<50} {}", error.stage.to_string(), error.path.display(), error.error);
+    }
+}
+
+/// The files a run left in place as a JSON array of `{path, stage, error}`
+/// objects, tagged with the run id when the run has an event stream
+pub fn error_report_json(errors: &[FileError], run_id: Option<&str>) -> Value {
+    Value::Array(errors.iter()
+        .map(|error| {
+            let mut entry = json!({
+                "path": error.path.display().to_string(),
+                "stage": error.stage.to_string(),
+                "error": error.error,
+            });
+            if let Some(run_id) = run_id {
+                entry["run_id"] = json!(run_id);
+            }
+            entry
+        })
+        .collect())
+}
+
+/// Write the error report to `path` as pretty-printed JSON
+pub fn write_error_report(path: &Path, errors: &[FileError], run_id: Option<&str>) -> Result<()> {
+    fs::write(path, format!("{:#}\n", error_report_json(errors, run_id)))?;
+    Ok(())
+}
@@ -1,17 +1,52 @@
 use std::path::{Path, PathBuf};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use std::fs;
 use crate::error::Result;
-use crate::models::Config;
-use crate::font::metadata::extract_font_metadata;
+use crate::models::{Config, FontMetadata, ProgressEvent, ReportAction};
+use crate::font::cache::cached_font_metadata;
+use super::{is_set_aside_folder, is_weight_folder};
+use super::processor::{is_font_extension, VARIABLE_FOLDER};
 use crate::utils::{
+    canonical_casing,
     ensure_directory_exists,
     safe_move_directory,
-    clean_name,
+    collision_free_path,
     log,
+    log_warn,
 };
 
+/// Metadata of the first readable font in a family folder
+pub(crate) fn first_font_metadata(family_dir: &Path, config: &Config) -> Option<FontMetadata> {
+    let entries: Vec<PathBuf> = fs::read_dir(family_dir).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+
+    entries.iter()
+        .filter(|path| path.is_file())
+        .find_map(|path| cached_font_metadata(path, config).ok().flatten())
+        // Families grouped by weight keep their fonts one level down
+        .or_else(|| entries.iter()
+            .filter(|path| path.is_dir())
+            .find_map(|path| first_font_metadata(path, config)))
+}
+
+/// Metadata of every readable font in a family folder, weight folders included
+pub(crate) fn family_font_metadata(family_dir: &Path, config: &Config) -> Vec<FontMetadata> {
+    let Ok(entries) = fs::read_dir(family_dir) else { return Vec::new() };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+
+    paths.iter()
+        .flat_map(|path| if path.is_dir() {
+            family_font_metadata(path, config)
+        } else {
+            cached_font_metadata(path, config).ok().flatten().into_iter().collect()
+        })
+        .collect()
+}
+
 /// Group font families by their foundry
 pub fn group_by_foundry(
     dir: &Path,
@@ -20,49 +55,97 @@ pub fn group_by_foundry(
     family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
     foundry_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
 ) -> Result<()> {
-    // Create a map to track which family belongs to which foundry
-    let mut family_to_foundry: HashMap<String, String> = HashMap::new();
+    // Create a map to track which family belongs to which foundry, sorted so
+    // folders merge, and clashing files are suffixed, in the same order every run
+    let mut family_to_foundry: BTreeMap<String, String> = BTreeMap::new();
+
+    // Foundries of the family folders this run organized into `dir`, by folder name
+    let known_foundries: HashMap<String, String> = match &config.family_foundries {
+        Some(foundries) => {
+            let foundries = foundries.lock().unwrap();
+            family_folders.lock().unwrap().iter()
+                .filter(|(_, family_dir)| family_dir.parent() == Some(dir))
+                .filter_map(|(family, family_dir)| Some((folder_name(family_dir), foundries.get(family)?.clone())))
+                .collect()
+        }
+        None => HashMap::new(),
+    };
 
     // First, scan the directory for font files to determine foundry for each family
-    for entry in fs::read_dir(dir)? {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-
-            // Only process directories (font family folders)
-            if path.is_dir() && path.file_name().unwrap_or_default() != "duplicates" {
-                let family_name = path.file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-                    .into_owned();
-
-                // Scan this family folder for font files to determine foundry
-                if let Ok(dir_entries) = fs::read_dir(&path) {
-                    for file_entry in dir_entries {
-                        if let Ok(file_entry) = file_entry {
-                            let file_path = file_entry.path();
-                            if file_path.is_file() {
-                                if let Ok(Some(metadata)) = extract_font_metadata(&file_path, config) {
-                                    family_to_foundry.insert(family_name.clone(), clean_name(&metadata.foundry));
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let name = folder_name(&path);
+
+        // Only process directories (font family folders)
+        if !path.is_dir() || is_set_aside_folder(&name, config) {
+            continue;
+        }
+
+        // Foundry folders of an earlier grouping are already in place
+        if is_foundry_folder(&path, config) {
+            foundry_folders.lock().unwrap().entry(name).or_insert(path);
+            continue;
+        }
+
+        // Families organized this run have a known foundry, others are scanned
+        if let Some(foundry) = known_foundries.get(&name) {
+            log(config, format!("Foundry of '{}' from the organize run: {}", name, foundry));
+            family_to_foundry.insert(name, config.folder_name(foundry));
+        } else if let Some(metadata) = first_font_metadata(&path, config) {
+            log(config, format!("Foundry of '{}' from a rescan: {}", name, metadata.foundry));
+            family_to_foundry.insert(name, config.folder_name(&metadata.foundry));
         }
     }
 
+    // Foundry names differing only in case share one folder: that of an
+    // earlier grouping, else the spelling most families use
+    let mut spellings: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+    for foundry in family_to_foundry.values() {
+        *spellings.entry(foundry.to_lowercase()).or_default().entry(foundry.as_str()).or_default() += 1;
+    }
+    let canonical_foundries: HashMap<String, String> = {
+        let existing = foundry_folders.lock().unwrap();
+        spellings.into_iter()
+            .filter_map(|(key, counts)| {
+                let name = existing.keys().filter(|name| name.to_lowercase() == key).min().cloned()
+                    .or_else(|| canonical_casing(counts).map(str::to_string))?;
+                Some((key, name))
+            })
+            .collect()
+    };
+    for foundry in family_to_foundry.values_mut() {
+        if let Some(name) = canonical_foundries.get(&foundry.to_lowercase()) {
+            *foundry = name.clone();
+        }
+    }
+
+    // Foundry folders already reported as created, dry runs never create them
+    let mut created_foundry_dirs: HashSet<PathBuf> = HashSet::new();
+
+    // Fonts of each family folder for the run report, listed before any folder moves
+    let moved_fonts: HashMap<&String, Vec<PathBuf>> = family_to_foundry.keys()
+        .map(|family| (family, if config.run_report.is_some() { folder_files(&dir.join(family)) } else { Vec::new() }))
+        .collect();
+
+    // Family folders named like a foundry, in any case, move aside first, so
+    // no family is moved into another family's folder, or into itself
+    let foundries: HashSet<String> = family_to_foundry.values().map(|foundry| foundry.to_lowercase()).collect();
+    let staged = stage_folders(dir, family_to_foundry.keys().filter(|family| foundries.contains(&family.to_lowercase())), "grouping", config)?;
+
     // Now move each family folder to its foundry folder
-    for (family, foundry) in family_to_foundry {
-        let family_dir = dir.join(&family);
-        let foundry_dir = dir.join(&foundry);
+    for (family, foundry) in &family_to_foundry {
+        let original_dir = dir.join(family);
+        let foundry_dir = dir.join(foundry);
+        let family_dir = staged.get(family).cloned().unwrap_or_else(|| original_dir.clone());
 
         // Create foundry directory if it doesn't exist
+        if !foundry_dir.is_dir() && created_foundry_dirs.insert(foundry_dir.clone()) {
+            config.report_progress(ProgressEvent::CreatingDirectory(foundry_dir.clone()));
+        }
         ensure_directory_exists(&foundry_dir, config)?;
 
         // Move family folder to foundry folder
-        let target_dir = foundry_dir.join(&family);
+        let target_dir = foundry_dir.join(family);
 
         if target_dir.exists() {
             // If target directory already exists, merge contents
@@ -86,6 +169,8 @@ pub fn group_by_foundry(
             );
             safe_move_directory(&family_dir, &target_dir, config)?;
         }
+        config.report_progress(ProgressEvent::MovingFile { from: original_dir.clone(), to: target_dir.clone() });
+        record_folder_move(&original_dir, &target_dir, &moved_fonts[family], config);
 
         // Update the tracking maps
         family_folders.lock().unwrap().insert(family.clone(), target_dir.clone());
@@ -93,6 +178,180 @@ pub fn group_by_foundry(
             .or_insert_with(|| foundry_dir.clone());
     }
 
+    config.report_checkpoint();
     Ok(())
 }
 
+/// Undo `group_by_foundry`: move the family folders out of every foundry
+/// folder in `dir` and remove the emptied foundry folders. A family whose
+/// folder name is already taken in `dir` is merged into that folder.
+/// Returns the number of family folders moved.
+pub fn ungroup_foundries(
+    dir: &Path,
+    config: &Config,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<usize> {
+    let mut foundry_dirs: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !is_set_aside_folder(&folder_name(path), config))
+        .filter(|path| is_foundry_folder(path, config))
+        .collect();
+    foundry_dirs.sort();
+
+    // Family folders of each foundry folder, with their fonts for the run
+    // report, listed before any folder moves
+    type FamilyFolder = (String, Vec<PathBuf>);
+    let mut foundries: Vec<(PathBuf, Vec<FamilyFolder>)> = Vec::new();
+    for foundry_dir in foundry_dirs {
+        let mut families: Vec<String> = fs::read_dir(&foundry_dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .map(|path| folder_name(&path))
+            .collect();
+        families.sort();
+        let families = families.into_iter()
+            .map(|family| {
+                let fonts = if config.run_report.is_some() { folder_files(&foundry_dir.join(&family)) } else { Vec::new() };
+                (family, fonts)
+            })
+            .collect();
+        foundries.push((foundry_dir, families));
+    }
+
+    // Foundry folders named like a family move aside first, so no family is
+    // merged into a foundry folder, or moved onto its own
+    let family_names: HashSet<&String> = foundries.iter()
+        .flat_map(|(_, families)| families.iter().map(|(family, _)| family))
+        .collect();
+    let foundry_names: Vec<String> = foundries.iter().map(|(foundry_dir, _)| folder_name(foundry_dir)).collect();
+    let staged = stage_folders(dir, foundry_names.iter().filter(|foundry| family_names.contains(foundry)), "ungrouping", config)?;
+
+    let mut moved = 0;
+    for (foundry_dir, families) in &foundries {
+        let source_dir = staged.get(&folder_name(foundry_dir)).cloned().unwrap_or_else(|| foundry_dir.clone());
+
+        for (family, moved_fonts) in families {
+            let original_dir = foundry_dir.join(family);
+            let target_dir = dir.join(family);
+
+            log(config, format!("Moving {} to {}", original_dir.display(), target_dir.display()));
+            safe_move_directory(&source_dir.join(family), &target_dir, config)?;
+            config.report_progress(ProgressEvent::MovingFile { from: original_dir.clone(), to: target_dir.clone() });
+            record_folder_move(&original_dir, &target_dir, moved_fonts, config);
+
+            for path in family_folders.lock().unwrap().values_mut() {
+                if let Ok(rest) = path.strip_prefix(&original_dir) {
+                    *path = target_dir.join(rest);
+                }
+            }
+            moved += 1;
+        }
+
+        if !config.dry_run {
+            if let Err(e) = fs::remove_dir(&source_dir) {
+                log_warn(config, format!("Could not remove foundry folder {}: {}", source_dir.display(), e));
+            }
+        }
+    }
+
+    config.report_checkpoint();
+    Ok(moved)
+}
+
+/// Move the named folders of `dir` to temporary `<name>.<purpose>` folders,
+/// returning where each went, so their names are free for other folders
+fn stage_folders<'a>(
+    dir: &Path,
+    names: impl Iterator<Item = &'a String>,
+    purpose: &str,
+    config: &Config,
+) -> Result<HashMap<String, PathBuf>> {
+    let mut staged = HashMap::new();
+    for name in names {
+        let staging = collision_free_path(dir, &format!("{}.{}", name, purpose), None, &HashSet::new());
+        log(config, format!("Moving {} aside to {}", dir.join(name).display(), staging.display()));
+        safe_move_directory(&dir.join(name), &staging, config)?;
+        staged.insert(name.clone(), staging);
+    }
+    Ok(staged)
+}
+
+/// Whether a folder is the foundry folder of an earlier grouping: it holds no
+/// fonts itself, and the fonts of its subfolders (other than weight and
+/// `Variable` folders) come from the foundry it is named after
+fn is_foundry_folder(dir: &Path, config: &Config) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else { return false };
+    let paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    if paths.iter().any(|path| path.is_file() && is_font_extension(path)) {
+        return false;
+    }
+
+    let foundry = folder_name(dir);
+    let mut families = 0;
+    for path in paths.iter().filter(|path| path.is_dir()) {
+        // Folders without readable fonts say nothing either way
+        let Some(metadata) = first_font_metadata(path, config) else { continue };
+        let name = folder_name(path);
+        if config.folder_name(&metadata.foundry) != foundry || name == VARIABLE_FOLDER || is_weight_folder(&name, metadata.weight, metadata.width_class()) {
+            return false;
+        }
+        families += 1;
+    }
+    families > 0
+}
+
+/// Final component of a path
+fn folder_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Files below a folder, weight folders included, sorted
+fn folder_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut files: Vec<PathBuf> = entries.flatten()
+        .map(|entry| entry.path())
+        .flat_map(|path| if path.is_dir() { folder_files(&path) } else { vec![path] })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Add a report entry for each font moved along with its family folder
+fn record_folder_move(from: &Path, to: &Path, files: &[PathBuf], config: &Config) {
+    for file in files.iter().filter(|file| is_font_extension(file)) {
+        if let Ok(relative) = file.strip_prefix(from) {
+            config.record_action(file, Some(&to.join(relative)), ReportAction::Moved, None);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glob::Pattern;
+    use crate::font::foundry::FoundryOverride;
+    use crate::testing::{TempDir, TestFont};
+
+    #[test]
+    fn foundry_spellings_share_one_folder() {
+        let tmp = TempDir::new("foundry-case");
+        for family in ["Gotham", "Mercury", "Sentinel"] {
+            TestFont::new(family, "Regular").write(&tmp.join(family).join(format!("{}.ttf", family)));
+        }
+        let overrides = [("Gotham", "Hoefler"), ("Mercury", "Hoefler"), ("Sentinel", "HOEFLER")]
+            .map(|(family, foundry)| FoundryOverride { pattern: Pattern::new(family).unwrap(), foundry: foundry.to_string() });
+        let config = Config { foundry_overrides: Some(Arc::new(overrides.to_vec())), ..Config::default() };
+
+        group_by_foundry(tmp.path(), &config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+        let top_level: Vec<String> = fs::read_dir(tmp.path()).unwrap().flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(top_level, ["Hoefler"]);
+        for family in ["Gotham", "Mercury", "Sentinel"] {
+            assert!(tmp.join("Hoefler").join(family).join(format!("{}.ttf", family)).is_file(), "{}", family);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::fs;
+use crate::error::Result;
+use crate::models::Config;
+use crate::utils::{
+    ensure_directory_exists,
+    safe_move_directory,
+    transliterate_name,
+    log,
+};
+use super::is_set_aside_folder;
+
+/// Bucket for names that do not start with an ASCII letter
+pub const OTHER_LETTER_FOLDER: &str = "#";
+
+/// Letter bucket of a top-level folder name: its uppercase first letter, or
+/// `#` when it does not start with an ASCII letter. With `--transliterate`
+/// the name's ASCII approximation decides, so "Żuraw Sans" goes to `Z`.
+pub fn first_letter_folder(name: &str, config: &Config) -> String {
+    let name = if config.transliterate { transliterate_name(name) } else { name.to_string() };
+    match name.chars().next() {
+        Some(first) if first.is_ascii_alphabetic() => first.to_ascii_uppercase().to_string(),
+        _ => OTHER_LETTER_FOLDER.to_string(),
+    }
+}
+
+/// Whether a top-level folder is a letter bucket from an earlier run
+fn is_letter_folder(name: &str) -> bool {
+    name == OTHER_LETTER_FOLDER || (name.len() == 1 && name.bytes().all(|b| b.is_ascii_uppercase()))
+}
+
+/// Move every top-level folder into an A-Z or `#` bucket by its first letter.
+///
+/// Runs after any other grouping, so it buckets family folders, or the
+/// foundry (style, script, ...) folders they were grouped into, e.g.
+/// `A/Adobe/Acumin/`. Set-aside folders such as `duplicates` stay at the root.
+pub fn group_by_first_letter(
+    dir: &Path,
+    config: &Config,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<()> {
+    let mut folders: Vec<String> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .map(|path| path.file_name().unwrap_or_default().to_string_lossy().into_owned())
+        .filter(|name| !is_set_aside_folder(name, config) && !is_letter_folder(name))
+        .collect();
+    folders.sort();
+
+    for folder in folders {
+        let source = dir.join(&folder);
+        let letter_dir = dir.join(first_letter_folder(&folder, config));
+        ensure_directory_exists(&letter_dir, config)?;
+
+        let target_dir = letter_dir.join(&folder);
+        log(config, format!("Moving {} to {}", source.display(), target_dir.display()));
+        safe_move_directory(&source, &target_dir, config)?;
+
+        // Families inside a moved grouping folder moved with it
+        for family_dir in family_folders.lock().unwrap().values_mut() {
+            if let Ok(rest) = family_dir.strip_prefix(&source) {
+                *family_dir = target_dir.join(rest);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn first_letter_buckets() {
+        let config = Config::default();
+        let cases = [
+            ("arial", "A"),
+            ("Roboto", "R"),
+            ("Żuraw Sans", "#"),
+            ("3D Block", "#"),
+            ("", "#"),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(first_letter_folder(name, &config), expected, "{}", name);
+        }
+
+        let transliterate = Config { transliterate: true, ..Config::default() };
+        assert_eq!(first_letter_folder("Żuraw Sans", &transliterate), "Z");
+        assert_eq!(first_letter_folder("arial", &transliterate), "A");
+    }
+
+    #[test]
+    fn folders_are_bucketed_and_set_aside_folders_stay() {
+        let tmp = TempDir::new("first-letter");
+        for folder in ["arial", "Żuraw Sans", "Adobe/Acumin", "duplicates", "R/Roboto"] {
+            fs::create_dir_all(tmp.join(folder)).unwrap();
+        }
+        fs::write(tmp.join("Adobe/Acumin/Acumin.otf"), b"font").unwrap();
+        let family_folders: Arc<Mutex<HashMap<String, PathBuf>>> = Arc::new(Mutex::new(HashMap::from([
+            ("Acumin".to_string(), tmp.join("Adobe/Acumin")),
+            ("arial".to_string(), tmp.join("arial")),
+        ])));
+
+        group_by_first_letter(tmp.path(), &Config::default(), family_folders.clone()).unwrap();
+        let mut top_level: Vec<String> = fs::read_dir(tmp.path()).unwrap().flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        top_level.sort();
+        assert_eq!(top_level, ["#", "A", "R", "duplicates"]);
+        assert!(tmp.join("A/arial").is_dir());
+        assert!(tmp.join("#/Żuraw Sans").is_dir());
+        // Foundry grouping composes, and buckets of an earlier run are left alone
+        assert!(tmp.join("A/Adobe/Acumin/Acumin.otf").is_file());
+        assert!(tmp.join("R/Roboto").is_dir());
+
+        let family_folders = family_folders.lock().unwrap();
+        assert_eq!(family_folders["Acumin"], tmp.join("A/Adobe/Acumin"));
+        assert_eq!(family_folders["arial"], tmp.join("A/arial"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::fs;
+use crate::error::Result;
+use crate::models::{Config, FontLicense};
+use crate::utils::{
+    ensure_directory_exists,
+    safe_move_directory,
+    clean_name,
+    log,
+};
+use super::{group::family_font_metadata, is_set_aside_folder};
+
+/// Folder for families under the OFL, Apache or GPL with font exception
+pub const LIBRE_FOLDER: &str = "Libre";
+/// Folder for families under a commercial license
+pub const COMMERCIAL_FOLDER: &str = "Commercial";
+/// Folder for families whose license is unknown or mixed
+pub const UNKNOWN_LICENSE_FOLDER: &str = "Unknown License";
+
+/// License folder of a family. A family is libre or commercial only when
+/// every readable face says so; a single unknown face, or a mix of libre and
+/// commercial faces, keeps the family out of `Libre`.
+pub fn family_license_folder(licenses: &[FontLicense]) -> &'static str {
+    if !licenses.is_empty() && licenses.iter().all(FontLicense::is_libre) {
+        LIBRE_FOLDER
+    } else if !licenses.is_empty() && licenses.iter().all(|license| *license == FontLicense::Proprietary) {
+        COMMERCIAL_FOLDER
+    } else {
+        UNKNOWN_LICENSE_FOLDER
+    }
+}
+
+/// Group font families into top-level `Libre`, `Commercial` and
+/// `Unknown License` folders
+pub fn group_by_license(
+    dir: &Path,
+    config: &Config,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<()> {
+    let license_folder_names: Vec<String> = [LIBRE_FOLDER, COMMERCIAL_FOLDER, UNKNOWN_LICENSE_FOLDER].iter()
+        .map(|folder| clean_name(folder))
+        .collect();
+
+    // Determine the license folder of each family folder from all its readable fonts
+    let mut family_to_folder: Vec<(String, &str)> = Vec::new();
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let family_name = path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        // Skip set-aside folders and folders created by a previous license grouping
+        if is_set_aside_folder(&family_name, config) || license_folder_names.contains(&family_name) {
+            continue;
+        }
+
+        let licenses: Vec<FontLicense> = family_font_metadata(&path, config).iter()
+            .map(|metadata| metadata.license)
+            .collect();
+        if licenses.is_empty() {
+            continue;
+        }
+        family_to_folder.push((family_name, family_license_folder(&licenses)));
+    }
+
+    // Move each family folder into its license folder
+    for (family, folder) in family_to_folder {
+        let family_dir = dir.join(&family);
+        let license_dir = dir.join(clean_name(folder));
+        ensure_directory_exists(&license_dir, config)?;
+
+        let target_dir = license_dir.join(&family);
+        log(
+            config,
+            format!("Moving {} to {} ({})", family_dir.display(), target_dir.display(), folder),
+        );
+        safe_move_directory(&family_dir, &target_dir, config)?;
+
+        family_folders.lock().unwrap().insert(family, target_dir);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,345 @@
+//! Allow-list of approved font families (`--lockfile`)
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use super::duplicates::SetAsidePlan;
+use crate::error::{Error, Result};
+use crate::font::metadata::extract_root_family;
+use crate::models::{Config, FontMetadata, MoveAction, PlannedMove, SkipReason, UnapprovedFont};
+use crate::utils::{log, normalize_family_name};
+
+/// A family approved by the lockfile, optionally limited to some weights and styles
+#[derive(Debug, Clone)]
+pub struct ApprovedFamily {
+    /// Family name as written in the lockfile
+    pub name: String,
+    /// Matching key of the name
+    key: String,
+    /// Allowed weights, any weight when `None`
+    pub weights: Option<Vec<u16>>,
+    /// Whether upright faces are allowed
+    pub upright: bool,
+    /// Whether italic and oblique faces are allowed
+    pub italic: bool,
+}
+
+impl ApprovedFamily {
+    /// Why a face of this family is not approved, `None` if it is
+    fn rejection(&self, metadata: &FontMetadata) -> Option<String> {
+        if let Some(weights) = &self.weights {
+            if !weights.contains(&metadata.weight) {
+                return Some(format!("weight {} is not approved for {}", metadata.weight, self.name));
+            }
+        }
+        if metadata.is_italic && !self.italic {
+            return Some(format!("italics are not approved for {}", self.name));
+        }
+        if !metadata.is_italic && !self.upright {
+            return Some(format!("upright faces are not approved for {}", self.name));
+        }
+        None
+    }
+}
+
+/// Approved families of a project, loaded from a TOML lockfile:
+///
+/// ```toml
+/// [families]
+/// "Helvetica Neue" = { weights = [400, 700], styles = ["upright"] }
+/// "Inter" = {}
+/// ```
+///
+/// `weights` and `styles` ("upright", "italic") are optional and allow
+/// everything when left out.
+#[derive(Debug, Clone, Default)]
+pub struct FontLockfile {
+    /// Approved families in lockfile order
+    pub families: Vec<ApprovedFamily>,
+}
+
+impl FontLockfile {
+    /// Load a lockfile
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| Error::Config(format!("Invalid lockfile {}: {}", path.display(), e)))
+    }
+
+    /// Parse lockfile contents
+    pub fn parse(content: &str) -> std::result::Result<Self, String> {
+        let table: toml::Table = content.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+        let families = table.get("families")
+            .ok_or("missing [families] table")?
+            .as_table()
+            .ok_or("'families' must be a table")?;
+
+        let mut lockfile = FontLockfile::default();
+        for (name, entry) in families {
+            let entry = entry.as_table()
+                .ok_or_else(|| format!("family '{}' must map to a table such as {{}}", name))?;
+
+            let weights = match entry.get("weights") {
+                None => None,
+                Some(weights) => Some(weights.as_array()
+                    .ok_or_else(|| format!("weights of '{}' must be a list", name))?
+                    .iter()
+                    .map(|weight| weight.as_integer()
+                        .and_then(|weight| u16::try_from(weight).ok())
+                        .ok_or_else(|| format!("weights of '{}' must be numbers such as 400", name)))
+                    .collect::<std::result::Result<Vec<u16>, String>>()?),
+            };
+
+            let (upright, italic) = match entry.get("styles") {
+                None => (true, true),
+                Some(styles) => {
+                    let styles = styles.as_array().ok_or_else(|| format!("styles of '{}' must be a list", name))?;
+                    let mut allowed = (false, false);
+                    for style in styles {
+                        match style.as_str().map(str::to_lowercase).as_deref() {
+                            Some("upright" | "normal" | "regular" | "roman") => allowed.0 = true,
+                            Some("italic" | "oblique") => allowed.1 = true,
+                            _ => return Err(format!("styles of '{}' must be \"upright\" or \"italic\", got {}", name, style)),
+                        }
+                    }
+                    allowed
+                }
+            };
+
+            lockfile.families.push(ApprovedFamily {
+                name: name.clone(),
+                key: family_match_key(name),
+                weights,
+                upright,
+                italic,
+            });
+        }
+
+        Ok(lockfile)
+    }
+
+    /// The approved family a font belongs to, if any
+    pub fn family_of(&self, metadata: &FontMetadata) -> Option<&ApprovedFamily> {
+        let key = family_match_key(&metadata.family_name);
+        self.families.iter().find(|family| family.key == key)
+    }
+
+    /// Why a font is not approved, `None` if it is
+    pub fn check(&self, metadata: &FontMetadata) -> Option<String> {
+        match self.family_of(metadata) {
+            Some(family) => family.rejection(metadata),
+            None => Some(format!("family '{}' is not in the lockfile", metadata.family_name)),
+        }
+    }
+
+    /// Approved families none of `fonts` belongs to, in lockfile order
+    pub fn missing_families<'a>(&self, fonts: impl IntoIterator<Item = &'a FontMetadata>) -> Vec<String> {
+        let present: HashSet<String> = fonts.into_iter()
+            .map(|metadata| family_match_key(&metadata.family_name))
+            .collect();
+        self.families.iter()
+            .filter(|family| !present.contains(&family.key))
+            .map(|family| family.name.clone())
+            .collect()
+    }
+}
+
+/// Key families are matched by: the grouping key with case and anything but
+/// letters and digits ignored, so "HelveticaNeue" matches "Helvetica Neue"
+pub fn family_match_key(family_name: &str) -> String {
+    normalize_family_name(&extract_root_family(family_name))
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// What happens to fonts the lockfile does not approve
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockfileMode {
+    /// Move them into the `_Unapproved` review folder
+    #[default]
+    Review,
+    /// Leave them where they are
+    Skip,
+}
+
+impl LockfileMode {
+    /// Parse a mode from its command-line name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "review" => Some(LockfileMode::Review),
+            "skip" => Some(LockfileMode::Skip),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LockfileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockfileMode::Review => write!(f, "review"),
+            LockfileMode::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+/// Check every font of the run against the lockfile. Unapproved fonts are set
+/// aside into `unapproved_dir` in review mode, or left in place in skip mode,
+/// and dropped from the run either way.
+///
+/// Returns the unapproved fonts, those left in place as skipped moves, and
+/// the lockfile families no font belongs to.
+pub(crate) fn apply_lockfile(
+    lockfile: &FontLockfile,
+    unapproved_dir: &Path,
+    config: &Config,
+    font_metadata_map: &Mutex<HashMap<PathBuf, FontMetadata>>,
+    processed_files: &Mutex<HashSet<PathBuf>>,
+    set_aside: &mut SetAsidePlan,
+) -> (Vec<UnapprovedFont>, Vec<PlannedMove>, Vec<String>) {
+    let metadata_map = font_metadata_map.lock().unwrap().clone();
+    let missing_families = lockfile.missing_families(metadata_map.values());
+
+    let mut unapproved: Vec<UnapprovedFont> = metadata_map.iter()
+        .filter_map(|(path, metadata)| {
+            lockfile.check(metadata).map(|reason| UnapprovedFont { path: path.clone(), reason })
+        })
+        .collect();
+    unapproved.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut skipped = Vec::new();
+    for font in &unapproved {
+        match config.lockfile_mode {
+            LockfileMode::Review => {
+                log(config, format!("Moving unapproved {} ({}) to {}", font.path.display(), font.reason, unapproved_dir.display()));
+                set_aside.add(&font.path, unapproved_dir, MoveAction::Move, font.reason.clone(), font_metadata_map, processed_files);
+            }
+            LockfileMode::Skip => {
+                log(config, format!("Skipping unapproved {} ({})", font.path.display(), font.reason));
+                skipped.push(PlannedMove::skip(font.path.clone(), SkipReason::Unapproved).with_reason(font.reason.clone()));
+                font_metadata_map.lock().unwrap().remove(&font.path);
+                processed_files.lock().unwrap().insert(font.path.clone());
+            }
+        }
+    }
+
+    (unapproved, skipped, missing_families)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::font::metadata::extract_font_metadata_from_bytes;
+    use crate::organizer::processor::organize_fonts;
+    use crate::testing::{TempDir, TestFont};
+
+    const LOCKFILE: &str = r#"
+        [families]
+        "Helvetica Neue" = { weights = [400, 700], styles = ["upright"] }
+        "Inter" = {}
+        "Lato" = { styles = ["italic"] }
+    "#;
+
+    fn face(family: &str, subfamily: &str, weight: u16, italic: bool) -> FontMetadata {
+        let font = TestFont::new(family, subfamily).weight(weight).fs_selection(u16::from(italic));
+        extract_font_metadata_from_bytes(Path::new("font.ttf"), &font.build(), &Config::default())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_weights_and_styles() {
+        let lockfile = FontLockfile::parse(LOCKFILE).unwrap();
+        let families: Vec<(&str, Option<&[u16]>, bool, bool)> = lockfile.families.iter()
+            .map(|family| (family.name.as_str(), family.weights.as_deref(), family.upright, family.italic))
+            .collect();
+        assert_eq!(families, [
+            ("Helvetica Neue", Some(&[400, 700][..]), true, false),
+            ("Inter", None, true, true),
+            ("Lato", None, false, true),
+        ]);
+    }
+
+    #[test]
+    fn rejects_malformed_lockfiles() {
+        let cases = [
+            ("", "missing [families] table"),
+            ("families = 1", "'families' must be a table"),
+            ("[families]\nInter = 1", "family 'Inter' must map to a table such as {}"),
+            ("[families]\nInter = { weights = [\"bold\"] }", "weights of 'Inter' must be numbers such as 400"),
+            ("[families]\nInter = { styles = [\"slanted\"] }", "styles of 'Inter' must be \"upright\" or \"italic\", got \"slanted\""),
+        ];
+        for (content, message) in cases {
+            assert_eq!(FontLockfile::parse(content).unwrap_err(), message);
+        }
+    }
+
+    #[test]
+    fn partial_weight_and_style_allowances() {
+        let lockfile = FontLockfile::parse(LOCKFILE).unwrap();
+        assert_eq!(lockfile.check(&face("Helvetica Neue", "Regular", 400, false)), None);
+        assert_eq!(lockfile.check(&face("Helvetica Neue", "Bold", 700, false)), None);
+        assert_eq!(
+            lockfile.check(&face("Helvetica Neue", "Light", 300, false)).as_deref(),
+            Some("weight 300 is not approved for Helvetica Neue"),
+        );
+        assert_eq!(
+            lockfile.check(&face("Helvetica Neue", "Bold Italic", 700, true)).as_deref(),
+            Some("italics are not approved for Helvetica Neue"),
+        );
+        assert_eq!(
+            lockfile.check(&face("Lato", "Regular", 400, false)).as_deref(),
+            Some("upright faces are not approved for Lato"),
+        );
+        assert_eq!(
+            lockfile.check(&face("Roboto", "Regular", 400, false)).as_deref(),
+            Some("family 'Roboto' is not in the lockfile"),
+        );
+    }
+
+    #[test]
+    fn names_match_after_normalization() {
+        assert_eq!(family_match_key("HelveticaNeue"), family_match_key("Helvetica Neue"));
+        assert_eq!(family_match_key("helvetica-neue"), family_match_key("Helvetica Neue"));
+        assert_ne!(family_match_key("Helvetica"), family_match_key("Helvetica Neue"));
+
+        let lockfile = FontLockfile::parse(LOCKFILE).unwrap();
+        let font = face("HelveticaNeue", "Regular", 400, false);
+        assert_eq!(lockfile.family_of(&font).map(|family| family.name.as_str()), Some("Helvetica Neue"));
+    }
+
+    #[test]
+    fn reports_missing_families() {
+        let lockfile = FontLockfile::parse(LOCKFILE).unwrap();
+        let fonts = [face("HelveticaNeue", "Regular", 400, false), face("Roboto", "Regular", 400, false)];
+        assert_eq!(lockfile.missing_families(&fonts), ["Inter", "Lato"]);
+    }
+
+    #[test]
+    fn unapproved_fonts_go_to_the_review_folder_or_stay() {
+        for mode in [LockfileMode::Review, LockfileMode::Skip] {
+            let tmp = TempDir::new("lockfile");
+            TestFont::new("Inter", "Regular").write(&tmp.join("inter.ttf"));
+            TestFont::new("Roboto", "Regular").write(&tmp.join("roboto.ttf"));
+            let config = Config {
+                lockfile: Some(Arc::new(FontLockfile::parse(LOCKFILE).unwrap())),
+                lockfile_mode: mode,
+                ..Config::default()
+            };
+
+            let report = organize_fonts(tmp.path(), &config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+            assert!(tmp.join("Inter/Inter.ttf").is_file());
+            assert_eq!(report.unapproved.len(), 1);
+            assert_eq!(report.missing_families, ["Helvetica Neue", "Lato"]);
+            match mode {
+                LockfileMode::Review => assert!(tmp.join("_Unapproved/roboto.ttf").is_file()),
+                LockfileMode::Skip => assert!(tmp.join("roboto.ttf").is_file()),
+            }
+        }
+    }
+}
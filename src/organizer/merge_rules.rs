@@ -0,0 +1,240 @@
+//! User overrides of the similarity merge (`--merge-rules`, `merges.toml`)
+//! and the answers remembered by `--interactive-merge`
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::font::foundry::user_config_dir;
+use crate::models::MergeDecision;
+
+/// Family names matched by a merge rule. A plain name matches exactly,
+/// ignoring case; glob wildcards are allowed.
+fn name_pattern(name: &str) -> std::result::Result<Pattern, String> {
+    Pattern::new(name).map_err(|e| format!("invalid family pattern '{}': {}", name, e))
+}
+
+fn matches_name(pattern: &Pattern, family_name: &str) -> bool {
+    let options = MatchOptions {
+        case_sensitive: false,
+        ..MatchOptions::new()
+    };
+    pattern.matches_with(family_name, options)
+}
+
+/// Two families that stay apart even when their names look alike
+#[derive(Debug)]
+struct NeverMerge {
+    first: Pattern,
+    second: Pattern,
+    fired: AtomicUsize,
+}
+
+/// Families organized into one target folder whatever their names
+#[derive(Debug)]
+struct ForceMerge {
+    pattern: Pattern,
+    target: String,
+    fired: AtomicUsize,
+}
+
+/// Merge decisions pinned by the user, loaded from a TOML file:
+///
+/// ```toml
+/// never_merge = [["DIN", "DIN Pro"], ["Nova*", "Novela"]]
+///
+/// [force_merge]
+/// "Helvetica Neue" = ["HelveticaNeue*", "Helvetica Neue LT Std"]
+/// ```
+///
+/// Force-merges move fonts whose family matches one of the names into the
+/// target family before the similarity merge; never-merge pairs are kept
+/// apart by it. Every rule counts how often it fired, for the end-of-run report.
+#[derive(Debug, Default)]
+pub struct MergeRules {
+    never_merge: Vec<NeverMerge>,
+    force_merge: Vec<ForceMerge>,
+}
+
+impl MergeRules {
+    /// Load a merge rules file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content).map_err(|e| Error::Config(format!("Invalid merge rules file {}: {}", path.display(), e)))
+    }
+
+    /// Parse merge rules
+    pub fn parse(content: &str) -> std::result::Result<Self, String> {
+        let table: toml::Table = content.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+        let mut rules = MergeRules::default();
+
+        if let Some(pairs) = table.get("never_merge") {
+            for pair in pairs.as_array().ok_or("'never_merge' must be a list of name pairs")? {
+                let names: Vec<&str> = pair.as_array()
+                    .map(|names| names.iter().filter_map(|name| name.as_str()).collect())
+                    .unwrap_or_default();
+                let [first, second] = names[..] else {
+                    return Err(format!("never_merge entries must be pairs of names such as [\"DIN\", \"DIN Pro\"], got {}", pair));
+                };
+                rules.never_merge.push(NeverMerge {
+                    first: name_pattern(first)?,
+                    second: name_pattern(second)?,
+                    fired: AtomicUsize::new(0),
+                });
+            }
+        }
+
+        if let Some(targets) = table.get("force_merge") {
+            for (target, names) in targets.as_table().ok_or("'force_merge' must be a table")? {
+                let names: Vec<&str> = match names {
+                    toml::Value::String(name) => vec![name.as_str()],
+                    toml::Value::Array(names) => names.iter()
+                        .map(|name| name.as_str().ok_or_else(|| format!("names merged into '{}' must be strings", target)))
+                        .collect::<std::result::Result<_, _>>()?,
+                    _ => return Err(format!("'{}' must map to a name or a list of names", target)),
+                };
+                for name in names {
+                    rules.force_merge.push(ForceMerge {
+                        pattern: name_pattern(name)?,
+                        target: target.clone(),
+                        fired: AtomicUsize::new(0),
+                    });
+                }
+            }
+        }
+
+        Ok(rules)
+    }
+
+    /// Target family of the first force-merge matching a font's family name
+    /// or the family group it would join, counting the rule as fired
+    pub fn force_target(&self, family_name: &str, family_group: &str) -> Option<&str> {
+        let rule = self.force_merge.iter()
+            .find(|rule| matches_name(&rule.pattern, family_name) || matches_name(&rule.pattern, family_group))?;
+        rule.fired.fetch_add(1, Ordering::Relaxed);
+        Some(&rule.target)
+    }
+
+    /// Whether a never-merge pair keeps two families apart, counting the rule as fired
+    pub fn keeps_apart(&self, a: &str, b: &str) -> bool {
+        let rule = self.never_merge.iter().find(|rule| {
+            (matches_name(&rule.first, a) && matches_name(&rule.second, b))
+                || (matches_name(&rule.first, b) && matches_name(&rule.second, a))
+        });
+        match rule {
+            Some(rule) => {
+                rule.fired.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every rule with how often it fired so far, in file order: never-merge
+    /// pairs first, then force-merges
+    pub fn rule_counts(&self) -> Vec<(String, usize)> {
+        let never = self.never_merge.iter().map(|rule| (
+            format!("never_merge \"{}\" / \"{}\"", rule.first.as_str(), rule.second.as_str()),
+            rule.fired.load(Ordering::Relaxed),
+        ));
+        let force = self.force_merge.iter().map(|rule| (
+            format!("force_merge \"{}\" -> \"{}\"", rule.pattern.as_str(), rule.target),
+            rule.fired.load(Ordering::Relaxed),
+        ));
+        never.chain(force).collect()
+    }
+
+    /// Print the end-of-run report: rules that fired, then rules that never did
+    pub fn print_report(&self) {
+        let counts = self.rule_counts();
+        let (fired, unused): (Vec<_>, Vec<_>) = counts.iter().partition(|(_, fired)| *fired > 0);
+        println!("Merge rules: {} of {} rules fired", fired.len(), counts.len());
+        for (rule, count) in fired {
+            println!("  {:>5}  {}", count, rule);
+        }
+
+        if !unused.is_empty() {
+            println!("  Rules that never fired (candidates to prune):");
+            for (rule, _) in unused {
+                println!("    - {}", rule);
+            }
+        }
+    }
+}
+
+/// Default location of the remembered merge decisions, `~/.config/fontsrt/merge_rules.json`
+pub fn default_remembered_merges_file() -> Option<PathBuf> {
+    Some(user_config_dir()?.join("merge_rules.json"))
+}
+
+/// Merge decisions answered "always" or "never" with `--interactive-merge`,
+/// so later runs do not ask about the same families again:
+///
+/// ```json
+/// {"always": [["Nova", "Novela"]], "never": [["DIN", "DIN Pro"]]}
+/// ```
+///
+/// Pairs match family names ignoring case, in either order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RememberedMerges {
+    #[serde(default)]
+    pub always: Vec<(String, String)>,
+    #[serde(default)]
+    pub never: Vec<(String, String)>,
+}
+
+impl RememberedMerges {
+    /// Load remembered decisions; a missing file remembers none
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| Error::Config(format!("Invalid remembered merges file {}: {}", path.display(), e)))
+    }
+
+    /// Write the decisions, creating the folder if needed
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Config(format!("Cannot serialize remembered merges: {}", e)))?;
+        fs::write(path, json + "\n")?;
+        Ok(())
+    }
+
+    /// The remembered answer for merging `family` into `merged_into`:
+    /// `Accept` for an "always" pair, `Reject` for a "never" pair
+    pub fn recall(&self, family: &str, merged_into: &str) -> Option<MergeDecision> {
+        if self.always.iter().any(|pair| same_pair(pair, family, merged_into)) {
+            Some(MergeDecision::Accept)
+        } else if self.never.iter().any(|pair| same_pair(pair, family, merged_into)) {
+            Some(MergeDecision::Reject)
+        } else {
+            None
+        }
+    }
+
+    /// Remember an "always" or "never" answer, replacing an earlier decision
+    /// about the pair; other answers are for this run only
+    pub fn remember(&mut self, family: &str, merged_into: &str, answer: MergeDecision) {
+        let list = match answer {
+            MergeDecision::Always => &mut self.always,
+            MergeDecision::Never => &mut self.never,
+            _ => return,
+        };
+        list.push((family.to_string(), merged_into.to_string()));
+        let other = if answer == MergeDecision::Always { &mut self.never } else { &mut self.always };
+        other.retain(|pair| !same_pair(pair, family, merged_into));
+    }
+}
+
+/// Whether a remembered pair names the two families, ignoring case and order
+fn same_pair((first, second): &(String, String), a: &str, b: &str) -> bool {
+    (first.eq_ignore_ascii_case(a) && second.eq_ignore_ascii_case(b))
+        || (first.eq_ignore_ascii_case(b) && second.eq_ignore_ascii_case(a))
+}
@@ -0,0 +1,638 @@
+//! Migration of libraries organized by older FontSrt versions or settings
+//! (`FontSrt migrate <DIR>`): the delta to the current conventions, previewed
+//! by category and applied in one journaled pass instead of letting a normal
+//! re-run reshuffle the tree
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::json;
+use crate::error::{Error, Result};
+use crate::font::cache::cached_font_metadata;
+use crate::models::{Config, FamilyPlan, FontMetadata, MoveAction, NamingPattern, PlannedMove, WidthClass};
+use crate::utils::{clean_name, format_font_name, log, safe_move_directory};
+use super::{
+    is_set_aside_folder,
+    is_weight_folder,
+    plan::check_moves,
+    processor::{claim_target_path, execute_planned_move, font_target_dir, grouping_folder_name, VARIABLE_FOLDER},
+    snapshot::{DecisionSnapshot, SNAPSHOT_FILE},
+};
+
+/// Journal of applied migrations, appended to in the library root
+pub const MIGRATION_JOURNAL: &str = "fontsrt-migration.jsonl";
+
+/// Kind of change a migration makes, each of which can be skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MigrationCategory {
+    /// Family folders renamed to their current name
+    Folders,
+    /// Fonts moved to another family folder, or into or out of weight folders
+    Regroup,
+    /// Fonts renamed to the current naming pattern
+    Renames,
+}
+
+impl MigrationCategory {
+    /// All categories, in the order they are previewed and applied
+    pub const ALL: [MigrationCategory; 3] = [
+        MigrationCategory::Folders,
+        MigrationCategory::Regroup,
+        MigrationCategory::Renames,
+    ];
+
+    /// Parse a category from its command-line name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "folders" => Some(MigrationCategory::Folders),
+            "regroup" => Some(MigrationCategory::Regroup),
+            "renames" => Some(MigrationCategory::Renames),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MigrationCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationCategory::Folders => write!(f, "folders"),
+            MigrationCategory::Regroup => write!(f, "regroup"),
+            MigrationCategory::Renames => write!(f, "renames"),
+        }
+    }
+}
+
+/// Conventions a library was organized with
+#[derive(Debug, Clone)]
+pub struct LibraryConventions {
+    /// Where they come from: the decision snapshot, or inferred from the tree
+    pub source: String,
+    /// Naming pattern of the file names, `None` when no pattern fits most fonts
+    pub naming_pattern: Option<NamingPattern>,
+    /// Whether fonts sit in weight folders inside their family folders
+    pub weight_folders: bool,
+}
+
+/// A family folder renamed as a whole
+#[derive(Debug, Clone)]
+pub struct FolderRename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// A font moved or renamed, with the categories of change it carries
+#[derive(Debug, Clone)]
+pub struct FontMigration {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub categories: Vec<MigrationCategory>,
+}
+
+/// Everything a migration would change, before any category is skipped
+#[derive(Debug, Clone)]
+pub struct MigrationPlan {
+    pub root: PathBuf,
+    pub previous: LibraryConventions,
+    pub current: LibraryConventions,
+    pub folder_renames: Vec<FolderRename>,
+    /// Fonts moved or renamed, sources as they are before the folder renames
+    pub fonts: Vec<FontMigration>,
+}
+
+impl MigrationPlan {
+    /// Number of changes in a category
+    pub fn count(&self, category: MigrationCategory) -> usize {
+        match category {
+            MigrationCategory::Folders => self.folder_renames.len(),
+            _ => self.fonts.iter().filter(|font| font.categories.contains(&category)).count(),
+        }
+    }
+
+    /// Folder and file moves needed when the `skip` categories are left out
+    pub fn estimated_moves(&self, skip: &[MigrationCategory]) -> usize {
+        let folders = if skip.contains(&MigrationCategory::Folders) { 0 } else { self.folder_renames.len() };
+        folders + self.fonts.iter()
+            .filter(|font| font.categories.iter().any(|category| !skip.contains(category)))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folder_renames.is_empty() && self.fonts.is_empty()
+    }
+}
+
+/// A font found in the library with the family folder it belongs to
+struct LibraryFont {
+    path: PathBuf,
+    family_dir: PathBuf,
+    metadata: FontMetadata,
+}
+
+/// Fonts below `dir`, skipping set-aside and hidden folders. A font's family
+/// folder is its parent, or its grandparent when the parent is its weight
+/// folder or the `Variable` folder.
+fn collect_library_fonts(dir: &Path, config: &Config, fonts: &mut Vec<LibraryFont>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if path.is_dir() {
+            if !name.starts_with('.') && !is_set_aside_folder(&name, config) {
+                collect_library_fonts(&path, config, fonts);
+            }
+            continue;
+        }
+
+        let Some(metadata) = cached_font_metadata(&path, config).ok().flatten() else { continue };
+        let Some(parent) = path.parent() else { continue };
+        let in_weight_folder = parent.file_name().is_some_and(|folder| {
+            let folder = folder.to_string_lossy();
+            is_weight_folder(&folder, metadata.weight, metadata.width_class()) || (metadata.is_variable && folder == VARIABLE_FOLDER)
+        });
+        let family_dir = match parent.parent().filter(|_| in_weight_folder) {
+            Some(family_dir) => family_dir.to_path_buf(),
+            None => parent.to_path_buf(),
+        };
+        fonts.push(LibraryFont { path, family_dir, metadata });
+    }
+}
+
+/// File name a font gets under a naming pattern, without the extension
+fn pattern_stem(metadata: &FontMetadata, pattern: &NamingPattern) -> String {
+    clean_name(&format_font_name(metadata, pattern))
+}
+
+/// Whether a file stem is a pattern name, possibly with a `_N` clash suffix
+pub(crate) fn stem_matches(stem: &str, expected: &str) -> bool {
+    stem == expected || stem.strip_prefix(expected)
+        .and_then(|rest| rest.strip_prefix('_'))
+        .is_some_and(|suffix| !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Conventions of a library: from its decision snapshot when there is one,
+/// else inferred from the file names and folders
+fn detect_conventions(root: &Path, fonts: &[LibraryFont]) -> LibraryConventions {
+    let weight_folders = fonts.iter().any(|font| font.path.parent() != Some(font.family_dir.as_path()));
+
+    if let Ok(snapshot) = DecisionSnapshot::load(&root.join(SNAPSHOT_FILE)) {
+        return LibraryConventions {
+            source: format!(
+                "{} (FontSrt {}, heuristics version {})",
+                SNAPSHOT_FILE, snapshot.tool_version, snapshot.heuristics_version
+            ),
+            weight_folders: snapshot.config.weight_folder(400, WidthClass::Normal).is_some(),
+            naming_pattern: Some(snapshot.config.naming_pattern),
+        };
+    }
+
+    // The built-in pattern most file names follow, if it covers at least half of them
+    let naming_pattern = NamingPattern::BUILTIN_NAMES.iter()
+        .filter_map(|name| NamingPattern::from_name(name))
+        .map(|pattern| {
+            let matching = fonts.iter()
+                .filter(|font| {
+                    let stem = font.path.file_stem().unwrap_or_default().to_string_lossy();
+                    stem_matches(&stem, &pattern_stem(&font.metadata, &pattern))
+                })
+                .count();
+            (pattern, matching)
+        })
+        .max_by_key(|(_, matching)| *matching)
+        .filter(|(_, matching)| *matching * 2 >= fonts.len() && *matching > 0)
+        .map(|(pattern, _)| pattern);
+
+    LibraryConventions { source: "inferred from the library".to_string(), naming_pattern, weight_folders }
+}
+
+/// Compute what migrating the library at `root` to the settings of `config` changes.
+///
+/// A family folder whose fonts all belong under another name is renamed, or
+/// its fonts regrouped into that folder when it already exists. Folders
+/// holding fonts of several families (kept together by a merge) stay as they
+/// are. Fonts are then placed in or out of weight folders and renamed to the
+/// current naming pattern.
+pub fn plan_migration(root: &Path, config: &Config) -> Result<MigrationPlan> {
+    let mut fonts = Vec::new();
+    collect_library_fonts(root, config, &mut fonts);
+
+    let previous = detect_conventions(root, &fonts);
+    let current = LibraryConventions {
+        source: "current settings".to_string(),
+        naming_pattern: Some(config.naming_pattern.clone()),
+        weight_folders: config.weight_folder(400, WidthClass::Normal).is_some(),
+    };
+
+    let mut by_family_dir: BTreeMap<PathBuf, Vec<LibraryFont>> = BTreeMap::new();
+    for font in fonts {
+        by_family_dir.entry(font.family_dir.clone()).or_default().push(font);
+    }
+
+    // Target folder of each family folder, the first folder to claim a new name renaming into it
+    let existing: HashSet<PathBuf> = by_family_dir.keys().cloned().collect();
+    let mut claimed_folders: HashSet<PathBuf> = HashSet::new();
+    let mut folder_renames = Vec::new();
+    let mut targets: HashMap<PathBuf, (PathBuf, bool)> = HashMap::new();
+    for (family_dir, fonts) in &by_family_dir {
+        let names: HashSet<String> = fonts.iter().map(|font| grouping_folder_name(&font.metadata.family_name)).collect();
+        let current_name = family_dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let target = match (names.len(), names.iter().next(), family_dir.parent()) {
+            (1, Some(name), Some(parent)) if *name != current_name && !name.is_empty() => parent.join(name),
+            _ => family_dir.clone(),
+        };
+
+        if target == *family_dir {
+            targets.insert(family_dir.clone(), (target, false));
+        } else if !existing.contains(&target) && claimed_folders.insert(target.clone()) {
+            folder_renames.push(FolderRename { from: family_dir.clone(), to: target.clone() });
+            targets.insert(family_dir.clone(), (target, true));
+        } else {
+            targets.insert(family_dir.clone(), (target, false));
+        }
+    }
+
+    let mut claimed_paths: HashSet<PathBuf> = HashSet::new();
+    let mut migrations = Vec::new();
+    for (family_dir, fonts) in by_family_dir {
+        let (target_family, renamed) = targets[&family_dir].clone();
+        for font in fonts {
+            let mut categories = Vec::new();
+            if target_family != family_dir && !renamed {
+                categories.push(MigrationCategory::Regroup);
+            }
+
+            let target_dir = font_target_dir(&target_family, &font.metadata, config);
+            let relative_dir = font.path.parent()
+                .and_then(|parent| parent.strip_prefix(&family_dir).ok())
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            if target_dir.strip_prefix(&target_family).ok() != Some(relative_dir.as_path())
+                && !categories.contains(&MigrationCategory::Regroup)
+            {
+                categories.push(MigrationCategory::Regroup);
+            }
+
+            let extension = font.path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+            let expected_stem = config.file_stem(&format_font_name(&font.metadata, &config.naming_pattern), &extension);
+            let stem = font.path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let renamed_file = !stem_matches(&stem, &expected_stem);
+            if renamed_file {
+                categories.push(MigrationCategory::Renames);
+            }
+
+            if categories.is_empty() {
+                continue;
+            }
+            let base_name = if renamed_file { expected_stem } else { stem };
+            let destination = claim_target_path(&target_dir, &base_name, &extension, &mut claimed_paths);
+            migrations.push(FontMigration { source: font.path, destination, categories });
+        }
+    }
+
+    Ok(MigrationPlan { root: root.to_path_buf(), previous, current, folder_renames, fonts: migrations })
+}
+
+/// Print the categorized preview of a migration
+pub fn print_migration_plan(plan: &MigrationPlan, skip: &[MigrationCategory]) {
+    let describe = |conventions: &LibraryConventions| format!(
+        "naming {}, weight folders {}",
+        conventions.naming_pattern.as_ref().map(|pattern| pattern.to_string()).unwrap_or_else(|| "unknown".to_string()),
+        if conventions.weight_folders { "yes" } else { "no" }
+    );
+    println!("Migration of {}", plan.root.display());
+    println!("  Previous conventions ({}): {}", plan.previous.source, describe(&plan.previous));
+    println!("  Current conventions: {}", describe(&plan.current));
+
+    if plan.is_empty() {
+        println!("Nothing to migrate, the library follows the current conventions.");
+        return;
+    }
+
+    for category in MigrationCategory::ALL {
+        let skipped = if skip.contains(&category) { " (skipped)" } else { "" };
+        println!("\n{} {}{}:", plan.count(category), category_label(category), skipped);
+        match category {
+            MigrationCategory::Folders => {
+                for rename in &plan.folder_renames {
+                    println!("    {} -> {}", display_relative(&rename.from, &plan.root), display_relative(&rename.to, &plan.root));
+                }
+            }
+            _ => {
+                for font in plan.fonts.iter().filter(|font| font.categories.contains(&category)) {
+                    println!("    {} -> {}", display_relative(&font.source, &plan.root), display_relative(&font.destination, &plan.root));
+                }
+            }
+        }
+    }
+    println!("\nEstimated moves: {}", plan.estimated_moves(skip));
+}
+
+fn category_label(category: MigrationCategory) -> &'static str {
+    match category {
+        MigrationCategory::Folders => "folders to rename",
+        MigrationCategory::Regroup => "files to re-group",
+        MigrationCategory::Renames => "files to rename",
+    }
+}
+
+fn display_relative(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).display().to_string()
+}
+
+/// Appends one JSON line per step to the migration journal, so an interrupted
+/// migration can be traced and undone by hand
+struct MigrationJournal {
+    file: fs::File,
+}
+
+impl MigrationJournal {
+    fn open(root: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(root.join(MIGRATION_JOURNAL))?;
+        Ok(Self { file })
+    }
+
+    fn record(&mut self, step: serde_json::Value) -> Result<()> {
+        writeln!(self.file, "{}", step)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Warning about an earlier migration of `root` whose journal has no end
+/// step, meaning it was interrupted part way
+pub fn interrupted_migration(root: &Path) -> Option<String> {
+    let journal = fs::read_to_string(root.join(MIGRATION_JOURNAL)).ok()?;
+    let last = journal.lines().rev().find(|line| !line.trim().is_empty())?;
+    let step: serde_json::Value = serde_json::from_str(last).ok()?;
+    (step["step"] != "end").then(|| format!(
+        "an earlier migration did not finish, see {} for the moves it made",
+        root.join(MIGRATION_JOURNAL).display()
+    ))
+}
+
+/// Apply a migration, leaving out the `skip` categories. Folders are renamed
+/// first, then the font moves are validated as one plan and carried out.
+/// Every step is journaled before the next one starts. Returns the number of
+/// folders and files moved.
+pub fn apply_migration(plan: &MigrationPlan, skip: &[MigrationCategory], config: &Config) -> Result<usize> {
+    if config.dry_run {
+        return Ok(0);
+    }
+    let mut journal = MigrationJournal::open(&plan.root)?;
+    let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    journal.record(json!({
+        "step": "start",
+        "time": started,
+        "skip": skip.iter().map(|category| category.to_string()).collect::<Vec<_>>(),
+        "moves": plan.estimated_moves(skip),
+    }))?;
+    let mut moved = 0;
+
+    // Sources inside renamed folders move along with them
+    let mut relocated: Vec<(&Path, &Path)> = Vec::new();
+    if !skip.contains(&MigrationCategory::Folders) {
+        for rename in &plan.folder_renames {
+            log(config, format!("Renaming folder {} to {}", rename.from.display(), rename.to.display()));
+            safe_move_directory(&rename.from, &rename.to, config)?;
+            journal.record(json!({ "step": "folder", "from": rename.from, "to": rename.to }))?;
+            relocated.push((&rename.from, &rename.to));
+            moved += 1;
+        }
+    }
+    let relocate = |path: &Path| -> PathBuf {
+        relocated.iter()
+            .find_map(|(from, to)| path.strip_prefix(from).ok().map(|rest| to.join(rest)))
+            .unwrap_or_else(|| path.to_path_buf())
+    };
+
+    // Font moves, with the skipped parts of each move undone
+    let mut families: BTreeMap<PathBuf, Vec<PlannedMove>> = BTreeMap::new();
+    let mut claimed: HashSet<PathBuf> = HashSet::new();
+    for font in &plan.fonts {
+        let source = relocate(&font.source);
+        let applied: Vec<MigrationCategory> = font.categories.iter().copied().filter(|category| !skip.contains(category)).collect();
+        if applied.is_empty() {
+            continue;
+        }
+        let target_dir = match applied.contains(&MigrationCategory::Regroup) {
+            true => relocate(font.destination.parent().unwrap_or(&plan.root)),
+            false => source.parent().unwrap_or(&plan.root).to_path_buf(),
+        };
+        let file_name = match applied.contains(&MigrationCategory::Renames) {
+            true => font.destination.file_stem(),
+            false => source.file_stem(),
+        }.unwrap_or_default().to_string_lossy().into_owned();
+        let extension = source.extension().unwrap_or_default().to_string_lossy().into_owned();
+        let destination = claim_target_path(&target_dir, &file_name, &extension, &mut claimed);
+        if destination == source {
+            continue;
+        }
+        families.entry(target_dir.clone()).or_default().push(PlannedMove::new(source, destination, MoveAction::Move));
+    }
+
+    let families: Vec<FamilyPlan> = families.into_iter()
+        .map(|(family_dir, moves)| FamilyPlan {
+            family_name: family_dir.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            family_dir: Some(family_dir),
+            moves,
+        })
+        .collect();
+    check_moves(&families, &[])?;
+
+    let mut emptied: HashSet<PathBuf> = HashSet::new();
+    for family in &families {
+        for planned in &family.moves {
+            execute_planned_move(planned, config)?;
+            journal.record(json!({ "step": "file", "from": planned.source, "to": planned.destination }))?;
+            if let Some(parent) = planned.source.parent() {
+                emptied.insert(parent.to_path_buf());
+            }
+            moved += 1;
+        }
+    }
+
+    // Weight and family folders left empty by the moves are removed, deepest first
+    let mut emptied: Vec<PathBuf> = emptied.into_iter().collect();
+    emptied.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+    for dir in emptied {
+        let mut dir = dir.as_path();
+        while dir != plan.root && fs::remove_dir(dir).is_ok() {
+            log(config, format!("Removed empty folder {}", dir.display()));
+            let Some(parent) = dir.parent() else { break };
+            dir = parent;
+        }
+    }
+
+    journal.record(json!({ "step": "end", "moved": moved }))?;
+    Ok(moved)
+}
+
+/// Parse `--skip` values, a comma separated list of categories
+pub fn parse_skip_categories(value: &str) -> Result<Vec<MigrationCategory>> {
+    value.split(',')
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| MigrationCategory::from_name(name).ok_or_else(|| Error::Config(format!(
+            "--skip expects {} separated by commas, got '{}'",
+            MigrationCategory::ALL.map(|category| category.to_string()).join(", "), name
+        ))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TestFont};
+
+    /// A library organized by an older version: `family-weight` names in
+    /// numeric weight folders, one family folder under a stale name and one
+    /// font filed apart from its family
+    fn old_library(tmp: &TempDir) {
+        TestFont::new("Roboto", "Regular").write(&tmp.join("roboto fonts/400/Roboto 400.ttf"));
+        TestFont::new("Roboto", "Bold").weight(700).write(&tmp.join("roboto fonts/700/Roboto 700.ttf"));
+        TestFont::new("Lato", "Regular").write(&tmp.join("Lato/400/Lato 400.ttf"));
+        TestFont::new("Lato", "Bold").weight(700).write(&tmp.join("Lato Old/700/Lato 700.ttf"));
+    }
+
+    /// Font files below `dir`, relative to it
+    fn library_files(dir: &Path) -> Vec<PathBuf> {
+        fn walk(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+            for entry in fs::read_dir(dir).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    walk(root, &path, files);
+                } else if path.extension().is_some_and(|extension| extension == "ttf") {
+                    files.push(path.strip_prefix(root).unwrap().to_path_buf());
+                }
+            }
+        }
+        let mut files = Vec::new();
+        walk(dir, dir, &mut files);
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn old_conventions_are_inferred_from_the_tree() {
+        let tmp = TempDir::new("migrate");
+        old_library(&tmp);
+
+        let plan = plan_migration(tmp.path(), &Config::default()).unwrap();
+        assert_eq!(plan.previous.source, "inferred from the library");
+        assert_eq!(plan.previous.naming_pattern, Some(NamingPattern::FamilyWeight));
+        assert!(plan.previous.weight_folders);
+        assert_eq!(plan.current.naming_pattern, Some(NamingPattern::FamilySubfamily));
+        assert!(!plan.current.weight_folders);
+    }
+
+    #[test]
+    fn preview_counts_each_category() {
+        let tmp = TempDir::new("migrate");
+        old_library(&tmp);
+
+        let plan = plan_migration(tmp.path(), &Config::default()).unwrap();
+        let renames: Vec<(PathBuf, PathBuf)> = plan.folder_renames.iter()
+            .map(|rename| (rename.from.clone(), rename.to.clone()))
+            .collect();
+        assert_eq!(renames, [(tmp.join("roboto fonts"), tmp.join("Roboto"))]);
+        assert_eq!(plan.count(MigrationCategory::Folders), 1);
+        assert_eq!(plan.count(MigrationCategory::Regroup), 4);
+        assert_eq!(plan.count(MigrationCategory::Renames), 4);
+
+        let lato_bold = plan.fonts.iter().find(|font| font.source.ends_with("Lato Old/700/Lato 700.ttf")).unwrap();
+        assert_eq!(lato_bold.destination, tmp.join("Lato/Lato (Bold).ttf"));
+        assert_eq!(lato_bold.categories, [MigrationCategory::Regroup, MigrationCategory::Renames]);
+    }
+
+    #[test]
+    fn skipped_categories_leave_the_estimate() {
+        let tmp = TempDir::new("migrate");
+        old_library(&tmp);
+
+        let plan = plan_migration(tmp.path(), &Config::default()).unwrap();
+        assert_eq!(plan.estimated_moves(&[]), 5);
+        assert_eq!(plan.estimated_moves(&[MigrationCategory::Folders]), 4);
+        assert_eq!(plan.estimated_moves(&[MigrationCategory::Regroup, MigrationCategory::Renames]), 1);
+        assert_eq!(plan.estimated_moves(&MigrationCategory::ALL), 0);
+    }
+
+    #[test]
+    fn applying_reaches_the_current_layout() {
+        let tmp = TempDir::new("migrate");
+        old_library(&tmp);
+        let config = Config::default();
+
+        let plan = plan_migration(tmp.path(), &config).unwrap();
+        assert_eq!(apply_migration(&plan, &[], &config).unwrap(), 5);
+        assert_eq!(library_files(tmp.path()), [
+            PathBuf::from("Lato/Lato (Bold).ttf"),
+            PathBuf::from("Lato/Lato.ttf"),
+            PathBuf::from("Roboto/Roboto (Bold).ttf"),
+            PathBuf::from("Roboto/Roboto.ttf"),
+        ]);
+        assert!(!tmp.join("Lato Old").exists());
+        assert!(!tmp.join("Roboto/400").exists());
+        assert!(interrupted_migration(tmp.path()).is_none());
+        assert!(plan_migration(tmp.path(), &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn skipped_renames_keep_the_old_file_names() {
+        let tmp = TempDir::new("migrate");
+        old_library(&tmp);
+        let config = Config::default();
+
+        let plan = plan_migration(tmp.path(), &config).unwrap();
+        let skip = parse_skip_categories("renames").unwrap();
+        assert_eq!(apply_migration(&plan, &skip, &config).unwrap(), 5);
+        assert_eq!(library_files(tmp.path()), [
+            PathBuf::from("Lato/Lato 400.ttf"),
+            PathBuf::from("Lato/Lato 700.ttf"),
+            PathBuf::from("Roboto/Roboto 400.ttf"),
+            PathBuf::from("Roboto/Roboto 700.ttf"),
+        ]);
+
+        let rest = plan_migration(tmp.path(), &config).unwrap();
+        assert_eq!(rest.count(MigrationCategory::Folders), 0);
+        assert_eq!(rest.count(MigrationCategory::Regroup), 0);
+        assert_eq!(rest.count(MigrationCategory::Renames), 4);
+    }
+
+    #[test]
+    fn dry_run_moves_nothing() {
+        let tmp = TempDir::new("migrate");
+        old_library(&tmp);
+        let config = Config { dry_run: true, ..Config::default() };
+
+        let plan = plan_migration(tmp.path(), &config).unwrap();
+        assert_eq!(apply_migration(&plan, &[], &config).unwrap(), 0);
+        assert!(tmp.join("roboto fonts/400/Roboto 400.ttf").exists());
+        assert!(!tmp.join(MIGRATION_JOURNAL).exists());
+    }
+
+    #[test]
+    fn unfinished_journal_is_reported() {
+        let tmp = TempDir::new("migrate");
+        fs::write(tmp.join(MIGRATION_JOURNAL), "{\"step\":\"start\"}\n{\"step\":\"folder\"}\n").unwrap();
+        assert!(interrupted_migration(tmp.path()).is_some());
+
+        fs::write(tmp.join(MIGRATION_JOURNAL), "{\"step\":\"start\"}\n{\"step\":\"end\",\"moved\":0}\n").unwrap();
+        assert!(interrupted_migration(tmp.path()).is_none());
+    }
+
+    #[test]
+    fn skip_categories_are_parsed() {
+        assert_eq!(parse_skip_categories("renames").unwrap(), [MigrationCategory::Renames]);
+        assert_eq!(
+            parse_skip_categories(" Folders, regroup,").unwrap(),
+            [MigrationCategory::Folders, MigrationCategory::Regroup]
+        );
+        assert!(parse_skip_categories("").unwrap().is_empty());
+
+        let error = parse_skip_categories("renames,weights").unwrap_err().to_string();
+        assert!(error.contains("folders, regroup, renames") && error.contains("'weights'"), "{}", error);
+    }
+}
@@ -1,10 +1,52 @@
 //! Font organization and processing functionality
 
+use crate::models::{Config, WeightFolderStyle, WidthClass};
+
 pub mod processor;
 pub mod batch;
 pub mod group;
+pub mod style;
+pub mod technology;
+pub mod script;
+pub mod license;
+pub mod classification;
+pub mod duplicates;
+pub mod designer;
+pub mod errors;
+pub mod lockfile;
+pub mod merge_rules;
+pub mod presets;
+pub mod snapshot;
+pub mod migrate;
+pub mod letter;
+pub(crate) mod plan;
 
-pub use processor::organize_fonts;
+pub use processor::{collect_font_candidates, execute_plan, organize_fonts, organize_single_font, plan_deduplicate, plan_organize, plan_organize_shared, print_organize_summary};
 pub use batch::batch_process;
-pub use group::group_by_foundry;
+pub use group::{group_by_foundry, ungroup_foundries};
+pub use style::group_by_style;
+pub use technology::group_by_technology;
+pub use script::group_by_script;
+pub use license::group_by_license;
+pub use letter::group_by_first_letter;
+pub use classification::group_by_classification;
+pub use designer::{designer_families, group_by_designer, print_designer_families};
+pub use errors::{error_report_json, print_error_report, write_error_report};
+
+
+/// Folders `organize_fonts` creates for files set aside from the library
+pub(crate) const SET_ASIDE_FOLDERS: [&str; 5] = ["duplicates", "synthetic", "corrupt", "_Unapproved", "_skipped"];
+
+/// Whether a top-level folder holds set-aside files rather than a font family,
+/// including a custom duplicates folder name
+pub(crate) fn is_set_aside_folder(name: &str, config: &Config) -> bool {
+    SET_ASIDE_FOLDERS.contains(&name)
+        || config.duplicates_dir.as_deref()
+            .is_some_and(|dir| dir.is_relative() && dir.as_os_str() == name)
+}
 
+/// Whether a folder name is the weight folder of `weight` and `width` in either style
+pub(crate) fn is_weight_folder(name: &str, weight: u16, width: WidthClass) -> bool {
+    [WeightFolderStyle::Numeric, WeightFolderStyle::Textual].iter()
+        .any(|style| style.folder_name(weight, width) == name)
+}
@@ -0,0 +1,177 @@
+//! Checks of a move plan, run before any file is touched
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use crate::error::{Error, Result};
+use crate::models::{FamilyPlan, MoveAction, PlannedMove};
+
+/// Check the invariants the move phase relies on: every source is planned
+/// once, every destination is claimed by one font and is free, a skipped
+/// font stays where it is and a family's fonts stay in its folder (or, when
+/// renamed in place, in their own). `others` are moves outside the family
+/// folders, such as files set aside.
+pub(crate) fn validate_moves(families: &[FamilyPlan], others: &[PlannedMove]) -> std::result::Result<(), String> {
+    let planned_moves = || families.iter()
+        .flat_map(|family| family.moves.iter().map(move |planned| (Some(family), planned)))
+        .chain(others.iter().map(|planned| (None, planned)));
+    let sources: HashSet<&PathBuf> = planned_moves().map(|(_, planned)| &planned.source).collect();
+    let mut seen_sources: HashSet<&PathBuf> = HashSet::new();
+    let mut destinations: HashSet<&PathBuf> = HashSet::new();
+
+    for (family, planned) in planned_moves() {
+        if !seen_sources.insert(&planned.source) {
+            return Err(format!("{} is planned more than once", planned.source.display()));
+        }
+        if !destinations.insert(&planned.destination) {
+            return Err(format!("{} is the destination of more than one font", planned.destination.display()));
+        }
+
+        match (planned.action, family.map(|family| (family, &family.family_dir))) {
+            (MoveAction::Skip(_), _) if planned.destination != planned.source => {
+                return Err(format!("{} is kept in place but planned to {}", planned.source.display(), planned.destination.display()));
+            }
+            (MoveAction::Skip(_), _) => continue,
+            (MoveAction::Move, Some((_, Some(family_dir)))) if !planned.destination.starts_with(family_dir) => {
+                return Err(format!("{} is planned outside its family folder {}", planned.source.display(), family_dir.display()));
+            }
+            (MoveAction::Move, Some((_, None))) if planned.destination.parent() != planned.source.parent() => {
+                return Err(format!("{} is renamed out of its folder to {}", planned.source.display(), planned.destination.display()));
+            }
+            (MoveAction::Deduplicate, Some((family, _))) => {
+                return Err(format!("{} is set aside as a duplicate within family '{}'", planned.source.display(), family.family_name));
+            }
+            _ => {}
+        }
+
+        if sources.contains(&planned.destination) {
+            return Err(format!("{} would overwrite {} before it is moved", planned.source.display(), planned.destination.display()));
+        }
+        if planned.destination.exists() {
+            return Err(format!("{} would overwrite the existing {}", planned.source.display(), planned.destination.display()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate a plan. A violation is a bug in the planner: it panics in debug
+/// builds and is returned as an error otherwise, before any file is touched.
+pub(crate) fn check_moves(families: &[FamilyPlan], others: &[PlannedMove]) -> Result<()> {
+    let violation = validate_moves(families, others).err();
+    debug_assert!(violation.is_none(), "invalid move plan: {:?}", violation);
+    match violation {
+        Some(violation) => Err(Error::Plan(violation)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use crate::models::SkipReason;
+
+    const LIBRARY: &str = "/nonexistent/library";
+
+    fn path(relative: &str) -> PathBuf {
+        Path::new(LIBRARY).join(relative)
+    }
+
+    fn planned(source: &str, destination: &str) -> PlannedMove {
+        PlannedMove::new(path(source), path(destination), MoveAction::Move)
+    }
+
+    fn family(name: &str, moves: Vec<PlannedMove>) -> FamilyPlan {
+        FamilyPlan { family_name: name.to_string(), family_dir: Some(path(name)), moves }
+    }
+
+    fn violation(families: &[FamilyPlan], others: &[PlannedMove]) -> String {
+        validate_moves(families, others).expect_err("the plan should be rejected")
+    }
+
+    #[test]
+    fn accepts_a_valid_plan() {
+        let families = [
+            family("Roboto", vec![planned("in/a.ttf", "Roboto/Roboto Regular.ttf"), planned("in/b.ttf", "Roboto/Roboto Bold.ttf")]),
+            family("Inter", vec![PlannedMove::skip(path("Inter/Inter Regular.ttf"), SkipReason::AlreadyOrganized)]),
+        ];
+        let others = [PlannedMove::new(path("in/c.ttf"), path("Duplicates/c.ttf"), MoveAction::Deduplicate)];
+        assert_eq!(validate_moves(&families, &others), Ok(()));
+        assert!(check_moves(&families, &others).is_ok());
+    }
+
+    #[test]
+    fn rejects_duplicate_sources() {
+        let families = [family("Roboto", vec![planned("in/a.ttf", "Roboto/Roboto Regular.ttf")])];
+        let others = [PlannedMove::new(path("in/a.ttf"), path("Duplicates/a.ttf"), MoveAction::Deduplicate)];
+        assert!(violation(&families, &others).contains("is planned more than once"));
+    }
+
+    #[test]
+    fn rejects_duplicate_destinations() {
+        let families = [family("Roboto", vec![
+            planned("in/a.ttf", "Roboto/Roboto Regular.ttf"),
+            planned("in/b.ttf", "Roboto/Roboto Regular.ttf"),
+        ])];
+        assert!(violation(&families, &[]).contains("is the destination of more than one font"));
+    }
+
+    #[test]
+    fn rejects_a_move_outside_its_family_folder() {
+        let families = [family("Roboto", vec![planned("in/a.ttf", "Inter/Roboto Regular.ttf")])];
+        assert!(violation(&families, &[]).contains("is planned outside its family folder"));
+    }
+
+    #[test]
+    fn rejects_overwriting_the_source_of_another_move() {
+        let families = [family("Roboto", vec![
+            planned("Roboto/a.ttf", "Roboto/b.ttf"),
+            planned("Roboto/b.ttf", "Roboto/c.ttf"),
+        ])];
+        assert!(violation(&families, &[]).contains("would overwrite"));
+        assert!(violation(&families, &[]).contains("before it is moved"));
+    }
+
+    #[test]
+    fn rejects_overwriting_an_existing_file() {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let families = [FamilyPlan {
+            family_name: "Crate".to_string(),
+            family_dir: Some(manifest.to_path_buf()),
+            moves: vec![PlannedMove::new(path("in/a.ttf"), manifest.join("Cargo.toml"), MoveAction::Move)],
+        }];
+        assert!(violation(&families, &[]).contains("would overwrite the existing"));
+    }
+
+    #[test]
+    fn rejects_a_skipped_font_that_moves() {
+        let skipped = PlannedMove::new(path("in/a.ttf"), path("Roboto/a.ttf"), MoveAction::Skip(SkipReason::Excluded));
+        assert!(violation(&[], &[skipped]).contains("is kept in place but planned to"));
+    }
+
+    #[test]
+    fn rejects_a_rename_in_place_out_of_its_folder() {
+        let families = [FamilyPlan {
+            family_name: "Roboto".to_string(),
+            family_dir: None,
+            moves: vec![planned("in/a.ttf", "out/Roboto Regular.ttf")],
+        }];
+        assert!(violation(&families, &[]).contains("is renamed out of its folder"));
+    }
+
+    #[test]
+    fn rejects_a_duplicate_set_aside_within_a_family() {
+        let families = [family("Roboto", vec![
+            PlannedMove::new(path("in/a.ttf"), path("Roboto/a.ttf"), MoveAction::Deduplicate),
+        ])];
+        assert!(violation(&families, &[]).contains("is set aside as a duplicate within family 'Roboto'"));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid move plan")]
+    fn check_moves_panics_on_a_violation_in_debug_builds() {
+        let families = [family("Roboto", vec![planned("in/a.ttf", "Inter/Roboto Regular.ttf")])];
+        let _ = check_moves(&families, &[]);
+    }
+}
@@ -0,0 +1,235 @@
+//! Named presets and per-line settings of batch file entries
+//! (`/fonts/inbox @quick --dry-run`)
+
+use std::path::PathBuf;
+use crate::error::{Error, Result};
+use crate::models::{Config, DuplicatePolicy, MergeMode, MergeStrategy, NamingPattern, OrganizationDepth, WeightFolderStyle};
+
+/// A named bundle of batch flags
+#[derive(Debug, Clone, Copy)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub flags: &'static [&'static str],
+}
+
+/// Presets batch lines can select with `@name`
+pub const BUILTIN_PRESETS: [Preset; 2] = [
+    Preset {
+        name: "archive",
+        description: "careful curation: keep the best copy of each face, merge only near-identical names, set aside corrupt and synthetic faces",
+        flags: &["--dup-policy", "keep-best", "--merge-families", "conservative", "--quarantine", "--quarantine-synthetic"],
+    },
+    Preset {
+        name: "quick",
+        description: "fast tidy: keep every copy and skip the similarity merge",
+        flags: &["--dup-policy", "keep-all", "--merge", "never"],
+    },
+];
+
+/// Built-in preset by name, ignoring case
+pub fn find_preset(name: &str) -> Option<&'static Preset> {
+    BUILTIN_PRESETS.iter().find(|preset| preset.name.eq_ignore_ascii_case(name))
+}
+
+/// Flags a batch line can set, those followed by a value first
+const BATCH_VALUE_FLAGS: [&str; 6] = ["--dup-policy", "--merge", "--merge-families", "--pattern", "--similarity", "--weight-folders"];
+const BATCH_SWITCHES: [&str; 9] = [
+    "--check-completeness",
+    "--detect-synthetic",
+    "--dry-run",
+    "--extract-zips",
+    "--group-by-weight",
+    "--no-fail-on-error",
+    "--quarantine",
+    "--quarantine-synthetic",
+    "--recursive",
+];
+
+/// One entry of a batch file
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchLine {
+    /// 1-based line number in the batch file
+    pub line_number: usize,
+    pub dir: PathBuf,
+    /// Preset selected with `@name`
+    pub preset: Option<String>,
+    /// Flags written after the directory, applied after the preset
+    pub flags: Vec<String>,
+}
+
+/// Parse a batch file line, `None` for blank lines and `#` comments.
+/// The directory runs up to the first ` @` or ` --`, so it may contain spaces.
+pub fn parse_batch_line(line_number: usize, line: &str) -> Option<BatchLine> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let settings_start = [line.find(" @"), line.find(" --")].into_iter().flatten().min();
+    let (dir, settings) = match settings_start {
+        Some(start) => (line[..start].trim_end(), &line[start..]),
+        None => (line, ""),
+    };
+
+    let mut preset = None;
+    let mut flags = Vec::new();
+    for token in settings.split_whitespace() {
+        match token.strip_prefix('@') {
+            Some(name) => preset = Some(name.to_string()),
+            None => flags.push(token.to_string()),
+        }
+    }
+
+    Some(BatchLine { line_number, dir: PathBuf::from(dir), preset, flags })
+}
+
+impl BatchLine {
+    /// Settings of this entry: the global `config`, then the preset, then
+    /// the flags of the line. Returns the effective settings as flags too,
+    /// for the batch report.
+    pub fn resolve(&self, config: &Config) -> Result<(Config, Vec<String>)> {
+        let mut effective = Vec::new();
+        if let Some(name) = &self.preset {
+            let preset = find_preset(name).ok_or_else(|| Error::Config(format!(
+                "line {}: unknown preset '@{}', expected one of {}",
+                self.line_number,
+                name,
+                BUILTIN_PRESETS.iter().map(|preset| format!("@{}", preset.name)).collect::<Vec<_>>().join(", ")
+            )))?;
+            effective.push(format!("@{}", preset.name));
+            effective.extend(preset.flags.iter().map(|flag| flag.to_string()));
+        }
+        effective.extend(self.flags.iter().cloned());
+
+        let mut line_config = config.clone();
+        apply_batch_flags(&mut line_config, &effective)
+            .map_err(|e| Error::Config(format!("line {}: {}", self.line_number, e)))?;
+        Ok((line_config, effective))
+    }
+}
+
+/// Apply batch flags to a configuration, later flags overriding earlier ones.
+/// `@preset` tokens are skipped.
+pub fn apply_batch_flags(config: &mut Config, flags: &[String]) -> std::result::Result<(), String> {
+    let mut tokens = flags.iter().filter(|token| !token.starts_with('@'));
+    while let Some(flag) = tokens.next() {
+        if BATCH_SWITCHES.contains(&flag.as_str()) {
+            match flag.as_str() {
+                "--check-completeness" => config.check_completeness = true,
+                "--detect-synthetic" => config.detect_synthetic = true,
+                "--dry-run" => config.dry_run = true,
+                "--extract-zips" => config.extract_zips = true,
+                "--group-by-weight" => config.organization_depth = OrganizationDepth::GroupByWeight,
+                "--no-fail-on-error" => config.fail_on_error = false,
+                "--quarantine" => config.quarantine_corrupt = true,
+                "--quarantine-synthetic" => {
+                    config.quarantine_synthetic = true;
+                    config.detect_synthetic = true;
+                }
+                _ => config.recursive = true,
+            }
+            continue;
+        }
+
+        if !BATCH_VALUE_FLAGS.contains(&flag.as_str()) {
+            return Err(format!(
+                "unknown flag '{}', batch lines accept {} and {}",
+                flag, BATCH_VALUE_FLAGS.join(", "), BATCH_SWITCHES.join(", ")
+            ));
+        }
+        let value = tokens.next().ok_or_else(|| format!("{} requires a value", flag))?;
+        match flag.as_str() {
+            "--dup-policy" => config.duplicate_policy = DuplicatePolicy::from_name(value)
+                .ok_or_else(|| format!("--dup-policy must be 'keep-best', 'keep-all' or 'keep-first', got '{}'", value))?,
+            "--merge" => config.merge_mode = MergeMode::from_name(value)
+                .ok_or_else(|| format!("--merge must be 'auto', 'always' or 'never', got '{}'", value))?,
+            "--merge-families" => config.set_merge_strategy(MergeStrategy::from_name(value)
+                .ok_or_else(|| format!("--merge-families must be 'off', 'conservative' or 'aggressive', got '{}'", value))?),
+            "--pattern" => config.naming_pattern = NamingPattern::from_name(value)
+                .ok_or_else(|| format!("--pattern expects a built-in pattern name, got '{}'", value))?,
+            "--similarity" => config.similarity_threshold = value.parse::<f32>().ok()
+                .filter(|threshold| (0.0..=1.0).contains(threshold))
+                .ok_or_else(|| format!("--similarity expects a number from 0.0 to 1.0, got '{}'", value))?,
+            _ => config.weight_folder_style = WeightFolderStyle::from_name(value)
+                .ok_or_else(|| format!("--weight-folders must be 'numeric' or 'textual', got '{}'", value))?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> BatchLine {
+        parse_batch_line(3, text).unwrap()
+    }
+
+    #[test]
+    fn parses_directory_preset_and_flags() {
+        assert_eq!(parse_batch_line(1, "   "), None);
+        assert_eq!(parse_batch_line(1, "# archived clients"), None);
+        assert_eq!(line("/fonts/inbox"), BatchLine {
+            line_number: 3,
+            dir: PathBuf::from("/fonts/inbox"),
+            preset: None,
+            flags: Vec::new(),
+        });
+        assert_eq!(line("/fonts/client a @archive --dry-run --pattern family-weight"), BatchLine {
+            line_number: 3,
+            dir: PathBuf::from("/fonts/client a"),
+            preset: Some("archive".to_string()),
+            flags: vec!["--dry-run".to_string(), "--pattern".to_string(), "family-weight".to_string()],
+        });
+    }
+
+    #[test]
+    fn preset_overrides_global_settings() {
+        let global = Config { duplicate_policy: DuplicatePolicy::KeepFirst, merge_mode: MergeMode::Auto, ..Config::default() };
+        let (config, effective) = line("/fonts/inbox @quick").resolve(&global).unwrap();
+        assert_eq!(config.duplicate_policy, DuplicatePolicy::KeepAll);
+        assert_eq!(config.merge_mode, MergeMode::Never);
+        assert_eq!(effective, ["@quick", "--dup-policy", "keep-all", "--merge", "never"]);
+    }
+
+    #[test]
+    fn line_flags_override_the_preset() {
+        let (config, effective) = line("/fonts/client-a @ARCHIVE --dup-policy keep-first --dry-run")
+            .resolve(&Config::default())
+            .unwrap();
+        assert_eq!(config.duplicate_policy, DuplicatePolicy::KeepFirst);
+        // The rest of the preset still applies
+        assert_eq!(config.merge_strategy, MergeStrategy::Conservative);
+        assert!(config.quarantine_corrupt && config.quarantine_synthetic && config.detect_synthetic);
+        assert!(config.dry_run);
+        assert_eq!(effective.first().map(String::as_str), Some("@archive"));
+        assert_eq!(effective.last().map(String::as_str), Some("--dry-run"));
+    }
+
+    #[test]
+    fn global_settings_apply_without_a_preset() {
+        let global = Config { duplicate_policy: DuplicatePolicy::KeepFirst, ..Config::default() };
+        let (config, effective) = line("/fonts/inbox --group-by-weight").resolve(&global).unwrap();
+        assert_eq!(config.duplicate_policy, DuplicatePolicy::KeepFirst);
+        assert_eq!(config.organization_depth, OrganizationDepth::GroupByWeight);
+        assert_eq!(effective, ["--group-by-weight"]);
+    }
+
+    fn resolve_error(text: &str) -> String {
+        match line(text).resolve(&Config::default()) {
+            Ok(_) => panic!("'{}' resolved", text),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = resolve_error("/fonts/inbox @tidy");
+        assert!(error.contains("line 3: unknown preset '@tidy', expected one of @archive, @quick"), "{}", error);
+        let error = resolve_error("/fonts/inbox --shred");
+        assert!(error.contains("line 3: unknown flag '--shred'"), "{}", error);
+        let error = resolve_error("/fonts/inbox --merge");
+        assert!(error.contains("line 3: --merge requires a value"), "{}", error);
+    }
+}
@@ -1,22 +1,76 @@
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use rayon::prelude::*;
-use crate::error::Result;
-use crate::models::{Config, FontMetadata};
-use crate::font::metadata::{extract_font_metadata, extract_root_family};
+use serde_json::json;
+use crate::error::{Error, Result};
+use super::is_set_aside_folder;
+use super::lockfile::{apply_lockfile, LockfileMode};
+use super::plan::check_moves;
+use super::duplicates::{hash_large_fonts, large_font_size, resolve_signature_duplicates, set_aside_exact_duplicates, SetAsidePlan};
+use crate::models::{
+    Config,
+    CorruptFont,
+    ErrorStage,
+    FamilyMerge,
+    FamilyPlan,
+    FileError,
+    FilterAction,
+    FontLicense,
+    FontMetadata,
+    FontScript,
+    FontTechnology,
+    MergeDecision,
+    MergeMode,
+    MergePhaseDecision,
+    MergeStrategy,
+    MetadataSource,
+    MoveAction,
+    OrganizePlan,
+    OrganizeReport,
+    OrganizeResult,
+    OrganizedFont,
+    PlannedMove,
+    PrecedenceChange,
+    ProgressEvent,
+    ProposedMerge,
+    ReportAction,
+    SkipReason,
+    SyntheticFace,
+    WidthClass,
+};
+use crate::database::is_database_file;
+use crate::utils::lock::is_lock_file;
+use crate::font::cache::cached_font_metadata;
+use crate::font::metadata::{detect_synthetic, diagnose_font_file, extract_root_family, font_extension, sample_outlines, FONT_EXTENSIONS, METRIC_SIDECAR_EXTENSIONS};
 use crate::utils::{
+    canonical_casing,
+    check_available_space,
+    existing_folder_ignoring_case,
     ensure_directory_exists,
+    extract_fonts_from_zip,
     safe_move_file,
+    content_hash,
+    collision_free_path,
+    is_junk_file,
+    is_junk_folder,
+    format_size,
     clean_name,
     log,
+    log_info,
+    log_trace,
+    log_error,
     format_font_name,
     normalize_family_name,
+    normalize_unicode_family_name,
 };
 
-/// Determine if two font family names are similar enough to be grouped together
-fn are_family_names_similar(name1: &str, name2: &str) -> bool {
+/// Determine if two font family names are similar enough to be grouped together,
+/// using the thresholds in `config`
+pub(crate) fn are_family_names_similar(name1: &str, name2: &str, config: &Config) -> bool {
     // If either name is empty, they're not similar
     if name1.is_empty() || name2.is_empty() {
         return false;
@@ -27,71 +81,184 @@ fn are_family_names_similar(name1: &str, name2: &str) -> bool {
         return true;
     }
 
-    // Normalize both names for comparison
-    let norm1 = name1.to_lowercase().replace("_", " ").trim().to_string();
-    let norm2 = name2.to_lowercase().replace("_", " ").trim().to_string();
+    are_similarity_keys_similar(&SimilarityKey::new(name1), &SimilarityKey::new(name2), config)
+}
+
+/// A family name composed to NFC and lowercased, with underscores as spaces,
+/// as compared for similarity, so a decomposed "André" matches a composed one
+fn similarity_key(name: &str) -> String {
+    normalize_unicode_family_name(name).to_lowercase().replace("_", " ").trim().to_string()
+}
+
+/// A similarity key with what comparisons need from it, worked out once per
+/// name rather than on each of the many comparisons a clustering pass makes
+struct SimilarityKey {
+    key: String,
+    chars: Vec<char>,
+    /// First word of the key, empty for a blank key
+    first_word: String,
+    /// Characters the key contains, one bit each (shared by characters 64 apart)
+    letters: u64,
+    width: WidthClass,
+}
+
+impl SimilarityKey {
+    fn new(name: &str) -> Self {
+        let key = similarity_key(name);
+        Self {
+            chars: key.chars().collect(),
+            first_word: key.split_whitespace().next().unwrap_or_default().to_string(),
+            letters: key.chars().fold(0, |letters, c| letters | 1 << (c as u32 % 64)),
+            width: WidthClass::from_family_name(&key),
+            key,
+        }
+    }
+}
 
+/// Whether two similarity keys are similar enough to be grouped together
+fn are_similarity_keys_similar(key1: &SimilarityKey, key2: &SimilarityKey, config: &Config) -> bool {
+    let (norm1, norm2) = (key1.key.as_str(), key2.key.as_str());
     // If normalized names are identical, they're similar
     if norm1 == norm2 {
         return true;
     }
 
-    // Get the first characters of each name
-    let first_char1 = norm1.chars().next();
-    let first_char2 = norm2.chars().next();
+    // Names spelling out different widths are separate families, so
+    // "Roboto Condensed" is never merged into "Roboto"
+    if key1.width != key2.width {
+        return false;
+    }
+
+    // Without merging only identical normalized names are grouped
+    if config.merge_strategy == MergeStrategy::Off {
+        return false;
+    }
 
     // If the first characters are different, the names are not similar
     // This prevents grouping "Hybrea", "Hygge", etc. together
-    if first_char1 != first_char2 {
+    if key1.chars.first() != key2.chars.first() {
         return false;
     }
 
     // Check if one name is a prefix of the other (e.g., "Roboto" and "Roboto Slab")
-    if norm1.starts_with(&norm2) || norm2.starts_with(&norm1) {
+    if norm1.starts_with(norm2) || norm2.starts_with(norm1) {
         return true;
     }
 
     // Split names into words and check if they share the first word
     // This helps with cases like "Breul A" and "Breul B"
-    let words1: Vec<&str> = norm1.split_whitespace().collect();
-    let words2: Vec<&str> = norm2.split_whitespace().collect();
-
-    if !words1.is_empty() && !words2.is_empty() && words1[0] == words2[0] {
-        // If the first words match and they're substantial (not just 1-2 characters)
-        // Increased minimum length to 4 characters to be more strict
-        if words1[0].len() >= 4 {
-            return true;
-        }
+    // If the first words match and they're substantial (not just 1-2 characters)
+    // Increased minimum length to 4 characters to be more strict
+    if key1.first_word.len() >= 4 && key1.first_word == key2.first_word {
+        return true;
     }
 
     // Check if names share a significant common prefix
     let min_len = std::cmp::min(norm1.len(), norm2.len());
     if min_len >= 4 {
-        let common_prefix_len = norm1.chars().zip(norm2.chars())
+        let common_prefix_len = key1.chars.iter().zip(&key2.chars)
             .take_while(|(c1, c2)| c1 == c2)
             .count();
 
-        // If the common prefix is at least 70% of the shorter name, consider them similar
-        // Increased from 50% to 70% to be more strict
-        if common_prefix_len >= (min_len * 7) / 10 {
+        // If the common prefix covers enough of the shorter name (70% by default),
+        // consider them similar
+        if common_prefix_len >= fraction_of(min_len, config.min_prefix_ratio) {
             return true;
         }
     }
 
     // Check for Levenshtein distance (edit distance)
-    // For short names, allow 1 edit; for longer names, allow more edits proportionally
-    // Reduced max_distance to be more strict
-    let max_distance = std::cmp::max(1, min_len / 5);
-    let distance = levenshtein_distance(&norm1, &norm2);
+    // For short names, allow 1 edit; for longer names, allow more edits
+    // proportionally (20% of the shorter name by default). A threshold of 0
+    // disables edit-distance matching.
+    if config.similarity_threshold <= 0.0 {
+        return false;
+    }
+    let max_distance = std::cmp::max(1, fraction_of(min_len, config.similarity_threshold));
+
+    // The length difference alone needs that many edits
+    if key1.chars.len().abs_diff(key2.chars.len()) > max_distance {
+        return false;
+    }
+    // Each edit adds or removes at most two of the characters a name
+    // contains, so names differing in more need more edits
+    if (key1.letters ^ key2.letters).count_ones() as usize > 2 * max_distance {
+        return false;
+    }
+    let distance = levenshtein_distance(&key1.chars, &key2.chars, max_distance);
 
     distance <= max_distance
 }
 
-/// Calculate the Levenshtein distance (edit distance) between two strings
-fn levenshtein_distance(s1: &str, s2: &str) -> usize {
-    let s1_chars: Vec<char> = s1.chars().collect();
-    let s2_chars: Vec<char> = s2.chars().collect();
+/// Bucket of a family name for similarity comparisons. Names in different
+/// buckets are never similar: fuzzy matches need the same first character.
+fn similarity_bucket(name: &str) -> Option<char> {
+    similarity_key(name).chars().next()
+}
+
+/// Number of sampled names with their neighbor kept as evidence
+const MERGE_EVIDENCE_EXAMPLES: usize = 5;
+
+/// Decide whether the similarity merge phase runs over these family names.
+///
+/// In auto mode an evenly spaced sample of the sorted names is checked for a
+/// similar neighbor within its bucket. When fewer than the configured share
+/// of sampled names has one, as in already curated archives, merging is
+/// skipped.
+fn decide_merge<'a>(names: impl Iterator<Item = &'a String>, config: &Config) -> MergePhaseDecision {
+    let mut decision = MergePhaseDecision {
+        mode: config.merge_mode,
+        strategy: config.merge_strategy,
+        merged: config.merge_mode != MergeMode::Never && config.merge_strategy != MergeStrategy::Off,
+        threshold: config.merge_neighbor_threshold,
+        ..MergePhaseDecision::default()
+    };
+    if config.merge_mode != MergeMode::Auto || config.merge_strategy == MergeStrategy::Off {
+        return decision;
+    }
+
+    let mut names: Vec<&String> = names.collect();
+    names.sort();
+    let mut buckets: HashMap<char, Vec<&String>> = HashMap::new();
+    for name in &names {
+        if let Some(bucket) = similarity_bucket(name) {
+            buckets.entry(bucket).or_default().push(name);
+        }
+    }
+
+    let sample_size = config.merge_sample_size.clamp(1, names.len().max(1));
+    let step = names.len() as f64 / sample_size as f64;
+    for index in 0..sample_size.min(names.len()) {
+        let name = names[(index as f64 * step) as usize];
+        decision.sampled += 1;
+
+        let neighbor = similarity_bucket(name)
+            .and_then(|bucket| buckets.get(&bucket))
+            .and_then(|bucket| {
+                bucket.iter().copied().find(|other| *other != name && are_family_names_similar(name, other, config))
+            });
+        if let Some(neighbor) = neighbor {
+            decision.with_neighbor += 1;
+            if decision.examples.len() < MERGE_EVIDENCE_EXAMPLES {
+                decision.examples.push((name.clone(), neighbor.clone()));
+            }
+        }
+    }
+
+    decision.merged = decision.neighbor_rate() >= decision.threshold;
+    decision
+}
+
+/// `ratio` of `len` rounded down, with slack so that e.g. 70% of 10 is 7
+/// despite 0.7 not being exact in floating point
+fn fraction_of(len: usize, ratio: f32) -> usize {
+    (len as f32 * ratio + 1e-3).floor() as usize
+}
 
+/// Calculate the Levenshtein distance (edit distance) between two strings,
+/// given as their characters. Past `limit` the exact distance does not
+/// matter: the computation stops once every path costs more.
+fn levenshtein_distance(s1_chars: &[char], s2_chars: &[char], limit: usize) -> usize {
     let m = s1_chars.len();
     let n = s2_chars.len();
 
@@ -99,49 +266,902 @@ fn levenshtein_distance(s1: &str, s2: &str) -> usize {
     if m == 0 { return n; }
     if n == 0 { return m; }
 
-    // Create a matrix of size (m+1) x (n+1)
-    let mut matrix = vec![vec![0; n + 1]; m + 1];
-
-    // Initialize the first row and column
-    for i in 0..=m {
-        matrix[i][0] = i;
-    }
-    for j in 0..=n {
-        matrix[0][j] = j;
-    }
+    // Only the previous row of the (m+1) x (n+1) matrix is needed
+    let mut previous: Vec<usize> = (0..=n).collect();
+    let mut current = vec![0; n + 1];
 
-    // Fill the matrix
     for i in 1..=m {
+        current[0] = i;
         for j in 1..=n {
             let cost = if s1_chars[i - 1] == s2_chars[j - 1] { 0 } else { 1 };
 
-            matrix[i][j] = std::cmp::min(
+            current[j] = std::cmp::min(
                 std::cmp::min(
-                    matrix[i - 1][j] + 1,      // deletion
-                    matrix[i][j - 1] + 1       // insertion
+                    previous[j] + 1,        // deletion
+                    current[j - 1] + 1      // insertion
                 ),
-                matrix[i - 1][j - 1] + cost    // substitution
+                previous[j - 1] + cost      // substitution
             );
         }
+        if current.iter().all(|&distance| distance > limit) {
+            return limit + 1;
+        }
+        std::mem::swap(&mut previous, &mut current);
     }
 
-    matrix[m][n]
+    previous[n]
+}
+
+/// Find an existing family folder registered under the same or a similar name
+fn find_registered_family_folder(
+    family_folders: &HashMap<String, PathBuf>,
+    family_name: &str,
+    config: &Config,
+) -> Option<(String, PathBuf)> {
+    let clean_family = clean_name(family_name);
+
+    if let Some(folder) = family_folders.get(&clean_family) {
+        if folder.is_dir() {
+            return Some((clean_family, folder.clone()));
+        }
+    }
+
+    // Sort so the match does not depend on HashMap iteration order
+    let mut candidates: Vec<(&String, &PathBuf)> = family_folders.iter()
+        .filter(|(name, folder)| are_family_names_similar(&clean_family, name, config) && folder.is_dir())
+        .collect();
+    candidates.sort();
+
+    candidates.first().map(|(name, folder)| ((*name).clone(), (*folder).clone()))
+}
+
+/// Folder name a family would be grouped under
+pub(crate) fn grouping_folder_name(family_name: &str) -> String {
+    clean_name(&normalize_family_name(&extract_root_family(family_name)))
+}
+
+/// Files to consider for organizing: those directly in `dir`, plus those in
+/// its subdirectories when `config.recursive` (skipping set-aside folders)
+pub fn collect_font_candidates(dir: &Path, config: &Config) -> Result<Vec<PathBuf>> {
+    Ok(collect_candidates(dir, config)?.0)
+}
+
+/// Like `collect_font_candidates`, also counting the junk files skipped
+pub(crate) fn collect_candidates(dir: &Path, config: &Config) -> Result<(Vec<PathBuf>, usize)> {
+    let mut candidates = Vec::new();
+    let mut junk_skipped = 0;
+    walk_candidates(dir, config, false, &mut candidates, &mut junk_skipped)?;
+    Ok((candidates, junk_skipped))
+}
+
+fn walk_candidates(
+    dir: &Path,
+    config: &Config,
+    in_junk_folder: bool,
+    candidates: &mut Vec<PathBuf>,
+    junk_skipped: &mut usize,
+) -> Result<()> {
+    for path in fs::read_dir(dir)?.flatten().map(|entry| entry.path()) {
+        if path.is_file() {
+            if config.default_ignores && (in_junk_folder || is_junk_file(&path)) {
+                log_trace(config, format!("Skipping junk file {}", path.display()));
+                *junk_skipped += 1;
+            } else if !is_database_file(&path) && !is_lock_file(&path) {
+                candidates.push(path);
+            }
+        } else if config.recursive
+            && path.is_dir()
+            && !is_set_aside_folder(&path.file_name().unwrap_or_default().to_string_lossy(), config)
+        {
+            let junk_folder = in_junk_folder || is_junk_folder(&path);
+            walk_candidates(&path, config, junk_folder, candidates, junk_skipped)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fonts extracted from the zip archives of a directory before it is planned
+#[derive(Default)]
+struct ExtractedArchives {
+    fonts: Vec<PathBuf>,
+    /// Folders the archives were extracted into
+    dirs: Vec<PathBuf>,
+    /// Archives that held fonts, which `execute_plan` deletes with `delete_zips`
+    archives: Vec<PathBuf>,
+    errors: Vec<FileError>,
+}
+
+/// Extract the fonts of the zip archives in `dir`, each into a folder named
+/// after the archive next to it
+fn extract_archives(dir: &Path, config: &Config) -> Result<ExtractedArchives> {
+    let (candidates, _) = collect_candidates(dir, config)?;
+    let mut extracted = ExtractedArchives::default();
+
+    let zips = candidates.iter().filter(|path| {
+        path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    });
+    for zip_path in zips {
+        let stem = zip_path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let parent = zip_path.parent().unwrap_or(Path::new("."));
+        let extraction_dir = collision_free_path(parent, &stem, None, &extracted.dirs.iter().cloned().collect());
+
+        match extract_fonts_from_zip(zip_path, &extraction_dir, config) {
+            Ok(fonts) if fonts.is_empty() => {
+                log(config, format!("No fonts in {}", zip_path.display()));
+            }
+            Ok(fonts) => {
+                extracted.archives.push(zip_path.clone());
+                config.emit("extracted", json!({
+                    "archive": zip_path.display().to_string(),
+                    "fonts": fonts.len(),
+                }));
+                extracted.fonts.extend(fonts);
+                extracted.dirs.push(extraction_dir);
+            }
+            Err(e) => {
+                log_error(config, format!("Could not extract {}: {}", zip_path.display(), e));
+                extracted.errors.push(FileError::new(zip_path, ErrorStage::Metadata, format!("could not extract: {}", e)));
+                // A partly extracted archive keeps its folder for cleanup
+                if extraction_dir.exists() {
+                    extracted.dirs.push(extraction_dir);
+                }
+            }
+        }
+    }
+
+    Ok(extracted)
+}
+
+/// Compare italic faces against the upright face of the same weight and width,
+/// and upright faces against the one closest to Regular, listing probable
+/// synthetic faces as (path, kind, reference path)
+fn find_synthetic_faces(metadata_map: &HashMap<PathBuf, FontMetadata>) -> Vec<SyntheticFace> {
+    let mut families: HashMap<(&str, u16), Vec<(&PathBuf, &FontMetadata)>> = HashMap::new();
+    for (path, metadata) in metadata_map {
+        families.entry((metadata.family_name.as_str(), metadata.width)).or_default().push((path, metadata));
+    }
+
+    let mut synthetic: Vec<SyntheticFace> = families.into_par_iter()
+        .flat_map_iter(|(_, mut faces)| {
+            faces.sort_by_key(|(path, _)| *path);
+            let samples: HashMap<&PathBuf, _> = faces.iter()
+                .filter_map(|(path, _)| Some((*path, sample_outlines(path)?)))
+                .collect();
+            let uprights: Vec<(&PathBuf, &FontMetadata)> = faces.iter()
+                .filter(|(_, metadata)| !metadata.is_italic)
+                .copied()
+                .collect();
+            let regular = uprights.iter().min_by_key(|(_, metadata)| metadata.weight.abs_diff(400)).copied();
+
+            let mut found = Vec::new();
+            for (path, metadata) in &faces {
+                let reference = if metadata.is_italic {
+                    uprights.iter().find(|(_, upright)| upright.weight == metadata.weight).copied()
+                } else {
+                    regular.filter(|(regular_path, _)| regular_path != path)
+                };
+
+                let Some((reference_path, reference_metadata)) = reference else { continue };
+                let (Some(reference_sample), Some(sample)) = (samples.get(reference_path), samples.get(path)) else {
+                    continue;
+                };
+
+                if let Some(kind) = detect_synthetic(reference_sample, reference_metadata, sample, metadata) {
+                    found.push(SyntheticFace {
+                        path: (*path).clone(),
+                        kind,
+                        reference: reference_path.clone(),
+                    });
+                }
+            }
+            found
+        })
+        .collect();
+
+    synthetic.sort_by(|a, b| a.path.cmp(&b.path));
+    synthetic
+}
+
+/// List fonts whose family folder differs solely because the typographic family
+/// name takes precedence over the legacy one, as (path, legacy family, family)
+fn find_precedence_changes(metadata_map: &HashMap<PathBuf, FontMetadata>) -> Vec<PrecedenceChange> {
+    let mut changes: Vec<PrecedenceChange> = metadata_map.iter()
+        .filter_map(|(path, metadata)| {
+            let legacy = metadata.legacy_family_name.as_ref()?;
+            (grouping_folder_name(legacy) != grouping_folder_name(&metadata.family_name))
+                .then(|| PrecedenceChange {
+                    path: path.clone(),
+                    legacy_family: legacy.clone(),
+                    family: metadata.family_name.clone(),
+                })
+        })
+        .collect();
+
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
 }
 
 // The utility functions for formatting font names and building paths
 // have been moved to utils::naming module for better organization
 
 
-/// Organize fonts in a directory
+/// Whether a path has a font file extension
+pub(crate) fn is_font_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Append one line per quarantined file to `corrupt/report.txt`, so reports of
+/// earlier runs are kept
+fn append_corrupt_report(corrupt_dir: &Path, corrupt_fonts: &[CorruptFont], config: &Config) -> Result<()> {
+    if config.dry_run || corrupt_fonts.is_empty() {
+        return Ok(());
+    }
+
+    let mut report = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(corrupt_dir.join("report.txt"))?;
+    if let Some(run_id) = config.run_id() {
+        writeln!(report, "# run {}", run_id)?;
+    }
+    for font in corrupt_fonts {
+        writeln!(report, "{}: {}", font.path.display(), font.reason)?;
+    }
+    Ok(())
+}
+
+/// `name` in `dir`, spelled like a folder already there that differs only in case
+fn folder_ignoring_case(dir: &Path, name: String) -> PathBuf {
+    dir.join(existing_folder_ignoring_case(dir, &name).unwrap_or(name))
+}
+
+/// Folder a family is organized into: a folder registered for the same or a
+/// similar family by an earlier run sharing the maps (e.g. another batch source
+/// organized into the same output root), else `<output_root>[/<foundry>]/<family>`.
+///
+/// Returns the family name the folder is registered under along with it.
+fn resolve_family_dir(
+    family_name: String,
+    foundry: &str,
+    output_root: &Path,
+    config: &Config,
+    family_folders: &Mutex<HashMap<String, PathBuf>>,
+) -> (String, PathBuf) {
+    if let Some((registered_name, registered_dir)) = find_registered_family_folder(&family_folders.lock().unwrap(), &family_name, config) {
+        if registered_name != clean_name(&family_name) {
+            log(config, format!(
+                "Family '{}' joins previously organized family '{}'",
+                family_name, registered_name
+            ));
+        }
+        return (registered_name, registered_dir);
+    }
+
+    // Create a directory specifically for this normalized family name
+    // Don't rely on build_folder_path which might use the original family name
+    let family_dir = if config.group_by_foundry {
+        // If grouping by foundry is enabled, create a foundry/family structure
+        let foundry_name = config.folder_name(foundry);
+        // Handle potential empty foundry name
+        let foundry_dir = if foundry_name.is_empty() {
+            output_root.join("Unknown_Foundry")
+        } else {
+            folder_ignoring_case(output_root, foundry_name)
+        };
+
+        // The foundry folder is created along with the family folder
+        folder_ignoring_case(&foundry_dir, config.folder_name(&family_name))
+    } else {
+        // Otherwise, use the normalized family name directly
+        folder_ignoring_case(output_root, config.folder_name(&family_name))
+    };
+
+    (family_name, family_dir)
+}
+
+/// Claim a file name for `base_name.extension` in `target_dir`, suffixed by
+/// `collision_free_path` when the name exists on disk or was claimed by
+/// another font of the plan.
+pub(crate) fn claim_target_path(target_dir: &Path, base_name: &str, extension: &str, claimed: &mut HashSet<PathBuf>) -> PathBuf {
+    let target = collision_free_path(target_dir, base_name, Some(extension), claimed);
+    claimed.insert(target.clone());
+    target
+}
+
+/// Folder inside each family folder holding its variable fonts (`--separate-variable`)
+pub(crate) const VARIABLE_FOLDER: &str = "Variable";
+
+/// Folder a font goes into inside its family folder: the `Variable` folder for
+/// variable fonts with `--separate-variable`, a weight folder when grouping by
+/// weight, else the family folder itself
+pub(crate) fn font_target_dir(family_dir: &Path, metadata: &FontMetadata, config: &Config) -> PathBuf {
+    if metadata.is_variable && config.separate_variable {
+        return family_dir.join(VARIABLE_FOLDER);
+    }
+    match config.weight_folder(metadata.weight, metadata.width_class()) {
+        Some(weight_folder) => family_dir.join(weight_folder),
+        None => family_dir.to_path_buf(),
+    }
+}
+
+/// Plan the destination of a font: its pattern name inside `family_dir`, or
+/// inside the folder it is in when renaming in place (`family_dir` is `None`).
+/// Clashing names get a suffix from `claim_target_path`.
+fn plan_font_move(
+    source: PathBuf,
+    metadata: &FontMetadata,
+    family_dir: Option<&Path>,
+    config: &Config,
+    claimed: &mut HashSet<PathBuf>,
+) -> PlannedMove {
+    let extension = font_extension(metadata);
+    let clean_base_name = config.file_stem(&format_font_name(metadata, &config.naming_pattern), &extension);
+    match source.extension().map(|ext| ext.to_string_lossy().to_lowercase()) {
+        Some(current) if current != extension => log(config, format!(
+            "{} holds {} data, giving it the .{} extension",
+            source.display(), metadata.format, extension
+        )),
+        None => log(config, format!("{} has no extension, giving it .{}", source.display(), extension)),
+        _ => {}
+    }
+
+    let target_dir = match family_dir {
+        // Nest the font in its weight folder when grouping by weight
+        Some(family_dir) => font_target_dir(family_dir, metadata, config),
+        None => source.parent().unwrap_or(Path::new("")).to_path_buf(),
+    };
+
+    let destination = target_dir.join(format!("{}.{}", clean_base_name, extension));
+    if destination == source {
+        claimed.insert(destination);
+        return PlannedMove::skip(source, SkipReason::AlreadyOrganized);
+    }
+
+    let final_path = claim_target_path(&target_dir, &clean_base_name, &extension, claimed);
+    if final_path != destination {
+        log(
+            config,
+            format!(
+                "Font with same name exists. Renaming {} to {}",
+                source.display(),
+                final_path.display()
+            ),
+        );
+    }
+
+    PlannedMove::new(source, final_path, MoveAction::Move)
+}
+
+/// Note when a font goes into a family folder not named after its family.
+/// Per-file messages are debug output, which the progress display prints as
+/// lines instead of drawing a bar they would interleave with.
+fn check_family_dir(source: &Path, metadata: &FontMetadata, family_dir: &Path, config: &Config) {
+    let expected_dir_name = config.folder_name(&normalize_family_name(&metadata.family_name));
+    let actual_dir_name = family_dir.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+
+    if expected_dir_name.to_lowercase() != actual_dir_name.to_lowercase() && !config.group_by_foundry {
+        log(
+            config,
+            format!(
+                "Font family mismatch - {} should go to {} but is being placed in {}",
+                source.display(),
+                expected_dir_name,
+                actual_dir_name
+            ),
+        );
+    }
+}
+
+/// Carry out one planned move, creating its weight folder when needed
+pub(crate) fn execute_planned_move(planned: &PlannedMove, config: &Config) -> Result<()> {
+    // Sidecars are looked up before the font leaves their folder
+    if !planned.moves() {
+        return Ok(());
+    }
+    let sidecars = metric_sidecars(&planned.source);
+    if let Some(target_dir) = planned.destination.parent() {
+        ensure_directory_exists(target_dir, config)?;
+    }
+    safe_move_file(&planned.source, &planned.destination, config)?;
+
+    for sidecar in sidecars {
+        let extension = sidecar.extension().unwrap_or_default();
+        let target = planned.destination.with_extension(extension);
+        if target.exists() {
+            log(config, format!("Not moving metrics file {}: {} already exists", sidecar.display(), target.display()));
+            continue;
+        }
+        log(config, format!("Moving metrics file {} to {}", sidecar.display(), target.display()));
+        if let Err(e) = safe_move_file(&sidecar, &target, config) {
+            log_error(config, format!("Could not move metrics file {}: {}", sidecar.display(), e));
+        }
+    }
+    Ok(())
+}
+
+/// Metric files (`.afm`, `.pfm`) next to a Type 1 font with the same file
+/// stem, which move along with the font
+fn metric_sidecars(font: &Path) -> Vec<PathBuf> {
+    let is_type1 = font.extension()
+        .is_some_and(|ext| matches!(ext.to_string_lossy().to_lowercase().as_str(), "pfb" | "pfa"));
+    let (Some(dir), Some(stem)) = (font.parent(), font.file_stem()) else { return Vec::new() };
+    if !is_type1 {
+        return Vec::new();
+    }
+
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut sidecars: Vec<PathBuf> = entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.file_stem() == Some(stem))
+        .filter(|path| path.extension()
+            .is_some_and(|ext| METRIC_SIDECAR_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())))
+        .collect();
+    sidecars.sort();
+    sidecars
+}
+
+/// Organize one font into the library at `output_root`, joining a family
+/// folder registered in `family_folders` when there is one. With
+/// `--rename-only` the font is renamed inside its folder instead.
+///
+/// Returns where the font was moved, or `None` if it is already in place.
+pub fn organize_single_font(
+    path: &Path,
+    metadata: &FontMetadata,
+    output_root: &Path,
+    config: &Config,
+    family_folders: &Mutex<HashMap<String, PathBuf>>,
+) -> Result<Option<PathBuf>> {
+    let family_dir = if config.rename_only {
+        None
+    } else {
+        let family_key = normalize_family_name(&extract_root_family(&metadata.family_name));
+        let (family_name, family_dir) = resolve_family_dir(family_key, &metadata.foundry, output_root, config, family_folders);
+        ensure_directory_exists(&family_dir, config)?;
+        family_folders.lock().unwrap().insert(clean_name(&family_name), family_dir.clone());
+        Some(family_dir)
+    };
+
+    if let Some(family_dir) = &family_dir {
+        check_family_dir(path, metadata, family_dir, config);
+    }
+    let planned = plan_font_move(path.to_path_buf(), metadata, family_dir.as_deref(), config, &mut HashSet::new());
+    if !planned.moves() {
+        return Ok(None);
+    }
+
+    execute_planned_move(&planned, config)?;
+    Ok(Some(planned.destination))
+}
+
+/// Fonts of one family with their metadata
+type FamilyFonts = Vec<(PathBuf, FontMetadata)>;
+
+/// Family groups keyed by normalized family name
+type FamilyGroups = HashMap<String, FamilyFonts>;
+
+/// Width most fonts of a family have; the narrower width wins a tie
+fn dominant_width(fonts: &FamilyFonts) -> WidthClass {
+    let mut counts: BTreeMap<WidthClass, usize> = BTreeMap::new();
+    for (_, metadata) in fonts {
+        *counts.entry(metadata.width_class()).or_default() += 1;
+    }
+    counts.into_iter().rev().max_by_key(|(_, count)| *count).map_or(WidthClass::Normal, |(width, _)| width)
+}
+
+/// Union-find over family indices, joining similar families into clusters
+struct FamilyClusters {
+    parent: Vec<usize>,
+}
+
+impl FamilyClusters {
+    fn new(len: usize) -> Self {
+        Self { parent: (0..len).collect() }
+    }
+
+    /// Representative of the cluster `index` belongs to
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            self.parent[index] = self.parent[self.parent[index]];
+            index = self.parent[index];
+        }
+        index
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a != root_b {
+            self.parent[root_a.max(root_b)] = root_a.min(root_b);
+        }
+    }
+}
+
+/// Merge families with similar names into clusters, each organized under the
+/// name of its largest family (the first by name on a tie).
+///
+/// Only names in the same similarity bucket are compared, bucket by bucket in
+/// parallel, and similar pairs are joined with union-find. Clusters are
+/// transitive and do not depend on map iteration order.
+///
+/// Families a never-merge rule keeps apart from a family of their cluster
+/// keep their own group. With a `merge_reviewer`, each remaining merge is
+/// proposed in cluster order before it is applied. Rejected families keep
+/// their own group, as do all remaining ones after `RejectAll`; quitting
+/// returns `Error::Cancelled`.
+fn merge_similar_families(family_groups: FamilyGroups, config: &Config) -> Result<(FamilyGroups, Vec<FamilyMerge>)> {
+    let original_family_count = family_groups.len();
+    let mut families: Vec<(String, FamilyFonts)> = family_groups.into_iter().collect();
+    families.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // Names are normalized once rather than on every comparison
+    let keys: Vec<SimilarityKey> = families.iter().map(|(name, _)| SimilarityKey::new(name)).collect();
+    let widths: Vec<WidthClass> = families.iter().map(|(_, fonts)| dominant_width(fonts)).collect();
+    let mut buckets: HashMap<char, Vec<usize>> = HashMap::new();
+    for (index, key) in keys.iter().enumerate() {
+        if let Some(&bucket) = key.chars.first() {
+            buckets.entry(bucket).or_default().push(index);
+        }
+    }
+
+    let similar_pairs: Vec<(usize, usize)> = buckets.into_par_iter()
+        .flat_map_iter(|(_, members)| {
+            let mut pairs = Vec::new();
+            for (position, &a) in members.iter().enumerate() {
+                for &b in &members[position + 1..] {
+                    if widths[a] == widths[b] && are_similarity_keys_similar(&keys[a], &keys[b], config) {
+                        pairs.push((a, b));
+                    }
+                }
+            }
+            pairs
+        })
+        .collect();
+
+    let mut clusters = FamilyClusters::new(families.len());
+    for (a, b) in similar_pairs {
+        clusters.union(a, b);
+    }
+
+    // Members of each cluster in name order, keyed by representative, so
+    // merges are reviewed in name order too
+    let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in 0..families.len() {
+        members.entry(clusters.find(index)).or_default().push(index);
+    }
+
+    let sizes: Vec<usize> = families.iter().map(|(_, fonts)| fonts.len()).collect();
+    let mut families: Vec<Option<(String, FamilyFonts)>> = families.into_iter().map(Some).collect();
+    let mut merged_family_groups: FamilyGroups = HashMap::new();
+    let mut family_merges: Vec<FamilyMerge> = Vec::new();
+    let mut accept_all = false;
+    let mut reject_all = false;
+    for cluster in members.into_values() {
+        // The family with the most fonts names the cluster; indices follow name
+        // order, so the first of equally large families wins
+        let primary = *cluster.iter()
+            .max_by_key(|&&index| (sizes[index], std::cmp::Reverse(index)))
+            .unwrap();
+        let (primary_name, mut all_fonts) = families[primary].take().unwrap();
+        let mut merged_names = vec![primary_name.clone()];
+
+        for index in cluster {
+            if let Some((other_name, other_fonts)) = families[index].take() {
+                if let Some(rules) = &config.merge_rules {
+                    if let Some(apart_from) = merged_names.iter().find(|name| rules.keeps_apart(&other_name, name)) {
+                        log(config, format!("Keeping family '{}' apart from '{}' (never_merge)", other_name, apart_from));
+                        merged_family_groups.insert(other_name, other_fonts);
+                        continue;
+                    }
+                }
+
+                if let Some(reviewer) = config.merge_reviewer.as_ref().filter(|_| !accept_all) {
+                    let proposal = ProposedMerge {
+                        family: other_name.clone(),
+                        fonts: other_fonts.len(),
+                        merged_into: primary_name.clone(),
+                        merged_into_fonts: sizes[primary],
+                    };
+                    let answer = if reject_all { MergeDecision::Reject } else { reviewer(&proposal) };
+                    match answer {
+                        MergeDecision::Accept | MergeDecision::Always => {}
+                        MergeDecision::AcceptAll => accept_all = true,
+                        MergeDecision::Reject | MergeDecision::Never | MergeDecision::RejectAll => {
+                            reject_all |= answer == MergeDecision::RejectAll;
+                            log(config, format!("Keeping family '{}' apart from '{}'", other_name, primary_name));
+                            merged_family_groups.insert(other_name, other_fonts);
+                            continue;
+                        }
+                        MergeDecision::Quit => return Err(Error::Cancelled("family merge review stopped".to_string())),
+                    }
+                }
+
+                log(config, format!(
+                    "Merged family '{}' into similar family '{}'",
+                    other_name, primary_name
+                ));
+                all_fonts.extend(other_fonts);
+                merged_names.push(other_name.clone());
+                family_merges.push(FamilyMerge { family: other_name, merged_into: primary_name.clone() });
+            }
+        }
+
+        merged_family_groups.insert(primary_name, all_fonts);
+    }
+
+    log(config, format!(
+        "After merging similar families: {} families (reduced from {})",
+        merged_family_groups.len(), original_family_count
+    ));
+
+    family_merges.sort_by(|a, b| a.merged_into.cmp(&b.merged_into).then_with(|| a.family.cmp(&b.family)));
+    Ok((merged_family_groups, family_merges))
+}
+
+/// Plan the move phase. Family folders are resolved and registered in name
+/// order, so a family joins a folder registered for a similar family the same
+/// way on every run; families renamed in place (`--rename-only`) get no folder.
+/// Fonts of the families in `family_merges` are expected in the folder of the
+/// family they merged into.
+fn plan_moves(
+    families: Vec<(String, FamilyFonts)>,
+    family_merges: &[FamilyMerge],
+    output_root: &Path,
+    config: &Config,
+    family_folders: &Mutex<HashMap<String, PathBuf>>,
+    foundry_folders: &Mutex<HashMap<String, PathBuf>>,
+) -> Vec<FamilyPlan> {
+    // Destinations taken by the plan, so two families sharing a folder never
+    // pick the same collision suffix
+    let mut claimed_paths: HashSet<PathBuf> = HashSet::new();
+    // Family folders of the plan by lowercase path, so different families
+    // never get folders that only differ in case
+    let mut planned_dirs: HashMap<String, PathBuf> = HashMap::new();
+    let mut plan = Vec::new();
+    let merged_families: HashSet<String> = family_merges.iter()
+        .map(|merge| merge.family.to_lowercase())
+        .collect();
+
+    for (family_name, font_group) in families {
+        if font_group.is_empty() {
+            continue;
+        }
+
+        log(config, format!("Processing family group: {} with {} fonts", family_name, font_group.len()));
+        config.report_progress(ProgressEvent::FamilyDetected(family_name.clone(), font_group.len()));
+
+        let (family_name, family_dir) = if config.rename_only {
+            (family_name, None)
+        } else {
+            let (family_name, family_dir) = resolve_family_dir(
+                family_name,
+                &font_group[0].1.foundry,
+                output_root,
+                config,
+                family_folders,
+            );
+            let family_dir = distinct_ignoring_case(family_dir, &mut planned_dirs, config);
+
+            // Store folder reference so later runs sharing the maps and foundry grouping
+            // can find it; lookups skip registered folders that could not be created
+            let clean_family = clean_name(&family_name);
+            family_folders.lock().unwrap().insert(clean_family.clone(), family_dir.clone());
+            log(config, format!("Registered family folder: {} -> {}", clean_family, family_dir.display()));
+            if let Some(foundries) = &config.family_foundries {
+                foundries.lock().unwrap().insert(clean_family.clone(), font_group[0].1.foundry.clone());
+            }
+
+            if config.group_by_foundry {
+                let first_font = &font_group[0];
+                let clean_foundry = clean_name(&first_font.1.foundry);
+                let parent_dir = family_dir.parent().unwrap_or(output_root).to_path_buf();
+                foundry_folders.lock().unwrap().insert(clean_foundry.clone(), parent_dir.clone());
+
+                log(config, format!("Registered foundry folder: {} -> {}", clean_foundry, parent_dir.display()));
+            }
+
+            (family_name, Some(family_dir))
+        };
+
+        let moves = font_group.into_iter()
+            .map(|(path, metadata)| {
+                let merged = merged_families.contains(&normalize_family_name(&extract_root_family(&metadata.family_name)).to_lowercase());
+                if let Some(family_dir) = family_dir.as_deref().filter(|_| !merged) {
+                    check_family_dir(&path, &metadata, family_dir, config);
+                }
+                plan_font_move(path, &metadata, family_dir.as_deref(), config, &mut claimed_paths)
+            })
+            .collect();
+        plan.push(FamilyPlan { family_name, family_dir, moves });
+    }
+
+    plan
+}
+
+/// `family_dir`, or when another family of the plan has a folder spelled the
+/// same apart from case, the first `<name>_N` free of such clashes. Families
+/// are planned in name order, so the suffix is the same on every run.
+fn distinct_ignoring_case(family_dir: PathBuf, planned_dirs: &mut HashMap<String, PathBuf>, config: &Config) -> PathBuf {
+    let key = |dir: &Path| dir.to_string_lossy().to_lowercase();
+    let mut distinct = family_dir.clone();
+    let mut suffix = 1;
+    while planned_dirs.get(&key(&distinct)).is_some_and(|planned| *planned != distinct) {
+        suffix += 1;
+        let name = format!("{}_{}", family_dir.file_name().unwrap_or_default().to_string_lossy(), suffix);
+        distinct = family_dir.with_file_name(name);
+    }
+    if distinct != family_dir {
+        log(config, format!("{} differs from another family folder only in case, using {}", family_dir.display(), distinct.display()));
+    }
+    planned_dirs.insert(key(&distinct), distinct.clone());
+    distinct
+}
+
+/// Execute the planned moves of one family, returning its folder, how many
+/// fonts were moved and how long it took. A family whose folder cannot be
+/// created is skipped with an error for each of its fonts.
+fn execute_family_plan(
+    family: &FamilyPlan,
+    config: &Config,
+    errors: &Mutex<Vec<FileError>>,
+) -> Option<(Option<PathBuf>, usize, Duration)> {
+    // Families not started when the run is cancelled stay where they are
+    if config.is_cancelled() {
+        return None;
+    }
+    let family_start = Instant::now();
+
+    if let Some(family_dir) = &family.family_dir {
+        // Create the directory once per family
+        if !family_dir.is_dir() {
+            config.report_progress(ProgressEvent::CreatingDirectory(family_dir.clone()));
+        }
+        if let Err(e) = ensure_directory_exists(family_dir, config) {
+            log_error(config, format!("Could not create family directory {}: {}", family_dir.display(), e));
+            // Skip this family group if we can't create the directory
+            errors.lock().unwrap().extend(family.moves.iter().map(|planned| {
+                FileError::new(&planned.source, ErrorStage::Directory, format!("could not create {}: {}", family_dir.display(), e))
+            }));
+            return None;
+        }
+
+        log(config, format!("Created directory for family {}: {}", family.family_name, family_dir.display()));
+    }
+
+    let mut moved = 0;
+    for planned in &family.moves {
+        if !planned.moves() {
+            log(config, format!("{} is already organized", planned.source.display()));
+            continue;
+        }
+
+        // Weight folders are created as their first font arrives
+        if let Some(target_dir) = planned.destination.parent().filter(|_| family.family_dir.is_some()) {
+            if let Err(e) = ensure_directory_exists(target_dir, config) {
+                log_error(config, format!("Could not create weight directory {}: {}", target_dir.display(), e));
+                errors.lock().unwrap().push(FileError::new(&planned.source, ErrorStage::Directory, e));
+                continue;
+            }
+        }
+
+        log(config, format!("Moving {} to {}", planned.source.display(), planned.destination.display()));
+        if let Err(e) = execute_planned_move(planned, config) {
+            log(
+                config,
+                format!("Error moving file {}: {}", planned.source.display(), e),
+            );
+            errors.lock().unwrap().push(FileError::new(&planned.source, ErrorStage::Move, format!("could not move to {}: {}", planned.destination.display(), e)));
+        } else {
+            moved += 1;
+            let action = if family.family_dir.is_none() { ReportAction::Renamed } else { ReportAction::Moved };
+            config.record_action(&planned.source, Some(&planned.destination), action, None);
+            config.emit("moved", json!({ "from": planned.source.display().to_string(), "to": planned.destination.display().to_string() }));
+            config.report_progress(ProgressEvent::MovingFile { from: planned.source.clone(), to: planned.destination.clone() });
+            log(
+                config,
+                format!("Successfully moved {} to {}", planned.source.display(), planned.destination.display()),
+            );
+        }
+    }
+
+    Some((family.family_dir.clone(), moved, family_start.elapsed()))
+}
+
+/// Organize fonts in a directory: with `--extract-zips` extract its archives
+/// (except in a dry run), plan where every font goes, then carry out the plan
 pub fn organize_fonts(
     dir: &Path,
     config: &Config,
     processed_files: Arc<Mutex<HashSet<PathBuf>>>,
     family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
     foundry_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
-) -> Result<()> {
-    let duplicates_dir = dir.join("duplicates");
-    ensure_directory_exists(&duplicates_dir, config)?;
+) -> Result<OrganizeReport> {
+    config.validate()?;
+    let extracted = if config.extract_zips && !config.dry_run {
+        extract_archives(dir, config)?
+    } else {
+        ExtractedArchives::default()
+    };
+    let plan = plan_with_archives(dir, config, processed_files, family_folders, foundry_folders, extracted)?;
+    execute_plan(&plan, config)
+}
+
+/// Plan the organization of a directory without touching any file: read the
+/// metadata of every font, find duplicates and merges, and decide where each
+/// font goes. Zip archives are not opened, so the fonts inside them are not
+/// part of the plan; `organize_fonts` extracts them first with `--extract-zips`.
+pub fn plan_organize(dir: &Path, config: &Config) -> Result<OrganizePlan> {
+    plan_organize_shared(dir, config, Arc::default(), Arc::default(), Arc::default())
+}
+
+/// Plan setting aside the duplicate copies of a directory under
+/// `Config::duplicate_policy`, and nothing else: no font joins a family
+/// folder, and corrupt, synthetic, filtered and unapproved fonts stay put.
+/// The report keeps the duplicates and the metadata errors.
+pub fn plan_deduplicate(dir: &Path, config: &Config) -> Result<OrganizePlan> {
+    let plan = plan_organize(dir, config)?;
+    let report = plan.report;
+    Ok(OrganizePlan {
+        dir: plan.dir,
+        output_root: plan.output_root,
+        set_aside: plan.set_aside.into_iter().filter(|planned| planned.action == MoveAction::Deduplicate).collect(),
+        families: Vec::new(),
+        skipped: Vec::new(),
+        report: OrganizeReport {
+            fonts_processed: report.fonts_processed,
+            duplicates: report.duplicates,
+            duplicate_bytes: report.duplicate_bytes,
+            large_fonts: report.large_fonts,
+            large_bytes: report.large_bytes,
+            demoted: report.demoted,
+            duplicates_dir: report.duplicates_dir,
+            errors: report.errors,
+            ..OrganizeReport::default()
+        },
+        extraction_dirs: Vec::new(),
+        extracted_archives: Vec::new(),
+    })
+}
+
+/// `plan_organize` for runs sharing the processed files and the family and
+/// foundry folder maps, e.g. the sources of a batch organized into one root.
+/// The maps are updated with the fonts and folders of the plan as it is made,
+/// so later plans sharing them build on it.
+pub fn plan_organize_shared(
+    dir: &Path,
+    config: &Config,
+    processed_files: Arc<Mutex<HashSet<PathBuf>>>,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+    foundry_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<OrganizePlan> {
+    config.validate()?;
+    plan_with_archives(dir, config, processed_files, family_folders, foundry_folders, ExtractedArchives::default())
+}
+
+/// `plan_organize_shared` with the fonts of archives extracted beforehand
+fn plan_with_archives(
+    dir: &Path,
+    config: &Config,
+    processed_files: Arc<Mutex<HashSet<PathBuf>>>,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+    foundry_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+    extracted: ExtractedArchives,
+) -> Result<OrganizePlan> {
+
+    // Fonts are read from `dir` but may be organized into a separate output root
+    let output_root = config.output_dir.as_deref().unwrap_or(dir);
+
+    config.emit("organize_start", json!({
+        "directory": dir.display().to_string(),
+        "output": output_root.display().to_string(),
+        "dry_run": config.dry_run,
+    }));
+
+    let duplicates_dir = config.duplicates_path(output_root);
 
     // Collect metadata for all fonts first to help with duplicate detection
     let font_metadata_map: Arc<Mutex<HashMap<PathBuf, FontMetadata>>> = Arc::new(Mutex::new(HashMap::new()));
@@ -149,288 +1169,1267 @@ pub fn organize_fonts(
     // Map all fonts by their signatures for duplication detection
     let font_signatures: Arc<Mutex<HashMap<String, Vec<PathBuf>>>> = Arc::new(Mutex::new(HashMap::new()));
 
+    // Map all fonts by their content hash to find byte-identical copies
+    let font_hashes: Arc<Mutex<HashMap<blake3::Hash, Vec<PathBuf>>>> = Arc::new(Mutex::new(HashMap::new()));
+
+    // Fonts above the large threshold with their size, hashed after the metadata pass
+    let large_fonts: Mutex<Vec<(PathBuf, u64)>> = Mutex::new(Vec::new());
+
+    // Files with a font extension that could not be read, with the reason
+    let corrupt_fonts: Mutex<Vec<CorruptFont>> = Mutex::new(Vec::new());
+
+    // Files left in place because a step failed for them, reported at the end of the run
+    let errors: Mutex<Vec<FileError>> = Mutex::new(extracted.errors);
+
+    // Fonts of excluded families, left in place
+    let excluded_fonts: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+    // Copies of installed fonts, left in place
+    let system_fonts: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+    // Fonts the filter rejects
+    let filtered_fonts: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+    // Fonts named from their file name, for the summary
+    let named_from_filename: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+    // Fonts extracted from zip archives join the candidates, once even when
+    // the scan found them in their extraction folder
+    let (mut candidates, junk_skipped) = collect_candidates(dir, config)?;
+    let fonts_extracted = extracted.fonts.len();
+    let scanned: HashSet<PathBuf> = candidates.iter().cloned().collect();
+    candidates.extend(extracted.fonts.into_iter().filter(|path| !scanned.contains(path)));
+    if config.extract_zips && config.dry_run {
+        let archives = candidates.iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip")))
+            .count();
+        if archives > 0 {
+            log_info(config, format!("Dry run: {} zip archives are left closed, their fonts are not in the plan", archives));
+        }
+    }
+
+    // Fonts of --parse-css join the candidates, once even when also in the directory
+    if let Some(css_faces) = &config.css_faces {
+        let mut known: HashSet<PathBuf> = candidates.iter()
+            .map(|path| path.canonicalize().unwrap_or_else(|_| path.clone()))
+            .collect();
+        // Rules often share a file, which joins once under its first spelling
+        let referenced: Vec<PathBuf> = css_faces.iter()
+            .flat_map(|face| &face.sources)
+            .filter(|path| path.is_file())
+            .filter(|path| known.insert(path.canonicalize().unwrap_or_else(|_| path.to_path_buf())))
+            .cloned()
+            .collect();
+        log_info(config, format!("{} fonts added from the stylesheet", referenced.len()));
+        candidates.extend(referenced);
+    }
+
+    let scans = {
+        let processed = processed_files.lock().unwrap();
+        candidates.iter().filter(|path| !processed.contains(*path)).count()
+    };
+    config.report_progress(ProgressEvent::ScanStarted { dir: dir.to_path_buf(), total: scans });
+
     // First pass: collect metadata
-    fs::read_dir(dir)?
-        .par_bridge()
-        .for_each(|entry| {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-
-                // Skip directories or processed files
-                if !path.is_file() || processed_files.lock().unwrap().contains(&path) {
+    candidates
+        .par_iter()
+        .for_each(|path| {
+            // Skip processed files, and every file once the run is cancelled
+            if config.is_cancelled() || processed_files.lock().unwrap().contains(path) {
+                return;
+            }
+
+            config.report_progress(ProgressEvent::ScanningFile(path.clone()));
+            let metadata = match cached_font_metadata(path, config) {
+                Ok(Some(metadata)) => metadata,
+                failed => {
+                    // Only files with a font extension are diagnosed, so readmes and
+                    // images are never quarantined
+                    let corrupt_reason = (config.quarantine_corrupt && is_font_extension(path))
+                        .then(|| diagnose_font_file(path).or_else(|| failed.as_ref().err().map(|e| e.to_string())))
+                        .flatten();
+                    match (corrupt_reason, failed) {
+                        (Some(reason), _) => corrupt_fonts.lock().unwrap().push(CorruptFont { path: path.clone(), reason }),
+                        (None, Err(e)) => errors.lock().unwrap().push(FileError::new(path, ErrorStage::Metadata, e)),
+                        (None, Ok(_)) => {}
+                    }
                     return;
                 }
+            };
 
-                if let Ok(Some(metadata)) = extract_font_metadata(&path, config) {
-                    // Add to metadata map
-                    font_metadata_map.lock().unwrap().insert(path.clone(), metadata.clone());
-
-                    // Add to signatures for duplicate detection
-                    let signature = format!("{}_{}_{}",
-                        metadata.family_name,
-                        metadata.weight,
-                        metadata.is_italic
-                    );
-
-                    font_signatures.lock().unwrap()
-                        .entry(signature)
-                        .or_insert_with(Vec::new)
-                        .push(path.clone());
+            config.record_font(path, &metadata);
+            if metadata.source == MetadataSource::Filename {
+                named_from_filename.lock().unwrap().push(path.clone());
+            }
+
+            // Excluded families stay where they are and take no part in the run
+            if config.is_excluded(&metadata.family_name) {
+                excluded_fonts.lock().unwrap().push(path.clone());
+                return;
+            }
+
+            // So are the families installed on the system (`--ignore-system-fonts`)
+            if config.is_system_font(&metadata.family_name) {
+                system_fonts.lock().unwrap().push(path.clone());
+                return;
+            }
+
+            // Fonts the filter rejects take no part in the run
+            if config.font_filter.as_ref().is_some_and(|filter| !filter(&metadata)) {
+                filtered_fonts.lock().unwrap().push(path.clone());
+                return;
+            }
+
+            // Add to metadata map
+            font_metadata_map.lock().unwrap().insert(path.clone(), metadata.clone());
+
+            // Add to signatures for duplicate detection
+            let signature = format!("{}_{}_{}_{}_{}",
+                metadata.family_name,
+                metadata.weight,
+                metadata.width,
+                metadata.is_italic,
+                metadata.is_variable
+            );
+
+            font_signatures.lock().unwrap()
+                .entry(signature)
+                .or_default()
+                .push(path.clone());
+
+            if let Some(size) = large_font_size(path, config) {
+                large_fonts.lock().unwrap().push((path.clone(), size));
+                return;
+            }
+            match content_hash(path) {
+                Ok(hash) => font_hashes.lock().unwrap()
+                    .entry(hash)
+                    .or_default()
+                    .push(path.clone()),
+                Err(e) => {
+                    log_error(config, format!("Could not hash {}: {}", path.display(), e));
+                    errors.lock().unwrap().push(FileError::new(path, ErrorStage::Metadata, format!("could not hash: {}", e)));
                 }
             }
         });
+    if config.is_cancelled() {
+        return Err(Error::Cancelled("stopped while reading font metadata, no files were moved".to_string()));
+    }
+
+    // Large fonts are pre-screened with a sampled hash and only read in full on a match
+    let mut large_fonts = large_fonts.into_inner().unwrap();
+    large_fonts.sort();
+    for (hash, paths) in hash_large_fonts(&large_fonts, config, &errors) {
+        font_hashes.lock().unwrap().entry(hash).or_default().extend(paths);
+    }
 
-    log(config, format!("Collected metadata for {} fonts", 
+    // Files set aside before the families are organized, in the order they are moved
+    let mut set_aside = SetAsidePlan::default();
+    let mut skipped: Vec<PlannedMove> = Vec::new();
+
+    // Files that failed to parse go to the corrupt folder with a report line each
+    let mut corrupt_fonts = corrupt_fonts.into_inner().unwrap();
+    corrupt_fonts.sort_by(|a, b| a.path.cmp(&b.path));
+    let corrupt_dir = config.set_aside_path(output_root, "corrupt");
+    for font in &corrupt_fonts {
+        log(config, format!("Quarantining {} ({}) to {}", font.path.display(), font.reason, corrupt_dir.display()));
+        set_aside.add(&font.path, &corrupt_dir, MoveAction::Move, font.reason.clone(), &font_metadata_map, &processed_files);
+    }
+
+    let mut skipped_by_exclusion = excluded_fonts.into_inner().unwrap();
+    skipped_by_exclusion.sort();
+    for path in &skipped_by_exclusion {
+        log(config, format!("Skipping {} (excluded family)", path.display()));
+        skipped.push(PlannedMove::skip(path.clone(), SkipReason::Excluded).with_reason("family is excluded"));
+    }
+    processed_files.lock().unwrap().extend(skipped_by_exclusion.iter().cloned());
+
+    let mut skipped_system_fonts = system_fonts.into_inner().unwrap();
+    skipped_system_fonts.sort();
+    for path in &skipped_system_fonts {
+        log(config, format!("Skipping {} (installed on the system)", path.display()));
+        skipped.push(PlannedMove::skip(path.clone(), SkipReason::SystemFont).with_reason("System font"));
+    }
+    processed_files.lock().unwrap().extend(skipped_system_fonts.iter().cloned());
+
+    let mut named_from_filename = named_from_filename.into_inner().unwrap();
+    named_from_filename.sort();
+
+    // Fonts the filter rejects go to the skipped folder, or stay in place
+    let mut filtered = filtered_fonts.into_inner().unwrap();
+    filtered.sort();
+    let filtered_dir = config.set_aside_path(output_root, "_skipped");
+    if config.filter_action == FilterAction::Quarantine {
+        for path in &filtered {
+            log(config, format!("Moving {} (not matching the filter) to {}", path.display(), filtered_dir.display()));
+            let reason = "does not match the filter".to_string();
+            set_aside.add(path, &filtered_dir, MoveAction::Move, reason, &font_metadata_map, &processed_files);
+        }
+    } else {
+        for path in &filtered {
+            log(config, format!("Skipping {} (not matching the filter)", path.display()));
+            if config.filter_action == FilterAction::Skip {
+                skipped.push(PlannedMove::skip(path.clone(), SkipReason::Filtered).with_reason("does not match the filter"));
+            }
+        }
+        processed_files.lock().unwrap().extend(filtered.iter().cloned());
+        if config.filter_action == FilterAction::Ignore {
+            filtered.clear();
+        }
+    }
+
+    // Set aside byte-identical copies into the duplicates directory, keeping the first path
+    let (duplicate_count, duplicate_bytes) = set_aside_exact_duplicates(
+        &font_hashes.lock().unwrap(),
+        &duplicates_dir,
+        config,
+        &font_metadata_map,
+        &processed_files,
+        &mut set_aside,
+    );
+
+    // Flag faces that are sheared or emboldened copies of another face in their family
+    let synthetic_faces = if config.detect_synthetic {
+        find_synthetic_faces(&font_metadata_map.lock().unwrap())
+    } else {
+        Vec::new()
+    };
+
+    if config.quarantine_synthetic {
+        let synthetic_dir = config.set_aside_path(output_root, "synthetic");
+        for face in &synthetic_faces {
+            log(config, format!("Quarantining {} ({}) to {}", face.path.display(), face.kind, synthetic_dir.display()));
+            set_aside.add(&face.path, &synthetic_dir, MoveAction::Move, face.kind.to_string(), &font_metadata_map, &processed_files);
+        }
+    }
+
+    // Keep one copy of each face according to the duplicate policy
+    let demoted_fonts = resolve_signature_duplicates(
+        &font_signatures.lock().unwrap(),
+        &duplicates_dir,
+        config,
+        &font_metadata_map,
+        &processed_files,
+        &mut set_aside,
+    );
+
+    // Fonts the lockfile does not approve go to the review folder, or stay in place
+    let unapproved_dir = config.set_aside_path(output_root, "_Unapproved");
+    let (unapproved, missing_families) = match &config.lockfile {
+        Some(lockfile) => {
+            let (unapproved, unapproved_skipped, missing_families) =
+                apply_lockfile(lockfile, &unapproved_dir, config, &font_metadata_map, &processed_files, &mut set_aside);
+            skipped.extend(unapproved_skipped);
+            (unapproved, missing_families)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+    skipped.sort_by(|a, b| a.source.cmp(&b.source));
+
+    log(config, format!("Collected metadata for {} fonts",
         font_metadata_map.lock().unwrap().len()));
 
     // Group fonts by normalized family name
     let metadata_map = font_metadata_map.lock().unwrap().clone();
     let metadata_count = metadata_map.len();
 
-    // Create a map of normalized family names to lists of (path, metadata) pairs
-    let mut family_groups: HashMap<String, Vec<(PathBuf, FontMetadata)>> = HashMap::new();
+    // Fonts that land in a different folder than their legacy family name would give
+    let precedence_changes = find_precedence_changes(&metadata_map);
+
+    let technologies: Vec<(FontTechnology, usize)> = FontTechnology::ALL.into_iter()
+        .map(|technology| (technology, metadata_map.values().filter(|metadata| metadata.technology == technology).count()))
+        .filter(|(_, fonts)| *fonts > 0)
+        .collect();
+    let script_coverage: Vec<(FontScript, usize)> = FontScript::ALL.into_iter()
+        .map(|script| (script, metadata_map.values().filter(|metadata| metadata.scripts.contains(&script)).count()))
+        .filter(|(_, fonts)| *fonts > 0)
+        .collect();
+    let licenses: Vec<(FontLicense, usize)> = FontLicense::ALL.into_iter()
+        .map(|license| (license, metadata_map.values().filter(|metadata| metadata.license == license).count()))
+        .filter(|(_, fonts)| *fonts > 0)
+        .collect();
+
+    // Group fonts by normalized family name ignoring case, so "Gotham" and
+    // "gotham" share one folder on every file system, counting each spelling
+    let mut case_groups: HashMap<String, (HashMap<String, usize>, FamilyFonts)> = HashMap::new();
 
     for (path, metadata) in &metadata_map {
-        // Use normalized family name as the grouping key
+        // Use normalized family name as the grouping key, unless a force-merge
+        // rule pins the font to another family
         let root_family = extract_root_family(&metadata.family_name);
-        let normalized_root_family = normalize_family_name(&root_family);
+        let mut normalized_root_family = normalize_family_name(&root_family);
+        if let Some(target) = config.merge_rules.as_ref()
+            .and_then(|rules| rules.force_target(&metadata.family_name, &normalized_root_family))
+        {
+            normalized_root_family = normalize_family_name(target);
+        }
 
-        family_groups
-            .entry(normalized_root_family)
-            .or_insert_with(Vec::new)
-            .push((path.clone(), metadata.clone()));
+        let (spellings, fonts) = case_groups.entry(normalized_root_family.to_lowercase()).or_default();
+        *spellings.entry(normalized_root_family).or_default() += 1;
+        fonts.push((path.clone(), metadata.clone()));
     }
 
-    log(config, format!("Initially grouped fonts into {} families", family_groups.len()));
+    // Each group is named after its most common spelling
+    let mut family_groups: FamilyGroups = HashMap::new();
+    for (spellings, fonts) in case_groups.into_values() {
+        let Some(family_name) = canonical_casing(spellings.iter().map(|(name, count)| (name.as_str(), *count))) else { continue };
+        if spellings.len() > 1 {
+            let mut variants: Vec<&String> = spellings.keys().collect();
+            variants.sort();
+            log(config, format!("Family spellings {:?} differ only in case, grouped as '{}'", variants, family_name));
+        }
+        family_groups.insert(family_name.to_string(), fonts);
+    }
 
-    // Quality of life improvement: Group similar families together to reduce folder count
-    // Merge both single-font families and smaller multi-font families into larger similar families
-    let mut merged_family_groups: HashMap<String, Vec<(PathBuf, FontMetadata)>> = HashMap::new();
-    let mut all_families: Vec<(String, Vec<(PathBuf, FontMetadata)>)> = family_groups.into_iter().collect();
+    log(config, format!("Initially grouped fonts into {} families", family_groups.len()));
 
-    // Store the original number of families for logging
-    let original_family_count = all_families.len();
+    // Merge similar families unless --merge decides against it
+    let merge_decision = decide_merge(family_groups.keys(), config);
+    let mut family_merges = Vec::new();
+    if merge_decision.merged {
+        (family_groups, family_merges) = merge_similar_families(family_groups, config)?;
+    } else {
+        log(config, format!("Skipping the similarity merge phase ({} mode, {} strategy)", merge_decision.mode, merge_decision.strategy));
+    }
 
-    // Sort families by size (descending) to prefer merging into larger groups
-    all_families.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+    // Fonts organized by an earlier run sharing the maps are left alone
+    let mut families: Vec<(String, FamilyFonts)> = family_groups.into_iter().collect();
+    families.sort_by(|a, b| a.0.cmp(&b.0));
+    {
+        let processed_set = processed_files.lock().unwrap();
+        for (_, font_group) in &mut families {
+            font_group.retain(|(path, _)| !processed_set.contains(path));
+            // Moves within a family run in path order, so collision suffixes are deterministic
+            font_group.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+    }
 
-    // Create a map to track which families have been merged
-    let mut merged_families: HashSet<String> = HashSet::new();
+    // Every font gets its single destination before any file is touched
+    let families = plan_moves(families, &family_merges, output_root, config, &family_folders, &foundry_folders);
+    let set_aside = set_aside.moves;
+    check_moves(&families, &[set_aside.as_slice(), skipped.as_slice()].concat())?;
+    processed_files.lock().unwrap().extend(
+        families.iter().flat_map(|family| family.moves.iter().map(|planned| planned.source.clone()))
+    );
+    let organized: Vec<OrganizedFont> = families.iter()
+        .flat_map(|family| family.moves.iter().map(move |planned| (family, planned)))
+        .filter_map(|(family, planned)| metadata_map.get(&planned.source).map(|metadata| OrganizedFont {
+            source: planned.source.clone(),
+            destination: planned.destination.clone(),
+            family: family.family_name.clone(),
+            subfamily: metadata.subfamily.clone(),
+            foundry: metadata.foundry.clone(),
+            weight: metadata.weight,
+            is_italic: metadata.is_italic,
+        }))
+        .collect();
+
+    let report = OrganizeReport {
+        fonts_processed: metadata_count,
+        files_moved: 0,
+        organized,
+        family_dirs: Vec::new(),
+        duplicates: duplicate_count + demoted_fonts.len(),
+        duplicate_bytes,
+        large_fonts: large_fonts.len(),
+        large_bytes: large_fonts.iter().map(|(_, size)| size).sum(),
+        demoted: demoted_fonts,
+        duplicates_dir,
+        synthetic_faces,
+        technologies,
+        script_coverage,
+        licenses,
+        corrupt_fonts,
+        corrupt_dir,
+        precedence_changes,
+        unapproved,
+        unapproved_dir,
+        missing_families,
+        skipped_by_exclusion,
+        skipped_system_fonts,
+        filtered,
+        filtered_dir,
+        named_from_filename,
+        junk_skipped,
+        zips_extracted: extracted.archives.len(),
+        fonts_extracted,
+        merge_decision,
+        family_merges,
+        move_phase_time: Duration::ZERO,
+        move_work_time: Duration::ZERO,
+        errors: errors.into_inner().unwrap(),
+        dry_run: config.dry_run,
+    };
+
+    Ok(OrganizePlan {
+        dir: dir.to_path_buf(),
+        output_root: output_root.to_path_buf(),
+        set_aside,
+        families,
+        skipped,
+        report,
+        extraction_dirs: extracted.dirs,
+        extracted_archives: extracted.archives,
+    })
+}
 
-    // First pass: identify primary families (largest in each similar group)
-    let mut primary_families: Vec<(String, Vec<(PathBuf, FontMetadata)>)> = Vec::new();
+/// Carry out a plan from `plan_organize`: set files aside, then create the
+/// family folders and move their fonts in parallel. A file whose move fails
+/// stays where it is and is reported with an error; the run goes on.
+/// Once `Config::cancel` is set, families not yet started are left in place
+/// and `Error::Cancelled` is returned.
+pub fn execute_plan(plan: &OrganizePlan, config: &Config) -> Result<OrganizeResult> {
+    // A run that fills the drive halfway leaves fonts half-moved, so it does not start
+    check_available_space(&plan.dir, &plan.output_root, config)?;
+
+    let mut report = plan.report.clone();
+    let errors: Mutex<Vec<FileError>> = Mutex::new(std::mem::take(&mut report.errors));
+
+    // Archives are deleted once their fonts are out, whatever becomes of the fonts
+    if config.delete_zips && !config.dry_run {
+        for zip_path in &plan.extracted_archives {
+            match fs::remove_file(zip_path) {
+                Ok(()) => log(config, format!("Deleted {}", zip_path.display())),
+                Err(e) => log(config, format!("Could not delete {}: {}", zip_path.display(), e)),
+            }
+        }
+    }
 
-    for (i, (family_name, fonts)) in all_families.iter().enumerate() {
-        // Skip if this family has already been merged
-        if merged_families.contains(family_name) {
+    let corrupt: HashSet<&PathBuf> = report.corrupt_fonts.iter().map(|font| &font.path).collect();
+    let mut failed_set_aside: HashSet<PathBuf> = HashSet::new();
+    for planned in &plan.set_aside {
+        let moved = planned.destination.parent()
+            .map_or(Ok(()), |dir| ensure_directory_exists(dir, config))
+            .and_then(|()| safe_move_file(&planned.source, &planned.destination, config));
+        if let Err(e) = moved {
+            log_error(config, format!("Could not move {} to {}: {}", planned.source.display(), planned.destination.display(), e));
+            errors.lock().unwrap().push(FileError::new(
+                &planned.source,
+                ErrorStage::Move,
+                format!("could not move to {}: {}", planned.destination.display(), e),
+            ));
+            failed_set_aside.insert(planned.source.clone());
             continue;
         }
 
-        // This becomes a primary family
-        primary_families.push((family_name.clone(), fonts.clone()));
+        let action = match planned.action {
+            MoveAction::Deduplicate => ReportAction::Duplicate,
+            _ if corrupt.contains(&planned.source) => ReportAction::Error,
+            _ => ReportAction::Moved,
+        };
+        config.emit("set_aside", json!({ "from": planned.source.display().to_string(), "to": planned.destination.display().to_string() }));
+        config.report_progress(ProgressEvent::MovingFile { from: planned.source.clone(), to: planned.destination.clone() });
+        config.record_action(&planned.source, Some(&planned.destination), action, planned.reason.clone());
+    }
 
-        // Find all similar families and mark them as merged
-        for (j, (other_family, _)) in all_families.iter().enumerate() {
-            if i != j && !merged_families.contains(other_family) {
-                if are_family_names_similar(family_name, other_family) {
-                    merged_families.insert(other_family.clone());
+    // Files whose move failed were not set aside after all
+    if !failed_set_aside.is_empty() {
+        for planned in plan.set_aside.iter().filter(|planned| failed_set_aside.contains(&planned.source)) {
+            if planned.action == MoveAction::Deduplicate {
+                report.duplicates -= 1;
+                if !report.demoted.iter().any(|font| font.path == planned.source) {
+                    report.duplicate_bytes -= fs::metadata(&planned.source).map(|meta| meta.len()).unwrap_or(0);
                 }
             }
         }
+        report.demoted.retain(|font| !failed_set_aside.contains(&font.path));
+        report.corrupt_fonts.retain(|font| !failed_set_aside.contains(&font.path));
+        report.unapproved.retain(|font| !failed_set_aside.contains(&font.path));
+        if config.filter_action == FilterAction::Quarantine {
+            report.filtered.retain(|path| !failed_set_aside.contains(path));
+        }
     }
+    append_corrupt_report(&report.corrupt_dir, &report.corrupt_fonts, config)?;
 
-    // Second pass: merge similar families into their primary families
-    for (primary_name, primary_fonts) in primary_families {
-        let mut all_fonts = primary_fonts;
+    for planned in &plan.skipped {
+        config.record_action(&planned.source, None, ReportAction::Skipped, planned.reason.clone());
+    }
 
-        // Find all families that should be merged into this primary family
-        for (other_name, other_fonts) in &all_families {
-            if other_name != &primary_name && are_family_names_similar(&primary_name, other_name) {
-                all_fonts.extend(other_fonts.clone());
+    // Families are created and filled in parallel, the moves within one family in order
+    let moves = plan.families.iter()
+        .flat_map(|family| &family.moves)
+        .filter(|planned| planned.moves())
+        .count();
+    config.report_progress(ProgressEvent::MoveStarted { dir: plan.dir.clone(), total: moves });
+    let move_phase_start = Instant::now();
+    let family_outcomes: Vec<(Option<PathBuf>, usize, Duration)> = plan.families
+        .par_iter()
+        .filter_map(|family| execute_family_plan(family, config, &errors))
+        .collect();
+    report.move_phase_time = move_phase_start.elapsed();
+    report.move_work_time = family_outcomes.iter().map(|(_, _, elapsed)| *elapsed).sum();
+    report.files_moved = family_outcomes.iter().map(|(_, moved, _)| moved).sum();
+    if config.is_cancelled() {
+        config.emit("cancelled", json!({ "directory": plan.dir.display().to_string(), "files_moved": report.files_moved }));
+        return Err(Error::Cancelled(format!("stopped after moving {} fonts", report.files_moved)));
+    }
+    let mut organized_family_dirs: Vec<PathBuf> = family_outcomes.into_iter().filter_map(|(dir, _, _)| dir).collect();
 
-                log(config, format!(
-                    "Merged family '{}' into similar family '{}'",
-                    other_name, primary_name
-                ));
-            }
+    // Extraction folders emptied by the moves are removed
+    for extraction_dir in &plan.extraction_dirs {
+        if fs::remove_dir(extraction_dir).is_err() {
+            log(config, format!("Keeping {}, it still holds files that were not organized", extraction_dir.display()));
         }
+    }
 
-        // Add the merged family to the result
-        merged_family_groups.insert(primary_name, all_fonts);
+    let mut errors = errors.into_inner().unwrap();
+    errors.sort_by(|a, b| a.path.cmp(&b.path));
+
+    // Fonts whose move failed stayed where they were
+    let failed: HashSet<&PathBuf> = errors.iter().map(|error| &error.path).collect();
+    report.organized.retain(|font| !failed.contains(&font.source));
+    report.organized.sort_by(|a, b| a.destination.cmp(&b.destination));
+    for error in &errors {
+        config.emit("error", json!({
+            "path": error.path.display().to_string(),
+            "stage": error.stage.to_string(),
+            "error": error.error,
+        }));
+        config.record_action(&error.path, None, ReportAction::Error, Some(format!("{}: {}", error.stage, error.error)));
+    }
+    report.errors = errors;
+    config.report_checkpoint();
+
+    organized_family_dirs.sort();
+    organized_family_dirs.dedup();
+    report.family_dirs = organized_family_dirs;
+
+    config.emit("organize_end", json!({
+        "directory": plan.dir.display().to_string(),
+        "fonts_processed": report.fonts_processed,
+        "files_moved": report.files_moved,
+        "duplicates": report.duplicates,
+        "synthetic_faces": report.synthetic_faces.len(),
+        "corrupt_fonts": report.corrupt_fonts.len(),
+        "unapproved": report.unapproved.len(),
+        "excluded": report.skipped_by_exclusion.len(),
+        "system_fonts": report.skipped_system_fonts.len(),
+        "filtered": report.filtered.len(),
+        "errors": report.errors.len(),
+        "similarity_merge": report.merge_decision.merged,
+        "family_merges": report.family_merges.len(),
+    }));
+
+    if config.progress_callback.is_some() {
+        config.report_progress(ProgressEvent::Complete(Box::new(report.clone())));
     }
+    Ok(report)
+}
 
-    // Add any families that weren't merged
-    for (family_name, fonts) in &all_families {
-        if !merged_families.contains(family_name) && !merged_family_groups.contains_key(family_name) {
-            merged_family_groups.insert(family_name.clone(), fonts.clone());
-        }
+/// Print the summary of an `organize_fonts` run
+pub fn print_organize_summary(report: &OrganizeReport, config: &Config) {
+    log_info(config, format!("Font organization summary{}:", if report.dry_run { " (dry run, nothing moved)" } else { "" }));
+    log_info(config, format!("  - {} fonts processed", report.fonts_processed));
+    if report.dry_run {
+        log_info(config, format!("  - {} fonts would be moved", report.files_moved));
+    }
+    if !report.technologies.is_empty() {
+        let technologies: Vec<String> = report.technologies.iter()
+            .map(|(technology, fonts)| format!("{} {}", technology, fonts))
+            .collect();
+        log_info(config, format!("  - technologies: {}", technologies.join(", ")));
+    }
+    if !report.script_coverage.is_empty() {
+        let scripts: Vec<String> = report.script_coverage.iter()
+            .map(|(script, fonts)| format!("{} {}", script, fonts))
+            .collect();
+        log_info(config, format!("  - script coverage: {}", scripts.join(", ")));
+    }
+    if !report.licenses.is_empty() {
+        let licenses: Vec<String> = report.licenses.iter()
+            .map(|(license, fonts)| format!("{} {}", license, fonts))
+            .collect();
+        log_info(config, format!("  - licenses: {}", licenses.join(", ")));
+    }
+    if report.large_fonts > 0 {
+        log_info(config, format!("  - {} large fonts ({}) pre-screened with sampled hashes", report.large_fonts, format_size(report.large_bytes)));
+    }
+    if report.exact_duplicates() > 0 {
+        log_info(config, format!(
+            "  - {} exact duplicates moved to {} ({})",
+            report.exact_duplicates(), report.duplicates_dir.display(), format_size(report.duplicate_bytes)
+        ));
     }
 
-    log(config, format!(
-        "After merging similar families: {} families (reduced from {})",
-        merged_family_groups.len(), original_family_count
-    ));
+    if !report.demoted.is_empty() {
+        log_info(config, format!(
+            "  - {} duplicates demoted to {} ({} policy):",
+            report.demoted.len(), report.duplicates_dir.display(), config.duplicate_policy
+        ));
+        for font in &report.demoted {
+            log_info(config, format!(
+                "      {} (version {}): {}, kept {} (version {})",
+                font.path.display(),
+                font.version.as_deref().unwrap_or("unknown"),
+                font.reason,
+                font.kept.file_name().unwrap_or_default().to_string_lossy(),
+                font.kept_version.as_deref().unwrap_or("unknown")
+            ));
+        }
+    }
 
-    // Use the merged family groups for further processing
-    family_groups = merged_family_groups;
+    if !report.synthetic_faces.is_empty() {
+        let action = if config.quarantine_synthetic { "moved to synthetic" } else { "flagged" };
+        log_info(config, format!("  - {} probable synthetic faces {}:", report.synthetic_faces.len(), action));
+        for face in &report.synthetic_faces {
+            log_info(config, format!(
+                "      {}: {} of {}",
+                face.path.display(), face.kind, face.reference.file_name().unwrap_or_default().to_string_lossy()
+            ));
+        }
+    }
 
-    // Process each family group
-    for (family_name, font_group) in family_groups {
-        if font_group.is_empty() {
-            continue;
+    if !report.unapproved.is_empty() {
+        let action = match config.lockfile_mode {
+            LockfileMode::Review => format!("moved to {}", report.unapproved_dir.display()),
+            LockfileMode::Skip => "left in place".to_string(),
+        };
+        log_info(config, format!("  - {} fonts not approved by the lockfile {}:", report.unapproved.len(), action));
+        for font in &report.unapproved {
+            log_info(config, format!("      {}: {}", font.path.display(), font.reason));
         }
+    }
 
-        log(config, format!("Processing family group: {} with {} fonts", family_name, font_group.len()));
+    if !report.skipped_by_exclusion.is_empty() {
+        log_info(config, format!("  - {} fonts of excluded families left in place:", report.skipped_by_exclusion.len()));
+        for path in &report.skipped_by_exclusion {
+            log_info(config, format!("      {}", path.display()));
+        }
+    }
 
-        // Create a directory specifically for this normalized family name
-        // Don't rely on build_folder_path which might use the original family name
-        let family_dir = if config.group_by_foundry {
-            // If grouping by foundry is enabled, create a foundry/family structure
-            let first_font = &font_group[0];
-            let foundry_name = clean_name(&first_font.1.foundry);
-            // Handle potential empty foundry name
-            let foundry_dir = if foundry_name.is_empty() {
-                dir.join("Unknown_Foundry")
-            } else {
-                dir.join(foundry_name)
-            };
+    if !report.skipped_system_fonts.is_empty() {
+        log_info(config, format!("  - {} fonts of families installed on the system left in place:", report.skipped_system_fonts.len()));
+        for path in &report.skipped_system_fonts {
+            log_info(config, format!("      {}", path.display()));
+        }
+    }
 
-            if let Err(e) = ensure_directory_exists(&foundry_dir, config) {
-                log(config, format!("Error creating foundry directory {}: {}", foundry_dir.display(), e));
-                // Fall back to base directory if foundry directory creation fails
-                dir.join(clean_name(&family_name))
-            } else {
-                foundry_dir.join(clean_name(&family_name))
-            }
-        } else {
-            // Otherwise, use the normalized family name directly
-            dir.join(clean_name(&family_name))
+    if !report.filtered.is_empty() {
+        let action = match config.filter_action {
+            FilterAction::Quarantine => format!("moved to {}", report.filtered_dir.display()),
+            _ => "left in place".to_string(),
         };
+        let criteria = config.filter.as_ref().map(|filter| format!(" ({})", filter)).unwrap_or_default();
+        log_info(config, format!("  - {} fonts not matching the filter{} {}:", report.filtered.len(), criteria, action));
+        for path in &report.filtered {
+            log_info(config, format!("      {}", path.display()));
+        }
+    }
 
-        // Create the directory once per family
-        if let Err(e) = ensure_directory_exists(&family_dir, config) {
-            log(config, format!("Error creating family directory {}: {}", family_dir.display(), e));
-            // Skip this family group if we can't create the directory
-            continue;
+    if !report.missing_families.is_empty() {
+        log_info(config, format!("  - {} approved families missing from the library:", report.missing_families.len()));
+        for family in &report.missing_families {
+            log_info(config, format!("      {}", family));
         }
+    }
 
-        log(config, format!("Created directory for family {}: {}", family_name, family_dir.display()));
+    if !report.named_from_filename.is_empty() {
+        log_info(config, format!("  - {} fonts without a usable name table named from their file name:", report.named_from_filename.len()));
+        for path in &report.named_from_filename {
+            log_info(config, format!("      {}", path.display()));
+        }
+    }
 
-        // Store folder reference for potential foundry grouping later
-        if config.group_by_foundry {
-            let clean_family = clean_name(&family_name);
-            family_folders.lock().unwrap().insert(clean_family.clone(), family_dir.clone());
+    if report.junk_skipped > 0 {
+        log_info(config, format!("  - {} junk files skipped (.DS_Store, ._*, __MACOSX, Thumbs.db, desktop.ini)", report.junk_skipped));
+    }
 
-            let first_font = &font_group[0];
-            let clean_foundry = clean_name(&first_font.1.foundry);
-            let parent_dir = family_dir.parent().unwrap_or(dir).to_path_buf();
-            foundry_folders.lock().unwrap().insert(clean_foundry.clone(), parent_dir.clone());
+    if report.zips_extracted > 0 {
+        log_info(config, format!("  - {} fonts extracted from {} zip archives", report.fonts_extracted, report.zips_extracted));
+    }
 
-            log(config, format!("Registered family folder: {} -> {}", clean_family, family_dir.display()));
-            log(config, format!("Registered foundry folder: {} -> {}", clean_foundry, parent_dir.display()));
+    if !report.corrupt_fonts.is_empty() {
+        log_info(config, format!(
+            "  - {} corrupt fonts moved to {} (see report.txt):",
+            report.corrupt_fonts.len(), report.corrupt_dir.display()
+        ));
+        for font in &report.corrupt_fonts {
+            log_info(config, format!("      {}: {}", font.path.display(), font.reason));
         }
+    }
 
-        // Process each font in the family
-        for (path, metadata) in font_group {
-            let mut processed_set = processed_files.lock().unwrap();
+    if report.move_phase_time > Duration::ZERO {
+        log(config, format!(
+            "  - move phase: {:.2}s for {:.2}s of family work ({:.1}x parallel speedup)",
+            report.move_phase_time.as_secs_f64(),
+            report.move_work_time.as_secs_f64(),
+            report.move_work_time.as_secs_f64() / report.move_phase_time.as_secs_f64()
+        ));
+    }
 
-            if processed_set.contains(&path) {
-                continue;
+    match report.merge_decision.mode {
+        _ if report.merge_decision.strategy == MergeStrategy::Off => {
+            log_info(config, "  - similarity merge off (--merge-families off)".to_string());
+        }
+        MergeMode::Auto => {
+            let decision = &report.merge_decision;
+            log_info(config, format!(
+                "  - similarity merge {}: {} of {} sampled families ({:.1}%) have a similar name, threshold {:.1}%",
+                if decision.merged { "ran" } else { "skipped" },
+                decision.with_neighbor, decision.sampled, decision.neighbor_rate(), decision.threshold
+            ));
+            for (name, neighbor) in &decision.examples {
+                log_info(config, format!("      '{}' ~ '{}'", name, neighbor));
             }
+        }
+        MergeMode::Never => log_info(config, "  - similarity merge skipped (--merge never)".to_string()),
+        MergeMode::Always => {}
+    }
 
-            processed_set.insert(path.clone());
-
-            // Format new filename based on naming pattern
-            let base_name = format_font_name(&metadata, &config.naming_pattern);
-            let clean_base_name = clean_name(&base_name);
-
-            // Get file extension
-            let extension = path.extension()
-                .and_then(|ext| ext.to_str())
-                .unwrap_or("ttf")
-                .to_lowercase();
-
-            // Create new filename
-            let new_filename = format!("{}.{}", clean_base_name, extension);
-            let new_path = family_dir.join(&new_filename);
-
-            // Verify the target directory is correct for this font
-            let normalized_font_family = normalize_family_name(&metadata.family_name);
-            let expected_dir_name = clean_name(&normalized_font_family);
-            let actual_dir_name = family_dir.file_name()
-                .and_then(|name| name.to_str())
-                .unwrap_or("");
-
-            if expected_dir_name != actual_dir_name && !config.group_by_foundry {
-                log(
-                    config,
-                    format!(
-                        "WARNING: Font family mismatch - {} should go to {} but is being placed in {}",
-                        path.display(),
-                        expected_dir_name,
-                        actual_dir_name
-                    ),
-                );
-            }
+    if !report.family_merges.is_empty() {
+        log_info(config, format!("  - {} families merged into a similar family:", report.family_merges.len()));
+        for merge in &report.family_merges {
+            log_info(config, format!("      \"{}\" merged into \"{}\"", merge.family, merge.merged_into));
+        }
+    }
 
-            // Handle file move or duplicate
-            if new_path.exists() {
-                let mut unique_name = new_filename.clone();
-                let mut counter = 1;
+    if !report.errors.is_empty() {
+        log_info(config, format!("  - {} files could not be organized (see error report)", report.errors.len()));
+    }
 
-                // Use family_dir instead of target_dir (which no longer exists)
-                while family_dir.join(&unique_name).exists() {
-                    let name_part = clean_base_name.clone();
-                    unique_name = format!("{}_{}.{}", name_part, counter, extension);
-                    counter += 1;
-                }
+    if !report.precedence_changes.is_empty() {
+        log_info(config, format!(
+            "  - {} fonts grouped by typographic family (name ID 16) instead of legacy family (ID 1):",
+            report.precedence_changes.len()
+        ));
+        for change in &report.precedence_changes {
+            log_info(config, format!("      {}: '{}' -> '{}'", change.path.display(), change.legacy_family, change.family));
+        }
+    }
+}
 
-                let final_path = family_dir.join(unique_name);
-
-                log(
-                    config,
-                    format!(
-                        "Font with same name exists. Renaming {} to {}",
-                        path.display(),
-                        final_path.display()
-                    ),
-                );
-
-                if let Err(e) = safe_move_file(&path, &final_path, config) {
-                    log(
-                        config,
-                        format!("Error moving file {}: {}", path.display(), e),
-                    );
-                } else {
-                    log(
-                        config,
-                        format!("Successfully moved {} to {}", path.display(), final_path.display()),
-                    );
-                }
-            } else {
-                log(
-                    config,
-                    format!("Moving {} to {}", path.display(), new_path.display()),
-                );
-
-                if let Err(e) = safe_move_file(&path, &new_path, config) {
-                    log(
-                        config,
-                        format!("Error moving file {}: {}", path.display(), e),
-                    );
-                } else {
-                    log(
-                        config,
-                        format!("Successfully moved {} to {}", path.display(), new_path.display()),
-                    );
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom};
+    use crate::models::{DuplicatePolicy, MergeMode, NamingPattern, OrganizationDepth, SyntheticKind, WeightFolderStyle};
+    use crate::testing::{italic_outline, sheared_outline, upright_outline, with_sample_glyphs, TempDir, TestFont};
+
+    /// Destinations of the fonts organized in `dir`, relative to it
+    fn organize(dir: &Path, config: &Config) -> Vec<PathBuf> {
+        let report = organize_fonts(dir, config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+        let mut destinations: Vec<PathBuf> = report.organized.iter()
+            .map(|font| font.destination.strip_prefix(dir).unwrap().to_path_buf())
+            .collect();
+        destinations.sort();
+        destinations
+    }
+
+    fn weight_folder_config(style: WeightFolderStyle) -> Config {
+        Config {
+            organization_depth: OrganizationDepth::GroupByWeight,
+            weight_folder_style: style,
+            naming_pattern: NamingPattern::Custom("%Family%-%Subfamily%".to_string()),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn group_by_weight_nests_fonts_in_numeric_weight_folders() {
+        let tmp = TempDir::new("weight-folders");
+        TestFont::new("Roboto", "Regular").write(&tmp.join("roboto.ttf"));
+        TestFont::new("Roboto", "Bold Italic").weight(700).fs_selection(1).write(&tmp.join("roboto-bi.ttf"));
+
+        let destinations = organize(tmp.path(), &weight_folder_config(WeightFolderStyle::Numeric));
+        assert_eq!(destinations, [
+            PathBuf::from("Roboto/400/Roboto-Regular.ttf"),
+            PathBuf::from("Roboto/700/Roboto-BoldItalic.ttf"),
+        ]);
+    }
+
+    #[test]
+    fn group_by_weight_uses_textual_weight_folders() {
+        let tmp = TempDir::new("weight-folders");
+        TestFont::new("Roboto", "Bold Italic").weight(700).fs_selection(1).write(&tmp.join("roboto-bi.ttf"));
+
+        let destinations = organize(tmp.path(), &weight_folder_config(WeightFolderStyle::Textual));
+        assert_eq!(destinations, [PathBuf::from("Roboto/Bold/Roboto-BoldItalic.ttf")]);
+    }
+
+    #[test]
+    fn fonts_in_their_weight_folder_are_already_organized() {
+        let tmp = TempDir::new("weight-folders");
+        TestFont::new("Roboto", "Bold").weight(700).write(&tmp.join("roboto-b.ttf"));
+        let config = weight_folder_config(WeightFolderStyle::Numeric);
+        organize(tmp.path(), &config);
+
+        let again = organize_fonts(tmp.path(), &config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+        assert_eq!(again.files_moved, 0);
+        assert!(tmp.join("Roboto/700/Roboto-Bold.ttf").is_file());
+    }
+
+    /// An italic-only script family whose Regular face is slanted
+    fn italic_only_family(dir: &Path) {
+        TestFont::new("Script Test", "Regular").fs_selection(1).write(&dir.join("script.ttf"));
+        TestFont::new("Script Test", "Bold").weight(700).fs_selection(1).write(&dir.join("script-bold.ttf"));
+    }
+
+    #[test]
+    fn italic_only_family_keeps_the_slant_in_names() {
+        let tmp = TempDir::new("italic-only");
+        italic_only_family(tmp.path());
+
+        let destinations = organize(tmp.path(), &Config::default());
+        assert_eq!(destinations, [
+            PathBuf::from("Script Test/Script Test (Bold).ttf"),
+            PathBuf::from("Script Test/Script Test (Italic).ttf"),
+        ]);
+    }
+
+    #[test]
+    fn italic_only_family_creates_only_its_weight_folders() {
+        let tmp = TempDir::new("italic-only");
+        italic_only_family(tmp.path());
+
+        organize(tmp.path(), &weight_folder_config(WeightFolderStyle::Numeric));
+        let mut folders: Vec<String> = fs::read_dir(tmp.join("Script Test")).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        folders.sort();
+        assert_eq!(folders, ["400", "700"]);
+    }
+
+    /// Curated families, no two alike
+    const CURATED: [&str; 20] = [
+        "Avenir", "Bodoni", "Caslon", "Didot", "Eurostile", "Futura", "Gill Sans", "Helvetica", "Inter", "Jenson",
+        "Kabel", "Lato", "Myriad", "Nunito", "Optima", "Palatino", "Quicksand", "Rockwell", "Syntax", "Trajan",
+    ];
+
+    fn auto_merge_config() -> Config {
+        Config { merge_mode: MergeMode::Auto, ..Config::default() }
+    }
+
+    #[test]
+    fn auto_merge_skips_curated_names() {
+        let names: Vec<String> = CURATED.iter().map(|name| name.to_string()).collect();
+        let decision = decide_merge(names.iter(), &auto_merge_config());
+        assert_eq!((decision.sampled, decision.with_neighbor), (20, 0));
+        assert!(decision.examples.is_empty());
+        assert!(!decision.merged);
+    }
+
+    #[test]
+    fn auto_merge_runs_when_names_have_neighbors() {
+        let names: Vec<String> = CURATED.iter()
+            .flat_map(|name| [name.to_string(), format!("{} Pro", name)])
+            .collect();
+        let decision = decide_merge(names.iter(), &auto_merge_config());
+        assert_eq!((decision.sampled, decision.with_neighbor), (40, 40));
+        assert_eq!(decision.examples.len(), MERGE_EVIDENCE_EXAMPLES);
+        assert_eq!(decision.examples[0], ("Avenir".to_string(), "Avenir Pro".to_string()));
+        assert!(decision.merged);
+    }
+
+    #[test]
+    fn auto_merge_samples_evenly_and_honors_the_threshold() {
+        // Four of the 22 names have a neighbor, every other one is sampled
+        let names: Vec<String> = CURATED.iter()
+            .map(|name| name.to_string())
+            .chain(["Avenir Pro".to_string(), "Lato Pro".to_string()])
+            .collect();
+        let config = Config { merge_sample_size: 11, merge_neighbor_threshold: 20.0, ..auto_merge_config() };
+        let decision = decide_merge(names.iter(), &config);
+        assert_eq!(decision.sampled, 11);
+        assert!(decision.with_neighbor > 0);
+        assert!(!decision.merged);
+
+        let config = Config { merge_neighbor_threshold: 1.0, ..config };
+        assert!(decide_merge(names.iter(), &config).merged);
+    }
+
+    #[test]
+    fn fixed_merge_modes_do_not_sample() {
+        let names: Vec<String> = CURATED.iter().map(|name| name.to_string()).collect();
+        for (mode, merged) in [(MergeMode::Always, true), (MergeMode::Never, false)] {
+            let decision = decide_merge(names.iter(), &Config { merge_mode: mode, ..Config::default() });
+            assert_eq!((decision.sampled, decision.merged), (0, merged), "{}", mode);
+        }
+        let off = Config { merge_strategy: MergeStrategy::Off, ..auto_merge_config() };
+        assert!(!decide_merge(names.iter(), &off).merged);
+    }
+
+    /// Pronounceable family names from `seed`, each followed by a "Pro" cut
+    /// that merges into it
+    fn generated_family_names(count: usize, mut seed: u64) -> Vec<String> {
+        const INITIALS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        const SYLLABLES: [&str; 16] = ["ka", "lo", "mi", "ner", "so", "tu", "va", "rel", "dan", "bri", "go", "fen", "pa", "qui", "ze", "hol"];
+        let mut next = move || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        let mut names = std::collections::BTreeSet::new();
+        while names.len() < count {
+            let mut name = String::from(INITIALS[next() % INITIALS.len()] as char);
+            for _ in 0..2 + next() % 3 {
+                name.push_str(SYLLABLES[next() % SYLLABLES.len()]);
             }
+            names.insert(format!("{} Pro", name));
+            names.insert(name);
         }
+        names.into_iter().collect()
     }
 
-    // Report statistics
-    println!("Font organization summary:");
-    println!("  - {} fonts processed", metadata_count);
+    /// Merged family name to its sorted member names
+    type Clusters = BTreeMap<String, Vec<String>>;
+
+    /// Clusters of `names` and the merges made, as (family, merged into),
+    /// with the groups inserted in `names` order
+    fn clusters(names: &[String], metadata: &FontMetadata, config: &Config) -> (Clusters, Vec<(String, String)>) {
+        let groups: FamilyGroups = names.iter()
+            .map(|name| (name.clone(), vec![(PathBuf::from(name), metadata.clone())]))
+            .collect();
+        let (groups, merges) = merge_similar_families(groups, config).unwrap();
+        let clusters = groups.into_iter()
+            .map(|(name, fonts)| {
+                let mut members: Vec<String> = fonts.into_iter().map(|(path, _)| path.display().to_string()).collect();
+                members.sort();
+                (name, members)
+            })
+            .collect();
+        (clusters, merges.into_iter().map(|merge| (merge.family, merge.merged_into)).collect())
+    }
 
-    Ok(())
+    #[test]
+    fn clustering_tens_of_thousands_of_families_ignores_input_order() {
+        let names = generated_family_names(20_000, 7);
+        let metadata = crate::font::metadata::extract_font_metadata_from_bytes(Path::new("font.ttf"), &TestFont::new("Font", "Regular").build(), &Config::default())
+            .unwrap()
+            .unwrap();
+        let config = Config::default();
+
+        let (forward, forward_merges) = clusters(&names, &metadata, &config);
+        assert_eq!(forward.values().map(Vec::len).sum::<usize>(), names.len());
+        assert!(forward.len() < names.len() / 2, "every \"Pro\" cut merges into its family");
+        for (name, members) in &forward {
+            assert!(members.contains(name), "{} is named after a family it does not hold", name);
+        }
+        let cluster_of: HashMap<&str, &str> = forward.iter()
+            .flat_map(|(name, members)| members.iter().map(move |member| (member.as_str(), name.as_str())))
+            .collect();
+        for name in names.iter().filter(|name| !name.ends_with(" Pro")) {
+            assert_eq!(cluster_of[format!("{} Pro", name).as_str()], cluster_of[name.as_str()]);
+        }
+
+        let mut reversed = names.clone();
+        reversed.reverse();
+        let (backward, backward_merges) = clusters(&reversed, &metadata, &config);
+        assert!(backward == forward, "clusters depend on input order");
+        assert!(backward_merges == forward_merges, "merges depend on input order");
+    }
+
+    /// A font padded with zeros to 4 MB, plus `edit` written at 2 MB
+    fn large_font(path: &Path, edit: &[u8]) {
+        let mut file = fs::File::create(path).unwrap();
+        file.write_all(&TestFont::new("Noto Sans CJK", "Regular").build()).unwrap();
+        file.set_len(4 << 20).unwrap();
+        file.seek(SeekFrom::Start(2 << 20)).unwrap();
+        file.write_all(edit).unwrap();
+    }
+
+    #[test]
+    fn large_fonts_are_only_set_aside_as_confirmed_copies() {
+        let tmp = TempDir::new("large-fonts");
+        large_font(&tmp.join("a.otf"), b"");
+        large_font(&tmp.join("b.otf"), b"");
+        // Same ends as the copies, so only the full hash tells it apart
+        large_font(&tmp.join("c.otf"), b"edited");
+        let config = Config { large_threshold: 1 << 20, duplicate_policy: DuplicatePolicy::KeepBest, ..Config::default() };
+
+        let report = organize_fonts(tmp.path(), &config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+        assert_eq!((report.large_fonts, report.large_bytes), (3, 3 * (4 << 20)));
+        assert_eq!(report.exact_duplicates(), 1);
+        // Keep-best would demote the edited font, but it is not byte-identical
+        assert!(report.demoted.is_empty());
+        assert_eq!(report.organized.len(), 2);
+    }
+
+    #[test]
+    fn excluded_families_stay_in_place() {
+        let tmp = TempDir::new("exclude");
+        TestFont::new("Arial", "Regular").write(&tmp.join("arial.ttf"));
+        TestFont::new("Arialita", "Regular").write(&tmp.join("arialita.ttf"));
+        let config = Config { exclude_patterns: vec![glob::Pattern::new("Arial").unwrap()], ..Config::default() };
+
+        let report = organize_fonts(tmp.path(), &config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+        assert_eq!(report.skipped_by_exclusion, [tmp.join("arial.ttf")]);
+        assert!(tmp.join("arial.ttf").is_file());
+        assert!(tmp.join("Arialita/Arialita.ttf").is_file());
+    }
+
+    #[test]
+    fn deduplicating_only_sets_the_copies_aside() {
+        let tmp = TempDir::new("dedupe-only");
+        let font = TestFont::new("Arial", "Regular").build();
+        fs::write(tmp.join("arial.ttf"), &font).unwrap();
+        fs::write(tmp.join("arial copy.ttf"), &font).unwrap();
+        TestFont::new("Inter", "Regular").write(&tmp.join("inter.ttf"));
+
+        let plan = plan_deduplicate(tmp.path(), &Config::default()).unwrap();
+        assert!(plan.families.is_empty());
+        assert_eq!(plan.set_aside.len(), 1);
+        assert!(plan.set_aside.iter().all(|planned| planned.action == MoveAction::Deduplicate));
+        assert!(plan.report.organized.is_empty());
+
+        execute_plan(&plan, &Config::default()).unwrap();
+        assert!(tmp.join("inter.ttf").is_file());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().filter(|entry| entry.as_ref().unwrap().path().is_file()).count(), 2);
+    }
+
+    #[test]
+    fn cancelled_runs_leave_the_fonts_in_place() {
+        let tmp = TempDir::new("cancel");
+        TestFont::new("Arial", "Regular").write(&tmp.join("arial.ttf"));
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let config = Config { cancel: Some(cancel.clone()), ..Config::default() };
+
+        let plan = plan_organize(tmp.path(), &config).unwrap();
+        cancel.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(matches!(execute_plan(&plan, &config), Err(Error::Cancelled(_))));
+        assert!(matches!(plan_organize(tmp.path(), &config), Err(Error::Cancelled(_))));
+        assert!(tmp.join("arial.ttf").is_file());
+        assert!(!tmp.join("Arial").exists());
+    }
+
+    #[test]
+    fn sheared_obliques_are_quarantined_and_genuine_italics_kept() {
+        let tmp = TempDir::new("synthetic");
+        with_sample_glyphs(TestFont::new("Genuine", "Regular"), upright_outline).write(&tmp.join("genuine.ttf"));
+        let italic = with_sample_glyphs(TestFont::new("Genuine", "Italic").fs_selection(1), italic_outline)
+            .write(&tmp.join("genuine-italic.ttf"));
+        let upright = with_sample_glyphs(TestFont::new("Faked", "Regular"), upright_outline).write(&tmp.join("faked.ttf"));
+        let oblique = with_sample_glyphs(TestFont::new("Faked", "Italic").fs_selection(1), sheared_outline)
+            .write(&tmp.join("faked-italic.ttf"));
+        let config = Config { detect_synthetic: true, quarantine_synthetic: true, ..Config::default() };
+
+        let report = organize_fonts(tmp.path(), &config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+        let flagged: Vec<(&Path, SyntheticKind, &Path)> = report.synthetic_faces.iter()
+            .map(|face| (face.path.as_path(), face.kind, face.reference.as_path()))
+            .collect();
+        assert_eq!(flagged, [(oblique.as_path(), SyntheticKind::Oblique, upright.as_path())]);
+
+        assert!(!italic.exists() && !oblique.exists());
+        assert!(tmp.join("Genuine/Genuine (Italic).ttf").is_file());
+        assert!(tmp.join("synthetic/faked-italic.ttf").is_file());
+        assert!(!tmp.join("Faked/Faked (Italic).ttf").exists());
+    }
+
+    #[test]
+    fn stylesheet_fonts_shared_by_several_rules_join_once() {
+        let tmp = TempDir::new("css-shared");
+        let library = tmp.join("library");
+        TestFont::new("Inter", "Regular").write(&library.join("inter.ttf"));
+        TestFont::new("Web Sans", "Regular").write(&tmp.join("site/fonts/web.ttf"));
+        let css = tmp.join("site/site.css");
+        fs::write(&css, "
+            @font-face { font-family: 'Web Sans'; src: url(fonts/web.ttf); }
+            @font-face { font-family: 'Web Sans Fallback'; src: url(../site/fonts/web.ttf); }
+            @font-face { font-family: 'Web Sans'; font-weight: bold; src: url(fonts/web.ttf); }
+            @font-face { font-family: 'Inter'; src: url(../library/inter.ttf); }
+        ").unwrap();
+        let faces = crate::utils::css::parse_css_font_faces(&css, None).unwrap();
+        let config = Config { css_faces: Some(Arc::new(faces)), ..Config::default() };
+
+        let report = organize_fonts(&library, &config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(report.organized.len(), 2);
+        assert!(library.join("Web Sans/Web Sans.ttf").is_file());
+        assert!(library.join("Inter/Inter.ttf").is_file());
+    }
+
+    #[test]
+    fn collections_move_as_one_file() {
+        let tmp = TempDir::new("collections");
+        let faces = [TestFont::new("SF Pro", "Regular").build(), TestFont::new("SF Pro", "Bold").weight(700).build()];
+        fs::write(tmp.join("sf-pro.otc"), crate::testing::collection(&faces)).unwrap();
+        TestFont::new("SF Pro", "Italic").fs_selection(1).write(&tmp.join("sf-pro-italic.ttf"));
+
+        assert_eq!(organize(tmp.path(), &Config::default()), [
+            PathBuf::from("SF Pro/SF Pro (Italic).ttf"),
+            PathBuf::from("SF Pro/SF Pro.otc"),
+        ]);
+    }
+
+    #[test]
+    fn extensions_follow_the_real_format() {
+        let tmp = TempDir::new("extensions");
+        TestFont::new("Arial", "Regular").write(&tmp.join("ARIAL.TTF"));
+        TestFont::new("Roboto", "Bold").weight(700).write(&tmp.join("Roboto-Bold.Otf"));
+        TestFont::new("Lato", "Regular").write(&tmp.join("Lato-Regular"));
+        // CFF-flavored data in a file named .ttf
+        let mut cff = TestFont::new("Inter", "Regular").build();
+        cff[..4].copy_from_slice(b"OTTO");
+        fs::write(tmp.join("Inter.ttf"), cff).unwrap();
+        fs::write(tmp.join("README"), "not a font").unwrap();
+
+        assert_eq!(organize(tmp.path(), &Config::default()), [
+            PathBuf::from("Arial/Arial.ttf"),
+            PathBuf::from("Inter/Inter.otf"),
+            PathBuf::from("Lato/Lato.ttf"),
+            PathBuf::from("Roboto/Roboto (Bold).ttf"),
+        ]);
+        assert!(tmp.join("README").is_file());
+    }
+
+    /// A font whose family and subfamily names are far longer than any path allows
+    fn long_named_font(dir: &Path) {
+        let family = "Lorem Ipsum Dolor Amet ".repeat(11);
+        let subfamily = "Extra Condensed Bold Display ".repeat(8);
+        TestFont::new(family.trim(), subfamily.trim()).weight(700).write(&dir.join("long.ttf"));
+    }
+
+    #[test]
+    fn long_target_paths_are_cut_deterministically() {
+        let tmp = TempDir::new("long-paths");
+        long_named_font(tmp.path());
+        let config = Config::default();
+
+        let destinations = organize(tmp.path(), &config);
+        let target = tmp.join(&destinations[0]);
+        assert!(target.as_os_str().len() > 260, "{}", target.display());
+        assert!(target.is_file());
+        assert_eq!(target.extension().unwrap(), "ttf");
+        for component in &destinations[0] {
+            assert!(component.len() <= config.max_name_length, "{:?}", component);
+        }
+
+        // The cut name is recognized as organized on the next run
+        let config = Config { recursive: true, ..config };
+        let report = organize_fonts(tmp.path(), &config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+        assert_eq!(report.organized[0].source, report.organized[0].destination);
+        assert_eq!(report.files_moved, 0);
+    }
+
+    #[test]
+    fn names_are_cut_to_the_maximum_length() {
+        let tmp = TempDir::new("long-paths");
+        long_named_font(tmp.path());
+        let config = Config { max_name_length: 64, ..Config::default() };
+
+        let destination = organize(tmp.path(), &config).remove(0);
+        let folder = destination.parent().unwrap().to_string_lossy().into_owned();
+        let file = destination.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(folder.len() <= 64 && !folder.ends_with(' '), "{}", folder);
+        // Room is kept for the extension and a collision suffix
+        assert!(file.len() <= 64 - crate::utils::COLLISION_SUFFIX_ROOM && file.ends_with(".ttf"), "{}", file);
+        assert!(folder.starts_with("Lorem Ipsum Dolor Amet"));
+    }
+
+    /// Whether no two of `paths` would be the same path on a case-insensitive file system
+    fn distinct_ignoring_case_on_disk(paths: &[PathBuf]) -> bool {
+        let lowercase: HashSet<String> = paths.iter().map(|path| path.to_string_lossy().to_lowercase()).collect();
+        lowercase.len() == paths.len()
+    }
+
+    #[test]
+    fn family_spellings_share_one_folder() {
+        let tmp = TempDir::new("case");
+        TestFont::new("Gotham", "Regular").write(&tmp.join("gotham-regular.ttf"));
+        TestFont::new("Gotham", "Bold").weight(700).write(&tmp.join("gotham-bold.ttf"));
+        TestFont::new("GOTHAM", "Light").weight(300).write(&tmp.join("GOTHAM-LIGHT.ttf"));
+
+        let destinations = organize(tmp.path(), &Config::default());
+        let folders: HashSet<&Path> = destinations.iter().map(|destination| destination.parent().unwrap()).collect();
+        assert_eq!(folders, HashSet::from([Path::new("Gotham")]));
+        assert!(distinct_ignoring_case_on_disk(&destinations));
+    }
+
+    #[test]
+    fn typographic_families_are_reported_as_precedence_changes() {
+        let tmp = TempDir::new("precedence");
+        // ID 1 more specific than ID 16, as for static weights of a family
+        let light = TestFont::new("Roboto Light", "Regular")
+            .name(16, "Roboto")
+            .name(17, "Light")
+            .weight(300)
+            .write(&tmp.join("roboto-light.ttf"));
+        // ID 16 more specific than ID 1, as for a sub-family sold apart
+        let display = TestFont::new("Helvetica", "Bold")
+            .name(16, "Helvetica Neue")
+            .name(17, "Bold")
+            .weight(700)
+            .write(&tmp.join("helvetica-neue.ttf"));
+        TestFont::new("Inter", "Regular").name(16, "Inter").write(&tmp.join("inter.ttf"));
+
+        let report = organize_fonts(tmp.path(), &Config::default(), Arc::default(), Arc::default(), Arc::default()).unwrap();
+        let changes: Vec<(&Path, &str, &str)> = report.precedence_changes.iter()
+            .map(|change| (change.path.as_path(), change.legacy_family.as_str(), change.family.as_str()))
+            .collect();
+        let mut expected = vec![
+            (display.as_path(), "Helvetica", "Helvetica Neue"),
+            (light.as_path(), "Roboto Light", "Roboto"),
+        ];
+        expected.sort();
+        assert_eq!(changes, expected);
+
+        let folders: HashSet<&Path> = report.organized.iter()
+            .map(|font| font.destination.strip_prefix(tmp.path()).unwrap().parent().unwrap())
+            .collect();
+        assert_eq!(folders, HashSet::from([Path::new("Roboto"), Path::new("Helvetica Neue"), Path::new("Inter")]));
+    }
+
+    #[test]
+    fn existing_folder_in_another_case_is_reused() {
+        let tmp = TempDir::new("case");
+        fs::create_dir(tmp.join("gotham")).unwrap();
+        fs::write(tmp.join("gotham/notes.txt"), "kept").unwrap();
+        TestFont::new("Gotham", "Bold").weight(700).write(&tmp.join("gotham-bold.ttf"));
+
+        // A case-sensitive file system would otherwise get a second Gotham folder
+        assert_eq!(organize(tmp.path(), &Config::default()), [PathBuf::from("gotham/Gotham (Bold).ttf")]);
+        assert!(tmp.join("gotham/notes.txt").is_file());
+    }
+
+    #[test]
+    fn folders_differing_only_in_case_are_suffixed() {
+        let config = Config::default();
+        let mut planned = HashMap::new();
+        let library = Path::new("/library");
+        assert_eq!(distinct_ignoring_case(library.join("Gotham"), &mut planned, &config), library.join("Gotham"));
+        assert_eq!(distinct_ignoring_case(library.join("GOTHAM"), &mut planned, &config), library.join("GOTHAM_2"));
+        assert_eq!(distinct_ignoring_case(library.join("gotham"), &mut planned, &config), library.join("gotham_3"));
+        // The same family planned again keeps its folder
+        assert_eq!(distinct_ignoring_case(library.join("Gotham"), &mut planned, &config), library.join("Gotham"));
+
+        let planned: Vec<PathBuf> = planned.into_values().collect();
+        assert!(distinct_ignoring_case_on_disk(&planned));
+    }
+
+    /// Roboto at two weights of normal width and a condensed bold
+    fn roboto_with_condensed(dir: &Path) {
+        TestFont::new("Roboto", "Regular").write(&dir.join("roboto.ttf"));
+        TestFont::new("Roboto", "Bold").weight(700).write(&dir.join("roboto-b.ttf"));
+        TestFont::new("Roboto", "Condensed Bold").weight(700).width(3).write(&dir.join("roboto-cb.ttf"));
+    }
+
+    #[test]
+    fn condensed_faces_get_their_own_weight_folder() {
+        let tmp = TempDir::new("widths");
+        roboto_with_condensed(tmp.path());
+        let destinations = organize(tmp.path(), &weight_folder_config(WeightFolderStyle::Numeric));
+        assert_eq!(destinations, [
+            PathBuf::from("Roboto/400/Roboto-Regular.ttf"),
+            PathBuf::from("Roboto/700/Roboto-Bold.ttf"),
+            PathBuf::from("Roboto/Condensed 700/Roboto-CondensedBold.ttf"),
+        ]);
+    }
+
+    #[test]
+    fn condensed_weight_folders_can_be_textual() {
+        let tmp = TempDir::new("widths");
+        roboto_with_condensed(tmp.path());
+        let destinations = organize(tmp.path(), &weight_folder_config(WeightFolderStyle::Textual));
+        assert_eq!(destinations, [
+            PathBuf::from("Roboto/Bold/Roboto-Bold.ttf"),
+            PathBuf::from("Roboto/Condensed Bold/Roboto-CondensedBold.ttf"),
+            PathBuf::from("Roboto/Regular/Roboto-Regular.ttf"),
+        ]);
+    }
+
+    #[test]
+    fn families_of_different_widths_are_not_similar() {
+        let config = Config::default();
+        assert!(!are_family_names_similar("Roboto", "Roboto Condensed", &config));
+        assert!(!are_family_names_similar("Open Sans Condensed", "Open Sans Expanded", &config));
+        assert!(are_family_names_similar("Roboto Condensed", "Roboto Condensed Pro", &config));
+    }
 }
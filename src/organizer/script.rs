@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::fs;
+use crate::error::Result;
+use crate::models::{Config, FontScript};
+use crate::utils::{
+    ensure_directory_exists,
+    safe_move_directory,
+    clean_name,
+    log,
+};
+use super::{group::first_font_metadata, is_set_aside_folder};
+
+/// Folder for families covering none of the detected scripts
+pub const OTHER_SCRIPT_FOLDER: &str = "Other Scripts";
+
+/// Group font families into top-level folders by their dominant script
+/// (Latin, Greek, Cyrillic, Hebrew, Arabic, Devanagari, Thai, CJK)
+pub fn group_by_script(
+    dir: &Path,
+    config: &Config,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<()> {
+    let script_folder_names: Vec<String> = FontScript::ALL.iter()
+        .map(|script| clean_name(&script.to_string()))
+        .chain([clean_name(OTHER_SCRIPT_FOLDER)])
+        .collect();
+
+    // Determine the dominant script of each family folder from its first readable font
+    let mut family_to_script: Vec<(String, Option<FontScript>)> = Vec::new();
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let family_name = path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        // Skip set-aside folders and folders created by a previous script grouping
+        if is_set_aside_folder(&family_name, config) || script_folder_names.contains(&family_name) {
+            continue;
+        }
+
+        if let Some(metadata) = first_font_metadata(&path, config) {
+            if metadata.scripts.len() > 1 {
+                let scripts: Vec<String> = metadata.scripts.iter().map(|script| script.to_string()).collect();
+                log(config, format!("{} covers {}, grouped under {}", family_name, scripts.join(", "), scripts[0]));
+            }
+            family_to_script.push((family_name, metadata.dominant_script()));
+        }
+    }
+
+    // Move each family folder into its script folder
+    for (family, script) in family_to_script {
+        let folder = script.map(|script| script.to_string()).unwrap_or_else(|| OTHER_SCRIPT_FOLDER.to_string());
+        let family_dir = dir.join(&family);
+        let script_dir = dir.join(clean_name(&folder));
+        ensure_directory_exists(&script_dir, config)?;
+
+        let target_dir = script_dir.join(&family);
+        log(
+            config,
+            format!("Moving {} to {} ({})", family_dir.display(), target_dir.display(), folder),
+        );
+        safe_move_directory(&family_dir, &target_dir, config)?;
+
+        family_folders.lock().unwrap().insert(family, target_dir);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,246 @@
+//! Decision snapshots: every input that shaped an organize run in one JSON
+//! file next to the library, so "why is this library shaped this way" can be
+//! answered later and the run repeated with `--reproduce`
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::models::Config;
+
+/// File name of the snapshot written into an organized library
+pub const SNAPSHOT_FILE: &str = "fontsrt-snapshot.json";
+
+/// Version of the snapshot file layout
+const SNAPSHOT_FORMAT: u64 = 1;
+
+/// Version of the grouping, merging and naming heuristics, including the
+/// built-in vendor, foundry and weight tables. Bump it with any change that
+/// makes the same fonts and settings organize differently; snapshots of
+/// another version are refused by `--reproduce`.
+pub const HEURISTICS_VERSION: u64 = 3;
+
+/// A user file that fed the run, kept verbatim
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInput {
+    /// Where the file was read from
+    #[serde(with = "crate::models::path_serde")]
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// Everything that determined the outcome of an organize run
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DecisionSnapshot {
+    pub format: u64,
+    /// FontSrt version that wrote the snapshot
+    pub tool_version: String,
+    pub heuristics_version: u64,
+    /// Run id shared with the event stream and error report, if there was one
+    pub run_id: Option<String>,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    /// Directory the fonts were read from
+    #[serde(with = "crate::models::path_serde")]
+    pub directory: PathBuf,
+    /// Batch line preset and flags applied on top of the command line, empty
+    /// for single runs
+    pub settings: Vec<String>,
+    /// Effective settings of the run
+    pub config: Config,
+    /// Foundry aliases, foundry map, merge rules and lockfile, keyed by the
+    /// `Config` field naming the file
+    pub inputs: BTreeMap<String, SnapshotInput>,
+}
+
+impl DecisionSnapshot {
+    /// Capture the settings and input files of a run over `directory`
+    pub fn capture(directory: &Path, config: &Config, settings: &[String]) -> Result<Self> {
+        let files = [
+            ("foundry_alias_file", &config.foundry_alias_file),
+            ("foundry_override_file", &config.foundry_override_file),
+            ("merge_rules_file", &config.merge_rules_file),
+            ("lockfile_file", &config.lockfile_file),
+        ];
+        let mut inputs = BTreeMap::new();
+        for (field, path) in files {
+            if let Some(path) = path {
+                inputs.insert(field.to_string(), SnapshotInput {
+                    path: path.clone(),
+                    content: fs::read_to_string(path)?,
+                });
+            }
+        }
+
+        Ok(Self {
+            format: SNAPSHOT_FORMAT,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            heuristics_version: HEURISTICS_VERSION,
+            run_id: config.run_id().map(str::to_string),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0),
+            directory: directory.to_path_buf(),
+            settings: settings.to_vec(),
+            config: config.clone(),
+            inputs,
+        })
+    }
+
+    /// Write the snapshot as pretty-printed JSON
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Config(format!("could not serialize snapshot: {}", e)))?;
+        fs::write(path, json + "\n")?;
+        Ok(())
+    }
+
+    /// Load a snapshot file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let snapshot: Self = serde_json::from_str(&content)
+            .map_err(|e| Error::Config(format!("Invalid snapshot {}: {}", path.display(), e)))?;
+        if snapshot.format != SNAPSHOT_FORMAT {
+            return Err(Error::Config(format!(
+                "Snapshot {} has format {}, this FontSrt reads format {}",
+                path.display(), snapshot.format, SNAPSHOT_FORMAT
+            )));
+        }
+        Ok(snapshot)
+    }
+
+    /// Refuse snapshots written under other heuristics, which would organize
+    /// the same fonts differently
+    pub fn check_compatible(&self) -> Result<()> {
+        if self.heuristics_version != HEURISTICS_VERSION {
+            return Err(Error::Config(format!(
+                "the snapshot was written by FontSrt {} with heuristics version {}, this FontSrt {} uses version {}",
+                self.tool_version, self.heuristics_version, env!("CARGO_PKG_VERSION"), HEURISTICS_VERSION
+            )));
+        }
+        Ok(())
+    }
+
+    /// The configuration of the snapshot with its input files loaded. The
+    /// files are written to `inputs_dir` as recorded, so later edits to the
+    /// originals do not change the outcome.
+    pub fn restore_config(&self, inputs_dir: &Path) -> Result<Config> {
+        let mut config = self.config.clone();
+        if !self.inputs.is_empty() {
+            fs::create_dir_all(inputs_dir)?;
+        }
+
+        for (field, input) in &self.inputs {
+            let name = input.path.file_name().map(PathBuf::from).unwrap_or_else(|| PathBuf::from(field));
+            let path = inputs_dir.join(name);
+            fs::write(&path, &input.content)?;
+            match field.as_str() {
+                "foundry_alias_file" => config.foundry_alias_file = Some(path),
+                "foundry_override_file" => config.foundry_override_file = Some(path),
+                "merge_rules_file" => config.merge_rules_file = Some(path),
+                "lockfile_file" => config.lockfile_file = Some(path),
+                _ => return Err(Error::Config(format!("Unknown snapshot input '{}'", field))),
+            }
+        }
+
+        config.load_foundry_aliases()?;
+        config.load_foundry_override_file()?;
+        config.load_merge_rules()?;
+        config.load_lockfile()?;
+        if let Some(filter) = config.filter.clone() {
+            config.set_font_filter(filter);
+        }
+        Ok(config)
+    }
+}
+
+/// Write the decision snapshot of a run into the organized library `output_dir`.
+/// Dry runs leave no snapshot.
+pub fn write_snapshot(output_dir: &Path, directory: &Path, config: &Config, settings: &[String]) -> Result<Option<PathBuf>> {
+    if config.dry_run {
+        return Ok(None);
+    }
+    let path = output_dir.join(SNAPSHOT_FILE);
+    DecisionSnapshot::capture(directory, config, settings)?.write(&path)?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use crate::models::{DuplicatePolicy, NamingPattern};
+    use crate::testing::TempDir;
+
+    fn approved_families(config: &Config) -> Vec<String> {
+        config.lockfile.as_ref().unwrap().families.iter().map(|family| family.name.clone()).collect()
+    }
+
+    /// A run with a lockfile and non-default settings, snapshotted into `tmp`
+    fn snapshot_run(tmp: &TempDir) -> (Config, PathBuf) {
+        let lockfile = tmp.join("fonts.lock.toml");
+        fs::write(&lockfile, "[families]\n\"Inter\" = {}\n").unwrap();
+        let mut config = Config {
+            naming_pattern: NamingPattern::Custom("%Family%-%Weight%".to_string()),
+            duplicate_policy: DuplicatePolicy::KeepAll,
+            lockfile_file: Some(lockfile),
+            ..Config::default()
+        };
+        config.load_lockfile().unwrap();
+
+        let settings = ["@quick".to_string()];
+        let path = write_snapshot(tmp.path(), &tmp.join("inbox"), &config, &settings).unwrap().unwrap();
+        (config, path)
+    }
+
+    #[test]
+    fn round_trip_survives_edits_to_the_inputs() {
+        let tmp = TempDir::new("snapshot");
+        let (config, path) = snapshot_run(&tmp);
+        // The original lockfile changes after the run
+        fs::write(config.lockfile_file.as_ref().unwrap(), "[families]\n\"Roboto\" = {}\n").unwrap();
+
+        let snapshot = DecisionSnapshot::load(&path).unwrap();
+        snapshot.check_compatible().unwrap();
+        assert_eq!(snapshot.directory, tmp.join("inbox"));
+        assert_eq!(snapshot.settings, ["@quick"]);
+        assert_eq!(snapshot.tool_version, env!("CARGO_PKG_VERSION"));
+
+        let restored = snapshot.restore_config(&tmp.join("inputs")).unwrap();
+        assert_eq!(restored.naming_pattern, config.naming_pattern);
+        assert_eq!(restored.duplicate_policy, DuplicatePolicy::KeepAll);
+        assert_eq!(restored.lockfile_file.as_deref(), Some(tmp.join("inputs/fonts.lock.toml").as_path()));
+        assert_eq!(approved_families(&restored), ["Inter"]);
+    }
+
+    #[test]
+    fn other_heuristics_are_refused() {
+        let tmp = TempDir::new("snapshot");
+        let (_, path) = snapshot_run(&tmp);
+        let mut snapshot: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        snapshot["heuristics_version"] = Value::from(HEURISTICS_VERSION - 1);
+        fs::write(&path, snapshot.to_string()).unwrap();
+
+        let error = DecisionSnapshot::load(&path).unwrap().check_compatible().unwrap_err().to_string();
+        assert!(error.contains(&format!("heuristics version {}", HEURISTICS_VERSION - 1)), "{}", error);
+    }
+
+    #[test]
+    fn other_formats_are_refused() {
+        let tmp = TempDir::new("snapshot");
+        let (_, path) = snapshot_run(&tmp);
+        let mut snapshot: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        snapshot["format"] = Value::from(SNAPSHOT_FORMAT + 1);
+        fs::write(&path, snapshot.to_string()).unwrap();
+
+        assert!(DecisionSnapshot::load(&path).is_err());
+    }
+
+    #[test]
+    fn dry_runs_leave_no_snapshot() {
+        let tmp = TempDir::new("snapshot");
+        let config = Config { dry_run: true, ..Config::default() };
+        assert_eq!(write_snapshot(tmp.path(), tmp.path(), &config, &[]).unwrap(), None);
+        assert!(!tmp.join(SNAPSHOT_FILE).exists());
+    }
+}
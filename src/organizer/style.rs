@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::fs;
+use crate::error::Result;
+use crate::models::{Config, FontStyleClass};
+use crate::utils::{
+    ensure_directory_exists,
+    safe_move_directory,
+    clean_name,
+    log,
+};
+use super::{group::first_font_metadata, is_set_aside_folder};
+
+/// Group font families into top-level folders by their PANOSE style class
+pub fn group_by_style(
+    dir: &Path,
+    config: &Config,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<()> {
+    let style_folder_names: Vec<String> = FontStyleClass::ALL.iter()
+        .map(|class| clean_name(&class.to_string()))
+        .collect();
+
+    // Determine the style class of each family folder from its first readable font
+    let mut family_to_style: Vec<(String, FontStyleClass)> = Vec::new();
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let family_name = path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        // Skip set-aside folders and folders created by a previous style grouping
+        if is_set_aside_folder(&family_name, config) || style_folder_names.contains(&family_name) {
+            continue;
+        }
+
+        if let Some(metadata) = first_font_metadata(&path, config) {
+            family_to_style.push((family_name, metadata.style_class));
+        }
+    }
+
+    // Move each family folder into its style folder
+    for (family, style_class) in family_to_style {
+        let family_dir = dir.join(&family);
+        let style_dir = dir.join(clean_name(&style_class.to_string()));
+        ensure_directory_exists(&style_dir, config)?;
+
+        let target_dir = style_dir.join(&family);
+        log(
+            config,
+            format!("Moving {} to {} ({})", family_dir.display(), target_dir.display(), style_class),
+        );
+        safe_move_directory(&family_dir, &target_dir, config)?;
+
+        family_folders.lock().unwrap().insert(family, target_dir);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::fs;
+use crate::error::Result;
+use crate::models::{Config, FontTechnology};
+use crate::utils::{
+    ensure_directory_exists,
+    safe_move_directory,
+    clean_name,
+    log,
+};
+use super::{group::first_font_metadata, is_set_aside_folder};
+
+/// Group font families into top-level folders by their technology generation
+/// (Legacy TrueType, OpenType, OpenType CFF, Variable, Color)
+pub fn group_by_technology(
+    dir: &Path,
+    config: &Config,
+    family_folders: Arc<Mutex<HashMap<String, PathBuf>>>,
+) -> Result<()> {
+    let technology_folder_names: Vec<String> = FontTechnology::ALL.iter()
+        .map(|technology| clean_name(&technology.to_string()))
+        .collect();
+
+    // Determine the technology of each family folder from its first readable font
+    let mut family_to_technology: Vec<(String, FontTechnology)> = Vec::new();
+
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let family_name = path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+
+        // Skip set-aside folders and folders created by a previous technology grouping
+        if is_set_aside_folder(&family_name, config) || technology_folder_names.contains(&family_name) {
+            continue;
+        }
+
+        if let Some(metadata) = first_font_metadata(&path, config) {
+            family_to_technology.push((family_name, metadata.technology));
+        }
+    }
+
+    // Move each family folder into its technology folder
+    for (family, technology) in family_to_technology {
+        let family_dir = dir.join(&family);
+        let technology_dir = dir.join(clean_name(&technology.to_string()));
+        ensure_directory_exists(&technology_dir, config)?;
+
+        let target_dir = technology_dir.join(&family);
+        log(
+            config,
+            format!("Moving {} to {} ({})", family_dir.display(), target_dir.display(), technology),
+        );
+        safe_move_directory(&family_dir, &target_dir, config)?;
+
+        family_folders.lock().unwrap().insert(family, target_dir);
+    }
+
+    Ok(())
+}
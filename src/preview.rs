@@ -0,0 +1,156 @@
+//! Self-contained HTML index of an organized library (`--generate-preview`)
+//!
+//! The page is built from the `OrganizeReport` of the run, so nothing is
+//! rescanned and a dry run previews the planned structure.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::error::Result;
+use crate::models::{OrganizeReport, OrganizedFont};
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 60rem; padding: 0 1rem; color: #222; }
+h1 { margin-bottom: 0.2rem; }
+.summary { color: #666; margin-top: 0; }
+#search { width: 100%; font-size: 1rem; padding: 0.5rem; margin: 1rem 0; box-sizing: border-box; }
+.foundries { columns: 3; padding-left: 1.2rem; }
+.count { color: #888; font-weight: normal; font-size: 0.9em; }
+details { border-top: 1px solid #ddd; padding: 0.3rem 0; }
+summary { cursor: pointer; font-weight: 600; }
+table { border-collapse: collapse; margin: 0.4rem 0 0.4rem 1.2rem; }
+td, th { text-align: left; padding: 0.1rem 1rem 0.1rem 0; }
+th { color: #666; font-weight: normal; }
+.file { font-family: ui-monospace, monospace; font-size: 0.85em; color: #555; }
+";
+
+const SCRIPT: &str = "\
+document.getElementById('search').addEventListener('input', function () {
+  var query = this.value.trim().toLowerCase();
+  document.querySelectorAll('section').forEach(function (section) {
+    var visible = 0;
+    section.querySelectorAll('details').forEach(function (family) {
+      var match = !query || family.dataset.search.indexOf(query) !== -1;
+      family.hidden = !match;
+      family.open = match && query.length > 0;
+      if (match) { visible++; }
+    });
+    section.hidden = visible === 0;
+  });
+});
+";
+
+/// Fonts of one family, with the foundry of its first font
+struct FamilyEntry<'a> {
+    foundry: &'a str,
+    fonts: Vec<&'a OrganizedFont>,
+}
+
+/// Write the HTML index of the library `report` describes. With
+/// `foundry_grouping` families are listed under their foundry, as the run
+/// grouped them; otherwise in one list with the foundry counts above it.
+pub fn write_preview(path: &Path, report: &OrganizeReport, library: &Path, foundry_grouping: bool) -> Result<()> {
+    fs::write(path, render_preview(report, library, foundry_grouping))?;
+    Ok(())
+}
+
+/// The HTML index of the library `report` describes
+pub fn render_preview(report: &OrganizeReport, library: &Path, foundry_grouping: bool) -> String {
+    let mut families: BTreeMap<&str, FamilyEntry> = BTreeMap::new();
+    for font in &report.organized {
+        families.entry(&font.family)
+            .or_insert_with(|| FamilyEntry { foundry: &font.foundry, fonts: Vec::new() })
+            .fonts.push(font);
+    }
+    for family in families.values_mut() {
+        family.fonts.sort_by(|a, b| (a.weight, a.is_italic, &a.subfamily).cmp(&(b.weight, b.is_italic, &b.subfamily)));
+    }
+
+    let mut foundries: BTreeMap<&str, Vec<(&str, &FamilyEntry)>> = BTreeMap::new();
+    for (name, family) in &families {
+        foundries.entry(family.foundry).or_default().push((name, family));
+    }
+
+    let title = escape(&library.display().to_string());
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>Font library {}</title>\n<style>\n{}</style>\n</head>\n<body>\n", title, STYLE));
+    html.push_str(&format!("<h1>{}</h1>\n", title));
+    html.push_str(&format!(
+        "<p class=\"summary\">{} fonts in {} families from {} foundries{}</p>\n",
+        report.organized.len(),
+        families.len(),
+        foundries.len(),
+        if report.dry_run { " (dry run: the planned structure)" } else { "" }
+    ));
+    html.push_str("<input id=\"search\" type=\"search\" placeholder=\"Search families, styles and file names\">\n");
+
+    html.push_str("<ul class=\"foundries\">\n");
+    for (foundry, members) in &foundries {
+        html.push_str(&format!("<li>{} {}</li>\n", escape(foundry), count_label(members)));
+    }
+    html.push_str("</ul>\n");
+
+    if foundry_grouping {
+        for (foundry, members) in &foundries {
+            html.push_str(&format!("<section>\n<h2>{} {}</h2>\n", escape(foundry), count_label(members)));
+            for (name, family) in members {
+                push_family(&mut html, name, family);
+            }
+            html.push_str("</section>\n");
+        }
+    } else {
+        html.push_str("<section>\n");
+        for (name, family) in &families {
+            push_family(&mut html, name, family);
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str(&format!("<script>\n{}</script>\n</body>\n</html>\n", SCRIPT));
+    html
+}
+
+/// "(2 families, 9 fonts)"
+fn count_label(members: &[(&str, &FamilyEntry)]) -> String {
+    let fonts: usize = members.iter().map(|(_, family)| family.fonts.len()).sum();
+    format!(
+        "<span class=\"count\">({} {}, {} {})</span>",
+        members.len(), if members.len() == 1 { "family" } else { "families" },
+        fonts, if fonts == 1 { "font" } else { "fonts" }
+    )
+}
+
+/// A collapsible family section with a row per font
+fn push_family(html: &mut String, name: &str, family: &FamilyEntry) {
+    let file_name = |font: &OrganizedFont| font.destination.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let search: Vec<String> = std::iter::once(name.to_string())
+        .chain(family.fonts.iter().flat_map(|font| [font.subfamily.clone(), file_name(font)]))
+        .collect();
+
+    html.push_str(&format!(
+        "<details data-search=\"{}\"><summary>{} <span class=\"count\">{} {}</span></summary>\n",
+        escape(&search.join(" ").to_lowercase()),
+        escape(name),
+        family.fonts.len(),
+        if family.fonts.len() == 1 { "font" } else { "fonts" }
+    ));
+    html.push_str("<table>\n<tr><th>Style</th><th>Weight</th><th>File</th></tr>\n");
+    for font in &family.fonts {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td class=\"file\">{}</td></tr>\n",
+            escape(&font.subfamily),
+            font.weight,
+            escape(&file_name(font))
+        ));
+    }
+    html.push_str("</table>\n</details>\n");
+}
+
+/// Escape text for HTML content and attribute values
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
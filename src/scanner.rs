@@ -0,0 +1,125 @@
+//! Builder-style entry point for using FontSrt as a library
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use rayon::prelude::*;
+use crate::error::{Error, Result};
+use crate::font::metadata::extract_faces_metadata;
+use crate::models::{Config, FontCollection, NamingPattern, OrganizeReport, ProgressEvent};
+use crate::organizer::{collect_font_candidates, organize_fonts};
+
+/// Scans a directory for fonts and organizes them.
+///
+/// ```no_run
+/// use fontsrt::FontScanner;
+///
+/// let collection = FontScanner::new()
+///     .with_directory("/home/me/Fonts".into())
+///     .recursive(true)
+///     .scan()?;
+/// println!("{} families", collection.families().len());
+/// # Ok::<(), fontsrt::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct FontScanner {
+    config: Config,
+    directory: Option<PathBuf>,
+    recursive: Option<bool>,
+    dry_run: Option<bool>,
+}
+
+impl Default for FontScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FontScanner {
+    /// Create a scanner with the default configuration
+    pub fn new() -> Self {
+        Self {
+            config: Config::new(false, NamingPattern::FamilySubfamily),
+            directory: None,
+            recursive: None,
+            dry_run: None,
+        }
+    }
+
+    /// Use this configuration for naming, grouping and logging
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Scan and organize this directory
+    pub fn with_directory(mut self, directory: PathBuf) -> Self {
+        self.directory = Some(directory);
+        self
+    }
+
+    /// Also pick up fonts in subdirectories, overriding the configuration
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = Some(recursive);
+        self
+    }
+
+    /// Report moves without performing them, overriding the configuration
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = Some(dry_run);
+        self
+    }
+
+    /// Receive progress events while organizing, e.g. to drive a progress bar
+    pub fn on_progress(mut self, callback: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        self.config.progress_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// The configuration a scan or organize run uses
+    pub fn config(&self) -> Config {
+        let mut config = self.config.clone();
+        if let Some(recursive) = self.recursive {
+            config.recursive = recursive;
+        }
+        if let Some(dry_run) = self.dry_run {
+            config.dry_run = dry_run;
+        }
+        config
+    }
+
+    fn directory(&self) -> Result<&PathBuf> {
+        let directory = self.directory.as_ref()
+            .ok_or_else(|| Error::Config("FontScanner has no directory, call with_directory".to_string()))?;
+        if !directory.is_dir() {
+            return Err(Error::InvalidPath(directory.clone()));
+        }
+        Ok(directory)
+    }
+
+    /// Discover fonts and read their metadata without moving anything. Each
+    /// face of a collection is a font of its own.
+    pub fn scan(&self) -> Result<FontCollection> {
+        let config = self.config();
+        let candidates = collect_font_candidates(self.directory()?, &config)?;
+
+        let fonts = candidates.par_iter()
+            .flat_map_iter(|path| extract_faces_metadata(path, &config).unwrap_or_default())
+            .collect();
+
+        Ok(FontCollection::new(fonts))
+    }
+
+    /// Run the full organizing pipeline on the directory
+    pub fn organize(&self) -> Result<OrganizeReport> {
+        let config = self.config();
+
+        organize_fonts(
+            self.directory()?,
+            &config,
+            Arc::new(Mutex::new(HashSet::new())),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+    }
+}
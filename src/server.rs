@@ -0,0 +1,497 @@
+//! Local JSON-RPC server exposing the organize/group operations
+//!
+//! Requests and responses are newline-delimited JSON-RPC 2.0 objects exchanged
+//! over a Unix domain socket or a localhost TCP port. There is no
+//! authentication beyond the filesystem permissions of the socket.
+//!
+//! Methods:
+//!
+//! - `info` `{path}`: metadata of one font
+//! - `plan` `{directory}`: the `OrganizePlan` of the directory, nothing moved
+//! - `apply` `{plan}`: carry out a plan returned by `plan`, returning its `OrganizeReport`
+//! - `report` `{directory}`: the `OrganizeReport` of the last run applied to the directory
+//! - `dedupe` `{directory, duplicate_policy?}`: set duplicate copies aside, nothing else
+//! - `organize` `{directory, group_by_foundry?}`: plan and apply in one call
+//! - `group` `{directory}`: group the family folders by foundry
+//! - `subscribe`: send the `progress` notifications of every request to this connection
+//! - `cancel` `{request_id}`: stop a running request at the next family
+//!
+//! `naming_pattern` overrides the server's naming pattern for one request.
+//! A `progress` notification carries the id of the request and one event of
+//! its `--json-events` stream, e.g. `moved`. Mutating methods lock the
+//! library (`.fontsrt.lock`), so the command-line tool and other servers
+//! never change it at the same time.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use serde_json::{json, Value};
+use crate::error::{Error, Result};
+use crate::models::{Config, DuplicatePolicy, NamingPattern, OrganizePlan};
+use crate::font::metadata::extract_font_metadata;
+use crate::organizer::{error_report_json, execute_plan, organize_fonts, group_by_foundry, plan_deduplicate, plan_organize};
+use crate::utils::events::EventStream;
+use crate::utils::lock::LibraryLock;
+use crate::utils::log;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const OPERATION_FAILED: i64 = -32000;
+const DIRECTORY_BUSY: i64 = -32001;
+const REQUEST_CANCELLED: i64 = -32002;
+
+/// Write half of a client connection, shared by the request threads
+type Connection = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Error returned by a request handler, mapped to a JSON-RPC error object
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(e: Error) -> Self {
+        let code = match e {
+            Error::Locked(_) => DIRECTORY_BUSY,
+            Error::Cancelled(_) => REQUEST_CANCELLED,
+            _ => OPERATION_FAILED,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+type RpcResult = std::result::Result<Value, RpcError>;
+
+/// State shared by every connection of a server
+struct Server {
+    config: Config,
+    /// Connections that asked for progress notifications
+    subscribers: Arc<Mutex<Vec<Connection>>>,
+    /// Cancellation flags of the running requests, by request id
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    /// Report of the last run applied to each directory
+    reports: Mutex<HashMap<PathBuf, Value>>,
+}
+
+/// Start serving requests on `endpoint` until the process is terminated.
+///
+/// A purely numeric endpoint is treated as a TCP port bound to 127.0.0.1;
+/// anything else is treated as the path of a Unix domain socket.
+pub fn serve(endpoint: &str, config: &Config) -> Result<()> {
+    let server = Arc::new(Server {
+        config: config.clone(),
+        subscribers: Arc::default(),
+        running: Mutex::default(),
+        reports: Mutex::default(),
+    });
+
+    if let Ok(port) = endpoint.parse::<u16>() {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        println!("Listening on 127.0.0.1:{}", port);
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let reader = stream.try_clone()?;
+            spawn_connection(reader, stream, server.clone());
+        }
+        return Ok(());
+    }
+
+    serve_unix(Path::new(endpoint), server)
+}
+
+#[cfg(unix)]
+fn serve_unix(socket_path: &Path, server: Arc<Server>) -> Result<()> {
+    use std::os::unix::net::UnixListener;
+
+    // A stale socket left behind by a previous run would make bind fail
+    if socket_path.exists() {
+        std::fs::remove_file(socket_path)?;
+    }
+
+    let listener = UnixListener::bind(socket_path)?;
+    println!("Listening on {}", socket_path.display());
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let reader = stream.try_clone()?;
+        spawn_connection(reader, stream, server.clone());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve_unix(socket_path: &Path, _server: Arc<Server>) -> Result<()> {
+    Err(Error::Config(format!(
+        "Unix sockets are not supported on this platform, use a TCP port instead of {}",
+        socket_path.display()
+    )))
+}
+
+/// Handle one client connection on its own thread.
+///
+/// Every request runs on a separate thread so a long organize call does not
+/// block other requests from the same client, e.g. the `cancel` of it;
+/// responses are written whole lines at a time behind a shared lock.
+fn spawn_connection<R, W>(reader: R, writer: W, server: Arc<Server>)
+where
+    R: io::Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    let connection: Connection = Arc::new(Mutex::new(Box::new(writer)));
+
+    thread::spawn(move || {
+        let mut workers = Vec::new();
+
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    log(&server.config, format!("Connection read error: {}", e));
+                    break;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let server = server.clone();
+            let connection = connection.clone();
+            workers.push(thread::spawn(move || {
+                let response = server.handle_line(&line, &connection);
+                if send(&connection, &response).is_err() {
+                    log(&server.config, "Client disconnected before response was sent".to_string());
+                }
+            }));
+        }
+
+        for worker in workers {
+            let _ = worker.join();
+        }
+        server.subscribers.lock().unwrap().retain(|subscriber| !Arc::ptr_eq(subscriber, &connection));
+    });
+}
+
+/// Write one message line to a connection
+fn send(connection: &Connection, message: &Value) -> io::Result<()> {
+    let mut writer = connection.lock().unwrap();
+    writeln!(writer, "{}", message).and_then(|_| writer.flush())
+}
+
+impl Server {
+    /// Parse a single request line and produce the response line
+    fn handle_line(&self, line: &str, connection: &Connection) -> Value {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(value) => value,
+            Err(e) => return error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+        };
+
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None => return error_response(id, RpcError::new(INVALID_REQUEST, "missing method")),
+        };
+        let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+
+        log(&self.config, format!("RPC request {}: {}", id, method));
+
+        let result = match method {
+            "info" => self.handle_info(&params),
+            "plan" => self.handle_plan(&id, &params),
+            "apply" => self.handle_apply(&id, &params),
+            "report" => self.handle_report(&params),
+            "dedupe" => self.handle_dedupe(&id, &params),
+            "organize" => self.handle_organize(&id, &params),
+            "group" => self.handle_group(&id, &params),
+            "subscribe" => self.handle_subscribe(connection),
+            "cancel" => self.handle_cancel(&params),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => error_response(id, err),
+        }
+    }
+
+    /// Apply the optional per-request overrides on top of the server configuration
+    fn request_config(&self, params: &Value) -> std::result::Result<Config, RpcError> {
+        let mut config = self.config.clone();
+
+        if let Some(pattern) = params.get("naming_pattern").and_then(Value::as_str) {
+            config.naming_pattern = NamingPattern::from_name(pattern)
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("unknown naming pattern '{}'", pattern)))?;
+        }
+
+        Ok(config)
+    }
+
+    /// Run a long operation of request `id`: its events go to the subscribers,
+    /// `cancel` can stop it, and with a `library` it holds the library lock.
+    /// An id already used by a running request is refused.
+    fn run<F>(&self, id: &Value, mut config: Config, library: Option<&Path>, operation: F) -> RpcResult
+    where
+        F: FnOnce(Config) -> Result<Value>,
+    {
+        let key = id.to_string();
+        let cancel = Arc::new(AtomicBool::new(false));
+        config.cancel = Some(cancel.clone());
+        // A request without an id cannot be cancelled, so it is not registered
+        if !id.is_null() {
+            match self.running.lock().unwrap().entry(key.clone()) {
+                Entry::Occupied(_) => {
+                    return Err(RpcError::new(INVALID_REQUEST, format!("request id {} is already in use by a running request", id)));
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(cancel);
+                }
+            }
+        }
+
+        let lock = match library {
+            Some(dir) if !dir.is_dir() => Err(RpcError::new(INVALID_PARAMS, Error::InvalidPath(dir.to_path_buf()).to_string())),
+            Some(dir) => LibraryLock::acquire(dir).map(Some).map_err(RpcError::from),
+            None => Ok(None),
+        };
+        let _lock = match lock {
+            Ok(lock) => lock,
+            Err(e) => {
+                self.running.lock().unwrap().remove(&key);
+                return Err(e);
+            }
+        };
+
+        let events = Arc::new(EventStream::with_writer(Box::new(ProgressSink {
+            request_id: id.clone(),
+            subscribers: self.subscribers.clone(),
+            line: Vec::new(),
+        })));
+        config.events = Some(events.clone());
+
+        let result = operation(config);
+        events.finish();
+        self.running.lock().unwrap().remove(&key);
+        result.map_err(RpcError::from)
+    }
+
+    /// Remember the report of a run applied to `dir` for `report`
+    fn remember_report(&self, dir: &Path, report: Value) {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        self.reports.lock().unwrap().insert(dir, report);
+    }
+
+    fn handle_info(&self, params: &Value) -> RpcResult {
+        let path = path_param(params, "path")?;
+
+        match extract_font_metadata(&path, &self.config) {
+            Ok(Some(metadata)) => Ok(json!({
+                "path": path.display().to_string(),
+                "family_name": metadata.family_name,
+                "subfamily": metadata.subfamily,
+                "full_name": metadata.full_name,
+                "foundry": metadata.foundry,
+                "weight": metadata.weight,
+                "is_italic": metadata.is_italic,
+                "named_instances": metadata.named_instances.iter()
+                    .map(|instance| json!({
+                        "name": instance.name,
+                        "postscript_name": instance.postscript_name,
+                        "coordinates": instance.coordinates.iter()
+                            .map(|(tag, value)| (tag.clone(), json!(value)))
+                            .collect::<serde_json::Map<_, _>>(),
+                    }))
+                    .collect::<Vec<_>>(),
+            })),
+            Ok(None) => Err(RpcError::new(OPERATION_FAILED, format!("{} is not a valid font file", path.display()))),
+            Err(e) => Err(RpcError::new(OPERATION_FAILED, e.to_string())),
+        }
+    }
+
+    fn handle_plan(&self, id: &Value, params: &Value) -> RpcResult {
+        let dir = path_param(params, "directory")?;
+        if !dir.is_dir() {
+            return Err(RpcError::new(INVALID_PARAMS, Error::InvalidPath(dir).to_string()));
+        }
+        let config = self.request_config(params)?;
+
+        self.run(id, config, None, |config| to_json(&plan_organize(&dir, &config)?))
+    }
+
+    fn handle_apply(&self, id: &Value, params: &Value) -> RpcResult {
+        let plan: OrganizePlan = params.get("plan")
+            .cloned()
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing parameter 'plan'"))
+            .and_then(|plan| serde_json::from_value(plan).map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid plan: {}", e))))?;
+        let config = self.request_config(params)?;
+
+        let output_root = plan.output_root.clone();
+        self.run(id, config, Some(&output_root), |config| {
+            let report = to_json(&execute_plan(&plan, &config)?)?;
+            self.remember_report(&plan.dir, report.clone());
+            Ok(report)
+        })
+    }
+
+    fn handle_report(&self, params: &Value) -> RpcResult {
+        let dir = path_param(params, "directory")?;
+        let key = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+        self.reports.lock().unwrap().get(&key).cloned().ok_or_else(|| {
+            RpcError::new(OPERATION_FAILED, format!("no run was applied to {} since the server started", dir.display()))
+        })
+    }
+
+    fn handle_dedupe(&self, id: &Value, params: &Value) -> RpcResult {
+        let dir = path_param(params, "directory")?;
+        let mut config = self.request_config(params)?;
+        // Keeping every copy would leave nothing to do
+        config.duplicate_policy = match params.get("duplicate_policy").and_then(Value::as_str) {
+            Some(policy) => DuplicatePolicy::from_name(policy)
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("unknown duplicate policy '{}'", policy)))?,
+            None if config.duplicate_policy == DuplicatePolicy::KeepAll => DuplicatePolicy::KeepBest,
+            None => config.duplicate_policy,
+        };
+        let output_root = config.output_dir.clone().unwrap_or_else(|| dir.clone());
+
+        self.run(id, config, Some(&output_root), |config| {
+            let report = execute_plan(&plan_deduplicate(&dir, &config)?, &config)?;
+            Ok(json!({
+                "directory": dir.display().to_string(),
+                "duplicates_dir": report.duplicates_dir.display().to_string(),
+                "duplicates": report.duplicates,
+                "duplicate_bytes": report.duplicate_bytes,
+                "demoted": report.demoted,
+                "errors": error_report_json(&report.errors, config.run_id()),
+            }))
+        })
+    }
+
+    fn handle_organize(&self, id: &Value, params: &Value) -> RpcResult {
+        let dir = path_param(params, "directory")?;
+        let mut config = self.request_config(params)?;
+        let then_group = params.get("group_by_foundry").and_then(Value::as_bool).unwrap_or(false);
+        // Grouping afterwards reuses the foundries found while organizing
+        config.family_foundries = Some(Arc::default());
+        let output_root = config.output_dir.clone().unwrap_or_else(|| dir.clone());
+
+        self.run(id, config, Some(&output_root), |mut config| {
+            let processed_files = Arc::new(Mutex::new(HashSet::new()));
+            let family_folders = Arc::new(Mutex::new(HashMap::new()));
+            let foundry_folders = Arc::new(Mutex::new(HashMap::new()));
+
+            let result = organize_fonts(&dir, &config, processed_files.clone(), family_folders.clone(), foundry_folders.clone())?;
+            self.remember_report(&dir, to_json(&result)?);
+
+            if then_group {
+                config.group_by_foundry = true;
+                group_by_foundry(&dir, &config, processed_files, family_folders, foundry_folders)?;
+            }
+
+            Ok(json!({
+                "directory": dir.display().to_string(),
+                "grouped_by_foundry": then_group,
+                "errors": error_report_json(&result.errors, config.run_id()),
+            }))
+        })
+    }
+
+    fn handle_group(&self, id: &Value, params: &Value) -> RpcResult {
+        let dir = path_param(params, "directory")?;
+        let mut config = self.request_config(params)?;
+        config.group_by_foundry = true;
+
+        self.run(id, config, Some(&dir), |config| {
+            group_by_foundry(
+                &dir,
+                &config,
+                Arc::new(Mutex::new(HashSet::new())),
+                Arc::new(Mutex::new(HashMap::new())),
+                Arc::new(Mutex::new(HashMap::new())),
+            )?;
+
+            Ok(json!({ "directory": dir.display().to_string() }))
+        })
+    }
+
+    fn handle_subscribe(&self, connection: &Connection) -> RpcResult {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if !subscribers.iter().any(|subscriber| Arc::ptr_eq(subscriber, connection)) {
+            subscribers.push(connection.clone());
+        }
+        Ok(json!({ "subscribed": true }))
+    }
+
+    fn handle_cancel(&self, params: &Value) -> RpcResult {
+        let request_id = params.get("request_id")
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing parameter 'request_id'"))?;
+        let cancel = self.running.lock().unwrap().get(&request_id.to_string()).cloned();
+        if let Some(cancel) = &cancel {
+            cancel.store(true, Ordering::SeqCst);
+        }
+        Ok(json!({ "request_id": request_id, "cancelled": cancel.is_some() }))
+    }
+}
+
+/// Event stream writer of one request, sending each event line to the
+/// subscribed connections as a `progress` notification
+struct ProgressSink {
+    request_id: Value,
+    subscribers: Arc<Mutex<Vec<Connection>>>,
+    /// Event line written so far
+    line: Vec<u8>,
+}
+
+impl Write for ProgressSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        while let Some(end) = self.line.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.line.drain(..=end).collect();
+            let Ok(event) = serde_json::from_slice::<Value>(&line) else { continue };
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "progress",
+                "params": { "request_id": self.request_id, "event": event },
+            });
+            // Connections that went away stop receiving notifications
+            self.subscribers.lock().unwrap().retain(|subscriber| send(subscriber, &notification).is_ok());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": err.code, "message": err.message },
+    })
+}
+
+/// Read a required string parameter as a path
+fn path_param(params: &Value, name: &str) -> std::result::Result<PathBuf, RpcError> {
+    params.get(name)
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing string parameter '{}'", name)))
+}
+
+/// Serialize a plan or report as a result
+fn to_json<T: serde::Serialize>(value: &T) -> Result<Value> {
+    serde_json::to_value(value).map_err(|e| Error::Io(e.into()))
+}
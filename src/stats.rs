@@ -0,0 +1,165 @@
+//! Statistics of an organized library, printed after a run with `--stats`
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use ttf_parser::Face;
+use crate::analysis::{family_font_files, scan_family_sizes};
+use crate::models::OrganizeResult;
+use crate::utils::format_size;
+
+/// Number of families listed by file count
+const LARGEST_FAMILIES: usize = 10;
+
+/// Statistics of an organize run and the library it left behind
+#[derive(Debug, Clone, Default)]
+pub struct FontStats {
+    /// Fonts moved (or, in a dry run, that would be moved) into family folders
+    pub files_moved: usize,
+    /// Fonts read but not moved into a family folder: already in place, set
+    /// aside as duplicates, or failed
+    pub files_skipped: usize,
+    /// Family folders fonts were organized into during the run
+    pub family_folders_created: usize,
+    /// Family folders in the library
+    pub family_folders: usize,
+    /// Folders families are nested in below the library root: foundries, or
+    /// style classes and designers when grouping by those
+    pub foundry_folders: usize,
+    /// Size of the family folders plus the byte-identical copies set aside
+    pub bytes_before: u64,
+    /// Size of the family folders
+    pub bytes_after: u64,
+    /// Number of fonts per OS/2 weight class
+    pub weight_histogram: BTreeMap<u16, usize>,
+    /// Families with the most fonts and their font counts, largest first
+    pub largest_families: Vec<(String, usize)>,
+    /// Families holding a single font, sorted
+    pub single_font_families: Vec<String>,
+}
+
+impl FontStats {
+    /// Bytes saved by setting aside byte-identical duplicates
+    pub fn deduplication_savings(&self) -> u64 {
+        self.bytes_before - self.bytes_after
+    }
+}
+
+/// Compute statistics of an organize run over the library at `dir`.
+///
+/// Family folders are found on disk, so folders nested by foundry, style or
+/// designer grouping after the run are counted too. Set-aside folders
+/// (duplicates, synthetic, corrupt, _Unapproved, _skipped) are not part of the library.
+pub fn compute_statistics(result: &OrganizeResult, dir: &Path) -> FontStats {
+    let set_aside: [PathBuf; 5] = [
+        result.duplicates_dir.clone(),
+        result.unapproved_dir.clone(),
+        result.filtered_dir.clone(),
+        result.corrupt_dir.clone(),
+        result.corrupt_dir.with_file_name("synthetic"),
+    ];
+    let families: Vec<_> = scan_family_sizes(dir).into_iter()
+        .filter(|family| !set_aside.iter().any(|folder| family.family_dir.starts_with(folder)))
+        .collect();
+
+    let mut weight_histogram: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut font_counts: Vec<(String, usize)> = Vec::new();
+    for family in &families {
+        let weights: Vec<u16> = family_font_files(&family.family_dir).iter()
+            .filter_map(|path| read_weight_class(path))
+            .collect();
+        for weight in &weights {
+            *weight_histogram.entry(*weight).or_default() += 1;
+        }
+        font_counts.push((family.family_name.clone(), weights.len()));
+    }
+
+    let single_font_families: Vec<String> = font_counts.iter()
+        .filter(|(_, fonts)| *fonts == 1)
+        .map(|(name, _)| name.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    font_counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    font_counts.truncate(LARGEST_FAMILIES);
+
+    let bytes_after: u64 = families.iter().map(|family| family.bytes).sum();
+    FontStats {
+        files_moved: result.files_moved,
+        files_skipped: result.fonts_processed.saturating_sub(result.files_moved),
+        family_folders_created: result.families_created(),
+        family_folders: families.len(),
+        foundry_folders: families.iter().filter_map(|family| family.group.as_ref()).collect::<BTreeSet<_>>().len(),
+        bytes_before: bytes_after + result.duplicate_bytes,
+        bytes_after,
+        weight_histogram,
+        largest_families: font_counts,
+        single_font_families,
+    }
+}
+
+/// OS/2 weight class of a font file, None for files that are not fonts
+fn read_weight_class(path: &Path) -> Option<u16> {
+    let data = fs::read(path).ok()?;
+    let face = Face::parse(&data, 0).ok()?;
+    Some(face.weight().to_number())
+}
+
+/// Print the statistics as a table
+pub fn print_statistics(stats: &FontStats) {
+    println!("Statistics:");
+    println!("  {:<24}{}", "Files moved", stats.files_moved);
+    println!("  {:<24}{}", "Files skipped", stats.files_skipped);
+    println!("  {:<24}{}", "Family folders created", stats.family_folders_created);
+    println!("  {:<24}{}", "Family folders", stats.family_folders);
+    println!("  {:<24}{}", "Foundry folders", stats.foundry_folders);
+    println!("  {:<24}{}", "Size before", format_size(stats.bytes_before));
+    println!("  {:<24}{}", "Size after", format_size(stats.bytes_after));
+    println!("  {:<24}{}", "Deduplication savings", format_size(stats.deduplication_savings()));
+
+    if !stats.weight_histogram.is_empty() {
+        let histogram: Vec<String> = stats.weight_histogram.iter()
+            .map(|(weight, fonts)| format!("{}: {}", weight, fonts))
+            .collect();
+        println!("  {:<24}{}", "Weights", histogram.join(", "));
+    }
+
+    if !stats.largest_families.is_empty() {
+        println!("  Largest families by file count:");
+        for (family, fonts) in &stats.largest_families {
+            println!("    {:>5}  {}", fonts, family);
+        }
+    }
+
+    if !stats.single_font_families.is_empty() {
+        println!("  Families with a single font ({}):", stats.single_font_families.len());
+        for family in &stats.single_font_families {
+            println!("    {}", family);
+        }
+    }
+}
+
+/// The statistics as JSON
+pub fn statistics_json(stats: &FontStats) -> Value {
+    let weights: serde_json::Map<String, Value> = stats.weight_histogram.iter()
+        .map(|(weight, fonts)| (weight.to_string(), json!(fonts)))
+        .collect();
+    let largest: Vec<Value> = stats.largest_families.iter()
+        .map(|(family, fonts)| json!({ "family": family, "fonts": fonts }))
+        .collect();
+
+    json!({
+        "files_moved": stats.files_moved,
+        "files_skipped": stats.files_skipped,
+        "family_folders_created": stats.family_folders_created,
+        "family_folders": stats.family_folders,
+        "foundry_folders": stats.foundry_folders,
+        "bytes_before": stats.bytes_before,
+        "bytes_after": stats.bytes_after,
+        "deduplication_savings": stats.deduplication_savings(),
+        "weight_histogram": weights,
+        "largest_families": largest,
+        "single_font_families": stats.single_font_families,
+    })
+}
@@ -0,0 +1,586 @@
+//! Fixtures shared by the unit tests: minimal fonts built in memory and
+//! scratch directories removed when dropped
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Platform IDs of name records
+pub const PLATFORM_UNICODE: u16 = 0;
+pub const PLATFORM_MACINTOSH: u16 = 1;
+pub const PLATFORM_WINDOWS: u16 = 3;
+
+/// Windows language ID of US English
+const LANGUAGE_ENGLISH_US: u16 = 0x409;
+
+/// A straight-sided glyph contour, as its on-curve points
+type Contour = Vec<(i16, i16)>;
+
+/// Name IDs set by `TestFont::new`
+const NAME_ID_FAMILY: u16 = 1;
+const NAME_ID_SUBFAMILY: u16 = 2;
+const NAME_ID_FULL_NAME: u16 = 4;
+const NAME_ID_POSTSCRIPT: u16 = 6;
+
+/// A TrueType font with just the tables metadata is read from: head, hhea,
+/// maxp, OS/2 (version 4), post and a Windows Unicode name table
+#[derive(Debug, Clone)]
+pub struct TestFont {
+    /// Name records as (platform, language, name ID, value)
+    names: Vec<(u16, u16, u16, String)>,
+    weight: u16,
+    width: u16,
+    fs_selection: u16,
+    mac_style: u16,
+    italic_angle: i16,
+    fixed_pitch: bool,
+    panose: [u8; 10],
+    family_class: u8,
+    vendor: [u8; 4],
+    os2_version: u16,
+    /// Glyph outlines by character, in glyph ID order
+    glyphs: Vec<(char, Vec<Contour>)>,
+    tables: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl TestFont {
+    /// A Regular-weight, normal-width font with the family (ID 1), subfamily
+    /// (ID 2), full name (ID 4) and PostScript name (ID 6) records
+    pub fn new(family: &str, subfamily: &str) -> Self {
+        let postscript = format!("{}-{}", family.replace(' ', ""), subfamily.replace(' ', ""));
+        Self {
+            names: [
+                (NAME_ID_FAMILY, family.to_string()),
+                (NAME_ID_SUBFAMILY, subfamily.to_string()),
+                (NAME_ID_FULL_NAME, format!("{} {}", family, subfamily)),
+                (NAME_ID_POSTSCRIPT, postscript),
+            ]
+            .into_iter()
+            .map(|(name_id, value)| (PLATFORM_WINDOWS, LANGUAGE_ENGLISH_US, name_id, value))
+            .collect(),
+            weight: 400,
+            width: 5,
+            fs_selection: 0,
+            mac_style: 0,
+            italic_angle: 0,
+            fixed_pitch: false,
+            panose: [0; 10],
+            family_class: 0,
+            vendor: *b"NONE",
+            os2_version: 4,
+            glyphs: Vec::new(),
+            tables: Vec::new(),
+        }
+    }
+
+    /// Set a Windows US English name record, replacing one with the same ID
+    pub fn name(mut self, name_id: u16, value: &str) -> Self {
+        self.names.retain(|(platform, language, id, _)| (*platform, *language, *id) != (PLATFORM_WINDOWS, LANGUAGE_ENGLISH_US, name_id));
+        self.names.push((PLATFORM_WINDOWS, LANGUAGE_ENGLISH_US, name_id, value.to_string()));
+        self
+    }
+
+    /// Add a name record for another platform or language; Macintosh
+    /// records are written in ASCII, the others in UTF-16
+    pub fn name_record(mut self, platform: u16, language: u16, name_id: u16, value: &str) -> Self {
+        self.names.push((platform, language, name_id, value.to_string()));
+        self
+    }
+
+    /// Drop every name record with this ID
+    pub fn without_name(mut self, name_id: u16) -> Self {
+        self.names.retain(|(_, _, id, _)| *id != name_id);
+        self
+    }
+
+    /// OS/2 usWeightClass
+    pub fn weight(mut self, weight: u16) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// OS/2 usWidthClass, 1 (ultra-condensed) to 9 (ultra-expanded)
+    pub fn width(mut self, width: u16) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// OS/2 fsSelection
+    pub fn fs_selection(mut self, fs_selection: u16) -> Self {
+        self.fs_selection = fs_selection;
+        self
+    }
+
+    /// head macStyle
+    pub fn mac_style(mut self, mac_style: u16) -> Self {
+        self.mac_style = mac_style;
+        self
+    }
+
+    /// post isFixedPitch
+    pub fn fixed_pitch(mut self) -> Self {
+        self.fixed_pitch = true;
+        self
+    }
+
+    /// OS/2 PANOSE bytes
+    pub fn panose(mut self, panose: [u8; 10]) -> Self {
+        self.panose = panose;
+        self
+    }
+
+    /// OS/2 achVendID
+    pub fn vendor(mut self, vendor: &[u8; 4]) -> Self {
+        self.vendor = *vendor;
+        self
+    }
+
+    /// OS/2 table version
+    pub fn os2_version(mut self, version: u16) -> Self {
+        self.os2_version = version;
+        self
+    }
+
+    /// Add a raw table, e.g. an empty `GSUB` or a hand-built `fvar`
+    pub fn table(mut self, tag: &[u8; 4], data: Vec<u8>) -> Self {
+        self.tables.push((*tag, data));
+        self
+    }
+
+    /// Add a glyph for `c` made of straight-sided contours, mapped in a
+    /// format 4 cmap
+    pub fn glyph(mut self, c: char, contours: &[&[(i16, i16)]]) -> Self {
+        self.glyphs.push((c, contours.iter().map(|contour| contour.to_vec()).collect()));
+        self
+    }
+
+    /// The font file's bytes
+    pub fn build(&self) -> Vec<u8> {
+        let mut tables = vec![
+            (*b"head", self.head()),
+            (*b"hhea", hhea()),
+            (*b"maxp", maxp(1 + self.glyphs.len() as u16)),
+            (*b"OS/2", self.os2()),
+            (*b"post", self.post()),
+            (*b"name", name_table(&self.names)),
+        ];
+        if !self.glyphs.is_empty() {
+            let (glyf, loca) = glyf_and_loca(&self.glyphs);
+            tables.extend([
+                (*b"glyf", glyf),
+                (*b"loca", loca),
+                (*b"cmap", cmap_table(&self.glyphs)),
+                (*b"hmtx", hmtx(1 + self.glyphs.len())),
+            ]);
+        }
+        tables.extend(self.tables.iter().cloned());
+        sfnt(0x0001_0000, tables)
+    }
+
+    /// Write the font to `path`, creating its folder, and return the path
+    pub fn write(&self, path: &Path) -> PathBuf {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, self.build()).unwrap();
+        path.to_path_buf()
+    }
+
+    fn head(&self) -> Vec<u8> {
+        let mut head = Vec::new();
+        put_u32(&mut head, 0x0001_0000); // version
+        put_u32(&mut head, 0x0001_0000); // fontRevision
+        put_u32(&mut head, 0); // checksumAdjustment
+        put_u32(&mut head, 0x5F0F_3CF5); // magicNumber
+        put_u16(&mut head, 0); // flags
+        put_u16(&mut head, 1000); // unitsPerEm
+        head.extend([0; 16]); // created, modified
+        for bound in [0i16, -200, 500, 800] {
+            put_i16(&mut head, bound);
+        }
+        put_u16(&mut head, self.mac_style);
+        put_u16(&mut head, 8); // lowestRecPPEM
+        put_i16(&mut head, 2); // fontDirectionHint
+        put_i16(&mut head, 0); // indexToLocFormat
+        put_i16(&mut head, 0); // glyphDataFormat
+        head
+    }
+
+    fn os2(&self) -> Vec<u8> {
+        let mut os2 = Vec::new();
+        put_u16(&mut os2, self.os2_version);
+        put_i16(&mut os2, 500); // xAvgCharWidth
+        put_u16(&mut os2, self.weight);
+        put_u16(&mut os2, self.width);
+        put_u16(&mut os2, 0); // fsType
+        os2.extend([0; 20]); // subscript, superscript and strikeout metrics
+        os2.extend([self.family_class, 0]);
+        os2.extend(self.panose);
+        os2.extend([0; 16]); // ulUnicodeRange1-4
+        os2.extend(self.vendor);
+        put_u16(&mut os2, self.fs_selection);
+        put_u16(&mut os2, 0x20); // usFirstCharIndex
+        put_u16(&mut os2, 0x7E); // usLastCharIndex
+        for metric in [800i16, -200, 0] {
+            put_i16(&mut os2, metric); // sTypoAscender, sTypoDescender, sTypoLineGap
+        }
+        put_u16(&mut os2, 800); // usWinAscent
+        put_u16(&mut os2, 200); // usWinDescent
+        os2.extend([0; 8]); // ulCodePageRange1-2
+        for metric in [500i16, 700] {
+            put_i16(&mut os2, metric); // sxHeight, sCapHeight
+        }
+        put_u16(&mut os2, 0); // usDefaultChar
+        put_u16(&mut os2, 0x20); // usBreakChar
+        put_u16(&mut os2, 0); // usMaxContext
+        os2
+    }
+
+    fn post(&self) -> Vec<u8> {
+        let mut post = Vec::new();
+        put_u32(&mut post, 0x0003_0000); // version 3, no glyph names
+        put_i16(&mut post, self.italic_angle);
+        put_u16(&mut post, 0);
+        put_i16(&mut post, -100); // underlinePosition
+        put_i16(&mut post, 50); // underlineThickness
+        put_u32(&mut post, u32::from(self.fixed_pitch));
+        post.extend([0; 16]); // memory usage hints
+        post
+    }
+}
+
+fn hhea() -> Vec<u8> {
+    let mut hhea = Vec::new();
+    put_u32(&mut hhea, 0x0001_0000);
+    for metric in [800i16, -200, 0] {
+        put_i16(&mut hhea, metric); // ascender, descender, lineGap
+    }
+    put_u16(&mut hhea, 500); // advanceWidthMax
+    for metric in [0i16, 0, 500, 1, 0, 0, 0, 0, 0, 0, 0] {
+        put_i16(&mut hhea, metric); // side bearings, extent, caret, reserved, metricDataFormat
+    }
+    put_u16(&mut hhea, 1); // numberOfHMetrics
+    hhea
+}
+
+/// One 500-unit advance shared by every glyph, as `hhea` has a single metric
+fn hmtx(glyphs: usize) -> Vec<u8> {
+    let mut hmtx = Vec::new();
+    put_u16(&mut hmtx, 500); // advanceWidth
+    for _ in 0..glyphs {
+        put_i16(&mut hmtx, 0); // leftSideBearing
+    }
+    hmtx
+}
+
+fn maxp(glyphs: u16) -> Vec<u8> {
+    let mut maxp = Vec::new();
+    put_u32(&mut maxp, 0x0000_5000); // version 0.5
+    put_u16(&mut maxp, glyphs);
+    maxp
+}
+
+/// Simple glyphs after an empty .notdef, with short loca offsets
+fn glyf_and_loca(glyphs: &[(char, Vec<Contour>)]) -> (Vec<u8>, Vec<u8>) {
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    put_u16(&mut loca, 0);
+    put_u16(&mut loca, 0);
+    for (_, contours) in glyphs {
+        let points: Vec<(i16, i16)> = contours.iter().flatten().copied().collect();
+        put_i16(&mut glyf, contours.len() as i16);
+        put_i16(&mut glyf, points.iter().map(|point| point.0).min().unwrap_or(0));
+        put_i16(&mut glyf, points.iter().map(|point| point.1).min().unwrap_or(0));
+        put_i16(&mut glyf, points.iter().map(|point| point.0).max().unwrap_or(0));
+        put_i16(&mut glyf, points.iter().map(|point| point.1).max().unwrap_or(0));
+        let mut end = 0;
+        for contour in contours {
+            end += contour.len();
+            put_u16(&mut glyf, end as u16 - 1); // endPtsOfContours
+        }
+        put_u16(&mut glyf, 0); // instructionLength
+        glyf.extend(std::iter::repeat_n(0x01, points.len())); // on-curve, 16-bit deltas
+        for axis in [0, 1] {
+            let mut last = 0;
+            for point in &points {
+                let value = if axis == 0 { point.0 } else { point.1 };
+                put_i16(&mut glyf, value - last);
+                last = value;
+            }
+        }
+        while glyf.len() % 4 != 0 {
+            glyf.push(0);
+        }
+        put_u16(&mut loca, (glyf.len() / 2) as u16);
+    }
+    (glyf, loca)
+}
+
+/// A Windows Unicode cmap with one format 4 segment per glyph, glyph IDs
+/// following the order the glyphs were added in
+fn cmap_table(glyphs: &[(char, Vec<Contour>)]) -> Vec<u8> {
+    let mut mapped: Vec<(u16, u16)> = glyphs.iter()
+        .enumerate()
+        .map(|(index, (c, _))| (*c as u16, index as u16 + 1))
+        .collect();
+    mapped.sort();
+    mapped.push((0xFFFF, 0));
+
+    let segments = mapped.len() as u16;
+    let entry_selector = 15 - segments.leading_zeros() as u16;
+    let search_range = 2 << entry_selector;
+    let mut cmap = Vec::new();
+    for field in [0, 1, 3, 1] {
+        put_u16(&mut cmap, field); // version, numTables, platform, encoding
+    }
+    put_u32(&mut cmap, 12);
+    for field in [4, 16 + 8 * segments, 0, 2 * segments, search_range, entry_selector, 2 * segments - search_range] {
+        put_u16(&mut cmap, field);
+    }
+    for (code, _) in &mapped {
+        put_u16(&mut cmap, *code); // endCode
+    }
+    put_u16(&mut cmap, 0); // reservedPad
+    for (code, _) in &mapped {
+        put_u16(&mut cmap, *code); // startCode
+    }
+    for (code, glyph) in &mapped {
+        put_u16(&mut cmap, if *code == 0xFFFF { 1 } else { glyph.wrapping_sub(*code) }); // idDelta
+    }
+    for _ in &mapped {
+        put_u16(&mut cmap, 0); // idRangeOffset
+    }
+    cmap
+}
+
+/// A format 0 name table of (platform, language, name ID, value) records
+fn name_table(names: &[(u16, u16, u16, String)]) -> Vec<u8> {
+    let mut names: Vec<&(u16, u16, u16, String)> = names.iter().collect();
+    names.sort_by_key(|(platform, language, name_id, _)| (*platform, *language, *name_id));
+
+    let mut records = Vec::new();
+    let mut strings = Vec::new();
+    for (platform, language, name_id, value) in names {
+        // Unicode 2.0 BMP, Mac Roman and Windows Unicode BMP encodings
+        let (encoding, encoded): (u16, Vec<u8>) = match *platform {
+            PLATFORM_MACINTOSH => (0, value.bytes().collect()),
+            PLATFORM_UNICODE => (3, value.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            _ => (1, value.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        };
+        for field in [*platform, encoding, *language, *name_id, encoded.len() as u16, strings.len() as u16] {
+            put_u16(&mut records, field);
+        }
+        strings.extend(encoded);
+    }
+
+    let mut table = Vec::new();
+    put_u16(&mut table, 0);
+    put_u16(&mut table, (records.len() / 12) as u16);
+    put_u16(&mut table, (6 + records.len()) as u16);
+    table.extend(records);
+    table.extend(strings);
+    table
+}
+
+/// `font` with the glyphs sampled for synthetic face detection, each drawn
+/// by `outline` from its height, which grows from glyph to glyph
+pub fn with_sample_glyphs(font: TestFont, outline: impl Fn(i16) -> Vec<(i16, i16)>) -> TestFont {
+    "aegnosHORb".chars()
+        .zip((500..).step_by(20))
+        .fold(font, |font, (c, height)| font.glyph(c, &[&outline(height)]))
+}
+
+/// A plain box, for the upright faces of synthetic face fixtures
+pub fn upright_outline(height: i16) -> Vec<(i16, i16)> {
+    vec![(50, 0), (450, 0), (450, height), (50, height)]
+}
+
+/// The upright outline slanted by about 11°, as a "fake" oblique is made
+pub fn sheared_outline(height: i16) -> Vec<(i16, i16)> {
+    upright_outline(height).into_iter().map(|(x, y)| (x + y / 5, y)).collect()
+}
+
+/// A redrawn italic: other shapes, with a descending stroke
+pub fn italic_outline(height: i16) -> Vec<(i16, i16)> {
+    vec![(40, -120), (300, -120), (420, 0), (480, height - 60), (220, height - 60), (100, 0), (60, -40), (40, -80)]
+}
+
+/// An `fvar` table with the given axes (tag, min, default, max) and named
+/// instances (subfamily name ID, coordinates)
+pub fn fvar_table(axes: &[(&[u8; 4], f32, f32, f32)], instances: &[(u16, Vec<f32>)]) -> Vec<u8> {
+    let fixed = |value: f32| ((value * 65536.0).round() as i32).to_be_bytes();
+    let mut table = Vec::new();
+    put_u32(&mut table, 0x0001_0000);
+    put_u16(&mut table, 16); // axesArrayOffset
+    put_u16(&mut table, 2); // reserved
+    put_u16(&mut table, axes.len() as u16);
+    put_u16(&mut table, 20); // axisSize
+    put_u16(&mut table, instances.len() as u16);
+    put_u16(&mut table, (4 + 4 * axes.len()) as u16); // instanceSize
+    for (tag, min, default, max) in axes {
+        table.extend(*tag);
+        for value in [*min, *default, *max] {
+            table.extend(fixed(value));
+        }
+        put_u16(&mut table, 0); // flags
+        put_u16(&mut table, 256); // axisNameID
+    }
+    for (name_id, coordinates) in instances {
+        put_u16(&mut table, *name_id);
+        put_u16(&mut table, 0); // flags
+        for value in coordinates {
+            table.extend(fixed(*value));
+        }
+    }
+    table
+}
+
+/// A version 1.1 `STAT` table with the given design axes (tag, ordering),
+/// format 1 axis values (axis index, flags, value name ID, value) and
+/// elided fallback name ID
+pub fn stat_table(axes: &[(&[u8; 4], u16)], values: &[(u16, u16, u16, f32)], elided_fallback_name_id: u16) -> Vec<u8> {
+    let axes_offset = 20;
+    let value_offsets = axes_offset + 8 * axes.len();
+    let mut table = Vec::new();
+    put_u16(&mut table, 1);
+    put_u16(&mut table, 1);
+    put_u16(&mut table, 8); // designAxisSize
+    put_u16(&mut table, axes.len() as u16);
+    put_u32(&mut table, axes_offset as u32);
+    put_u16(&mut table, values.len() as u16);
+    put_u32(&mut table, value_offsets as u32);
+    put_u16(&mut table, elided_fallback_name_id);
+    for (tag, ordering) in axes {
+        table.extend(*tag);
+        put_u16(&mut table, 256); // axisNameID
+        put_u16(&mut table, *ordering);
+    }
+    // Axis value offsets are relative to the offset array
+    for index in 0..values.len() {
+        put_u16(&mut table, (2 * values.len() + 12 * index) as u16);
+    }
+    for (axis, flags, name_id, value) in values {
+        for field in [1, *axis, *flags, *name_id] {
+            put_u16(&mut table, field);
+        }
+        table.extend(((value * 65536.0).round() as i32).to_be_bytes());
+    }
+    table
+}
+
+/// A TrueType collection of the given fonts
+pub fn collection(fonts: &[Vec<u8>]) -> Vec<u8> {
+    let header_len = 12 + 4 * fonts.len();
+    let mut data = Vec::new();
+    data.extend(b"ttcf");
+    put_u32(&mut data, 0x0001_0000);
+    put_u32(&mut data, fonts.len() as u32);
+
+    // Each font's table offsets are shifted by where the font lands in the file
+    let mut offset = header_len;
+    let mut bodies = Vec::new();
+    for font in fonts {
+        put_u32(&mut data, offset as u32);
+        let mut font = font.clone();
+        let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+        for index in 0..num_tables {
+            let at = 12 + 16 * index + 8;
+            let shifted = u32::from_be_bytes([font[at], font[at + 1], font[at + 2], font[at + 3]]) + offset as u32;
+            font[at..at + 4].copy_from_slice(&shifted.to_be_bytes());
+        }
+        offset += font.len();
+        bodies.push(font);
+    }
+    for body in bodies {
+        data.extend(body);
+    }
+    data
+}
+
+/// Lay out an SFNT file: the table directory sorted by tag, then every table
+/// padded to four bytes
+fn sfnt(version: u32, mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let count = tables.len() as u16;
+    let entry_selector = 15 - count.leading_zeros() as u16;
+    let search_range = 16 << entry_selector;
+
+    let mut data = Vec::new();
+    put_u32(&mut data, version);
+    put_u16(&mut data, count);
+    put_u16(&mut data, search_range);
+    put_u16(&mut data, entry_selector);
+    put_u16(&mut data, count * 16 - search_range);
+
+    let mut offset = 12 + 16 * tables.len();
+    let mut body = Vec::new();
+    for (tag, table) in &tables {
+        data.extend(tag);
+        put_u32(&mut data, checksum(table));
+        put_u32(&mut data, offset as u32);
+        put_u32(&mut data, table.len() as u32);
+
+        body.extend(table);
+        while body.len() % 4 != 0 {
+            body.push(0);
+        }
+        offset = 12 + 16 * tables.len() + body.len();
+    }
+    data.extend(body);
+    data
+}
+
+fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_be_bytes(word)
+        })
+        .fold(0u32, u32::wrapping_add)
+}
+
+fn put_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend(value.to_be_bytes());
+}
+
+fn put_i16(data: &mut Vec<u8>, value: i16) {
+    data.extend(value.to_be_bytes());
+}
+
+fn put_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend(value.to_be_bytes());
+}
+
+/// A scratch directory under the system temporary folder, removed with
+/// everything in it when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(label: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "fontsrt-test-{}-{}-{}",
+            label,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.0.join(relative)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,230 @@
+//! Fonts referenced by the `@font-face` rules of a stylesheet (`--parse-css`)
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::error::{Error, Result};
+use crate::models::FontFormat;
+
+lazy_static! {
+    static ref FONT_FACE_RE: Regex = Regex::new(r"(?is)@font-face\s*\{([^}]*)\}").unwrap();
+    static ref FAMILY_RE: Regex = Regex::new(r"(?i)font-family\s*:\s*([^;]+)").unwrap();
+    static ref WEIGHT_RE: Regex = Regex::new(r"(?i)font-weight\s*:\s*([a-z0-9]+)").unwrap();
+    static ref STYLE_RE: Regex = Regex::new(r"(?i)font-style\s*:\s*([a-z]+)").unwrap();
+    static ref URL_RE: Regex = Regex::new(r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)'"\s]*))\s*\)"#).unwrap();
+}
+
+/// One `@font-face` rule: the family, weight and style it declares and the
+/// local font files it points to
+#[derive(Debug, Clone, Default)]
+pub struct CssFontFace {
+    pub family: Option<String>,
+    /// `font-weight`, with `normal` as 400 and `bold` as 700
+    pub weight: Option<u16>,
+    /// `font-style` is `italic` or `oblique`
+    pub italic: bool,
+    /// Files of the `src` URLs, resolved against the stylesheet's folder, and
+    /// the fonts of `data:` URIs decoded into a `DecodedFontsDir`
+    pub sources: Vec<PathBuf>,
+}
+
+/// Temporary folder the `data:` fonts of a stylesheet are decoded into. It is
+/// created with the first font and removed, with the fonts still in it, once
+/// the run is over or the value is dropped.
+#[derive(Debug)]
+pub struct DecodedFontsDir {
+    path: PathBuf,
+}
+
+impl Default for DecodedFontsDir {
+    /// A folder of this process under the system's temporary folder
+    fn default() -> Self {
+        Self { path: std::env::temp_dir().join(format!("fontsrt-css-{}", std::process::id())) }
+    }
+}
+
+impl DecodedFontsDir {
+    /// Path of the folder, which may not exist yet
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Remove the folder and every font left in it
+    pub fn remove(&self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+impl Drop for DecodedFontsDir {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+impl CssFontFace {
+    /// Whether `path` is one of the rule's sources
+    pub fn has_source(&self, path: &Path) -> bool {
+        let canonical = path.canonicalize().ok();
+        self.sources.iter().any(|source| source == path || (canonical.is_some() && source.canonicalize().ok() == canonical))
+    }
+}
+
+/// The font files the `@font-face` rules of a stylesheet point to, in order.
+/// Remote URLs are skipped, `data:` fonts are decoded into `decoded`, or
+/// skipped without one, as in dry runs.
+pub fn extract_font_paths_from_css(css_path: &Path, decoded: Option<&DecodedFontsDir>) -> Result<Vec<PathBuf>> {
+    Ok(parse_css_font_faces(css_path, decoded)?.into_iter().flat_map(|face| face.sources).collect())
+}
+
+/// The `@font-face` rules of a stylesheet, with their `data:` fonts decoded
+/// into `decoded`; without it they are left out of the sources
+pub fn parse_css_font_faces(css_path: &Path, decoded: Option<&DecodedFontsDir>) -> Result<Vec<CssFontFace>> {
+    let css = fs::read_to_string(css_path)
+        .map_err(|e| Error::Config(format!("Cannot read stylesheet {}: {}", css_path.display(), e)))?;
+    let base_dir = css_path.parent().unwrap_or(Path::new(""));
+    let stem = css_path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+
+    let mut faces = Vec::new();
+    let mut decoded_count = 0;
+    for rule in FONT_FACE_RE.captures_iter(&css) {
+        let body = &rule[1];
+        let mut face = CssFontFace {
+            family: FAMILY_RE.captures(body)
+                .map(|family| family[1].trim().trim_matches(['"', '\'']).trim().to_string())
+                .filter(|family| !family.is_empty()),
+            weight: WEIGHT_RE.captures(body).and_then(|weight| match weight[1].to_lowercase().as_str() {
+                "normal" => Some(400),
+                "bold" => Some(700),
+                number => number.parse().ok(),
+            }),
+            italic: STYLE_RE.captures(body)
+                .is_some_and(|style| matches!(style[1].to_lowercase().as_str(), "italic" | "oblique")),
+            sources: Vec::new(),
+        };
+
+        for url in URL_RE.captures_iter(body) {
+            let url = url.get(1).or(url.get(2)).or(url.get(3)).map_or("", |url| url.as_str()).trim();
+            if let Some(data) = url.strip_prefix("data:") {
+                let Some(scratch) = decoded.map(DecodedFontsDir::path) else { continue };
+                let Some(bytes) = data.split_once(";base64,").and_then(|(_, encoded)| decode_base64(encoded)) else {
+                    continue;
+                };
+                let extension = match FontFormat::detect(&bytes) {
+                    Some(FontFormat::OpenType) => "otf",
+                    Some(FontFormat::Woff) => "woff",
+                    Some(FontFormat::Woff2) => "woff2",
+                    Some(FontFormat::Type1) => "pfb",
+                    _ if bytes.starts_with(b"ttcf") => "ttc",
+                    _ => "ttf",
+                };
+                decoded_count += 1;
+                let target = scratch.join(format!("{}-{}.{}", stem, decoded_count, extension));
+                fs::create_dir_all(scratch)?;
+                fs::write(&target, bytes)?;
+                face.sources.push(target);
+            } else if !url.is_empty() && !url.contains("://") && !url.starts_with("//") {
+                // Query strings and fragments, e.g. cache busters, are not part of the file name
+                let file = url.split(['?', '#']).next().unwrap_or_default().replace("%20", " ");
+                face.sources.push(base_dir.join(file));
+            }
+        }
+        faces.push(face);
+    }
+    Ok(faces)
+}
+
+/// Decode standard base64, ignoring whitespace; `None` when it is malformed
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TempDir, TestFont};
+
+    /// Standard base64 with padding
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::new();
+        for chunk in bytes.chunks(3) {
+            let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (index, &byte)| buffer | u32::from(byte) << (16 - 8 * index));
+            for index in 0..4 {
+                encoded.push(if index <= chunk.len() { ALPHABET[(buffer >> (18 - 6 * index) & 63) as usize] as char } else { '=' });
+            }
+        }
+        encoded
+    }
+
+    #[test]
+    fn urls_in_every_quoting_style_resolve_against_the_stylesheet() {
+        let tmp = TempDir::new("css-urls");
+        let css = tmp.join("css/site.css");
+        fs::create_dir_all(tmp.join("css")).unwrap();
+        fs::write(&css, r#"
+            body { font-family: "Web Sans"; }
+            @font-face {
+                font-family: "Web Sans";
+                font-weight: bold;
+                font-style: italic;
+                src: url("../fonts/a.woff2") format("woff2"), url('b b.ttf'), url(c.otf?v=3#iefix),
+                     url(d%20e.ttf), url(https://cdn.example.com/f.woff), url(//cdn.example.com/g.woff);
+            }
+            @FONT-FACE { font-family: 'Mono'; font-weight: 300; src: url( h.ttf ); }
+        "#).unwrap();
+        let dir = tmp.join("css");
+
+        let faces = parse_css_font_faces(&css, None).unwrap();
+        assert_eq!(faces.len(), 2);
+        assert_eq!((faces[0].family.as_deref(), faces[0].weight, faces[0].italic), (Some("Web Sans"), Some(700), true));
+        assert_eq!(faces[0].sources, [dir.join("../fonts/a.woff2"), dir.join("b b.ttf"), dir.join("c.otf"), dir.join("d e.ttf")]);
+        assert_eq!((faces[1].family.as_deref(), faces[1].weight, faces[1].italic), (Some("Mono"), Some(300), false));
+        assert_eq!(faces[1].sources, [dir.join("h.ttf")]);
+
+        assert_eq!(extract_font_paths_from_css(&css, None).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn data_uri_fonts_are_decoded_only_into_a_scratch_folder() {
+        let tmp = TempDir::new("css-data");
+        let font = TestFont::new("Embedded", "Regular").build();
+        let css = tmp.join("site.css");
+        fs::write(&css, format!(
+            "@font-face {{ font-family: Embedded; src: url(data:font/ttf;base64,{}) format('truetype'), url('data:font/ttf;base64,@@'); }}",
+            encode_base64(&font),
+        )).unwrap();
+
+        // Without a folder to decode into, as in dry runs, nothing is written
+        assert!(extract_font_paths_from_css(&css, None).unwrap().is_empty());
+
+        let decoded = DecodedFontsDir::default();
+        let sources = extract_font_paths_from_css(&css, Some(&decoded)).unwrap();
+        assert_eq!(sources, [decoded.path().join("site-1.ttf")]);
+        assert_eq!(fs::read(&sources[0]).unwrap(), font);
+
+        drop(decoded);
+        assert!(!sources[0].exists());
+        assert!(!sources[0].parent().unwrap().exists());
+    }
+}
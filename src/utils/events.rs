@@ -0,0 +1,158 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Map, Value};
+use crate::error::Result;
+
+/// Newline-delimited JSON event stream of a run (`--json-events <FILE>`).
+///
+/// Every event carries the run id and a sequence number starting at 1. The
+/// number is assigned and the line written under one lock, so lines appear in
+/// sequence order without gaps even when emitted from many threads. A final
+/// `end` event carries the total count so consumers can detect truncation.
+pub struct EventStream {
+    run_id: String,
+    /// Last sequence number handed out, and where lines are written
+    writer: Mutex<(u64, Box<dyn Write + Send>)>,
+}
+
+impl EventStream {
+    /// Stream events into a file, or to stderr when `path` is "-"
+    pub fn create(path: &Path) -> Result<Self> {
+        let writer: Box<dyn Write + Send> = if path.as_os_str() == "-" {
+            Box::new(io::stderr())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        Ok(Self::with_writer(writer))
+    }
+
+    /// Stream events into any writer with a fresh run id
+    pub fn with_writer(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            run_id: new_run_id(),
+            writer: Mutex::new((0, writer)),
+        }
+    }
+
+    /// Identifier shared by every event and artifact of this run
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Emit one event; `data` fields are merged into the event object, except
+    /// `seq`, `run_id` and `event`, which always keep the stream's values
+    pub fn emit(&self, event: &str, data: Value) {
+        let mut writer = self.writer.lock().unwrap();
+        self.write_event(&mut writer, event, data);
+    }
+
+    /// Emit the terminal `end` event, whose `count` includes itself
+    pub fn finish(&self) {
+        let mut writer = self.writer.lock().unwrap();
+        let count = writer.0 + 1;
+        self.write_event(&mut writer, "end", json!({ "count": count }));
+    }
+
+    /// Assign the next sequence number and write the event line, with the lock held
+    fn write_event(&self, writer: &mut (u64, Box<dyn Write + Send>), event: &str, data: Value) {
+        writer.0 += 1;
+
+        // The reserved keys go in last so data fields cannot replace them
+        let mut line = match data {
+            Value::Object(fields) => fields,
+            _ => Map::new(),
+        };
+        line.insert("seq".to_string(), json!(writer.0));
+        line.insert("run_id".to_string(), json!(self.run_id));
+        line.insert("event".to_string(), json!(event));
+
+        // A failing event sink must not abort the run it is reporting on
+        let _ = writeln!(writer.1, "{}", Value::Object(line)).and_then(|_| writer.1.flush());
+    }
+}
+
+/// Run id made from the start time and process id, e.g. "18c2f0a9d1e4b000-4d2"
+fn new_run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}-{:x}", nanos, std::process::id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Writer appending into a buffer the test can read back
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn events(buffer: &SharedBuffer) -> Vec<Value> {
+        let bytes = buffer.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sequence_has_no_gaps_across_threads() {
+        let buffer = SharedBuffer::default();
+        let stream = Arc::new(EventStream::with_writer(Box::new(buffer.clone())));
+
+        let handles: Vec<_> = (0..8)
+            .map(|thread| {
+                let stream = Arc::clone(&stream);
+                thread::spawn(move || {
+                    for index in 0..50 {
+                        stream.emit("move", json!({ "thread": thread, "index": index }));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        stream.finish();
+
+        let events = events(&buffer);
+        assert_eq!(events.len(), 401);
+        for (index, event) in events.iter().enumerate() {
+            assert_eq!(event["seq"], json!(index as u64 + 1));
+            assert_eq!(event["run_id"], json!(stream.run_id()));
+        }
+        let end = events.last().unwrap();
+        assert_eq!(end["event"], json!("end"));
+        assert_eq!(end["count"], json!(401));
+    }
+
+    #[test]
+    fn data_fields_do_not_replace_reserved_keys() {
+        let buffer = SharedBuffer::default();
+        let stream = EventStream::with_writer(Box::new(buffer.clone()));
+        stream.emit("move", json!({ "seq": 99, "run_id": "other", "event": "forged", "path": "a.ttf" }));
+
+        let events = events(&buffer);
+        assert_eq!(events[0]["seq"], json!(1));
+        assert_eq!(events[0]["run_id"], json!(stream.run_id()));
+        assert_eq!(events[0]["event"], json!("move"));
+        assert_eq!(events[0]["path"], json!("a.ttf"));
+    }
+}
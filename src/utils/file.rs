@@ -1,56 +1,414 @@
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
-use crate::error::Result;
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+use crate::error::{Error, Result};
+use crate::font::metadata::{FONT_EXTENSIONS, METRIC_SIDECAR_EXTENSIONS};
 use crate::models::Config;
-use crate::utils::logging::log;
+use crate::utils::logging::{log, log_error};
+
+/// Paths this long exceed `MAX_PATH` on Windows, counting the terminating NUL
+const WINDOWS_MAX_PATH: usize = 260;
+
+/// The path to hand to file system calls: on Windows, an absolute path of
+/// `MAX_PATH` characters or more gets the `\\?\` prefix (`\\?\UNC\` for
+/// shares) that lifts the limit. Other paths, and every path on other
+/// platforms, are returned unchanged.
+pub fn long_path(path: &Path) -> PathBuf {
+    if cfg!(windows) && path.is_absolute() && path.as_os_str().len() >= WINDOWS_MAX_PATH {
+        // Prefixed paths skip normalization, so separators must be backslashes
+        let text = path.to_string_lossy().replace('/', "\\");
+        if !text.starts_with(r"\\?\") {
+            return match text.strip_prefix(r"\\") {
+                Some(share) => PathBuf::from(format!(r"\\?\UNC\{}", share)),
+                None => PathBuf::from(format!(r"\\?\{}", text)),
+            };
+        }
+    }
+    path.to_path_buf()
+}
 
 /// Create a directory if it doesn't exist
 pub fn ensure_directory_exists(dir: &Path, config: &Config) -> Result<()> {
+    if config.dry_run {
+        return Ok(());
+    }
+
     if !dir.exists() {
         log(
             config,
             format!("Directory {} does not exist. Creating it now.", dir.display()),
         );
-        fs::create_dir_all(dir)?;
+        fs::create_dir_all(long_path(dir))?;
+    }
+    Ok(())
+}
+
+/// Hash a file's contents to detect byte-identical copies
+pub fn content_hash(path: &Path) -> Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(fs::File::open(path)?)?;
+    Ok(hasher.finalize())
+}
+
+/// Bytes read from each end of a file by `sampled_hash`
+pub const HASH_SAMPLE_BYTES: u64 = 1 << 20;
+
+/// Hash a file's length, first and last `HASH_SAMPLE_BYTES`, to pre-screen
+/// large fonts for byte-identical copies without reading all of them.
+///
+/// Identical files always have the same sampled hash, so a file whose
+/// sampled hash is unique has no copy. Different files can share one when
+/// they differ only in the middle, so a match must be confirmed with
+/// `content_hash` before either file is treated as a copy.
+pub fn sampled_hash(path: &Path) -> Result<blake3::Hash> {
+    let mut file = fs::File::open(path)?;
+    let length = file.metadata()?.len();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&length.to_le_bytes());
+
+    if length <= 2 * HASH_SAMPLE_BYTES {
+        hasher.update_reader(file)?;
+    } else {
+        hasher.update_reader((&mut file).take(HASH_SAMPLE_BYTES))?;
+        file.seek(io::SeekFrom::End(-(HASH_SAMPLE_BYTES as i64)))?;
+        hasher.update_reader(file)?;
+    }
+    Ok(hasher.finalize())
+}
+
+/// Format a byte count for display, e.g. "1.4 MB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Parse a human-readable size such as "500K", "1.5MB" or "2G" into bytes
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim().to_uppercase();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier: u64 = match unit.trim().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Files operating systems leave next to fonts: Finder metadata (`.DS_Store`),
+/// AppleDouble resource forks (`._*`) and Explorer caches and settings
+const JUNK_FILE_NAMES: &[&str] = &[".ds_store", "thumbs.db", "desktop.ini"];
+
+/// Whether a file is operating system junk rather than a font, whatever its extension
+pub fn is_junk_file(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.starts_with("._") || JUNK_FILE_NAMES.contains(&name.to_lowercase().as_str())
+}
+
+/// Whether a folder holds only junk, like the `__MACOSX` folder of zips made by Finder
+pub fn is_junk_folder(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == "__MACOSX")
+}
+
+/// Bytes available to unprivileged users on the file system holding `dir`
+#[cfg(unix)]
+pub fn free_space(dir: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stats` is a valid, writable statvfs
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+/// Whether two existing paths are on the same file system, so moving between
+/// them is a rename rather than a copy
+#[cfg(unix)]
+fn same_file_system(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    matches!((fs::metadata(a), fs::metadata(b)), (Ok(a), Ok(b)) if a.dev() == b.dev())
+}
+
+/// Whether two existing paths are on the same drive
+#[cfg(not(unix))]
+fn same_file_system(a: &Path, b: &Path) -> bool {
+    let root = |path: &Path| path.canonicalize().ok().and_then(|path| path.components().next().map(|c| c.as_os_str().to_os_string()));
+    root(a).is_some() && root(a) == root(b)
+}
+
+/// Room left for new folders, the font database and reports when fonts are
+/// moved within one file system
+const SAME_FILE_SYSTEM_OVERHEAD: u64 = 16 << 20;
+
+/// Check that the file system of `dest_dir` has room for organizing the fonts
+/// of `src_dir` with a 10% margin. Moving within one file system only renames
+/// files, so a small overhead is needed; moving to another one copies every
+/// font first. Dry runs, and platforms where the free space is unknown, pass.
+pub fn check_available_space(src_dir: &Path, dest_dir: &Path, config: &Config) -> Result<()> {
+    if config.dry_run {
+        return Ok(());
+    }
+    // The output root may not exist yet, its nearest existing folder decides
+    let Some(dest_dir) = dest_dir.ancestors().find(|path| path.exists()) else {
+        return Ok(());
+    };
+    let Some(available) = free_space(dest_dir) else {
+        log(config, format!("Free space of {} is unknown, skipping the space check", dest_dir.display()));
+        return Ok(());
+    };
+
+    let required = if same_file_system(src_dir, dest_dir) {
+        SAME_FILE_SYSTEM_OVERHEAD
+    } else {
+        font_files_size(src_dir, config.recursive)
+    };
+    log(config, format!("{} needed on {}, {} available", format_size(required), dest_dir.display(), format_size(available)));
+    let required = required + required / 10;
+    if available < required {
+        return Err(Error::Io(io::Error::new(io::ErrorKind::StorageFull, format!(
+            "not enough space on {}: organizing {} needs {} with a 10% margin, but only {} is available",
+            dest_dir.display(), src_dir.display(), format_size(required), format_size(available)
+        ))));
     }
     Ok(())
 }
 
+/// Total size of the font files in a directory, and in its subdirectories
+/// when `recursive`
+fn font_files_size(dir: &Path, recursive: bool) -> u64 {
+    let mut bytes = 0;
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive {
+                bytes += font_files_size(&path, recursive);
+            }
+        } else if path.extension().is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())) {
+            bytes += fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+        }
+    }
+    bytes
+}
+
+/// Name of a folder in `dir` that is spelled like `name` apart from case, so a
+/// new folder joins it rather than creating a second one on a case-sensitive
+/// file system (or racing for the same one on a case-insensitive file system)
+pub fn existing_folder_ignoring_case(dir: &Path, name: &str) -> Option<String> {
+    let lowercase = name.to_lowercase();
+    let mut matches: Vec<String> = fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|existing| existing.to_lowercase() == lowercase)
+        .collect();
+    // An exact match wins, else the first in sort order
+    matches.sort();
+    matches.iter().position(|existing| existing == name)
+        .map(|index| matches.swap_remove(index))
+        .or_else(|| matches.into_iter().next())
+}
+
+/// Total size and count of all files below a directory
+pub fn directory_size(dir: &Path) -> (u64, usize) {
+    let mut bytes = 0;
+    let mut files = 0;
+
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                let (nested_bytes, nested_files) = directory_size(&path);
+                bytes += nested_bytes;
+                files += nested_files;
+            } else if let Ok(metadata) = entry.metadata() {
+                bytes += metadata.len();
+                files += 1;
+            }
+        }
+    }
+
+    (bytes, files)
+}
+
 /// Safely move a file with fallback to copy+delete if rename fails
 pub fn safe_move_file(src: &Path, dest: &Path, config: &Config) -> Result<()> {
+    if config.dry_run {
+        log(config, format!("Dry run: would move {} to {}", src.display(), dest.display()));
+        return Ok(());
+    }
+
     // First try to rename (fast path)
-    match fs::rename(src, dest) {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            // If rename fails, log it and try copy+delete
+    if let Err(e) = fs::rename(long_path(src), long_path(dest)) {
+        // If rename fails, log it and try copy+delete
+        log(
+            config,
+            format!("Rename failed for {}, trying copy+delete: {}", src.display(), e),
+        );
+
+        // Copy the file, keeping the original if the copy is not identical
+        copy_verified(src, dest, config)?;
+
+        // Delete the original
+        if let Err(e) = fs::remove_file(long_path(src)) {
             log(
                 config,
-                format!("Rename failed for {}, trying copy+delete: {}", src.display(), e),
+                format!("Warning: Could not delete source file {} after copying: {}", src.display(), e),
             );
+            // We still consider this a success since the file was copied
+        }
+    }
 
-            // Copy the file
-            fs::copy(src, dest)?;
+    // Moves keep size and mtime, so the cached metadata follows the font
+    if let Some(cache) = &config.metadata_cache {
+        cache.relocate(src, dest);
+    }
+    if let Some(database) = &config.font_database {
+        database.relocate(src, dest);
+    }
+    Ok(())
+}
 
-            // Delete the original
-            match fs::remove_file(src) {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    log(
-                        config,
-                        format!("Warning: Could not delete source file {} after copying: {}", src.display(), e),
-                    );
-                    // We still consider this a success since the file was copied
-                    Ok(())
-                }
-            }
+/// Copy `src` to `dest` and, with `config.verify_copy`, check that the copy
+/// has the same BLAKE3 hash. A copy that differs or cannot be read back is
+/// deleted and the source is left alone.
+fn copy_verified(src: &Path, dest: &Path, config: &Config) -> Result<()> {
+    let (src, dest) = (&long_path(src), &long_path(dest));
+    fs::copy(src, dest)?;
+    if !config.verify_copy {
+        return Ok(());
+    }
+
+    let mismatch = match (content_hash(src), content_hash(dest)) {
+        (Ok(original), Ok(copy)) if original == copy => return Ok(()),
+        (Ok(_), Ok(_)) => "differs from the original".to_string(),
+        (Err(e), _) | (_, Err(e)) => format!("could not be verified: {}", e),
+    };
+    let _ = fs::remove_file(dest);
+    log_error(config, format!("Copy of {} to {} {}, removed it and kept the original", src.display(), dest.display(), mismatch));
+    Err(Error::Io(io::Error::other(format!("Copy of {} to {} {}", src.display(), dest.display(), mismatch))))
+}
+
+/// Split a `base_N` collision suffix off a file stem, e.g. `Foo_2` into
+/// `("Foo", Some(2))`. Stems without one come back whole.
+pub fn split_collision_suffix(stem: &str) -> (&str, Option<u32>) {
+    match stem.rsplit_once('_') {
+        Some((base, number)) if !base.is_empty() && !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) => {
+            number.parse().map_or((stem, None), |number| (base, Some(number)))
         }
+        _ => (stem, None),
     }
 }
 
+/// Path in `dir` for `stem` with `extension`, or, when that name exists on
+/// disk or is in `claimed`, a collision-suffixed name.
+///
+/// The suffix continues after the highest `base_N` of the same extension
+/// already in `dir` or claimed, where `base` is `stem` without its own
+/// suffix, so a second copy of `Foo_1.ttf` becomes `Foo_2.ttf` (or later)
+/// rather than `Foo_1_1.ttf`. Every place that resolves a name clash goes
+/// through here, so their numbering never interleaves or stacks.
+pub fn collision_free_path(dir: &Path, stem: &str, extension: Option<&str>, claimed: &HashSet<PathBuf>) -> PathBuf {
+    let file_name = |stem: &str| match extension {
+        Some(extension) => format!("{}.{}", stem, extension),
+        None => stem.to_string(),
+    };
+    let taken = |path: &Path| path.exists() || claimed.contains(path);
+
+    let target = dir.join(file_name(stem));
+    if !taken(&target) {
+        return target;
+    }
+
+    let (base, _) = split_collision_suffix(stem);
+    let suffix_of = |path: &Path| -> Option<u32> {
+        let name = path.file_name()?.to_str()?;
+        let name_stem = match extension {
+            Some(extension) => name.strip_suffix(extension)?.strip_suffix('.')?,
+            None => name,
+        };
+        match split_collision_suffix(name_stem) {
+            (name_base, Some(number)) if name_base == base => Some(number),
+            _ => (name_stem == base).then_some(0),
+        }
+    };
+    let highest = fs::read_dir(dir).into_iter().flatten().flatten()
+        .map(|entry| entry.path())
+        .chain(claimed.iter().filter(|path| path.parent() == Some(dir)).cloned())
+        .filter_map(|path| suffix_of(&path))
+        .max()
+        .unwrap_or(0);
+
+    let mut counter = highest + 1;
+    loop {
+        let target = dir.join(file_name(&format!("{}_{}", base, counter)));
+        if !taken(&target) {
+            return target;
+        }
+        counter += 1;
+    }
+}
+
+/// Path in `dir` for `file`'s name, collision-suffixed if that name is taken
+pub fn unique_path_in(dir: &Path, file: &Path) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file.extension().map(|extension| extension.to_string_lossy());
+    collision_free_path(dir, &stem, extension.as_deref(), &HashSet::new())
+}
+
+/// Rename a file to `new_name` inside the directory it is in, suffixing `_1`,
+/// `_2`, ... when the name is taken by another file
+pub fn safe_rename_in_place(src: &Path, new_name: &str, config: &Config) -> Result<()> {
+    let dir = src.parent().unwrap_or(Path::new(""));
+    if dir.join(new_name) == src {
+        return Ok(());
+    }
+
+    let dest = unique_path_in(dir, Path::new(new_name));
+    safe_move_file(src, &dest, config)
+}
+
 /// Safely move a directory with fallback to recursive copy+delete if rename fails
 pub fn safe_move_directory(src_dir: &Path, dest_dir: &Path, config: &Config) -> Result<()> {
+    if config.dry_run {
+        log(config, format!("Dry run: would move directory {} to {}", src_dir.display(), dest_dir.display()));
+        return Ok(());
+    }
+
+    if let Some(cache) = &config.metadata_cache {
+        cache.relocate_tree(src_dir, dest_dir);
+    }
+    if let Some(database) = &config.font_database {
+        database.relocate_tree(src_dir, dest_dir);
+    }
+
     // First try to rename (fast path)
-    match fs::rename(src_dir, dest_dir) {
+    match fs::rename(long_path(src_dir), long_path(dest_dir)) {
         Ok(_) => Ok(()),
         Err(e) => {
             // If rename fails, log it and try recursive copy+delete
@@ -65,8 +423,9 @@ pub fn safe_move_directory(src_dir: &Path, dest_dir: &Path, config: &Config) ->
             // Copy all contents recursively
             merge_directories(src_dir, dest_dir, config)?;
 
-            // Try to remove the source directory
-            match fs::remove_dir_all(src_dir) {
+            // Remove the source directory once emptied; files whose copy
+            // failed or could not be verified are still in it
+            match fs::remove_dir(long_path(src_dir)) {
                 Ok(_) => Ok(()),
                 Err(e) => {
                     log(
@@ -83,42 +442,26 @@ pub fn safe_move_directory(src_dir: &Path, dest_dir: &Path, config: &Config) ->
 
 /// Merge the contents of two directories
 pub fn merge_directories(src_dir: &Path, dest_dir: &Path, config: &Config) -> Result<()> {
-    let entries = fs::read_dir(src_dir)?;
+    if config.dry_run {
+        log(config, format!("Dry run: would merge {} into {}", src_dir.display(), dest_dir.display()));
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(long_path(src_dir))?;
 
     for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        let file_name = path.file_name().unwrap_or_default();
-        let dest_path = dest_dir.join(file_name);
+        // Paths stay unprefixed for logs and the metadata cache
+        let path = src_dir.join(entry?.file_name());
+        let dest_path = dest_dir.join(path.file_name().unwrap_or_default());
 
         if path.is_file() {
-            // If destination file already exists, create a unique name
-            if dest_path.exists() {
-                let mut counter = 1;
-                let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
-                let extension = path.extension().unwrap_or_default().to_string_lossy();
-
-                let mut unique_path = dest_dir.join(format!("{}_{}.{}", file_stem, counter, extension));
-
-                while unique_path.exists() {
-                    counter += 1;
-                    unique_path = dest_dir.join(format!("{}_{}.{}", file_stem, counter, extension));
-                }
-
-                if let Err(e) = safe_move_file(&path, &unique_path, config) {
-                    log(
-                        config,
-                        format!("Error moving file {}: {}", path.display(), e),
-                    );
-                }
-            } else {
-                // Move the file
-                if let Err(e) = safe_move_file(&path, &dest_path, config) {
-                    log(
-                        config,
-                        format!("Error moving file {}: {}", path.display(), e),
-                    );
-                }
+            // A clashing name continues the destination's suffix numbering
+            let unique_path = unique_path_in(dest_dir, &path);
+            if let Err(e) = safe_move_file(&path, &unique_path, config) {
+                log(
+                    config,
+                    format!("Error moving file {}: {}", path.display(), e),
+                );
             }
         } else if path.is_dir() {
             // Create destination directory if it doesn't exist
@@ -128,7 +471,7 @@ pub fn merge_directories(src_dir: &Path, dest_dir: &Path, config: &Config) -> Re
             merge_directories(&path, &dest_path, config)?;
 
             // Remove the now-empty source directory
-            if let Err(e) = fs::remove_dir(&path) {
+            if let Err(e) = fs::remove_dir(long_path(&path)) {
                 log(
                     config,
                     format!("Error removing directory {}: {}", path.display(), e),
@@ -140,3 +483,265 @@ pub fn merge_directories(src_dir: &Path, dest_dir: &Path, config: &Config) -> Re
     Ok(())
 }
 
+/// Extract the font files of a zip archive into `dest_dir`, returning their paths.
+///
+/// Folders inside the archive are flattened, clashing names get a `_N`
+/// suffix and other files are skipped; Type 1 metric files are extracted
+/// but not returned. Zip archives inside the archive are
+/// opened one level deep. `dest_dir` is written even in a dry run, so callers
+/// pass a scratch directory then.
+pub fn extract_fonts_from_zip(zip_path: &Path, dest_dir: &Path, config: &Config) -> Result<Vec<PathBuf>> {
+    let mut archive = ZipArchive::new(fs::File::open(zip_path)?).map_err(io::Error::from)?;
+    let mut extracted = Vec::new();
+    extract_archive_fonts(&mut archive, zip_path, dest_dir, 0, config, &mut extracted)?;
+    Ok(extracted)
+}
+
+/// Extract the fonts of an open archive, descending into nested archives up to depth 1
+fn extract_archive_fonts<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    zip_path: &Path,
+    dest_dir: &Path,
+    depth: usize,
+    config: &Config,
+    extracted: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::from)?;
+        if entry.is_dir() {
+            continue;
+        }
+        // Entries with absolute or `..` paths are skipped rather than trusted
+        let Some(entry_path) = entry.enclosed_name() else {
+            continue;
+        };
+        if config.default_ignores
+            && (is_junk_file(&entry_path) || entry_path.ancestors().any(is_junk_folder))
+        {
+            continue;
+        }
+        let Some(name) = entry_path.file_name().map(PathBuf::from) else {
+            continue;
+        };
+        let extension = name.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if extension == "zip" && depth == 0 {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            match ZipArchive::new(Cursor::new(data)) {
+                Ok(mut nested) => extract_archive_fonts(&mut nested, zip_path, dest_dir, depth + 1, config, extracted)?,
+                Err(e) => log(config, format!("Skipping nested archive {} in {}: {}", name.display(), zip_path.display(), e)),
+            }
+            continue;
+        }
+        if !FONT_EXTENSIONS.contains(&extension.as_str()) && !METRIC_SIDECAR_EXTENSIONS.contains(&extension.as_str()) {
+            continue;
+        }
+
+        fs::create_dir_all(dest_dir)?;
+        let target = unique_path_in(dest_dir, &name);
+        io::copy(&mut entry, &mut fs::File::create(&target)?)?;
+        log(config, format!("Extracted {} from {}", target.display(), zip_path.display()));
+        // Type 1 metric files are extracted to travel with their font, not organized themselves
+        if FONT_EXTENSIONS.contains(&extension.as_str()) {
+            extracted.push(target);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn parse_size_units() {
+        let cases = [
+            ("512", Some(512)),
+            ("500K", Some(500 << 10)),
+            ("1.5MB", Some(3 << 19)),
+            ("2g", Some(2 << 30)),
+            ("1 TiB", Some(1 << 40)),
+            ("12 parsecs", None),
+            ("MB", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_size(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn cross_device_copy_into_vault() {
+        // A move to another device falls back to this verified copy
+        let library = TempDir::new("library");
+        let vault = TempDir::new("vault");
+        let source = library.join("Roboto-Regular.ttf");
+        fs::write(&source, b"font bytes").unwrap();
+        let dest = vault.join("Library/duplicates/Roboto-Regular.ttf");
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+
+        let config = Config { verify_copy: true, ..Config::default() };
+        copy_verified(&source, &dest, &config).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"font bytes");
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn long_paths_are_prefixed_on_windows_only() {
+        let name = "Lorem Ipsum ".repeat(25);
+        let short = if cfg!(windows) { PathBuf::from(r"C:\fonts\Roboto.ttf") } else { PathBuf::from("/fonts/Roboto.ttf") };
+        let long = short.with_file_name(format!("{}.ttf", name));
+        assert!(long.as_os_str().len() > WINDOWS_MAX_PATH);
+
+        assert_eq!(long_path(&short), short);
+        assert_eq!(long_path(Path::new(&name)), Path::new(&name));
+        if cfg!(windows) {
+            assert_eq!(long_path(&long), PathBuf::from(format!(r"\\?\C:\fonts\{}.ttf", name)));
+            let share = PathBuf::from(format!(r"\\server\fonts\{}.ttf", name));
+            assert_eq!(long_path(&share), PathBuf::from(format!(r"\\?\UNC\server\fonts\{}.ttf", name)));
+            assert_eq!(long_path(&long_path(&long)), long_path(&long));
+        } else {
+            assert_eq!(long_path(&long), long);
+        }
+    }
+
+    #[test]
+    fn existing_folders_are_found_ignoring_case() {
+        let tmp = TempDir::new("case");
+        fs::create_dir(tmp.join("gotham")).unwrap();
+        fs::write(tmp.join("Lato"), "not a folder").unwrap();
+        assert_eq!(existing_folder_ignoring_case(tmp.path(), "Gotham").as_deref(), Some("gotham"));
+        assert_eq!(existing_folder_ignoring_case(tmp.path(), "Lato"), None);
+
+        // Only a case-sensitive file system holds both; the exact spelling wins there
+        if fs::create_dir(tmp.join("GOTHAM")).is_err() {
+            return;
+        }
+        assert_eq!(existing_folder_ignoring_case(tmp.path(), "gotham").as_deref(), Some("gotham"));
+        assert_eq!(existing_folder_ignoring_case(tmp.path(), "Gotham").as_deref(), Some("GOTHAM"));
+    }
+
+    /// File names in `dir` with their contents, sorted
+    fn folder_contents(dir: &Path) -> Vec<(String, String)> {
+        let mut contents: Vec<(String, String)> = fs::read_dir(dir).unwrap().flatten()
+            .filter(|entry| entry.path().is_file())
+            .map(|entry| (entry.file_name().to_string_lossy().into_owned(), fs::read_to_string(entry.path()).unwrap()))
+            .collect();
+        contents.sort();
+        contents
+    }
+
+    #[test]
+    fn collision_suffixes_are_split() {
+        let cases = [
+            ("Foo_1", ("Foo", Some(1))),
+            ("Foo_12", ("Foo", Some(12))),
+            ("Foo_1_2", ("Foo_1", Some(2))),
+            ("Foo", ("Foo", None)),
+            ("Foo_", ("Foo_", None)),
+            ("Foo_Bold", ("Foo_Bold", None)),
+            ("_1", ("_1", None)),
+            ("Foo_99999999999", ("Foo_99999999999", None)),
+        ];
+        for (stem, expected) in cases {
+            assert_eq!(split_collision_suffix(stem), expected, "{}", stem);
+        }
+    }
+
+    #[test]
+    fn collision_suffixes_continue_from_the_highest() {
+        let tmp = TempDir::new("collisions");
+        for name in ["Foo.ttf", "Foo_1.ttf", "Foo_3.ttf", "Foo_7.otf", "Foobar_9.ttf"] {
+            fs::write(tmp.join(name), name).unwrap();
+        }
+        let free = |stem: &str, claimed: &HashSet<PathBuf>| collision_free_path(tmp.path(), stem, Some("ttf"), claimed);
+
+        assert_eq!(free("Bar", &HashSet::new()), tmp.join("Bar.ttf"));
+        assert_eq!(free("Foo_2", &HashSet::new()), tmp.join("Foo_2.ttf"));
+        // Other extensions and longer bases do not count, suffixes never stack
+        assert_eq!(free("Foo", &HashSet::new()), tmp.join("Foo_4.ttf"));
+        assert_eq!(free("Foo_1", &HashSet::new()), tmp.join("Foo_4.ttf"));
+        let claimed: HashSet<PathBuf> = [tmp.join("Foo_4.ttf"), tmp.join("Bar.ttf")].into();
+        assert_eq!(free("Foo", &claimed), tmp.join("Foo_5.ttf"));
+        assert_eq!(free("Bar", &claimed), tmp.join("Bar_1.ttf"));
+
+        fs::create_dir(tmp.join("Roboto")).unwrap();
+        assert_eq!(collision_free_path(tmp.path(), "Roboto", None, &HashSet::new()), tmp.join("Roboto_1"));
+    }
+
+    #[test]
+    fn merging_pre_suffixed_folders_does_not_stack_or_overwrite() {
+        let tmp = TempDir::new("collisions");
+        let dest = tmp.join("Roboto");
+        fs::create_dir(&dest).unwrap();
+        for name in ["Foo.ttf", "Foo_1.ttf", "Bar_2.ttf"] {
+            fs::write(dest.join(name), format!("dest {}", name)).unwrap();
+        }
+        for source in ["Roboto Pro", "Roboto Std"] {
+            fs::create_dir(tmp.join(source)).unwrap();
+            for name in ["Foo_1.ttf", "Bar_2.ttf", "Baz.ttf"] {
+                fs::write(tmp.join(source).join(name), format!("{} {}", source, name)).unwrap();
+            }
+        }
+
+        let config = Config::default();
+        merge_directories(&tmp.join("Roboto Pro"), &dest, &config).unwrap();
+        merge_directories(&tmp.join("Roboto Std"), &dest, &config).unwrap();
+
+        let contents = folder_contents(&dest);
+        let names: Vec<&str> = contents.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, [
+            "Bar_2.ttf", "Bar_3.ttf", "Bar_4.ttf", "Baz.ttf", "Baz_1.ttf",
+            "Foo.ttf", "Foo_1.ttf", "Foo_2.ttf", "Foo_3.ttf",
+        ]);
+        let mut sources: Vec<&str> = contents.iter().map(|(_, source)| source.as_str()).collect();
+        sources.sort();
+        sources.dedup();
+        assert_eq!(sources.len(), 9, "a file was overwritten: {:?}", contents);
+        assert!(contents.contains(&("Foo_1.ttf".to_string(), "dest Foo_1.ttf".to_string())));
+        assert!(folder_contents(&tmp.join("Roboto Pro")).is_empty());
+    }
+
+    /// A sparse file of `length` bytes, zero except for each `(offset, bytes)`
+    fn sparse_file(path: &Path, length: u64, writes: &[(u64, &[u8])]) {
+        let mut file = fs::File::create(path).unwrap();
+        file.set_len(length).unwrap();
+        for (offset, bytes) in writes {
+            file.seek(io::SeekFrom::Start(*offset)).unwrap();
+            io::Write::write_all(&mut file, bytes).unwrap();
+        }
+    }
+
+    #[test]
+    fn sampled_hash_reads_only_the_ends() {
+        let tmp = TempDir::new("sampled-hash");
+        let length = 8 * HASH_SAMPLE_BYTES;
+        sparse_file(&tmp.join("a.ttf"), length, &[(0, b"head")]);
+        sparse_file(&tmp.join("copy.ttf"), length, &[(0, b"head")]);
+        sparse_file(&tmp.join("middle.ttf"), length, &[(0, b"head"), (length / 2, b"edit")]);
+        sparse_file(&tmp.join("tail.ttf"), length, &[(0, b"head"), (length - 4, b"tail")]);
+        sparse_file(&tmp.join("longer.ttf"), length + 1, &[(0, b"head")]);
+
+        let sampled = |name: &str| sampled_hash(&tmp.join(name)).unwrap();
+        let full = |name: &str| content_hash(&tmp.join(name)).unwrap();
+        assert_eq!(sampled("a.ttf"), sampled("copy.ttf"));
+        assert_eq!(full("a.ttf"), full("copy.ttf"));
+        // A change in the middle is only caught by the full hash
+        assert_eq!(sampled("a.ttf"), sampled("middle.ttf"));
+        assert_ne!(full("a.ttf"), full("middle.ttf"));
+        assert_ne!(sampled("a.ttf"), sampled("tail.ttf"));
+        assert_ne!(sampled("a.ttf"), sampled("longer.ttf"));
+    }
+
+    #[test]
+    fn small_files_are_sampled_whole() {
+        let tmp = TempDir::new("sampled-hash");
+        let length = 2 * HASH_SAMPLE_BYTES;
+        sparse_file(&tmp.join("a.ttf"), length, &[]);
+        sparse_file(&tmp.join("b.ttf"), length, &[(length / 2, b"edit")]);
+        assert_ne!(sampled_hash(&tmp.join("a.ttf")).unwrap(), sampled_hash(&tmp.join("b.ttf")).unwrap());
+    }
+}
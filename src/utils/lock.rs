@@ -0,0 +1,108 @@
+//! Exclusive lock on a library while a run changes it (`<library>/.fontsrt.lock`)
+//!
+//! The lock is a file holding the id of the process that created it, so the
+//! command-line tool, the server and other processes never reorganize the
+//! same library at once. A lock left behind by a process that no longer runs
+//! is taken over.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::error::{Error, Result};
+
+/// File name of the lock in the library root
+pub const LIBRARY_LOCK_FILE: &str = ".fontsrt.lock";
+
+/// Whether a file is a library lock, which the organizer never treats as a font
+pub fn is_lock_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == LIBRARY_LOCK_FILE)
+}
+
+/// Claim on a library, released when dropped
+#[derive(Debug)]
+pub struct LibraryLock {
+    path: PathBuf,
+}
+
+impl LibraryLock {
+    /// Lock the library at `dir`, failing with `Error::Locked` while another
+    /// run, in this process or another, holds it
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        let path = dir.join(LIBRARY_LOCK_FILE);
+        // A stale lock is removed once, then claimed like a missing one
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(std::process::id().to_string().as_bytes())?;
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let holder = fs::read_to_string(&path).ok().and_then(|pid| pid.trim().parse::<u32>().ok());
+                    match holder {
+                        Some(pid) if !process_is_running(pid) => {
+                            let _ = fs::remove_file(&path);
+                        }
+                        _ => return Err(Error::Locked(dir.to_path_buf())),
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(Error::Locked(dir.to_path_buf()))
+    }
+
+    /// Path of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LibraryLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+fn process_is_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else { return false };
+    // SAFETY: signal 0 only checks that the process exists and may be signalled
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a way to check, every holder is assumed to still run
+#[cfg(not(unix))]
+fn process_is_running(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn a_held_library_cannot_be_locked_again() {
+        let tmp = TempDir::new("library-lock");
+        let lock = LibraryLock::acquire(tmp.path()).unwrap();
+        assert!(lock.path().is_file());
+        assert!(matches!(LibraryLock::acquire(tmp.path()), Err(Error::Locked(_))));
+
+        drop(lock);
+        assert!(!tmp.join(LIBRARY_LOCK_FILE).exists());
+        assert!(LibraryLock::acquire(tmp.path()).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_locks_are_taken_over() {
+        let tmp = TempDir::new("library-lock-stale");
+        // Process ids are below 2^22 on Linux, so this one never runs
+        fs::write(tmp.join(LIBRARY_LOCK_FILE), "2147483000").unwrap();
+        let lock = LibraryLock::acquire(tmp.path()).unwrap();
+        assert_eq!(fs::read_to_string(lock.path()).unwrap(), std::process::id().to_string());
+    }
+}
@@ -1,9 +1,186 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
 use crate::models::Config;
 
-/// Log a debug message if debug mode is enabled
+/// Log a problem that cost a file or a step of the run; printed even with `--quiet`
+pub fn log_error(config: &Config, message: String) {
+    write_log(config, LogLevel::Error, message);
+}
+
+/// Log a real problem the run worked around, printed by default
+pub fn log_warn(config: &Config, message: String) {
+    write_log(config, LogLevel::Warn, message);
+}
+
+/// Log what a default run reports, such as the steps of a batch and the summary
+pub fn log_info(config: &Config, message: String) {
+    write_log(config, LogLevel::Info, message);
+}
+
+/// Log a debug message, printed with `-v` (or `--debug`)
 pub fn log(config: &Config, message: String) {
-    if config.debug_mode {
-        println!("[DEBUG] {}", message);
+    write_log(config, LogLevel::Debug, message);
+}
+
+/// Log a per-file detail too chatty for `-v`, printed with `-vv`
+pub fn log_trace(config: &Config, message: String) {
+    write_log(config, LogLevel::Trace, message);
+}
+
+/// Write a message to the log file, if one is open, whatever its level, and
+/// print it when the level is within the configured verbosity. Leading line
+/// breaks separate blocks of console output and are left out of the file.
+fn write_log(config: &Config, level: LogLevel, message: String) {
+    let text = message.trim_start_matches('\n');
+    if let Some(log_file) = config.log_file.as_ref().filter(|_| !text.is_empty()) {
+        log_file.write(level, config.log_context.as_deref(), text);
+    }
+    if level > config.log_level() {
+        return;
+    }
+    print!("{}", &message[..message.len() - text.len()]);
+    let message = text;
+    match level {
+        LogLevel::Error => println!("Error: {}", message),
+        LogLevel::Warn => println!("Warning: {}", message),
+        LogLevel::Info => println!("{}", message),
+        LogLevel::Debug => println!("[DEBUG] {}", message),
+        LogLevel::Trace => println!("[TRACE] {}", message),
+    }
+}
+
+/// Severity of a log message, and the verbosity of a run: messages up to the
+/// verbosity are printed. Ordered from the most to the least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// `--quiet`
+    Error,
+    Warn,
+    /// The default
+    #[default]
+    Info,
+    /// `-v`
+    Debug,
+    /// `-vv`
+    Trace,
+}
+
+impl LogLevel {
+    /// Command-line names of the levels, from the most to the least severe
+    pub const NAMES: [&'static str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+    /// Parse a level from its command-line name; `warning` is also accepted
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            "trace" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogLevel::Error => write!(f, "ERROR"),
+            LogLevel::Warn => write!(f, "WARN"),
+            LogLevel::Info => write!(f, "INFO"),
+            LogLevel::Debug => write!(f, "DEBUG"),
+            LogLevel::Trace => write!(f, "TRACE"),
+        }
     }
 }
 
+/// Log file of a run (`--log-file <PATH>`), written whether or not `--debug`
+/// is on. Each message up to the file's level (`--log-level`, `Info` by
+/// default) is a JSON line such as
+/// `{"ts":"2024-05-01T12:00:00.123Z","level":"INFO","context":"fonts/inbox","msg":"..."}`,
+/// `context` only in batch runs. The file is appended to, so runs accumulate
+/// a history. Writes are buffered and flushed on drop.
+pub struct LogFile {
+    writer: Mutex<BufWriter<File>>,
+    level: LogLevel,
+}
+
+impl LogFile {
+    /// Open the log file for appending, creating it if needed; messages less
+    /// severe than `level` are left out
+    pub fn create(path: &Path, level: LogLevel) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::Config(format!("Cannot open log file {}: {}", path.display(), e)))?;
+        Ok(Self { writer: Mutex::new(BufWriter::new(file)), level })
+    }
+
+    /// Append a line; a failing log file must not abort the run it logs
+    pub fn write(&self, level: LogLevel, context: Option<&str>, message: &str) {
+        if level > self.level {
+            return;
+        }
+        let line = LogLine { ts: timestamp(), level: level.to_string(), context, msg: message };
+        if let Ok(line) = serde_json::to_string(&line) {
+            let _ = writeln!(self.writer.lock().unwrap(), "{}", line);
+        }
+    }
+
+    /// Write the buffered lines to disk
+    pub fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+/// One line of the log file, its fields in this order
+#[derive(Serialize)]
+struct LogLine<'a> {
+    ts: String,
+    level: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<&'a str>,
+    msg: &'a str,
+}
+
+impl Drop for LogFile {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Current UTC time in ISO 8601 with milliseconds, e.g. "2024-05-01T12:00:00.123Z"
+fn timestamp() -> String {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = elapsed.as_secs();
+    let (year, month, day) = civil_date(seconds / 86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day,
+        seconds / 3600 % 24, seconds / 60 % 60, seconds % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+/// Year, month and day of a day count since 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`)
+pub(crate) fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
@@ -1,14 +1,25 @@
 pub mod file;
 pub mod naming;
 pub mod logging;
+pub mod events;
+pub mod report;
+pub mod css;
+pub mod lock;
 
-pub use file::{ensure_directory_exists, safe_move_file, safe_move_directory};
+pub use file::{long_path, ensure_directory_exists, extract_fonts_from_zip, safe_move_file, safe_move_directory, safe_rename_in_place, content_hash, sampled_hash, collision_free_path, split_collision_suffix, unique_path_in, format_size, parse_size, directory_size, free_space, check_available_space, existing_folder_ignoring_case, is_junk_file, is_junk_folder};
 pub use naming::{
+    canonical_casing,
     clean_name,
+    truncate_name,
+    COLLISION_SUFFIX_ROOM,
     format_font_name,
     generate_font_filename,
-    build_folder_path,
-    normalize_family_name
+    normalize_family_name,
+    normalize_unicode_family_name,
+    transliterate_name,
+    ascii_name,
+    tag_cjk_name,
+    names_from_filename,
 };
-pub use logging::log;
+pub use logging::{log, log_error, log_info, log_trace, log_warn, LogLevel};
 
@@ -1,24 +1,209 @@
-/// Clean a name for use in filenames
+use deunicode::deunicode;
+use unicode_normalization::UnicodeNormalization;
+
+/// Compose a name to Unicode NFC, so a family decomposed on one system
+/// (macOS file names are NFD) and composed on another gets the same folder
+pub fn normalize_unicode_family_name(name: &str) -> String {
+    name.nfc().collect()
+}
+
+/// ASCII approximation of a name for ASCII-only paths (`--transliterate`),
+/// e.g. "Нота" becomes "Nota"
+pub fn transliterate_name(name: &str) -> String {
+    deunicode(name).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// ASCII approximation of a name that leaves CJK characters as they are
+/// (`--ascii-names`), e.g. "Čačak Sans" becomes "Cacak Sans" and "Straße"
+/// becomes "Strasse"
+pub fn ascii_name(name: &str) -> String {
+    let mut ascii = String::new();
+    let mut run = String::new();
+    for c in name.nfc() {
+        if is_cjk(c) {
+            ascii.push_str(&deunicode(&run));
+            run.clear();
+            ascii.push(c);
+        } else {
+            run.push(c);
+        }
+    }
+    ascii.push_str(&deunicode(&run));
+    ascii.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A CJK family name prefixed with the ASCII family part of the PostScript
+/// name, e.g. "LogoTypeGothic ロゴたいぷゴシック", so it sorts and reads in
+/// ASCII tooling. Other names, and names without a usable PostScript name,
+/// come back as they are.
+pub fn tag_cjk_name(name: &str, postscript_name: Option<&str>) -> String {
+    let tag = postscript_name
+        .and_then(|postscript| postscript.split('-').next())
+        .filter(|tag| !tag.is_empty() && tag.bytes().all(|b| b.is_ascii_alphanumeric()));
+    match tag {
+        Some(tag) if name.chars().any(is_cjk) && !name.contains(tag) => format!("{} {}", tag, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Whether a character is Chinese, Japanese or Korean script, which has no
+/// faithful ASCII approximation
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF         // Hangul Jamo
+        | 0x3000..=0x30FF       // CJK punctuation, Hiragana and Katakana
+        | 0x3130..=0x318F       // Hangul compatibility Jamo
+        | 0x31F0..=0x31FF       // Katakana phonetic extensions
+        | 0x3400..=0x4DBF       // CJK extension A
+        | 0x4E00..=0x9FFF       // CJK unified ideographs
+        | 0xAC00..=0xD7AF       // Hangul syllables
+        | 0xF900..=0xFAFF       // CJK compatibility ideographs
+        | 0xFF65..=0xFF9F       // Halfwidth Katakana
+        | 0x20000..=0x2FFFF     // CJK extensions B and later
+    )
+}
+
+/// The spelling a name shared by variants differing only in case is shown
+/// with: the one used most, on a tie one starting with a capital letter, then
+/// the first in sort order, so the choice never depends on scan order
+pub fn canonical_casing<'a>(variants: impl IntoIterator<Item = (&'a str, usize)>) -> Option<&'a str> {
+    variants.into_iter()
+        .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count)
+            .then_with(|| a.starts_with(char::is_uppercase).cmp(&b.starts_with(char::is_uppercase)))
+            .then_with(|| b.cmp(a)))
+        .map(|(name, _)| name)
+}
+
+/// Device names Windows reserves, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Clean a name for use in filenames that every platform can create
 pub fn clean_name(name: &str) -> String {
-    // Replace invalid filename characters with underscores
+    // Replace invalid filename characters with underscores, one for a run of them
     let invalid_chars = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
-    let mut cleaned = name.to_string();
+    let mut cleaned = String::new();
+    let mut replacing = false;
+    for c in normalize_unicode_family_name(name).chars() {
+        if invalid_chars.contains(&c) {
+            if !replacing {
+                cleaned.push('_');
+            }
+            replacing = true;
+        } else {
+            cleaned.push(c);
+            replacing = false;
+        }
+    }
 
-    for c in invalid_chars {
-        cleaned = cleaned.replace(c, "_");
+    // Remove leading spaces and dots
+    let mut cleaned = cleaned.trim_start_matches([' ', '.']).to_string();
+
+    // Windows cannot create "CON" or "aux.ttf", so the device name gets an underscore
+    let stem_len = cleaned.find('.').unwrap_or(cleaned.len());
+    if RESERVED_NAMES.iter().any(|reserved| cleaned[..stem_len].trim_end().eq_ignore_ascii_case(reserved)) {
+        cleaned.insert(cleaned[..stem_len].trim_end().len(), '_');
     }
 
-    // Remove leading/trailing spaces and dots
-    cleaned = cleaned.trim().trim_matches('.').to_string();
+    // NTFS drops trailing spaces and dots, so names must not end in them
+    cleaned.truncate(cleaned.trim_end_matches([' ', '.']).len());
 
-    // Ensure the name is not empty
-    if cleaned.is_empty() {
+    // Ensure the name is not empty or made of replaced characters only
+    if cleaned.chars().all(|c| c == '_') {
         cleaned = "Unknown".to_string();
     }
 
     cleaned
 }
 
+/// Bytes kept free in a truncated file stem for a `_N` collision suffix
+pub const COLLISION_SUFFIX_ROOM: usize = 4;
+
+/// Cut a cleaned name to at most `max_len` bytes on a character boundary,
+/// dropping the trailing spaces and dots the cut may expose. The same name
+/// is always cut the same way, so truncated names are recognized on the next run.
+pub fn truncate_name(name: &str, max_len: usize) -> String {
+    if name.len() <= max_len {
+        return name.to_string();
+    }
+    let mut end = max_len;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    name[..end].trim_end_matches([' ', '.']).to_string()
+}
+
+/// Words that start the style part of a file name, e.g. the "Bold" of
+/// `OpenSans-BoldItalic.ttf`
+const FILENAME_STYLE_WORDS: [&str; 18] = [
+    "thin", "hairline", "extra", "ultra", "semi", "demi", "light", "regular", "book",
+    "normal", "roman", "medium", "bold", "heavy", "black", "italic", "oblique", "it",
+];
+
+/// First words of compound weights, joined to the word after them ("SemiBold")
+const WEIGHT_PREFIXES: [&str; 4] = ["extra", "ultra", "semi", "demi"];
+
+/// Family and subfamily guessed from the file name of a font without a
+/// usable name table: the stem is split on `-`, `_`, spaces and camel case,
+/// the words before the first style word are the family. `OpenSans-BoldItalic.ttf`
+/// gives ("Open Sans", "Bold Italic"), `PT_Serif Caption Italic.otf` gives
+/// ("PT Serif Caption", "Italic"), and a name this tool gave, such as
+/// `Open Sans (Bold Italic).ttf`, is read back as it was written. `None`
+/// when no family word is left.
+pub fn names_from_filename(path: &Path) -> Option<(String, String)> {
+    let stem = path.file_stem()?.to_str()?;
+    if let Some((family, subfamily)) = stem.split_once(" (").filter(|(family, _)| !family.trim().is_empty()) {
+        let subfamily = subfamily.trim_end_matches(')').trim();
+        let subfamily = if subfamily.is_empty() { "Regular" } else { subfamily };
+        return Some((family.trim().to_string(), subfamily.to_string()));
+    }
+    let words: Vec<&str> = stem.split(['-', '_', ' ', '.'])
+        .flat_map(split_camel_case)
+        .collect();
+    let style_start = words.iter()
+        .position(|word| FILENAME_STYLE_WORDS.contains(&word.to_lowercase().as_str()))
+        .unwrap_or(words.len());
+    if style_start == 0 {
+        return None;
+    }
+
+    let mut style = Vec::new();
+    let mut prefix = "";
+    for word in &words[style_start..] {
+        match word.to_lowercase().as_str() {
+            lower if WEIGHT_PREFIXES.contains(&lower) => prefix = word,
+            "it" => style.push("Italic".to_string()),
+            _ => style.push(format!("{}{}", std::mem::take(&mut prefix), word)),
+        }
+    }
+    let subfamily = if style.is_empty() { "Regular".to_string() } else { style.join(" ") };
+    Some((words[..style_start].join(" "), subfamily))
+}
+
+/// Split a word where a lowercase letter is followed by a capital, and
+/// before the last capital of a run followed by a lowercase letter
+/// ("OpenSans" into "Open" and "Sans", "PTSerif" into "PT" and "Serif")
+fn split_camel_case(word: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in 1..chars.len() {
+        let (index, c) = chars[i];
+        let previous = chars[i - 1].1;
+        let next_lower = chars.get(i + 1).is_some_and(|(_, next)| next.is_lowercase());
+        if c.is_uppercase() && (previous.is_lowercase() || (previous.is_uppercase() && next_lower)) {
+            parts.push(&word[start..index]);
+            start = index;
+        }
+    }
+    parts.push(&word[start..]);
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
 /// Capitalize the first letter of each word in a string
 #[allow(dead_code)]
 pub fn capitalize_words(s: &str) -> String {
@@ -36,8 +221,9 @@ pub fn capitalize_words(s: &str) -> String {
 
 /// Normalize a font family name for more precise folder creation
 pub fn normalize_family_name(family_name: &str) -> String {
-    // 1. Clean and early exit for empty input
-    let normalized = family_name.trim();
+    // 1. Compose to NFC, clean and early exit for empty input
+    let composed = normalize_unicode_family_name(family_name);
+    let normalized = composed.trim();
     if normalized.is_empty() {
         return String::from("Unknown");
     }
@@ -63,6 +249,7 @@ pub fn normalize_family_name(family_name: &str) -> String {
     result
 }
 
+use crate::font::metadata::font_extension;
 use crate::models::{FontMetadata, NamingPattern, Config};
 use std::path::{Path, PathBuf};
 
@@ -70,46 +257,96 @@ use std::path::{Path, PathBuf};
 pub fn format_font_name(metadata: &FontMetadata, pattern: &NamingPattern) -> String {
     use NamingPattern::*;
 
+    // A variable font covers a range of styles, so its axes replace the
+    // subfamily of its default instance
+    if let Some(label) = metadata.variable_label() {
+        match pattern {
+            FamilySubfamily | FamilyWeight => return format!("{} {}", metadata.family_name, label),
+            FoundryFamilySubfamily => return format!("{} {} {}", metadata.foundry, metadata.family_name, label),
+            FoundryFamily => return format!("{}_{} {}", metadata.foundry, metadata.family_name, label),
+            Custom(_) => {}
+        }
+    }
+
+    let subfamily = named_subfamily(metadata);
     match pattern {
         FamilySubfamily => {
-            if metadata.subfamily.to_lowercase() == "regular" {
+            if subfamily.to_lowercase() == "regular" {
                 metadata.family_name.clone()
             } else {
-                format!("{} ({})", metadata.family_name, metadata.subfamily)
+                format!("{} ({})", metadata.family_name, subfamily)
             }
         },
         FoundryFamilySubfamily => {
-            if metadata.subfamily.to_lowercase() == "regular" {
+            if subfamily.to_lowercase() == "regular" {
                 format!("{} {}", metadata.foundry, metadata.family_name)
             } else {
-                format!("{} {} ({})", metadata.foundry, metadata.family_name, metadata.subfamily)
+                format!("{} {} ({})", metadata.foundry, metadata.family_name, subfamily)
             }
         },
         FamilyWeight => {
-            format!("{} {}{}", 
-                metadata.family_name, 
-                metadata.weight,
-                if metadata.is_italic { " Italic " } else { "" }
-            )
+            let width = metadata.width_name();
+            let family = if width.is_empty() {
+                metadata.family_name.clone()
+            } else {
+                format!("{} {}", metadata.family_name, width)
+            };
+
+            match metadata.slant {
+                Some(slant) => format!("{} {} {}", family, metadata.weight, slant),
+                None => format!("{} {}", family, metadata.weight),
+            }
         },
         FoundryFamily => {
-            if metadata.subfamily.to_lowercase() == "regular" {
+            if subfamily.to_lowercase() == "regular" {
                 format!("{}_{}", metadata.foundry, metadata.family_name)
             } else {
-                format!("{}_{} ({})", metadata.foundry, metadata.family_name, metadata.subfamily)
+                format!("{}_{} ({})", metadata.foundry, metadata.family_name, subfamily)
             }
         },
+        Custom(template) => expand_template(template, metadata),
+    }
+}
+
+/// Subfamily used in file names. Italic-only families (common for scripts)
+/// often call their single face "Regular" although it is slanted; such faces
+/// are named by their slant so the italic is not lost.
+fn named_subfamily(metadata: &FontMetadata) -> String {
+    match metadata.slant {
+        Some(slant) if metadata.subfamily.to_lowercase() == "regular" => slant.to_string(),
+        _ => metadata.subfamily.clone(),
     }
 }
 
+/// Expand the `%Token%` placeholders of a custom naming template.
+///
+/// Tokens that render empty (e.g. `%Width%` for normal-width faces) leave no
+/// stray whitespace or empty parentheses behind. `%Subfamily%` of a variable
+/// font is its axes label.
+pub fn expand_template(template: &str, metadata: &FontMetadata) -> String {
+    let style = metadata.slant.map(|slant| slant.to_string()).unwrap_or_default();
+    let subfamily = metadata.variable_label().unwrap_or_else(|| metadata.subfamily.clone());
+
+    let expanded = template
+        .replace("%Family%", &metadata.family_name)
+        .replace("%Subfamily%", &subfamily)
+        .replace("%Foundry%", &metadata.foundry)
+        .replace("%Weight%", &metadata.weight.to_string())
+        .replace("%Width%", metadata.width_name())
+        .replace("%Version%", metadata.version.as_deref().unwrap_or_default())
+        .replace("%Style%", &style)
+        .replace("%Tech%", &metadata.technology.to_string())
+        .replace("%License%", &metadata.license.to_string())
+        .replace("()", "")
+        .replace("[]", "");
+
+    expanded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 /// Generate a filename for a font based on its metadata
 pub fn generate_font_filename(metadata: &FontMetadata, pattern: &NamingPattern) -> String {
     let base_name = format_font_name(metadata, pattern);
-    let extension = metadata.original_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("ttf")
-        .to_lowercase();
+    let extension = font_extension(metadata);
 
     format!("{}.{}", clean_name(&base_name), extension)
 }
@@ -138,3 +375,63 @@ pub fn build_folder_path(base_dir: &Path, metadata: &FontMetadata, config: &Conf
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_device_names_get_an_underscore() {
+        for reserved in RESERVED_NAMES {
+            assert_eq!(clean_name(reserved), format!("{}_", reserved));
+            assert_eq!(clean_name(&reserved.to_lowercase()), format!("{}_", reserved.to_lowercase()));
+            assert_eq!(clean_name(&format!("{}.ttf", reserved)), format!("{}_.ttf", reserved));
+            assert_eq!(clean_name(&format!("{} .otf", reserved)), format!("{}_ .otf", reserved));
+        }
+        // Only the whole stem is a device name
+        for name in ["Aux Display", "Console", "COM10", "LPT0", "Nullarbor.ttf"] {
+            assert_eq!(clean_name(name), name);
+        }
+    }
+
+    #[test]
+    fn trailing_dots_and_spaces_are_trimmed() {
+        let cases = [
+            ("Helvetica Neue.", "Helvetica Neue"),
+            ("Univers . . ", "Univers"),
+            ("  .hidden", "hidden"),
+            ("AUX.", "AUX_"),
+            ("Font?", "Font_"),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(clean_name(name), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn runs_of_invalid_characters_become_one_underscore() {
+        assert_eq!(clean_name("Foo<>:Bar"), "Foo_Bar");
+        assert_eq!(clean_name("A/B\\C"), "A_B_C");
+        assert_eq!(clean_name("<>:\"/\\|?*"), "Unknown");
+        assert_eq!(clean_name(""), "Unknown");
+        assert_eq!(clean_name(" . "), "Unknown");
+    }
+
+    #[test]
+    fn truncation_drops_exposed_trailing_dots() {
+        assert_eq!(truncate_name("Roboto Condensed", 100), "Roboto Condensed");
+        assert_eq!(truncate_name("Roboto Condensed", 7), "Roboto");
+        assert_eq!(truncate_name("Żuraw", 2), "Ż");
+        assert_eq!(truncate_name("Żuraw", 1), "");
+    }
+
+    #[test]
+    fn canonical_casing_prefers_the_most_used_spelling() {
+        assert_eq!(canonical_casing([("gotham", 3), ("Gotham", 2)]), Some("gotham"));
+        // Ties go to a capitalized spelling, then sort order
+        assert_eq!(canonical_casing([("gotham", 2), ("Gotham", 2)]), Some("Gotham"));
+        assert_eq!(canonical_casing([("GOTHAM", 1), ("Gotham", 1), ("gotham", 1)]), Some("GOTHAM"));
+        assert_eq!(canonical_casing([("Gotham", 1), ("GOTHAM", 1)]), Some("GOTHAM"));
+        assert_eq!(canonical_casing(Vec::new()), None);
+    }
+}
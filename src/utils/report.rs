@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use crate::error::Result;
+use crate::models::report::{Report, ReportAction, ReportEntry, REPORT_FORMAT};
+use crate::models::{FontMetadata, MetadataSource};
+
+/// Family, foundry, weight and slant of a font and where they were read
+/// from, kept for its report entries
+#[derive(Clone)]
+struct FontFacts {
+    family: String,
+    foundry: String,
+    weight: u16,
+    italic: bool,
+    source: MetadataSource,
+}
+
+struct ReportState {
+    report: Report,
+    /// Facts of every font whose metadata was read, by the paths it had
+    fonts: HashMap<PathBuf, FontFacts>,
+    finished: bool,
+}
+
+/// Collects the report of a run (`--report <FILE>`) and writes it.
+///
+/// The file is replaced at each checkpoint (the end of an organize, grouping
+/// or batch job) and when the run finishes. A writer dropped before
+/// `finish`, as when the run fails, writes what it has with `complete` false.
+pub struct ReportWriter {
+    path: PathBuf,
+    started: Instant,
+    state: Mutex<ReportState>,
+}
+
+impl ReportWriter {
+    /// A report of a run starting now, written to `path`
+    pub fn new(path: &Path, dry_run: bool, run_id: Option<&str>) -> Self {
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        let report = Report {
+            format: REPORT_FORMAT,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            run_id: run_id.map(str::to_string),
+            dry_run,
+            started_at,
+            ..Report::default()
+        };
+        Self {
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            state: Mutex::new(ReportState { report, fonts: HashMap::new(), finished: false }),
+        }
+    }
+
+    /// Remember the metadata of a font for the entries of its later actions
+    pub fn record_font(&self, path: &Path, metadata: &FontMetadata) {
+        let facts = FontFacts {
+            family: metadata.family_name.clone(),
+            foundry: metadata.foundry.clone(),
+            weight: metadata.weight,
+            italic: metadata.is_italic,
+            source: metadata.source,
+        };
+        self.state.lock().unwrap().fonts.insert(path.to_path_buf(), facts);
+    }
+
+    /// Add an entry for an action on the font at `source`
+    pub fn record(&self, source: &Path, destination: Option<&Path>, action: ReportAction, reason: Option<String>) {
+        let mut state = self.state.lock().unwrap();
+        let facts = state.fonts.get(source).cloned();
+        // The font keeps its facts at its new path, for actions of later steps
+        if let (Some(destination), Some(facts)) = (destination, &facts) {
+            state.fonts.insert(destination.to_path_buf(), facts.clone());
+        }
+
+        state.report.totals.count(action);
+        state.report.fonts.push(ReportEntry {
+            source: source.to_path_buf(),
+            destination: destination.map(Path::to_path_buf),
+            family: facts.as_ref().map(|facts| facts.family.clone()),
+            foundry: facts.as_ref().map(|facts| facts.foundry.clone()),
+            weight: facts.as_ref().map(|facts| facts.weight),
+            italic: facts.as_ref().map(|facts| facts.italic),
+            metadata_source: facts.as_ref().map(|facts| facts.source),
+            action,
+            reason,
+        });
+    }
+
+    /// Write the report so far, e.g. at the end of one step of the run
+    pub fn checkpoint(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.write(&mut state)
+    }
+
+    /// Write the final report, marked complete
+    pub fn finish(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.report.complete = true;
+        state.finished = true;
+        self.write(&mut state)
+    }
+
+    /// Path the report is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replace the report file atomically
+    fn write(&self, state: &mut ReportState) -> Result<()> {
+        state.report.duration_secs = self.started.elapsed().as_secs_f64();
+        let content = serde_json::to_string_pretty(&state.report)
+            .map_err(|e| crate::error::Error::Config(format!("could not serialize the report: {}", e)))?;
+
+        let temp = self.path.with_extension(format!("tmp-{}", std::process::id()));
+        fs::write(&temp, content)?;
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Drop for ReportWriter {
+    fn drop(&mut self) {
+        let Ok(mut state) = self.state.lock() else { return };
+        if !state.finished {
+            let _ = self.write(&mut state);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde_json::json;
+use crate::error::{Error, Result};
+use crate::font::metadata::extract_font_metadata;
+use crate::models::Config;
+use crate::organizer::{is_set_aside_folder, organize_single_font};
+use crate::utils::{is_junk_file, is_junk_folder, log};
+
+/// Extensions of files picked up by the watcher
+const WATCHED_EXTENSIONS: [&str; 8] = ["ttf", "otf", "ttc", "otc", "woff", "woff2", "pfb", "pfa"];
+
+/// How long a file must be quiet before it is organized, so fonts still being
+/// copied in are not moved half-written
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// How often the loop wakes up to check for settled files and Ctrl-C
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Watch `dir` and organize fonts into the library as they arrive, until Ctrl-C.
+/// Pending files are organized before returning.
+pub fn watch(dir: &Path, config: &Config) -> Result<()> {
+    let output_root = config.output_dir.as_deref().unwrap_or(dir);
+    let family_folders = Mutex::new(existing_family_folders(output_root, config));
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_handler = stop.clone();
+    ctrlc::set_handler(move || stop_handler.store(true, Ordering::SeqCst))
+        .map_err(|e| Error::Config(format!("Could not install Ctrl-C handler: {}", e)))?;
+
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|e| Error::Config(format!("Could not start watching {}: {}", dir.display(), e)))?;
+    // Only the top level is watched: organized fonts land in family folders
+    // below it and must not be picked up again
+    watcher.watch(dir, RecursiveMode::NonRecursive)
+        .map_err(|e| Error::Config(format!("Could not start watching {}: {}", dir.display(), e)))?;
+
+    println!("Watching {} for new fonts, press Ctrl-C to stop", dir.display());
+
+    // Last event time per file; copies emit a create followed by writes
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+
+    while !stop.load(Ordering::SeqCst) {
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths.into_iter().filter(|path| is_watched_font(path) && !is_ignored(path, config)) {
+                        pending.insert(path, Instant::now());
+                    }
+                }
+            }
+            Ok(Err(e)) => log(config, format!("Watch error: {}", e)),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        let settled: Vec<PathBuf> = pending.iter()
+            .filter(|(_, last_event)| last_event.elapsed() >= DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            organize_arrival(&path, output_root, config, &family_folders);
+        }
+    }
+
+    // Flush files that were still settling when Ctrl-C arrived
+    let mut remaining: Vec<PathBuf> = pending.into_keys().collect();
+    remaining.sort();
+    for path in remaining {
+        organize_arrival(&path, output_root, config, &family_folders);
+    }
+
+    println!("Stopped watching {}", dir.display());
+    Ok(())
+}
+
+/// Whether a path has one of the watched font extensions
+fn is_watched_font(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| WATCHED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Whether a path is operating system junk, such as a `._` resource fork
+/// written next to a font copied from a Mac
+fn is_ignored(path: &Path, config: &Config) -> bool {
+    config.default_ignores && (is_junk_file(path) || path.ancestors().any(is_junk_folder))
+}
+
+/// Organize one newly arrived font, logging the result
+fn organize_arrival(
+    path: &Path,
+    output_root: &Path,
+    config: &Config,
+    family_folders: &Mutex<HashMap<String, PathBuf>>,
+) {
+    // The file may have been removed or renamed again while settling
+    if !path.is_file() {
+        return;
+    }
+
+    let metadata = match extract_font_metadata(path, config) {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => {
+            println!("Skipped {}: not a readable font", path.display());
+            return;
+        }
+        Err(e) => {
+            println!("Skipped {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    match organize_single_font(path, &metadata, output_root, config, family_folders) {
+        Ok(Some(target)) => {
+            println!("Organized {} -> {}", path.display(), target.display());
+            config.emit("moved", json!({ "from": path.display().to_string(), "to": target.display().to_string() }));
+        }
+        Ok(None) => log(config, format!("{} is already organized", path.display())),
+        Err(e) => println!("Error organizing {}: {}", path.display(), e),
+    }
+}
+
+/// Family folders already in the library, so arriving fonts join them
+fn existing_family_folders(output_root: &Path, config: &Config) -> HashMap<String, PathBuf> {
+    let mut family_folders = HashMap::new();
+
+    for dir in subfolders(output_root, config) {
+        if config.group_by_foundry {
+            for family_dir in subfolders(&dir, config) {
+                family_folders.insert(folder_name(&family_dir), family_dir);
+            }
+        } else {
+            family_folders.insert(folder_name(&dir), dir);
+        }
+    }
+
+    family_folders
+}
+
+/// Subfolders of a directory, skipping set-aside folders
+fn subfolders(dir: &Path, config: &Config) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| entries.flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir() && !is_set_aside_folder(&folder_name(path), config))
+            .collect())
+        .unwrap_or_default()
+}
+
+fn folder_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
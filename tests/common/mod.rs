@@ -0,0 +1,326 @@
+//! Fixtures shared by the integration tests: minimal fonts built in memory,
+//! seeded libraries of generated family folders and scratch directories
+//! removed when dropped. The unit test fixtures of `src/testing.rs` are not
+//! part of the library, so the fonts are built here again with the few
+//! tables metadata is read from.
+
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Windows platform, Unicode BMP encoding and US English language IDs
+const WINDOWS_ENGLISH: [u16; 3] = [3, 1, 0x409];
+
+/// A TrueType font with head, hhea, maxp, OS/2, post and a Windows name
+/// table holding the family (ID 1), subfamily (ID 2), full name (ID 4) and
+/// PostScript name (ID 6)
+pub fn font(family: &str, subfamily: &str, weight: u16, vendor: &[u8; 4]) -> Vec<u8> {
+    let postscript = format!("{}-{}", family.replace(' ', ""), subfamily.replace(' ', ""));
+    let full_name = format!("{} {}", family, subfamily);
+    let names = [(1, family), (2, subfamily), (4, full_name.as_str()), (6, postscript.as_str())];
+    let italic = subfamily.contains("Italic");
+
+    sfnt(vec![
+        (*b"head", head(italic)),
+        (*b"hhea", hhea()),
+        (*b"maxp", maxp()),
+        (*b"OS/2", os2(weight, vendor, italic)),
+        (*b"post", post()),
+        (*b"name", name_table(&names)),
+    ])
+}
+
+/// Write `font(...)` to `path`, creating its folder, and return the path
+pub fn write_font(path: &Path, family: &str, subfamily: &str, weight: u16, vendor: &[u8; 4]) -> PathBuf {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(path, font(family, subfamily, weight, vendor)).unwrap();
+    path.to_path_buf()
+}
+
+/// Families a generated library picks from. "Google" is also the foundry
+/// of the GOOG vendor ID, so grouping has to move it aside first.
+const FAMILIES: [&str; 10] = [
+    "Inter", "Roboto", "Source Serif", "Lato", "Acumin", "Minion", "Gotham", "Fira Sans", "Noto Sans", "Google",
+];
+
+/// Vendor IDs of the generated families: known foundries, an unregistered
+/// ID and a blank one, whose families go to the Unknown folder
+const VENDORS: [&[u8; 4]; 6] = [b"GOOG", b"ADBE", b"APPL", b"RSMS", b"ZZZZ", b"    "];
+
+/// Faces of a family as (subfamily, weight)
+const FACES: [(&str, u16); 6] = [
+    ("Regular", 400), ("Italic", 400), ("Bold", 700), ("Bold Italic", 700), ("Light", 300), ("Black", 900),
+];
+
+/// Deterministic random numbers (SplitMix64), so a failing seed can be replayed
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number below `bound`
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// True with a probability of one in `odds`
+    pub fn one_in(&mut self, odds: usize) -> bool {
+        self.below(odds) == 0
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// Fill `root` with a flat organized library of family folders generated
+/// from `seed`: two to eight families, each with a vendor and one to four
+/// faces, kept loose or in weight folders. Some fonts carry a collision
+/// suffix already and some folders a README. Returns the number of fonts.
+pub fn generate_library(root: &Path, seed: u64) -> usize {
+    let mut rng = Rng::new(seed);
+    let mut families: Vec<&str> = FAMILIES.to_vec();
+    let mut fonts = 0;
+
+    for _ in 0..2 + rng.below(7) {
+        let family = families.remove(rng.below(families.len()));
+        let vendor = *rng.pick(&VENDORS);
+        let family_dir = root.join(family);
+        let weight_folders = rng.one_in(3);
+
+        let mut faces = FACES.to_vec();
+        for _ in 0..1 + rng.below(4) {
+            let (subfamily, weight) = faces.remove(rng.below(faces.len()));
+            let folder = if weight_folders { family_dir.join(weight.to_string()) } else { family_dir.clone() };
+            let suffix = if rng.one_in(5) { "_1" } else { "" };
+            write_font(&folder.join(format!("{} {}{}.ttf", family, subfamily, suffix)), family, subfamily, weight, vendor);
+            fonts += 1;
+        }
+        if rng.one_in(4) {
+            fs::write(family_dir.join("README.txt"), format!("{} from seed {}", family, seed)).unwrap();
+        }
+    }
+    fonts
+}
+
+/// Every folder (`None`) and file (its bytes) below `root`, by relative path
+pub fn tree_snapshot(root: &Path) -> BTreeMap<PathBuf, Option<Vec<u8>>> {
+    fn walk(root: &Path, dir: &Path, tree: &mut BTreeMap<PathBuf, Option<Vec<u8>>>) {
+        for path in fs::read_dir(dir).unwrap().flatten().map(|entry| entry.path()) {
+            let relative = path.strip_prefix(root).unwrap().to_path_buf();
+            if path.is_dir() {
+                tree.insert(relative, None);
+                walk(root, &path, tree);
+            } else {
+                tree.insert(relative, Some(fs::read(&path).unwrap()));
+            }
+        }
+    }
+
+    let mut tree = BTreeMap::new();
+    walk(root, root, &mut tree);
+    tree
+}
+
+fn head(italic: bool) -> Vec<u8> {
+    let mut head = Vec::new();
+    put_u32(&mut head, 0x0001_0000); // version
+    put_u32(&mut head, 0x0001_0000); // fontRevision
+    put_u32(&mut head, 0); // checksumAdjustment
+    put_u32(&mut head, 0x5F0F_3CF5); // magicNumber
+    put_u16(&mut head, 0); // flags
+    put_u16(&mut head, 1000); // unitsPerEm
+    head.extend([0; 16]); // created, modified
+    for bound in [0i16, -200, 500, 800] {
+        put_i16(&mut head, bound);
+    }
+    put_u16(&mut head, if italic { 2 } else { 0 }); // macStyle
+    put_u16(&mut head, 8); // lowestRecPPEM
+    put_i16(&mut head, 2); // fontDirectionHint
+    put_i16(&mut head, 0); // indexToLocFormat
+    put_i16(&mut head, 0); // glyphDataFormat
+    head
+}
+
+fn hhea() -> Vec<u8> {
+    let mut hhea = Vec::new();
+    put_u32(&mut hhea, 0x0001_0000);
+    for metric in [800i16, -200, 0] {
+        put_i16(&mut hhea, metric); // ascender, descender, lineGap
+    }
+    put_u16(&mut hhea, 500); // advanceWidthMax
+    for metric in [0i16, 0, 500, 1, 0, 0, 0, 0, 0, 0, 0] {
+        put_i16(&mut hhea, metric); // side bearings, extent, caret, reserved, metricDataFormat
+    }
+    put_u16(&mut hhea, 1); // numberOfHMetrics
+    hhea
+}
+
+fn maxp() -> Vec<u8> {
+    let mut maxp = Vec::new();
+    put_u32(&mut maxp, 0x0000_5000); // version 0.5
+    put_u16(&mut maxp, 1); // numGlyphs
+    maxp
+}
+
+fn os2(weight: u16, vendor: &[u8; 4], italic: bool) -> Vec<u8> {
+    let mut os2 = Vec::new();
+    put_u16(&mut os2, 4); // version
+    put_i16(&mut os2, 500); // xAvgCharWidth
+    put_u16(&mut os2, weight);
+    put_u16(&mut os2, 5); // usWidthClass, normal
+    put_u16(&mut os2, 0); // fsType
+    os2.extend([0; 20]); // subscript, superscript and strikeout metrics
+    os2.extend([0; 2]); // sFamilyClass
+    os2.extend([0; 10]); // panose
+    os2.extend([0; 16]); // ulUnicodeRange1-4
+    os2.extend(vendor);
+    put_u16(&mut os2, if italic { 0x01 } else { 0x40 }); // fsSelection, ITALIC or REGULAR
+    put_u16(&mut os2, 0x20); // usFirstCharIndex
+    put_u16(&mut os2, 0x7E); // usLastCharIndex
+    for metric in [800i16, -200, 0] {
+        put_i16(&mut os2, metric); // sTypoAscender, sTypoDescender, sTypoLineGap
+    }
+    put_u16(&mut os2, 800); // usWinAscent
+    put_u16(&mut os2, 200); // usWinDescent
+    os2.extend([0; 8]); // ulCodePageRange1-2
+    for metric in [500i16, 700] {
+        put_i16(&mut os2, metric); // sxHeight, sCapHeight
+    }
+    put_u16(&mut os2, 0); // usDefaultChar
+    put_u16(&mut os2, 0x20); // usBreakChar
+    put_u16(&mut os2, 0); // usMaxContext
+    os2
+}
+
+fn post() -> Vec<u8> {
+    let mut post = Vec::new();
+    put_u32(&mut post, 0x0003_0000); // version 3, no glyph names
+    put_u32(&mut post, 0); // italicAngle
+    put_i16(&mut post, -100); // underlinePosition
+    put_i16(&mut post, 50); // underlineThickness
+    put_u32(&mut post, 0); // isFixedPitch
+    post.extend([0; 16]); // memory usage hints
+    post
+}
+
+/// A format 0 name table of Windows English records (name ID, value)
+fn name_table(names: &[(u16, &str)]) -> Vec<u8> {
+    let mut records = Vec::new();
+    let mut strings = Vec::new();
+    for (name_id, value) in names {
+        let encoded: Vec<u8> = value.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        for field in WINDOWS_ENGLISH.into_iter().chain([*name_id, encoded.len() as u16, strings.len() as u16]) {
+            put_u16(&mut records, field);
+        }
+        strings.extend(encoded);
+    }
+
+    let mut table = Vec::new();
+    put_u16(&mut table, 0);
+    put_u16(&mut table, names.len() as u16);
+    put_u16(&mut table, (6 + records.len()) as u16);
+    table.extend(records);
+    table.extend(strings);
+    table
+}
+
+/// A TrueType font file of the tables, sorted by tag with padded bodies
+fn sfnt(mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let count = tables.len() as u16;
+    let entry_selector = 15 - count.leading_zeros() as u16;
+    let search_range = 16 << entry_selector;
+
+    let mut data = Vec::new();
+    put_u32(&mut data, 0x0001_0000);
+    put_u16(&mut data, count);
+    put_u16(&mut data, search_range);
+    put_u16(&mut data, entry_selector);
+    put_u16(&mut data, count * 16 - search_range);
+
+    let header = 12 + 16 * tables.len();
+    let mut body = Vec::new();
+    for (tag, table) in &tables {
+        data.extend(tag);
+        put_u32(&mut data, checksum(table));
+        put_u32(&mut data, (header + body.len()) as u32);
+        put_u32(&mut data, table.len() as u32);
+
+        body.extend(table);
+        while body.len() % 4 != 0 {
+            body.push(0);
+        }
+    }
+    data.extend(body);
+    data
+}
+
+fn checksum(table: &[u8]) -> u32 {
+    table.chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_be_bytes(word)
+        })
+        .fold(0u32, u32::wrapping_add)
+}
+
+fn put_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend(value.to_be_bytes());
+}
+
+fn put_i16(data: &mut Vec<u8>, value: i16) {
+    data.extend(value.to_be_bytes());
+}
+
+fn put_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend(value.to_be_bytes());
+}
+
+/// Scratch directory under the system temporary directory
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(label: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "fontsrt-it-{}-{}-{}",
+            label,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.0.join(relative)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,44 @@
+//! Grouping by foundry and ungrouping again are lossless: over generated
+//! libraries, group→ungroup→group leaves the tree of the first grouping
+
+mod common;
+
+use std::path::Path;
+use std::sync::Arc;
+use common::{generate_library, tree_snapshot, TempDir};
+use fontsrt::models::Config;
+use fontsrt::organizer::{group_by_foundry, ungroup_foundries};
+
+/// Number of generated libraries, each from its own seed
+const SEEDS: u64 = 32;
+
+fn group(dir: &Path, config: &Config) {
+    group_by_foundry(dir, config, Arc::default(), Arc::default(), Arc::default()).unwrap();
+}
+
+#[test]
+fn group_ungroup_group_is_byte_identical() {
+    let config = Config { group_by_foundry: true, ..Config::default() };
+
+    for seed in 0..SEEDS {
+        let tmp = TempDir::new(&format!("round-trip-{}", seed));
+        let fonts = generate_library(tmp.path(), seed);
+        let flat = tree_snapshot(tmp.path());
+
+        group(tmp.path(), &config);
+        let grouped = tree_snapshot(tmp.path());
+        assert_ne!(grouped, flat, "seed {}: nothing was grouped", seed);
+        assert_eq!(
+            grouped.values().flatten().count(),
+            flat.values().flatten().count(),
+            "seed {}: grouping lost files", seed
+        );
+
+        let moved = ungroup_foundries(tmp.path(), &config, Arc::default()).unwrap();
+        assert!(moved > 0, "seed {}: nothing was ungrouped", seed);
+        assert_eq!(tree_snapshot(tmp.path()), flat, "seed {}: ungrouping did not restore the flat library of {} fonts", seed, fonts);
+
+        group(tmp.path(), &config);
+        assert_eq!(tree_snapshot(tmp.path()), grouped, "seed {}: the second grouping differs from the first", seed);
+    }
+}
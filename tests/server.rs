@@ -0,0 +1,113 @@
+//! A plan→apply cycle driven over the Unix socket of `fontsrt serve`
+
+#![cfg(all(unix, feature = "serve"))]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use common::{write_font, TempDir};
+use fontsrt::models::Config;
+use fontsrt::utils::lock::{LibraryLock, LIBRARY_LOCK_FILE};
+
+/// A connection to the server, reading the responses and notifications it sends
+struct Client {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+    /// Progress notifications read while waiting for responses
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    /// Start a server on `socket` and connect to it once it listens
+    fn start(socket: &Path) -> Self {
+        let endpoint = socket.display().to_string();
+        thread::spawn(move || fontsrt::server::serve(&endpoint, &Config::default()));
+
+        let started = Instant::now();
+        let stream = loop {
+            match UnixStream::connect(socket) {
+                Ok(stream) => break stream,
+                Err(_) if started.elapsed() < Duration::from_secs(10) => thread::sleep(Duration::from_millis(20)),
+                Err(e) => panic!("the server did not start: {}", e),
+            }
+        };
+        stream.set_read_timeout(Some(Duration::from_secs(60))).unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        Self { stream, reader, notifications: Vec::new() }
+    }
+
+    /// Send a request and wait for its response
+    fn call(&mut self, id: u64, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        writeln!(self.stream, "{}", request).unwrap();
+
+        loop {
+            let mut line = String::new();
+            assert!(self.reader.read_line(&mut line).unwrap() > 0, "the server closed the connection");
+            let message: Value = serde_json::from_str(&line).unwrap();
+            if message["id"] == json!(id) {
+                return message;
+            }
+            assert_eq!(message["method"], "progress", "unexpected message {}", message);
+            self.notifications.push(message);
+        }
+    }
+}
+
+/// Destinations of the fonts a plan moves into family folders
+fn planned_destinations(plan: &Value) -> Vec<PathBuf> {
+    plan["families"].as_array().unwrap().iter()
+        .flat_map(|family| family["moves"].as_array().unwrap())
+        .map(|planned| PathBuf::from(planned["destination"].as_str().unwrap()))
+        .collect()
+}
+
+#[test]
+fn plan_then_apply_over_the_socket() {
+    let tmp = TempDir::new("server");
+    let library = tmp.join("library");
+    let regular = write_font(&library.join("Inter-Regular.ttf"), "Inter", "Regular", 400, b"RSMS");
+    let bold = write_font(&library.join("Inter-Bold.ttf"), "Inter", "Bold", 700, b"RSMS");
+    let mut client = Client::start(&tmp.join("fontsrt.sock"));
+
+    assert_eq!(client.call(1, "subscribe", json!({}))["result"]["subscribed"], true);
+
+    // Planning moves nothing
+    let response = client.call(2, "plan", json!({ "directory": library }));
+    let plan = response["result"].clone();
+    assert!(plan.is_object(), "plan failed: {}", response);
+    let destinations = planned_destinations(&plan);
+    assert_eq!(destinations.len(), 2);
+    assert!(destinations.iter().all(|destination| destination.starts_with(library.join("Inter"))));
+    assert!(regular.is_file() && bold.is_file());
+
+    // A library locked by another run is not changed
+    let lock = LibraryLock::acquire(&library).unwrap();
+    let response = client.call(3, "apply", json!({ "plan": plan }));
+    assert_eq!(response["error"]["code"], -32001);
+    assert!(regular.is_file() && bold.is_file());
+    drop(lock);
+
+    let response = client.call(4, "apply", json!({ "plan": plan }));
+    assert_eq!(response["result"]["files_moved"], 2, "apply failed: {}", response);
+    assert!(!regular.exists() && !bold.exists());
+    assert!(destinations.iter().all(|destination| destination.is_file()));
+    assert!(!library.join(LIBRARY_LOCK_FILE).exists());
+
+    // Every move was streamed to the subscriber, ahead of the response
+    let moved: Vec<&Value> = client.notifications.iter()
+        .filter(|notification| notification["params"]["request_id"] == 4 && notification["params"]["event"]["event"] == "moved")
+        .collect();
+    assert_eq!(moved.len(), 2);
+
+    let report = client.call(5, "report", json!({ "directory": library }));
+    assert_eq!(report["result"], response["result"]);
+
+    let cancel = client.call(6, "cancel", json!({ "request_id": 4 }));
+    assert_eq!(cancel["result"]["cancelled"], false);
+}